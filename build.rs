@@ -11,6 +11,11 @@ fn main() {
     println!("cargo:rerun-if-changed=web/vite.config.ts");
     println!("cargo:rerun-if-changed=web/index.html");
 
+    // Headless build (`--no-default-features`): nothing to embed
+    if std::env::var("CARGO_FEATURE_WEB_UI").is_err() {
+        return;
+    }
+
     // Skip npm build if:
     // 1. CROSS_COMPILE is set (cross-compilation environment)
     // 2. SKIP_WEB_BUILD is set
@@ -116,7 +116,13 @@ local function on_irc_message(network, channel, nick, message)
                 guessed_filename = string.gsub(guessed_filename, "%]$", "")
             end
 
-            botarr.queue(url, guessed_filename)
+            -- Per-filter scheduling defaults; unset values fall back to the category settings
+            botarr.queue(url, guessed_filename, {
+                priority = filter.priority,
+                max_retries = filter.max_retries,
+                category = filter.category,
+                template = filter.template,
+            })
         else
             botarr.print("autodl.lua", "Could not extract pack number from message: " .. message)
         end
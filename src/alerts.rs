@@ -0,0 +1,176 @@
+//! Alerts Module
+//!
+//! Collects transfer failures over a rolling hour and raises a single alert
+//! when too many pile up, naming the most common error, so a systemic problem
+//! (banned ident, dead proxy) shows up once instead of as a flood. Also posts
+//! other notifications to the alert webhook.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Window failures are counted over, and the minimum gap between alerts
+const ALERT_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Aggregated failure alert
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureAlert {
+    /// Failures within the last hour
+    pub failures: usize,
+    /// Most common error (numbers masked so similar errors group together)
+    pub top_error: String,
+    pub top_error_count: usize,
+}
+
+impl FailureAlert {
+    pub fn message(&self) -> String {
+        format!(
+            "Botarr: {} transfers failed in the last hour. Most common error ({}x): {}",
+            self.failures, self.top_error_count, self.top_error
+        )
+    }
+}
+
+/// Rolling window of recent transfer failures
+#[derive(Debug, Default)]
+pub struct FailureWindow {
+    failures: VecDeque<(Instant, String)>,
+    last_alert: Option<Instant>,
+}
+
+impl FailureWindow {
+    /// Record a failure; returns an alert once more than `threshold` failures
+    /// happened within the hour and no alert was raised in the last hour
+    pub fn record(&mut self, error: &str, threshold: usize) -> Option<FailureAlert> {
+        self.record_at(Instant::now(), error, threshold)
+    }
+
+    fn record_at(&mut self, now: Instant, error: &str, threshold: usize) -> Option<FailureAlert> {
+        while self
+            .failures
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > ALERT_WINDOW)
+        {
+            self.failures.pop_front();
+        }
+        self.failures.push_back((now, normalize_error(error)));
+
+        if threshold == 0 || self.failures.len() <= threshold {
+            return None;
+        }
+        if self
+            .last_alert
+            .is_some_and(|at| now.duration_since(at) < ALERT_WINDOW)
+        {
+            return None;
+        }
+        self.last_alert = Some(now);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, error) in &self.failures {
+            *counts.entry(error.as_str()).or_default() += 1;
+        }
+        let (top_error, top_error_count) = counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))?;
+
+        Some(FailureAlert {
+            failures: self.failures.len(),
+            top_error: top_error.to_string(),
+            top_error_count,
+        })
+    }
+}
+
+/// Mask digits so errors that differ only in addresses, ports or counts match
+fn normalize_error(error: &str) -> String {
+    let mut out = String::with_capacity(error.len());
+    for c in error.trim().chars() {
+        if c.is_ascii_digit() {
+            if !out.ends_with('#') {
+                out.push('#');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Post an alert to a webhook
+pub async fn send_alert(url: &str, alert: &FailureAlert) {
+    let details = serde_json::json!({ "alert": alert });
+    send_notification(url, "failure_alert", &alert.message(), details).await;
+}
+
+/// Webhook payload carrying the message as both `content` and `text`, which
+/// Discord and Slack style webhooks display, and the fields of `details` for
+/// other consumers
+pub fn webhook_payload(
+    event: &str,
+    message: &str,
+    details: serde_json::Value,
+) -> serde_json::Value {
+    let mut payload = serde_json::json!({
+        "event": event,
+        "content": message,
+        "text": message,
+    });
+    if let (Some(payload), serde_json::Value::Object(details)) = (payload.as_object_mut(), details)
+    {
+        payload.extend(details);
+    }
+    payload
+}
+
+/// Post a notification to a webhook
+pub async fn send_notification(url: &str, event: &str, message: &str, details: serde_json::Value) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let payload = webhook_payload(event, message, details);
+    match client.post(url).json(&payload).send().await {
+        Ok(resp) if resp.status().is_success() => {
+            tracing::debug!("Notification {} sent to {}", event, url);
+        }
+        Ok(resp) => tracing::warn!(
+            "Notification {} to {} returned {}",
+            event,
+            url,
+            resp.status()
+        ),
+        Err(e) => tracing::warn!("Notification {} to {} failed: {}", event, url, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_window_aggregates() {
+        let mut window = FailureWindow::default();
+        let start = Instant::now();
+
+        assert!(window
+            .record_at(start, "Connection to 1.2.3.4:5000 refused", 2)
+            .is_none());
+        assert!(window.record_at(start, "Bot timed out", 2).is_none());
+        let alert = window
+            .record_at(start, "Connection to 5.6.7.8:6000 refused", 2)
+            .unwrap();
+        assert_eq!(alert.failures, 3);
+        assert_eq!(alert.top_error, "Connection to #.#.#.#:# refused");
+        assert_eq!(alert.top_error_count, 2);
+
+        // One alert per hour at most
+        assert!(window.record_at(start, "Bot timed out", 2).is_none());
+
+        // Old failures drop out of the window
+        let later = start + ALERT_WINDOW + Duration::from_secs(1);
+        assert!(window.record_at(later, "Bot timed out", 2).is_none());
+        assert!(window.record_at(later, "Bot timed out", 2).is_none());
+        assert!(window.record_at(later, "Bot timed out", 2).is_some());
+    }
+}
@@ -0,0 +1,155 @@
+//! Anime Numbering Module
+//!
+//! Many anime bots name releases by absolute episode number (`Show - 17`) rather
+//! than `S02E05`. Mappings give the absolute offset of each season so queries and
+//! results can be translated between the two schemes.
+
+use crate::config::{AnimeMapping, AppConfig};
+use crate::library::{normalize_title, EpisodeNumber, NamingScheme};
+use regex::Regex;
+
+/// Translates between season/episode and absolute episode numbers
+pub struct AnimeMapper {
+    mappings: Vec<AnimeMapping>,
+    scheme: NamingScheme,
+    absolute_re: Regex,
+}
+
+impl AnimeMapper {
+    pub fn new(mappings: Vec<AnimeMapping>) -> Self {
+        Self {
+            mappings,
+            scheme: NamingScheme::default(),
+            // Episode number right after the title: `Show - 17`, `Show 017v2`, `Show_17 [1080p]`
+            absolute_re: Regex::new(r"^[\s_.\-]*(?:ep?\s*)?(\d{1,4})(?:v\d)?(?:[\s_.\[\(]|$)")
+                .unwrap(),
+        }
+    }
+
+    /// Build a mapper from the config mappings plus the optional mapping file
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut mappings = config.anime_mappings.clone();
+        if !config.anime_mapping_file.is_empty() {
+            match std::fs::read_to_string(&config.anime_mapping_file)
+                .map_err(|e| e.to_string())
+                .and_then(|c| {
+                    serde_json::from_str::<Vec<AnimeMapping>>(&c).map_err(|e| e.to_string())
+                }) {
+                Ok(extra) => mappings.extend(extra),
+                Err(e) => tracing::warn!(
+                    "Failed to load anime mapping file {}: {}",
+                    config.anime_mapping_file,
+                    e
+                ),
+            }
+        }
+        Self::new(mappings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /// Rewrite a `Show S02E05` query into its absolute form (`Show 17`)
+    pub fn to_absolute_query(&self, query: &str) -> Option<String> {
+        let ep = self.scheme.parse(query)?;
+        let marker = Regex::new(r"(?i)\bS\d{1,2}[ ._-]?E\d{1,4}\b|\b\d{1,2}x\d{2,3}\b").ok()?;
+        let title = marker.split(query).next()?.trim();
+        let normalized = normalize_title(title);
+
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|m| m.season == ep.season && normalize_title(&m.title) == normalized)?;
+        Some(format!("{} {:02}", title, mapping.offset + ep.episode))
+    }
+
+    /// Resolve an absolute-numbered release name to a season/episode
+    pub fn resolve_absolute(&self, filename: &str) -> Option<EpisodeNumber> {
+        // Names that already carry a season/episode need no mapping
+        if self.scheme.parse(filename).is_some() {
+            return None;
+        }
+
+        // Drop leading `[Group]` tags before matching the title
+        let stripped = Regex::new(r"^\s*(\[[^\]]*\]\s*)+")
+            .ok()?
+            .replace(filename, "");
+        let normalized = normalize_title(&stripped);
+
+        for mapping in &self.mappings {
+            let title = normalize_title(&mapping.title);
+            let Some(rest) = normalized.strip_prefix(&title) else {
+                continue;
+            };
+            let Some(caps) = self.absolute_re.captures(rest) else {
+                continue;
+            };
+            let Ok(absolute) = caps[1].parse::<u32>() else {
+                continue;
+            };
+
+            // Pick the season with the largest offset below the absolute number
+            let season = self
+                .mappings
+                .iter()
+                .filter(|m| normalize_title(&m.title) == title && m.offset < absolute)
+                .max_by_key(|m| m.offset)?;
+            return Some(EpisodeNumber {
+                season: season.season,
+                episode: absolute - season.offset,
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapper() -> AnimeMapper {
+        AnimeMapper::new(vec![
+            AnimeMapping {
+                title: "Some Show".to_string(),
+                season: 1,
+                offset: 0,
+            },
+            AnimeMapping {
+                title: "Some Show".to_string(),
+                season: 2,
+                offset: 12,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_absolute_query_rewrite() {
+        let m = mapper();
+        assert_eq!(
+            m.to_absolute_query("Some.Show S02E05").as_deref(),
+            Some("Some.Show 17")
+        );
+        assert_eq!(m.to_absolute_query("Other Show S02E05"), None);
+    }
+
+    #[test]
+    fn test_resolve_absolute() {
+        let m = mapper();
+        assert_eq!(
+            m.resolve_absolute("[Group] Some Show - 17 [1080p].mkv"),
+            Some(EpisodeNumber {
+                season: 2,
+                episode: 5
+            })
+        );
+        assert_eq!(
+            m.resolve_absolute("[Group] Some Show - 03v2 [720p].mkv"),
+            Some(EpisodeNumber {
+                season: 1,
+                episode: 3
+            })
+        );
+        assert_eq!(m.resolve_absolute("Some.Show.S02E05.mkv"), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! API Keys
+//!
+//! Endpoints called by *arr apps, browser links and scripts rather than the
+//! dashboard check the configured API key. They take it as the `apikey`
+//! query parameter or in the `X-Api-Key` header.
+
+use crate::api::models::ApiKeyParams;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use std::convert::Infallible;
+
+/// API key a request carries, if any
+#[derive(Debug, Clone, Default)]
+pub struct ApiKey(pub Option<String>);
+
+impl ApiKey {
+    pub fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+impl<S> FromRequestParts<S> for ApiKey
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let from_query = Query::<ApiKeyParams>::try_from_uri(&parts.uri)
+            .ok()
+            .and_then(|Query(params)| params.apikey);
+        let key = from_query.or_else(|| {
+            parts
+                .headers
+                .get("x-api-key")?
+                .to_str()
+                .ok()
+                .map(str::to_string)
+        });
+        Ok(Self(key.filter(|k| !k.is_empty())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn key(request: Request<()>) -> Option<String> {
+        let (mut parts, _) = request.into_parts();
+        ApiKey::from_request_parts(&mut parts, &()).await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn test_api_key() {
+        let request = |uri: &str| Request::builder().uri(uri);
+        assert_eq!(
+            key(request("/add?url=x&apikey=s%20e").body(()).unwrap()).await,
+            Some("s e".to_string())
+        );
+        assert_eq!(
+            key(request("/add")
+                .header("X-Api-Key", "secret")
+                .body(())
+                .unwrap())
+            .await,
+            Some("secret".to_string())
+        );
+        assert_eq!(key(request("/add?apikey=").body(()).unwrap()).await, None);
+    }
+}
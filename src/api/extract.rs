@@ -0,0 +1,296 @@
+//! JSON Request Bodies
+//!
+//! `ApiJson` takes the place of axum's `Json` extractor for request bodies.
+//! A body that isn't JSON, or doesn't fit the request type, is refused with
+//! a structured error naming the field, what it should have been and a hint,
+//! instead of axum's plain-text rejection, so scripts can tell what to fix.
+
+use axum::body::Bytes;
+use axum::extract::{FromRequest, OptionalFromRequest, Request};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+static INVALID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^invalid (?:type|value|length):? (.+), expected (.+)$").unwrap());
+static UNKNOWN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^unknown (variant|field) `(.*)`, expected (.+)$").unwrap());
+static FIELD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(missing|duplicate) field `(.*)`$").unwrap());
+
+/// JSON request body, see the module docs
+#[derive(Debug)]
+pub struct ApiJson<T>(pub T);
+
+/// Why a request body was refused
+#[derive(Debug, Serialize)]
+pub struct BodyError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: String,
+    /// Path of the offending field, e.g. `labels[2]` or `networks.rizon.port`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// What the field should have been, e.g. `u32` or ``one of `low`, `normal`, `high` ``
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// What was sent instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub found: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub hint: String,
+}
+
+impl BodyError {
+    fn new(status: StatusCode, error: String, hint: String) -> Self {
+        Self {
+            status,
+            error,
+            field: None,
+            expected: None,
+            found: None,
+            line: None,
+            column: None,
+            hint,
+        }
+    }
+
+    fn content_type() -> Self {
+        Self::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Request body must be JSON".to_string(),
+            "Send the body with `Content-Type: application/json`".to_string(),
+        )
+    }
+
+    /// Explain a body that failed to deserialize at `path` (`.` for the root)
+    fn from_json(path: String, inner: &serde_json::Error) -> Self {
+        let (line, column) = (inner.line(), inner.column());
+        let full = inner.to_string();
+        let suffix = format!(" at line {} column {}", line, column);
+        let message = full.strip_suffix(&suffix).unwrap_or(&full).to_string();
+        let mut error = Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::new(),
+            String::new(),
+        );
+        if line > 0 {
+            (error.line, error.column) = (Some(line), Some(column));
+        }
+        // `?` stands for a place in the body that couldn't be named
+        let path = Some(path).filter(|p| p != "." && p != "?");
+        let at = |name: &str| match &path {
+            Some(parent) => format!("{}.{}", parent, name),
+            None => name.to_string(),
+        };
+        error.field = path.clone();
+
+        use serde_json::error::Category;
+        match inner.classify() {
+            Category::Syntax | Category::Io => {
+                error.error = format!("Request body is not valid JSON: {}", message);
+                error.hint = format!(
+                    "Check the quotes, commas and brackets around line {} column {}",
+                    line, column
+                );
+            }
+            Category::Eof if full.starts_with("EOF while parsing a value at line 1 column 0") => {
+                error.error = "Request body is empty".to_string();
+                error.hint = "Send a JSON object, e.g. `{}`".to_string();
+            }
+            Category::Eof => {
+                error.error = format!("Request body ends too early: {}", message);
+                error.hint = "Close every object, array and string".to_string();
+            }
+            Category::Data => {
+                if let Some(c) = FIELD.captures(&message) {
+                    error.field = Some(at(&c[2]));
+                    error.hint = if &c[1] == "missing" {
+                        format!("Add the required field `{}`", &c[2])
+                    } else {
+                        format!("Send `{}` only once", &c[2])
+                    };
+                } else if let Some(c) = UNKNOWN.captures(&message) {
+                    if &c[1] == "field" {
+                        error.field = Some(at(&c[2]));
+                    }
+                    error.found = Some(format!("`{}`", &c[2]));
+                    error.expected = Some(c[3].to_string());
+                    error.hint = format!("Use {}", &c[3]);
+                } else if let Some(c) = INVALID.captures(&message) {
+                    error.found = Some(c[1].to_string());
+                    error.expected = Some(c[2].to_string());
+                    error.hint = format!("Send {} instead of {}", &c[2], &c[1]);
+                } else {
+                    error.hint = "Check the value against the API documentation".to_string();
+                }
+                error.error = match &error.field {
+                    Some(field) => format!("Invalid value for {}: {}", field, message),
+                    None => format!("Invalid request body: {}", message),
+                };
+            }
+        }
+        error
+    }
+}
+
+impl IntoResponse for BodyError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+/// Whether the request says its body is JSON (`application/json` or `*/*+json`)
+pub fn json_content_type(headers: &HeaderMap) -> bool {
+    let Some(value) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let mime = value.split(';').next().unwrap_or_default().trim();
+    mime.eq_ignore_ascii_case("application/json")
+        || (mime.len() >= 17
+            && mime[..12].eq_ignore_ascii_case("application/")
+            && mime.to_ascii_lowercase().ends_with("+json"))
+}
+
+impl<T: DeserializeOwned> ApiJson<T> {
+    /// Deserialize a request body
+    #[allow(clippy::result_large_err)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BodyError> {
+        let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+        let value = serde_path_to_error::deserialize(&mut deserializer)
+            .map_err(|e| BodyError::from_json(e.path().to_string(), e.inner()))?;
+        deserializer
+            .end()
+            .map_err(|e| BodyError::from_json(".".to_string(), &e))?;
+        Ok(Self(value))
+    }
+}
+
+async fn read_body<S: Send + Sync>(req: Request, state: &S) -> Result<Bytes, BodyError> {
+    Bytes::from_request(req, state).await.map_err(|rejection| {
+        let hint = if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            "Send a smaller body"
+        } else {
+            "Send the request again"
+        };
+        BodyError::new(rejection.status(), rejection.body_text(), hint.to_string())
+    })
+}
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = BodyError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !json_content_type(req.headers()) {
+            return Err(BodyError::content_type());
+        }
+        Self::from_bytes(&read_body(req, state).await?)
+    }
+}
+
+/// An optional body: absent when the request has no `Content-Type`
+impl<T, S> OptionalFromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = BodyError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if req.headers().get(header::CONTENT_TYPE).is_none() {
+            return Ok(None);
+        }
+        if !json_content_type(req.headers()) {
+            return Err(BodyError::content_type());
+        }
+        Self::from_bytes(&read_body(req, state).await?).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Body {
+        url: String,
+        #[serde(default)]
+        labels: Vec<String>,
+        #[serde(default)]
+        priority: Option<Priority>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Priority {
+        Low,
+        High,
+    }
+
+    fn refuse(body: &str) -> BodyError {
+        ApiJson::<Body>::from_bytes(body.as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn test_body_errors() {
+        assert!(ApiJson::<Body>::from_bytes(br#"{"url": "irc://a/#b/C/1"}"#).is_ok());
+
+        let e = refuse(r#"{"url": "x", "labels": ["a", 7]}"#);
+        assert_eq!(e.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(e.field.as_deref(), Some("labels[1]"));
+        assert_eq!(e.expected.as_deref(), Some("a string"));
+        assert_eq!(e.found.as_deref(), Some("integer `7`"));
+        assert_eq!((e.line, e.column), (Some(1), Some(30)));
+
+        let e = refuse(r#"{"labels": []}"#);
+        assert_eq!(e.field.as_deref(), Some("url"));
+        assert_eq!(e.hint, "Add the required field `url`");
+
+        let e = refuse(r#"{"url": "x", "priority": "urgent"}"#);
+        assert_eq!(e.field.as_deref(), Some("priority"));
+        assert_eq!(e.found.as_deref(), Some("`urgent`"));
+        assert_eq!(e.expected.as_deref(), Some("`low` or `high`"));
+
+        let e = refuse(r#"{"url": "x",}"#);
+        assert!(e.error.starts_with("Request body is not valid JSON"));
+        assert_eq!(e.field, None);
+        assert_eq!(refuse("").error, "Request body is empty");
+        assert!(refuse(r#"{"url": "x""#)
+            .error
+            .starts_with("Request body ends too early"));
+        assert!(refuse(r#"{"url": "x"} {}"#)
+            .error
+            .contains("trailing characters"));
+
+        let mut headers = HeaderMap::new();
+        assert!(!json_content_type(&headers));
+        headers.insert(
+            header::CONTENT_TYPE,
+            "application/json; charset=utf-8".parse().unwrap(),
+        );
+        assert!(json_content_type(&headers));
+        headers.insert(
+            header::CONTENT_TYPE,
+            "application/merge-patch+json".parse().unwrap(),
+        );
+        assert!(json_content_type(&headers));
+        headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        assert!(!json_content_type(&headers));
+    }
+}
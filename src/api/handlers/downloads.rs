@@ -1,20 +1,63 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect},
     Json,
 };
+use futures::stream::{self, StreamExt};
+use std::collections::VecDeque;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
+use crate::anime::AnimeMapper;
+use crate::api::auth::ApiKey;
+use crate::api::extract::ApiJson;
 use crate::api::models::*;
-use crate::config::AppConfig;
-use crate::postprocess::{run_postprocess, PostprocessConfig};
-use crate::xdcc::transfer::EnhancedTransferManager;
-use crate::xdcc::{TransferPriority, TransferStatus, XdccClient, XdccConfig, XdccEvent, XdccUrl};
+use crate::api::pagination::{PageParams, MAX_LIMIT};
+use crate::arr::DedupeMode;
+use crate::config::{mask_url_password, AppConfig};
+use crate::confirm;
+use crate::filename::{saved_path, unique_path, FilenamePolicy};
+use crate::notifications::{self, Notification};
+use crate::package::{package_job, PackageConfig, PackageReport, PackagingClaim};
+use crate::plugin::hooks::{self, HookOutcome, ScriptHooks};
+use crate::postprocess::{move_file, run_postprocess, PostprocessConfig};
+use crate::quarantine::{quarantine_dir, screen, ScreenConfig};
+use crate::release::{classify, parse_release};
+use crate::request_id::TRANSFER_ID;
+use crate::search_cache;
+use crate::thumbnail;
+use crate::xdcc::query::{transliterate, SearchFilter};
+use crate::xdcc::transfer::{EnhancedTransfer, EnhancedTransferManager, TransferJob};
+use crate::xdcc::{
+    http, Throttle, TransferMethod, TransferPriority, TransferStatus, XdccClient, XdccConfig,
+    XdccError, XdccEvent, XdccSearchResult, XdccUrl,
+};
 use crate::AppState;
 
+/// Maximum number of search results enriched with TMDB metadata per search
+const METADATA_LOOKUP_LIMIT: usize = 50;
+/// Library and metadata lookups run in parallel per search
+const LOOKUP_CONCURRENCY: usize = 8;
+/// IRC lines kept per download attempt for failure bundles
+const IRC_CAPTURE_LINES: usize = 200;
+/// How often the resume index is updated while downloading
+const PARTIAL_RECORD_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Speed limit of a transfer in KiB/s: its own, else its template's (0 = none)
+fn speed_limit_kbps(config: &AppConfig, transfer: &EnhancedTransfer) -> u64 {
+    transfer.max_speed_kbps.unwrap_or_else(|| {
+        transfer
+            .transfer
+            .template
+            .as_deref()
+            .and_then(|t| config.template(t))
+            .map_or(0, |(_, t)| t.max_speed_kbps)
+    })
+}
+
 pub fn spawn_download_task(
     tid: String,
     url: XdccUrl,
@@ -24,55 +67,124 @@ pub fn spawn_download_task(
     config: Arc<RwLock<AppConfig>>,
     plugin_manager: Arc<crate::plugin::PluginManager>,
 ) {
-    tokio::spawn(async move {
+    // A root span, so retries spawned from inside the task don't nest
+    let span = tracing::info_span!(parent: None, "transfer", id = %tid);
+    tokio::spawn(
+        async move {
         tracing::info!("Starting XDCC download task for {}", tid);
 
+        let hooks = ScriptHooks::from_config(&*config.read().await);
+        let Some(url) = run_before_download_hook(&tid, url, &hooks, &transfer_manager).await else {
+            tracing::info!("Download {} vetoed by {}", tid, hooks::ON_BEFORE_DOWNLOAD);
+            return;
+        };
+
+        let (transfer, offers, pool, bandwidth) = {
+            let tm = transfer_manager.read().await;
+            (
+                tm.get_transfer(&tid).await,
+                tm.offers(),
+                tm.connection_pool(),
+                tm.bandwidth(),
+            )
+        };
+        let (expected_filename, expected_size, category, relocated_to) = match transfer.as_ref() {
+            Some(t) => (
+                t.transfer.filename.clone(),
+                t.transfer.size,
+                t.transfer.category.clone(),
+                t.download_dir.clone(),
+            ),
+            None => (None, None, None, None),
+        };
+        // Transfers moved away after a disk error continue where they were moved to
+        let download_dir = relocated_to.unwrap_or(download_dir);
+
         let app_config = config.read().await;
+        let limiter = match transfer.as_ref() {
+            Some(t) => {
+                t.limiter
+                    .set_rate(speed_limit_kbps(&app_config, t) * 1024);
+                t.limiter.clone()
+            }
+            None => Default::default(),
+        };
+        // Size limits follow the category given when queuing, else the one the name suggests
+        let category = category.or_else(|| {
+            expected_filename
+                .as_deref()
+                .map(|f| classify(f).to_string())
+        });
+        let size_limits = app_config.size_limits(category.as_deref());
         let client_config = XdccConfig {
-            nickname: app_config.nickname.clone(),
-            username: app_config.username.clone(),
-            realname: app_config.realname.clone(),
-            use_ssl: app_config.use_ssl,
-            connect_timeout_secs: app_config.connect_timeout,
-            timeout_secs: app_config.general_timeout,
-            download_dir: download_dir.clone(),
-            networks: app_config
-                .networks
-                .iter()
-                .map(|(k, v)| {
-                    (
-                        k.clone(),
-                        (
-                            v.host.clone(),
-                            v.port,
-                            v.ssl,
-                            v.autojoin_channels.clone(),
-                            v.join_delay_secs,
-                            v.nickserv_password.clone(),
-                        ),
-                    )
-                })
-                .collect(),
-            proxy_enabled: app_config.proxy_enabled,
-            proxy_url: app_config.proxy_url.clone(),
-            resume_enabled: app_config.resume_enabled,
+            expected_filename,
+            expected_size,
+            offers,
+            transfer_id: tid.clone(),
+            hooks: hooks.clone(),
+            throttle: Throttle {
+                global: bandwidth,
+                transfer: limiter,
+            },
+            size_limits,
+            pool: app_config.shared_irc_connections.then_some(pool),
+            ..XdccConfig::from_app_config(&app_config, download_dir.clone(), &url.bot)
         };
+        let (http_fallback, http_fallback_after) =
+            (app_config.http_fallback, app_config.http_fallback_after);
         drop(app_config);
 
-        let client = XdccClient::new(client_config);
+        // After repeated DCC failures, fetch from a mirror the bot advertised.
+        // Mirrors are fetched directly, so not when traffic has to go through the proxy.
+        let outbound = client_config.outbound();
+        let mirror = match transfer_manager.read().await.get_transfer(&tid).await {
+            Some(t)
+                if http_fallback
+                    && t.retry_count >= http_fallback_after
+                    && outbound.proxy_url.is_empty()
+                    && !outbound.require_proxy =>
+            {
+                t.transfer.mirrors.iter().find(|m| http::is_supported(m)).cloned()
+            }
+            _ => None,
+        };
+
+        let filename_policy = client_config.filename_policy;
 
+        // Paused before it got going
+        if cancel_token.is_cancelled() {
+            return;
+        }
         {
             let tm = transfer_manager.write().await;
             tm.update_status(&tid, TransferStatus::Connecting).await;
         }
 
         let mut retry_info: Option<(XdccUrl, CancellationToken)> = None;
+        let mut failure: Option<String> = None;
+        // IRC lines seen during this attempt, for the failure bundle
+        let mut irc_capture: VecDeque<String> = VecDeque::new();
+        let mut partial_recorded_at: Option<std::time::Instant> = None;
 
-        match client.start_download(url).await {
+        let network = url.network.clone();
+        let started = match mirror {
+            Some(mirror) => {
+                transfer_manager
+                    .read()
+                    .await
+                    .add_log(&tid, format!("DCC failed repeatedly, switching to mirror {}", mirror))
+                    .await;
+                Ok(http::start_download(mirror, client_config))
+            }
+            None => XdccClient::new(client_config).start_download(url).await,
+        };
+        match started {
             Ok(mut rx) => {
                 tracing::info!("Download channel open for {}", tid);
                 loop {
                     tokio::select! {
+                        // A cancelled or paused transfer takes no more events
+                        biased;
                         _ = cancel_token.cancelled() => {
                             tracing::info!("Download cancelled for {}", tid);
                             break;
@@ -101,12 +213,32 @@ pub fn spawn_download_task(
                                     let tm = transfer_manager.write().await;
                                     tm.add_log(&tid, format!("DCC SEND from {}:{} - {} ({} bytes)", ip, port, filename, size)).await;
                                     tm.set_file_info(&tid, filename.clone(), size).await;
+                                    tm.set_method(&tid, TransferMethod::Dcc).await;
                                     tm.update_status(&tid, TransferStatus::Downloading).await;
                                     plugin_manager.emit_signal("download_started", crate::plugin::EventData::String(filename));
                                 }
-                                Some(XdccEvent::Progress { downloaded, total, speed }) => {
+                                Some(XdccEvent::MirrorDownload { url, filename, size }) => {
+                                    tracing::info!("HTTP download from {} - {} ({} bytes)", url, filename, size);
                                     let tm = transfer_manager.write().await;
-                                    tm.update_progress(&tid, downloaded, speed).await;
+                                    tm.add_log(&tid, format!("HTTP download from {} - {} ({} bytes)", url, filename, size)).await;
+                                    tm.set_file_info(&tid, filename.clone(), size).await;
+                                    tm.set_method(&tid, TransferMethod::Http).await;
+                                    tm.update_status(&tid, TransferStatus::Downloading).await;
+                                    plugin_manager.emit_signal("download_started", crate::plugin::EventData::String(filename));
+                                }
+                                Some(XdccEvent::Progress { downloaded, received, total, speed }) => {
+                                    let tm = transfer_manager.read().await;
+                                    tm.update_progress(&tid, downloaded, received, speed).await;
+                                    if partial_recorded_at.is_none_or(|at| at.elapsed() >= PARTIAL_RECORD_INTERVAL) {
+                                        tm.record_partial_file(&tid, filename_policy).await;
+                                        partial_recorded_at = Some(std::time::Instant::now());
+                                    }
+                                    let notify_mins = config.read().await.eta_notify_mins;
+                                    if notify_mins > 0 {
+                                        if let Some(t) = tm.check_eta_notice(&tid, notify_mins * 60).await {
+                                            notify_eta(&t, &config, &plugin_manager).await;
+                                        }
+                                    }
                                     if downloaded % (10 * 1024 * 1024) < 65536 {
                                         let pct = if total > 0 { (downloaded as f64 / total as f64) * 100.0 } else { 0.0 };
                                         tracing::debug!("Download progress: {:.1}% ({}/{} bytes)", pct, downloaded, total);
@@ -117,17 +249,70 @@ pub fn spawn_download_task(
                                     {
                                         let tm = transfer_manager.write().await;
                                         tm.add_log(&tid, "Download completed successfully".to_string()).await;
+                                        tm.forget_partial_file(&tid).await;
                                     }
 
-                                    let completed_filename = {
+                                    let (completed_filename, category, pack_info, template) = {
                                         let tm = transfer_manager.read().await;
                                         if let Some(t) = tm.get_transfer(&tid).await {
-                                            t.transfer.filename.clone()
+                                            (t.transfer.filename.clone(), t.transfer.category.clone(), t.transfer.pack_info.clone(), t.transfer.template.clone())
                                         } else {
-                                            None
+                                            (None, None, None, None)
                                         }
                                     };
 
+                                    // Detect the category from the file name when none was supplied
+                                    let category = category.or_else(|| {
+                                        completed_filename.as_deref().map(|f| classify(f).to_string())
+                                    });
+                                    if let Some(ref category) = category {
+                                        let tm = transfer_manager.write().await;
+                                        tm.set_category(&tid, Some(category.clone())).await;
+                                        tm.add_log(&tid, format!("Category: {}", category)).await;
+                                    }
+
+                                    let file_path = completed_filename.as_ref().map(|filename| {
+                                        saved_path(std::path::Path::new(&download_dir), filename)
+                                            .to_string_lossy()
+                                            .to_string()
+                                    });
+
+                                    // Hold back suspicious files instead of treating them as good completions
+                                    if let Some(ref file_path) = file_path {
+                                        let (screen_config, quarantine_dir) = {
+                                            let app_config = config.read().await;
+                                            (ScreenConfig::from_config(&app_config), quarantine_dir(&app_config))
+                                        };
+                                        if let Err(reason) = screen(std::path::Path::new(file_path), pack_info.as_ref(), &screen_config).await {
+                                            tracing::warn!("Quarantining {}: {}", file_path, reason);
+                                            let tm = transfer_manager.write().await;
+                                            match move_file(file_path, &quarantine_dir.to_string_lossy()).await {
+                                                Ok(moved) => {
+                                                    tm.add_log(&tid, format!("Quarantined to {}: {}", moved, reason)).await;
+                                                    if let Some(name) = std::path::Path::new(&moved).file_name() {
+                                                        tm.set_filename(&tid, name.to_string_lossy().to_string()).await;
+                                                    }
+                                                }
+                                                Err(e) => tm.add_log(&tid, format!("Quarantine move failed: {}", e)).await,
+                                            }
+                                            tm.set_quarantined(&tid, reason.clone()).await;
+                                            plugin_manager.emit_signal("download_quarantined", crate::plugin::EventData::Tuple2(file_path.clone(), reason));
+                                            break;
+                                        }
+                                    }
+
+                                    let policy = FilenamePolicy::from_config(&*config.read().await);
+                                    let (completed_filename, file_path, category) = run_complete_hook(
+                                        &tid,
+                                        &hooks,
+                                        policy,
+                                        &transfer_manager,
+                                        completed_filename,
+                                        file_path,
+                                        category,
+                                    )
+                                    .await;
+
                                     {
                                         let tm = transfer_manager.write().await;
                                         tm.set_completed(&tid).await;
@@ -136,30 +321,13 @@ pub fn spawn_download_task(
                                         plugin_manager.emit_signal("download_completed", crate::plugin::EventData::String(filename));
                                     }
 
-                                    if let Some(filename) = completed_filename {
-                                        let app_config = config.read().await;
-                                        if app_config.move_completed || app_config.postprocess_script_enabled {
-                                            let pp_config = PostprocessConfig {
-                                                move_completed_dir: if app_config.move_completed && !app_config.move_completed_dir.is_empty() {
-                                                    Some(app_config.move_completed_dir.clone())
-                                                } else {
-                                                    None
-                                                },
-                                                script_path: if app_config.postprocess_script_enabled && !app_config.postprocess_script.is_empty() {
-                                                    Some(app_config.postprocess_script.clone())
-                                                } else {
-                                                    None
-                                                },
-                                                script_timeout_secs: app_config.postprocess_timeout,
-                                            };
-                                            drop(app_config);
-
-                                            let safe_filename = filename.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
-                                            let file_path = std::path::Path::new(&download_dir)
-                                                .join(&safe_filename)
-                                                .to_string_lossy()
-                                                .to_string();
-
+                                    if let Some(file_path) = file_path {
+                                        let mut saved_to = file_path.clone();
+                                        let pp_config = {
+                                            let app_config = config.read().await;
+                                            PostprocessConfig::for_transfer(&app_config, category.as_deref(), template.as_deref())
+                                        };
+                                        if !pp_config.is_empty() {
                                             tracing::info!("Running postprocessing on: {}", file_path);
                                             let result = run_postprocess(&file_path, &pp_config).await;
 
@@ -174,8 +342,20 @@ pub fn spawn_download_task(
                                             if let Some(exit_code) = result.script_exit_code {
                                                 tracing::info!("Postprocess script exited with code: {}", exit_code);
                                             }
+                                            let tm = transfer_manager.read().await;
+                                            for step in &result.steps {
+                                                tm.add_log(&tid, format!("Postprocess {} {:?}: {}", step.action, step.status, step.output.trim())).await;
+                                            }
+                                            tm.set_postprocess(&tid, result.steps).await;
+                                            if let Some(path) = result.final_path {
+                                                saved_to = path;
+                                            }
                                         }
+                                        transfer_manager.read().await.set_saved_to(&tid, saved_to.clone()).await;
+                                        spawn_thumbnail(&tid, &saved_to, &transfer_manager, &config);
+                                        package_completed_job(&tid, &transfer_manager, &config).await;
                                     }
+                                    notify_transfer(&tid, notifications::COMPLETED, &transfer_manager, &config).await;
                                     break;
                                 }
                                 Some(XdccEvent::Error(e)) => {
@@ -183,15 +363,50 @@ pub fn spawn_download_task(
                                     plugin_manager.emit_signal("download_failed", crate::plugin::EventData::String(format!("{}", e)));
                                     let tm = transfer_manager.write().await;
                                     tm.add_log(&tid, format!("Error: {}", e)).await;
+                                    if matches!(e, XdccError::NoResponse(_)) {
+                                        tm.record_bot_silence(&tid).await;
+                                    }
+                                    if let XdccError::DiskWrite(_) = e {
+                                        // Retrying can't help until there is space again
+                                        tm.pause_for_disk_error(&tid, e.to_string()).await;
+                                        tm.record_partial_file(&tid, filename_policy).await;
+                                        let transfer = tm.get_transfer(&tid).await;
+                                        drop(tm);
+                                        if let Some(t) = transfer {
+                                            notify_disk_error(&t.transfer, &e, &config, &plugin_manager).await;
+                                        }
+                                        break;
+                                    }
                                     retry_info = tm.set_failed(&tid, e.to_string(), e.is_fatal()).await;
+                                    failure = Some(e.to_string());
                                     break;
                                 }
                                 Some(XdccEvent::IrcMessage(network, channel, nick, message)) => {
+                                    capture_irc(&mut irc_capture, format!("{} <{}> {}", channel, nick, message));
                                     plugin_manager.emit_signal("irc_message", crate::plugin::EventData::Tuple4(network, channel, nick, message));
                                 }
                                 Some(XdccEvent::IrcNotice(nick, message)) => {
+                                    capture_irc(&mut irc_capture, format!("-{}- {}", nick, message));
+                                    transfer_manager.read().await.record_bot_notice(&tid, &nick, &message).await;
                                     plugin_manager.emit_signal("irc_notice", crate::plugin::EventData::Tuple2(nick, message));
                                 }
+                                Some(XdccEvent::PackInfo(info)) => {
+                                    let tm = transfer_manager.write().await;
+                                    if let Some(ref mismatch) = info.mismatch {
+                                        tracing::warn!("Pack info mismatch for {}: {}", tid, mismatch);
+                                    }
+                                    tm.set_pack_info(&tid, info).await;
+                                }
+                                Some(XdccEvent::SavingAs(name)) => {
+                                    let tm = transfer_manager.write().await;
+                                    tm.add_log(&tid, format!("A finished file has the same name, saving as {}", name)).await;
+                                    tm.set_filename(&tid, name).await;
+                                }
+                                Some(XdccEvent::Resolved(source, server)) => {
+                                    let tm = transfer_manager.write().await;
+                                    tm.add_log(&tid, format!("Network resolved to {} ({})", server, source)).await;
+                                    tm.set_resolution(&tid, source.to_string(), server).await;
+                                }
                                 Some(XdccEvent::Log(msg)) => {
                                     let tm = transfer_manager.write().await;
                                     tm.add_log(&tid, msg).await;
@@ -207,66 +422,681 @@ pub fn spawn_download_task(
                 tracing::error!("Failed to start download {}: {}", tid, e);
                 let tm = transfer_manager.write().await;
                 retry_info = tm.set_failed(&tid, e.to_string(), e.is_fatal()).await;
+                failure = Some(e.to_string());
             }
         }
 
-        if let Some((retry_url, new_token)) = retry_info {
-            tracing::info!("Spawning retry download for {}", tid);
+        if failure.is_some() && retry_info.is_none() {
+            save_failure_bundle(&tid, &network, &irc_capture, &transfer_manager, &config).await;
+            notify_transfer(&tid, notifications::FAILED, &transfer_manager, &config).await;
+        }
+
+        if let Some(mut error) = failure {
+            let (threshold, webhook) = {
+                let cfg = config.read().await;
+                // Error texts can name bots and files; don't let them leave Botarr
+                if cfg.is_private_network(&network) {
+                    error = "Transfer failed on a private network".to_string();
+                }
+                (
+                    cfg.failure_alert_threshold as usize,
+                    cfg.alert_webhook_url.clone(),
+                )
+            };
+            let alert = transfer_manager
+                .read()
+                .await
+                .record_failure_for_alert(&error, threshold)
+                .await;
+            if let Some(alert) = alert {
+                tracing::warn!("{}", alert.message());
+                plugin_manager.emit_signal(
+                    "failure_alert",
+                    crate::plugin::EventData::String(alert.message()),
+                );
+                if !webhook.is_empty() {
+                    crate::alerts::send_alert(&webhook, &alert).await;
+                }
+            }
+        }
+
+        if retry_info.is_some() {
+            // Back through the queue, so retries count towards queue_limit
             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-            spawn_download_task(
-                tid.clone(),
-                retry_url,
-                new_token,
-                download_dir,
-                transfer_manager,
-                config,
-                plugin_manager.clone(),
-            );
+            if transfer_manager.read().await.requeue(&tid).await {
+                tracing::info!("Queued retry of {}", tid);
+            }
         } else {
             tracing::info!("Download task finished for {}", tid);
         }
+    }
+        .instrument(span),
+    );
+}
+
+/// Run the on_before_download hook, which may veto the download, switch it
+/// to another pack or change its category and labels. Returns the URL to
+/// download, or `None` if the transfer was vetoed (and marked failed).
+async fn run_before_download_hook(
+    tid: &str,
+    url: XdccUrl,
+    hooks: &ScriptHooks,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+) -> Option<XdccUrl> {
+    if !hooks.is_set(hooks::ON_BEFORE_DOWNLOAD) {
+        return Some(url);
+    }
+    let Some(t) = transfer_manager.read().await.get_transfer(tid).await else {
+        return Some(url);
+    };
+    let arg = serde_json::json!({
+        "id": tid,
+        "url": url.to_url(),
+        "network": url.network,
+        "channel": url.channel,
+        "bot": url.bot,
+        "pack": url.slot,
+        "filename": t.transfer.filename,
+        "size": t.transfer.size,
+        "category": t.transfer.category,
+        "priority": t.priority,
+        "labels": t.transfer.labels,
+        "retry": t.retry_count,
+    });
+    // Not under the transfer manager lock: the script may take a while
+    let outcome = hooks.run(hooks::ON_BEFORE_DOWNLOAD, arg).await;
+    let tm = transfer_manager.read().await;
+    match outcome {
+        HookOutcome::Veto => {
+            let reason = format!("Vetoed by {} hook", hooks::ON_BEFORE_DOWNLOAD);
+            tm.add_log(tid, reason.clone()).await;
+            tm.set_failed(tid, reason, true).await;
+            None
+        }
+        HookOutcome::Changed(serde_json::Value::Object(changes)) => {
+            let mut url = url;
+            if let Some(new_url) = changes.get("url").and_then(|v| v.as_str()) {
+                match XdccUrl::parse(new_url) {
+                    Ok(new_url) => {
+                        tm.add_log(tid, format!("Hook switched source to {}", new_url))
+                            .await;
+                        url = new_url;
+                    }
+                    Err(e) => {
+                        tm.add_log(tid, format!("Hook returned bad URL: {}", e))
+                            .await
+                    }
+                }
+            }
+            if let Some(category) = changes.get("category").and_then(|v| v.as_str()) {
+                tm.set_category(tid, Some(category.to_string())).await;
+            }
+            if let Some(labels) = changes.get("labels").and_then(|v| v.as_array()) {
+                let labels = labels
+                    .iter()
+                    .filter_map(|l| l.as_str().map(str::to_string))
+                    .collect();
+                tm.set_annotations(tid, labels, t.transfer.note.clone())
+                    .await;
+            }
+            Some(url)
+        }
+        _ => Some(url),
+    }
+}
+
+/// Run the on_complete hook, which may rename the downloaded file or change
+/// its category (and so the postprocessing it gets). Returns the possibly
+/// updated filename, path and category.
+async fn run_complete_hook(
+    tid: &str,
+    hooks: &ScriptHooks,
+    policy: FilenamePolicy,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+    filename: Option<String>,
+    file_path: Option<String>,
+    category: Option<String>,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let (Some(name), Some(path)) = (filename.as_deref(), file_path.as_deref()) else {
+        return (filename, file_path, category);
+    };
+    if !hooks.is_set(hooks::ON_COMPLETE) {
+        return (filename, file_path, category);
+    }
+    let Some(t) = transfer_manager.read().await.get_transfer(tid).await else {
+        return (filename, file_path, category);
+    };
+    let arg = serde_json::json!({
+        "id": tid,
+        "filename": name,
+        "path": path,
+        "size": t.transfer.size,
+        "category": category,
+        "labels": t.transfer.labels,
+        "network": t.transfer.url.network,
+        "bot": t.transfer.url.bot,
+    });
+    let HookOutcome::Changed(serde_json::Value::Object(changes)) =
+        hooks.run(hooks::ON_COMPLETE, arg).await
+    else {
+        return (filename, file_path, category);
+    };
+    let tm = transfer_manager.read().await;
+
+    let mut category = category;
+    if let Some(new) = changes
+        .get("category")
+        .and_then(|v| v.as_str())
+        .filter(|c| !c.is_empty())
+    {
+        tm.set_category(tid, Some(new.to_string())).await;
+        tm.add_log(tid, format!("Category set by hook: {}", new))
+            .await;
+        category = Some(new.to_string());
+    }
+
+    if let Some(new_name) = changes.get("filename").and_then(|v| v.as_str()) {
+        let safe_name = policy.sanitize(new_name);
+        let dir = std::path::Path::new(path)
+            .parent()
+            .unwrap_or(std::path::Path::new(""));
+        if !new_name.is_empty() && safe_name != name {
+            let new_path = unique_path(dir, &safe_name);
+            let safe_name = new_path
+                .file_name()
+                .map_or(safe_name, |n| n.to_string_lossy().to_string());
+            match tokio::fs::rename(path, &new_path).await {
+                Ok(()) => {
+                    tm.add_log(tid, format!("Renamed by hook to {}", safe_name))
+                        .await;
+                    tm.set_file_info(tid, safe_name.clone(), t.transfer.size.unwrap_or(0))
+                        .await;
+                    let new_path = new_path.to_string_lossy().to_string();
+                    return (Some(safe_name), Some(new_path), category);
+                }
+                Err(e) => tm.add_log(tid, format!("Hook rename failed: {}", e)).await,
+            }
+        }
+    }
+    (filename, file_path, category)
+}
+
+/// Keep the last IRC lines of a download attempt
+fn capture_irc(capture: &mut VecDeque<String>, line: String) {
+    if capture.len() >= IRC_CAPTURE_LINES {
+        capture.pop_front();
+    }
+    capture.push_back(format!(
+        "{} {}",
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
+        line
+    ));
+}
+
+/// Write the failure bundle of a transfer that won't be retried, if enabled
+async fn save_failure_bundle(
+    tid: &str,
+    network: &str,
+    irc_capture: &VecDeque<String>,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+    config: &Arc<RwLock<AppConfig>>,
+) {
+    let config = config.read().await.clone();
+    if !config.failure_bundles {
+        return;
+    }
+    let (transfer, events) = {
+        let tm = transfer_manager.read().await;
+        (tm.get_transfer(tid).await, tm.get_logs(tid).await)
+    };
+    let Some(transfer) = transfer else {
+        return;
+    };
+    // Chat on private networks stays out of bundles meant for sharing
+    let irc: Vec<String> = if config.is_private_network(network) {
+        Vec::new()
+    } else {
+        irc_capture.iter().cloned().collect()
+    };
+
+    let bundle = crate::bundle::build_bundle(&crate::bundle::BundleInput {
+        transfer: &transfer.transfer,
+        retry_count: transfer.retry_count,
+        events: &events,
+        irc: &irc,
+        config: &config,
+    });
+    let path = crate::bundle::bundle_path(&config, tid);
+    let result = match bundle {
+        Ok(bytes) => match path.parent() {
+            Some(dir) => match tokio::fs::create_dir_all(dir).await {
+                Ok(()) => tokio::fs::write(&path, bytes).await,
+                Err(e) => Err(e),
+            },
+            None => tokio::fs::write(&path, bytes).await,
+        }
+        .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(()) => tracing::info!("Saved failure bundle {}", path.display()),
+        Err(e) => tracing::warn!("Failed to save failure bundle for {}: {}", tid, e),
+    }
+}
+
+/// Grab a frame of a completed video for the history view, if enabled. Runs
+/// in the background so ffmpeg doesn't hold up packaging and notifications.
+fn spawn_thumbnail(
+    tid: &str,
+    path: &str,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+    config: &Arc<RwLock<AppConfig>>,
+) {
+    let tid = tid.to_string();
+    let path = std::path::PathBuf::from(path);
+    let transfer_manager = transfer_manager.clone();
+    let config = config.clone();
+    tokio::spawn(
+        async move {
+            let (ffmpeg, out) = {
+                let cfg = config.read().await;
+                if !cfg.thumbnails {
+                    return;
+                }
+                (
+                    cfg.ffmpeg_path.clone(),
+                    thumbnail::thumbnail_path(&cfg.thumbnail_dir, &tid),
+                )
+            };
+            // Files uploaded to an rclone remote are no longer here
+            if !thumbnail::is_video(&path) || !path.is_file() {
+                return;
+            }
+            let message = match thumbnail::generate(&ffmpeg, &path, &out).await {
+                Ok(()) => "Thumbnail created".to_string(),
+                Err(e) => {
+                    tracing::warn!("No thumbnail for {}: {}", tid, e);
+                    format!("No thumbnail: {}", e)
+                }
+            };
+            transfer_manager.read().await.add_log(&tid, message).await;
+        }
+        .in_current_span(),
+    );
+}
+
+/// Package the job of a completed transfer once all of its transfers are done,
+/// if enabled
+async fn package_completed_job(
+    tid: &str,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+    config: &Arc<RwLock<AppConfig>>,
+) {
+    let package_config = {
+        let cfg = config.read().await;
+        if !cfg.package_jobs {
+            return;
+        }
+        PackageConfig::from_config(&cfg)
+    };
+    let job = transfer_manager.read().await.job_of(tid).await;
+    if let Some(job) = job.filter(|j| j.status == "completed") {
+        let _ = package_and_record(&job, &package_config, transfer_manager).await;
+    }
+}
+
+/// Package a job and log the outcome on each of its transfers. A job being
+/// packaged already is refused, and stays claimed until its transfers point
+/// at the moved files.
+async fn package_and_record(
+    job: &TransferJob,
+    package_config: &PackageConfig,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+) -> Result<PackageReport, String> {
+    let Some(_claim) = PackagingClaim::acquire(&job.id) else {
+        return Err("Job is already being packaged".to_string());
+    };
+    // Another packaging may have finished since the job was looked up
+    let job = transfer_manager
+        .read()
+        .await
+        .job_of(&job.id)
+        .await
+        .unwrap_or_else(|| job.clone());
+    let job = &job;
+    let result = package_job(job, package_config).await;
+    let tm = transfer_manager.read().await;
+    match &result {
+        Ok(report) => {
+            tracing::info!("Packaged job {} into {}", job.id, report.dir);
+            for file in &report.files {
+                tm.add_log(&file.transfer_id, format!("Packaged into {}", report.dir))
+                    .await;
+                tm.set_saved_to(&file.transfer_id, file.path.clone()).await;
+            }
+            for id in &report.missing {
+                tm.add_log(id, "File not found for packaging".to_string())
+                    .await;
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to package job {}: {}", job.id, e);
+            tm.add_log(&job.id, format!("Packaging failed: {}", e))
+                .await;
+        }
+    }
+    result
+}
+
+/// Send a completed or failed transfer to the notification targets
+async fn notify_transfer(
+    tid: &str,
+    event: &'static str,
+    transfer_manager: &Arc<RwLock<EnhancedTransferManager>>,
+    config: &Arc<RwLock<AppConfig>>,
+) {
+    let Some(t) = transfer_manager.read().await.get_transfer(tid).await else {
+        return;
+    };
+    let cfg = config.read().await;
+    let private = cfg.is_private_network(&t.transfer.url.network);
+    notifications::dispatch(&cfg, Notification::transfer(event, &t.transfer, private));
+}
+
+/// Tell the user a transfer was paused because its file couldn't be written
+async fn notify_disk_error(
+    transfer: &crate::xdcc::XdccTransfer,
+    error: &XdccError,
+    config: &Arc<RwLock<AppConfig>>,
+    plugin_manager: &crate::plugin::PluginManager,
+) {
+    let (webhook, private) = {
+        let cfg = config.read().await;
+        (
+            cfg.alert_webhook_url.clone(),
+            cfg.is_private_network(&transfer.url.network),
+        )
+    };
+    let filename = transfer.filename.clone().unwrap_or_default();
+    tracing::error!("Paused {} after a disk error: {}", filename, error);
+    plugin_manager.emit_signal(
+        "disk_error",
+        crate::plugin::EventData::Tuple2(filename.clone(), error.to_string()),
+    );
+
+    if webhook.is_empty() {
+        return;
+    }
+    let (message, details) = if private {
+        (
+            "Botarr: a download on a private network was paused after a disk error".to_string(),
+            serde_json::json!({}),
+        )
+    } else {
+        (
+            format!("Botarr: {} was paused after a disk error", filename),
+            serde_json::json!({
+                "id": transfer.id,
+                "filename": filename,
+                "error": error.to_string(),
+            }),
+        )
+    };
+    tokio::spawn(async move {
+        crate::alerts::send_notification(&webhook, "disk_error", &message, details).await;
     });
 }
 
+/// Announce that a download is about to finish
+async fn notify_eta(
+    transfer: &crate::xdcc::XdccTransfer,
+    config: &Arc<RwLock<AppConfig>>,
+    plugin_manager: &crate::plugin::PluginManager,
+) {
+    let (webhook, private) = {
+        let cfg = config.read().await;
+        (
+            cfg.alert_webhook_url.clone(),
+            cfg.is_private_network(&transfer.url.network),
+        )
+    };
+    let filename = transfer.filename.clone().unwrap_or_default();
+    let mins = transfer.eta_secs.unwrap_or(0).div_ceil(60).max(1);
+    tracing::info!("{} finishes in about {} min", filename, mins);
+    plugin_manager.emit_signal(
+        "download_eta",
+        crate::plugin::EventData::Tuple2(filename.clone(), mins.to_string()),
+    );
+
+    if webhook.is_empty() {
+        return;
+    }
+    let (message, details) = if private {
+        (
+            format!(
+                "Botarr: a download on a private network finishes in about {} min",
+                mins
+            ),
+            serde_json::json!({ "eta_secs": transfer.eta_secs }),
+        )
+    } else {
+        (
+            format!("Botarr: {} finishes in about {} min", filename, mins),
+            serde_json::json!({
+                "id": transfer.id,
+                "filename": filename,
+                "size": transfer.size,
+                "eta_secs": transfer.eta_secs,
+            }),
+        )
+    };
+    // Don't hold up the transfer while the webhook responds
+    tokio::spawn(async move {
+        crate::alerts::send_notification(&webhook, "download_eta", &message, details).await;
+    });
+}
+
+/// Per-provider status from the most recent searches
+pub async fn xdcc_search_providers(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.search_aggregator.provider_statuses())
+}
+
 pub async fn xdcc_search(
     State(state): State<AppState>,
     Query(params): Query<SearchRequest>,
+    Query(page_params): Query<PageParams>,
 ) -> impl IntoResponse {
-    let providers = params.providers.map(|p| {
+    // Without `page` or `limit` every result is returned
+    let paged = page_params.page.is_some() || page_params.limit.is_some();
+    let page = page_params.page(MAX_LIMIT);
+    let providers = params.providers.as_ref().map(|p| {
         p.split(',')
             .map(|s| s.trim().to_string())
             .filter(|s| !s.is_empty())
             .collect::<Vec<_>>()
     });
 
-    match state
-        .search_aggregator
-        .search(&params.query, providers.as_deref())
-        .await
-    {
-        Ok(results) => {
-            let count = results.len();
-            let results_json = serde_json::to_string(&results).ok();
-            if let Err(e) =
-                state
-                    .database
-                    .insert_search(&params.query, count as i64, results_json.as_deref())
-            {
-                tracing::error!("Failed to save search history: {}", e);
-            }
-            Json(SearchResponse { results, count }).into_response()
+    let config = state.config.read().await.clone();
+    let (mut query, mut filter) = SearchFilter::parse_query(&params.query);
+    filter.extend(params.include.as_deref(), params.exclude.as_deref());
+    if config.search_normalize {
+        query = transliterate(&query);
+        filter.set_normalize(true);
+    }
+    if let Some(pattern) = params.regex.as_deref().filter(|p| !p.is_empty()) {
+        if let Err(error) = filter.set_regex(pattern) {
+            return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response();
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+    }
+    if query.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: e.to_string(),
+                error: "Query must contain at least one search term".to_string(),
             }),
         )
-            .into_response(),
+            .into_response();
+    }
+
+    // Every page of a search is cut from the same results
+    let cache_key = search_cache::key(
+        &query,
+        providers.as_deref(),
+        &filter,
+        params.sort.as_deref(),
+        params.max_age_days,
+    );
+    let results = match state.search_cache.get(&cache_key) {
+        Some(results) => results,
+        None => {
+            let results = match run_search(
+                &state,
+                &config,
+                &query,
+                &filter,
+                &params,
+                providers.as_deref(),
+            )
+            .await
+            {
+                Ok(results) => results,
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ErrorResponse {
+                            error: e.to_string(),
+                        }),
+                    )
+                        .into_response()
+                }
+            };
+            record_search(&state, &config, &params.query, &results);
+            state.search_cache.insert(cache_key, &results);
+            results
+        }
+    };
+
+    let count = results.len();
+    if !paged {
+        return Json(SearchResponse { results, count }).into_response();
     }
+    (
+        page.headers(count as i64),
+        Json(SearchResponse {
+            results: page.slice(results),
+            count,
+        }),
+    )
+        .into_response()
 }
 
-pub async fn xdcc_parse_url(Json(req): Json<ParseUrlRequest>) -> impl IntoResponse {
+/// Search the providers and filter, annotate and hook the results
+async fn run_search(
+    state: &AppState,
+    config: &AppConfig,
+    query: &str,
+    filter: &SearchFilter,
+    params: &SearchRequest,
+    providers: Option<&[String]>,
+) -> Result<Vec<XdccSearchResult>, XdccError> {
+    let mut results = state.search_aggregator.search(query, providers).await?;
+
+    let mapper = AnimeMapper::from_config(config);
+    if !mapper.is_empty() {
+        // Also search the absolute-numbered form of `Show S02E05` queries
+        if let Some(absolute) = mapper.to_absolute_query(query) {
+            tracing::info!("Anime mapping: also searching '{}'", absolute);
+            match state.search_aggregator.search(&absolute, providers).await {
+                Ok(extra) => {
+                    for r in extra {
+                        if !results.iter().any(|e| e.url == r.url) {
+                            results.push(r);
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("Absolute search '{}' failed: {}", absolute, e),
+            }
+        }
+        for r in results.iter_mut() {
+            r.annotations.episode = mapper
+                .resolve_absolute(&r.filename)
+                .map(|ep| ep.to_string());
+        }
+    }
+
+    filter.apply(&mut results);
+    // Results without a date are kept; most providers don't report one
+    if let Some(days) = params.max_age_days {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+        results.retain(|r| r.last_seen.is_none_or(|seen| seen >= cutoff));
+    }
+    if params.sort.as_deref() == Some("freshness") {
+        results.sort_by_key(|r| std::cmp::Reverse(r.last_seen));
+    }
+    let mut results = ScriptHooks::from_config(config)
+        .filter_search_results(query, results)
+        .await;
+
+    let dedupe = DedupeMode::from_config(config);
+    if dedupe != DedupeMode::Off {
+        let lookups: Vec<_> = results
+            .iter()
+            .map(|r| state.arr_library.find(config, &r.filename))
+            .collect();
+        let found: Vec<_> = stream::iter(lookups)
+            .buffered(LOOKUP_CONCURRENCY)
+            .collect()
+            .await;
+        for (r, service) in results.iter_mut().zip(found) {
+            r.annotations.in_library = service.map(str::to_string);
+        }
+        if dedupe == DedupeMode::Filter {
+            results.retain(|r| r.annotations.in_library.is_none());
+        }
+    }
+
+    if !config.tmdb_api_key.is_empty() {
+        let releases: Vec<_> = results
+            .iter()
+            .take(METADATA_LOOKUP_LIMIT)
+            .map(|r| parse_release(&r.filename))
+            .collect();
+        let lookups: Vec<_> = releases
+            .iter()
+            .map(|release| state.metadata.resolve(&config.tmdb_api_key, release))
+            .collect();
+        let resolved: Vec<_> = stream::iter(lookups)
+            .buffered(LOOKUP_CONCURRENCY)
+            .collect()
+            .await;
+        for (r, metadata) in results.iter_mut().zip(resolved) {
+            r.annotations.metadata = metadata;
+        }
+    }
+    Ok(results)
+}
+
+/// Add a search and its results to the search history
+fn record_search(state: &AppState, config: &AppConfig, query: &str, results: &[XdccSearchResult]) {
+    // Packs from private networks are shown but never stored
+    let stored: Vec<_> = results
+        .iter()
+        .filter(|r| !config.is_private_network(&r.network))
+        .cloned()
+        .collect();
+    let results_json =
+        crate::db::serialize_results_capped(&stored, config.search_results_max_kb as usize * 1024);
+    if let Err(e) = state.database.insert_search(
+        query,
+        results.len() as i64,
+        results_json.as_deref(),
+        config.search_history_merge_window_mins,
+    ) {
+        tracing::error!("Failed to save search history: {}", e);
+    }
+}
+
+pub async fn xdcc_parse_url(ApiJson(req): ApiJson<ParseUrlRequest>) -> impl IntoResponse {
     match XdccUrl::parse(&req.url) {
         Ok(url) => Json(ParseUrlResponse {
             valid: true,
@@ -283,7 +1113,7 @@ pub async fn xdcc_parse_url(Json(req): Json<ParseUrlRequest>) -> impl IntoRespon
 
 pub async fn xdcc_download(
     State(state): State<AppState>,
-    Json(req): Json<DownloadRequest>,
+    ApiJson(req): ApiJson<DownloadRequest>,
 ) -> impl IntoResponse {
     let url = match XdccUrl::parse(&req.url) {
         Ok(u) => u,
@@ -298,17 +1128,94 @@ pub async fn xdcc_download(
         }
     };
 
+    let template = match req.template.as_deref() {
+        Some(name) => match state.config.read().await.template(name) {
+            Some((name, template)) => Some((name.clone(), template.clone())),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: format!("Unknown template: {}", name),
+                    }),
+                )
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+
     let priority = match req.priority.as_deref() {
         Some("low") => TransferPriority::Low,
         Some("high") => TransferPriority::High,
         Some("urgent") => TransferPriority::Urgent,
-        _ => TransferPriority::Normal,
+        Some(_) => TransferPriority::Normal,
+        None => template
+            .as_ref()
+            .and_then(|(_, t)| t.priority)
+            .unwrap_or_default(),
     };
+    let category = req
+        .category
+        .clone()
+        .or_else(|| template.as_ref().and_then(|(_, t)| t.category.clone()));
+
+    if let Some(after) = req.after.as_deref() {
+        let tm = state.transfer_manager.read().await;
+        if tm.get_transfer(after).await.is_none() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Unknown transfer: {}", after),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    if req.dry_run {
+        let template = template.map(|(name, _)| name);
+        let plan = plan_download(&state, &url, &req, priority, category, template).await;
+        return Json(plan).into_response();
+    }
 
     let result = {
         let tm = state.transfer_manager.write().await;
-        tm.create_transfer(url.clone(), priority, true, req.filename.clone())
-            .await
+        if let Some((id, status)) = tm.coalesce_request(&url, priority).await {
+            tracing::info!("Download request for {} attached to transfer {}", url, id);
+            return (
+                [(TRANSFER_ID, id.clone())],
+                Json(DownloadResponse {
+                    transfer_id: id.clone(),
+                    status: format!("{:?}", status).to_lowercase(),
+                    duplicate_of: Some(id),
+                }),
+            )
+                .into_response();
+        }
+        let result = tm
+            .create_transfer(url.clone(), priority, true, req.filename.clone())
+            .await;
+        if let Ok((id, _)) = &result {
+            if category.is_some() {
+                tm.set_category(id, category).await;
+            }
+            if let Some(size) = req.size {
+                tm.set_expected_size(id, size).await;
+            }
+            if !req.labels.is_empty() || req.note.is_some() {
+                tm.set_annotations(id, req.labels.clone(), req.note.clone())
+                    .await;
+            }
+            if let Some((name, template)) = &template {
+                tm.apply_template(id, name, template).await;
+            }
+            if let Some(after) = req.after.clone() {
+                if let Err(e) = tm.set_after(id, Some(after)).await {
+                    tracing::warn!("Could not set dependency of {}: {}", id, e);
+                }
+            }
+        }
+        result
     };
 
     let (transfer_id, _cancel_token) = match result {
@@ -318,17 +1225,328 @@ pub async fn xdcc_download(
         }
     };
 
-    Json(DownloadResponse {
-        transfer_id,
-        status: "paused".to_string(),
-    })
-    .into_response()
+    tracing::info!("Queued {} as transfer {}", url, transfer_id);
+    (
+        [(TRANSFER_ID, transfer_id.clone())],
+        Json(DownloadResponse {
+            transfer_id,
+            status: "paused".to_string(),
+            duplicate_of: None,
+        }),
+    )
+        .into_response()
+}
+
+/// Work out what a download request would do without queueing or
+/// connecting: the duplicate checks, network resolution, proxy, size limits,
+/// free space and place in the queue
+async fn plan_download(
+    state: &AppState,
+    url: &XdccUrl,
+    req: &DownloadRequest,
+    priority: TransferPriority,
+    category: Option<String>,
+    template: Option<String>,
+) -> DownloadPlan {
+    let mut problems = Vec::new();
+    let file_name = req
+        .filename
+        .as_deref()
+        .map(EnhancedTransferManager::strip_irc_codes);
+    let (duplicate_of, queue_position) = {
+        let tm = state.transfer_manager.read().await;
+        match tm.find_unfinished(url).await {
+            Some((id, _, _)) => {
+                let position = tm.get_transfer(&id).await.and_then(|t| t.queue_position);
+                (Some(id), position)
+            }
+            None => {
+                if let Err(e) = tm.check_new(url, file_name.as_deref()).await {
+                    problems.push(e);
+                }
+                (None, Some(tm.queue_position_for(priority).await))
+            }
+        }
+    };
+    // Size limits and postprocessing follow the category the name suggests when none is given
+    let category = category.or_else(|| file_name.as_deref().map(|f| classify(f).to_string()));
+
+    let config = state.config.read().await.clone();
+    let client_config = XdccConfig::from_app_config(&config, state.download_dir.clone(), &url.bot);
+    let resolved = client_config.resolve_network_source(&url.network);
+    if resolved.is_none() {
+        problems.push(format!(
+            "Network {} is not configured and there is no fallback host",
+            url.network
+        ));
+    }
+    let outbound = client_config.outbound();
+    if outbound.require_proxy && outbound.proxy_url.is_empty() {
+        problems.push("Proxy is required but not enabled".to_string());
+    }
+    if let Some(size) = req.size {
+        if let Err(e) = config.size_limits(category.as_deref()).check(size) {
+            problems.push(format!("Size limits: {}", e));
+        }
+    }
+    if let Some(quota) = config.monthly_quota_bytes() {
+        if state
+            .database
+            .month_traffic()
+            .is_ok_and(|used| used as u64 >= quota)
+        {
+            problems.push("Monthly traffic quota reached; the queue is held".to_string());
+        }
+    }
+
+    let download_dir = std::path::Path::new(&state.download_dir);
+    // The directory is created with the first download
+    let free_space = download_dir
+        .ancestors()
+        .find(|dir| dir.exists())
+        .and_then(|dir| crate::selftest::free_space(dir).ok());
+    if let (Some(free), Some(size)) = (free_space, req.size) {
+        if free < size {
+            let gb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+            problems.push(format!(
+                "Only {:.2} GB free, {:.2} GB needed",
+                gb(free),
+                gb(size)
+            ));
+        }
+    }
+    let destination = file_name.as_deref().map(|name| {
+        download_dir
+            .join(client_config.filename_policy.sanitize(name))
+            .to_string_lossy()
+            .to_string()
+    });
+    let move_dir =
+        PostprocessConfig::for_transfer(&config, category.as_deref(), template.as_deref())
+            .move_completed_dir;
+
+    let (host, port, ssl, network_source) = match resolved {
+        Some(((host, port, ssl, ..), source)) => {
+            (Some(host), Some(port), Some(ssl), Some(source.to_string()))
+        }
+        None => (None, None, None, None),
+    };
+    DownloadPlan {
+        dry_run: true,
+        ok: problems.is_empty(),
+        problems,
+        url: url.to_string(),
+        file_name,
+        priority,
+        category,
+        template,
+        duplicate_of,
+        host,
+        port,
+        ssl,
+        network_source,
+        proxy: (!outbound.proxy_url.is_empty()).then(|| mask_url_password(&outbound.proxy_url)),
+        download_dir: state.download_dir.clone(),
+        destination,
+        move_dir,
+        free_space,
+        queue_position,
+    }
+}
+
+/// Quick-add endpoint for browser protocol handlers and bookmarklets.
+/// Accepts irc://, botarr:// and web+botarr:// links, queues them and redirects to the UI.
+/// Being a GET link, any page could trigger it, so it always needs the API key.
+pub async fn xdcc_quick_add(
+    State(state): State<AppState>,
+    key: ApiKey,
+    Query(params): Query<QuickAddParams>,
+) -> impl IntoResponse {
+    {
+        let config = state.config.read().await;
+        if config.api_key.is_empty() {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    error: "Set an API key to use quick-add links".to_string(),
+                }),
+            )
+                .into_response();
+        }
+        if !config.api_key_is(key.as_deref()) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: "Invalid or missing API key".to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+
+    let raw = params.url.trim();
+    let normalized = match raw
+        .strip_prefix("web+botarr://")
+        .or_else(|| raw.strip_prefix("botarr://"))
+    {
+        Some(rest) => format!("irc://{}", rest),
+        None => raw.to_string(),
+    };
+
+    let url = match XdccUrl::parse(&normalized) {
+        Ok(u) => u,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let priority = match params.priority.as_deref() {
+        Some("low") => TransferPriority::Low,
+        Some("high") => TransferPriority::High,
+        Some("urgent") => TransferPriority::Urgent,
+        _ => TransferPriority::Normal,
+    };
+
+    let result = {
+        let tm = state.transfer_manager.write().await;
+        if let Some((id, _)) = tm.coalesce_request(&url, priority).await {
+            tracing::info!("Quick-add for {} attached to transfer {}", url, id);
+            return Redirect::to(&format!("/?tab=activities&transfer={}", id)).into_response();
+        }
+        let result = tm
+            .create_transfer(url, priority, false, params.filename.clone())
+            .await;
+        if let Ok((id, _)) = &result {
+            if let Some(category) = params.category.clone() {
+                tm.set_category(id, Some(category)).await;
+            }
+            if let Some(labels) = params.labels.as_deref() {
+                let labels = labels.split(',').map(str::to_string).collect();
+                tm.set_annotations(id, labels, None).await;
+            }
+        }
+        result
+    };
+
+    match result {
+        Ok((transfer_id, _)) => {
+            tracing::info!("Quick-add queued transfer {}", transfer_id);
+            Redirect::to(&format!("/?tab=activities&transfer={}", transfer_id)).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response(),
+    }
 }
 
-pub async fn xdcc_list_transfers(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn xdcc_list_transfers(
+    State(state): State<AppState>,
+    Query(params): Query<TransferListParams>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    let concurrency = state.config.read().await.queue_limit as usize;
     let tm = state.transfer_manager.read().await;
-    let transfers = tm.list_transfers().await;
-    Json(serde_json::json!({ "transfers": transfers }))
+    let mut transfers = tm.list_transfers().await;
+    let estimates = tm.queue_estimates(concurrency).await;
+    for t in &mut transfers {
+        t.estimated_start = estimates.get(&t.transfer.id).copied();
+    }
+    if let Some(label) = params.label.as_deref() {
+        transfers.retain(|t| {
+            t.transfer
+                .labels
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(label))
+        });
+    }
+    (
+        page.headers(transfers.len() as i64),
+        Json(serde_json::json!({ "transfers": page.slice(transfers) })),
+    )
+}
+
+/// Make a transfer wait for another one, or clear its dependency
+pub async fn xdcc_set_after(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    ApiJson(req): ApiJson<SetAfterRequest>,
+) -> impl IntoResponse {
+    let tm = state.transfer_manager.write().await;
+    if tm.get_transfer(&id).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Transfer not found".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    match tm.set_after(&id, req.after).await {
+        Ok(()) => Json(serde_json::json!({"status": "updated"})).into_response(),
+        Err(error) => (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response(),
+    }
+}
+
+/// Transfers grouped by their dependencies
+pub async fn xdcc_list_jobs(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    let tm = state.transfer_manager.read().await;
+    let jobs = tm.list_jobs().await;
+    (
+        page.headers(jobs.len() as i64),
+        Json(serde_json::json!({ "jobs": page.slice(jobs) })),
+    )
+}
+
+/// Gather the files of a completed job into one folder, whether or not
+/// packaging runs automatically
+pub async fn xdcc_package_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let package_config = PackageConfig::from_config(&*state.config.read().await);
+    let job = state.transfer_manager.read().await.job_of(&id).await;
+    let Some(job) = job.filter(|j| j.id == id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Job not found".to_string(),
+            }),
+        )
+            .into_response();
+    };
+    match package_and_record(&job, &package_config, &state.transfer_manager).await {
+        Ok(report) => Json(report).into_response(),
+        Err(error) => (StatusCode::CONFLICT, Json(ErrorResponse { error })).into_response(),
+    }
+}
+
+/// Replace the labels and note of a transfer or history record
+pub async fn xdcc_set_annotations(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    ApiJson(req): ApiJson<AnnotationsRequest>,
+) -> impl IntoResponse {
+    let tm = state.transfer_manager.write().await;
+    if tm.set_annotations(&id, req.labels, req.note).await {
+        Json(serde_json::json!({"status": "updated"})).into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Transfer not found".to_string(),
+            }),
+        )
+            .into_response()
+    }
 }
 
 pub async fn xdcc_get_transfer(
@@ -348,6 +1566,56 @@ pub async fn xdcc_get_transfer(
     }
 }
 
+/// DCC offers held for manual approval
+pub async fn xdcc_list_offers(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    let offers = state.transfer_manager.read().await.offers().list();
+    (
+        page.headers(offers.len() as i64),
+        Json(serde_json::json!({ "offers": page.slice(offers) })),
+    )
+}
+
+pub async fn xdcc_accept_offer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    decide_offer(&state, &id, true).await
+}
+
+pub async fn xdcc_reject_offer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    decide_offer(&state, &id, false).await
+}
+
+async fn decide_offer(state: &AppState, id: &str, accept: bool) -> axum::response::Response {
+    let tm = state.transfer_manager.read().await;
+    match tm.offers().decide(id, accept) {
+        Some(offer) => {
+            let status = if accept { "accepted" } else { "rejected" };
+            tracing::info!(
+                "DCC offer of {} from {} {}",
+                offer.filename,
+                offer.sender,
+                status
+            );
+            Json(serde_json::json!({ "status": status, "offer": offer })).into_response()
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Offer not found or no longer pending".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn xdcc_cancel_transfer(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -384,6 +1652,25 @@ pub async fn xdcc_retry_transfer(
     }
 }
 
+/// Pause a queued or running transfer, keeping its partial file
+pub async fn xdcc_pause_transfer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tm = state.transfer_manager.write().await;
+    if tm.pause_transfer(&id).await {
+        Json(serde_json::json!({"status": "paused", "transfer_id": id})).into_response()
+    } else {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Cannot pause transfer".to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
 pub async fn xdcc_resume_transfer(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -402,19 +1689,158 @@ pub async fn xdcc_resume_transfer(
     }
 }
 
+/// Move the file of a transfer paused by a disk error to
+/// `alternate_download_dir` and resume it there
+pub async fn xdcc_relocate_transfer(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let error =
+        |status: StatusCode, error: String| (status, Json(ErrorResponse { error })).into_response();
+    let (target, download_dir, policy) = {
+        let cfg = state.config.read().await;
+        (
+            cfg.alternate_download_dir.clone(),
+            cfg.download_dir.clone(),
+            FilenamePolicy::from_config(&cfg),
+        )
+    };
+    if target.is_empty() {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "No alternate_download_dir configured".to_string(),
+        );
+    }
+
+    let transfer = state.transfer_manager.read().await.get_transfer(&id).await;
+    let Some(t) = transfer else {
+        return error(StatusCode::NOT_FOUND, "Transfer not found".to_string());
+    };
+    if t.transfer.status != TransferStatus::Paused {
+        return error(
+            StatusCode::CONFLICT,
+            "Only paused transfers can be relocated".to_string(),
+        );
+    }
+
+    // Bring along what was downloaded so far, without holding the manager
+    // while the file is copied
+    let mut saved_as = None;
+    if let Some(name) = &t.transfer.filename {
+        let dir = t.download_dir.as_deref().unwrap_or(&download_dir);
+        let source = std::path::Path::new(dir).join(policy.sanitize(name));
+        if source.exists() {
+            match move_file(&source.to_string_lossy(), &target).await {
+                Ok(moved_to) => {
+                    saved_as = std::path::Path::new(&moved_to)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .filter(|n| *n != policy.sanitize(name));
+                }
+                Err(e) => {
+                    return error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Failed to move {}: {}", source.display(), e),
+                    );
+                }
+            }
+        }
+    }
+
+    let tm = state.transfer_manager.read().await;
+    if let Some(saved_as) = saved_as {
+        tm.set_filename(&id, saved_as).await;
+    }
+    match tm.relocate(&id, target.clone()).await {
+        Ok(()) => (
+            [(TRANSFER_ID, id.clone())],
+            Json(serde_json::json!({
+                "status": "resumed",
+                "transfer_id": id,
+                "download_dir": target,
+            })),
+        )
+            .into_response(),
+        Err(e) => error(StatusCode::CONFLICT, e),
+    }
+}
+
 pub async fn xdcc_get_transfer_logs(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
     let tm = state.transfer_manager.read().await;
-    let logs = tm.get_logs(&id).await;
-    Json(serde_json::json!({ "logs": logs })).into_response()
+    let events = tm.get_events(&id).await;
+    Json(serde_json::json!({
+        "logs": events.logs,
+        "speed_samples": events.speed_samples,
+    }))
+    .into_response()
+}
+
+/// Download the failure bundle of a transfer: the one saved when it failed,
+/// or one built from its current state (without IRC lines)
+pub async fn xdcc_get_transfer_bundle(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    let path = crate::bundle::bundle_path(&config, &id);
+
+    let bytes = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let (transfer, events) = {
+                let tm = state.transfer_manager.read().await;
+                (tm.get_transfer(&id).await, tm.get_logs(&id).await)
+            };
+            let Some(transfer) = transfer else {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(ErrorResponse {
+                        error: "Transfer not found".to_string(),
+                    }),
+                )
+                    .into_response();
+            };
+            match crate::bundle::build_bundle(&crate::bundle::BundleInput {
+                transfer: &transfer.transfer,
+                retry_count: transfer.retry_count,
+                events: &events,
+                irc: &[],
+                config: &config,
+            }) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ErrorResponse {
+                            error: format!("Failed to build bundle: {}", e),
+                        }),
+                    )
+                        .into_response()
+                }
+            }
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"botarr-{}.zip\"", id),
+            ),
+        ],
+        bytes,
+    )
+        .into_response()
 }
 
 pub async fn xdcc_set_priority(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    Json(req): Json<SetPriorityRequest>,
+    ApiJson(req): ApiJson<SetPriorityRequest>,
 ) -> impl IntoResponse {
     let priority = match req.priority.as_str() {
         "low" => TransferPriority::Low,
@@ -437,10 +1863,45 @@ pub async fn xdcc_set_priority(
     }
 }
 
-pub async fn xdcc_bot_stats(State(state): State<AppState>) -> impl IntoResponse {
+/// Set or clear a transfer's own speed limit, also while it downloads
+pub async fn xdcc_set_speed_limit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    ApiJson(req): ApiJson<SpeedLimitRequest>,
+) -> impl IntoResponse {
+    let updated = state
+        .transfer_manager
+        .read()
+        .await
+        .set_speed_limit(&id, req.max_speed_kbps)
+        .await;
+    let Some(transfer) = updated else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Transfer not found".to_string(),
+            }),
+        )
+            .into_response();
+    };
+    let kbps = speed_limit_kbps(&*state.config.read().await, &transfer);
+    transfer.limiter.set_rate(kbps * 1024);
+    Json(serde_json::json!({"status": "updated", "max_speed_kbps": kbps})).into_response()
+}
+
+/// Bot statistics, most reliable first; `networks` covers all bots
+pub async fn xdcc_bot_stats(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
     let tm = state.transfer_manager.read().await;
     let stats = tm.get_all_bot_stats().await;
-    Json(serde_json::json!({ "bots": stats }))
+    let networks = crate::xdcc::transfer::network_hours(&stats);
+    (
+        page.headers(stats.len() as i64),
+        Json(serde_json::json!({ "bots": page.slice(stats), "networks": networks })),
+    )
 }
 
 pub async fn xdcc_analytics(State(state): State<AppState>) -> impl IntoResponse {
@@ -449,6 +1910,34 @@ pub async fn xdcc_analytics(State(state): State<AppState>) -> impl IntoResponse
     Json(analytics)
 }
 
+/// Daily DCC traffic per network and usage of the monthly quota
+pub async fn xdcc_traffic(
+    State(state): State<AppState>,
+    Query(params): Query<TrafficParams>,
+) -> impl IntoResponse {
+    let quota_bytes = state.config.read().await.monthly_quota_bytes();
+    let traffic = state
+        .database
+        .list_traffic(params.days.clamp(1, 366))
+        .and_then(|days| Ok((days, state.database.month_traffic()?)));
+    match traffic {
+        Ok((days, month_bytes)) => Json(serde_json::json!({
+            "days": days,
+            "month_bytes": month_bytes,
+            "quota_bytes": quota_bytes,
+            "quota_exceeded": quota_bytes.is_some_and(|q| month_bytes as u64 >= q),
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn xdcc_queue_status(State(state): State<AppState>) -> impl IntoResponse {
     let tm = state.transfer_manager.read().await;
     let queue_size = tm.queue_size().await;
@@ -457,3 +1946,52 @@ pub async fn xdcc_queue_status(State(state): State<AppState>) -> impl IntoRespon
         "status": "ok"
     }))
 }
+
+/// Cancel every queued, paused and running transfer; has to be confirmed
+pub async fn xdcc_cancel_queue(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tm = state.transfer_manager.read().await;
+    let unfinished: Vec<_> = tm
+        .list_transfers()
+        .await
+        .into_iter()
+        .filter(|t| {
+            !matches!(
+                t.transfer.status,
+                TransferStatus::Completed
+                    | TransferStatus::Failed
+                    | TransferStatus::Cancelled
+                    | TransferStatus::Quarantined
+            )
+        })
+        .collect();
+    if unfinished.is_empty() {
+        return Json(serde_json::json!({"status": "ok", "cancelled": 0})).into_response();
+    }
+
+    let ids: Vec<String> = unfinished.iter().map(|t| t.transfer.id.clone()).collect();
+    let summary = serde_json::json!({
+        "transfers": ids.len(),
+        "downloaded_bytes": unfinished.iter().map(|t| t.transfer.downloaded).sum::<u64>(),
+    });
+    let operation = confirm::operation("queue_cancel", &ids);
+    if let Err(refused) = state.confirmations.check(&headers, &operation, summary) {
+        return refused;
+    }
+
+    let mut cancelled = 0;
+    for id in &ids {
+        if tm.cancel_transfer(id).await {
+            cancelled += 1;
+        }
+    }
+    tracing::info!("Cancelled the whole queue ({} transfers)", cancelled);
+    Json(serde_json::json!({"status": "ok", "cancelled": cancelled})).into_response()
+}
+
+pub async fn xdcc_summary(State(state): State<AppState>) -> impl IntoResponse {
+    let tm = state.transfer_manager.read().await;
+    Json(tm.get_summary().await)
+}
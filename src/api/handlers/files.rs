@@ -0,0 +1,201 @@
+use crate::api::auth::ApiKey;
+use crate::api::models::ErrorResponse;
+use crate::filename::saved_path;
+use crate::postprocess::{MoveTarget, PostprocessConfig};
+use crate::range::{self, RangeRequest};
+use crate::xdcc::TransferStatus;
+use crate::AppState;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Size of the chunks a file is streamed in
+const CHUNK: u64 = 64 * 1024;
+
+fn error(status: StatusCode, error: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// Places the file of a completed download may be, most likely first
+async fn file_candidates(state: &AppState, id: &str) -> Result<Vec<PathBuf>, Response> {
+    let download_dir = std::path::Path::new(&state.download_dir);
+    if let Some(t) = state.transfer_manager.read().await.get_transfer(id).await {
+        if t.transfer.status != TransferStatus::Completed {
+            return Err(error(StatusCode::CONFLICT, "Transfer hasn't completed"));
+        }
+        let mut candidates: Vec<PathBuf> = t.transfer.saved_to.iter().map(PathBuf::from).collect();
+        candidates.extend(t.transfer.filename.map(|f| saved_path(download_dir, &f)));
+        return Ok(candidates);
+    }
+
+    let record = match state.database.get_download(id) {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err(error(StatusCode::NOT_FOUND, "Download not found")),
+        Err(e) => {
+            return Err(error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Database error: {}", e),
+            ))
+        }
+    };
+    if !record.status.eq_ignore_ascii_case("completed") {
+        return Err(error(StatusCode::CONFLICT, "Download hasn't completed"));
+    }
+    let Some(file_name) = record.file_name else {
+        return Ok(Vec::new());
+    };
+    let mut candidates = vec![saved_path(download_dir, &file_name)];
+    // Moved by postprocessing to its category's or template's directory
+    let move_dir = {
+        let config = state.config.read().await;
+        PostprocessConfig::for_transfer(
+            &config,
+            record.category.as_deref(),
+            record.template.as_deref(),
+        )
+        .move_completed_dir
+    };
+    if let Some(MoveTarget::Local(dir)) = move_dir.as_deref().map(MoveTarget::parse) {
+        candidates.push(saved_path(std::path::Path::new(&dir), &file_name));
+    }
+    Ok(candidates)
+}
+
+/// `Content-Disposition` offering the file under its own name
+fn attachment(path: &std::path::Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "download".to_string());
+    let ascii: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii,
+        urlencoding::encode(&name)
+    )
+}
+
+/// Stream the file of a completed download, honouring `Range` requests.
+/// Needs the API key, so it is refused while none is set.
+pub async fn download_file(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    key: ApiKey,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    {
+        let config = state.config.read().await;
+        if config.api_key.is_empty() {
+            return error(
+                StatusCode::FORBIDDEN,
+                "Set an API key to download files over the API",
+            );
+        }
+        if !config.api_key_is(key.as_deref()) {
+            return error(StatusCode::UNAUTHORIZED, "Invalid or missing API key");
+        }
+    }
+
+    let candidates = match file_candidates(&state, &id).await {
+        Ok(candidates) => candidates,
+        Err(response) => return response,
+    };
+    let Some(path) = candidates.into_iter().find(|p| p.is_file()) else {
+        return error(StatusCode::NOT_FOUND, "The file is no longer on disk");
+    };
+    let mut file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Cannot open the file: {}", e),
+            )
+        }
+    };
+    let total = match file.metadata().await {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Cannot read the file: {}", e),
+            )
+        }
+    };
+
+    let requested = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    let (status, start, length, content_range) = match range::parse(requested, total) {
+        RangeRequest::Full => (StatusCode::OK, 0, total, None),
+        RangeRequest::Partial(r) => (
+            StatusCode::PARTIAL_CONTENT,
+            r.start,
+            r.length(),
+            Some(r.content_range(total)),
+        ),
+        RangeRequest::Unsatisfiable => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", total))],
+            )
+                .into_response()
+        }
+    };
+    if start > 0 {
+        if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Cannot read the file: {}", e),
+            );
+        }
+    }
+    tracing::info!("Serving {} of {} ({} bytes)", id, path.display(), length);
+
+    let stream = futures::stream::unfold((file, length), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; CHUNK.min(remaining) as usize];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), (file, remaining - n as u64)))
+            }
+            Err(e) => Some((Err(e), (file, 0))),
+        }
+    });
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_DISPOSITION, attachment(&path));
+    if let Some(content_range) = content_range {
+        response = response.header(header::CONTENT_RANGE, content_range);
+    }
+    response
+        .body(Body::from_stream(stream))
+        .unwrap_or_else(|e| error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
@@ -1,8 +1,18 @@
+use crate::api::extract::ApiJson;
 use crate::api::models::*;
+use crate::api::pagination::{PageParams, MAX_LIMIT};
+use crate::confirm;
+use crate::db::PaginatedResponse;
+use crate::postprocess::{move_file, run_postprocess, PostprocessConfig};
+use crate::quarantine::quarantined_path;
+use crate::release::parse_release;
+use crate::request_id::TRANSFER_ID;
+use crate::thumbnail::thumbnail_path;
+use crate::xdcc::{TransferPriority, TransferStatus, XdccUrl};
 use crate::AppState;
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -11,12 +21,51 @@ use axum::{
 pub async fn xdcc_history(
     State(state): State<AppState>,
     Query(params): Query<HistoryRequest>,
+    Query(page): Query<PageParams>,
 ) -> impl IntoResponse {
+    let (tmdb_api_key, thumbnail_dir) = {
+        let config = state.config.read().await;
+        (config.tmdb_api_key.clone(), config.thumbnail_dir.clone())
+    };
+    let page = page.page(100);
+
     match state
         .database
-        .list_downloads(params.page, params.limit as i64)
+        .list_downloads(page.page, page.limit, params.label.as_deref())
     {
-        Ok(history) => Json(history).into_response(),
+        Ok(history) => {
+            let mut items = Vec::with_capacity(history.items.len());
+            for record in history.items {
+                let metadata = match record.file_name.as_deref() {
+                    Some(name) if !tmdb_api_key.is_empty() => {
+                        state
+                            .metadata
+                            .resolve(&tmdb_api_key, &parse_release(name))
+                            .await
+                    }
+                    _ => None,
+                };
+                let thumbnail = tokio::fs::metadata(thumbnail_path(&thumbnail_dir, &record.id))
+                    .await
+                    .is_ok_and(|m| m.is_file());
+                items.push(HistoryItem {
+                    record,
+                    metadata,
+                    thumbnail,
+                });
+            }
+            (
+                page.headers(history.total),
+                Json(PaginatedResponse {
+                    items,
+                    total: history.total,
+                    page: history.page,
+                    limit: history.limit,
+                    total_pages: history.total_pages,
+                }),
+            )
+                .into_response()
+        }
         Err(e) => {
             tracing::error!("Failed to fetch download history: {}", e);
             (
@@ -82,17 +131,31 @@ pub async fn xdcc_clear_history(State(state): State<AppState>) -> impl IntoRespo
     }
 }
 
-/// Bulk delete download history
+/// Bulk delete download history; deleting the files has to be confirmed
 pub async fn xdcc_bulk_delete_history(
     State(state): State<AppState>,
-    Json(req): Json<BulkDeleteRequest>,
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<BulkDeleteRequest>,
 ) -> impl IntoResponse {
+    if req.delete_files {
+        let bytes: i64 = req
+            .ids
+            .iter()
+            .filter_map(|id| state.database.get_download(id).ok().flatten())
+            .filter_map(|record| record.size)
+            .sum();
+        let operation = confirm::operation("history_delete_files", &req.ids);
+        let summary = serde_json::json!({ "items": req.ids.len(), "bytes": bytes });
+        if let Err(refused) = state.confirmations.check(&headers, &operation, summary) {
+            return refused;
+        }
+    }
+
     let tm = state.transfer_manager.write().await;
     let mut deleted = 0;
 
     for id in &req.ids {
         if tm.delete_history_item(id, req.delete_files).await {
-            let _ = state.database.delete_download(id);
             deleted += 1;
         }
     }
@@ -101,15 +164,513 @@ pub async fn xdcc_bulk_delete_history(
         "status": "ok",
         "deleted": deleted
     }))
+    .into_response()
+}
+
+/// Queue a history item again with its original category and priority
+pub async fn xdcc_redownload(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<RedownloadParams>,
+) -> impl IntoResponse {
+    let record = match state.database.get_download(&id) {
+        Ok(Some(record)) => record,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "History item not found".to_string(),
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let original = XdccUrl {
+        network: record.network.clone(),
+        channel: record.channel.clone(),
+        bot: record.bot.clone(),
+        slot: record.slot,
+    };
+    let mut url = original.clone();
+    if let (true, Some(filename)) = (params.resolve, record.file_name.as_deref()) {
+        match state
+            .search_aggregator
+            .find_source(&original, filename)
+            .await
+        {
+            Ok(Some(found)) => url = found,
+            Ok(None) => tracing::warn!(
+                "No current source found for {}, using the original pack",
+                filename
+            ),
+            Err(e) => tracing::warn!("Search for {} failed: {}", filename, e),
+        }
+    }
+
+    let priority = match record.priority.as_str() {
+        "low" => TransferPriority::Low,
+        "high" => TransferPriority::High,
+        "urgent" => TransferPriority::Urgent,
+        _ => TransferPriority::Normal,
+    };
+
+    let tm = state.transfer_manager.write().await;
+    if let Some((existing, status)) = tm.coalesce_request(&url, priority).await {
+        return (
+            [(TRANSFER_ID, existing.clone())],
+            Json(DownloadResponse {
+                transfer_id: existing.clone(),
+                status: format!("{:?}", status).to_lowercase(),
+                duplicate_of: Some(existing),
+            }),
+        )
+            .into_response();
+    }
+    let (transfer_id, _) = tm
+        .redownload(url.clone(), priority, record.file_name.clone())
+        .await;
+    // The category picks the postprocess destination
+    if record.category.is_some() {
+        tm.set_category(&transfer_id, record.category.clone()).await;
+    }
+    if let Some(size) = record.size {
+        tm.set_expected_size(&transfer_id, size as u64).await;
+    }
+    tracing::info!(
+        "Re-downloading history item {} from {} as {}",
+        id,
+        url,
+        transfer_id
+    );
+
+    (
+        [(TRANSFER_ID, transfer_id.clone())],
+        Json(serde_json::json!({
+            "transfer_id": transfer_id,
+            "status": "pending",
+            "url": url.to_string(),
+            "resolved": url != original,
+        })),
+    )
+        .into_response()
+}
+
+/// Thumbnail of a completed video download
+pub async fn xdcc_history_thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let path = thumbnail_path(&state.config.read().await.thumbnail_dir, &id);
+    match tokio::fs::read(&path).await {
+        Ok(image) => (
+            [
+                (header::CONTENT_TYPE, "image/jpeg"),
+                (header::CACHE_CONTROL, "private, max-age=86400"),
+            ],
+            image,
+        )
+            .into_response(),
+        Err(_) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "No thumbnail for this download".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// List history items in the trash
+pub async fn xdcc_trash(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    match state.database.list_trash() {
+        Ok(items) => (page.headers(items.len() as i64), Json(page.slice(items))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Restore a history item from the trash
+pub async fn xdcc_restore_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let tm = state.transfer_manager.write().await;
+    match tm.restore_history_item(&id).await {
+        Ok(()) => Json(serde_json::json!({"status": "restored"})).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response(),
+    }
+}
+
+/// Permanently delete everything in the trash
+pub async fn xdcc_empty_trash(State(state): State<AppState>) -> impl IntoResponse {
+    match state.database.purge_trash(None) {
+        Ok(paths) => {
+            remove_trashed_files(&paths).await;
+            Json(serde_json::json!({"status": "emptied", "files": paths.len()})).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Delete files of purged trash entries
+pub async fn remove_trashed_files(paths: &[String]) {
+    for path in paths {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            tracing::warn!("Failed to delete trashed file {}: {}", path, e);
+        }
+    }
+}
+
+/// List quarantined downloads
+pub async fn xdcc_quarantine(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    match state.database.list_downloads_by_status("Quarantined") {
+        Ok(items) => (page.headers(items.len() as i64), Json(page.slice(items))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Load a quarantined download and the path of its file
+fn quarantined_record(
+    state: &AppState,
+    config: &crate::config::AppConfig,
+    id: &str,
+) -> Result<(crate::db::DownloadRecord, std::path::PathBuf), (StatusCode, Json<ErrorResponse>)> {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Quarantined download not found".to_string(),
+            }),
+        )
+    };
+    let record = state
+        .database
+        .get_download(id)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+        })?
+        .filter(|r| r.status == "Quarantined")
+        .ok_or_else(not_found)?;
+    let path = quarantined_path(config, record.file_name.as_deref().ok_or_else(not_found)?);
+    Ok((record, path))
+}
+
+/// Release a quarantined download: move it back to the download directory,
+/// mark it completed and run postprocessing
+pub async fn xdcc_release_quarantine(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    let (record, path) = match quarantined_record(&state, &config, &id) {
+        Ok(found) => found,
+        Err(e) => return e.into_response(),
+    };
+
+    let released = match move_file(&path.to_string_lossy(), &config.download_dir).await {
+        Ok(released) => released,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to release {}: {}", path.display(), e),
+                }),
+            )
+                .into_response()
+        }
+    };
+    tracing::info!("Released {} from quarantine", released);
+
+    let transfer_manager = state.transfer_manager.clone();
+    {
+        let tm = transfer_manager.read().await;
+        tm.resolve_quarantine(&id, TransferStatus::Completed, None)
+            .await;
+        tm.add_log(&id, format!("Released from quarantine to {}", released))
+            .await;
+    }
+
+    let pp_config = PostprocessConfig::for_transfer(
+        &config,
+        record.category.as_deref(),
+        record.template.as_deref(),
+    );
+    if !pp_config.is_empty() {
+        let file = released.clone();
+        tokio::spawn(async move {
+            let result = run_postprocess(&file, &pp_config).await;
+            transfer_manager
+                .read()
+                .await
+                .set_postprocess(&id, result.steps)
+                .await;
+        });
+    }
+
+    Json(serde_json::json!({"status": "released", "path": released})).into_response()
+}
+
+/// Partial files in the resume index that are still on disk, with their
+/// current size. Entries whose file is gone are dropped.
+pub async fn xdcc_partials(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(MAX_LIMIT);
+    let partials = match state.database.list_partial_files() {
+        Ok(partials) => partials,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let mut items = Vec::with_capacity(partials.len());
+    for partial in partials {
+        // Stale entries are left for the prune task to drop
+        if let Some(on_disk) = partial_on_disk(&partial).await {
+            let mut value = serde_json::to_value(&partial).unwrap_or_default();
+            value["on_disk"] = on_disk.into();
+            items.push(value);
+        }
+    }
+    (page.headers(items.len() as i64), Json(page.slice(items))).into_response()
+}
+
+/// Bytes of a partial file on disk, unless the file is gone or was finished
+/// by something else
+async fn partial_on_disk(partial: &crate::db::PartialFile) -> Option<u64> {
+    tokio::fs::metadata(&partial.path)
+        .await
+        .ok()
+        .map(|meta| meta.len())
+        .filter(|&len| len < partial.size)
+}
+
+/// Forget partial files that are gone or complete, returning how many
+pub async fn prune_partials(database: &crate::db::Database) -> usize {
+    let partials = match database.list_partial_files() {
+        Ok(partials) => partials,
+        Err(e) => {
+            tracing::warn!("Failed to list partial files: {}", e);
+            return 0;
+        }
+    };
+    let mut removed = 0;
+    for partial in partials {
+        if partial_on_disk(&partial).await.is_none()
+            && database.delete_partial_file(partial.id).unwrap_or(false)
+        {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Queue a partial file again, resuming from what is on disk
+pub async fn xdcc_resume_partial(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let partial = match state.database.get_partial_file(id) {
+        Ok(Some(partial)) => partial,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Partial file not found".to_string(),
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    };
+    let url = match XdccUrl::parse(&partial.url) {
+        Ok(url) => url,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Invalid recorded URL: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let tm = state.transfer_manager.write().await;
+    if let Some((existing, status)) = tm.coalesce_request(&url, TransferPriority::Normal).await {
+        return (
+            [(TRANSFER_ID, existing.clone())],
+            Json(DownloadResponse {
+                transfer_id: existing.clone(),
+                status: format!("{:?}", status).to_lowercase(),
+                duplicate_of: Some(existing),
+            }),
+        )
+            .into_response();
+    }
+    let (transfer_id, _) = tm
+        .redownload(
+            url,
+            TransferPriority::Normal,
+            Some(partial.file_name.clone()),
+        )
+        .await;
+    tm.set_expected_size(&transfer_id, partial.size).await;
+    tracing::info!("Resuming partial file {} as {}", partial.path, transfer_id);
+
+    (
+        [(TRANSFER_ID, transfer_id.clone())],
+        Json(DownloadResponse {
+            transfer_id,
+            status: "pending".to_string(),
+            duplicate_of: None,
+        }),
+    )
+        .into_response()
+}
+
+/// Drop a partial file from the resume index, leaving the file alone
+pub async fn xdcc_forget_partial(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.database.delete_partial_file(id) {
+        Ok(true) => Json(serde_json::json!({ "status": "ok" })).into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Partial file not found".to_string(),
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Purge a quarantined download: delete its file and mark it failed
+pub async fn xdcc_purge_quarantine(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    let (record, path) = match quarantined_record(&state, &config, &id) {
+        Ok(found) => found,
+        Err(e) => return e.into_response(),
+    };
+
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => tracing::info!("Purged {} from quarantine", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to delete {}: {}", path.display(), e),
+                }),
+            )
+                .into_response()
+        }
+    }
+
+    let error = format!(
+        "Purged from quarantine: {}",
+        record.error.unwrap_or_default()
+    );
+    state
+        .transfer_manager
+        .read()
+        .await
+        .resolve_quarantine(&id, TransferStatus::Failed, Some(error))
+        .await;
+
+    Json(serde_json::json!({"status": "purged"})).into_response()
 }
 
 /// Get search history with pagination
 pub async fn xdcc_search_history(
     State(state): State<AppState>,
-    Query(params): Query<PaginationParams>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(20);
+    match state.database.list_searches(page.page, page.limit) {
+        Ok(response) => (page.headers(response.total), Json(response)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Most frequently searched queries
+pub async fn xdcc_search_history_stats(
+    State(state): State<AppState>,
+    Query(params): Query<TopQueriesParams>,
 ) -> impl IntoResponse {
-    match state.database.list_searches(params.page, params.limit) {
-        Ok(response) => Json(response).into_response(),
+    match state.database.top_queries(params.limit.clamp(1, 100)) {
+        Ok(queries) => Json(serde_json::json!({ "top_queries": queries })).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -165,7 +726,7 @@ pub async fn xdcc_delete_search_history(
 /// Bulk delete search history
 pub async fn xdcc_bulk_delete_search_history(
     State(state): State<AppState>,
-    Json(req): Json<BulkDeleteSearchRequest>,
+    ApiJson(req): ApiJson<BulkDeleteSearchRequest>,
 ) -> impl IntoResponse {
     match state.database.bulk_delete_searches(&req.ids) {
         Ok(deleted) => Json(serde_json::json!({
@@ -182,3 +743,37 @@ pub async fn xdcc_bulk_delete_search_history(
             .into_response(),
     }
 }
+
+/// Export download history, bot stats and analytics, optionally redacted
+pub async fn xdcc_export_analytics(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let records = match state
+        .database
+        .list_downloads(1, params.limit.clamp(1, 100_000), None)
+    {
+        Ok(history) => history.items,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let (bots, analytics) = {
+        let tm = state.transfer_manager.read().await;
+        (tm.get_all_bot_stats().await, tm.get_analytics().await)
+    };
+    Json(crate::export::build_export(
+        records,
+        bots,
+        analytics,
+        params.redact,
+    ))
+    .into_response()
+}
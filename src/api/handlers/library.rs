@@ -0,0 +1,156 @@
+use crate::api::extract::ApiJson;
+use crate::api::models::*;
+use crate::library::{self, EpisodeNumber, NamingScheme};
+use crate::plugin::hooks::ScriptHooks;
+use crate::xdcc::TransferPriority;
+use crate::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+/// Upper bound on searches issued by a single auto-grab request
+const MAX_AUTO_GRAB: usize = 25;
+
+fn error(status: StatusCode, error: String) -> axum::response::Response {
+    (status, Json(ErrorResponse { error })).into_response()
+}
+
+/// Find missing episodes of a series in a library folder, optionally adding
+/// watchlist filters for them or grabbing them right away
+pub async fn find_library_gaps(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<LibraryGapsRequest>,
+) -> impl IntoResponse {
+    let scheme = match req.pattern.as_deref() {
+        Some(p) if !p.is_empty() => match NamingScheme::custom(p) {
+            Ok(s) => s,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        },
+        _ => NamingScheme::default(),
+    };
+
+    if req.episodes_per_season.len() > library::MAX_SEASONS
+        || req
+            .episodes_per_season
+            .values()
+            .any(|&n| n > library::MAX_EPISODES_PER_SEASON)
+    {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "episodes_per_season allows up to {} seasons of up to {} episodes",
+                library::MAX_SEASONS,
+                library::MAX_EPISODES_PER_SEASON
+            ),
+        );
+    }
+
+    let root = std::path::Path::new(&req.path);
+    if !root.is_dir() {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("Library path not found: {}", req.path),
+        );
+    }
+
+    let found = match library::scan_episodes(root, &scheme) {
+        Ok(f) => f,
+        Err(e) => {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to scan library: {}", e),
+            )
+        }
+    };
+    let missing = library::find_gaps(&found, &req.episodes_per_season);
+
+    let mut watchlist_added = 0;
+    if req.create_watchlist && !missing.is_empty() {
+        let filters = missing
+            .iter()
+            .map(|ep| {
+                library::watchlist_filter(&req.series, *ep, req.network.as_deref(), &req.channels)
+            })
+            .collect();
+        match library::add_watchlist_filters(filters) {
+            Ok(added) => {
+                watchlist_added = added;
+                if added > 0 {
+                    state.plugin_manager.emit_signal(
+                        "config_changed",
+                        crate::plugin::EventData::String("autodl.lua".to_string()),
+                    );
+                }
+            }
+            Err(e) => {
+                return error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to update watchlist: {}", e),
+                )
+            }
+        }
+    }
+
+    let mut queued = Vec::new();
+    if req.auto_grab {
+        for ep in missing.iter().take(MAX_AUTO_GRAB) {
+            if let Some(id) = grab_episode(&state, &req.series, &scheme, *ep).await {
+                queued.push(id);
+            }
+        }
+    }
+
+    Json(LibraryGapsResponse {
+        series: req.series,
+        found: found.values().map(|s| s.len()).sum(),
+        missing,
+        watchlist_added,
+        queued,
+    })
+    .into_response()
+}
+
+/// Search for a single episode and queue the most downloaded matching pack
+async fn grab_episode(
+    state: &AppState,
+    series: &str,
+    scheme: &NamingScheme,
+    ep: EpisodeNumber,
+) -> Option<String> {
+    let query = format!("{} {}", series, ep);
+    let results = match state.search_aggregator.search(&query, None).await {
+        Ok(r) => {
+            let hooks = ScriptHooks::from_config(&*state.config.read().await);
+            hooks.filter_search_results(&query, r).await
+        }
+        Err(e) => {
+            tracing::warn!("Gap search for '{}' failed: {}", query, e);
+            return None;
+        }
+    };
+
+    let best = results
+        .into_iter()
+        .filter(|r| scheme.parse(&r.filename) == Some(ep))
+        .max_by_key(|r| r.gets.unwrap_or(0))?;
+
+    if crate::arr::already_in_library(&state.config, &state.arr_library, Some(&best.filename)).await
+    {
+        return None;
+    }
+
+    let tm = state.transfer_manager.read().await;
+    match tm
+        .create_transfer(
+            best.url,
+            TransferPriority::Normal,
+            false,
+            Some(best.filename),
+        )
+        .await
+    {
+        Ok((id, _)) => Some(id),
+        Err(e) => {
+            tracing::warn!("Failed to queue gap fill for '{}': {}", query, e);
+            None
+        }
+    }
+}
@@ -1,4 +1,10 @@
 pub mod downloads;
+pub mod files;
 pub mod history;
+pub mod library;
+pub mod packs;
+pub mod sabnzbd;
 pub mod settings;
 pub mod system;
+pub mod torznab;
+pub mod watchlist;
@@ -0,0 +1,144 @@
+use crate::api::extract::ApiJson;
+use crate::api::models::*;
+use crate::api::pagination::PageParams;
+use crate::xdcc::packlist;
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+/// List imported packs
+pub async fn list_packs(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(100);
+    match state.database.list_packs(page.page, page.limit) {
+        Ok(packs) => (page.headers(packs.total), Json(packs)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Fetch a bot's pack list from a URL and store it for local search
+pub async fn import_packlist_url(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<PackImportUrlRequest>,
+) -> impl IntoResponse {
+    let parsed = match packlist::fetch_packlist(&req.url).await {
+        Ok(p) => p,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    store_packlist(
+        &state,
+        &req.network,
+        &req.channel,
+        req.bot,
+        &req.url,
+        parsed,
+    )
+}
+
+/// Import a pack list sent as the request body (pasted or uploaded iroffer
+/// text or HTML), for bots whose lists aren't published anywhere
+pub async fn import_packlist(
+    State(state): State<AppState>,
+    Query(params): Query<PackImportParams>,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    let body = packlist::decode(&body);
+    if body.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Pack list is empty".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let parsed = packlist::parse_packlist(&body);
+    store_packlist(
+        &state,
+        &params.network,
+        &params.channel,
+        params.bot,
+        "upload",
+        parsed,
+    )
+}
+
+/// Replace a bot's stored packs with a parsed pack list
+fn store_packlist(
+    state: &AppState,
+    network: &str,
+    channel: &str,
+    bot: Option<String>,
+    source: &str,
+    parsed: packlist::ParsedPacklist,
+) -> axum::response::Response {
+    let Some(bot) = bot.filter(|b| !b.is_empty()).or(parsed.bot) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Bot name not found in pack list; please specify it".to_string(),
+            }),
+        )
+            .into_response();
+    };
+
+    if parsed.packs.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                error: "No packs found in the pack list".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    match state
+        .database
+        .replace_packs(network, channel, &bot, source, &parsed.packs)
+    {
+        Ok(imported) => {
+            tracing::info!(
+                "Imported {} packs for {} on {} from {}",
+                imported,
+                bot,
+                network,
+                source
+            );
+            Json(serde_json::json!({
+                "status": "imported",
+                "bot": bot,
+                "imported": imported
+            }))
+            .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
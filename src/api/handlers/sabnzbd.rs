@@ -0,0 +1,329 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::api::auth::ApiKey;
+use crate::api::models::SabnzbdParams;
+use crate::config::AppConfig;
+use crate::confirm;
+use crate::filename::saved_path;
+use crate::postprocess::PostprocessConfig;
+use crate::sabnzbd::{self, NzbRelease};
+use crate::xdcc::{TransferStatus, XdccUrl};
+use crate::AppState;
+
+/// Categories every instance offers, next to the configured ones
+const CATEGORIES: &[&str] = &["tv", "anime", "movie", "music", "other"];
+
+fn sab_error(error: impl Into<String>) -> Response {
+    Json(json!({ "status": false, "error": error.into() })).into_response()
+}
+
+fn categories(config: &AppConfig) -> Vec<String> {
+    let mut names: Vec<String> = CATEGORIES.iter().map(|c| c.to_string()).collect();
+    let mut configured: Vec<&String> = config.categories.keys().collect();
+    configured.sort();
+    for name in configured {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+/// Where a completed download of `category` is kept
+fn storage_dir(config: &AppConfig, category: Option<&str>, template: Option<&str>) -> String {
+    PostprocessConfig::for_transfer(config, category, template)
+        .move_completed_dir
+        .filter(|d| !d.starts_with("rclone:"))
+        .unwrap_or_else(|| config.download_dir.clone())
+}
+
+/// SABnzbd-compatible API, so Sonarr and Radarr can use Botarr as their
+/// SABnzbd download client
+pub async fn sabnzbd_api(
+    State(state): State<AppState>,
+    key: ApiKey,
+    headers: HeaderMap,
+    Query(params): Query<SabnzbdParams>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    if !config.api_key_matches(key.as_deref()) {
+        return sab_error("API Key Incorrect");
+    }
+
+    match params.mode.as_str() {
+        "version" => Json(json!({ "version": sabnzbd::VERSION })).into_response(),
+        "fullstatus" => Json(json!({
+            "status": {
+                "version": sabnzbd::VERSION,
+                "completedir": config.completed_dir(),
+            }
+        }))
+        .into_response(),
+        "get_cats" => {
+            let mut names = vec!["*".to_string()];
+            names.extend(categories(&config));
+            Json(json!({ "categories": names })).into_response()
+        }
+        "get_config" => get_config(&config),
+        "addurl" => {
+            let name = params.name.clone().unwrap_or_default();
+            let release = release_from_url(&name, params.nzbname.as_deref());
+            add(&state, release, &params).await
+        }
+        "addfile" | "addlocalfile" => {
+            let release = sabnzbd::parse_nzb(&String::from_utf8_lossy(&body));
+            add(&state, release, &params).await
+        }
+        "queue" => match params.name.as_deref() {
+            Some("delete") => {
+                let tm = state.transfer_manager.read().await;
+                let mut removed = Vec::new();
+                for id in params.value.as_deref().unwrap_or_default().split(',') {
+                    if tm.cancel_transfer(id.trim()).await {
+                        removed.push(id.trim().to_string());
+                    }
+                }
+                Json(json!({ "status": true, "nzo_ids": removed })).into_response()
+            }
+            _ => queue(&state).await,
+        },
+        "history" => match params.name.as_deref() {
+            Some("delete") => {
+                let ids: Vec<String> = params
+                    .value
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|id| id.trim().to_string())
+                    .collect();
+                // Deleting files is in the lockdown `files` group, checked by
+                // its middleware, and has to be confirmed like a bulk delete
+                let delete_files = params.del_files == Some(1);
+                if delete_files {
+                    let bytes: i64 = ids
+                        .iter()
+                        .filter_map(|id| state.database.get_download(id).ok().flatten())
+                        .filter_map(|record| record.size)
+                        .sum();
+                    let operation = confirm::operation("history_delete_files", &ids);
+                    let summary = json!({ "items": ids.len(), "bytes": bytes });
+                    if let Err(refused) = state.confirmations.check(&headers, &operation, summary) {
+                        return refused;
+                    }
+                }
+                let tm = state.transfer_manager.read().await;
+                for id in &ids {
+                    tm.delete_history_item(id, delete_files).await;
+                }
+                Json(json!({ "status": true })).into_response()
+            }
+            _ => history(&state, &config, &params),
+        },
+        other => sab_error(format!("Mode {} is not supported", other)),
+    }
+}
+
+fn get_config(config: &AppConfig) -> Response {
+    let mut cats = vec![json!({
+        "name": "*", "order": 0, "pp": "", "script": "", "dir": "", "priority": -100,
+    })];
+    for (i, name) in categories(config).iter().enumerate() {
+        let dir = config
+            .categories
+            .get(name)
+            .map(|c| c.move_dir.clone())
+            .filter(|d| !d.starts_with("rclone:"))
+            .unwrap_or_default();
+        cats.push(json!({
+            "name": name, "order": i + 1, "pp": "", "script": "", "dir": dir, "priority": -100,
+        }));
+    }
+    Json(json!({
+        "config": {
+            "misc": {
+                "complete_dir": config.completed_dir(),
+                "download_dir": config.download_dir,
+                "enable_tv_sorting": false,
+                "enable_movie_sorting": false,
+                "enable_date_sorting": false,
+                "pre_check": false,
+                "history_retention": "",
+                "history_retention_option": "all",
+            },
+            "categories": cats,
+            "sorters": [],
+        }
+    }))
+    .into_response()
+}
+
+/// Pack of an `addurl` request: an `irc://` URL, or a grab link of the
+/// Newznab feed carrying one
+fn release_from_url(name: &str, nzbname: Option<&str>) -> Option<NzbRelease> {
+    let (url, size) = if XdccUrl::parse(name).is_ok() {
+        (name.to_string(), None)
+    } else {
+        let query = name.split_once('?')?.1;
+        let param = |key: &str| {
+            query
+                .split('&')
+                .filter_map(|p| p.split_once('='))
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, v)| {
+                    urlencoding::decode(&v.replace('+', " "))
+                        .ok()
+                        .map(|v| v.into_owned())
+                })
+        };
+        (param("url")?, param("size").and_then(|s| s.parse().ok()))
+    };
+    Some(NzbRelease {
+        name: nzbname
+            .filter(|n| !n.is_empty())
+            .unwrap_or(&url)
+            .to_string(),
+        url,
+        size,
+    })
+}
+
+/// Queue a pack, or attach to the transfer already downloading it
+async fn add(state: &AppState, release: Option<NzbRelease>, params: &SabnzbdParams) -> Response {
+    let Some(release) = release else {
+        return sab_error("No XDCC pack found in the request");
+    };
+    let url = match XdccUrl::parse(&release.url) {
+        Ok(url) => url,
+        Err(e) => return sab_error(e.to_string()),
+    };
+    let priority = sabnzbd::priority(params.priority);
+
+    let tm = state.transfer_manager.write().await;
+    if let Some((id, _)) = tm.coalesce_request(&url, priority).await {
+        tracing::info!(
+            "SABnzbd add of {} attached to transfer {}",
+            release.name,
+            id
+        );
+        return Json(json!({ "status": true, "nzo_ids": [id] })).into_response();
+    }
+    match tm
+        .create_transfer(url, priority, false, Some(release.name.clone()))
+        .await
+    {
+        Ok((id, _)) => {
+            if let Some(cat) = params.cat.as_deref().filter(|c| !c.is_empty() && *c != "*") {
+                tm.set_category(&id, Some(cat.to_string())).await;
+            }
+            if let Some(size) = release.size {
+                tm.set_expected_size(&id, size).await;
+            }
+            tracing::info!("Queued {} through the SABnzbd API as {}", release.name, id);
+            Json(json!({ "status": true, "nzo_ids": [id] })).into_response()
+        }
+        Err(e) => sab_error(e),
+    }
+}
+
+async fn queue(state: &AppState) -> Response {
+    let transfers = state.transfer_manager.read().await.list_transfers().await;
+    let mb = |bytes: u64| format!("{:.2}", bytes as f64 / 1_048_576.0);
+    let mut speed = 0.0;
+    let slots: Vec<serde_json::Value> = transfers
+        .iter()
+        .filter(|t| {
+            !matches!(
+                t.transfer.status,
+                TransferStatus::Completed
+                    | TransferStatus::Failed
+                    | TransferStatus::Cancelled
+                    | TransferStatus::Quarantined
+            )
+        })
+        .enumerate()
+        .map(|(index, t)| {
+            let transfer = &t.transfer;
+            speed += transfer.speed;
+            let size = transfer.size.unwrap_or(0);
+            json!({
+                "index": index,
+                "nzo_id": transfer.id,
+                "filename": transfer.filename.clone().unwrap_or_else(|| transfer.url.to_string()),
+                "cat": transfer.category.clone().unwrap_or_else(|| "*".to_string()),
+                "priority": sabnzbd::priority_name(t.priority),
+                "status": sabnzbd::queue_status(&transfer.status),
+                "mb": mb(size),
+                "mbleft": mb(size.saturating_sub(transfer.downloaded)),
+                "percentage": format!("{:.0}", transfer.progress),
+                "timeleft": sabnzbd::timeleft(transfer.eta_secs.unwrap_or(0)),
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "queue": {
+            "status": if slots.is_empty() { "Idle" } else { "Downloading" },
+            "paused": false,
+            "kbpersec": format!("{:.2}", speed / 1024.0),
+            "noofslots": slots.len(),
+            "slots": slots,
+        }
+    }))
+    .into_response()
+}
+
+fn history(state: &AppState, config: &AppConfig, params: &SabnzbdParams) -> Response {
+    let limit = params.limit.filter(|l| *l > 0).unwrap_or(100);
+    let start = params.start.unwrap_or(0).max(0);
+    let (records, total) =
+        match state
+            .database
+            .list_downloads_range(start, limit, params.category.as_deref())
+        {
+            Ok(records) => records,
+            Err(e) => return sab_error(format!("Database error: {}", e)),
+        };
+
+    let slots: Vec<serde_json::Value> = records
+        .into_iter()
+        .map(|r| {
+            let name = r.file_name.clone().unwrap_or_default();
+            let dir = storage_dir(config, r.category.as_deref(), r.template.as_deref());
+            let storage = saved_path(std::path::Path::new(&dir), &name);
+            let parse = |t: &str| chrono::DateTime::parse_from_rfc3339(t).ok();
+            let completed = parse(&r.completed_at);
+            let download_time = completed
+                .zip(parse(&r.created_at))
+                .map_or(0, |(end, start)| (end - start).num_seconds().max(0));
+            let completed_ok = r.status == "Completed";
+            json!({
+                "nzo_id": r.id,
+                "name": name,
+                "nzb_name": name,
+                "category": r.category.unwrap_or_else(|| "*".to_string()),
+                "bytes": r.size.unwrap_or(0),
+                "status": if completed_ok { "Completed" } else { "Failed" },
+                "fail_message": if completed_ok { String::new() } else { r.error.unwrap_or_default() },
+                "storage": storage.to_string_lossy(),
+                "download_time": download_time,
+                "completed": completed.map_or(0, |t| t.timestamp()),
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "history": {
+            "noofslots": total,
+            "slots": slots,
+        }
+    }))
+    .into_response()
+}
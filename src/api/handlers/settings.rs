@@ -1,24 +1,75 @@
-use crate::api::models::UpdateSettingsRequest;
-use crate::config::NetworkConfig;
+use crate::api::extract::{json_content_type, ApiJson};
+use crate::api::models::{ErrorResponse, ResolveNetworkParams, UpdateSettingsRequest};
+use crate::config::{keep_provider_secrets, keep_secret, keep_url_password, NetworkConfig};
+use crate::events::{self, AppEvent};
+use crate::network_import::{self, ImportResult};
+use crate::request_id::RequestId;
+use crate::xdcc::{NetworkSource, XdccConfig};
 use crate::AppState;
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    Json,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
 
-/// Get current settings
+/// Tell other clients the config changed
+fn announce(
+    state: &AppState,
+    section: &'static str,
+    network: Option<String>,
+    request_id: Option<Extension<RequestId>>,
+) {
+    // Nobody listening is fine
+    let _ = state.events.send(AppEvent::ConfigChanged {
+        section,
+        network,
+        request_id: request_id.map(|Extension(RequestId(id))| id),
+    });
+}
+
+/// Refusal of a change made against settings that changed since the
+/// client read them
+fn version_conflict(state: &AppState) -> Response {
+    (
+        StatusCode::CONFLICT,
+        [(header::ETAG, state.config_writer.etag())],
+        Json(ErrorResponse {
+            error: "Settings were changed by someone else; reload them and try again".to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// Get current settings, with secrets masked
 pub async fn get_settings(State(state): State<AppState>) -> impl IntoResponse {
     let config = state.config.read().await;
-    Json(config.clone())
+    (
+        [(header::ETAG, state.config_writer.etag())],
+        Json(config.masked()),
+    )
 }
 
-/// Update settings
+/// Update settings. With `If-Match`, only if they are still at that version.
+/// Secrets sent as the placeholder of [`get_settings`] are left unchanged.
 pub async fn update_settings(
     State(state): State<AppState>,
-    Json(req): Json<UpdateSettingsRequest>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
+    ApiJson(req): ApiJson<UpdateSettingsRequest>,
 ) -> impl IntoResponse {
     let mut config = state.config.write().await;
+    if !state.config_writer.matches(&headers) {
+        return version_conflict(&state);
+    }
+    // Checked up front, as updates are applied as they are read
+    if let Some(Err(error)) = req
+        .quarantine_patterns
+        .as_deref()
+        .map(crate::quarantine::validate_patterns)
+    {
+        return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response();
+    }
 
     // Apply partial updates
     if let Some(v) = req.use_ssl {
@@ -34,7 +85,10 @@ pub async fn update_settings(
         config.proxy_enabled = v;
     }
     if let Some(v) = req.proxy_url {
-        config.proxy_url = v;
+        config.proxy_url = keep_url_password(v, &config.proxy_url);
+    }
+    if let Some(v) = req.require_proxy {
+        config.require_proxy = v;
     }
     if let Some(v) = req.nickname {
         config.nickname = v;
@@ -45,6 +99,24 @@ pub async fn update_settings(
     if let Some(v) = req.realname {
         config.realname = v;
     }
+    if let Some(v) = req.irc_idle_timeout_mins {
+        config.irc_idle_timeout_mins = v;
+    }
+    if let Some(v) = req.irc_max_connections {
+        config.irc_max_connections = v;
+    }
+    if let Some(v) = req.shared_irc_connections {
+        config.shared_irc_connections = v;
+    }
+    if let Some(v) = req.shared_irc_idle_secs {
+        config.shared_irc_idle_secs = v;
+    }
+    if let Some(v) = req.http_fallback {
+        config.http_fallback = v;
+    }
+    if let Some(v) = req.http_fallback_after {
+        config.http_fallback_after = v;
+    }
     if let Some(v) = req.max_retries {
         config.max_retries = v.clamp(0, 10);
     }
@@ -54,6 +126,29 @@ pub async fn update_settings(
     if let Some(v) = req.queue_limit {
         config.queue_limit = v.clamp(1, 10);
     }
+    if let Some(v) = req.max_download_speed {
+        config.max_download_speed = v;
+    }
+    if let Some(v) = req.monthly_quota_gb {
+        config.monthly_quota_gb = v;
+    }
+    if let Some(v) = req.request_gap_secs {
+        config.request_gap_secs = v.min(3600);
+    }
+    if let Some(v) = req.max_channels_per_network {
+        config.max_channels_per_network = v;
+    }
+    if let Some(v) = req.pending_research_mins {
+        config.pending_research_mins = v;
+    }
+    if let Some(v) = req.queue_warmup_secs {
+        config.queue_warmup_secs = v.min(3600);
+    }
+    if let Some(v) = req.source_policy {
+        if matches!(v.as_str(), "selected" | "fastest") {
+            config.source_policy = v;
+        }
+    }
     if let Some(v) = req.passive_dcc {
         config.passive_dcc = v;
     }
@@ -63,9 +158,36 @@ pub async fn update_settings(
     if let Some(v) = req.dcc_port_max {
         config.dcc_port_max = v;
     }
+    if let Some(v) = req.dcc_external_ip {
+        config.dcc_external_ip = v.trim().to_string();
+    }
     if let Some(v) = req.resume_enabled {
         config.resume_enabled = v;
     }
+    if let Some(v) = req.xdcc_info_check {
+        config.xdcc_info_check = v;
+    }
+    if let Some(v) = req.xdcc_info_abort_on_mismatch {
+        config.xdcc_info_abort_on_mismatch = v;
+    }
+    if let Some(v) = req.outbound_bind {
+        if let Err(e) = crate::net::resolve_bind_address(&v) {
+            tracing::warn!("Outbound bind '{}' is currently unavailable: {}", v, e);
+        }
+        config.outbound_bind = v.trim().to_string();
+    }
+    if let Some(v) = req.turbo_dcc_bots {
+        config.turbo_dcc_bots = v;
+    }
+    if let Some(v) = req.dcc_connect_timeout {
+        config.dcc_connect_timeout = v.clamp(5, 120);
+    }
+    if let Some(v) = req.dcc_connect_retries {
+        config.dcc_connect_retries = v.min(10);
+    }
+    if let Some(v) = req.manual_offer_approval {
+        config.manual_offer_approval = v;
+    }
     if let Some(v) = req.enabled_providers {
         config.enabled_providers = v;
     }
@@ -75,77 +197,445 @@ pub async fn update_settings(
     if let Some(v) = req.search_timeout {
         config.search_timeout = v.clamp(10, 120);
     }
+    if let Some(v) = req.search_max_pages {
+        config.search_max_pages = v.clamp(1, 20);
+    }
+    if let Some(v) = req.search_max_results {
+        config.search_max_results = v;
+    }
+    if let Some(v) = req.search_page_delay_ms {
+        config.search_page_delay_ms = v.min(10_000);
+    }
+    if let Some(v) = req.search_user_agent {
+        config.search_user_agent = v;
+    }
+    if let Some(v) = req.search_normalize {
+        config.search_normalize = v;
+    }
+    if let Some(v) = req.provider_settings {
+        config.provider_settings = keep_provider_secrets(v, &config.provider_settings);
+    }
+    if let Some(v) = req.external_providers {
+        config.external_providers = v;
+    }
+    if let Some(v) = req.provider_block_backoff_mins {
+        config.provider_block_backoff_mins = v;
+    }
+    if let Some(v) = req.search_history_merge_window_mins {
+        config.search_history_merge_window_mins = v;
+    }
+    if let Some(v) = req.search_results_max_kb {
+        config.search_results_max_kb = v;
+    }
+    if let Some(v) = req.search_results_retention_days {
+        config.search_results_retention_days = v;
+    }
+    if let Some(v) = req.trash_retention_days {
+        config.trash_retention_days = v;
+    }
+    if let Some(v) = req.event_compress_after_days {
+        config.event_compress_after_days = v;
+    }
+    if let Some(v) = req.db_maintenance_weekly {
+        config.db_maintenance_weekly = v;
+    }
+    if let Some(v) = req.anime_mappings {
+        config.anime_mappings = v;
+    }
+    if let Some(v) = req.anime_mapping_file {
+        config.anime_mapping_file = v;
+    }
     if let Some(v) = req.networks {
         config.networks = v;
     }
+    if let Some(v) = req.network_fallback_hosts {
+        config.network_fallback_hosts = v;
+    }
     // Postprocessing settings
+    if let Some(v) = req.templates {
+        config.templates = v;
+    }
+    if let Some(v) = req.min_file_size_mb {
+        config.min_file_size_mb = v;
+    }
+    if let Some(v) = req.max_file_size_mb {
+        config.max_file_size_mb = v;
+    }
+    if let Some(v) = req.filename_policy {
+        if matches!(v.as_str(), "windows" | "posix") {
+            config.filename_policy = v;
+        }
+    }
+    if let Some(v) = req.alternate_download_dir {
+        config.alternate_download_dir = v.trim().to_string();
+    }
     if let Some(v) = req.move_completed {
         config.move_completed = v;
     }
     if let Some(v) = req.move_completed_dir {
         config.move_completed_dir = v;
     }
+    if let Some(v) = req.move_retries {
+        config.move_retries = v;
+    }
+    if let Some(v) = req.rclone_path {
+        config.rclone_path = v;
+    }
+    if let Some(v) = req.verify_checksums {
+        config.verify_checksums = v;
+    }
+    if let Some(v) = req.quarantine_patterns {
+        config.quarantine_patterns = v;
+    }
+    if let Some(v) = req.av_scan_command {
+        config.av_scan_command = v;
+    }
+    if let Some(v) = req.quarantine_dir {
+        config.quarantine_dir = v;
+    }
     if let Some(v) = req.postprocess_script_enabled {
         config.postprocess_script_enabled = v;
     }
     if let Some(v) = req.postprocess_script {
         config.postprocess_script = v;
     }
+    if let Some(v) = req.postprocess_steps {
+        config.postprocess_steps = v;
+    }
+    if let Some(v) = req.extract_delete_archives {
+        config.extract_delete_archives = v;
+    }
+    if let Some(v) = req.categories {
+        config.categories = v;
+    }
+    if let Some(v) = req.package_jobs {
+        config.package_jobs = v;
+    }
+    if let Some(v) = req.package_dir {
+        config.package_dir = v;
+    }
+    if let Some(v) = req.package_template {
+        if !v.trim().is_empty() {
+            config.package_template = v;
+        }
+    }
+    if let Some(v) = req.package_manifest {
+        config.package_manifest = v;
+    }
+    if let Some(v) = req.script_hooks {
+        config.script_hooks = v;
+    }
     if let Some(v) = req.postprocess_timeout {
         config.postprocess_timeout = v.clamp(10, 3600);
     }
+    // Monitoring settings
+    if let Some(v) = req.heartbeat_url {
+        config.heartbeat_url = keep_secret(v, &config.heartbeat_url);
+    }
+    if let Some(v) = req.heartbeat_interval_mins {
+        config.heartbeat_interval_mins = v.clamp(1, 60);
+    }
+    if let Some(v) = req.alert_webhook_url {
+        config.alert_webhook_url = keep_secret(v, &config.alert_webhook_url);
+    }
+    if let Some(v) = req.failure_alert_threshold {
+        config.failure_alert_threshold = v;
+    }
+    if let Some(v) = req.notifications {
+        config.notifications = crate::notifications::keep_secrets(v, &config.notifications);
+    }
+    if let Some(v) = req.failure_bundles {
+        config.failure_bundles = v;
+    }
+    if let Some(v) = req.failure_bundle_dir {
+        config.failure_bundle_dir = v;
+    }
+    if let Some(v) = req.thumbnails {
+        config.thumbnails = v;
+    }
+    if let Some(v) = req.thumbnail_dir {
+        config.thumbnail_dir = v;
+    }
+    if let Some(v) = req.ffmpeg_path {
+        config.ffmpeg_path = v;
+    }
+    if let Some(v) = req.eta_notify_mins {
+        config.eta_notify_mins = v;
+    }
+    if let Some(v) = req.speedtest_url {
+        config.speedtest_url = v.trim().to_string();
+    }
+    if let Some(v) = req.speedtest_max_secs {
+        config.speedtest_max_secs = v.clamp(5, 600);
+    }
+    // Integration settings
+    if let Some(v) = req.api_key {
+        config.api_key = keep_secret(v, &config.api_key);
+    }
+    if let Some(v) = req.disabled_api_groups {
+        config.disabled_api_groups = v
+            .into_iter()
+            .map(|g| g.trim().to_lowercase())
+            .filter(|g| {
+                let known = crate::lockdown::GROUPS.contains(&g.as_str());
+                if !known {
+                    tracing::warn!("Ignoring unknown API group '{}'", g);
+                }
+                known
+            })
+            .collect();
+    }
+    if let Some(v) = req.sonarr_url {
+        config.sonarr_url = v;
+    }
+    if let Some(v) = req.sonarr_api_key {
+        config.sonarr_api_key = keep_secret(v, &config.sonarr_api_key);
+    }
+    if let Some(v) = req.radarr_url {
+        config.radarr_url = v;
+    }
+    if let Some(v) = req.radarr_api_key {
+        config.radarr_api_key = keep_secret(v, &config.radarr_api_key);
+    }
+    if let Some(v) = req.arr_dedupe_mode {
+        if matches!(v.as_str(), "off" | "flag" | "filter") {
+            config.arr_dedupe_mode = v;
+        }
+    }
+    if let Some(v) = req.tmdb_api_key {
+        config.tmdb_api_key = keep_secret(v, &config.tmdb_api_key);
+    }
+    // Web UI settings
+    if let Some(v) = req.asset_cache_secs {
+        config.asset_cache_secs = v;
+    }
 
-    // Save to file
-    let config_path =
-        std::env::var("BOTARR_CONFIG_FILE").unwrap_or_else(|_| "config.json".to_string());
-    if let Err(e) = config.save(&config_path) {
-        tracing::warn!("Failed to save config: {}", e);
+    let etag = state.config_writer.changed();
+    let max_download_speed = config.max_download_speed;
+    drop(config);
+    if req.max_download_speed.is_some() {
+        let tm = state.transfer_manager.read().await;
+        tm.bandwidth().set_rate(max_download_speed * 1024);
     }
+    announce(&state, events::SETTINGS, None, request_id);
 
-    Json(serde_json::json!({ "status": "ok" }))
+    (
+        [(header::ETAG, etag)],
+        Json(serde_json::json!({ "status": "ok" })),
+    )
+        .into_response()
 }
 
-/// Get all networks
+/// Get all networks, with NickServ passwords masked
 pub async fn get_networks(State(state): State<AppState>) -> impl IntoResponse {
     let config = state.config.read().await;
-    Json(config.networks.clone())
+    (
+        [(header::ETAG, state.config_writer.etag())],
+        Json(config.masked_networks()),
+    )
+}
+
+/// Connection settings a download from network `name` would use, including
+/// the fallback for names that aren't configured
+pub async fn resolve_network(
+    State(state): State<AppState>,
+    Query(params): Query<ResolveNetworkParams>,
+) -> impl IntoResponse {
+    let config = state.config.read().await;
+    let xdcc_config = XdccConfig::from_app_config(&config, config.download_dir.clone(), "");
+    let Some(((host, port, ssl, autojoin_channels, join_delay_secs, nickserv_password), source)) =
+        xdcc_config.resolve_network_source(&params.name)
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!(
+                    "Network {} is not configured and there is no fallback host",
+                    params.name
+                ),
+            }),
+        )
+            .into_response();
+    };
+    let (source, network, template) = match source {
+        NetworkSource::Configured(key) => ("configured", Some(key), None),
+        NetworkSource::Hostname => ("hostname", None, None),
+        NetworkSource::Heuristic(template) => ("heuristic", None, Some(template)),
+        NetworkSource::Simulated => ("simulated", None, None),
+    };
+    Json(serde_json::json!({
+        "name": params.name,
+        "source": source,
+        "network": network,
+        "template": template,
+        "host": host,
+        "port": port,
+        "ssl": ssl,
+        "autojoin_channels": autojoin_channels,
+        "join_delay_secs": join_delay_secs,
+        "nickserv": !nickserv_password.is_empty(),
+        "private": config.is_private_network(&params.name),
+    }))
+    .into_response()
 }
 
-/// Add or update a network
+/// Add or update a network. A masked NickServ password keeps the stored one.
 pub async fn update_network(
     State(state): State<AppState>,
     Path(name): Path<String>,
-    Json(network): Json<NetworkConfig>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
+    ApiJson(mut network): ApiJson<NetworkConfig>,
 ) -> impl IntoResponse {
+    if let Err(error) = network_import::validate(&name, &network) {
+        return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error })).into_response();
+    }
     let mut config = state.config.write().await;
+    if !state.config_writer.matches(&headers) {
+        return version_conflict(&state);
+    }
+    let current = config.networks.get(&name);
+    network.nickserv_password = keep_secret(
+        network.nickserv_password,
+        current.map_or("", |n| n.nickserv_password.as_str()),
+    );
     config.networks.insert(name.clone(), network);
+    let etag = state.config_writer.changed();
+    drop(config);
+    announce(&state, events::NETWORKS, Some(name.clone()), request_id);
+
+    (
+        [(header::ETAG, etag)],
+        Json(serde_json::json!({ "status": "ok", "network": name })),
+    )
+        .into_response()
+}
 
-    // Save to file
-    let config_path =
-        std::env::var("BOTARR_CONFIG_FILE").unwrap_or_else(|_| "config.json".to_string());
-    if let Err(e) = config.save(&config_path) {
-        tracing::warn!("Failed to save config: {}", e);
+/// Add or update many networks at once, from a JSON array of networks (each
+/// with a `name`) or, sent as text, an mIRC servers.ini. Entries are checked
+/// one by one and the valid ones are stored together. An existing network is
+/// updated whatever the case of its name.
+pub async fn import_networks(
+    State(state): State<AppState>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    let entries = if json_content_type(&headers) {
+        match ApiJson::<Vec<serde_json::Value>>::from_bytes(&body) {
+            Ok(ApiJson(items)) => network_import::parse_json(items),
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        network_import::parse_servers_ini(&String::from_utf8_lossy(&body))
+    };
+    if entries.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "No networks found in the import".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let mut config = state.config.write().await;
+    if !state.config_writer.matches(&headers) {
+        return version_conflict(&state);
+    }
+    let mut results = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                results.push(ImportResult::error(index, None, e));
+                continue;
+            }
+        };
+        let name = entry.name.trim().to_string();
+        if let Err(e) = network_import::validate(&name, &entry.network) {
+            results.push(ImportResult::error(index, Some(name), e));
+            continue;
+        }
+        if !seen.insert(name.to_lowercase()) {
+            let error = format!("{} is listed more than once", name);
+            results.push(ImportResult::error(index, Some(name), error));
+            continue;
+        }
+
+        let existing = config
+            .networks
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(&name))
+            .cloned();
+        let network = entry.merge(existing.as_ref().and_then(|k| config.networks.get(k)));
+        let status = if existing.is_some() {
+            "updated"
+        } else {
+            "created"
+        };
+        let key = existing.unwrap_or(name);
+        config.networks.insert(key.clone(), network);
+        results.push(ImportResult {
+            index,
+            name: Some(key),
+            status,
+            error: None,
+        });
     }
 
-    Json(serde_json::json!({ "status": "ok", "network": name }))
+    let count = |status: &str| results.iter().filter(|r| r.status == status).count();
+    let (created, updated, failed) = (count("created"), count("updated"), count("error"));
+    let etag = if created + updated > 0 {
+        let etag = state.config_writer.changed();
+        drop(config);
+        announce(&state, events::NETWORKS, None, request_id);
+        tracing::info!(
+            "Imported networks: {} created, {} updated, {} failed",
+            created,
+            updated,
+            failed
+        );
+        etag
+    } else {
+        state.config_writer.etag()
+    };
+    (
+        [(header::ETAG, etag)],
+        Json(serde_json::json!({
+            "created": created,
+            "updated": updated,
+            "failed": failed,
+            "results": results,
+        })),
+    )
+        .into_response()
 }
 
 /// Delete a network
 pub async fn delete_network(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     let mut config = state.config.write().await;
+    if !state.config_writer.matches(&headers) {
+        return version_conflict(&state);
+    }
 
     if config.networks.remove(&name).is_some() {
-        // Save to file
-        let config_path =
-            std::env::var("BOTARR_CONFIG_FILE").unwrap_or_else(|_| "config.json".to_string());
-        if let Err(e) = config.save(&config_path) {
-            tracing::warn!("Failed to save config: {}", e);
-        }
-        Json(serde_json::json!({ "status": "ok", "deleted": name }))
+        let etag = state.config_writer.changed();
+        drop(config);
+        announce(&state, events::NETWORKS, Some(name.clone()), request_id);
+        (
+            [(header::ETAG, etag)],
+            Json(serde_json::json!({ "status": "ok", "deleted": name })),
+        )
+            .into_response()
     } else {
         Json(serde_json::json!({ "status": "error", "message": "Network not found" }))
+            .into_response()
     }
 }
@@ -1,7 +1,7 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, State,
     },
     http::StatusCode,
     response::IntoResponse,
@@ -10,9 +10,195 @@ use axum::{
 use futures::{sink::SinkExt, stream::StreamExt};
 use std::collections::HashMap;
 
-use crate::api::models::{ErrorResponse, PluginStatusResponse};
+use crate::api::auth::ApiKey;
+use crate::api::extract::ApiJson;
+use crate::api::models::{
+    ErrorResponse, NotificationTestRequest, NotificationTestResult, PluginStatusResponse,
+    SelfTestRequest, WatchlistImport,
+};
+use crate::notifications::{self, Notification};
 use crate::AppState;
 
+/// Version and database size information
+pub async fn get_system_info(State(state): State<AppState>) -> impl IntoResponse {
+    match state.database.stats() {
+        Ok(database) => Json(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "database": database,
+            "last_maintenance": state.database.last_maintenance().ok().flatten(),
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Run the self-test; responds 200 when every check passes, 503 otherwise
+pub async fn run_selftest(
+    State(state): State<AppState>,
+    body: Option<ApiJson<SelfTestRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|ApiJson(req)| req).unwrap_or_default();
+    let config = state.config.read().await.clone();
+    let report = crate::selftest::run(
+        &config,
+        &state.database,
+        &state.search_aggregator,
+        req.network.as_deref().filter(|n| !n.is_empty()),
+        // Only the primary talks to bots
+        req.speedtest && !config.mirror_mode,
+    )
+    .await;
+
+    let status = if report.passed {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Send a test notification; responds 200 when every target accepted it, 502 otherwise
+pub async fn test_notifications(
+    State(state): State<AppState>,
+    body: Option<ApiJson<NotificationTestRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|ApiJson(req)| req).unwrap_or_default();
+    let name = req.name.filter(|n| !n.is_empty());
+    let targets: Vec<_> = state
+        .config
+        .read()
+        .await
+        .notifications
+        .iter()
+        .filter(|t| match name.as_deref() {
+            Some(name) => t.label().eq_ignore_ascii_case(name),
+            None => t.enabled,
+        })
+        .cloned()
+        .collect();
+    if targets.is_empty() {
+        let error = match name {
+            Some(name) => format!("No notification target named {}", name),
+            None => "No notification targets are enabled".to_string(),
+        };
+        return (StatusCode::NOT_FOUND, Json(ErrorResponse { error })).into_response();
+    }
+
+    let notification = Notification::new(
+        notifications::TEST,
+        "Botarr: test notification".to_string(),
+        serde_json::json!({}),
+    );
+    let mut results = Vec::new();
+    for target in &targets {
+        let outcome = target.send(&notification).await;
+        results.push(NotificationTestResult {
+            name: target.label().to_string(),
+            kind: target.backend.kind(),
+            ok: outcome.is_ok(),
+            error: outcome.err(),
+        });
+    }
+    let status = if results.iter().all(|r| r.ok) {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    (status, Json(serde_json::json!({ "results": results }))).into_response()
+}
+
+/// Compact the database and report its size before and after
+pub async fn run_db_maintenance(State(state): State<AppState>) -> impl IntoResponse {
+    let database = state.database.clone();
+    match tokio::task::spawn_blocking(move || database.run_maintenance()).await {
+        Ok(Ok(report)) => {
+            tracing::info!(
+                "Database compacted from {} to {} bytes in {}ms",
+                report.size_before,
+                report.size_after,
+                report.duration_ms
+            );
+            Json(report).into_response()
+        }
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Maintenance failed: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Force-disconnect a network: its IRC client connection and channel monitors
+pub async fn disconnect_network(
+    State(state): State<AppState>,
+    Path(network): Path<String>,
+) -> impl IntoResponse {
+    let client = state
+        .irc_client_manager
+        .disconnect(&network, "Disconnected by user")
+        .await;
+    let monitors = state.irc_monitor.stop_monitors_for_network(&network).await;
+
+    if !client && monitors == 0 {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("No connections to {}", network),
+            }),
+        )
+            .into_response();
+    }
+    Json(serde_json::json!({
+        "network": network,
+        "client_disconnected": client,
+        "monitors_stopped": monitors,
+    }))
+    .into_response()
+}
+
+/// Per-bot reliability metrics for Prometheus
+pub async fn get_metrics(State(state): State<AppState>, key: ApiKey) -> impl IntoResponse {
+    if !state.config.read().await.api_key_matches(key.as_deref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or missing API key".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let stats = state
+        .transfer_manager
+        .read()
+        .await
+        .get_all_bot_stats()
+        .await;
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        crate::metrics::render_bot_metrics(&stats),
+    )
+        .into_response()
+}
+
 pub async fn get_plugin_status(State(state): State<AppState>) -> Json<PluginStatusResponse> {
     let loaded_scripts = state.plugin_manager.loaded_scripts.read().unwrap().clone();
 
@@ -54,9 +240,96 @@ pub async fn get_autodl_filters() -> impl IntoResponse {
     }
 }
 
+/// Restart the autodl plugin after its filters changed
+async fn reload_autodl(state: &AppState, filters: &serde_json::Value) {
+    // Always stop old monitors first to prevent duplicates when updating filters or toggling
+    state
+        .irc_monitor
+        .stop_monitors_for_plugin("autodl.lua")
+        .await;
+
+    // Check if plugin is enabled, attempt to load the plugin if not already loaded
+    if let Some(enabled) = filters.get("enabled").and_then(|f| f.as_bool()) {
+        if enabled {
+            state
+                .plugin_manager
+                .load_script_file(std::path::Path::new("plugins/autodl.lua"));
+            // Tell the plugin to reload its config and restart monitors
+            state.plugin_manager.emit_signal(
+                "config_changed",
+                crate::plugin::EventData::String("autodl.lua".to_string()),
+            );
+        }
+    } else if let Some(filters_array) = filters.get("filters").and_then(|f| f.as_array()) {
+        // Fallback for older formats
+        if !filters_array.is_empty() {
+            state
+                .plugin_manager
+                .load_script_file(std::path::Path::new("plugins/autodl.lua"));
+        }
+    }
+}
+
+/// Download the watchlist filters as a JSON file
+pub async fn export_autodl_filters() -> impl IntoResponse {
+    let watchlist = crate::library::read_watchlist();
+    let export = serde_json::json!({
+        "version": 1,
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "filters": watchlist.get("filters").cloned().unwrap_or_else(|| serde_json::json!([])),
+    });
+    (
+        [(
+            axum::http::header::CONTENT_DISPOSITION,
+            "attachment; filename=\"botarr-watchlist.json\"",
+        )],
+        Json(export),
+    )
+}
+
+/// Add exported watchlist filters, skipping ones that exist by name, or
+/// replace the current ones with them
+pub async fn import_autodl_filters(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WatchlistImport>,
+) -> impl IntoResponse {
+    if let Some(i) = req
+        .filters
+        .iter()
+        .position(|f| !f.get("name").is_some_and(|n| n.is_string()))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("Filter {} has no name", i + 1),
+            }),
+        )
+            .into_response();
+    }
+    let total = req.filters.len();
+    match crate::library::import_watchlist_filters(req.filters, req.replace) {
+        Ok(added) => {
+            reload_autodl(&state, &crate::library::read_watchlist()).await;
+            Json(serde_json::json!({
+                "added": added,
+                "skipped": total - added,
+                "replaced": req.replace,
+            }))
+            .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to save: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn update_autodl_filters(
     State(state): State<AppState>,
-    Json(filters): Json<serde_json::Value>,
+    ApiJson(filters): ApiJson<serde_json::Value>,
 ) -> impl IntoResponse {
     match serde_json::to_string_pretty(&filters) {
         Ok(json_str) => {
@@ -70,33 +343,7 @@ pub async fn update_autodl_filters(
                     .into_response();
             }
 
-            // Always stop old monitors first to prevent duplicates when updating filters or toggling
-            state
-                .irc_monitor
-                .stop_monitors_for_plugin("autodl.lua")
-                .await;
-
-            // Check if plugin is enabled, attempt to load the plugin if not already loaded
-            if let Some(enabled) = filters.get("enabled").and_then(|f| f.as_bool()) {
-                if enabled {
-                    state
-                        .plugin_manager
-                        .load_script_file(std::path::Path::new("plugins/autodl.lua"));
-                    // Tell the plugin to reload its config and restart monitors
-                    state.plugin_manager.emit_signal(
-                        "config_changed",
-                        crate::plugin::EventData::String("autodl.lua".to_string()),
-                    );
-                }
-            } else if let Some(filters_array) = filters.get("filters").and_then(|f| f.as_array()) {
-                // Fallback for older formats
-                if !filters_array.is_empty() {
-                    state
-                        .plugin_manager
-                        .load_script_file(std::path::Path::new("plugins/autodl.lua"));
-                }
-            }
-
+            reload_autodl(&state, &filters).await;
             Json(serde_json::json!({ "success": true })).into_response()
         }
         Err(e) => (
@@ -109,6 +356,41 @@ pub async fn update_autodl_filters(
     }
 }
 
+/// Stream live events (config changes) as JSON text messages
+pub async fn events_ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_events_socket(socket, state))
+}
+
+async fn handle_events_socket(socket: WebSocket, state: AppState) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut rx = state.events.subscribe();
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) => {
+                    let Ok(json) = serde_json::to_string(&event) else { continue };
+                    if sender.send(Message::Text(json.into())).await.is_err() {
+                        break;
+                    }
+                }
+                // Missed events still mean something changed
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::debug!("Events client fell behind by {} events", n);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            // Only closing matters from the client side
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
 pub async fn irc_ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
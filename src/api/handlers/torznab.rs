@@ -0,0 +1,373 @@
+use crate::api::auth::ApiKey;
+use crate::api::models::*;
+use crate::plugin::hooks::ScriptHooks;
+use crate::torznab::{self, TorznabItem};
+use crate::xdcc::query::transliterate;
+use crate::xdcc::{TransferPriority, XdccUrl};
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Number of recent imported packs served when the *arr polls without a query
+const RSS_ITEMS: i64 = 100;
+
+/// Base URL the client reached us on, for links pointing back at Botarr
+fn external_base(headers: &HeaderMap) -> String {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost:3001");
+    let proto = headers
+        .get("x-forwarded-proto")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("http");
+    format!("{}://{}", proto, host)
+}
+
+fn xml(status: StatusCode, body: String) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+/// Torznab API (`t=caps`, `search`, `tvsearch`, `movie`)
+pub async fn torznab_api(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    key: ApiKey,
+    Query(params): Query<TorznabParams>,
+) -> impl IntoResponse {
+    feed(state, headers, key, params, false).await
+}
+
+/// The Torznab API as a Newznab indexer, for the SABnzbd download client
+pub async fn newznab_api(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    key: ApiKey,
+    Query(params): Query<TorznabParams>,
+) -> impl IntoResponse {
+    feed(state, headers, key, params, true).await
+}
+
+async fn feed(
+    state: AppState,
+    headers: HeaderMap,
+    key: ApiKey,
+    params: TorznabParams,
+    usenet: bool,
+) -> Response {
+    let config = state.config.read().await.clone();
+    if !config.api_key_matches(key.as_deref()) {
+        return xml(
+            StatusCode::UNAUTHORIZED,
+            torznab::error_xml(100, "Invalid API key"),
+        );
+    }
+
+    let mut query = params.q.clone().unwrap_or_default().trim().to_string();
+    match params.t.as_str() {
+        "caps" => return xml(StatusCode::OK, torznab::caps_xml()),
+        "search" | "movie" => {}
+        "tvsearch" => {
+            if let Some(season) = params.season.as_deref().and_then(|s| s.parse::<u32>().ok()) {
+                match params.ep.as_deref().and_then(|e| e.parse::<u32>().ok()) {
+                    Some(ep) => query = format!("{} S{:02}E{:02}", query, season, ep),
+                    None => query = format!("{} S{:02}", query, season),
+                }
+            }
+        }
+        other => {
+            return xml(
+                StatusCode::OK,
+                torznab::error_xml(202, &format!("No such function: {}", other)),
+            )
+        }
+    }
+    if config.search_normalize {
+        query = transliterate(&query);
+    }
+    let query = query.trim();
+
+    // Results as (url, file name, size, last seen)
+    type Item = (
+        XdccUrl,
+        String,
+        Option<u64>,
+        Option<chrono::DateTime<chrono::Utc>>,
+    );
+    let results: Vec<Item> = if query.is_empty() {
+        // RSS sync and the *arr's connection test poll without a query
+        match state.database.list_packs(1, RSS_ITEMS) {
+            Ok(packs) => packs
+                .items
+                .into_iter()
+                .map(|p| {
+                    (
+                        XdccUrl {
+                            network: p.network,
+                            channel: p.channel,
+                            bot: p.bot,
+                            slot: p.slot,
+                        },
+                        p.file_name,
+                        p.size.map(|s| s as u64),
+                        chrono::DateTime::parse_from_rfc3339(&p.imported_at)
+                            .ok()
+                            .map(|t| t.with_timezone(&chrono::Utc)),
+                    )
+                })
+                .collect(),
+            Err(e) => {
+                return xml(
+                    StatusCode::OK,
+                    torznab::error_xml(900, &format!("Database error: {}", e)),
+                )
+            }
+        }
+    } else {
+        match state.search_aggregator.search(query, None).await {
+            Ok(results) => {
+                let hooks = ScriptHooks::from_config(&*state.config.read().await);
+                hooks
+                    .filter_search_results(query, results)
+                    .await
+                    .into_iter()
+                    .map(|r| (r.url, r.filename, r.size, r.last_seen))
+                    .collect()
+            }
+            Err(e) => {
+                return xml(
+                    StatusCode::OK,
+                    torznab::error_xml(900, &format!("Search failed: {}", e)),
+                )
+            }
+        }
+    };
+
+    let base = external_base(&headers);
+    let requested: Vec<u32> = params
+        .cat
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|c| c.trim().parse().ok())
+        .collect();
+    let now = chrono::Utc::now();
+    let items: Vec<TorznabItem> = results
+        .into_iter()
+        // Packs on private networks stay inside Botarr
+        .filter(|(url, ..)| !config.is_private_network(&url.network))
+        .filter_map(|(url, name, size, last_seen)| {
+            let category = torznab::category_for(&name);
+            // Releases are only sorted into parent categories, so TV/HD
+            // requests match TV releases
+            if !requested.is_empty() && !requested.iter().any(|c| c / 1000 == category / 1000) {
+                return None;
+            }
+            let url_str = url.to_string();
+            let mut link = format!(
+                "{}/{}/grab?url={}&name={}&cat={}",
+                base,
+                if usenet { "newznab" } else { "torznab" },
+                urlencoding::encode(&url_str),
+                urlencoding::encode(&name),
+                category
+            );
+            if let Some(size) = size {
+                link.push_str(&format!("&size={}", size));
+            }
+            if let Some(key) = key.as_deref() {
+                link.push_str(&format!("&apikey={}", urlencoding::encode(key)));
+            }
+            Some(TorznabItem {
+                title: name,
+                size,
+                link,
+                guid: url_str,
+                category,
+                pub_date: last_seen.unwrap_or(now),
+            })
+        })
+        .collect();
+
+    xml(StatusCode::OK, torznab::feed_xml(&items, usenet))
+}
+
+/// NZB for a release grabbed from the Newznab feed. Nothing is queued until
+/// the *arr hands it to the SABnzbd API.
+pub async fn newznab_grab(
+    State(state): State<AppState>,
+    key: ApiKey,
+    Query(params): Query<TorznabGrabParams>,
+) -> impl IntoResponse {
+    if !state.config.read().await.api_key_matches(key.as_deref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or missing API key".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    if let Err(e) = XdccUrl::parse(&params.url) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let category = torznab::download_category(params.cat.unwrap_or(torznab::CAT_OTHER));
+    let nzb = crate::sabnzbd::nzb_document(&params.name, &params.url, params.size, category);
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-nzb".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.nzb\"",
+                    params.name.replace('"', "")
+                ),
+            ),
+        ],
+        nzb,
+    )
+        .into_response()
+}
+
+/// Queue a release grabbed from the Torznab feed and answer with a
+/// placeholder torrent for the *arr's blackhole folder
+pub async fn torznab_grab(
+    State(state): State<AppState>,
+    key: ApiKey,
+    Query(params): Query<TorznabGrabParams>,
+) -> impl IntoResponse {
+    if !state.config.read().await.api_key_matches(key.as_deref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or missing API key".to_string(),
+            }),
+        )
+            .into_response();
+    }
+    let url = match XdccUrl::parse(&params.url) {
+        Ok(url) => url,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+                .into_response()
+        }
+    };
+
+    let tm = state.transfer_manager.write().await;
+    if let Some((id, _)) = tm.coalesce_request(&url, TransferPriority::Normal).await {
+        tracing::info!(
+            "Torznab grab of {} attached to transfer {}",
+            params.name,
+            id
+        );
+    } else {
+        match tm
+            .create_transfer(
+                url,
+                TransferPriority::Normal,
+                false,
+                Some(params.name.clone()),
+            )
+            .await
+        {
+            Ok((id, _)) => {
+                if let Some(cat) = params.cat {
+                    tm.set_category(&id, Some(torznab::download_category(cat).to_string()))
+                        .await;
+                }
+                if let Some(size) = params.size {
+                    tm.set_expected_size(&id, size).await;
+                }
+                tracing::info!("Queued {} from the Torznab feed as {}", params.name, id);
+            }
+            Err(e) => {
+                return (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })).into_response()
+            }
+        }
+    }
+
+    let torrent = torznab::placeholder_torrent(&params.name, params.size.unwrap_or(0));
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-bittorrent".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.torrent\"",
+                    params.name.replace('"', "")
+                ),
+            ),
+        ],
+        torrent,
+    )
+        .into_response()
+}
+
+/// Copy-pasteable Sonarr/Radarr indexer and download client settings
+pub async fn arr_preset(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    key: ApiKey,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    if !config.api_key_matches(key.as_deref()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Invalid or missing API key".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let torznab_url = format!("{}/torznab", external_base(&headers));
+    // The *arr writes placeholder torrents here and imports from the watch folder
+    let torrent_folder = std::path::Path::new(&config.download_dir)
+        .join("arr-blackhole")
+        .to_string_lossy()
+        .to_string();
+    let watch_folder = config.completed_dir();
+
+    Json(serde_json::json!({
+        "torznab_url": torznab_url,
+        "api_key": config.api_key,
+        "sonarr": torznab::arr_preset(
+            &torznab_url,
+            &config.api_key,
+            &[torznab::CAT_TV, torznab::CAT_TV_SD, torznab::CAT_TV_HD],
+            Some(&[torznab::CAT_TV_ANIME]),
+            &torrent_folder,
+            &watch_folder,
+        ),
+        "radarr": torznab::arr_preset(
+            &torznab_url,
+            &config.api_key,
+            &[torznab::CAT_MOVIES, torznab::CAT_MOVIES_HD],
+            None,
+            &torrent_folder,
+            &watch_folder,
+        ),
+    }))
+    .into_response()
+}
@@ -0,0 +1,193 @@
+use crate::api::extract::ApiJson;
+use crate::api::models::*;
+use crate::api::pagination::PageParams;
+use crate::config::AppConfig;
+use crate::db::WatchlistEntry;
+use crate::watchlist::{self, MIN_INTERVAL_MINS};
+use crate::xdcc::query::SearchFilter;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Most recent finds returned with an entry
+const HITS_SHOWN: i64 = 50;
+
+fn error(status: StatusCode, error: String) -> Response {
+    (status, Json(ErrorResponse { error })).into_response()
+}
+
+fn not_found() -> Response {
+    error(
+        StatusCode::NOT_FOUND,
+        "Watchlist entry not found".to_string(),
+    )
+}
+
+fn database_error(e: rusqlite::Error) -> Response {
+    error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+/// Check a request and turn it into an entry
+fn validate(
+    req: WatchlistRequest,
+    config: &AppConfig,
+    id: i64,
+    created_at: String,
+) -> Result<WatchlistEntry, String> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Name must not be empty".to_string());
+    }
+    if SearchFilter::parse_query(&req.query).0.is_empty() {
+        return Err("Query must contain at least one search term".to_string());
+    }
+    if req.interval_mins < MIN_INTERVAL_MINS {
+        return Err(format!(
+            "interval_mins must be at least {}",
+            MIN_INTERVAL_MINS
+        ));
+    }
+    if let (Some(min), Some(max)) = (req.min_size, req.max_size) {
+        if min > max {
+            return Err("min_size must not be above max_size".to_string());
+        }
+    }
+    let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let template = non_empty(req.template);
+    if let Some(template) = template.as_deref() {
+        if config.template(template).is_none() {
+            return Err(format!("Unknown template: {}", template));
+        }
+    }
+    Ok(WatchlistEntry {
+        id,
+        name,
+        query: req.query.trim().to_string(),
+        min_size: req.min_size,
+        max_size: req.max_size,
+        network: non_empty(req.network),
+        bot: non_empty(req.bot),
+        category: non_empty(req.category),
+        template,
+        interval_mins: req.interval_mins,
+        enabled: req.enabled,
+        last_run_at: None,
+        created_at,
+    })
+}
+
+/// List watchlist entries
+pub async fn list_watchlist(
+    State(state): State<AppState>,
+    Query(page): Query<PageParams>,
+) -> impl IntoResponse {
+    let page = page.page(100);
+    match state.database.list_watchlist() {
+        Ok(entries) => {
+            let total = entries.len() as i64;
+            (page.headers(total), Json(page.slice(entries))).into_response()
+        }
+        Err(e) => database_error(e),
+    }
+}
+
+/// Add a watchlist entry; it first runs within a minute
+pub async fn create_watchlist_entry(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<WatchlistRequest>,
+) -> impl IntoResponse {
+    let entry = {
+        let config = state.config.read().await;
+        match validate(req, &config, 0, chrono::Utc::now().to_rfc3339()) {
+            Ok(entry) => entry,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        }
+    };
+    match state.database.insert_watchlist_entry(&entry) {
+        Ok(id) => {
+            tracing::info!("Added watchlist entry '{}' ({})", entry.name, id);
+            Json(WatchlistEntry { id, ..entry }).into_response()
+        }
+        Err(e) => database_error(e),
+    }
+}
+
+/// A watchlist entry and the files it found most recently
+pub async fn get_watchlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let entry = match state.database.get_watchlist_entry(id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return not_found(),
+        Err(e) => return database_error(e),
+    };
+    match state.database.list_watchlist_hits(id, HITS_SHOWN) {
+        Ok(hits) => Json(WatchlistDetail { entry, hits }).into_response(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// Replace the settings of a watchlist entry; the files it found are kept
+pub async fn update_watchlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ApiJson(req): ApiJson<WatchlistRequest>,
+) -> impl IntoResponse {
+    let existing = match state.database.get_watchlist_entry(id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return not_found(),
+        Err(e) => return database_error(e),
+    };
+    let entry = {
+        let config = state.config.read().await;
+        match validate(req, &config, id, existing.created_at) {
+            Ok(entry) => WatchlistEntry {
+                last_run_at: existing.last_run_at,
+                ..entry
+            },
+            Err(e) => return error(StatusCode::BAD_REQUEST, e),
+        }
+    };
+    match state.database.update_watchlist_entry(&entry) {
+        Ok(true) => Json(entry).into_response(),
+        Ok(false) => not_found(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// Remove a watchlist entry; transfers it queued are left alone
+pub async fn delete_watchlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.database.delete_watchlist_entry(id) {
+        Ok(true) => Json(serde_json::json!({"status": "deleted"})).into_response(),
+        Ok(false) => not_found(),
+        Err(e) => database_error(e),
+    }
+}
+
+/// Run a watchlist entry now, even if disabled, and list the new files it queued
+pub async fn run_watchlist_entry(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let entry = match state.database.get_watchlist_entry(id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return not_found(),
+        Err(e) => return database_error(e),
+    };
+    match watchlist::run(&state, &entry).await {
+        Ok(hits) => Json(serde_json::json!({ "found": hits })).into_response(),
+        Err(e @ watchlist::RunError::Busy) => error(StatusCode::CONFLICT, e.to_string()),
+        Err(e) => error(StatusCode::BAD_GATEWAY, format!("Search failed: {}", e)),
+    }
+}
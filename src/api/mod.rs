@@ -1,20 +1,32 @@
+pub mod auth;
+pub mod extract;
 pub mod handlers;
 pub mod models;
+pub mod pagination;
 
 use crate::AppState;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{delete, get, post, put},
     Router,
 };
 
 pub use handlers::downloads::spawn_download_task;
 
+/// Largest pack list accepted by `/api/packs/import` (big bots list thousands of packs)
+const PACKLIST_MAX_BYTES: usize = 32 * 1024 * 1024;
+
 pub fn routes() -> Router<AppState> {
     Router::new()
         // Downloads & Queue
         .route("/api/search", get(handlers::downloads::xdcc_search))
+        .route(
+            "/api/search/providers",
+            get(handlers::downloads::xdcc_search_providers),
+        )
         .route("/api/parse", post(handlers::downloads::xdcc_parse_url))
         .route("/api/download", post(handlers::downloads::xdcc_download))
+        .route("/add", get(handlers::downloads::xdcc_quick_add))
         .route(
             "/api/transfers",
             get(handlers::downloads::xdcc_list_transfers),
@@ -28,21 +40,98 @@ pub fn routes() -> Router<AppState> {
             "/api/transfers/{id}/retry",
             post(handlers::downloads::xdcc_retry_transfer),
         )
+        .route(
+            "/api/transfers/{id}/pause",
+            post(handlers::downloads::xdcc_pause_transfer),
+        )
         .route(
             "/api/transfers/{id}/resume",
             post(handlers::downloads::xdcc_resume_transfer),
         )
+        .route(
+            "/api/transfers/{id}/relocate",
+            post(handlers::downloads::xdcc_relocate_transfer),
+        )
         .route(
             "/api/transfers/{id}/priority",
             post(handlers::downloads::xdcc_set_priority),
         )
+        .route(
+            "/api/transfers/{id}/limit",
+            post(handlers::downloads::xdcc_set_speed_limit),
+        )
         .route(
             "/api/transfers/{id}/logs",
             get(handlers::downloads::xdcc_get_transfer_logs),
         )
+        .route(
+            "/api/transfers/{id}/after",
+            put(handlers::downloads::xdcc_set_after),
+        )
+        .route("/api/jobs", get(handlers::downloads::xdcc_list_jobs))
+        .route(
+            "/api/jobs/{id}/package",
+            post(handlers::downloads::xdcc_package_job),
+        )
+        .route(
+            "/api/transfers/{id}/annotations",
+            put(handlers::downloads::xdcc_set_annotations),
+        )
+        .route(
+            "/api/transfers/{id}/bundle",
+            get(handlers::downloads::xdcc_get_transfer_bundle),
+        )
+        .route("/api/offers", get(handlers::downloads::xdcc_list_offers))
+        .route(
+            "/api/offers/{id}/accept",
+            post(handlers::downloads::xdcc_accept_offer),
+        )
+        .route(
+            "/api/offers/{id}/reject",
+            post(handlers::downloads::xdcc_reject_offer),
+        )
         .route("/api/bots/stats", get(handlers::downloads::xdcc_bot_stats))
         .route("/api/analytics", get(handlers::downloads::xdcc_analytics))
-        .route("/api/queue", get(handlers::downloads::xdcc_queue_status))
+        .route(
+            "/api/analytics/export",
+            get(handlers::history::xdcc_export_analytics),
+        )
+        .route(
+            "/api/analytics/traffic",
+            get(handlers::downloads::xdcc_traffic),
+        )
+        .route(
+            "/api/queue",
+            get(handlers::downloads::xdcc_queue_status)
+                .delete(handlers::downloads::xdcc_cancel_queue),
+        )
+        // Watchlist
+        .route(
+            "/api/watchlist",
+            get(handlers::watchlist::list_watchlist)
+                .post(handlers::watchlist::create_watchlist_entry),
+        )
+        .route(
+            "/api/watchlist/{id}",
+            get(handlers::watchlist::get_watchlist_entry)
+                .put(handlers::watchlist::update_watchlist_entry)
+                .delete(handlers::watchlist::delete_watchlist_entry),
+        )
+        .route(
+            "/api/watchlist/{id}/run",
+            post(handlers::watchlist::run_watchlist_entry),
+        )
+        .route("/metrics", get(handlers::system::get_metrics))
+        .route("/api/system/selftest", post(handlers::system::run_selftest))
+        .route(
+            "/api/notifications/test",
+            post(handlers::system::test_notifications),
+        )
+        .route(
+            "/api/system/db/maintenance",
+            post(handlers::system::run_db_maintenance),
+        )
+        .route("/api/summary", get(handlers::downloads::xdcc_summary))
         // History
         .route(
             "/api/history",
@@ -56,6 +145,48 @@ pub fn routes() -> Router<AppState> {
             "/api/history/bulk",
             post(handlers::history::xdcc_bulk_delete_history),
         )
+        .route(
+            "/api/history/trash",
+            get(handlers::history::xdcc_trash).delete(handlers::history::xdcc_empty_trash),
+        )
+        .route(
+            "/api/history/{id}/annotations",
+            put(handlers::downloads::xdcc_set_annotations),
+        )
+        .route(
+            "/api/history/{id}/thumbnail",
+            get(handlers::history::xdcc_history_thumbnail),
+        )
+        .route(
+            "/api/history/{id}/redownload",
+            post(handlers::history::xdcc_redownload),
+        )
+        .route(
+            "/api/history/{id}/restore",
+            post(handlers::history::xdcc_restore_history),
+        )
+        .route(
+            "/api/files/{id}/download",
+            get(handlers::files::download_file),
+        )
+        .route("/api/quarantine", get(handlers::history::xdcc_quarantine))
+        .route(
+            "/api/quarantine/{id}",
+            delete(handlers::history::xdcc_purge_quarantine),
+        )
+        .route(
+            "/api/quarantine/{id}/release",
+            post(handlers::history::xdcc_release_quarantine),
+        )
+        .route("/api/partials", get(handlers::history::xdcc_partials))
+        .route(
+            "/api/partials/{id}",
+            delete(handlers::history::xdcc_forget_partial),
+        )
+        .route(
+            "/api/partials/{id}/resume",
+            post(handlers::history::xdcc_resume_partial),
+        )
         .route(
             "/api/search-history",
             get(handlers::history::xdcc_search_history)
@@ -65,10 +196,29 @@ pub fn routes() -> Router<AppState> {
             "/api/search-history/{id}",
             delete(handlers::history::xdcc_delete_search_history),
         )
+        .route(
+            "/api/search-history/stats",
+            get(handlers::history::xdcc_search_history_stats),
+        )
         .route(
             "/api/search-history/bulk",
             post(handlers::history::xdcc_bulk_delete_search_history),
         )
+        // Imported pack lists
+        .route("/api/packs", get(handlers::packs::list_packs))
+        .route(
+            "/api/packs/import",
+            post(handlers::packs::import_packlist).layer(DefaultBodyLimit::max(PACKLIST_MAX_BYTES)),
+        )
+        .route(
+            "/api/packs/import-url",
+            post(handlers::packs::import_packlist_url),
+        )
+        // Library
+        .route(
+            "/api/library/gaps",
+            post(handlers::library::find_library_gaps),
+        )
         // Settings & Networks
         .route(
             "/api/settings",
@@ -78,10 +228,18 @@ pub fn routes() -> Router<AppState> {
             "/api/settings/networks",
             get(handlers::settings::get_networks),
         )
+        .route(
+            "/api/settings/networks/import",
+            post(handlers::settings::import_networks),
+        )
         .route(
             "/api/settings/networks/{name}",
             put(handlers::settings::update_network).delete(handlers::settings::delete_network),
         )
+        .route(
+            "/api/networks/resolve",
+            get(handlers::settings::resolve_network),
+        )
         // Plugins & System
         .route(
             "/api/plugins/status",
@@ -91,5 +249,29 @@ pub fn routes() -> Router<AppState> {
             "/api/plugins/autodl/filters",
             get(handlers::system::get_autodl_filters).put(handlers::system::update_autodl_filters),
         )
+        .route(
+            "/api/plugins/autodl/filters/export",
+            get(handlers::system::export_autodl_filters),
+        )
+        .route(
+            "/api/plugins/autodl/filters/import",
+            post(handlers::system::import_autodl_filters),
+        )
         .route("/api/irc/ws", get(handlers::system::irc_ws_handler))
+        .route("/api/events", get(handlers::system::events_ws_handler))
+        .route(
+            "/api/irc/networks/{network}/disconnect",
+            post(handlers::system::disconnect_network),
+        )
+        .route("/api/system", get(handlers::system::get_system_info))
+        // Sonarr/Radarr integration
+        .route("/torznab/api", get(handlers::torznab::torznab_api))
+        .route("/torznab/grab", get(handlers::torznab::torznab_grab))
+        .route("/newznab/api", get(handlers::torznab::newznab_api))
+        .route("/newznab/grab", get(handlers::torznab::newznab_grab))
+        .route(
+            "/sabnzbd/api",
+            get(handlers::sabnzbd::sabnzbd_api).post(handlers::sabnzbd::sabnzbd_api),
+        )
+        .route("/api/arr/preset", get(handlers::torznab::arr_preset))
 }
@@ -1,5 +1,8 @@
-use crate::config::NetworkConfig;
-use crate::xdcc::{XdccSearchResult, XdccUrl};
+use crate::config::{
+    AnimeMapping, CategoryConfig, DownloadTemplate, NetworkConfig, ProviderSettings,
+};
+use crate::notifications::NotificationTarget;
+use crate::xdcc::{TransferPriority, XdccSearchResult, XdccUrl};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -7,11 +10,22 @@ use std::collections::HashMap;
 pub struct SearchRequest {
     pub query: String,
     pub providers: Option<String>,
+    /// Comma-separated terms that must appear in result filenames
+    pub include: Option<String>,
+    /// Comma-separated terms that must not appear in result filenames
+    pub exclude: Option<String>,
+    /// Regex result filenames must match
+    pub regex: Option<String>,
+    /// `freshness` lists the most recently announced packs first
+    pub sort: Option<String>,
+    /// Drop results last announced more than this many days ago
+    pub max_age_days: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SearchResponse {
     pub results: Vec<XdccSearchResult>,
+    /// Results on all pages
     pub count: usize,
 }
 
@@ -34,12 +48,233 @@ pub struct DownloadRequest {
     pub priority: Option<String>,
     #[serde(default)]
     pub filename: Option<String>,
+    /// Download category; detected from the file name when omitted
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Expected size in bytes, checked by the xdcc info pre-check
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Tags to attach, e.g. the automation queuing it
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Name of a download template supplying defaults for the above
+    #[serde(default)]
+    pub template: Option<String>,
+    /// ID of a transfer that has to complete before this one starts
+    #[serde(default)]
+    pub after: Option<String>,
+    /// Check the request and return what would happen without queueing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuickAddParams {
+    pub url: String,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Comma-separated labels
+    #[serde(default)]
+    pub labels: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TorznabParams {
+    #[serde(default)]
+    pub t: String,
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub season: Option<String>,
+    #[serde(default)]
+    pub ep: Option<String>,
+    /// Comma-separated Torznab category ids
+    #[serde(default)]
+    pub cat: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TorznabGrabParams {
+    pub url: String,
+    pub name: String,
+    #[serde(default)]
+    pub cat: Option<u32>,
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// Query of the SABnzbd-compatible API
+#[derive(Debug, Deserialize)]
+pub struct SabnzbdParams {
+    #[serde(default)]
+    pub mode: String,
+    /// URL to add, or the action for `queue` and `history` (e.g. `delete`)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Ids the action applies to, comma-separated
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub nzbname: Option<String>,
+    #[serde(default)]
+    pub cat: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Category filter of `history`
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub start: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub del_files: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveNetworkParams {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyParams {
+    #[serde(default)]
+    pub apikey: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct DownloadResponse {
     pub transfer_id: String,
     pub status: String,
+    /// Set when the request was attached to a transfer of the same pack that
+    /// was already queued or running
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+}
+
+/// What a download request would do, returned for `dry_run` requests
+#[derive(Debug, Serialize)]
+pub struct DownloadPlan {
+    pub dry_run: bool,
+    /// Whether the request would be queued (or attached to a duplicate)
+    pub ok: bool,
+    /// Why it would be refused or couldn't complete
+    pub problems: Vec<String>,
+    pub url: String,
+    pub file_name: Option<String>,
+    pub priority: TransferPriority,
+    /// Category given or detected from the file name
+    pub category: Option<String>,
+    pub template: Option<String>,
+    /// Queued or running transfer of the same pack the request would attach to
+    pub duplicate_of: Option<String>,
+    /// IRC server the download would connect to and how it was found
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub ssl: Option<bool>,
+    pub network_source: Option<String>,
+    /// Proxy connections would go through, password masked
+    pub proxy: Option<String>,
+    pub download_dir: String,
+    /// Path the file would be saved at, when its name is known
+    pub destination: Option<String>,
+    /// Directory the file is moved to once completed
+    pub move_dir: Option<String>,
+    /// Bytes free where the file would be saved
+    pub free_space: Option<u64>,
+    /// Place in the queue once started (1 = next)
+    pub queue_position: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackImportUrlRequest {
+    pub url: String,
+    pub network: String,
+    pub channel: String,
+    /// Bot nickname; taken from the pack list itself when omitted
+    #[serde(default)]
+    pub bot: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackImportParams {
+    pub network: String,
+    pub channel: String,
+    /// Bot nickname; taken from the pack list itself when omitted
+    #[serde(default)]
+    pub bot: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferListParams {
+    /// Only transfers with this label
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetAfterRequest {
+    /// Transfer to wait for, or null to start independently
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnotationsRequest {
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LibraryGapsRequest {
+    /// Library folder of the series
+    pub path: String,
+    /// Series name used for searches and watchlist filters
+    pub series: String,
+    /// Custom file name regex with `season` and `episode` named groups
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Expected episode count per season (defaults to the highest episode found)
+    #[serde(default)]
+    pub episodes_per_season: std::collections::BTreeMap<u32, u32>,
+    /// Add an autodl watchlist filter for every missing episode
+    #[serde(default)]
+    pub create_watchlist: bool,
+    /// Search for every missing episode and queue the best match
+    #[serde(default)]
+    pub auto_grab: bool,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LibraryGapsResponse {
+    pub series: String,
+    pub found: usize,
+    pub missing: Vec<crate::library::EpisodeNumber>,
+    pub watchlist_added: usize,
+    pub queued: Vec<String>,
+}
+
+/// Download history entry with optional TMDB metadata
+#[derive(Debug, Serialize)]
+pub struct HistoryItem {
+    #[serde(flatten)]
+    pub record: crate::db::DownloadRecord,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<crate::metadata::MediaMetadata>,
+    /// A thumbnail is served at `/api/history/{id}/thumbnail`
+    pub thumbnail: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -55,6 +290,12 @@ pub struct PluginStatusResponse {
     pub raw_irc_logs: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SpeedLimitRequest {
+    /// KiB/s, 0 for no limit, or null for the transfer's template's
+    pub max_speed_kbps: Option<u64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SetPriorityRequest {
     pub priority: String,
@@ -62,17 +303,101 @@ pub struct SetPriorityRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct HistoryRequest {
-    #[serde(default = "default_history_page")]
-    pub page: i64,
-    #[serde(default = "default_history_limit")]
-    pub limit: usize,
+    /// Only records with this label
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Watchlist filters to import; an export file can be sent as is
+#[derive(Debug, Deserialize)]
+pub struct WatchlistImport {
+    pub filters: Vec<serde_json::Value>,
+    /// Replace the current filters instead of adding to them
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// Watchlist entry to create or replace
+#[derive(Debug, Deserialize)]
+pub struct WatchlistRequest {
+    pub name: String,
+    /// Search query, with `+term`/`-term` keywords
+    pub query: String,
+    /// Size bounds in bytes
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub bot: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default = "default_watch_interval")]
+    pub interval_mins: u32,
+    #[serde(default = "default_watch_enabled")]
+    pub enabled: bool,
+}
+
+fn default_watch_interval() -> u32 {
+    60
+}
+
+fn default_watch_enabled() -> bool {
+    true
 }
 
-fn default_history_page() -> i64 {
-    1
+/// Watchlist entry with the files it found most recently
+#[derive(Debug, Serialize)]
+pub struct WatchlistDetail {
+    #[serde(flatten)]
+    pub entry: crate::db::WatchlistEntry,
+    pub hits: Vec<crate::db::WatchlistHit>,
 }
-fn default_history_limit() -> usize {
-    100
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SelfTestRequest {
+    /// Configured network to connect to and register on
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Also download `speedtest_url` to measure throughput
+    #[serde(default)]
+    pub speedtest: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationTestRequest {
+    /// Target to test, even if disabled; all enabled targets when unset
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Outcome of a test notification for one target
+#[derive(Debug, Serialize)]
+pub struct NotificationTestResult {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    /// Strip file names and replace bot names with placeholders
+    #[serde(default)]
+    pub redact: bool,
+    /// Most recent downloads to include
+    #[serde(default = "default_export_limit")]
+    pub limit: i64,
+}
+
+fn default_export_limit() -> i64 {
+    1000
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,18 +407,30 @@ pub struct DeleteHistoryParams {
 }
 
 #[derive(Debug, Deserialize)]
-pub struct PaginationParams {
-    #[serde(default = "default_page")]
-    pub page: i64,
-    #[serde(default = "default_limit")]
+pub struct RedownloadParams {
+    /// Search for the file again and use its current source
+    #[serde(default)]
+    pub resolve: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopQueriesParams {
+    #[serde(default = "default_top_queries_limit")]
     pub limit: i64,
 }
 
-fn default_page() -> i64 {
-    1
+#[derive(Debug, Deserialize)]
+pub struct TrafficParams {
+    #[serde(default = "default_traffic_days")]
+    pub days: i64,
+}
+
+fn default_traffic_days() -> i64 {
+    30
 }
-fn default_limit() -> i64 {
-    20
+
+fn default_top_queries_limit() -> i64 {
+    10
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,23 +452,103 @@ pub struct UpdateSettingsRequest {
     pub general_timeout: Option<u64>,
     pub proxy_enabled: Option<bool>,
     pub proxy_url: Option<String>,
+    pub require_proxy: Option<bool>,
     pub nickname: Option<String>,
     pub username: Option<String>,
     pub realname: Option<String>,
+    pub irc_idle_timeout_mins: Option<u64>,
+    pub irc_max_connections: Option<usize>,
+    pub shared_irc_connections: Option<bool>,
+    pub shared_irc_idle_secs: Option<u64>,
+    pub http_fallback: Option<bool>,
+    pub http_fallback_after: Option<u32>,
     pub max_retries: Option<u32>,
     pub retry_delay: Option<u64>,
     pub queue_limit: Option<u32>,
+    pub max_download_speed: Option<u64>,
+    pub monthly_quota_gb: Option<u64>,
+    pub request_gap_secs: Option<u64>,
+    pub max_channels_per_network: Option<usize>,
+    pub pending_research_mins: Option<u64>,
+    pub queue_warmup_secs: Option<u64>,
+    pub source_policy: Option<String>,
     pub passive_dcc: Option<bool>,
     pub dcc_port_min: Option<u16>,
     pub dcc_port_max: Option<u16>,
+    pub dcc_external_ip: Option<String>,
     pub resume_enabled: Option<bool>,
+    pub xdcc_info_check: Option<bool>,
+    pub xdcc_info_abort_on_mismatch: Option<bool>,
+    pub outbound_bind: Option<String>,
+    pub turbo_dcc_bots: Option<Vec<String>>,
+    pub dcc_connect_timeout: Option<u64>,
+    pub dcc_connect_retries: Option<u32>,
+    pub manual_offer_approval: Option<bool>,
     pub enabled_providers: Option<Vec<String>>,
     pub results_per_page: Option<u32>,
     pub search_timeout: Option<u64>,
+    pub search_max_pages: Option<u32>,
+    pub search_max_results: Option<usize>,
+    pub search_page_delay_ms: Option<u64>,
+    pub search_user_agent: Option<String>,
+    pub search_normalize: Option<bool>,
+    pub provider_settings: Option<HashMap<String, ProviderSettings>>,
+    pub external_providers: Option<HashMap<String, String>>,
+    pub provider_block_backoff_mins: Option<u64>,
+    pub search_history_merge_window_mins: Option<u64>,
+    pub search_results_max_kb: Option<u64>,
+    pub search_results_retention_days: Option<u64>,
+    pub trash_retention_days: Option<u64>,
+    pub event_compress_after_days: Option<u64>,
+    pub db_maintenance_weekly: Option<bool>,
+    pub anime_mappings: Option<Vec<AnimeMapping>>,
+    pub anime_mapping_file: Option<String>,
     pub networks: Option<HashMap<String, NetworkConfig>>,
+    pub network_fallback_hosts: Option<Vec<String>>,
+    pub templates: Option<HashMap<String, DownloadTemplate>>,
+    pub filename_policy: Option<String>,
+    pub min_file_size_mb: Option<u64>,
+    pub max_file_size_mb: Option<u64>,
+    pub alternate_download_dir: Option<String>,
     pub move_completed: Option<bool>,
     pub move_completed_dir: Option<String>,
+    pub move_retries: Option<u32>,
+    pub rclone_path: Option<String>,
+    pub verify_checksums: Option<bool>,
+    pub quarantine_patterns: Option<Vec<String>>,
+    pub av_scan_command: Option<String>,
+    pub quarantine_dir: Option<String>,
     pub postprocess_script_enabled: Option<bool>,
     pub postprocess_script: Option<String>,
     pub postprocess_timeout: Option<u64>,
+    pub postprocess_steps: Option<Vec<crate::postprocess::PostprocessStep>>,
+    pub extract_delete_archives: Option<bool>,
+    pub categories: Option<HashMap<String, CategoryConfig>>,
+    pub package_jobs: Option<bool>,
+    pub package_dir: Option<String>,
+    pub package_template: Option<String>,
+    pub package_manifest: Option<bool>,
+    pub script_hooks: Option<HashMap<String, String>>,
+    pub heartbeat_url: Option<String>,
+    pub heartbeat_interval_mins: Option<u64>,
+    pub alert_webhook_url: Option<String>,
+    pub failure_alert_threshold: Option<u32>,
+    pub notifications: Option<Vec<NotificationTarget>>,
+    pub failure_bundles: Option<bool>,
+    pub failure_bundle_dir: Option<String>,
+    pub thumbnails: Option<bool>,
+    pub thumbnail_dir: Option<String>,
+    pub ffmpeg_path: Option<String>,
+    pub eta_notify_mins: Option<u64>,
+    pub speedtest_url: Option<String>,
+    pub speedtest_max_secs: Option<u64>,
+    pub api_key: Option<String>,
+    pub disabled_api_groups: Option<Vec<String>>,
+    pub sonarr_url: Option<String>,
+    pub sonarr_api_key: Option<String>,
+    pub radarr_url: Option<String>,
+    pub radarr_api_key: Option<String>,
+    pub arr_dedupe_mode: Option<String>,
+    pub tmdb_api_key: Option<String>,
+    pub asset_cache_secs: Option<u64>,
 }
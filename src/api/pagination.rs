@@ -0,0 +1,125 @@
+//! List Pagination
+//!
+//! List endpoints take a `page` (counted from 1) and a `limit` query
+//! parameter and describe the whole list in the `X-Total-Count`, `X-Page`,
+//! `X-Limit` and `X-Total-Pages` response headers. Limits are capped at
+//! [`MAX_LIMIT`] so a single request can't ask for an unbounded response;
+//! out of range values are clamped rather than refused.
+
+use axum::http::HeaderName;
+use serde::Deserialize;
+
+/// Largest page any list endpoint returns
+pub const MAX_LIMIT: i64 = 500;
+
+pub const TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+pub const PAGE: HeaderName = HeaderName::from_static("x-page");
+pub const LIMIT: HeaderName = HeaderName::from_static("x-limit");
+pub const TOTAL_PAGES: HeaderName = HeaderName::from_static("x-total-pages");
+
+/// `page` and `limit` query parameters, read next to an endpoint's own ones
+#[derive(Debug, Default, Deserialize)]
+pub struct PageParams {
+    #[serde(default)]
+    pub page: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+impl PageParams {
+    /// Page asked for, with `default_limit` items when no limit was given
+    pub fn page(&self, default_limit: i64) -> Page {
+        Page {
+            page: self.page.unwrap_or(1).max(1),
+            limit: self.limit.unwrap_or(default_limit).clamp(1, MAX_LIMIT),
+        }
+    }
+}
+
+/// Validated page of a list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub page: i64,
+    pub limit: i64,
+}
+
+impl Page {
+    /// Items before this page
+    pub fn offset(&self) -> i64 {
+        (self.page - 1).saturating_mul(self.limit)
+    }
+
+    /// Items of this page out of the whole list
+    pub fn slice<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset() as usize)
+            .take(self.limit as usize)
+            .collect()
+    }
+
+    /// Pages of a list of `total` items
+    pub fn total_pages(&self, total: i64) -> i64 {
+        (total + self.limit - 1) / self.limit
+    }
+
+    /// Headers describing this page of a list of `total` items
+    pub fn headers(&self, total: i64) -> [(HeaderName, String); 4] {
+        [
+            (TOTAL_COUNT, total.to_string()),
+            (PAGE, self.page.to_string()),
+            (LIMIT, self.limit.to_string()),
+            (TOTAL_PAGES, self.total_pages(total).to_string()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page() {
+        let params = PageParams {
+            page: Some(0),
+            limit: Some(100_000),
+        };
+        let page = params.page(20);
+        assert_eq!(
+            page,
+            Page {
+                page: 1,
+                limit: MAX_LIMIT
+            }
+        );
+        assert_eq!(PageParams::default().page(20).limit, 20);
+        assert_eq!(
+            PageParams {
+                page: None,
+                limit: Some(-5)
+            }
+            .page(20)
+            .limit,
+            1
+        );
+
+        let page = Page { page: 2, limit: 3 };
+        assert_eq!(page.slice((1..=8).collect()), vec![4, 5, 6]);
+        assert!(Page { page: 4, limit: 3 }
+            .slice((1..=8).collect::<Vec<_>>())
+            .is_empty());
+        assert_eq!(
+            Page {
+                page: i64::MAX,
+                limit: MAX_LIMIT
+            }
+            .offset(),
+            i64::MAX
+        );
+
+        let headers = page.headers(8);
+        assert_eq!(headers[0], (TOTAL_COUNT, "8".to_string()));
+        assert_eq!(headers[3], (TOTAL_PAGES, "3".to_string()));
+        assert_eq!(page.total_pages(0), 0);
+    }
+}
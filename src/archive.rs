@@ -0,0 +1,549 @@
+//! Archive Extraction
+//!
+//! Unpacks completed downloads for the `extract` postprocess step. Packs
+//! often come as multi-volume RAR (`.part1.rar`, or `.rar` with `.r00`...)
+//! or split 7z/zip (`.7z.001`) sets, one volume per transfer, so every volume
+//! that completes tries its set and the set is unpacked once all volumes are
+//! there. Zip files are read with the built-in zip library; RAR, 7z and tar
+//! archives need `unrar`, 7-Zip or `tar`, looked up on the PATH.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+use tokio::process::Command;
+
+/// Sets being unpacked and sets unpacked so far, keyed by their first volume
+#[derive(Default)]
+struct State {
+    /// One lock per set being unpacked, so two volumes finishing together
+    /// don't unpack into the same directory while other sets go ahead
+    running: HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>,
+    /// Modification time of the first volume when the set was unpacked, which
+    /// tells a set downloaded again apart from the one unpacked before.
+    /// Entries whose first volume is gone are pruned, as `extract` reports
+    /// those from the output directory.
+    done: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(Mutex::default);
+
+/// Holds a set's lock and drops its entry once no other volume waits on it
+struct SetGuard {
+    first: PathBuf,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl SetGuard {
+    async fn acquire(first: &Path) -> Self {
+        let lock = {
+            let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+            state
+                .running
+                .entry(first.to_path_buf())
+                .or_default()
+                .clone()
+        };
+        let guard = lock.clone().lock_owned().await;
+        Self {
+            first: first.to_path_buf(),
+            lock,
+            guard: Some(guard),
+        }
+    }
+}
+
+impl Drop for SetGuard {
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        // The map and this guard hold the only references
+        if Arc::strong_count(&self.lock) <= 2 {
+            state.running.remove(&self.first);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Rar,
+    SevenZip,
+    Tar,
+}
+
+/// An archive, possibly spread over several volumes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveSet {
+    pub format: Format,
+    /// Volume extraction starts from
+    pub first: PathBuf,
+    /// Name shared by the volumes, used for the output directory
+    pub stem: String,
+    pub multi_volume: bool,
+}
+
+/// What the extract step did with a file
+#[derive(Debug, PartialEq)]
+pub enum Extracted {
+    /// Unpacked into `dir`; continue with its largest file
+    Done {
+        dir: PathBuf,
+        largest: PathBuf,
+        files: usize,
+    },
+    /// A volume of a set that can't be unpacked yet
+    Waiting(String),
+    /// Unpacked earlier along with another volume of its set
+    AlreadyDone,
+}
+
+/// Strip an ASCII suffix, ignoring case
+fn strip_suffix_ci<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let split = name.len().checked_sub(suffix.len())?;
+    (name.is_char_boundary(split) && name[split..].eq_ignore_ascii_case(suffix))
+        .then(|| &name[..split])
+}
+
+/// Split `name.<tag><digits>` into `name`, the tag as written and the digits
+fn split_numbered<'a>(name: &'a str, tag: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    let digits_at = name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let digits = &name[digits_at..];
+    let rest = &name[..digits_at];
+    if digits.is_empty() {
+        return None;
+    }
+    let stem = strip_suffix_ci(rest, tag)?;
+    Some((stem, &rest[stem.len()..], digits))
+}
+
+/// Work out the archive set a file belongs to, or `None` when it isn't an archive
+pub fn detect(path: &Path) -> Option<ArchiveSet> {
+    let name = path.file_name()?.to_str()?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let set = |format, stem: &str, first: String, multi_volume| ArchiveSet {
+        format,
+        first: dir.join(first),
+        stem: stem.to_string(),
+        multi_volume,
+    };
+
+    if let Some(base) = strip_suffix_ci(name, ".rar") {
+        let ext = &name[base.len()..];
+        // name.part01.rar, name.part02.rar...
+        if let Some((stem, tag, digits)) = split_numbered(base, ".part") {
+            let first = format!("{}{}{:0width$}{}", stem, tag, 1, ext, width = digits.len());
+            return Some(set(Format::Rar, stem, first, true));
+        }
+        // name.rar followed by name.r00, name.r01...
+        let multi = dir.join(format!("{}.r00", base)).exists()
+            || dir.join(format!("{}.R00", base)).exists();
+        return Some(set(Format::Rar, base, name.to_string(), multi));
+    }
+    if let Some((stem, tag, digits)) = split_numbered(name, ".r") {
+        if digits.len() == 2 {
+            let ext = if tag == ".R" { ".RAR" } else { ".rar" };
+            return Some(set(Format::Rar, stem, format!("{}{}", stem, ext), true));
+        }
+    }
+    // name.7z.001, name.zip.001...
+    if let Some((base, _, digits)) = split_numbered(name, ".") {
+        for ext in [".7z", ".zip"] {
+            if let Some(stem) = strip_suffix_ci(base, ext) {
+                let first = format!("{}.{:0width$}", base, 1, width = digits.len());
+                return Some(set(Format::SevenZip, stem, first, true));
+            }
+        }
+    }
+    let single = [
+        (".zip", Format::Zip),
+        (".7z", Format::SevenZip),
+        (".tar.gz", Format::Tar),
+        (".tar.bz2", Format::Tar),
+        (".tar.xz", Format::Tar),
+        (".tgz", Format::Tar),
+        (".tar", Format::Tar),
+    ];
+    single.iter().find_map(|(ext, format)| {
+        strip_suffix_ci(name, ext).map(|stem| set(*format, stem, name.to_string(), false))
+    })
+}
+
+/// Files in the set's directory that are volumes of it
+pub fn volumes(set: &ArchiveSet) -> Vec<PathBuf> {
+    if !set.multi_volume {
+        return vec![set.first.clone()];
+    }
+    let dir = set.first.parent().unwrap_or(Path::new("."));
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && detect(p).is_some_and(|s| s.first == set.first))
+        .collect()
+}
+
+/// Position of a volume within its set: `.rar` before `.r00`, `.part1.rar`
+/// and `.001` first otherwise
+fn volume_number(path: &Path) -> Option<u32> {
+    let name = path.file_name()?.to_str()?;
+    if let Some(base) = strip_suffix_ci(name, ".rar") {
+        return match split_numbered(base, ".part") {
+            Some((_, _, digits)) => digits.parse().ok(),
+            None => Some(0),
+        };
+    }
+    if let Some((_, _, digits)) = split_numbered(name, ".r") {
+        return digits.parse::<u32>().ok().map(|n| n + 1);
+    }
+    split_numbered(name, ".").and_then(|(_, _, digits)| digits.parse().ok())
+}
+
+/// Whether the volumes on disk leave a gap before the last one. A missing
+/// last volume only shows in the extractor's output.
+fn has_gap(volumes: &[PathBuf]) -> bool {
+    let numbers: BTreeSet<u32> = volumes.iter().filter_map(|v| volume_number(v)).collect();
+    match (numbers.first(), numbers.last()) {
+        (Some(first), Some(last)) => (last - first + 1) as usize != numbers.len(),
+        _ => false,
+    }
+}
+
+/// Whether the extractor failed because a volume after the ones on disk is missing
+fn missing_volume(error: &str) -> bool {
+    let error = error.to_ascii_lowercase();
+    [
+        "cannot find volume",
+        "missing volume",
+        "unexpected end of archive",
+        "unexpected end of data",
+        "need to start extraction from a previous volume",
+    ]
+    .iter()
+    .any(|m| error.contains(m))
+}
+
+/// First executable of `names` found on the PATH
+pub fn find_tool(names: &[&str]) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        names.iter().find_map(|name| {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Some(path);
+            }
+            let exe = dir.join(format!("{}.exe", name));
+            exe.is_file().then_some(exe)
+        })
+    })
+}
+
+const SEVEN_ZIP: &[&str] = &["7z", "7zz", "7za"];
+
+/// External command unpacking `first` into `dest`
+fn command(format: Format, first: &Path, dest: &Path) -> Result<Command, String> {
+    let (first, dest) = (first.as_os_str(), dest.as_os_str());
+    let seven_zip = |tool: PathBuf| {
+        let mut cmd = Command::new(tool);
+        let mut out = std::ffi::OsString::from("-o");
+        out.push(dest);
+        cmd.arg("x").arg("-y").arg(out).arg(first);
+        cmd
+    };
+    match format {
+        Format::Rar => {
+            if let Some(unrar) = find_tool(&["unrar"]) {
+                let mut dest = dest.to_os_string();
+                dest.push("/");
+                let mut cmd = Command::new(unrar);
+                cmd.args(["x", "-o+", "-y"]).arg(first).arg(dest);
+                Ok(cmd)
+            } else {
+                find_tool(SEVEN_ZIP)
+                    .map(seven_zip)
+                    .ok_or_else(|| "Extracting RAR archives needs unrar or 7-Zip (7z)".to_string())
+            }
+        }
+        Format::SevenZip => find_tool(SEVEN_ZIP)
+            .map(seven_zip)
+            .ok_or_else(|| "Extracting 7z archives needs 7-Zip (7z, 7zz or 7za)".to_string()),
+        Format::Zip => {
+            if let Some(unzip) = find_tool(&["unzip"]) {
+                let mut cmd = Command::new(unzip);
+                cmd.arg("-o").arg(first).arg("-d").arg(dest);
+                Ok(cmd)
+            } else {
+                find_tool(SEVEN_ZIP)
+                    .map(seven_zip)
+                    .ok_or_else(|| "No unzip or 7-Zip to fall back on".to_string())
+            }
+        }
+        Format::Tar => {
+            let tar = find_tool(&["tar", "bsdtar"])
+                .ok_or_else(|| "Extracting tar archives needs tar".to_string())?;
+            let mut cmd = Command::new(tar);
+            cmd.arg("-xf").arg(first).arg("-C").arg(dest);
+            Ok(cmd)
+        }
+    }
+}
+
+async fn run_command(mut cmd: Command) -> Result<(), String> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run extractor: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let message = if stderr.trim().is_empty() {
+        stdout
+    } else {
+        stderr
+    };
+    // The last lines say what went wrong
+    let lines: Vec<&str> = message.trim().lines().collect();
+    Err(format!(
+        "Extraction failed: {}",
+        lines[lines.len().saturating_sub(3)..].join(" ")
+    ))
+}
+
+/// Unpack with the zip library, then with an external tool if the library
+/// can't read the archive (e.g. an unsupported compression method)
+async fn extract_zip(first: &Path, dest: &Path) -> Result<(), String> {
+    let (file, dir) = (first.to_path_buf(), dest.to_path_buf());
+    let library = tokio::task::spawn_blocking(move || {
+        let reader = std::fs::File::open(&file).map_err(|e| e.to_string())?;
+        zip::ZipArchive::new(reader)
+            .and_then(|mut archive| archive.extract(&dir))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match library {
+        Ok(()) => Ok(()),
+        Err(e) => match command(Format::Zip, first, dest) {
+            Ok(cmd) => run_command(cmd).await,
+            Err(_) => Err(format!("Extraction failed: {}", e)),
+        },
+    }
+}
+
+/// Largest regular file below a directory, and how many files there are,
+/// or `None` when there are no files
+fn largest_file(dir: &Path) -> Result<Option<(PathBuf, usize)>, String> {
+    let mut best: Option<(u64, PathBuf)> = None;
+    let mut files = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            std::fs::read_dir(&dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+            let path = entry.path();
+            let meta = std::fs::symlink_metadata(&path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            if meta.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            files += 1;
+            if best.as_ref().is_none_or(|(size, _)| meta.len() > *size) {
+                best = Some((meta.len(), path));
+            }
+        }
+    }
+    Ok(best.map(|(_, path)| (path, files)))
+}
+
+/// Unpack the set `path` belongs to into a directory named after it, or
+/// return `None` when the file isn't an archive. With `delete_archives` the
+/// volumes are removed once unpacked.
+pub async fn extract(path: &Path, delete_archives: bool) -> Result<Option<Extracted>, String> {
+    let Some(set) = detect(path) else {
+        return Ok(None);
+    };
+    let dest = set.first.parent().unwrap_or(Path::new(".")).join(&set.stem);
+
+    let _set_lock = SetGuard::acquire(&set.first).await;
+    let Ok(meta) = tokio::fs::metadata(&set.first).await else {
+        if dest.is_dir() {
+            return Ok(Some(Extracted::AlreadyDone));
+        }
+        let name = set.first.file_name().unwrap_or_default().to_string_lossy();
+        return Ok(Some(Extracted::Waiting(format!("Waiting for {}", name))));
+    };
+    let modified = meta.modified().ok();
+    {
+        let state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        if state.done.get(&set.first) == Some(&modified) {
+            return Ok(Some(Extracted::AlreadyDone));
+        }
+    }
+    if set.multi_volume && has_gap(&volumes(&set)) {
+        return Ok(Some(Extracted::Waiting(
+            "Waiting for the remaining volumes".to_string(),
+        )));
+    }
+
+    // Only a directory made here is removed again when unpacking fails
+    let created = !dest.exists();
+    tokio::fs::create_dir_all(&dest)
+        .await
+        .map_err(|e| format!("Cannot create {}: {}", dest.display(), e))?;
+    let outcome = match set.format {
+        Format::Zip => extract_zip(&set.first, &dest).await,
+        format => match command(format, &set.first, &dest) {
+            Ok(cmd) => run_command(cmd).await,
+            Err(e) => Err(e),
+        },
+    }
+    .and_then(|()| largest_file(&dest)?.ok_or_else(|| "Archive was empty".to_string()));
+    let (largest, files) = match outcome {
+        Ok(found) => found,
+        Err(e) => {
+            if created {
+                if let Err(e) = tokio::fs::remove_dir_all(&dest).await {
+                    tracing::warn!("Failed to remove {}: {}", dest.display(), e);
+                }
+            }
+            // The last volume to complete unpacks the set
+            if set.multi_volume && missing_volume(&e) {
+                return Ok(Some(Extracted::Waiting(format!(
+                    "Waiting for the remaining volumes ({})",
+                    e
+                ))));
+            }
+            return Err(e);
+        }
+    };
+
+    {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        state.done.retain(|first, _| first.exists());
+        state.done.insert(set.first.clone(), modified);
+    }
+    if delete_archives {
+        for volume in volumes(&set) {
+            if let Err(e) = tokio::fs::remove_file(&volume).await {
+                tracing::warn!("Failed to delete {}: {}", volume.display(), e);
+            }
+        }
+    }
+    Ok(Some(Extracted::Done {
+        dir: dest,
+        largest,
+        files,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect() {
+        let dir = Path::new("/dl");
+        let detect = |name: &str| detect(&dir.join(name));
+
+        let part = detect("Show.S01.part02.rar").unwrap();
+        assert_eq!(part.format, Format::Rar);
+        assert_eq!(part.first, dir.join("Show.S01.part01.rar"));
+        assert_eq!(part.stem, "Show.S01");
+        assert!(part.multi_volume);
+
+        let old_style = detect("Movie.r07").unwrap();
+        assert_eq!(old_style.first, dir.join("Movie.rar"));
+        assert!(old_style.multi_volume);
+
+        let split = detect("Pack.7z.003").unwrap();
+        assert_eq!(split.format, Format::SevenZip);
+        assert_eq!(split.first, dir.join("Pack.7z.001"));
+
+        let zip = detect("Album.ZIP").unwrap();
+        assert_eq!((zip.format, zip.stem.as_str()), (Format::Zip, "Album"));
+        assert!(!zip.multi_volume);
+        assert_eq!(detect("Backup.tar.gz").unwrap().stem, "Backup");
+
+        assert!(detect("Show.S01E01.mkv").is_none());
+        assert!(detect("Episode.720").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_extract_zip() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = temp_dir.path().join("Album.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("cover.jpg", options).unwrap();
+        zip.write_all(b"jpg").unwrap();
+        zip.start_file("disc/01 Track.flac", options).unwrap();
+        zip.write_all(b"a longer flac file").unwrap();
+        zip.finish().unwrap();
+
+        let Some(Extracted::Done {
+            dir,
+            largest,
+            files,
+        }) = extract(&archive, true).await.unwrap()
+        else {
+            panic!("zip was not extracted");
+        };
+        assert_eq!(dir, temp_dir.path().join("Album"));
+        assert_eq!(largest, dir.join("disc/01 Track.flac"));
+        assert_eq!(files, 2);
+        assert!(!archive.exists());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_volume() {
+        let temp_dir = TempDir::new().unwrap();
+        let second = temp_dir.path().join("Show.part2.rar");
+        std::fs::write(&second, "volume").unwrap();
+
+        let outcome = extract(&second, true).await.unwrap();
+        assert_eq!(
+            outcome,
+            Some(Extracted::Waiting("Waiting for Show.part1.rar".to_string()))
+        );
+        assert!(second.exists());
+    }
+
+    #[test]
+    fn test_volume_gaps() {
+        let dir = Path::new("/dl");
+        let paths = |names: &[&str]| names.iter().map(|n| dir.join(n)).collect::<Vec<_>>();
+
+        assert!(!has_gap(&paths(&["Show.part1.rar", "Show.part2.rar"])));
+        assert!(has_gap(&paths(&["Show.part1.rar", "Show.part3.rar"])));
+        assert!(!has_gap(&paths(&["Movie.rar", "Movie.r00", "Movie.r01"])));
+        assert!(has_gap(&paths(&["Movie.rar", "Movie.r01"])));
+        assert!(has_gap(&paths(&["Pack.7z.001", "Pack.7z.003"])));
+
+        assert!(missing_volume(
+            "Extraction failed: Cannot find volume Show.part3.rar"
+        ));
+        assert!(!missing_volume("Extraction failed: CRC failed in Show.mkv"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_extraction() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = temp_dir.path().join("Broken.zip");
+        std::fs::write(&archive, "not a zip").unwrap();
+
+        assert!(extract(&archive, true).await.is_err());
+        assert!(!temp_dir.path().join("Broken").exists());
+        assert!(archive.exists());
+    }
+}
@@ -0,0 +1,343 @@
+//! *arr Library Module
+//!
+//! Looks up releases in Sonarr/Radarr so search results and auto-grabs for
+//! things that are already in the library can be flagged or skipped.
+
+use crate::config::AppConfig;
+use crate::library::{normalize_title, NamingScheme};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+/// How long library snapshots are cached
+const CACHE_TTL: Duration = Duration::from_secs(600);
+/// How long lookups against a Sonarr/Radarr that failed are skipped
+const FAILURE_TTL: Duration = Duration::from_secs(60);
+
+/// What to do with results that are already in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeMode {
+    Off,
+    Flag,
+    Filter,
+}
+
+impl DedupeMode {
+    pub fn from_config(config: &AppConfig) -> Self {
+        match config.arr_dedupe_mode.as_str() {
+            "flag" => Self::Flag,
+            "filter" => Self::Filter,
+            _ => Self::Off,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SonarrSeries {
+    id: i64,
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SonarrEpisode {
+    season_number: u32,
+    episode_number: u32,
+    #[serde(default)]
+    has_file: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RadarrMovie {
+    title: String,
+    #[serde(default)]
+    year: u32,
+    #[serde(default)]
+    has_file: bool,
+}
+
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.fetched_at.elapsed() < CACHE_TTL
+    }
+}
+
+type CachedSlot<T> = RwLock<Option<Cached<T>>>;
+type EpisodeCache = RwLock<HashMap<i64, Cached<HashSet<(u32, u32)>>>>;
+
+/// Cached view of the Sonarr/Radarr libraries
+pub struct ArrLibrary {
+    client: reqwest::Client,
+    scheme: NamingScheme,
+    movie_re: Regex,
+    /// Normalized series title -> Sonarr series id
+    series: CachedSlot<HashMap<String, i64>>,
+    /// Sonarr series id -> downloaded (season, episode) pairs
+    episodes: EpisodeCache,
+    /// Downloaded movies as (normalized title, year)
+    movies: CachedSlot<HashSet<(String, u32)>>,
+    /// Base URLs whose last request failed, and when
+    failed: RwLock<HashMap<String, Instant>>,
+    /// Held while refreshing a snapshot, so concurrent lookups fetch it once
+    refresh: Mutex<()>,
+}
+
+/// Why a lookup couldn't be answered
+enum LookupError {
+    Failed(String),
+    /// The service failed moments ago and isn't asked again yet
+    HeldBack,
+}
+
+impl ArrLibrary {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+            scheme: NamingScheme::default(),
+            movie_re: Regex::new(r"^(?P<title>.+?)[ ._(\[-]+(?P<year>(?:19|20)\d{2})\b").unwrap(),
+            series: RwLock::new(None),
+            episodes: RwLock::new(HashMap::new()),
+            movies: RwLock::new(None),
+            failed: RwLock::new(HashMap::new()),
+            refresh: Mutex::new(()),
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        base: &str,
+        api_key: &str,
+        path: &str,
+    ) -> Result<T, LookupError> {
+        if self
+            .failed
+            .read()
+            .await
+            .get(base)
+            .is_some_and(|at| at.elapsed() < FAILURE_TTL)
+        {
+            return Err(LookupError::HeldBack);
+        }
+        let url = format!("{}{}", base.trim_end_matches('/'), path);
+        let result = match self
+            .client
+            .get(&url)
+            .header("X-Api-Key", api_key)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => response.json().await,
+            Err(e) => Err(e),
+        };
+        let mut failed = self.failed.write().await;
+        match result {
+            Ok(value) => {
+                failed.remove(base);
+                Ok(value)
+            }
+            Err(e) => {
+                failed.insert(base.to_string(), Instant::now());
+                Err(LookupError::Failed(format!("{}: {}", url, e)))
+            }
+        }
+    }
+
+    /// Check whether a release is already downloaded in Sonarr or Radarr.
+    /// Returns the name of the service that has it.
+    pub async fn find(&self, config: &AppConfig, filename: &str) -> Option<&'static str> {
+        if let Some(ep) = self.scheme.parse(filename) {
+            if config.sonarr_url.is_empty() {
+                return None;
+            }
+            let title = normalize_title(self.scheme.title(filename).unwrap_or_default());
+            return match self
+                .has_episode(config, &title, ep.season, ep.episode)
+                .await
+            {
+                Ok(true) => Some("Sonarr"),
+                Ok(false) | Err(LookupError::HeldBack) => None,
+                Err(LookupError::Failed(e)) => {
+                    tracing::warn!("Sonarr lookup failed: {}", e);
+                    None
+                }
+            };
+        }
+
+        if config.radarr_url.is_empty() {
+            return None;
+        }
+        let caps = self.movie_re.captures(filename)?;
+        let title = normalize_title(&caps["title"]);
+        let year: u32 = caps["year"].parse().ok()?;
+        match self.has_movie(config, &title, year).await {
+            Ok(true) => Some("Radarr"),
+            Ok(false) | Err(LookupError::HeldBack) => None,
+            Err(LookupError::Failed(e)) => {
+                tracing::warn!("Radarr lookup failed: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn has_episode(
+        &self,
+        config: &AppConfig,
+        title: &str,
+        season: u32,
+        episode: u32,
+    ) -> Result<bool, LookupError> {
+        let series_stale = || async {
+            !self
+                .series
+                .read()
+                .await
+                .as_ref()
+                .is_some_and(|c| c.is_fresh())
+        };
+        if series_stale().await {
+            let _refresh = self.refresh.lock().await;
+            if series_stale().await {
+                let list: Vec<SonarrSeries> = self
+                    .get_json(&config.sonarr_url, &config.sonarr_api_key, "/api/v3/series")
+                    .await?;
+                let map = list
+                    .into_iter()
+                    .map(|s| (normalize_title(&s.title), s.id))
+                    .collect();
+                *self.series.write().await = Some(Cached::new(map));
+            }
+        }
+
+        let Some(series_id) = self
+            .series
+            .read()
+            .await
+            .as_ref()
+            .and_then(|c| c.value.get(title).copied())
+        else {
+            return Ok(false);
+        };
+
+        let episodes_stale = || async {
+            !self
+                .episodes
+                .read()
+                .await
+                .get(&series_id)
+                .is_some_and(|c| c.is_fresh())
+        };
+        if episodes_stale().await {
+            let _refresh = self.refresh.lock().await;
+            if episodes_stale().await {
+                let list: Vec<SonarrEpisode> = self
+                    .get_json(
+                        &config.sonarr_url,
+                        &config.sonarr_api_key,
+                        &format!("/api/v3/episode?seriesId={}", series_id),
+                    )
+                    .await?;
+                let set = list
+                    .into_iter()
+                    .filter(|e| e.has_file)
+                    .map(|e| (e.season_number, e.episode_number))
+                    .collect();
+                self.episodes
+                    .write()
+                    .await
+                    .insert(series_id, Cached::new(set));
+            }
+        }
+
+        Ok(self
+            .episodes
+            .read()
+            .await
+            .get(&series_id)
+            .is_some_and(|c| c.value.contains(&(season, episode))))
+    }
+
+    async fn has_movie(
+        &self,
+        config: &AppConfig,
+        title: &str,
+        year: u32,
+    ) -> Result<bool, LookupError> {
+        let stale = || async {
+            !self
+                .movies
+                .read()
+                .await
+                .as_ref()
+                .is_some_and(|c| c.is_fresh())
+        };
+        if stale().await {
+            let _refresh = self.refresh.lock().await;
+            if stale().await {
+                let list: Vec<RadarrMovie> = self
+                    .get_json(&config.radarr_url, &config.radarr_api_key, "/api/v3/movie")
+                    .await?;
+                let set = list
+                    .into_iter()
+                    .filter(|m| m.has_file)
+                    .map(|m| (normalize_title(&m.title), m.year))
+                    .collect();
+                *self.movies.write().await = Some(Cached::new(set));
+            }
+        }
+
+        Ok(self
+            .movies
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|c| c.value.contains(&(title.to_string(), year))))
+    }
+}
+
+/// Check an auto-grab candidate against the *arr libraries when de-duplication is enabled
+pub async fn already_in_library(
+    config: &tokio::sync::RwLock<AppConfig>,
+    library: &ArrLibrary,
+    filename: Option<&str>,
+) -> bool {
+    let Some(filename) = filename else {
+        return false;
+    };
+    let config = config.read().await.clone();
+    if DedupeMode::from_config(&config) == DedupeMode::Off {
+        return false;
+    }
+    match library.find(&config, filename).await {
+        Some(service) => {
+            tracing::info!("Skipping {}: already in {}", filename, service);
+            true
+        }
+        None => false,
+    }
+}
+
+impl Default for ArrLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,157 @@
+//! Embedded Web UI
+//!
+//! Serves the frontend bundle built into the binary. File names that carry a
+//! content hash (`assets/index-B4x9Qk2e.js`) change whenever their content
+//! does, so browsers may cache them for `asset_cache_secs`; everything else,
+//! `index.html` in particular, is revalidated against its ETag on every load
+//! so a new release is picked up right away.
+//!
+//! Without the `web-ui` feature nothing is embedded and page requests get a
+//! JSON 503 pointing to the API.
+
+use crate::api::models::ErrorResponse;
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+#[cfg(feature = "web-ui")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "web/dist"]
+struct Asset;
+
+/// Whether the file name ends in a bundler content hash, e.g. `index-B4x9Qk2e.js`
+#[cfg(feature = "web-ui")]
+fn is_hashed(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let stem = name.split_once('.').map_or(name, |(stem, _)| stem);
+    let Some((_, hash)) = stem.rsplit_once('-') else {
+        return false;
+    };
+    hash.len() >= 8
+        && hash.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && (hash.chars().any(|c| c.is_ascii_digit())
+            || (hash.chars().any(|c| c.is_ascii_uppercase())
+                && hash.chars().any(|c| c.is_ascii_lowercase())))
+}
+
+#[cfg(feature = "web-ui")]
+fn cache_control(path: &str, max_age: u64) -> String {
+    if max_age > 0 && is_hashed(path) {
+        format!("public, max-age={}, immutable", max_age)
+    } else {
+        "no-cache".to_string()
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+/// Response for the embedded file at `path`, if there is one
+#[cfg(feature = "web-ui")]
+fn serve(path: &str, headers: &HeaderMap, max_age: u64) -> Option<Response> {
+    use axum::http::header;
+
+    let file = Asset::get(path)?;
+    let etag = format!(
+        "\"{}\"",
+        file.metadata
+            .sha256_hash()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    );
+    let cache = cache_control(path, max_age);
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if not_modified {
+        return Some(
+            (
+                StatusCode::NOT_MODIFIED,
+                [(header::ETAG, etag), (header::CACHE_CONTROL, cache)],
+            )
+                .into_response(),
+        );
+    }
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    Some(
+        (
+            [
+                (header::CONTENT_TYPE, mime.as_ref().to_string()),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache),
+            ],
+            file.data,
+        )
+            .into_response(),
+    )
+}
+
+#[cfg(not(feature = "web-ui"))]
+fn serve(_path: &str, _headers: &HeaderMap, _max_age: u64) -> Option<Response> {
+    None
+}
+
+pub async fn static_handler(
+    State(state): State<AppState>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    if path == "api" || path.starts_with("api/") {
+        return error(StatusCode::NOT_FOUND, "Unknown API endpoint");
+    }
+    let path = if path.is_empty() { "index.html" } else { path };
+    let max_age = state.config.read().await.asset_cache_secs;
+
+    if let Some(response) = serve(path, &headers, max_age) {
+        return response;
+    }
+    if path.contains('.') {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    // Fallback to index.html for SPA routing
+    serve("index.html", &headers, max_age).unwrap_or_else(|| {
+        error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The web UI is not included in this build; the API is available under /api",
+        )
+    })
+}
+
+#[cfg(all(test, feature = "web-ui"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_control() {
+        let year = 365 * 24 * 3600;
+        assert_eq!(
+            cache_control("assets/index-B4x9Qk2e.js", year),
+            "public, max-age=31536000, immutable"
+        );
+        assert!(is_hashed("assets/vendor-react-DkQ_abcd.css"));
+        assert!(is_hashed("assets/logo-1a2b3c4d.svg"));
+        assert!(!is_hashed("index.html"));
+        assert!(!is_hashed("assets/date-picker.js"));
+        assert!(!is_hashed("assets/my-component.js"));
+        assert_eq!(cache_control("index.html", year), "no-cache");
+        assert_eq!(cache_control("assets/index-B4x9Qk2e.js", 0), "no-cache");
+    }
+}
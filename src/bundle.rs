@@ -0,0 +1,117 @@
+//! Failure Bundle Module
+//!
+//! Packs everything needed to debug a failed transfer (event log, IRC lines
+//! seen during the attempt, a masked config snapshot and timings) into a zip
+//! that can be attached to a bug report.
+
+use crate::config::AppConfig;
+use crate::xdcc::XdccTransfer;
+use std::io::Write;
+use std::path::PathBuf;
+use zip::write::SimpleFileOptions;
+
+/// What goes into a bundle
+pub struct BundleInput<'a> {
+    pub transfer: &'a XdccTransfer,
+    pub retry_count: u32,
+    pub events: &'a [String],
+    pub irc: &'a [String],
+    pub config: &'a AppConfig,
+}
+
+/// Where the bundle of a transfer is stored
+pub fn bundle_path(config: &AppConfig, id: &str) -> PathBuf {
+    let id: String = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    PathBuf::from(&config.failure_bundle_dir).join(format!("{}.zip", id))
+}
+
+/// Build the zip archive of a failed transfer
+pub fn build_bundle(input: &BundleInput) -> zip::result::ZipResult<Vec<u8>> {
+    let transfer = input.transfer;
+    let timings = serde_json::json!({
+        "created_at": transfer.created_at,
+        "failed_at": transfer.updated_at,
+        "elapsed_secs": (transfer.updated_at - transfer.created_at).num_seconds(),
+        "retry_count": input.retry_count,
+        "downloaded_bytes": transfer.downloaded,
+        "size": transfer.size,
+        "last_speed": transfer.speed,
+    });
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    let mut add = |name: &str, content: &[u8]| -> zip::result::ZipResult<()> {
+        zip.start_file(name, options)?;
+        zip.write_all(content)?;
+        Ok(())
+    };
+
+    add(
+        "transfer.json",
+        &serde_json::to_vec_pretty(transfer).unwrap_or_default(),
+    )?;
+    add(
+        "timings.json",
+        &serde_json::to_vec_pretty(&timings).unwrap_or_default(),
+    )?;
+    add("events.log", input.events.join("\n").as_bytes())?;
+    add("irc.log", input.irc.join("\n").as_bytes())?;
+    add(
+        "config.json",
+        // Masked like the settings API shows it
+        &serde_json::to_vec_pretty(&input.config.masked()).unwrap_or_default(),
+    )?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xdcc::XdccUrl;
+    use std::io::Read;
+
+    #[test]
+    fn test_build_bundle_redacts_config() {
+        let config = AppConfig {
+            api_key: "hunter2".to_string(),
+            alert_webhook_url: "https://discord.com/api/webhooks/1/secret".to_string(),
+            ..Default::default()
+        };
+        let mut transfer = XdccTransfer::new(
+            "abc".to_string(),
+            XdccUrl {
+                network: "irc.rizon.net".to_string(),
+                channel: "#news".to_string(),
+                bot: "Bot".to_string(),
+                slot: 1,
+            },
+        );
+        transfer.error = Some("Connection refused".to_string());
+
+        let bytes = build_bundle(&BundleInput {
+            transfer: &transfer,
+            retry_count: 3,
+            events: &["Connecting".to_string()],
+            irc: &["-Bot- All Slots Full".to_string()],
+            config: &config,
+        })
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut snapshot = String::new();
+        archive
+            .by_name("config.json")
+            .unwrap()
+            .read_to_string(&mut snapshot)
+            .unwrap();
+        assert!(!snapshot.contains("hunter2"));
+        assert!(!snapshot.contains("webhooks"));
+        assert!(snapshot.contains("\"nickname\""));
+        assert!(archive.by_name("irc.log").is_ok());
+        assert!(archive.by_name("timings.json").is_ok());
+    }
+}
@@ -0,0 +1,225 @@
+//! Config Persistence
+//!
+//! Every settings change bumps a version, sent to clients as the `ETag` of
+//! the settings. A client that sends it back in `If-Match` has its change
+//! refused with 409 when someone else changed the settings in between,
+//! instead of silently overwriting their fields. The config file is written
+//! by a background task once changes have settled, not on the request path
+//! under the config lock, and flushed on shutdown. A failed write is retried
+//! with backoff until it succeeds.
+
+use crate::config::AppConfig;
+use axum::http::{header, HeaderMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify, RwLock};
+
+/// How long changes have to settle before the config file is written
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+/// First wait before writing again after a failed write; doubled per failure
+const RETRY_MIN: Duration = Duration::from_secs(1);
+/// Longest wait between retries
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct ConfigWriter {
+    version: Arc<AtomicU64>,
+    /// Version last written to the config file
+    saved: Arc<AtomicU64>,
+    dirty: Arc<Notify>,
+    config: Arc<RwLock<AppConfig>>,
+    path: String,
+    /// Held while writing, so a flush and the background task don't race
+    saving: Arc<Mutex<()>>,
+}
+
+impl ConfigWriter {
+    /// Start the task saving `config` to `path` after changes
+    pub fn spawn(config: Arc<RwLock<AppConfig>>, path: String) -> Self {
+        Self::with_timing(config, path, SAVE_DEBOUNCE, RETRY_MIN)
+    }
+
+    fn with_timing(
+        config: Arc<RwLock<AppConfig>>,
+        path: String,
+        debounce: Duration,
+        retry_min: Duration,
+    ) -> Self {
+        let writer = Self {
+            version: Arc::new(AtomicU64::new(1)),
+            saved: Arc::new(AtomicU64::new(1)),
+            dirty: Arc::new(Notify::new()),
+            config,
+            path,
+            saving: Arc::new(Mutex::new(())),
+        };
+        let task = writer.clone();
+        tokio::spawn(async move {
+            loop {
+                task.dirty.notified().await;
+                // Let a burst of changes settle into one write
+                while tokio::time::timeout(debounce, task.dirty.notified())
+                    .await
+                    .is_ok()
+                {}
+                let mut retry = retry_min;
+                while !task.flush().await {
+                    // A new change tries again right away
+                    let _ = tokio::time::timeout(retry, task.dirty.notified()).await;
+                    retry = (retry * 2).min(RETRY_MAX);
+                }
+            }
+        });
+        writer
+    }
+
+    /// Write changes not saved yet to the config file now. Returns false if
+    /// writing failed.
+    pub async fn flush(&self) -> bool {
+        let _saving = self.saving.lock().await;
+        // Versions only change under the config write lock
+        let (version, snapshot) = {
+            let config = self.config.read().await;
+            (self.version(), config.clone())
+        };
+        if version <= self.saved.load(Ordering::SeqCst) {
+            return true;
+        }
+        let path = self.path.clone();
+        match tokio::task::spawn_blocking(move || snapshot.save(&path)).await {
+            Ok(Ok(())) => {
+                self.saved.store(version, Ordering::SeqCst);
+                true
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to save config: {}", e);
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Config save task failed: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Current settings version
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// `ETag` of the current settings
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version())
+    }
+
+    /// Whether a request may change the settings: it sent no `If-Match`, or
+    /// one naming the current version. `If-Match` compares strongly, so weak
+    /// `W/` tags never match. Check it under the config write lock.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        let Some(if_match) = headers.get(header::IF_MATCH) else {
+            return true;
+        };
+        let etag = self.etag();
+        if_match.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == etag)
+        })
+    }
+
+    /// Record a change made under the config write lock and schedule
+    /// saving it. Returns the new `ETag`.
+    pub fn changed(&self) -> String {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.dirty.notify_one();
+        self.etag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[tokio::test]
+    async fn test_config_writer() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("config.json");
+        let config = Arc::new(RwLock::new(AppConfig::default()));
+        let writer = ConfigWriter::with_timing(
+            config.clone(),
+            path.to_string_lossy().to_string(),
+            Duration::from_millis(50),
+            RETRY_MIN,
+        );
+
+        let mut headers = HeaderMap::new();
+        assert!(writer.matches(&headers));
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("\"1\""));
+        assert!(writer.matches(&headers));
+
+        // Two quick changes are saved together
+        config.write().await.nickname = "first".to_string();
+        writer.changed();
+        config.write().await.nickname = "second".to_string();
+        assert_eq!(writer.changed(), "\"3\"");
+        assert!(!writer.matches(&headers));
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("W/\"3\""));
+        assert!(!writer.matches(&headers));
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("\"1\", \"3\""));
+        assert!(writer.matches(&headers));
+        assert!(!path.exists());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(AppConfig::load(&path.to_string_lossy()).nickname, "second");
+    }
+
+    #[tokio::test]
+    async fn test_flush() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("config.json");
+        let config = Arc::new(RwLock::new(AppConfig::default()));
+        let writer = ConfigWriter::with_timing(
+            config.clone(),
+            path.to_string_lossy().to_string(),
+            Duration::from_secs(60),
+            RETRY_MIN,
+        );
+
+        // Nothing changed, nothing written
+        assert!(writer.flush().await);
+        assert!(!path.exists());
+
+        config.write().await.nickname = "pending".to_string();
+        writer.changed();
+        assert!(writer.flush().await);
+        assert_eq!(AppConfig::load(&path.to_string_lossy()).nickname, "pending");
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed_save() {
+        let temp = tempfile::TempDir::new().unwrap();
+        // The config directory can't be created while a file is in its place
+        let blocker = temp.path().join("conf");
+        std::fs::write(&blocker, b"").unwrap();
+        let path = blocker.join("config.json");
+        let config = Arc::new(RwLock::new(AppConfig::default()));
+        let writer = ConfigWriter::with_timing(
+            config.clone(),
+            path.to_string_lossy().to_string(),
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        );
+
+        config.write().await.nickname = "retried".to_string();
+        writer.changed();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!path.exists());
+
+        std::fs::remove_file(&blocker).unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(AppConfig::load(&path.to_string_lossy()).nickname, "retried");
+    }
+}
@@ -0,0 +1,135 @@
+//! Confirmation Tokens
+//!
+//! Operations that throw away a lot at once, such as deleting the files of
+//! history items or cancelling the whole queue, take two requests. The first
+//! is refused with 428 and a short-lived token along with what would be
+//! affected; only a repeat of the same operation carrying the token in
+//! `X-Confirm-Token` goes through. A looping script or a stray click can't
+//! wipe gigabytes of downloads in one request.
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const HEADER: &str = "x-confirm-token";
+/// How long a token can be confirmed for
+const TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Tokens issued and not yet used, with their operation and expiry
+#[derive(Clone, Default)]
+pub struct Confirmations {
+    pending: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+}
+
+impl Confirmations {
+    /// Let an operation through if the request confirms it, or refuse it with
+    /// a new token and `summary` describing what it would do
+    #[allow(clippy::result_large_err)]
+    pub fn check(
+        &self,
+        headers: &HeaderMap,
+        operation: &str,
+        summary: serde_json::Value,
+    ) -> Result<(), Response> {
+        let token = headers.get(HEADER).and_then(|v| v.to_str().ok());
+        let error = match token {
+            Some(token) if self.confirm_at(token, operation, Instant::now()) => return Ok(()),
+            Some(_) => "Confirmation token is invalid, expired or for another operation",
+            None => "This operation has to be confirmed",
+        };
+        let token = self.issue_at(operation, Instant::now());
+        let mut body = serde_json::json!({
+            "error": error,
+            "confirm_token": token,
+            "expires_in": TOKEN_TTL.as_secs(),
+        });
+        if let (Some(body), serde_json::Value::Object(summary)) = (body.as_object_mut(), summary) {
+            body.extend(summary);
+        }
+        Err((StatusCode::PRECONDITION_REQUIRED, Json(body)).into_response())
+    }
+
+    fn issue_at(&self, operation: &str, now: Instant) -> String {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, (_, expires)| *expires > now);
+        pending.insert(token.clone(), (operation.to_string(), now + TOKEN_TTL));
+        token
+    }
+
+    /// Use up a token; true if it was issued for `operation` and hasn't expired
+    fn confirm_at(&self, token: &str, operation: &str, now: Instant) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        match pending.remove(token) {
+            Some((op, expires)) if op == operation && expires > now => true,
+            // A token for another operation stays usable for its own
+            Some(entry) if entry.0 != operation => {
+                pending.insert(token.to_string(), entry);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Name of an operation on a set of ids, independent of their order
+pub fn operation(action: &str, ids: &[String]) -> String {
+    let mut ids: Vec<&str> = ids.iter().map(|id| id.trim()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    format!("{}:{}", action, ids.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm() {
+        let confirmations = Confirmations::default();
+        let now = Instant::now();
+        let op = operation("history_delete", &["b".to_string(), "a".to_string()]);
+        assert_eq!(
+            op,
+            operation("history_delete", &["a".to_string(), "b".to_string()])
+        );
+
+        let token = confirmations.issue_at(&op, now);
+        assert!(!confirmations.confirm_at(&token, "queue_cancel:", now));
+        assert!(confirmations.confirm_at(&token, &op, now));
+        // Tokens are single use
+        assert!(!confirmations.confirm_at(&token, &op, now));
+
+        let token = confirmations.issue_at(&op, now);
+        assert!(!confirmations.confirm_at(&token, &op, now + TOKEN_TTL));
+        assert!(!confirmations.confirm_at("made-up", &op, now));
+    }
+
+    #[test]
+    fn test_check() {
+        let confirmations = Confirmations::default();
+        let mut headers = HeaderMap::new();
+        let refused = confirmations
+            .check(
+                &headers,
+                "queue_cancel:",
+                serde_json::json!({"transfers": 3}),
+            )
+            .unwrap_err();
+        assert_eq!(refused.status(), StatusCode::PRECONDITION_REQUIRED);
+
+        let token = confirmations.issue_at("queue_cancel:", Instant::now());
+        headers.insert(HEADER, token.parse().unwrap());
+        assert!(confirmations
+            .check(&headers, "queue_cancel:", serde_json::json!({}))
+            .is_ok());
+        assert!(confirmations
+            .check(&headers, "queue_cancel:", serde_json::json!({}))
+            .is_err());
+    }
+}
@@ -2,15 +2,23 @@
 //!
 //! Provides SQLite-based storage for download and search history.
 
-use chrono::Utc;
-use rusqlite::{params, Connection, Result as SqliteResult, Row};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult, Row};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+/// zstd level for archived transfer events. Higher levels barely shrink small
+/// JSON logs further and cost many times the CPU.
+const EVENTS_ZSTD_LEVEL: i32 = 3;
+
 /// Map a database row to a DownloadRecord.
-/// Expects columns in order: id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at
+/// Expects columns in order: id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
 fn row_to_download_record(row: &Row<'_>) -> rusqlite::Result<DownloadRecord> {
+    let labels: Option<String> = row.get(13)?;
+    let extraction: Option<String> = row.get(18)?;
+    let max_speed_kbps: Option<i64> = row.get(19)?;
     Ok(DownloadRecord {
         id: row.get(0)?,
         file_name: row.get(1)?,
@@ -24,6 +32,34 @@ fn row_to_download_record(row: &Row<'_>) -> rusqlite::Result<DownloadRecord> {
         error: row.get(9)?,
         created_at: row.get(10)?,
         completed_at: row.get(11)?,
+        category: row.get(12)?,
+        labels: labels
+            .and_then(|l| serde_json::from_str(&l).ok())
+            .unwrap_or_default(),
+        note: row.get(14)?,
+        template: row.get(15)?,
+        after: row.get(16)?,
+        method: row.get(17)?,
+        extraction: extraction.and_then(|e| serde_json::from_str(&e).ok()),
+        max_speed_kbps: max_speed_kbps.map(|k| k as u64),
+    })
+}
+
+/// Map a database row to a PackRecord.
+/// Expects columns in order: id, network, channel, bot, slot, file_name, size, size_str, gets, source, imported_at
+fn row_to_pack_record(row: &Row<'_>) -> rusqlite::Result<PackRecord> {
+    Ok(PackRecord {
+        id: row.get(0)?,
+        network: row.get(1)?,
+        channel: row.get(2)?,
+        bot: row.get(3)?,
+        slot: row.get(4)?,
+        file_name: row.get(5)?,
+        size: row.get(6)?,
+        size_str: row.get(7)?,
+        gets: row.get(8)?,
+        source: row.get(9)?,
+        imported_at: row.get(10)?,
     })
 }
 
@@ -47,6 +83,38 @@ pub struct DownloadRecord {
     pub error: Option<String>,
     pub created_at: String,
     pub completed_at: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Free-form tags, e.g. the automation that queued it
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Download template the transfer was queued with
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Transfer that has to complete before this one starts
+    #[serde(default)]
+    pub after: Option<String>,
+    /// How the file was fetched (`dcc` or `http`), if it got that far
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Outcome of the extract postprocess step, if it ran
+    #[serde(default)]
+    pub extraction: Option<crate::postprocess::StepResult>,
+    /// Speed limit set on the transfer itself in KiB/s (0 = none)
+    #[serde(default)]
+    pub max_speed_kbps: Option<u64>,
+}
+
+/// Download history record in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedDownload {
+    #[serde(flatten)]
+    pub record: DownloadRecord,
+    pub deleted_at: String,
+    /// Where the file was moved to, if it was deleted with the record
+    pub trash_path: Option<String>,
 }
 
 /// Search history record
@@ -56,7 +124,143 @@ pub struct SearchRecord {
     pub query: String,
     pub results_count: i64,
     pub results_json: Option<String>,
+    /// Time of the most recent search
     pub searched_at: String,
+    /// Number of times the query was searched (merged repeats)
+    pub search_count: i64,
+}
+
+/// Database size breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStats {
+    /// Total size of the database file in bytes
+    pub size_bytes: i64,
+    /// Bytes in unused pages that will be reused before the file grows
+    pub free_bytes: i64,
+    pub search_history_rows: i64,
+    /// Bytes taken by stored search results
+    pub search_results_bytes: i64,
+    pub download_history_rows: i64,
+}
+
+/// Outcome of compacting the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub ran_at: String,
+    pub size_before: i64,
+    pub size_after: i64,
+    pub duration_ms: i64,
+}
+
+/// Aggregated stats for a search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryStats {
+    pub query: String,
+    pub search_count: i64,
+    pub last_searched: String,
+}
+
+/// Bytes received from a network on one day (UTC)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficRecord {
+    /// Day as YYYY-MM-DD
+    pub day: String,
+    pub network: String,
+    pub bytes: i64,
+}
+
+/// Log lines and speed samples of a transfer, kept after it finishes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransferEvents {
+    pub logs: Vec<String>,
+    /// (seconds since the download started, bytes per second)
+    pub speed_samples: Vec<(u64, f64)>,
+}
+
+/// Partially downloaded file and the pack it came from, so it can be resumed
+/// after its transfer is gone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialFile {
+    pub id: i64,
+    pub path: String,
+    /// `irc://` URL of the pack
+    pub url: String,
+    pub file_name: String,
+    /// Size the bot advertised
+    pub size: u64,
+    /// Bytes received when last recorded
+    pub offset: u64,
+    pub transfer_id: Option<String>,
+    pub updated_at: String,
+}
+
+/// Last known state of an unfinished transfer, so it can pick up where it
+/// was after a restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferCheckpoint {
+    pub id: String,
+    pub status: String,
+    /// Bytes received when last checkpointed
+    pub downloaded: u64,
+    pub size: Option<u64>,
+    pub file_name: Option<String>,
+    /// Directory the file is saved in, when moved away from the download directory
+    pub download_dir: Option<String>,
+    pub retry_count: u32,
+    /// Place in the queue (1 = next), for transfers waiting in it
+    pub queue_position: Option<usize>,
+    pub updated_at: String,
+    /// Speed limit set on the transfer itself in KiB/s (0 = none)
+    pub max_speed_kbps: Option<u64>,
+}
+
+/// Saved search run on a schedule; new matches are queued
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchlistEntry {
+    pub id: i64,
+    pub name: String,
+    /// Search query, with `+term`/`-term` keywords
+    pub query: String,
+    /// Size bounds in bytes; results of unknown size are skipped when set
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Only take results from this network or bot
+    pub network: Option<String>,
+    pub bot: Option<String>,
+    pub category: Option<String>,
+    /// Download template applied to queued transfers
+    pub template: Option<String>,
+    /// Minutes between runs
+    pub interval_mins: u32,
+    pub enabled: bool,
+    pub last_run_at: Option<String>,
+    pub created_at: String,
+}
+
+/// File a watchlist entry found, with the transfer queued for it (none when
+/// it was already downloaded or in the library)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchlistHit {
+    pub file_name: String,
+    pub url: String,
+    pub transfer_id: Option<String>,
+    pub found_at: String,
+}
+
+/// Pack imported from an external pack list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackRecord {
+    pub id: i64,
+    pub network: String,
+    pub channel: String,
+    pub bot: String,
+    pub slot: i32,
+    pub file_name: String,
+    pub size: Option<i64>,
+    pub size_str: String,
+    pub gets: Option<i64>,
+    pub source: String,
+    pub imported_at: String,
 }
 
 /// Paginated response wrapper
@@ -80,6 +284,18 @@ impl Database {
         Ok(db)
     }
 
+    /// Open an existing database read-only, e.g. the primary's database on a
+    /// mirror instance. The schema is left to the primary.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> SqliteResult<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
     /// Initialize database schema
     fn init_schema(&self) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
@@ -119,6 +335,11 @@ impl Database {
             [],
         );
 
+        let _ = conn.execute(
+            "ALTER TABLE search_history ADD COLUMN search_count INTEGER NOT NULL DEFAULT 1",
+            [],
+        );
+
         // Migration: add slot and priority columns to download_history if they don't exist
         let _ = conn.execute(
             "ALTER TABLE download_history ADD COLUMN slot INTEGER DEFAULT 0",
@@ -128,6 +349,155 @@ impl Database {
             "ALTER TABLE download_history ADD COLUMN priority TEXT DEFAULT 'normal'",
             [],
         );
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN category TEXT", []);
+        // Migration: soft delete (trash) columns
+        let _ = conn.execute(
+            "ALTER TABLE download_history ADD COLUMN deleted_at TEXT",
+            [],
+        );
+        let _ = conn.execute(
+            "ALTER TABLE download_history ADD COLUMN trash_path TEXT",
+            [],
+        );
+        // Migration: labels (JSON array) and notes
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN labels TEXT", []);
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN note TEXT", []);
+        // Migration: download template
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN template TEXT", []);
+        // Migration: transfer dependencies
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN after_id TEXT", []);
+        // Migration: transfer method
+        let _ = conn.execute("ALTER TABLE download_history ADD COLUMN method TEXT", []);
+        // Migration: archive extraction outcome
+        let _ = conn.execute(
+            "ALTER TABLE download_history ADD COLUMN extraction TEXT",
+            [],
+        );
+        // Migration: per-transfer speed limit
+        let _ = conn.execute(
+            "ALTER TABLE download_history ADD COLUMN max_speed_kbps INTEGER",
+            [],
+        );
+
+        // Imported pack lists table
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS packs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                network TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                bot TEXT NOT NULL,
+                slot INTEGER NOT NULL,
+                file_name TEXT NOT NULL,
+                size INTEGER,
+                size_str TEXT NOT NULL DEFAULT '',
+                gets INTEGER,
+                source TEXT NOT NULL,
+                imported_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Daily DCC traffic per network
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS traffic (
+                day TEXT NOT NULL,
+                network TEXT NOT NULL,
+                bytes INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (day, network)
+            )",
+            [],
+        )?;
+
+        // Logs and speed samples of transfers; `data` is JSON, zstd
+        // compressed once `compressed` is set
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transfer_events (
+                id TEXT PRIMARY KEY,
+                data BLOB NOT NULL,
+                compressed INTEGER NOT NULL DEFAULT 0,
+                saved_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Partial downloads by file, for resuming without the transfer
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS partial_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                url TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                offset INTEGER NOT NULL,
+                transfer_id TEXT,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Checkpoints of unfinished transfers, restored on startup
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transfers (
+                id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                downloaded INTEGER NOT NULL DEFAULT 0,
+                size INTEGER,
+                file_name TEXT,
+                download_dir TEXT,
+                retry_count INTEGER NOT NULL DEFAULT 0,
+                queue_position INTEGER,
+                updated_at TEXT NOT NULL,
+                max_speed_kbps INTEGER
+            )",
+            [],
+        )?;
+        // Migration: per-transfer speed limit
+        let _ = conn.execute(
+            "ALTER TABLE transfers ADD COLUMN max_speed_kbps INTEGER",
+            [],
+        );
+
+        // Scheduled searches and the files they found
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS watchlist (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                query TEXT NOT NULL,
+                min_size INTEGER,
+                max_size INTEGER,
+                network TEXT,
+                bot TEXT,
+                category TEXT,
+                template TEXT,
+                interval_mins INTEGER NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                last_run_at TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS watchlist_hits (
+                watch_id INTEGER NOT NULL,
+                file_name TEXT NOT NULL COLLATE NOCASE,
+                url TEXT NOT NULL,
+                transfer_id TEXT,
+                found_at TEXT NOT NULL,
+                PRIMARY KEY (watch_id, file_name)
+            )",
+            [],
+        )?;
+
+        // VACUUM/ANALYZE runs, so the weekly one knows when it last ran
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS maintenance_runs (
+                ran_at TEXT NOT NULL,
+                size_before INTEGER NOT NULL,
+                size_after INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL
+            )",
+            [],
+        )?;
 
         // Create indexes for faster queries
         conn.execute(
@@ -138,19 +508,142 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_search_searched_at ON search_history(searched_at DESC)",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_packs_bot ON packs(network, bot)",
+            [],
+        )?;
 
         Ok(())
     }
 
+    /// Verify the database file accepts writes (the write is rolled back)
+    pub fn check_writable(&self) -> SqliteResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO traffic (day, network, bytes) VALUES ('selftest', 'selftest', 0)
+             ON CONFLICT(day, network) DO NOTHING",
+            [],
+        )?;
+        tx.rollback()
+    }
+
+    /// Verify the database connection is usable
+    pub fn health_check(&self) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT 1", [], |_| Ok(()))
+    }
+
+    /// Report the database size and what takes up the space
+    pub fn stats(&self) -> SqliteResult<DatabaseStats> {
+        let conn = self.conn.lock().unwrap();
+        let pragma = |name: &str| -> SqliteResult<i64> {
+            conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+        };
+        let page_size = pragma("page_size")?;
+        Ok(DatabaseStats {
+            size_bytes: pragma("page_count")? * page_size,
+            free_bytes: pragma("freelist_count")? * page_size,
+            search_history_rows: conn.query_row(
+                "SELECT COUNT(*) FROM search_history",
+                [],
+                |row| row.get(0),
+            )?,
+            search_results_bytes: conn.query_row(
+                "SELECT COALESCE(SUM(LENGTH(results_json)), 0) FROM search_history",
+                [],
+                |row| row.get(0),
+            )?,
+            download_history_rows: conn.query_row(
+                "SELECT COUNT(*) FROM download_history",
+                [],
+                |row| row.get(0),
+            )?,
+        })
+    }
+
+    /// Rebuild the file without its free pages and refresh the query
+    /// planner statistics. Other database access waits until it finishes.
+    pub fn run_maintenance(&self) -> SqliteResult<MaintenanceReport> {
+        let conn = self.conn.lock().unwrap();
+        let size = || -> SqliteResult<i64> {
+            conn.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get(0),
+            )
+        };
+        let started = std::time::Instant::now();
+        let size_before = size()?;
+        conn.execute_batch("VACUUM; ANALYZE;")?;
+        let report = MaintenanceReport {
+            ran_at: Utc::now().to_rfc3339(),
+            size_before,
+            size_after: size()?,
+            duration_ms: started.elapsed().as_millis() as i64,
+        };
+        conn.execute(
+            "INSERT INTO maintenance_runs (ran_at, size_before, size_after, duration_ms)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                report.ran_at,
+                report.size_before,
+                report.size_after,
+                report.duration_ms
+            ],
+        )?;
+        Ok(report)
+    }
+
+    /// The most recent maintenance run
+    pub fn last_maintenance(&self) -> SqliteResult<Option<MaintenanceReport>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT ran_at, size_before, size_after, duration_ms FROM maintenance_runs
+             ORDER BY ran_at DESC LIMIT 1",
+            [],
+            |row| {
+                Ok(MaintenanceReport {
+                    ran_at: row.get(0)?,
+                    size_before: row.get(1)?,
+                    size_after: row.get(2)?,
+                    duration_ms: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    /// Whether maintenance hasn't run within `interval`
+    pub fn maintenance_due(&self, interval: chrono::Duration) -> bool {
+        match self.last_maintenance() {
+            Ok(Some(last)) => DateTime::parse_from_rfc3339(&last.ran_at)
+                .map_or(true, |t| Utc::now() - t.with_timezone(&Utc) >= interval),
+            Ok(None) => true,
+            Err(_) => false,
+        }
+    }
+
     // ==================== Download History ====================
 
     /// Insert a download record
     pub fn insert_download(&self, record: &DownloadRecord) -> SqliteResult<()> {
         let conn = self.conn.lock().unwrap();
+        // An update leaves the trash columns alone, so a record saved again
+        // while in the trash stays there
         conn.execute(
-            "INSERT OR REPLACE INTO download_history 
-             (id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "INSERT INTO download_history
+             (id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
+             ON CONFLICT(id) DO UPDATE SET
+                file_name = excluded.file_name, size = excluded.size, network = excluded.network,
+                bot = excluded.bot, channel = excluded.channel, slot = excluded.slot,
+                priority = excluded.priority, status = excluded.status, error = excluded.error,
+                created_at = excluded.created_at, completed_at = excluded.completed_at,
+                category = excluded.category, labels = excluded.labels, note = excluded.note,
+                template = excluded.template, after_id = excluded.after_id,
+                method = excluded.method, extraction = excluded.extraction,
+                max_speed_kbps = excluded.max_speed_kbps",
             params![
                 record.id,
                 record.file_name,
@@ -164,34 +657,82 @@ impl Database {
                 record.error,
                 record.created_at,
                 record.completed_at,
+                record.category,
+                serde_json::to_string(&record.labels).ok(),
+                record.note,
+                record.template,
+                record.after,
+                record.method,
+                record.extraction.as_ref().and_then(|e| serde_json::to_string(e).ok()),
+                record.max_speed_kbps.map(|k| k as i64),
             ],
         )?;
         Ok(())
     }
 
-    /// List download history with pagination
+    /// Download history from `offset` on, newest first, optionally only one
+    /// category (`*` for records without one), and how many records match
+    pub fn list_downloads_range(
+        &self,
+        offset: i64,
+        limit: i64,
+        category: Option<&str>,
+    ) -> SqliteResult<(Vec<DownloadRecord>, i64)> {
+        let conn = self.conn.lock().unwrap();
+        let category = category.filter(|c| *c != "*");
+        let filter = "deleted_at IS NULL
+             AND (?1 IS NULL OR COALESCE(category, '*') = ?1 COLLATE NOCASE)";
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM download_history WHERE {}", filter),
+            params![category],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
+             FROM download_history
+             WHERE {}
+             ORDER BY completed_at DESC
+             LIMIT ?2 OFFSET ?3",
+            filter
+        ))?;
+        let items = stmt
+            .query_map(params![category, limit, offset], row_to_download_record)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((items, total))
+    }
+
+    /// List download history with pagination, optionally only records with a label
     pub fn list_downloads(
         &self,
         page: i64,
         limit: i64,
+        label: Option<&str>,
     ) -> SqliteResult<PaginatedResponse<DownloadRecord>> {
         let conn = self.conn.lock().unwrap();
+        let filter = "deleted_at IS NULL
+             AND (?1 IS NULL OR EXISTS (
+                 SELECT 1 FROM json_each(download_history.labels) WHERE value = ?1 COLLATE NOCASE
+             ))";
 
         // Get total count
-        let total: i64 = conn.query_row("SELECT COUNT(*) FROM download_history", [], |row| {
-            row.get(0)
-        })?;
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM download_history WHERE {}", filter),
+            params![label],
+            |row| row.get(0),
+        )?;
 
         let offset = (page - 1) * limit;
-        let mut stmt = conn.prepare(
-            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
              FROM download_history
+             WHERE {}
              ORDER BY completed_at DESC
-             LIMIT ?1 OFFSET ?2"
-        )?;
+             LIMIT ?2 OFFSET ?3",
+            filter
+        ))?;
 
         let items = stmt
-            .query_map(params![limit, offset], row_to_download_record)?
+            .query_map(params![label, limit, offset], row_to_download_record)?
             .collect::<Result<Vec<_>, _>>()?;
 
         let total_pages = (total + limit - 1) / limit;
@@ -209,9 +750,10 @@ impl Database {
     pub fn get_incomplete_downloads(&self) -> SqliteResult<Vec<DownloadRecord>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
              FROM download_history
-             WHERE status NOT IN ('Completed', 'Failed', 'Cancelled')"
+             WHERE status NOT IN ('Completed', 'Failed', 'Cancelled', 'Quarantined')
+               AND deleted_at IS NULL"
         )?;
 
         let items = stmt
@@ -225,9 +767,10 @@ impl Database {
     pub fn get_recent_finished_downloads(&self, limit: i64) -> SqliteResult<Vec<DownloadRecord>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
              FROM download_history
-             WHERE status IN ('Completed', 'Failed', 'Cancelled')
+             WHERE status IN ('Completed', 'Failed', 'Cancelled', 'Quarantined')
+               AND deleted_at IS NULL
              ORDER BY completed_at DESC
              LIMIT ?1"
         )?;
@@ -239,6 +782,23 @@ impl Database {
         Ok(items)
     }
 
+    /// List downloads with a given status, newest first
+    pub fn list_downloads_by_status(&self, status: &str) -> SqliteResult<Vec<DownloadRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
+             FROM download_history
+             WHERE status = ?1 AND deleted_at IS NULL
+             ORDER BY completed_at DESC"
+        )?;
+
+        let items = stmt
+            .query_map(params![status], row_to_download_record)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(items)
+    }
+
     /// Check if a URL has already been downloaded (or attempted)
     pub fn is_url_downloaded(
         &self,
@@ -250,7 +810,8 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT count(*) FROM download_history 
-             WHERE network = ?1 AND bot = ?2 AND channel = ?3 AND slot = ?4",
+             WHERE network = ?1 AND bot = ?2 AND channel = ?3 AND slot = ?4
+               AND deleted_at IS NULL",
         )?;
         let count: i64 = stmt.query_row(params![network, bot, channel, slot], |row| row.get(0))?;
         Ok(count > 0)
@@ -260,7 +821,8 @@ impl Database {
     pub fn get_all_download_filenames(&self) -> SqliteResult<Vec<String>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT DISTINCT file_name FROM download_history WHERE file_name IS NOT NULL",
+            "SELECT DISTINCT file_name FROM download_history
+             WHERE file_name IS NOT NULL AND deleted_at IS NULL",
         )?;
         let items = stmt
             .query_map([], |row| row.get(0))?
@@ -272,27 +834,14 @@ impl Database {
     pub fn get_download(&self, id: &str) -> SqliteResult<Option<DownloadRecord>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps
              FROM download_history
-             WHERE id = ?1"
+             WHERE id = ?1 AND deleted_at IS NULL"
         )?;
 
         let mut rows = stmt.query(params![id])?;
         if let Some(row) = rows.next()? {
-            Ok(Some(DownloadRecord {
-                id: row.get(0)?,
-                file_name: row.get(1)?,
-                size: row.get(2)?,
-                network: row.get(3)?,
-                bot: row.get(4)?,
-                channel: row.get(5)?,
-                slot: row.get(6)?,
-                priority: row.get(7)?,
-                status: row.get(8)?,
-                error: row.get(9)?,
-                created_at: row.get(10)?,
-                completed_at: row.get(11)?,
-            }))
+            Ok(Some(row_to_download_record(row)?))
         } else {
             Ok(None)
         }
@@ -305,6 +854,126 @@ impl Database {
         Ok(rows > 0)
     }
 
+    /// Replace the labels and note of a download record
+    pub fn set_annotations(
+        &self,
+        id: &str,
+        labels: &[String],
+        note: Option<&str>,
+    ) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE download_history SET labels = ?2, note = ?3 WHERE id = ?1",
+            params![id, serde_json::to_string(labels).ok(), note],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Record how extracting a completed download went
+    pub fn set_download_extraction(
+        &self,
+        id: &str,
+        extraction: &crate::postprocess::StepResult,
+    ) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE download_history SET extraction = ?2 WHERE id = ?1",
+            params![id, serde_json::to_string(extraction).ok()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Move a download record to the trash, remembering where its file went
+    pub fn trash_download(&self, id: &str, trash_path: Option<&str>) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE download_history SET deleted_at = ?2, trash_path = ?3
+             WHERE id = ?1 AND deleted_at IS NULL",
+            params![id, Utc::now().to_rfc3339(), trash_path],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Get a download record from the trash
+    pub fn get_trashed_download(&self, id: &str) -> SqliteResult<Option<TrashedDownload>> {
+        Ok(self
+            .query_trash("WHERE id = ?1 AND deleted_at IS NOT NULL", params![id])?
+            .pop())
+    }
+
+    /// Download records in the trash, most recently deleted first
+    pub fn list_trash(&self) -> SqliteResult<Vec<TrashedDownload>> {
+        self.query_trash("WHERE deleted_at IS NOT NULL", [])
+    }
+
+    fn query_trash<P: rusqlite::Params>(
+        &self,
+        filter: &str,
+        params: P,
+    ) -> SqliteResult<Vec<TrashedDownload>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, file_name, size, network, bot, channel, slot, priority, status, error, created_at, completed_at, category, labels, note, template, after_id, method, extraction, max_speed_kbps, deleted_at, trash_path
+             FROM download_history
+             {}
+             ORDER BY deleted_at DESC",
+            filter
+        ))?;
+        let items = stmt
+            .query_map(params, |row| {
+                Ok(TrashedDownload {
+                    record: row_to_download_record(row)?,
+                    deleted_at: row.get(20)?,
+                    trash_path: row.get(21)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    /// Take a download record out of the trash
+    pub fn restore_download(&self, id: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE download_history SET deleted_at = NULL, trash_path = NULL
+             WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Permanently delete records trashed more than `days` ago (all of them
+    /// with `None`), returning the trashed files to remove
+    pub fn purge_trash(&self, days: Option<u64>) -> SqliteResult<Vec<String>> {
+        let cutoff = match days {
+            Some(days) => (Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339(),
+            None => Utc::now().to_rfc3339(),
+        };
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT trash_path FROM download_history
+             WHERE deleted_at IS NOT NULL AND deleted_at <= ?1 AND trash_path IS NOT NULL",
+        )?;
+        let paths = stmt
+            .query_map(params![cutoff], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        conn.execute(
+            "DELETE FROM download_history WHERE deleted_at IS NOT NULL AND deleted_at <= ?1",
+            params![cutoff],
+        )?;
+        Ok(paths)
+    }
+
+    /// Ids of every download record, trashed ones included
+    pub fn download_ids(&self) -> SqliteResult<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM download_history")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<HashSet<String>, _>>()?;
+        Ok(ids)
+    }
+
     /// Bulk delete download records
     pub fn bulk_delete_downloads(&self, ids: &[String]) -> SqliteResult<usize> {
         let conn = self.conn.lock().unwrap();
@@ -320,24 +989,55 @@ impl Database {
         Ok(rows)
     }
 
-    /// Clear all download history
+    /// Move all download history to the trash
     pub fn clear_download_history(&self) -> SqliteResult<usize> {
         let conn = self.conn.lock().unwrap();
-        let rows = conn.execute("DELETE FROM download_history", [])?;
+        let rows = conn.execute(
+            "UPDATE download_history SET deleted_at = ?1 WHERE deleted_at IS NULL",
+            params![Utc::now().to_rfc3339()],
+        )?;
         Ok(rows)
     }
 
     // ==================== Search History ====================
 
-    /// Insert a search record with results
+    /// Insert a search record with results. If the same query (ignoring case)
+    /// was recorded within `merge_window_mins`, that entry is updated and its
+    /// count bumped instead.
     pub fn insert_search(
         &self,
         query: &str,
         results_count: i64,
         results_json: Option<&str>,
+        merge_window_mins: u64,
     ) -> SqliteResult<i64> {
         let conn = self.conn.lock().unwrap();
-        let now = Utc::now().to_rfc3339();
+        let now = Utc::now();
+
+        if merge_window_mins > 0 {
+            let cutoff = (now - chrono::Duration::minutes(merge_window_mins as i64)).to_rfc3339();
+            let existing: Option<i64> = conn
+                .query_row(
+                    "SELECT id FROM search_history
+                     WHERE lower(query) = lower(?1) AND searched_at >= ?2
+                     ORDER BY searched_at DESC LIMIT 1",
+                    params![query, cutoff],
+                    |row| row.get(0),
+                )
+                .ok();
+            if let Some(id) = existing {
+                conn.execute(
+                    "UPDATE search_history
+                     SET results_count = ?1, results_json = ?2, searched_at = ?3,
+                         search_count = search_count + 1
+                     WHERE id = ?4",
+                    params![results_count, results_json, now.to_rfc3339(), id],
+                )?;
+                return Ok(id);
+            }
+        }
+
+        let now = now.to_rfc3339();
         conn.execute(
             "INSERT INTO search_history (query, results_count, results_json, searched_at) VALUES (?1, ?2, ?3, ?4)",
             params![query, results_count, results_json, now],
@@ -358,7 +1058,7 @@ impl Database {
 
         let offset = (page - 1) * limit;
         let mut stmt = conn.prepare(
-            "SELECT id, query, results_count, results_json, searched_at
+            "SELECT id, query, results_count, results_json, searched_at, search_count
              FROM search_history
              ORDER BY searched_at DESC
              LIMIT ?1 OFFSET ?2",
@@ -372,6 +1072,7 @@ impl Database {
                     results_count: row.get(2)?,
                     results_json: row.get(3)?,
                     searched_at: row.get(4)?,
+                    search_count: row.get(5)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -387,6 +1088,476 @@ impl Database {
         })
     }
 
+    /// Clear stored results of searches older than `days`, keeping the queries
+    pub fn prune_search_results(&self, days: u64) -> SqliteResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let cutoff = (Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+        conn.execute(
+            "UPDATE search_history SET results_json = NULL
+             WHERE results_json IS NOT NULL AND searched_at < ?1",
+            params![cutoff],
+        )
+    }
+
+    /// Store the events of a transfer, replacing earlier ones
+    pub fn save_transfer_events(&self, id: &str, events: &TransferEvents) -> SqliteResult<()> {
+        let data = serde_json::to_vec(events).unwrap_or_default();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO transfer_events (id, data, compressed, saved_at) VALUES (?1, ?2, 0, ?3)
+             ON CONFLICT(id) DO UPDATE SET data = ?2, compressed = 0, saved_at = ?3",
+            params![id, data, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Events of a transfer, decompressed if they were archived
+    pub fn get_transfer_events(&self, id: &str) -> SqliteResult<Option<TransferEvents>> {
+        let conn = self.conn.lock().unwrap();
+        let row: Option<(Vec<u8>, bool)> = conn
+            .query_row(
+                "SELECT data, compressed FROM transfer_events WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((data, compressed)) = row else {
+            return Ok(None);
+        };
+        let data = if compressed {
+            match zstd::decode_all(data.as_slice()) {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::warn!("Failed to decompress events of {}: {}", id, e);
+                    return Ok(None);
+                }
+            }
+        } else {
+            data
+        };
+        Ok(serde_json::from_slice(&data).ok())
+    }
+
+    /// Compress the events of transfers that completed more than `days` ago,
+    /// and drop events whose history entry is gone. The database is unlocked
+    /// while compressing.
+    pub fn compress_transfer_events(&self, days: u64) -> SqliteResult<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+        let rows = {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "DELETE FROM transfer_events
+                 WHERE id NOT IN (SELECT id FROM download_history)",
+                [],
+            )?;
+            let mut stmt = conn.prepare(
+                "SELECT e.id, e.data FROM transfer_events e
+                 JOIN download_history h ON h.id = e.id
+                 WHERE e.compressed = 0 AND h.status = 'Completed' AND h.completed_at < ?1",
+            )?;
+            let rows = stmt
+                .query_map(params![cutoff], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+
+        let packed: Vec<_> = rows
+            .into_iter()
+            .filter_map(
+                |(id, data)| match zstd::encode_all(data.as_slice(), EVENTS_ZSTD_LEVEL) {
+                    Ok(packed) => Some((id, data, packed)),
+                    Err(e) => {
+                        tracing::warn!("Failed to compress events of {}: {}", id, e);
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        let conn = self.conn.lock().unwrap();
+        let mut compressed = 0;
+        for (id, data, packed) in packed {
+            // Skipped if the events were rewritten in the meantime
+            compressed += conn.execute(
+                "UPDATE transfer_events SET data = ?1, compressed = 1
+                 WHERE id = ?2 AND compressed = 0 AND data = ?3",
+                params![packed, id, data],
+            )?;
+        }
+        Ok(compressed)
+    }
+
+    /// Record or update the partial file at `path`
+    pub fn save_partial_file(
+        &self,
+        path: &str,
+        url: &str,
+        file_name: &str,
+        size: u64,
+        offset: u64,
+        transfer_id: Option<&str>,
+    ) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO partial_files (path, url, file_name, size, offset, transfer_id, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(path) DO UPDATE SET url = ?2, file_name = ?3, size = ?4, offset = ?5,
+                transfer_id = ?6, updated_at = ?7",
+            params![
+                path,
+                url,
+                file_name,
+                size as i64,
+                offset as i64,
+                transfer_id,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn partial_file_from_row(row: &Row) -> SqliteResult<PartialFile> {
+        Ok(PartialFile {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            url: row.get(2)?,
+            file_name: row.get(3)?,
+            size: row.get::<_, i64>(4)? as u64,
+            offset: row.get::<_, i64>(5)? as u64,
+            transfer_id: row.get(6)?,
+            updated_at: row.get(7)?,
+        })
+    }
+
+    /// Recorded partial files, most recently updated first
+    pub fn list_partial_files(&self) -> SqliteResult<Vec<PartialFile>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, url, file_name, size, offset, transfer_id, updated_at
+             FROM partial_files ORDER BY updated_at DESC",
+        )?;
+        let files = stmt
+            .query_map([], Self::partial_file_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
+    pub fn get_partial_file(&self, id: i64) -> SqliteResult<Option<PartialFile>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, path, url, file_name, size, offset, transfer_id, updated_at
+             FROM partial_files WHERE id = ?1",
+            params![id],
+            Self::partial_file_from_row,
+        )
+        .optional()
+    }
+
+    pub fn delete_partial_file(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM partial_files WHERE id = ?1", params![id])? > 0)
+    }
+
+    /// Forget the partial file of a transfer, once it completed
+    pub fn delete_partial_files_of(&self, transfer_id: &str) -> SqliteResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM partial_files WHERE transfer_id = ?1",
+            params![transfer_id],
+        )
+    }
+
+    /// Write the checkpoint of an unfinished transfer
+    pub fn save_transfer_checkpoint(&self, checkpoint: &TransferCheckpoint) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO transfers (id, status, downloaded, size, file_name, download_dir,
+                retry_count, queue_position, updated_at, max_speed_kbps)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                checkpoint.id,
+                checkpoint.status,
+                checkpoint.downloaded as i64,
+                checkpoint.size.map(|s| s as i64),
+                checkpoint.file_name,
+                checkpoint.download_dir,
+                checkpoint.retry_count,
+                checkpoint.queue_position.map(|p| p as i64),
+                checkpoint.updated_at,
+                checkpoint.max_speed_kbps.map(|k| k as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Checkpoints of unfinished transfers, by transfer id
+    pub fn list_transfer_checkpoints(&self) -> SqliteResult<HashMap<String, TransferCheckpoint>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, status, downloaded, size, file_name, download_dir, retry_count,
+                queue_position, updated_at, max_speed_kbps
+             FROM transfers",
+        )?;
+        let items = stmt
+            .query_map([], |row| {
+                Ok(TransferCheckpoint {
+                    id: row.get(0)?,
+                    status: row.get(1)?,
+                    downloaded: row.get::<_, i64>(2)? as u64,
+                    size: row.get::<_, Option<i64>>(3)?.map(|s| s as u64),
+                    file_name: row.get(4)?,
+                    download_dir: row.get(5)?,
+                    retry_count: row.get(6)?,
+                    queue_position: row.get::<_, Option<i64>>(7)?.map(|p| p as usize),
+                    updated_at: row.get(8)?,
+                    max_speed_kbps: row.get::<_, Option<i64>>(9)?.map(|k| k as u64),
+                })
+            })?
+            .map(|c| c.map(|c| (c.id.clone(), c)))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(items)
+    }
+
+    /// Drop the checkpoint of a transfer that finished or was removed
+    pub fn delete_transfer_checkpoint(&self, id: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM transfers WHERE id = ?1", params![id])? > 0)
+    }
+
+    fn watchlist_entry_from_row(row: &Row) -> SqliteResult<WatchlistEntry> {
+        Ok(WatchlistEntry {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            query: row.get(2)?,
+            min_size: row.get::<_, Option<i64>>(3)?.map(|s| s as u64),
+            max_size: row.get::<_, Option<i64>>(4)?.map(|s| s as u64),
+            network: row.get(5)?,
+            bot: row.get(6)?,
+            category: row.get(7)?,
+            template: row.get(8)?,
+            interval_mins: row.get(9)?,
+            enabled: row.get(10)?,
+            last_run_at: row.get(11)?,
+            created_at: row.get(12)?,
+        })
+    }
+
+    /// Watchlist entries, oldest first
+    pub fn list_watchlist(&self) -> SqliteResult<Vec<WatchlistEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, query, min_size, max_size, network, bot, category, template,
+                interval_mins, enabled, last_run_at, created_at
+             FROM watchlist ORDER BY id",
+        )?;
+        let entries = stmt
+            .query_map([], Self::watchlist_entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    pub fn get_watchlist_entry(&self, id: i64) -> SqliteResult<Option<WatchlistEntry>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, name, query, min_size, max_size, network, bot, category, template,
+                interval_mins, enabled, last_run_at, created_at
+             FROM watchlist WHERE id = ?1",
+            params![id],
+            Self::watchlist_entry_from_row,
+        )
+        .optional()
+    }
+
+    /// Add a watchlist entry, returning its id; `id` and `last_run_at` are ignored
+    pub fn insert_watchlist_entry(&self, entry: &WatchlistEntry) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO watchlist (name, query, min_size, max_size, network, bot, category,
+                template, interval_mins, enabled, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                entry.name,
+                entry.query,
+                entry.min_size.map(|s| s as i64),
+                entry.max_size.map(|s| s as i64),
+                entry.network,
+                entry.bot,
+                entry.category,
+                entry.template,
+                entry.interval_mins,
+                entry.enabled,
+                entry.created_at,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Replace the settings of a watchlist entry, keeping its run times
+    pub fn update_watchlist_entry(&self, entry: &WatchlistEntry) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE watchlist SET name = ?2, query = ?3, min_size = ?4, max_size = ?5,
+                network = ?6, bot = ?7, category = ?8, template = ?9, interval_mins = ?10,
+                enabled = ?11
+             WHERE id = ?1",
+            params![
+                entry.id,
+                entry.name,
+                entry.query,
+                entry.min_size.map(|s| s as i64),
+                entry.max_size.map(|s| s as i64),
+                entry.network,
+                entry.bot,
+                entry.category,
+                entry.template,
+                entry.interval_mins,
+                entry.enabled,
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Remove a watchlist entry and the files it found
+    pub fn delete_watchlist_entry(&self, id: i64) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM watchlist_hits WHERE watch_id = ?1",
+            params![id],
+        )?;
+        Ok(conn.execute("DELETE FROM watchlist WHERE id = ?1", params![id])? > 0)
+    }
+
+    pub fn set_watchlist_run(&self, id: i64, at: &str) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE watchlist SET last_run_at = ?2 WHERE id = ?1",
+            params![id, at],
+        )?;
+        Ok(())
+    }
+
+    /// Whether a watchlist entry found a file before (names compare case-insensitively)
+    pub fn has_watchlist_hit(&self, watch_id: i64, file_name: &str) -> SqliteResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT 1 FROM watchlist_hits WHERE watch_id = ?1 AND file_name = ?2",
+            params![watch_id, file_name],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|found| found.is_some())
+    }
+
+    pub fn add_watchlist_hit(&self, watch_id: i64, hit: &WatchlistHit) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO watchlist_hits (watch_id, file_name, url, transfer_id, found_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                watch_id,
+                hit.file_name,
+                hit.url,
+                hit.transfer_id,
+                hit.found_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Files a watchlist entry found, newest first
+    pub fn list_watchlist_hits(
+        &self,
+        watch_id: i64,
+        limit: i64,
+    ) -> SqliteResult<Vec<WatchlistHit>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT file_name, url, transfer_id, found_at FROM watchlist_hits
+             WHERE watch_id = ?1 ORDER BY found_at DESC LIMIT ?2",
+        )?;
+        let hits = stmt
+            .query_map(params![watch_id, limit], |row| {
+                Ok(WatchlistHit {
+                    file_name: row.get(0)?,
+                    url: row.get(1)?,
+                    transfer_id: row.get(2)?,
+                    found_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(hits)
+    }
+
+    /// Most searched queries, combining entries that differ only in case
+    pub fn top_queries(&self, limit: i64) -> SqliteResult<Vec<QueryStats>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT MAX(query), SUM(search_count) AS total, MAX(searched_at)
+             FROM search_history
+             GROUP BY lower(query)
+             ORDER BY total DESC, MAX(searched_at) DESC
+             LIMIT ?1",
+        )?;
+        let stats = stmt
+            .query_map(params![limit], |row| {
+                Ok(QueryStats {
+                    query: row.get(0)?,
+                    search_count: row.get(1)?,
+                    last_searched: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(stats)
+    }
+
+    /// Add received bytes to today's traffic for a network
+    pub fn add_traffic(&self, network: &str, bytes: u64) -> SqliteResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let day = Utc::now().format("%Y-%m-%d").to_string();
+        conn.execute(
+            "INSERT INTO traffic (day, network, bytes) VALUES (?1, ?2, ?3)
+             ON CONFLICT(day, network) DO UPDATE SET bytes = bytes + excluded.bytes",
+            params![day, network, bytes as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Daily traffic per network over the last `days` days, newest first
+    pub fn list_traffic(&self, days: i64) -> SqliteResult<Vec<TrafficRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let since = (Utc::now() - chrono::Duration::days(days.max(1) - 1))
+            .format("%Y-%m-%d")
+            .to_string();
+        let mut stmt = conn.prepare(
+            "SELECT day, network, bytes FROM traffic
+             WHERE day >= ?1
+             ORDER BY day DESC, network",
+        )?;
+        let records = stmt
+            .query_map(params![since], |row| {
+                Ok(TrafficRecord {
+                    day: row.get(0)?,
+                    network: row.get(1)?,
+                    bytes: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// Total bytes received in the current calendar month (UTC)
+    pub fn month_traffic(&self) -> SqliteResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let month_start = Utc::now().format("%Y-%m-01").to_string();
+        conn.query_row(
+            "SELECT COALESCE(SUM(bytes), 0) FROM traffic WHERE day >= ?1",
+            params![month_start],
+            |row| row.get(0),
+        )
+    }
+
     /// Delete a search record
     pub fn delete_search(&self, id: i64) -> SqliteResult<bool> {
         let conn = self.conn.lock().unwrap();
@@ -450,4 +1621,489 @@ impl Database {
 
         Ok(alternatives)
     }
+
+    // ==================== Imported Packs ====================
+
+    /// Replace all stored packs of a bot with a freshly imported list
+    pub fn replace_packs(
+        &self,
+        network: &str,
+        channel: &str,
+        bot: &str,
+        source: &str,
+        packs: &[crate::xdcc::packlist::PackEntry],
+    ) -> SqliteResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM packs WHERE network = ?1 AND bot = ?2",
+            params![network, bot],
+        )?;
+
+        let now = Utc::now().to_rfc3339();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO packs (network, channel, bot, slot, file_name, size, size_str, gets, source, imported_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for pack in packs {
+                stmt.execute(params![
+                    network,
+                    channel,
+                    bot,
+                    pack.slot,
+                    pack.filename,
+                    pack.size.map(|s| s as i64),
+                    pack.size_str,
+                    pack.gets.map(|g| g as i64),
+                    source,
+                    now,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(packs.len())
+    }
+
+    /// Search imported packs; every whitespace-separated term must match the file name
+    pub fn search_packs(&self, query: &str, limit: i64) -> SqliteResult<Vec<PackRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|t| format!("%{}%", t))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let conditions: Vec<_> = (0..terms.len())
+            .map(|i| format!("file_name LIKE ?{}", i + 1))
+            .collect();
+        let sql = format!(
+            "SELECT id, network, channel, bot, slot, file_name, size, size_str, gets, source, imported_at
+             FROM packs
+             WHERE {}
+             ORDER BY imported_at DESC
+             LIMIT {}",
+            conditions.join(" AND "),
+            limit
+        );
+
+        let mut stmt = conn.prepare(&sql)?;
+        let params: Vec<&dyn rusqlite::ToSql> =
+            terms.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+        let items = stmt
+            .query_map(params.as_slice(), row_to_pack_record)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    /// List imported packs with pagination
+    pub fn list_packs(&self, page: i64, limit: i64) -> SqliteResult<PaginatedResponse<PackRecord>> {
+        let conn = self.conn.lock().unwrap();
+
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM packs", [], |row| row.get(0))?;
+
+        let offset = (page - 1) * limit;
+        let mut stmt = conn.prepare(
+            "SELECT id, network, channel, bot, slot, file_name, size, size_str, gets, source, imported_at
+             FROM packs
+             ORDER BY network, bot, slot
+             LIMIT ?1 OFFSET ?2",
+        )?;
+
+        let items = stmt
+            .query_map(params![limit, offset], row_to_pack_record)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total_pages = (total + limit - 1) / limit;
+
+        Ok(PaginatedResponse {
+            items,
+            total,
+            page,
+            limit,
+            total_pages,
+        })
+    }
+
+    /// Delete all imported packs of a bot
+    pub fn delete_packs(&self, network: &str, bot: &str) -> SqliteResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM packs WHERE network = ?1 AND bot = ?2",
+            params![network, bot],
+        )
+    }
+}
+
+/// Serialize search results for storage, dropping trailing results until the
+/// JSON fits in `max_bytes` (0 for no limit)
+pub fn serialize_results_capped(
+    results: &[crate::xdcc::XdccSearchResult],
+    max_bytes: usize,
+) -> Option<String> {
+    let json = serde_json::to_string(results).ok()?;
+    if max_bytes == 0 || json.len() <= max_bytes {
+        return Some(json);
+    }
+
+    // Start from a proportional estimate and shrink until it fits
+    let mut keep = results.len() * max_bytes / json.len();
+    while keep > 0 {
+        let json = serde_json::to_string(&results[..keep]).ok()?;
+        if json.len() <= max_bytes {
+            return Some(json);
+        }
+        keep = keep * 9 / 10;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xdcc::{XdccSearchResult, XdccUrl};
+
+    #[test]
+    fn test_serialize_results_capped() {
+        let results: Vec<XdccSearchResult> = (1..=100)
+            .map(|slot| {
+                let url = XdccUrl {
+                    network: "irc.example.net".to_string(),
+                    channel: "#chan".to_string(),
+                    bot: "Bot".to_string(),
+                    slot,
+                };
+                XdccSearchResult {
+                    filename: format!("File.{}.mkv", slot),
+                    size: None,
+                    size_str: String::new(),
+                    bot: url.bot.clone(),
+                    network: url.network.clone(),
+                    channel: url.channel.clone(),
+                    slot,
+                    gets: None,
+                    url,
+                    last_seen: None,
+                    annotations: Default::default(),
+                }
+            })
+            .collect();
+
+        let full = serialize_results_capped(&results, 0).unwrap();
+        let capped = serialize_results_capped(&results, full.len() / 3).unwrap();
+        assert!(capped.len() <= full.len() / 3);
+        let kept: Vec<XdccSearchResult> = serde_json::from_str(&capped).unwrap();
+        assert!(!kept.is_empty() && kept.len() < results.len());
+        assert_eq!(kept[0].slot, 1);
+
+        assert!(serialize_results_capped(&results, 10).is_none());
+    }
+
+    #[test]
+    fn test_maintenance() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("botarr.db")).unwrap();
+        assert!(db.maintenance_due(chrono::Duration::weeks(1)));
+        {
+            let conn = db.conn.lock().unwrap();
+            for i in 0..200 {
+                conn.execute(
+                    "INSERT INTO search_history (query, results_json, searched_at)
+                     VALUES (?1, ?2, '2024-01-01T00:00:00Z')",
+                    params![format!("query {}", i), "x".repeat(4096)],
+                )
+                .unwrap();
+            }
+            conn.execute("DELETE FROM search_history", []).unwrap();
+        }
+
+        let report = db.run_maintenance().unwrap();
+        assert!(report.size_after < report.size_before);
+        assert_eq!(db.stats().unwrap().free_bytes, 0);
+        assert!(!db.maintenance_due(chrono::Duration::weeks(1)));
+        assert_eq!(
+            db.last_maintenance().unwrap().unwrap().ran_at,
+            report.ran_at
+        );
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let db = Database::new(":memory:").unwrap();
+        let now = Utc::now().to_rfc3339();
+        let record = DownloadRecord {
+            id: "abc".to_string(),
+            file_name: Some("Show.S01E01.mkv".to_string()),
+            size: Some(100),
+            network: "irc.rizon.net".to_string(),
+            bot: "Bot".to_string(),
+            channel: "#news".to_string(),
+            slot: 1,
+            priority: "normal".to_string(),
+            status: "Completed".to_string(),
+            error: None,
+            created_at: now.clone(),
+            completed_at: now,
+            category: None,
+            labels: vec!["sonarr".to_string()],
+            note: None,
+            template: None,
+            after: None,
+            method: None,
+            extraction: None,
+            max_speed_kbps: None,
+        };
+        db.insert_download(&record).unwrap();
+        assert_eq!(
+            db.list_downloads(1, 10, Some("Sonarr")).unwrap().items[0].labels,
+            vec!["sonarr"]
+        );
+        assert_eq!(db.list_downloads(1, 10, Some("radarr")).unwrap().total, 0);
+
+        assert!(db
+            .trash_download("abc", Some("/dl/.trash/abc_Show.S01E01.mkv"))
+            .unwrap());
+        assert_eq!(db.list_downloads(1, 10, None).unwrap().total, 0);
+        assert!(db.get_download("abc").unwrap().is_none());
+        assert!(!db
+            .is_url_downloaded("irc.rizon.net", "Bot", "#news", 1)
+            .unwrap());
+        assert_eq!(db.list_trash().unwrap().len(), 1);
+        // Saving the record again doesn't take it out of the trash
+        db.insert_download(&DownloadRecord {
+            note: Some("saved again".to_string()),
+            ..record
+        })
+        .unwrap();
+        assert_eq!(db.list_downloads(1, 10, None).unwrap().total, 0);
+        let trashed = db.list_trash().unwrap();
+        assert_eq!(trashed[0].record.note.as_deref(), Some("saved again"));
+        assert!(trashed[0].trash_path.is_some());
+        assert!(db.download_ids().unwrap().contains("abc"));
+        // Not old enough to purge yet
+        assert!(db.purge_trash(Some(7)).unwrap().is_empty());
+
+        assert!(db.restore_download("abc").unwrap());
+        assert_eq!(db.list_downloads(1, 10, None).unwrap().total, 1);
+        assert!(db.list_trash().unwrap().is_empty());
+
+        db.clear_download_history().unwrap();
+        assert_eq!(db.purge_trash(None).unwrap(), Vec::<String>::new());
+        assert!(db.list_trash().unwrap().is_empty());
+        assert!(db.download_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_traffic_accounting() {
+        let db = Database::new(":memory:").unwrap();
+        db.add_traffic("irc.rizon.net", 1000).unwrap();
+        db.add_traffic("irc.rizon.net", 500).unwrap();
+        db.add_traffic("irc.abjects.net", 42).unwrap();
+
+        let records = db.list_traffic(30).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].network, "irc.abjects.net");
+        assert_eq!(records[1].bytes, 1500);
+        assert_eq!(db.month_traffic().unwrap(), 1542);
+    }
+
+    #[test]
+    fn test_transfer_checkpoints() {
+        let db = Database::new(":memory:").unwrap();
+        let mut checkpoint = TransferCheckpoint {
+            id: "t1".to_string(),
+            status: "Downloading".to_string(),
+            downloaded: 100,
+            size: Some(1000),
+            file_name: Some("a.mkv".to_string()),
+            download_dir: None,
+            retry_count: 1,
+            queue_position: None,
+            updated_at: Utc::now().to_rfc3339(),
+            max_speed_kbps: Some(500),
+        };
+        db.save_transfer_checkpoint(&checkpoint).unwrap();
+        checkpoint.downloaded = 400;
+        db.save_transfer_checkpoint(&checkpoint).unwrap();
+
+        let checkpoints = db.list_transfer_checkpoints().unwrap();
+        assert_eq!(checkpoints.len(), 1);
+        assert_eq!(checkpoints["t1"], checkpoint);
+        assert!(db.delete_transfer_checkpoint("t1").unwrap());
+        assert!(!db.delete_transfer_checkpoint("t1").unwrap());
+        assert!(db.list_transfer_checkpoints().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_partial_files() {
+        let db = Database::new(":memory:").unwrap();
+        let url = "irc://irc.rizon.net/news/Bot/7";
+        db.save_partial_file("/dl/a.mkv", url, "a.mkv", 1000, 100, Some("t1"))
+            .unwrap();
+        db.save_partial_file("/dl/a.mkv", url, "a.mkv", 1000, 400, Some("t1"))
+            .unwrap();
+        db.save_partial_file("/dl/b.mkv", url, "b.mkv", 50, 10, Some("t2"))
+            .unwrap();
+
+        let files = db.list_partial_files().unwrap();
+        assert_eq!(files.len(), 2);
+        let a = files.iter().find(|f| f.path == "/dl/a.mkv").unwrap();
+        assert_eq!((a.offset, a.size), (400, 1000));
+        assert_eq!(db.get_partial_file(a.id).unwrap().as_ref(), Some(a));
+
+        assert_eq!(db.delete_partial_files_of("t1").unwrap(), 1);
+        let b = &db.list_partial_files().unwrap()[0];
+        assert!(db.delete_partial_file(b.id).unwrap());
+        assert!(db.list_partial_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_watchlist() {
+        let db = Database::new(":memory:").unwrap();
+        let mut entry = WatchlistEntry {
+            id: 0,
+            name: "Show".to_string(),
+            query: "show 1080p -hevc".to_string(),
+            min_size: Some(100 << 20),
+            max_size: None,
+            network: Some("rizon".to_string()),
+            bot: None,
+            category: Some("tv".to_string()),
+            template: None,
+            interval_mins: 60,
+            enabled: true,
+            last_run_at: None,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        entry.id = db.insert_watchlist_entry(&entry).unwrap();
+        assert_eq!(
+            db.get_watchlist_entry(entry.id).unwrap().as_ref(),
+            Some(&entry)
+        );
+
+        entry.enabled = false;
+        assert!(db.update_watchlist_entry(&entry).unwrap());
+        db.set_watchlist_run(entry.id, "2026-01-01T00:00:00+00:00")
+            .unwrap();
+        let stored = &db.list_watchlist().unwrap()[0];
+        assert!(!stored.enabled);
+        assert_eq!(
+            stored.last_run_at.as_deref(),
+            Some("2026-01-01T00:00:00+00:00")
+        );
+
+        let hit = WatchlistHit {
+            file_name: "Show.S01E01.1080p.mkv".to_string(),
+            url: "irc://irc.rizon.net/news/Bot/7".to_string(),
+            transfer_id: Some("t1".to_string()),
+            found_at: Utc::now().to_rfc3339(),
+        };
+        db.add_watchlist_hit(entry.id, &hit).unwrap();
+        db.add_watchlist_hit(entry.id, &hit).unwrap();
+        assert!(db
+            .has_watchlist_hit(entry.id, "show.s01e01.1080P.mkv")
+            .unwrap());
+        assert!(!db.has_watchlist_hit(entry.id + 1, &hit.file_name).unwrap());
+        assert_eq!(db.list_watchlist_hits(entry.id, 10).unwrap(), vec![hit]);
+
+        assert!(db.delete_watchlist_entry(entry.id).unwrap());
+        assert!(!db.delete_watchlist_entry(entry.id).unwrap());
+        assert!(db.list_watchlist_hits(entry.id, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_downloads_range() {
+        let db = Database::new(":memory:").unwrap();
+        let categories = [Some("tv"), Some("movie"), Some("TV"), None, Some("tv")];
+        for (i, category) in categories.into_iter().enumerate() {
+            let at = (Utc::now() - chrono::Duration::minutes(i as i64)).to_rfc3339();
+            db.insert_download(&DownloadRecord {
+                id: i.to_string(),
+                file_name: None,
+                size: None,
+                network: "irc.rizon.net".to_string(),
+                bot: "Bot".to_string(),
+                channel: "#news".to_string(),
+                slot: i as i32,
+                priority: "normal".to_string(),
+                status: "Completed".to_string(),
+                error: None,
+                created_at: at.clone(),
+                completed_at: at,
+                category: category.map(str::to_string),
+                labels: Vec::new(),
+                note: None,
+                template: None,
+                after: None,
+                method: None,
+                extraction: None,
+                max_speed_kbps: None,
+            })
+            .unwrap();
+        }
+        let ids = |(items, total): (Vec<DownloadRecord>, i64)| {
+            (items.into_iter().map(|r| r.id).collect::<Vec<_>>(), total)
+        };
+        assert_eq!(
+            ids(db.list_downloads_range(1, 2, Some("tv")).unwrap()),
+            (vec!["2".to_string(), "4".to_string()], 3)
+        );
+        assert_eq!(
+            ids(db.list_downloads_range(3, 10, None).unwrap()),
+            (vec!["3".to_string(), "4".to_string()], 5)
+        );
+        assert_eq!(db.list_downloads_range(0, 10, Some("*")).unwrap().1, 5);
+    }
+
+    #[test]
+    fn test_transfer_events_compression() {
+        let db = Database::new(":memory:").unwrap();
+        let long_ago = (Utc::now() - chrono::Duration::days(30)).to_rfc3339();
+        for (id, status) in [("done", "Completed"), ("failed", "Failed")] {
+            db.insert_download(&DownloadRecord {
+                id: id.to_string(),
+                file_name: None,
+                size: None,
+                network: "irc.rizon.net".to_string(),
+                bot: "Bot".to_string(),
+                channel: "#news".to_string(),
+                slot: 1,
+                priority: "normal".to_string(),
+                status: status.to_string(),
+                error: None,
+                created_at: long_ago.clone(),
+                completed_at: long_ago.clone(),
+                category: None,
+                labels: Vec::new(),
+                note: None,
+                template: None,
+                after: None,
+                method: None,
+                extraction: None,
+                max_speed_kbps: None,
+            })
+            .unwrap();
+        }
+        let events = TransferEvents {
+            logs: (0..200)
+                .map(|i| format!("[2024-01-01] Progress {}", i))
+                .collect(),
+            speed_samples: (0..100).map(|i| (i * 10, 1024.0 * i as f64)).collect(),
+        };
+        for id in ["done", "failed", "gone"] {
+            db.save_transfer_events(id, &events).unwrap();
+        }
+
+        assert_eq!(db.compress_transfer_events(7).unwrap(), 1);
+        // Already compressed, and the others don't qualify
+        assert_eq!(db.compress_transfer_events(7).unwrap(), 0);
+        assert_eq!(
+            db.get_transfer_events("done").unwrap(),
+            Some(events.clone())
+        );
+        assert_eq!(db.get_transfer_events("failed").unwrap(), Some(events));
+        assert_eq!(db.get_transfer_events("gone").unwrap(), None);
+    }
 }
@@ -0,0 +1,58 @@
+//! Live Events
+//!
+//! State changes other clients should know about are broadcast to every
+//! connection of the `/api/events` WebSocket as JSON, so open UI tabs and
+//! automations refresh instead of working from stale data.
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events a slow client may fall behind by before it misses some
+const CAPACITY: usize = 64;
+
+/// Settings changed through `PUT /api/settings`
+pub const SETTINGS: &str = "settings";
+/// A network was added, changed or removed
+pub const NETWORKS: &str = "networks";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AppEvent {
+    /// The config was changed and saved
+    ConfigChanged {
+        section: &'static str,
+        /// Network that changed, for `networks`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        network: Option<String>,
+        /// `X-Request-Id` of the change, so its sender can skip its own event
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<String>,
+    },
+}
+
+pub fn channel() -> broadcast::Sender<AppEvent> {
+    broadcast::channel(CAPACITY).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = AppEvent::ConfigChanged {
+            section: NETWORKS,
+            network: Some("rizon".to_string()),
+            request_id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"type": "config_changed", "section": "networks", "network": "rizon"})
+        );
+
+        let events = channel();
+        let mut rx = events.subscribe();
+        events.send(event.clone()).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), event);
+    }
+}
@@ -0,0 +1,176 @@
+//! Analytics Export Module
+//!
+//! Builds a snapshot of download history and analytics that can be attached to
+//! a bug report. Redaction strips file names and replaces bot names with stable
+//! placeholders, keeping sizes, durations and networks.
+
+use crate::db::DownloadRecord;
+use crate::xdcc::transfer::{BotStats, DownloadAnalytics};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Download history entry as exported
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedDownload {
+    pub network: String,
+    pub bot: String,
+    pub file_name: Option<String>,
+    pub size: Option<i64>,
+    pub status: String,
+    pub priority: String,
+    pub category: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    /// Seconds between the transfer being created and finishing
+    pub duration_secs: Option<i64>,
+}
+
+/// Exported analytics snapshot
+#[derive(Debug, Serialize)]
+pub struct AnalyticsExport {
+    pub version: &'static str,
+    pub generated_at: DateTime<Utc>,
+    pub redacted: bool,
+    pub analytics: DownloadAnalytics,
+    pub bots: Vec<BotStats>,
+    pub downloads: Vec<ExportedDownload>,
+}
+
+/// Replaces bot names with `bot-1`, `bot-2`, ... consistently across an export
+#[derive(Debug, Default)]
+struct Redactor {
+    bots: HashMap<String, String>,
+}
+
+impl Redactor {
+    fn bot(&mut self, name: &str) -> String {
+        let next = self.bots.len() + 1;
+        self.bots
+            .entry(name.to_lowercase())
+            .or_insert_with(|| format!("bot-{}", next))
+            .clone()
+    }
+
+    fn download(&mut self, mut download: ExportedDownload) -> ExportedDownload {
+        let bot = self.bot(&download.bot);
+        let file_name = download.file_name.take();
+        download.error = download.error.map(|error| {
+            let mut error = error.replace(&download.bot, &bot);
+            if let Some(name) = file_name.as_deref().filter(|n| !n.is_empty()) {
+                error = error.replace(name, "<file>");
+            }
+            error
+        });
+        download.bot = bot;
+        download
+    }
+}
+
+impl From<DownloadRecord> for ExportedDownload {
+    fn from(record: DownloadRecord) -> Self {
+        let duration_secs = match (
+            DateTime::parse_from_rfc3339(&record.created_at),
+            DateTime::parse_from_rfc3339(&record.completed_at),
+        ) {
+            (Ok(start), Ok(end)) if end >= start => Some((end - start).num_seconds()),
+            _ => None,
+        };
+        Self {
+            network: record.network,
+            bot: record.bot,
+            file_name: record.file_name,
+            size: record.size,
+            status: record.status,
+            priority: record.priority,
+            category: record.category,
+            error: record.error,
+            created_at: record.created_at,
+            duration_secs,
+        }
+    }
+}
+
+/// Build an export, redacting file and bot names when `redact` is set
+pub fn build_export(
+    records: Vec<DownloadRecord>,
+    mut bots: Vec<BotStats>,
+    mut analytics: DownloadAnalytics,
+    redact: bool,
+) -> AnalyticsExport {
+    let mut downloads: Vec<ExportedDownload> = records.into_iter().map(Into::into).collect();
+
+    if redact {
+        let mut redactor = Redactor::default();
+        downloads = downloads
+            .into_iter()
+            .map(|d| redactor.download(d))
+            .collect();
+        for stats in &mut bots {
+            stats.bot_name = redactor.bot(&stats.bot_name);
+        }
+        analytics.most_reliable_bot = analytics.most_reliable_bot.map(|b| redactor.bot(&b));
+    }
+
+    AnalyticsExport {
+        version: env!("CARGO_PKG_VERSION"),
+        generated_at: Utc::now(),
+        redacted: redact,
+        analytics,
+        bots,
+        downloads,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(bot: &str, file: &str, error: Option<&str>) -> DownloadRecord {
+        DownloadRecord {
+            id: "1".to_string(),
+            file_name: Some(file.to_string()),
+            size: Some(1024),
+            network: "irc.rizon.net".to_string(),
+            bot: bot.to_string(),
+            channel: "#chan".to_string(),
+            slot: 1,
+            priority: "normal".to_string(),
+            status: "failed".to_string(),
+            error: error.map(str::to_string),
+            created_at: "2024-01-01T00:00:00+00:00".to_string(),
+            completed_at: "2024-01-01T00:01:30+00:00".to_string(),
+            category: None,
+            labels: Vec::new(),
+            note: None,
+            template: None,
+            after: None,
+            method: None,
+            extraction: None,
+            max_speed_kbps: None,
+        }
+    }
+
+    #[test]
+    fn test_redacted_export() {
+        let records = vec![
+            record(
+                "Ginpachi-Sensei",
+                "Show.S01E01.mkv",
+                Some("Ginpachi-Sensei refused Show.S01E01.mkv"),
+            ),
+            record("Other|Bot", "Movie.mkv", None),
+            record("ginpachi-sensei", "Show.S01E02.mkv", None),
+        ];
+        let export = build_export(records, Vec::new(), DownloadAnalytics::default(), true);
+
+        let d = &export.downloads;
+        assert_eq!(d[0].bot, "bot-1");
+        assert_eq!(d[1].bot, "bot-2");
+        assert_eq!(d[2].bot, "bot-1");
+        assert!(d.iter().all(|d| d.file_name.is_none()));
+        assert_eq!(d[0].error.as_deref(), Some("bot-1 refused <file>"));
+        assert_eq!(d[0].duration_secs, Some(90));
+        assert_eq!(d[0].network, "irc.rizon.net");
+    }
+}
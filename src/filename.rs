@@ -0,0 +1,152 @@
+//! File Names
+//!
+//! File names come from bots and can contain anything. `filename_policy`
+//! decides how they are made safe to save:
+//!
+//! - `windows` (default): replaces the characters Windows and SMB shares
+//!   reject (`<>:"/\|?*` and control characters), trims trailing dots and
+//!   spaces and escapes reserved device names such as `CON`
+//! - `posix`: only replaces `/` and NUL, keeping scene names intact
+//!
+//! Unicode is kept either way. When a finished file already has the name, the
+//! new one is saved as `Name (1).mkv`, `Name (2).mkv`... instead of replacing it.
+
+use crate::config::AppConfig;
+use std::path::{Path, PathBuf};
+
+/// Device names Windows reserves regardless of extension
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// How file names are made safe to save
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilenamePolicy {
+    #[default]
+    Windows,
+    Posix,
+}
+
+impl FilenamePolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        match config.filename_policy.as_str() {
+            "posix" => Self::Posix,
+            _ => Self::Windows,
+        }
+    }
+
+    /// Make `name` usable as a single file name. Applying it twice changes nothing.
+    pub fn sanitize(self, name: &str) -> String {
+        let mut safe: String = match self {
+            Self::Windows => name
+                .chars()
+                .map(|c| {
+                    if c.is_control()
+                        || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+                    {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect(),
+            Self::Posix => name.replace(['/', '\0'], "_"),
+        };
+        if self == Self::Windows {
+            safe.truncate(safe.trim_end_matches(['.', ' ']).len());
+            let stem = safe.split('.').next().unwrap_or_default().trim_end();
+            if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+                safe.insert(0, '_');
+            }
+        }
+        if matches!(safe.as_str(), "" | "." | "..") {
+            return "_".to_string();
+        }
+        safe
+    }
+}
+
+/// `dir/name`, or `dir/Name (n).ext` with the first `n` that isn't taken
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| !p.exists())
+        .expect("unbounded range")
+}
+
+/// Where a saved file named `name` is in `dir`. Names are recorded as saved,
+/// but downloads from before the policy was configurable hold the name the
+/// bot offered, which was saved in its Windows-safe form.
+pub fn saved_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(FilenamePolicy::Posix.sanitize(name));
+    if path.exists() {
+        return path;
+    }
+    let legacy = dir.join(FilenamePolicy::Windows.sanitize(name));
+    if legacy.exists() {
+        legacy
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        let windows = FilenamePolicy::Windows;
+        let posix = FilenamePolicy::Posix;
+        let name = "[Grp] Show: Part 2 - 01 <1080p>?.mkv";
+        assert_eq!(
+            windows.sanitize(name),
+            "[Grp] Show_ Part 2 - 01 _1080p__.mkv"
+        );
+        assert_eq!(posix.sanitize(name), name);
+        assert_eq!(posix.sanitize("../a/b\0.mkv"), ".._a_b_.mkv");
+        assert_eq!(
+            windows.sanitize("進撃の巨人 – Épisode 01.mkv"),
+            "進撃の巨人 – Épisode 01.mkv"
+        );
+        assert_eq!(windows.sanitize("Show\t01.mkv. "), "Show_01.mkv");
+        assert_eq!(windows.sanitize("con.txt"), "_con.txt");
+        assert_eq!(windows.sanitize("Console.txt"), "Console.txt");
+        assert_eq!(windows.sanitize(".."), "_");
+        assert_eq!(posix.sanitize(""), "_");
+        for policy in [windows, posix] {
+            let once = policy.sanitize(name);
+            assert_eq!(policy.sanitize(&once), once);
+        }
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        assert_eq!(unique_path(dir, "Show.01.mkv"), dir.join("Show.01.mkv"));
+        std::fs::write(dir.join("Show.01.mkv"), b"a").unwrap();
+        assert_eq!(unique_path(dir, "Show.01.mkv"), dir.join("Show.01 (1).mkv"));
+        std::fs::write(dir.join("Show.01 (1).mkv"), b"b").unwrap();
+        assert_eq!(unique_path(dir, "Show.01.mkv"), dir.join("Show.01 (2).mkv"));
+        std::fs::write(dir.join(".nfo"), b"c").unwrap();
+        assert_eq!(unique_path(dir, ".nfo"), dir.join(".nfo (1)"));
+
+        // Legacy entries recorded the offered name
+        std::fs::write(dir.join("A_B.mkv"), b"d").unwrap();
+        assert_eq!(saved_path(dir, "A:B.mkv"), dir.join("A_B.mkv"));
+        assert_eq!(
+            saved_path(dir, "Show.01 (1).mkv"),
+            dir.join("Show.01 (1).mkv")
+        );
+    }
+}
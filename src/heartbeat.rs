@@ -0,0 +1,83 @@
+//! Heartbeat Module
+//!
+//! Periodically pings a healthchecks.io style URL while the service is healthy,
+//! so a dead or wedged instance on a headless box gets noticed quickly.
+
+use crate::AppState;
+use std::time::Duration;
+
+/// Health of the service as seen by the heartbeat
+#[derive(Debug)]
+pub struct HealthStatus {
+    pub database_ok: bool,
+    pub reachable_providers: Vec<String>,
+}
+
+impl HealthStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.database_ok && !self.reachable_providers.is_empty()
+    }
+}
+
+/// Check the database and search providers
+pub async fn check_health(state: &AppState) -> HealthStatus {
+    let database_ok = match state.database.health_check() {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("Heartbeat: database check failed: {}", e);
+            false
+        }
+    };
+
+    HealthStatus {
+        database_ok,
+        reachable_providers: state.search_aggregator.reachable_providers().await,
+    }
+}
+
+/// Spawn the background heartbeat loop
+pub fn spawn_heartbeat(state: AppState) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        loop {
+            let (url, interval_mins) = {
+                let cfg = state.config.read().await;
+                (
+                    cfg.heartbeat_url.clone(),
+                    cfg.heartbeat_interval_mins.max(1),
+                )
+            };
+
+            if url.is_empty() {
+                // Disabled: check again shortly in case it gets configured
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+
+            let health = check_health(&state).await;
+            // healthchecks.io convention: append /fail to signal an unhealthy state
+            let target = if health.is_healthy() {
+                url.clone()
+            } else {
+                tracing::warn!("Heartbeat: service unhealthy: {:?}", health);
+                format!("{}/fail", url.trim_end_matches('/'))
+            };
+
+            match client.get(&target).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    tracing::debug!("Heartbeat ping sent to {}", target);
+                }
+                Ok(resp) => {
+                    tracing::warn!("Heartbeat ping to {} returned {}", target, resp.status());
+                }
+                Err(e) => tracing::warn!("Heartbeat ping to {} failed: {}", target, e),
+            }
+
+            tokio::time::sleep(Duration::from_secs(interval_mins * 60)).await;
+        }
+    });
+}
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
+
+use crate::config::AppConfig;
 use tokio::sync::{broadcast, mpsc, RwLock};
 
 #[derive(serde::Serialize, Clone, Debug)]
@@ -26,20 +27,24 @@ pub struct WsCommand {
 
 pub struct IrcClientConnection {
     pub tx: mpsc::Sender<String>,
+    /// Last time the user sent something on this connection
+    pub last_used: std::sync::Mutex<Instant>,
 }
 
 #[derive(Clone)]
 pub struct InteractiveClientManager {
     connections: Arc<RwLock<HashMap<String, IrcClientConnection>>>,
     pub ws_tx: broadcast::Sender<WsMessage>,
+    config: Arc<RwLock<AppConfig>>,
 }
 
 impl InteractiveClientManager {
-    pub fn new() -> Self {
+    pub fn new(config: Arc<RwLock<AppConfig>>) -> Self {
         let (ws_tx, _) = broadcast::channel(1024);
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
             ws_tx,
+            config,
         }
     }
 
@@ -56,22 +61,71 @@ impl InteractiveClientManager {
                 if let (Some(network), Some(message)) = (cmd.network, cmd.message) {
                     let conns = self.connections.read().await;
                     if let Some(conn) = conns.get(&network) {
+                        *conn.last_used.lock().unwrap() = Instant::now();
                         let _ = conn.tx.send(format!("{}\r\n", message)).await;
                     }
                 }
             }
             "disconnect" => {
                 if let Some(network) = cmd.network {
-                    let mut conns = self.connections.write().await;
-                    if let Some(conn) = conns.remove(&network) {
-                        let _ = conn.tx.send("QUIT :Leaving\r\n".to_string()).await;
-                    }
+                    self.disconnect(&network, "Leaving").await;
                 }
             }
             _ => {}
         }
     }
 
+    /// Send QUIT and drop the connection to a network; false if not connected
+    pub async fn disconnect(&self, network: &str, reason: &str) -> bool {
+        let conn = self.connections.write().await.remove(network);
+        match conn {
+            Some(conn) => {
+                let _ = conn.tx.send(format!("QUIT :{}\r\n", reason)).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disconnect connections nothing was sent on for longer than `idle`
+    pub async fn reap_idle(&self, idle: Duration) -> Vec<String> {
+        let idle_networks: Vec<String> = self
+            .connections
+            .read()
+            .await
+            .iter()
+            .filter(|(_, conn)| conn.last_used.lock().unwrap().elapsed() > idle)
+            .map(|(network, _)| network.clone())
+            .collect();
+
+        for network in &idle_networks {
+            self.disconnect(network, "Idle").await;
+            let _ = self.ws_tx.send(WsMessage {
+                r#type: "status".to_string(),
+                network: network.clone(),
+                target: None,
+                message: format!("Disconnected after {} minutes idle.", idle.as_secs() / 60),
+            });
+        }
+        idle_networks
+    }
+
+    /// Periodically disconnect idle connections
+    pub fn spawn_idle_reaper(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                let idle_mins = self.config.read().await.irc_idle_timeout_mins;
+                if idle_mins == 0 {
+                    continue;
+                }
+                for network in self.reap_idle(Duration::from_secs(idle_mins * 60)).await {
+                    tracing::info!("Closed idle IRC connection to {}", network);
+                }
+            }
+        });
+    }
+
     async fn connect(&self, network: String, host: String, port: u16, ssl: bool, nick: String) {
         let mut conns = self.connections.write().await;
         if conns.contains_key(&network) {
@@ -84,11 +138,32 @@ impl InteractiveClientManager {
             return;
         }
 
+        let max_connections = self.config.read().await.irc_max_connections;
+        if max_connections > 0 && conns.len() >= max_connections {
+            let _ = self.ws_tx.send(WsMessage {
+                r#type: "error".to_string(),
+                network: network.clone(),
+                target: None,
+                message: format!(
+                    "Connection limit reached ({} networks); disconnect one first",
+                    max_connections
+                ),
+            });
+            return;
+        }
+
         let (tx, mut rx) = mpsc::channel::<String>(100);
-        conns.insert(network.clone(), IrcClientConnection { tx });
+        conns.insert(
+            network.clone(),
+            IrcClientConnection {
+                tx,
+                last_used: std::sync::Mutex::new(Instant::now()),
+            },
+        );
 
         let ws_tx = self.ws_tx.clone();
         let connections = self.connections.clone();
+        let outbound = crate::net::Outbound::from_config(&*self.config.read().await);
 
         tokio::spawn(async move {
             let server_addr = format!("{}:{}", host, port);
@@ -102,7 +177,7 @@ impl InteractiveClientManager {
 
             let tcp_stream = match tokio::time::timeout(
                 Duration::from_secs(15),
-                TcpStream::connect(&server_addr),
+                outbound.connect(&server_addr, false),
             )
             .await
             {
@@ -0,0 +1,328 @@
+//! Library Module
+//!
+//! Scans a local media library folder for a series and reports missing episodes,
+//! so gaps can be filled through search or autodl watchlist filters.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Path of the autodl filter file used as the watchlist
+pub const AUTODL_FILTERS_PATH: &str = "plugins/autodl.json";
+
+/// Video extensions considered when scanning a library folder
+pub const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "avi", "m4v", "ts", "wmv", "mov", "webm"];
+
+/// Longest season gaps are looked for in, declared or found
+pub const MAX_EPISODES_PER_SEASON: u32 = 1000;
+/// Most seasons whose length a gap scan may declare
+pub const MAX_SEASONS: usize = 100;
+
+/// Lowercase a title and collapse separators so `Show.Name` matches `show name`
+pub fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A season/episode pair
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EpisodeNumber {
+    pub season: u32,
+    pub episode: u32,
+}
+
+impl std::fmt::Display for EpisodeNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "S{:02}E{:02}", self.season, self.episode)
+    }
+}
+
+/// Episode naming scheme used to parse file names
+pub struct NamingScheme {
+    patterns: Vec<Regex>,
+}
+
+impl Default for NamingScheme {
+    fn default() -> Self {
+        Self {
+            patterns: vec![
+                // Show.S01E02, Show.s1e2, multi-episode S01E02E03 (first episode only)
+                Regex::new(r"(?i)\bS(?P<season>\d{1,2})[ ._-]?E(?P<episode>\d{1,4})").unwrap(),
+                // Show 1x02
+                Regex::new(r"(?i)\b(?P<season>\d{1,2})x(?P<episode>\d{2,3})\b").unwrap(),
+            ],
+        }
+    }
+}
+
+impl NamingScheme {
+    /// Build a scheme from a custom regex with `season` and `episode` named groups
+    pub fn custom(pattern: &str) -> Result<Self, String> {
+        let re = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+        let names: Vec<_> = re.capture_names().flatten().collect();
+        if !names.contains(&"season") || !names.contains(&"episode") {
+            return Err("Pattern must contain 'season' and 'episode' named groups".to_string());
+        }
+        Ok(Self { patterns: vec![re] })
+    }
+
+    /// Series title part of a file name (everything before the episode marker)
+    pub fn title<'a>(&self, name: &'a str) -> Option<&'a str> {
+        self.patterns.iter().find_map(|re| {
+            let m = re.find(name)?;
+            Some(name[..m.start()].trim_end_matches([' ', '.', '_', '-']))
+        })
+    }
+
+    /// Parse the season/episode from a file name
+    pub fn parse(&self, name: &str) -> Option<EpisodeNumber> {
+        self.patterns.iter().find_map(|re| {
+            let caps = re.captures(name)?;
+            Some(EpisodeNumber {
+                season: caps["season"].parse().ok()?,
+                episode: caps["episode"].parse().ok()?,
+            })
+        })
+    }
+}
+
+/// Collect the episodes present in a library folder (recursively). Symlinked
+/// folders aren't followed, so links can't lead the scan out of the library
+/// or around in a loop.
+pub fn scan_episodes(
+    root: &Path,
+    scheme: &NamingScheme,
+) -> std::io::Result<BTreeMap<u32, BTreeSet<u32>>> {
+    let mut found: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let is_video = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| VIDEO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                .unwrap_or(false);
+            if !is_video {
+                continue;
+            }
+
+            if let Some(ep) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| scheme.parse(n))
+            {
+                found.entry(ep.season).or_default().insert(ep.episode);
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// List missing episodes per season.
+///
+/// Without an expected episode count, a season is assumed to run up to the
+/// highest episode found in it. Seasons are cut off at
+/// [`MAX_EPISODES_PER_SEASON`] episodes.
+pub fn find_gaps(
+    found: &BTreeMap<u32, BTreeSet<u32>>,
+    expected: &BTreeMap<u32, u32>,
+) -> Vec<EpisodeNumber> {
+    let seasons: BTreeSet<u32> = found.keys().chain(expected.keys()).copied().collect();
+
+    let mut missing = Vec::new();
+    for season in seasons {
+        let present = found.get(&season);
+        let last = expected
+            .get(&season)
+            .copied()
+            .or_else(|| present.and_then(|p| p.iter().next_back().copied()))
+            .unwrap_or(0)
+            .min(MAX_EPISODES_PER_SEASON);
+
+        for episode in 1..=last {
+            if !present.is_some_and(|p| p.contains(&episode)) {
+                missing.push(EpisodeNumber { season, episode });
+            }
+        }
+    }
+    missing
+}
+
+/// Build an autodl watchlist filter for a missing episode.
+///
+/// Filters are named `EVENT:` so they are removed once a matching file completes.
+pub fn watchlist_filter(
+    series: &str,
+    ep: EpisodeNumber,
+    network: Option<&str>,
+    channels: &[String],
+) -> serde_json::Value {
+    let keywords: Vec<String> = series.split_whitespace().map(str::to_string).collect();
+    let mut filter = serde_json::json!({
+        "name": format!("EVENT: {} {}", series, ep),
+        "keywords": keywords,
+        "match": format!(r"(?i)S0*{}E0*{}\b", ep.season, ep.episode),
+        "channels": channels,
+    });
+    if let Some(network) = network {
+        filter["network"] = serde_json::Value::String(network.to_string());
+    }
+    filter
+}
+
+/// The autodl watchlist file, or an empty enabled one
+pub fn read_watchlist() -> serde_json::Value {
+    std::fs::read_to_string(AUTODL_FILTERS_PATH)
+        .ok()
+        .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+        .unwrap_or_else(|| serde_json::json!({ "enabled": true, "filters": [] }))
+}
+
+/// Write the watchlist through a temporary file, so the autodl plugin never
+/// reads a half-written one
+fn write_watchlist(json: &serde_json::Value) -> std::io::Result<()> {
+    let content = serde_json::to_string_pretty(json).map_err(std::io::Error::other)?;
+    let tmp = format!("{}.tmp", AUTODL_FILTERS_PATH);
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, AUTODL_FILTERS_PATH)
+}
+
+/// Add filters to a watchlist, skipping ones that already exist by name, or
+/// replace its filters with them. Returns the number of filters added.
+pub fn merge_watchlist(
+    json: &mut serde_json::Value,
+    filters: Vec<serde_json::Value>,
+    replace: bool,
+) -> usize {
+    if replace || !json.get("filters").is_some_and(|f| f.is_array()) {
+        json["filters"] = serde_json::json!([]);
+    }
+    let existing = json["filters"].as_array_mut().unwrap();
+
+    let mut added = 0;
+    for filter in filters {
+        let exists = existing
+            .iter()
+            .any(|f| f.get("name").is_some() && f.get("name") == filter.get("name"));
+        if !exists {
+            existing.push(filter);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Append filters to the autodl watchlist, skipping ones that already exist by name.
+/// Returns the number of filters added.
+pub fn add_watchlist_filters(filters: Vec<serde_json::Value>) -> std::io::Result<usize> {
+    import_watchlist_filters(filters, false)
+}
+
+/// Add filters to the autodl watchlist file, or replace the ones in it.
+/// Returns the number of filters added.
+pub fn import_watchlist_filters(
+    filters: Vec<serde_json::Value>,
+    replace: bool,
+) -> std::io::Result<usize> {
+    let mut json = read_watchlist();
+    let added = merge_watchlist(&mut json, filters, replace);
+    if added > 0 || replace {
+        write_watchlist(&json)?;
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_watchlist() {
+        let filter = |name: &str| serde_json::json!({ "name": name, "keywords": [name] });
+        let mut json = serde_json::json!({ "enabled": false });
+        assert_eq!(
+            merge_watchlist(&mut json, vec![filter("A"), filter("B")], false),
+            2
+        );
+        assert_eq!(
+            merge_watchlist(&mut json, vec![filter("B"), filter("C")], false),
+            1
+        );
+        assert_eq!(json["filters"].as_array().unwrap().len(), 3);
+        assert_eq!(json["enabled"], false);
+
+        assert_eq!(merge_watchlist(&mut json, vec![filter("D")], true), 1);
+        assert_eq!(json["filters"], serde_json::json!([filter("D")]));
+    }
+
+    #[test]
+    fn test_parse_and_find_gaps() {
+        let scheme = NamingScheme::default();
+        assert_eq!(
+            scheme.parse("Show.Name.S01E05.1080p.mkv"),
+            Some(EpisodeNumber {
+                season: 1,
+                episode: 5
+            })
+        );
+        assert_eq!(
+            scheme.parse("Show Name - 2x03.avi"),
+            Some(EpisodeNumber {
+                season: 2,
+                episode: 3
+            })
+        );
+        assert_eq!(scheme.parse("Movie.2024.1080p.mkv"), None);
+
+        let mut found = BTreeMap::new();
+        found.insert(1, BTreeSet::from([1, 2, 4]));
+        let mut expected = BTreeMap::new();
+        expected.insert(2, 2);
+
+        let gaps = find_gaps(&found, &expected);
+        let names: Vec<String> = gaps.iter().map(|g| g.to_string()).collect();
+        assert_eq!(names, vec!["S01E03", "S02E01", "S02E02"]);
+
+        let found = BTreeMap::from([(1, BTreeSet::from([u32::MAX]))]);
+        assert_eq!(
+            find_gaps(&found, &BTreeMap::new()).len(),
+            MAX_EPISODES_PER_SEASON as usize
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_symlinked_folders() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        let season = root.join("Season 1");
+        std::fs::create_dir_all(&season).unwrap();
+        std::fs::write(season.join("Show.S01E01.mkv"), b"").unwrap();
+        // A link back up would loop forever if followed
+        std::os::unix::fs::symlink(root, season.join("loop")).unwrap();
+
+        let found = scan_episodes(root, &NamingScheme::default()).unwrap();
+        assert_eq!(found, BTreeMap::from([(1, BTreeSet::from([1]))]));
+    }
+}
@@ -0,0 +1,154 @@
+//! API Lockdown Module
+//!
+//! An instance exposed to the internet may only need search, downloads and
+//! status. `disabled_api_groups` names groups of endpoints to refuse with
+//! `403`; the setting is read on every request, so changes apply at once.
+
+use crate::api::models::ErrorResponse;
+use crate::AppState;
+use axum::{
+    extract::{Query, Request, State},
+    http::{Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Settings and network configuration
+pub const SETTINGS: &str = "settings";
+/// Files on disk: quarantine, history trash and deletion, partial files,
+/// failure bundles, job packaging and file downloads
+pub const FILES: &str = "files";
+/// Raw IRC console and connection control
+pub const IRC: &str = "irc";
+/// Plugin status and autodl filters
+pub const PLUGINS: &str = "plugins";
+/// System information, self-test, test notifications and Prometheus metrics
+pub const SYSTEM: &str = "system";
+
+pub const GROUPS: &[&str] = &[SETTINGS, FILES, IRC, PLUGINS, SYSTEM];
+
+/// Whether a SABnzbd API call deletes history files (`mode=history&name=delete&del_files=1`)
+fn sabnzbd_deletes_files(uri: &Uri) -> bool {
+    let Ok(Query(params)) = Query::<std::collections::HashMap<String, String>>::try_from_uri(uri)
+    else {
+        return false;
+    };
+    let param = |name: &str| params.get(name).map(String::as_str);
+    param("mode") == Some("history")
+        && param("name") == Some("delete")
+        && param("del_files") == Some("1")
+}
+
+/// Group an endpoint belongs to, if any
+fn group(method: &Method, uri: &Uri) -> Option<&'static str> {
+    let segments: Vec<&str> = uri.path().trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", "settings", ..] => Some(SETTINGS),
+        ["api", "history", "bulk"] if method == Method::POST => Some(FILES),
+        ["api", "history", _] if method == Method::DELETE => Some(FILES),
+        ["sabnzbd", "api"] if sabnzbd_deletes_files(uri) => Some(FILES),
+        ["api", "quarantine", ..]
+        | ["api", "partials", ..]
+        | ["api", "history", "trash"]
+        | ["api", "history", _, "restore" | "thumbnail"]
+        | ["api", "transfers", _, "bundle" | "relocate"]
+        | ["api", "jobs", _, "package"]
+        | ["api", "files", ..] => Some(FILES),
+        ["api", "irc", ..] => Some(IRC),
+        ["api", "plugins", ..] => Some(PLUGINS),
+        ["api", "system" | "notifications", ..] | ["metrics"] => Some(SYSTEM),
+        _ => None,
+    }
+}
+
+/// Middleware refusing endpoints of disabled groups
+pub async fn guard(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(group) = group(req.method(), req.uri()) else {
+        return next.run(req).await;
+    };
+    let disabled = state
+        .config
+        .read()
+        .await
+        .disabled_api_groups
+        .iter()
+        .any(|g| g.eq_ignore_ascii_case(group));
+    if !disabled {
+        return next.run(req).await;
+    }
+
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse {
+            error: format!("The {} API is disabled on this instance", group),
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group() {
+        let group = |method: Method, uri: &str| group(&method, &uri.parse().unwrap());
+        assert_eq!(group(Method::GET, "/api/settings"), Some(SETTINGS));
+        assert_eq!(
+            group(Method::GET, "/api/settings/networks/rizon"),
+            Some(SETTINGS)
+        );
+        assert_eq!(group(Method::GET, "/api/quarantine/3/release"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/history/trash"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/history/7/thumbnail"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/partials/4/resume"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/history/7/restore"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/transfers/abc/bundle"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/jobs/abc/package"), Some(FILES));
+        assert_eq!(group(Method::GET, "/api/files/abc/download"), Some(FILES));
+        assert_eq!(
+            group(Method::GET, "/api/transfers/abc/relocate"),
+            Some(FILES)
+        );
+        assert_eq!(group(Method::GET, "/api/irc/ws"), Some(IRC));
+        assert_eq!(
+            group(Method::GET, "/api/plugins/autodl/filters"),
+            Some(PLUGINS)
+        );
+        assert_eq!(group(Method::GET, "/api/system/selftest"), Some(SYSTEM));
+        assert_eq!(group(Method::GET, "/api/notifications/test"), Some(SYSTEM));
+        assert_eq!(group(Method::GET, "/metrics"), Some(SYSTEM));
+        assert_eq!(group(Method::GET, "/api/search"), None);
+        assert_eq!(group(Method::GET, "/api/history"), None);
+        assert_eq!(group(Method::GET, "/api/transfers/abc"), None);
+        assert_eq!(group(Method::GET, "/torznab/api"), None);
+    }
+
+    #[test]
+    fn test_history_deletes() {
+        let group = |method: Method, uri: &str| group(&method, &uri.parse().unwrap());
+        assert_eq!(group(Method::DELETE, "/api/history/7"), Some(FILES));
+        assert_eq!(
+            group(Method::DELETE, "/api/history/7?delete_file=true"),
+            Some(FILES)
+        );
+        assert_eq!(group(Method::GET, "/api/history/7"), None);
+        assert_eq!(group(Method::POST, "/api/history/bulk"), Some(FILES));
+        assert_eq!(
+            group(
+                Method::GET,
+                "/sabnzbd/api?mode=history&name=delete&value=a,b&del_files=1"
+            ),
+            Some(FILES)
+        );
+        assert_eq!(
+            group(
+                Method::POST,
+                "/sabnzbd/api?mode=history&name=delete&value=a"
+            ),
+            None
+        );
+        assert_eq!(group(Method::GET, "/sabnzbd/api?mode=history"), None);
+    }
+}
@@ -1,28 +1,52 @@
+mod alerts;
+mod anime;
 mod api;
+mod archive;
+mod arr;
+mod assets;
+mod bundle;
 mod config;
+mod config_writer;
+mod confirm;
 mod db;
+mod events;
+mod export;
+mod filename;
+mod heartbeat;
 mod irc_client;
+mod library;
+mod lockdown;
+mod metadata;
+mod metrics;
+mod mirror;
+mod net;
+mod network_import;
+mod notifications;
+mod package;
 mod plugin;
 mod postprocess;
+mod quarantine;
+mod range;
+mod release;
+mod request_id;
+mod sabnzbd;
+mod search_cache;
+mod selftest;
+mod simulate;
+mod thumbnail;
+mod torznab;
+mod watchlist;
 mod xdcc;
 
 use crate::config::AppConfig;
+use crate::notifications::Notification;
 use crate::xdcc::{SearchAggregator, TransferManager};
-use axum::{
-    http::{header, StatusCode, Uri},
-    response::IntoResponse,
-    Router,
-};
-use rust_embed::RustEmbed;
+use axum::Router;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-#[derive(RustEmbed)]
-#[folder = "web/dist"]
-struct Asset;
-
 #[derive(Clone)]
 pub struct AppState {
     pub search_aggregator: Arc<SearchAggregator>,
@@ -30,9 +54,18 @@ pub struct AppState {
     pub download_dir: String,
     pub database: Arc<db::Database>,
     pub config: Arc<RwLock<AppConfig>>,
+    /// Settings version and the task persisting settings changes
+    pub config_writer: config_writer::ConfigWriter,
     pub plugin_manager: Arc<plugin::PluginManager>,
     pub irc_monitor: Arc<xdcc::monitor::IrcMonitor>,
     pub irc_client_manager: Arc<irc_client::InteractiveClientManager>,
+    pub arr_library: Arc<arr::ArrLibrary>,
+    pub metadata: Arc<metadata::MetadataResolver>,
+    pub events: tokio::sync::broadcast::Sender<events::AppEvent>,
+    /// Tokens confirming destructive bulk operations
+    pub confirmations: confirm::Confirmations,
+    /// Results of recent searches, for their later pages
+    pub search_cache: search_cache::SearchCache,
 }
 
 #[tokio::main]
@@ -53,12 +86,6 @@ async fn main() -> anyhow::Result<()> {
         std::env::var("BOTARR_DOWNLOAD_DIR").unwrap_or_else(|_| "downloads".to_string());
     tokio::fs::create_dir_all(&download_dir).await?;
 
-    // Initialize database
-    let db_path = std::env::var("BOTARR_DB_PATH").unwrap_or_else(|_| "botarr.db".to_string());
-    let database = db::Database::new(&db_path)
-        .map_err(|e| anyhow::anyhow!("Failed to initialize database: {}", e))?;
-    tracing::info!("Database initialized at: {}", db_path);
-
     // Load application config
     let config_path =
         std::env::var("BOTARR_CONFIG_FILE").unwrap_or_else(|_| "config.json".to_string());
@@ -68,11 +95,52 @@ async fn main() -> anyhow::Result<()> {
         "Config loaded with {} networks configured",
         app_config.networks.len()
     );
+    let mirror = app_config.mirror_mode;
+
+    // Initialize database (a mirror only reads the primary's)
+    let db_path = std::env::var("BOTARR_DB_PATH").unwrap_or_else(|_| "botarr.db".to_string());
+    let database = if mirror {
+        db::Database::open_read_only(&db_path)
+    } else {
+        db::Database::new(&db_path)
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to initialize database: {}", e))?;
+    if mirror {
+        tracing::info!(
+            "Running as a read-only mirror of the database at: {}",
+            db_path
+        );
+    } else {
+        tracing::info!("Database initialized at: {}", db_path);
+    }
+
+    // `botarr selftest [network] [--speedtest]` checks the setup, prints a
+    // report and exits
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("selftest") {
+        let search = SearchAggregator::from_config(&app_config);
+        let speedtest = args.iter().any(|a| a == "--speedtest");
+        let network = args.iter().skip(2).find(|a| !a.starts_with("--"));
+        let report = selftest::run(
+            &app_config,
+            &database,
+            &search,
+            network.map(String::as_str),
+            speedtest,
+        )
+        .await;
+        print!("{}", report.to_text());
+        std::process::exit(if report.passed { 0 } else { 1 });
+    }
 
     let database = Arc::new(database);
     let mut tm = TransferManager::new(download_dir.clone());
     tm.set_database(database.clone());
-    let _restored_transfers = tm.restore_incomplete_transfers().await;
+    tm.bandwidth()
+        .set_rate(app_config.max_download_speed * 1024);
+    if !mirror {
+        let _restored_transfers = tm.restore_incomplete_transfers().await;
+    }
     tm.restore_recent_finished_transfers(20).await;
 
     // Initialize Plugin Manager
@@ -87,58 +155,109 @@ async fn main() -> anyhow::Result<()> {
 
     let plugins_dir = std::path::Path::new("plugins");
     std::fs::create_dir_all(plugins_dir).unwrap_or_default();
-    plugin_manager.load_scripts(plugins_dir);
+    // Plugins queue downloads, which is the primary's job
+    if !mirror {
+        plugin_manager.load_scripts(plugins_dir);
+    }
 
+    let shared_config = Arc::new(RwLock::new(app_config.clone()));
     let irc_monitor = Arc::new(xdcc::monitor::IrcMonitor::new(
-        Arc::new(RwLock::new(app_config.clone())),
+        shared_config.clone(),
         plugin_manager.clone(),
     ));
 
-    let irc_client_manager = Arc::new(irc_client::InteractiveClientManager::new());
+    let irc_client_manager = Arc::new(irc_client::InteractiveClientManager::new(
+        shared_config.clone(),
+    ));
+
+    // Downloads and searches go to a mock IRC server instead of real networks
+    let simulation = if simulate::requested() && !mirror {
+        let server = simulate::MockServer::start(Default::default()).await?;
+        simulate::install(server.addr());
+        tracing::warn!(
+            "Simulation mode: all networks resolve to the mock IRC server at {}",
+            server.addr()
+        );
+        Some(server)
+    } else {
+        None
+    };
+
+    let mut search_aggregator = match &simulation {
+        Some(server) => {
+            let mut search = SearchAggregator::new();
+            search.add_provider(Box::new(xdcc::providers::SimulatedProvider::new(
+                server.packs(),
+            )));
+            search
+        }
+        None => SearchAggregator::from_config(&app_config),
+    };
+    search_aggregator.add_provider(Box::new(xdcc::providers::LocalPackProvider::new(
+        database.clone(),
+    )));
 
     let state = AppState {
-        search_aggregator: Arc::new(SearchAggregator::with_default_providers(None)),
+        search_aggregator: Arc::new(search_aggregator),
         transfer_manager: Arc::new(RwLock::new(tm)),
         download_dir: download_dir.clone(),
         database: database.clone(),
-        config: Arc::new(RwLock::new(app_config)),
+        config_writer: config_writer::ConfigWriter::spawn(
+            shared_config.clone(),
+            config_path.clone(),
+        ),
+        config: shared_config,
         plugin_manager: plugin_manager.clone(),
         irc_monitor: irc_monitor.clone(),
         irc_client_manager: irc_client_manager.clone(),
+        arr_library: Arc::new(arr::ArrLibrary::new()),
+        metadata: Arc::new(metadata::MetadataResolver::new()),
+        events: events::channel(),
+        confirmations: confirm::Confirmations::default(),
+        search_cache: search_cache::SearchCache::default(),
     };
 
     let monitor_clone = irc_monitor.clone();
     let tm_clone = state.transfer_manager.clone();
+    let config_clone = state.config.clone();
+    let arr_clone = state.arr_library.clone();
     tokio::spawn(async move {
         while let Some(action) = plugin_rx.recv().await {
+            let start_paused = matches!(action, plugin::PluginAction::Queue(..));
             match action {
                 plugin::PluginAction::MonitorChannel(plugin_name, network, channel) => {
                     monitor_clone.start_monitoring(plugin_name, network, channel);
                 }
-                plugin::PluginAction::Download(url, filename) => {
-                    let lock = tm_clone.read().await;
-                    if let Ok(xdcc_url) = crate::xdcc::XdccUrl::parse(&url) {
-                        let _ = lock
-                            .create_transfer(
-                                xdcc_url,
-                                crate::xdcc::transfer::TransferPriority::Normal,
-                                false,
-                                filename,
-                            )
-                            .await;
+                plugin::PluginAction::Download(url, filename, opts)
+                | plugin::PluginAction::Queue(url, filename, opts) => {
+                    if arr::already_in_library(&config_clone, &arr_clone, filename.as_deref()).await
+                    {
+                        continue;
                     }
-                }
-                plugin::PluginAction::Queue(url, filename) => {
+                    let opts = opts.resolve(&*config_clone.read().await, filename.as_deref());
+                    let Ok(xdcc_url) = crate::xdcc::XdccUrl::parse(&url) else {
+                        continue;
+                    };
                     let lock = tm_clone.read().await;
-                    if let Ok(xdcc_url) = crate::xdcc::XdccUrl::parse(&url) {
-                        let _ = lock
-                            .create_transfer(
-                                xdcc_url,
-                                crate::xdcc::transfer::TransferPriority::Normal,
-                                true,
-                                filename,
-                            )
-                            .await;
+                    if let Ok((id, _)) = lock
+                        .create_transfer(
+                            xdcc_url,
+                            opts.priority.unwrap_or_default(),
+                            start_paused,
+                            filename,
+                        )
+                        .await
+                    {
+                        lock.set_category(&id, opts.category).await;
+                        if let Some(max_retries) = opts.max_retries {
+                            lock.set_max_retries(&id, max_retries).await;
+                        }
+                        if let Some(name) = opts.template {
+                            let config = config_clone.read().await;
+                            if let Some((_, template)) = config.template(&name) {
+                                lock.apply_template(&id, &name, template).await;
+                            }
+                        }
                     }
                 }
             }
@@ -148,20 +267,71 @@ async fn main() -> anyhow::Result<()> {
     // Build router
     let app = Router::new()
         .merge(api::routes())
-        .fallback(static_handler)
+        .fallback(assets::static_handler)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            mirror::guard,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            lockdown::guard,
+        ))
+        .layer(axum::middleware::from_fn(request_id::tag))
         .with_state(state.clone()); // state must be cloned here because we need it below
 
+    if mirror {
+        // Nothing is downloaded or written here; just serve the dashboards
+        let addr = SocketAddr::from(([0, 0, 0, 0], 3001));
+        tracing::info!("Listening on {}", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+        return Ok(());
+    }
+
     // Start Queue Processor
     let queue_state = state.clone();
     tokio::spawn(async move {
         tracing::info!("Queue processor started");
+        let started = std::time::Instant::now();
+        let mut over_quota = false;
+        // When the queue last went from empty to busy
+        let mut busy_since = None;
         loop {
             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
 
-            let active_count = {
+            // Hold queued transfers once the monthly traffic quota is used up
+            let quota = queue_state.config.read().await.monthly_quota_bytes();
+            let exceeded = quota.is_some_and(|q| {
+                queue_state
+                    .database
+                    .month_traffic()
+                    .is_ok_and(|used| used as u64 >= q)
+            });
+            if exceeded != over_quota {
+                over_quota = exceeded;
+                if exceeded {
+                    tracing::warn!("Monthly traffic quota reached, pausing the queue");
+                    notifications::dispatch(
+                        &*queue_state.config.read().await,
+                        Notification::new(
+                            notifications::QUEUE_PAUSED,
+                            "Botarr: the monthly traffic quota is used up, queued downloads are on hold"
+                                .to_string(),
+                            serde_json::json!({}),
+                        ),
+                    );
+                } else {
+                    tracing::info!("Monthly traffic quota available again, resuming the queue");
+                }
+            }
+            if exceeded {
+                continue;
+            }
+
+            let (active_count, finished) = {
                 let tm = queue_state.transfer_manager.read().await;
                 let transfers = tm.list_transfers().await;
-                transfers
+                let active_count = transfers
                     .iter()
                     .filter(|t| {
                         let status = format!("{:?}", t.transfer.status).to_lowercase();
@@ -170,18 +340,53 @@ async fn main() -> anyhow::Result<()> {
                             "connecting" | "joining" | "requesting" | "downloading"
                         )
                     })
-                    .count()
+                    .count();
+                let waiting = transfers
+                    .iter()
+                    .any(|t| t.transfer.status == xdcc::TransferStatus::Pending);
+                let finished = if active_count > 0 || waiting {
+                    busy_since.get_or_insert_with(chrono::Utc::now);
+                    None
+                } else {
+                    busy_since.take().map(|since| {
+                        Notification::queue_finished(transfers.iter().map(|t| &t.transfer), since)
+                    })
+                };
+                (active_count, finished)
             };
+            if let Some(notification) = finished {
+                tracing::info!("{}", notification.message);
+                notifications::dispatch(&*queue_state.config.read().await, notification);
+            }
 
-            let limit = {
+            let (limit, etiquette, fastest) = {
                 let cfg = queue_state.config.read().await;
-                cfg.queue_limit as usize
+                let etiquette = xdcc::transfer::RequestEtiquette::from_config(&cfg);
+                (
+                    etiquette.concurrency(cfg.queue_limit as usize, started.elapsed()),
+                    etiquette,
+                    cfg.source_policy == "fastest",
+                )
             };
 
             if active_count < limit {
+                if fastest {
+                    // Looked up without holding the manager
+                    let lookup = queue_state
+                        .transfer_manager
+                        .read()
+                        .await
+                        .find_faster_sources()
+                        .await;
+                    let moves = lookup.await;
+                    if !moves.is_empty() {
+                        let tm = queue_state.transfer_manager.read().await;
+                        tm.move_to_faster_sources(moves).await;
+                    }
+                }
                 let pop_result = {
                     let tm = queue_state.transfer_manager.write().await;
-                    tm.pop_queue().await
+                    tm.pop_queue(&etiquette).await
                 };
 
                 if let Some((id, url, token)) = pop_result {
@@ -200,40 +405,178 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Move transfers stuck behind a vanished bot to another source
+    let research_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            let mins = research_state.config.read().await.pending_research_mins;
+            if mins == 0 {
+                continue;
+            }
+            let stale = {
+                let tm = research_state.transfer_manager.read().await;
+                tm.take_stale_pending(chrono::Duration::minutes(mins as i64))
+                    .await
+            };
+            for (id, url, filename) in stale {
+                let found = match research_state
+                    .search_aggregator
+                    .find_source_everywhere(&url, &filename)
+                    .await
+                {
+                    Ok(Some(found)) => found,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        tracing::warn!("Re-search for {} failed: {}", filename, e);
+                        continue;
+                    }
+                };
+                // Still listed by the bot it is queued for
+                if found.bot.eq_ignore_ascii_case(&url.bot)
+                    && found.network.eq_ignore_ascii_case(&url.network)
+                {
+                    continue;
+                }
+                let tm = research_state.transfer_manager.read().await;
+                let reason = format!("{} no longer offers this file", url.bot);
+                if tm.retarget(&id, found.clone(), &reason).await {
+                    tracing::info!(
+                        "{} vanished, moved transfer {} of {} to {}",
+                        url.bot,
+                        id,
+                        filename,
+                        found
+                    );
+                }
+            }
+        }
+    });
+
+    heartbeat::spawn_heartbeat(state.clone());
+    watchlist::spawn_scheduler(state.clone());
+    irc_client_manager.clone().spawn_idle_reaper();
+
+    // Prune stored search results and trashed history past their retention
+    // period and stale partial files, compress old transfer events and
+    // compact the database weekly
+    let prune_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            let (days, trash_days, events_days, maintenance) = {
+                let config = prune_state.config.read().await;
+                (
+                    config.search_results_retention_days,
+                    config.trash_retention_days,
+                    config.event_compress_after_days,
+                    config.db_maintenance_weekly,
+                )
+            };
+            match prune_state.database.purge_trash(Some(trash_days)) {
+                Ok(paths) => api::handlers::history::remove_trashed_files(&paths).await,
+                Err(e) => tracing::warn!("Failed to empty the trash: {}", e),
+            }
+            let removed = api::handlers::history::prune_partials(&prune_state.database).await;
+            if removed > 0 {
+                tracing::info!("Forgot {} partial files that are gone or complete", removed);
+            }
+            // Thumbnails go with their history items
+            if let Ok(ids) = prune_state.database.download_ids() {
+                let dir = std::path::PathBuf::from(&prune_state.config.read().await.thumbnail_dir);
+                let removed =
+                    tokio::task::spawn_blocking(move || thumbnail::remove_orphans(&dir, &ids))
+                        .await
+                        .unwrap_or(0);
+                if removed > 0 {
+                    tracing::info!("Removed {} thumbnails of deleted downloads", removed);
+                }
+            }
+            if days > 0 {
+                match prune_state.database.prune_search_results(days) {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Pruned stored results from {} old searches", n),
+                    Err(e) => tracing::warn!("Failed to prune search results: {}", e),
+                }
+            }
+            if events_days > 0 {
+                let database = prune_state.database.clone();
+                let compressed = tokio::task::spawn_blocking(move || {
+                    database.compress_transfer_events(events_days)
+                })
+                .await
+                .unwrap_or(Ok(0));
+                match compressed {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("Compressed the events of {} old transfers", n),
+                    Err(e) => tracing::warn!("Failed to compress transfer events: {}", e),
+                }
+            }
+            if maintenance
+                && prune_state
+                    .database
+                    .maintenance_due(chrono::Duration::weeks(1))
+            {
+                let database = prune_state.database.clone();
+                match tokio::task::spawn_blocking(move || database.run_maintenance()).await {
+                    Ok(Ok(report)) => tracing::info!(
+                        "Weekly database maintenance: {} -> {} bytes",
+                        report.size_before,
+                        report.size_after
+                    ),
+                    Ok(Err(e)) => tracing::warn!("Database maintenance failed: {}", e),
+                    Err(e) => tracing::warn!("Database maintenance failed: {}", e),
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+        }
+    });
+
     // Start server
     let addr = SocketAddr::from(([0, 0, 0, 0], 3001)); // Default port 3001 for Botarr
     tracing::info!("Listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    let config_writer = state.config_writer.clone();
+    let transfer_manager = state.transfer_manager.clone();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            // Open event sockets can hold the server up; save settings first
+            config_writer.flush().await;
+            let traffic = transfer_manager.read().await.flush_traffic();
+            traffic.await;
+        })
+        .await?;
+    state.config_writer.flush().await;
 
     Ok(())
 }
 
-async fn static_handler(uri: Uri) -> impl IntoResponse {
-    let path = uri.path().trim_start_matches('/').to_string();
-    let path = if path.is_empty() {
-        "index.html".to_string()
-    } else {
-        path
-    };
-
-    match Asset::get(&path) {
-        Some(content) => {
-            let mime = mime_guess::from_path(&path).first_or_octet_stream();
-            ([(header::CONTENT_TYPE, mime.as_ref())], content.data).into_response()
+/// Wait for Ctrl-C or `SIGTERM`
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Cannot listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
         }
-        None => {
-            if path.contains('.') {
-                return StatusCode::NOT_FOUND.into_response();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
             }
-            // Fallback to index.html for SPA routing
-            match Asset::get("index.html") {
-                Some(content) => {
-                    let mime = mime_guess::from_path("index.html").first_or_octet_stream();
-                    ([(header::CONTENT_TYPE, mime.as_ref())], content.data).into_response()
-                }
-                None => StatusCode::NOT_FOUND.into_response(),
+            Err(e) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
             }
         }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+    tracing::info!("Shutting down");
 }
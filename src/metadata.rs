@@ -0,0 +1,184 @@
+//! Metadata Module
+//!
+//! Resolves parsed release names to TMDB entries (with TVDB ids for series) so
+//! search results and history can show titles, years and posters.
+
+use crate::library::normalize_title;
+use crate::release::ParsedRelease;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const TMDB_API: &str = "https://api.themoviedb.org/3";
+const TMDB_POSTER_BASE: &str = "https://image.tmdb.org/t/p/w342";
+/// How long lookups (including misses) are cached
+const CACHE_TTL: Duration = Duration::from_secs(24 * 3600);
+/// How long a failed lookup is remembered before TMDB is asked again
+const FAILURE_TTL: Duration = Duration::from_secs(300);
+
+/// Metadata attached to a release
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaMetadata {
+    /// "tv" or "movie"
+    pub media_type: String,
+    pub tmdb_id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvdb_id: Option<u64>,
+    pub title: String,
+    pub year: Option<u32>,
+    pub poster_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbSearch {
+    #[serde(default)]
+    results: Vec<TmdbResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbResult {
+    id: u64,
+    /// Movie title
+    title: Option<String>,
+    /// Series title
+    name: Option<String>,
+    release_date: Option<String>,
+    first_air_date: Option<String>,
+    poster_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbExternalIds {
+    tvdb_id: Option<u64>,
+}
+
+/// Cached TMDB lookups
+pub struct MetadataResolver {
+    client: reqwest::Client,
+    /// Lookup results with the time they expire
+    cache: RwLock<HashMap<String, (Option<MediaMetadata>, Instant)>>,
+}
+
+impl MetadataResolver {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Resolve a parsed release, using the cache when possible
+    pub async fn resolve(&self, api_key: &str, release: &ParsedRelease) -> Option<MediaMetadata> {
+        if api_key.is_empty() || release.title.is_empty() {
+            return None;
+        }
+
+        let is_tv = release.episode.is_some() || release.absolute_episode.is_some();
+        let key = format!(
+            "{}|{}|{}",
+            if is_tv { "tv" } else { "movie" },
+            normalize_title(&release.title),
+            release.year.unwrap_or(0)
+        );
+
+        if let Some((cached, expires)) = self.cache.read().await.get(&key) {
+            if Instant::now() < *expires {
+                return cached.clone();
+            }
+        }
+
+        let (result, ttl) = match self.lookup(api_key, release, is_tv).await {
+            Ok(m) => (m, CACHE_TTL),
+            Err(e) => {
+                // Failures are cached briefly so an outage isn't retried per result
+                tracing::warn!("TMDB lookup for '{}' failed: {}", release.title, e);
+                (None, FAILURE_TTL)
+            }
+        };
+        let mut cache = self.cache.write().await;
+        let now = Instant::now();
+        cache.retain(|_, (_, expires)| now < *expires);
+        cache.insert(key, (result.clone(), now + ttl));
+        result
+    }
+
+    async fn lookup(
+        &self,
+        api_key: &str,
+        release: &ParsedRelease,
+        is_tv: bool,
+    ) -> Result<Option<MediaMetadata>, reqwest::Error> {
+        let (kind, year_param) = if is_tv {
+            ("tv", "first_air_date_year")
+        } else {
+            ("movie", "year")
+        };
+
+        let mut url = format!(
+            "{}/search/{}?api_key={}&query={}",
+            TMDB_API,
+            kind,
+            urlencoding::encode(api_key),
+            urlencoding::encode(&release.title)
+        );
+        if let Some(year) = release.year {
+            url.push_str(&format!("&{}={}", year_param, year));
+        }
+
+        let search: TmdbSearch = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let Some(first) = search.results.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let tvdb_id = if is_tv {
+            let url = format!(
+                "{}/tv/{}/external_ids?api_key={}",
+                TMDB_API,
+                first.id,
+                urlencoding::encode(api_key)
+            );
+            match self.client.get(&url).send().await {
+                Ok(resp) => resp
+                    .json::<TmdbExternalIds>()
+                    .await
+                    .ok()
+                    .and_then(|e| e.tvdb_id),
+                Err(_) => None,
+            }
+        } else {
+            None
+        };
+
+        let date = first.release_date.or(first.first_air_date);
+        Ok(Some(MediaMetadata {
+            media_type: kind.to_string(),
+            tmdb_id: first.id,
+            tvdb_id,
+            title: first
+                .title
+                .or(first.name)
+                .unwrap_or_else(|| release.title.clone()),
+            year: date.and_then(|d| d.get(..4).and_then(|y| y.parse().ok())),
+            poster_url: first
+                .poster_path
+                .map(|p| format!("{}{}", TMDB_POSTER_BASE, p)),
+        }))
+    }
+}
+
+impl Default for MetadataResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
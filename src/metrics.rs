@@ -0,0 +1,98 @@
+//! Prometheus Metrics Module
+//!
+//! Renders per-bot reliability statistics in the Prometheus text exposition
+//! format, so bot quality can be graphed and alerted on over time.
+
+use crate::xdcc::transfer::BotStats;
+use std::fmt::Write;
+
+/// Escape a label value (backslash, double quote and newline)
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render bot statistics as Prometheus metrics
+pub fn render_bot_metrics(stats: &[BotStats]) -> String {
+    let mut out = String::new();
+    let labels: Vec<String> = stats
+        .iter()
+        .map(|s| {
+            format!(
+                "bot=\"{}\",network=\"{}\"",
+                escape_label(&s.bot_name),
+                escape_label(&s.network)
+            )
+        })
+        .collect();
+
+    let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&BotStats) -> String| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (s, labels) in stats.iter().zip(&labels) {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value(s));
+        }
+    };
+
+    family(
+        "botarr_bot_reliability_score",
+        "gauge",
+        "Bot reliability score from 0 to 1",
+        &|s| s.reliability_score.to_string(),
+    );
+    family(
+        "botarr_bot_downloads_success_total",
+        "counter",
+        "Downloads from the bot that completed",
+        &|s| s.successful_downloads.to_string(),
+    );
+    family(
+        "botarr_bot_downloads_failed_total",
+        "counter",
+        "Downloads from the bot that failed",
+        &|s| s.failed_downloads.to_string(),
+    );
+    family(
+        "botarr_bot_received_bytes_total",
+        "counter",
+        "Bytes received from the bot in completed downloads",
+        &|s| s.total_bytes.to_string(),
+    );
+    family(
+        "botarr_bot_average_speed_bytes_per_second",
+        "gauge",
+        "Average download speed from the bot",
+        &|s| s.average_speed.to_string(),
+    );
+    family(
+        "botarr_bot_last_seen_timestamp_seconds",
+        "gauge",
+        "Unix time of the last download attempt from the bot",
+        &|s| s.last_seen.timestamp().to_string(),
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bot_metrics() {
+        let mut stats = BotStats::new("Bot\"1".to_string(), "irc.rizon.net".to_string());
+        stats.successful_downloads = 3;
+        stats.failed_downloads = 1;
+
+        let text = render_bot_metrics(&[stats]);
+        assert!(text.contains("# TYPE botarr_bot_reliability_score gauge"));
+        assert!(text.contains(
+            "botarr_bot_downloads_success_total{bot=\"Bot\\\"1\",network=\"irc.rizon.net\"} 3"
+        ));
+        assert!(text.contains(
+            "botarr_bot_downloads_failed_total{bot=\"Bot\\\"1\",network=\"irc.rizon.net\"} 1"
+        ));
+    }
+}
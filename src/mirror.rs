@@ -0,0 +1,164 @@
+//! Mirror Mode Module
+//!
+//! A mirror is a second Botarr instance that opens the primary's database
+//! read-only to serve dashboards from another location. It never downloads
+//! itself: requests that would change state are refused, and download
+//! requests are forwarded to the primary's API when `primary_url` is set.
+
+use crate::api::models::ErrorResponse;
+use crate::AppState;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use std::time::Duration;
+
+/// Largest download request body forwarded to the primary
+const FORWARD_MAX_BYTES: usize = 64 * 1024;
+
+/// What a mirror does with a request
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    /// Serve locally (reads only)
+    Local,
+    /// Send to the primary
+    Forward,
+    /// Refuse, the mirror can't change state
+    Reject,
+}
+
+fn route(method: &Method, path: &str) -> Route {
+    match (method, path) {
+        (&Method::POST, "/api/download")
+        | (&Method::GET, "/add")
+        | (&Method::GET, "/torznab/grab") => Route::Forward,
+        (&Method::POST, path)
+            if path.starts_with("/api/history/") && path.ends_with("/redownload") =>
+        {
+            Route::Forward
+        }
+        // The self-test only reads
+        (&Method::POST, "/api/system/selftest") => Route::Local,
+        (&Method::GET | &Method::HEAD | &Method::OPTIONS, _) => Route::Local,
+        _ => Route::Reject,
+    }
+}
+
+/// Middleware enforcing mirror mode; a no-op on the primary
+pub async fn guard(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let (mirror, primary_url, primary_api_key) = {
+        let config = state.config.read().await;
+        (
+            config.mirror_mode,
+            config.primary_url.trim_end_matches('/').to_string(),
+            config.primary_api_key.clone(),
+        )
+    };
+    if !mirror {
+        return next.run(req).await;
+    }
+
+    match route(req.method(), req.uri().path()) {
+        Route::Local => next.run(req).await,
+        Route::Forward if !primary_url.is_empty() => {
+            forward(req, &primary_url, &primary_api_key).await
+        }
+        Route::Forward | Route::Reject => (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "This Botarr instance is a read-only mirror".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Pass a download request on to the primary and relay its answer
+async fn forward(req: Request, primary_url: &str, api_key: &str) -> Response {
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    // Quick-add and Torznab grab links are GETs carrying their own apikey
+    let link = req.method() == Method::GET;
+    let body = match axum::body::to_bytes(req.into_body(), FORWARD_MAX_BYTES).await {
+        Ok(body) => body,
+        Err(e) => return bad_gateway(format!("Failed to read request: {}", e)),
+    };
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return bad_gateway(e.to_string()),
+    };
+    let url = format!("{}{}", primary_url, path_and_query);
+    let request = if link {
+        client.get(&url)
+    } else {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        client
+            .post(format!(
+                "{}{}apikey={}",
+                url,
+                separator,
+                urlencoding::encode(api_key)
+            ))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+    };
+
+    let resp = match request.send().await {
+        Ok(resp) => resp,
+        Err(e) => return bad_gateway(format!("Primary at {} unreachable: {}", primary_url, e)),
+    };
+    let status = resp.status();
+    tracing::info!("Forwarded {} to primary: {}", path_and_query, status);
+
+    // Quick-add answers with a redirect into the primary's UI; stay on the mirror
+    if link && status.is_redirection() {
+        return Redirect::to("/").into_response();
+    }
+    let content_type = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("application/json")
+        .to_string();
+    let body: Bytes = resp.bytes().await.unwrap_or_default();
+    Response::builder()
+        .status(status.as_u16())
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+fn bad_gateway(error: String) -> Response {
+    (StatusCode::BAD_GATEWAY, Json(ErrorResponse { error })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route(&Method::GET, "/api/history"), Route::Local);
+        assert_eq!(route(&Method::POST, "/api/download"), Route::Forward);
+        assert_eq!(route(&Method::GET, "/add"), Route::Forward);
+        assert_eq!(route(&Method::GET, "/torznab/grab"), Route::Forward);
+        assert_eq!(
+            route(&Method::POST, "/api/history/abc/redownload"),
+            Route::Forward
+        );
+        assert_eq!(route(&Method::PUT, "/api/settings"), Route::Reject);
+        assert_eq!(route(&Method::DELETE, "/api/history/abc"), Route::Reject);
+    }
+}
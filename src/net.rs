@@ -0,0 +1,168 @@
+//! Outbound Connections
+//!
+//! Opens IRC and DCC sockets, optionally bound to a local address or network
+//! interface (e.g. a VPN tunnel) and tunnelled through the SOCKS5 proxy. If the
+//! configured interface is down, or the proxy is required but unreachable, the
+//! connection fails instead of silently going out through the default route.
+
+use crate::config::AppConfig;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::{lookup_host, TcpSocket, TcpStream};
+
+/// Resolve a bind setting (IP address or interface name) to a local address.
+/// Empty means no binding.
+pub fn resolve_bind_address(bind: &str) -> io::Result<Option<IpAddr>> {
+    let bind = bind.trim();
+    if bind.is_empty() {
+        return Ok(None);
+    }
+
+    let interfaces = if_addrs::get_if_addrs()?;
+    if let Ok(ip) = bind.parse::<IpAddr>() {
+        if !ip.is_unspecified() && !interfaces.iter().any(|i| i.ip() == ip) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("Bind address {} is not assigned to any interface", ip),
+            ));
+        }
+        return Ok(Some(ip));
+    }
+
+    // Interface name: prefer its IPv4 address
+    let mut addrs: Vec<IpAddr> = interfaces
+        .iter()
+        .filter(|i| i.name == bind)
+        .map(|i| i.ip())
+        .collect();
+    addrs.sort_by_key(|ip| !ip.is_ipv4());
+    addrs.into_iter().next().map(Some).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("Interface {} is down or has no address", bind),
+        )
+    })
+}
+
+/// How outgoing IRC and DCC connections are made
+#[derive(Debug, Clone, Default)]
+pub struct Outbound {
+    /// Local address or interface name, empty for the default route
+    pub bind: String,
+    /// SOCKS5 proxy ("host:port" or "socks5://host:port"), empty if disabled
+    pub proxy_url: String,
+    /// Never connect directly: tunnel every connection through the proxy
+    pub require_proxy: bool,
+}
+
+impl Outbound {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            bind: config.outbound_bind.clone(),
+            proxy_url: if config.proxy_enabled {
+                config.proxy_url.clone()
+            } else {
+                String::new()
+            },
+            require_proxy: config.require_proxy,
+        }
+    }
+
+    /// Connect to `addr` ("host:port"). The proxy is used when `via_proxy` is
+    /// set and one is configured, and always when it is required.
+    pub async fn connect(&self, addr: &str, via_proxy: bool) -> io::Result<TcpStream> {
+        if !self.require_proxy && (!via_proxy || self.proxy_url.is_empty()) {
+            return connect_tcp(addr, &self.bind).await;
+        }
+
+        let refuse = |msg: String| {
+            if self.require_proxy {
+                io::Error::other(format!("{}; refusing to connect directly", msg))
+            } else {
+                io::Error::other(msg)
+            }
+        };
+        if self.proxy_url.is_empty() {
+            return Err(refuse("Proxy is required but not enabled".to_string()));
+        }
+
+        let proxy_addr = self.proxy_url.trim_start_matches("socks5://");
+        tracing::info!("Connecting via SOCKS5 proxy: {} -> {}", proxy_addr, addr);
+        let stream = connect_tcp(proxy_addr, &self.bind)
+            .await
+            .map_err(|e| refuse(format!("Proxy {} is unreachable: {}", proxy_addr, e)))?;
+        tokio_socks::tcp::Socks5Stream::connect_with_socket(stream, addr)
+            .await
+            .map(|s| s.into_inner()) // Unwrap to get the raw tunnelled TcpStream
+            .map_err(|e| refuse(format!("Proxy connection to {} failed: {}", addr, e)))
+    }
+}
+
+/// Connect to `addr` ("host:port"), binding the local end as configured
+pub async fn connect_tcp(addr: &str, bind: &str) -> io::Result<TcpStream> {
+    let Some(local) = resolve_bind_address(bind)? else {
+        return TcpStream::connect(addr).await;
+    };
+
+    let mut last_err = None;
+    for remote in lookup_host(addr).await? {
+        if remote.is_ipv4() != local.is_ipv4() {
+            continue;
+        }
+        let socket = if remote.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.bind(SocketAddr::new(local, 0))?;
+        match socket.connect(remote).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("No address of {} is reachable from {}", addr, local),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_bind_address() {
+        assert_eq!(resolve_bind_address("").unwrap(), None);
+        assert_eq!(
+            resolve_bind_address("127.0.0.1").unwrap(),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert!(resolve_bind_address("203.0.113.77").is_err());
+        assert!(resolve_bind_address("no-such-interface0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_required_proxy_never_connects_directly() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let mut outbound = Outbound {
+            require_proxy: true,
+            ..Default::default()
+        };
+        let err = outbound.connect(&addr, false).await.unwrap_err();
+        assert!(err.to_string().contains("refusing to connect directly"));
+
+        // Point the proxy at a closed port
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        outbound.proxy_url = format!("socks5://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = outbound.connect(&addr, false).await.unwrap_err();
+        assert!(err.to_string().contains("unreachable"));
+
+        outbound.require_proxy = false;
+        assert!(outbound.connect(&addr, false).await.is_ok());
+    }
+}
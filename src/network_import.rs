@@ -0,0 +1,287 @@
+//! Network Import
+//!
+//! Adds or updates many networks in one go, from a JSON array of network
+//! settings or from an mIRC `servers.ini`. Every entry is checked on its own,
+//! so one bad entry doesn't hold back the rest.
+
+use crate::config::{keep_secret, NetworkConfig};
+use serde::{Deserialize, Serialize};
+
+/// A network to add or update: its name and settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct NetworkImport {
+    pub name: String,
+    #[serde(flatten)]
+    pub network: NetworkConfig,
+    /// Only the server is known (from a servers.ini), so an existing network
+    /// keeps its other settings
+    #[serde(skip)]
+    pub server_only: bool,
+}
+
+impl NetworkImport {
+    /// Settings to store, given the network's `current` ones if it exists
+    pub fn merge(self, current: Option<&NetworkConfig>) -> NetworkConfig {
+        let mut network = match (self.server_only, current) {
+            (true, Some(current)) => NetworkConfig {
+                host: self.network.host,
+                port: self.network.port,
+                ssl: self.network.ssl,
+                ..current.clone()
+            },
+            _ => self.network,
+        };
+        network.nickserv_password = keep_secret(
+            network.nickserv_password,
+            current.map_or("", |n| n.nickserv_password.as_str()),
+        );
+        network
+    }
+}
+
+/// What happened to one entry of an import
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportResult {
+    /// Position of the entry in the import, from 0
+    pub index: usize,
+    /// Network name as stored, or as given when the entry was refused
+    pub name: Option<String>,
+    /// `created`, `updated` or `error`
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportResult {
+    pub fn error(index: usize, name: Option<String>, error: String) -> Self {
+        Self {
+            index,
+            name,
+            status: "error",
+            error: Some(error),
+        }
+    }
+}
+
+/// Entries of a JSON import, each parsed on its own
+pub fn parse_json(items: Vec<serde_json::Value>) -> Vec<Result<NetworkImport, String>> {
+    items
+        .into_iter()
+        .map(|item| {
+            let name = item
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(str::to_string);
+            serde_json::from_value::<NetworkImport>(item).map_err(|e| match name {
+                Some(name) => format!("{}: {}", name, e),
+                None => e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Networks of an mIRC `servers.ini`, from lines such as
+/// `n0=Rizon: RandomSERVER:irc.rizon.net:6660-6669,+6697GROUP:Rizon`.
+/// A network listing several servers takes its first one, on its first TLS
+/// port (`+`) when it has one.
+pub fn parse_servers_ini(text: &str) -> Vec<Result<NetworkImport, String>> {
+    let mut entries: Vec<Result<NetworkImport, String>> = Vec::new();
+    // Server entries are n0=, n1=... in the [servers] section, or anywhere
+    // in a list pasted without section headers
+    let mut in_servers = true;
+    for line in text.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_servers = section.eq_ignore_ascii_case("servers");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if !in_servers || !key.starts_with('n') || key[1..].parse::<u32>().is_err() {
+            continue;
+        }
+        let entry = parse_server_line(value);
+        if let Ok(parsed) = &entry {
+            let listed = entries
+                .iter()
+                .flatten()
+                .any(|e| e.name.eq_ignore_ascii_case(&parsed.name));
+            if listed {
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+fn parse_server_line(value: &str) -> Result<NetworkImport, String> {
+    let Some((_, server)) = value.split_once("SERVER:") else {
+        return Err(format!("No SERVER: in {}", value));
+    };
+    let (server, group) = match server.split_once("GROUP:") {
+        Some((server, group)) => (server, group.trim()),
+        None => (server, ""),
+    };
+    let (host, ports) = server.split_once(':').unwrap_or((server, ""));
+    let host = host.trim();
+    let name = if group.is_empty() { host } else { group };
+
+    // Ports are a list of single ports and ranges, TLS ones marked with `+`
+    let ports: Vec<&str> = ports
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    let (port, ssl) = match ports.iter().find(|p| p.starts_with('+')).or(ports.first()) {
+        Some(p) => {
+            let ssl = p.starts_with('+');
+            let first = p.trim_start_matches('+').split('-').next().unwrap_or("");
+            let port = first
+                .parse::<u16>()
+                .map_err(|_| format!("{}: invalid port {}", name, p))?;
+            (port, ssl)
+        }
+        None => (6667, false),
+    };
+    Ok(NetworkImport {
+        name: name.to_string(),
+        network: NetworkConfig::new(host.to_string(), port, ssl),
+        server_only: true,
+    })
+}
+
+/// Why a network can't be stored under `name`, if it can't. Used for imports
+/// and single network updates alike.
+pub fn validate(name: &str, network: &NetworkConfig) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Network name must not be empty".to_string());
+    }
+    if name.contains(|c: char| c.is_whitespace() || c == '/') {
+        return Err(format!(
+            "{}: network names can't contain spaces or slashes",
+            name
+        ));
+    }
+    if network.host.is_empty()
+        || network.host.contains("://")
+        || network
+            .host
+            .contains(|c: char| c.is_whitespace() || c == '/')
+    {
+        return Err(format!("{}: invalid host '{}'", name, network.host));
+    }
+    if network.port == 0 {
+        return Err(format!("{}: port must not be 0", name));
+    }
+    if let Some(channel) = network
+        .autojoin_channels
+        .iter()
+        .find(|c| !c.starts_with(['#', '&']) || c.contains(char::is_whitespace))
+    {
+        return Err(format!("{}: invalid channel '{}'", name, channel));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let entries = parse_json(vec![
+            serde_json::json!({"name": "Rizon", "host": "irc.rizon.net"}),
+            serde_json::json!({"name": "Abjects", "host": "irc.abjects.net", "port": "x"}),
+            serde_json::json!({"host": "irc.example.net"}),
+        ]);
+        let rizon = entries[0].as_ref().unwrap();
+        assert_eq!((rizon.network.port, rizon.network.ssl), (6697, true));
+        assert!(entries[1].as_ref().unwrap_err().starts_with("Abjects: "));
+        assert!(entries[2].as_ref().unwrap_err().contains("name"));
+    }
+
+    #[test]
+    fn test_parse_servers_ini() {
+        let ini = "[servers]\r\n\
+            n0=Rizon: Random serverSERVER:irc.rizon.net:6660-6669,+6697GROUP:Rizon\r\n\
+            n1=Rizon: EUSERVER:irc.eu.rizon.net:6667GROUP:Rizon\r\n\
+            n2=EFnet: RandomSERVER:irc.efnet.org:6665-6669GROUP:EFnet\r\n\
+            n3=BrokenSERVER:irc.broken.net:abcGROUP:Broken\r\n\
+            n4=No server here\r\n\
+            [options]\r\n\
+            n5=0,1\r\n";
+        let entries = parse_servers_ini(ini);
+        assert_eq!(entries.len(), 4);
+        let rizon = entries[0].as_ref().unwrap();
+        assert_eq!(rizon.name, "Rizon");
+        assert_eq!(
+            (
+                rizon.network.host.as_str(),
+                rizon.network.port,
+                rizon.network.ssl
+            ),
+            ("irc.rizon.net", 6697, true)
+        );
+        let efnet = entries[1].as_ref().unwrap();
+        assert_eq!((efnet.network.port, efnet.network.ssl), (6665, false));
+        assert!(entries[2].as_ref().unwrap_err().contains("invalid port"));
+        assert!(entries[3].as_ref().unwrap_err().contains("No SERVER:"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let check = |name: &str, host: &str, port: u16, channels: &[&str]| {
+            let network = NetworkConfig {
+                autojoin_channels: channels.iter().map(|c| c.to_string()).collect(),
+                ..NetworkConfig::new(host.to_string(), port, true)
+            };
+            validate(name, &network)
+        };
+        assert!(check("Rizon", "irc.rizon.net", 6697, &["#news"]).is_ok());
+        assert!(check(" ", "irc.rizon.net", 6697, &[]).is_err());
+        assert!(check("My Net", "irc.rizon.net", 6697, &[]).is_err());
+        assert!(check("Rizon", "irc://irc.rizon.net", 6697, &[]).is_err());
+        assert!(check("Rizon", "irc.rizon.net", 0, &[]).is_err());
+        assert!(check("Rizon", "irc.rizon.net", 6697, &["news"]).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let current = NetworkConfig {
+            autojoin_channels: vec!["#news".to_string()],
+            join_delay_secs: 30,
+            nickserv_password: "secret".to_string(),
+            private: true,
+            ..NetworkConfig::new("irc.old.net".to_string(), 6667, false)
+        };
+        let ini = parse_servers_ini("n0=Rizon: RandomSERVER:irc.rizon.net:+6697GROUP:Rizon")
+            .remove(0)
+            .unwrap();
+        let merged = ini.clone().merge(Some(&current));
+        assert_eq!(
+            merged,
+            NetworkConfig {
+                host: "irc.rizon.net".to_string(),
+                port: 6697,
+                ssl: true,
+                ..current.clone()
+            }
+        );
+        assert_eq!(ini.merge(None).autojoin_channels, Vec::<String>::new());
+
+        // A JSON entry replaces the settings, but the password placeholder
+        // keeps the stored one
+        let json = parse_json(vec![serde_json::json!({
+            "name": "Rizon",
+            "host": "irc.rizon.net",
+            "nickserv_password": crate::config::SECRET_PLACEHOLDER,
+        })])
+        .remove(0)
+        .unwrap();
+        let merged = json.merge(Some(&current));
+        assert!(merged.autojoin_channels.is_empty());
+        assert_eq!(merged.nickserv_password, "secret");
+    }
+}
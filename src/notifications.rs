@@ -0,0 +1,593 @@
+//! Notifications
+//!
+//! Tells people about finished and failed downloads and about the queue
+//! through the services listed in `notifications`: Discord, Telegram,
+//! Pushover or any webhook. Every target picks the events it wants. Messages
+//! are sent in the background, so a slow service never holds up a transfer.
+
+use crate::config::{keep_secret, mask_secret, AppConfig};
+use crate::xdcc::{TransferStatus, XdccTransfer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A download finished
+pub const COMPLETED: &str = "completed";
+/// A download failed and won't be retried
+pub const FAILED: &str = "failed";
+/// Nothing is queued or downloading any more
+pub const QUEUE_FINISHED: &str = "queue_finished";
+/// The queue was held because the monthly traffic quota ran out
+pub const QUEUE_PAUSED: &str = "queue_paused";
+/// Sent by `POST /api/notifications/test`, whatever the target's events
+pub const TEST: &str = "test";
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+const PUSHOVER_API: &str = "https://api.pushover.net/1/messages.json";
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Service a notification is sent to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Backend {
+    Discord {
+        webhook_url: String,
+    },
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+    },
+    Pushover {
+        app_token: String,
+        user_key: String,
+    },
+    /// JSON POST in the format of `alert_webhook_url`
+    Webhook {
+        url: String,
+    },
+}
+
+impl Backend {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Backend::Discord { .. } => "discord",
+            Backend::Telegram { .. } => "telegram",
+            Backend::Pushover { .. } => "pushover",
+            Backend::Webhook { .. } => "webhook",
+        }
+    }
+
+    /// URL and JSON body that deliver a notification
+    pub fn request(&self, notification: &Notification) -> (String, serde_json::Value) {
+        match self {
+            Backend::Discord { webhook_url } => (
+                webhook_url.clone(),
+                serde_json::json!({
+                    "username": "Botarr",
+                    "content": notification.message,
+                }),
+            ),
+            Backend::Telegram { bot_token, chat_id } => (
+                format!("{}/bot{}/sendMessage", TELEGRAM_API, bot_token),
+                serde_json::json!({
+                    "chat_id": chat_id,
+                    "text": notification.message,
+                }),
+            ),
+            Backend::Pushover {
+                app_token,
+                user_key,
+            } => (
+                PUSHOVER_API.to_string(),
+                serde_json::json!({
+                    "token": app_token,
+                    "user": user_key,
+                    "title": notification.title(),
+                    "message": notification.message,
+                }),
+            ),
+            Backend::Webhook { url } => (
+                url.clone(),
+                crate::alerts::webhook_payload(
+                    notification.event,
+                    &notification.message,
+                    notification.details.clone(),
+                ),
+            ),
+        }
+    }
+
+    fn masked(&self) -> Backend {
+        match self {
+            Backend::Discord { webhook_url } => Backend::Discord {
+                webhook_url: mask_secret(webhook_url),
+            },
+            Backend::Telegram { bot_token, chat_id } => Backend::Telegram {
+                bot_token: mask_secret(bot_token),
+                chat_id: chat_id.clone(),
+            },
+            Backend::Pushover {
+                app_token,
+                user_key,
+            } => Backend::Pushover {
+                app_token: mask_secret(app_token),
+                user_key: mask_secret(user_key),
+            },
+            Backend::Webhook { url } => Backend::Webhook {
+                url: mask_secret(url),
+            },
+        }
+    }
+
+    /// Put back the stored secrets the placeholder stands in for
+    fn keep_secrets(self, current: &Backend) -> Backend {
+        match (self, current) {
+            (Backend::Discord { webhook_url }, Backend::Discord { webhook_url: cur }) => {
+                Backend::Discord {
+                    webhook_url: keep_secret(webhook_url, cur),
+                }
+            }
+            (
+                Backend::Telegram { bot_token, chat_id },
+                Backend::Telegram { bot_token: cur, .. },
+            ) => Backend::Telegram {
+                bot_token: keep_secret(bot_token, cur),
+                chat_id,
+            },
+            (
+                Backend::Pushover {
+                    app_token,
+                    user_key,
+                },
+                Backend::Pushover {
+                    app_token: cur_app,
+                    user_key: cur_user,
+                },
+            ) => Backend::Pushover {
+                app_token: keep_secret(app_token, cur_app),
+                user_key: keep_secret(user_key, cur_user),
+            },
+            (Backend::Webhook { url }, Backend::Webhook { url: cur }) => Backend::Webhook {
+                url: keep_secret(url, cur),
+            },
+            (backend, _) => backend,
+        }
+    }
+
+    /// Clear placeholders that no stored secret can be found for
+    fn drop_placeholders(self) -> Backend {
+        let blank = match &self {
+            Backend::Discord { .. } => Backend::Discord {
+                webhook_url: String::new(),
+            },
+            Backend::Telegram { .. } => Backend::Telegram {
+                bot_token: String::new(),
+                chat_id: String::new(),
+            },
+            Backend::Pushover { .. } => Backend::Pushover {
+                app_token: String::new(),
+                user_key: String::new(),
+            },
+            Backend::Webhook { .. } => Backend::Webhook { url: String::new() },
+        };
+        self.keep_secrets(&blank)
+    }
+}
+
+/// A configured notification target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationTarget {
+    /// Shown in logs and test results, defaults to the backend type
+    #[serde(default)]
+    pub name: String,
+    #[serde(flatten)]
+    pub backend: Backend,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Events sent to this target, all of them when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl NotificationTarget {
+    pub fn label(&self) -> &str {
+        if self.name.is_empty() {
+            self.backend.kind()
+        } else {
+            &self.name
+        }
+    }
+
+    /// Whether this target is sent an event
+    pub fn wants(&self, event: &str) -> bool {
+        self.enabled
+            && (event == TEST
+                || self.events.is_empty()
+                || self.events.iter().any(|e| e.eq_ignore_ascii_case(event)))
+    }
+
+    pub fn masked(&self) -> NotificationTarget {
+        NotificationTarget {
+            backend: self.backend.masked(),
+            ..self.clone()
+        }
+    }
+
+    /// Send a notification, returning why it wasn't delivered
+    pub async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let (url, body) = self.backend.request(notification);
+        // Errors may contain the URL, which carries the token for most backends
+        let resp = client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.without_url().to_string())?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} returned {}",
+                self.backend.kind(),
+                resp.status()
+            ))
+        }
+    }
+}
+
+/// Keep stored secrets for targets sent back with the placeholder, matched by
+/// name and backend type, so removing or reordering targets doesn't hand one
+/// target's secrets to another. When no stored target, or more than one,
+/// matches, the placeholder is cleared instead of guessing.
+pub fn keep_secrets(
+    targets: Vec<NotificationTarget>,
+    current: &[NotificationTarget],
+) -> Vec<NotificationTarget> {
+    targets
+        .into_iter()
+        .map(|target| {
+            let mut same = current
+                .iter()
+                .filter(|c| c.name == target.name && c.backend.kind() == target.backend.kind());
+            let backend = match (same.next(), same.next()) {
+                (Some(cur), None) => target.backend.keep_secrets(&cur.backend),
+                _ => target.backend.drop_placeholders(),
+            };
+            NotificationTarget { backend, ..target }
+        })
+        .collect()
+}
+
+/// An event to tell people about
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub event: &'static str,
+    pub message: String,
+    /// Extra fields for webhook consumers
+    pub details: serde_json::Value,
+}
+
+impl Notification {
+    pub fn new(event: &'static str, message: String, details: serde_json::Value) -> Self {
+        Self {
+            event,
+            message,
+            details,
+        }
+    }
+
+    /// A transfer completed or failed. File names and errors of transfers on
+    /// private networks are left out.
+    pub fn transfer(event: &'static str, transfer: &XdccTransfer, private: bool) -> Self {
+        let outcome = if event == COMPLETED {
+            "finished downloading"
+        } else {
+            "failed"
+        };
+        if private {
+            return Self::new(
+                event,
+                format!("Botarr: a download on a private network {}", outcome),
+                serde_json::json!({ "id": transfer.id }),
+            );
+        }
+        let filename = transfer.filename.as_deref().unwrap_or("Unknown file");
+        let mut message = format!("Botarr: {} {}", filename, outcome);
+        if let (FAILED, Some(error)) = (event, transfer.error.as_deref()) {
+            message.push_str(&format!(": {}", error));
+        }
+        Self::new(
+            event,
+            message,
+            serde_json::json!({
+                "id": transfer.id,
+                "filename": transfer.filename,
+                "size": transfer.size,
+                "category": transfer.category,
+                "error": transfer.error,
+                "saved_to": transfer.saved_to,
+            }),
+        )
+    }
+
+    /// Everything queued since `since` was worked through
+    pub fn queue_finished<'a>(
+        transfers: impl IntoIterator<Item = &'a XdccTransfer>,
+        since: DateTime<Utc>,
+    ) -> Self {
+        let (mut completed, mut failed) = (0, 0);
+        for t in transfers.into_iter().filter(|t| t.updated_at >= since) {
+            match t.status {
+                TransferStatus::Completed => completed += 1,
+                TransferStatus::Failed => failed += 1,
+                _ => {}
+            }
+        }
+        Self::new(
+            QUEUE_FINISHED,
+            format!(
+                "Botarr: the queue is empty ({} completed, {} failed)",
+                completed, failed
+            ),
+            serde_json::json!({ "completed": completed, "failed": failed }),
+        )
+    }
+
+    fn title(&self) -> &'static str {
+        match self.event {
+            COMPLETED => "Download completed",
+            FAILED => "Download failed",
+            QUEUE_FINISHED => "Queue finished",
+            QUEUE_PAUSED => "Queue paused",
+            _ => "Botarr",
+        }
+    }
+}
+
+/// Send a notification to every enabled target that wants it
+pub fn dispatch(config: &AppConfig, notification: Notification) {
+    for target in config
+        .notifications
+        .iter()
+        .filter(|t| t.wants(notification.event))
+    {
+        let target = target.clone();
+        let notification = notification.clone();
+        tokio::spawn(async move {
+            match target.send(&notification).await {
+                Ok(()) => tracing::debug!(
+                    "Notification {} sent to {}",
+                    notification.event,
+                    target.label()
+                ),
+                Err(e) => tracing::warn!(
+                    "Notification {} to {} failed: {}",
+                    notification.event,
+                    target.label(),
+                    e
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SECRET_PLACEHOLDER;
+
+    fn target(backend: Backend, events: &[&str]) -> NotificationTarget {
+        NotificationTarget {
+            name: String::new(),
+            backend,
+            enabled: true,
+            events: events.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_config_format() {
+        let target: NotificationTarget = serde_json::from_value(serde_json::json!({
+            "type": "telegram",
+            "bot_token": "123:abc",
+            "chat_id": "42",
+            "events": ["failed"]
+        }))
+        .unwrap();
+        assert!(target.enabled);
+        assert_eq!(target.label(), "telegram");
+        assert_eq!(
+            target.backend,
+            Backend::Telegram {
+                bot_token: "123:abc".to_string(),
+                chat_id: "42".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_wants() {
+        let all = target(
+            Backend::Webhook {
+                url: "https://example.com/hook".to_string(),
+            },
+            &[],
+        );
+        assert!(all.wants(COMPLETED));
+        assert!(all.wants(QUEUE_FINISHED));
+
+        let mut failures = target(all.backend.clone(), &["Failed"]);
+        assert!(failures.wants(FAILED));
+        assert!(!failures.wants(COMPLETED));
+        assert!(failures.wants(TEST));
+        failures.enabled = false;
+        assert!(!failures.wants(FAILED));
+        assert!(!failures.wants(TEST));
+    }
+
+    #[test]
+    fn test_requests() {
+        let notification = Notification::new(
+            COMPLETED,
+            "Botarr: Show.mkv finished downloading".to_string(),
+            serde_json::json!({ "id": "t1" }),
+        );
+
+        let (url, body) = Backend::Telegram {
+            bot_token: "123:abc".to_string(),
+            chat_id: "42".to_string(),
+        }
+        .request(&notification);
+        assert_eq!(url, "https://api.telegram.org/bot123:abc/sendMessage");
+        assert_eq!(body["chat_id"], "42");
+        assert_eq!(body["text"], notification.message);
+
+        let (url, body) = Backend::Pushover {
+            app_token: "app".to_string(),
+            user_key: "user".to_string(),
+        }
+        .request(&notification);
+        assert_eq!(url, PUSHOVER_API);
+        assert_eq!(body["title"], "Download completed");
+        assert_eq!(body["user"], "user");
+
+        let (_, body) = Backend::Webhook {
+            url: "https://example.com/hook".to_string(),
+        }
+        .request(&notification);
+        assert_eq!(body["event"], "completed");
+        assert_eq!(body["content"], notification.message);
+        assert_eq!(body["id"], "t1");
+    }
+
+    #[test]
+    fn test_private_transfer() {
+        let mut transfer = XdccTransfer::new(
+            "t1".to_string(),
+            crate::xdcc::XdccUrl {
+                network: "secret".to_string(),
+                channel: "#c".to_string(),
+                bot: "Bot".to_string(),
+                slot: 1,
+            },
+        );
+        transfer.filename = Some("Show.mkv".to_string());
+        transfer.error = Some("Bot is offline".to_string());
+
+        let public = Notification::transfer(FAILED, &transfer, false);
+        assert_eq!(public.message, "Botarr: Show.mkv failed: Bot is offline");
+        let private = Notification::transfer(FAILED, &transfer, true);
+        assert!(!private.message.contains("Show.mkv"));
+        assert!(private.details.get("filename").is_none());
+    }
+
+    #[test]
+    fn test_queue_finished() {
+        let since = Utc::now() - chrono::Duration::minutes(10);
+        let transfer = |status: TransferStatus, mins_ago: i64| {
+            let mut t = XdccTransfer::new(
+                "t".to_string(),
+                crate::xdcc::XdccUrl {
+                    network: "rizon".to_string(),
+                    channel: "#c".to_string(),
+                    bot: "Bot".to_string(),
+                    slot: 1,
+                },
+            );
+            t.status = status;
+            t.updated_at = Utc::now() - chrono::Duration::minutes(mins_ago);
+            t
+        };
+        let transfers = [
+            transfer(TransferStatus::Completed, 1),
+            transfer(TransferStatus::Completed, 2),
+            transfer(TransferStatus::Failed, 3),
+            // Finished before the queue got busy
+            transfer(TransferStatus::Completed, 60),
+            transfer(TransferStatus::Paused, 1),
+        ];
+        let n = Notification::queue_finished(&transfers, since);
+        assert_eq!(n.event, QUEUE_FINISHED);
+        assert_eq!(
+            n.message,
+            "Botarr: the queue is empty (2 completed, 1 failed)"
+        );
+    }
+
+    #[test]
+    fn test_secrets() {
+        let stored = vec![
+            target(
+                Backend::Pushover {
+                    app_token: "app".to_string(),
+                    user_key: "user".to_string(),
+                },
+                &[],
+            ),
+            target(
+                Backend::Discord {
+                    webhook_url: "https://discord.com/api/webhooks/1/x".to_string(),
+                },
+                &[],
+            ),
+        ];
+        let masked: Vec<_> = stored.iter().map(|t| t.masked()).collect();
+        assert_eq!(
+            masked[0].backend,
+            Backend::Pushover {
+                app_token: SECRET_PLACEHOLDER.to_string(),
+                user_key: SECRET_PLACEHOLDER.to_string(),
+            }
+        );
+        assert_eq!(keep_secrets(masked.clone(), &stored), stored);
+
+        // Moved to another position, it keeps its own secrets
+        let moved = keep_secrets(vec![masked[1].clone()], &stored);
+        assert_eq!(moved[0], stored[1]);
+    }
+
+    #[test]
+    fn test_secrets_after_removal() {
+        let webhook = |name: &str, url: &str| NotificationTarget {
+            name: name.to_string(),
+            ..target(
+                Backend::Webhook {
+                    url: url.to_string(),
+                },
+                &[],
+            )
+        };
+        let stored = vec![
+            webhook("first", "https://one.example/hook"),
+            webhook("second", "https://two.example/hook"),
+        ];
+        let masked: Vec<_> = stored.iter().map(|t| t.masked()).collect();
+
+        // The first is removed: the second keeps its own URL
+        let kept = keep_secrets(masked[1..].to_vec(), &stored);
+        assert_eq!(kept, vec![stored[1].clone()]);
+
+        // Unnamed targets of the same type can't be told apart
+        let unnamed = vec![
+            webhook("", "https://one.example/hook"),
+            webhook("", "https://two.example/hook"),
+        ];
+        let kept = keep_secrets(vec![unnamed[1].masked()], &unnamed);
+        assert_eq!(kept[0].backend, Backend::Webhook { url: String::new() });
+        // A renamed target has nothing to match
+        let renamed = NotificationTarget {
+            name: "renamed".to_string(),
+            ..masked[0].clone()
+        };
+        let kept = keep_secrets(vec![renamed], &stored);
+        assert_eq!(kept[0].backend, Backend::Webhook { url: String::new() });
+    }
+}
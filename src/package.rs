@@ -0,0 +1,279 @@
+//! Job Packaging
+//!
+//! Once every transfer of a job (transfers linked by dependencies, e.g. the
+//! parts of a multi-part archive) has completed, their files can be gathered
+//! into one folder named from a template, optionally with an NFO manifest
+//! listing each file's size and checksums.
+
+use crate::config::AppConfig;
+use crate::filename::FilenamePolicy;
+use crate::postprocess::{move_file, render_name};
+use crate::quarantine::hash_file;
+use crate::xdcc::transfer::TransferJob;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+/// Jobs being packaged right now
+static PACKAGING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Mutex::default);
+
+/// Claim on packaging a job, so the last transfers of a job completing
+/// together (or a manual request meeting the automatic one) don't move the
+/// same files twice. Released when dropped.
+pub struct PackagingClaim(String);
+
+impl PackagingClaim {
+    /// Claim a job, unless it is being packaged already
+    pub fn acquire(job_id: &str) -> Option<Self> {
+        let mut packaging = PACKAGING.lock().unwrap_or_else(|e| e.into_inner());
+        if !packaging.insert(job_id.to_string()) {
+            return None;
+        }
+        Some(Self(job_id.to_string()))
+    }
+}
+
+impl Drop for PackagingClaim {
+    fn drop(&mut self) {
+        PACKAGING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.0);
+    }
+}
+
+/// Where and how completed jobs are packaged
+#[derive(Debug, Clone)]
+pub struct PackageConfig {
+    /// Directory the job folders are created in
+    pub dir: String,
+    /// Folder name pattern, see [`folder_name`]
+    pub template: String,
+    /// Write an NFO manifest with sizes and checksums
+    pub manifest: bool,
+    pub filename_policy: FilenamePolicy,
+}
+
+impl PackageConfig {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            dir: if config.package_dir.is_empty() {
+                config.completed_dir()
+            } else {
+                config.package_dir.clone()
+            },
+            template: config.package_template.clone(),
+            manifest: config.package_manifest,
+            filename_policy: FilenamePolicy::from_config(config),
+        }
+    }
+}
+
+/// File gathered into a job folder
+#[derive(Debug, Clone, Serialize)]
+pub struct PackagedFile {
+    pub transfer_id: String,
+    pub path: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crc32: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+}
+
+/// Outcome of packaging a job
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageReport {
+    pub dir: String,
+    pub files: Vec<PackagedFile>,
+    /// Path of the NFO manifest, if one was written
+    pub manifest: Option<String>,
+    /// Transfers whose file was not found on local disk
+    pub missing: Vec<String>,
+}
+
+/// Folder name for a job. Takes the placeholders of the rename step, filled
+/// in from the first transfer's file name, plus `{id}` (first 8 characters
+/// of the job id) and `{date}` (`YYYY-MM-DD` the job was created).
+pub fn folder_name(template: &str, job: &TransferJob, policy: FilenamePolicy) -> String {
+    let root = &job.transfers[0].transfer;
+    let file_name = root.filename.clone().unwrap_or_else(|| root.id.clone());
+    let pattern = template
+        .replace("{id}", &job.id.chars().take(8).collect::<String>())
+        .replace("{date}", &job.created_at.format("%Y-%m-%d").to_string());
+    let name = render_name(&pattern, &file_name, policy);
+    if name.is_empty() {
+        policy.sanitize(&job.id)
+    } else {
+        name
+    }
+}
+
+/// NFO manifest listing the packaged files
+pub fn manifest_text(name: &str, files: &[PackagedFile]) -> String {
+    let total: u64 = files.iter().map(|f| f.size).sum();
+    let mut text = format!(
+        "Package: {}\nCreated: {}\nFiles: {} ({} bytes)\n\n",
+        name,
+        chrono::Utc::now().to_rfc3339(),
+        files.len(),
+        total
+    );
+    for file in files {
+        let file_name = Path::new(&file.path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "{}  {} bytes  CRC32 {}  MD5 {}\n",
+            file_name,
+            file.size,
+            file.crc32.as_deref().unwrap_or("-"),
+            file.md5.as_deref().unwrap_or("-")
+        ));
+    }
+    text
+}
+
+/// Move the files of a completed job into one folder and write the manifest
+pub async fn package_job(
+    job: &TransferJob,
+    config: &PackageConfig,
+) -> Result<PackageReport, String> {
+    if job.status != "completed" {
+        return Err(format!(
+            "Job has {} of {} transfers completed",
+            job.completed, job.total
+        ));
+    }
+    let name = folder_name(&config.template, job, config.filename_policy);
+    let dir = Path::new(&config.dir).join(&name);
+    let dir_str = dir.to_string_lossy().to_string();
+
+    let mut files = Vec::new();
+    let mut missing = Vec::new();
+    for t in &job.transfers {
+        let transfer = &t.transfer;
+        let Some(source) = transfer
+            .saved_to
+            .as_deref()
+            .filter(|p| Path::new(p).is_file())
+        else {
+            missing.push(transfer.id.clone());
+            continue;
+        };
+        let path = move_file(source, &dir_str)
+            .await
+            .map_err(|e| format!("Cannot move {} to {}: {}", source, dir_str, e))?;
+        let size = tokio::fs::metadata(&path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let (crc32, md5) = if config.manifest {
+            let hashed = path.clone();
+            match tokio::task::spawn_blocking(move || hash_file(Path::new(&hashed))).await {
+                Ok(Ok((crc32, md5))) => (Some(crc32), Some(md5)),
+                _ => (None, None),
+            }
+        } else {
+            (None, None)
+        };
+        files.push(PackagedFile {
+            transfer_id: transfer.id.clone(),
+            path,
+            size,
+            crc32,
+            md5,
+        });
+    }
+    if files.is_empty() {
+        return Err("No file of the job was found on disk".to_string());
+    }
+
+    let manifest = if config.manifest {
+        let path = dir.join(format!("{}.nfo", name));
+        tokio::fs::write(&path, manifest_text(&name, &files))
+            .await
+            .map_err(|e| format!("Cannot write manifest: {}", e))?;
+        Some(path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    Ok(PackageReport {
+        dir: dir_str,
+        files,
+        manifest,
+        missing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xdcc::transfer::EnhancedTransfer;
+    use crate::xdcc::{TransferStatus, XdccTransfer, XdccUrl};
+    use tempfile::TempDir;
+
+    fn member(id: &str, file: &Path) -> EnhancedTransfer {
+        let url = XdccUrl::parse("irc://irc.rizon.net/#news/Bot/1").unwrap();
+        let mut transfer = XdccTransfer::new(id.to_string(), url);
+        transfer.status = TransferStatus::Completed;
+        transfer.filename = file.file_name().map(|n| n.to_string_lossy().to_string());
+        transfer.saved_to = Some(file.to_string_lossy().to_string());
+        EnhancedTransfer::new(transfer)
+    }
+
+    #[tokio::test]
+    async fn test_package_job() {
+        let temp = TempDir::new().unwrap();
+        let part1 = temp.path().join("Show.Name.2020.1080p-GRP.part1.rar");
+        let part2 = temp.path().join("Show.Name.2020.1080p-GRP.part2.rar");
+        std::fs::write(&part1, b"abc").unwrap();
+        std::fs::write(&part2, b"defg").unwrap();
+        let mut lost = member("lost", &temp.path().join("gone.rar"));
+        lost.transfer.saved_to = None;
+        let job = TransferJob::new(vec![
+            member("0123456789", &part1),
+            member("b", &part2),
+            lost,
+        ]);
+        let config = PackageConfig {
+            dir: temp.path().join("packages").to_string_lossy().to_string(),
+            template: "{title} ({year}) [{id}]".to_string(),
+            manifest: true,
+            filename_policy: FilenamePolicy::Posix,
+        };
+
+        let report = package_job(&job, &config).await.unwrap();
+        let dir = temp
+            .path()
+            .join("packages")
+            .join("Show Name (2020) [01234567]");
+        assert_eq!(report.dir, dir.to_string_lossy());
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.missing, vec!["lost".to_string()]);
+        assert!(dir.join("Show.Name.2020.1080p-GRP.part2.rar").is_file());
+        assert!(!part1.exists());
+        assert_eq!(report.files[0].crc32.as_deref(), Some("352441C2"));
+
+        let nfo = std::fs::read_to_string(report.manifest.unwrap()).unwrap();
+        assert!(nfo.contains("Files: 2 (7 bytes)"));
+        assert!(nfo
+            .contains("part1.rar  3 bytes  CRC32 352441C2  MD5 900150983cd24fb0d6963f7d28e17f72"));
+
+        let mut running = job.clone();
+        running.status = "running".to_string();
+        assert!(package_job(&running, &config).await.is_err());
+    }
+
+    #[test]
+    fn test_packaging_claim() {
+        let claim = PackagingClaim::acquire("job").unwrap();
+        assert!(PackagingClaim::acquire("job").is_none());
+        assert!(PackagingClaim::acquire("other").is_some());
+        drop(claim);
+        assert!(PackagingClaim::acquire("job").is_some());
+    }
+}
@@ -0,0 +1,281 @@
+//! Script Hooks
+//!
+//! Unlike plugin signals, which only notify scripts, hooks let a Lua script
+//! change what Botarr is about to do. Each hook point runs the script
+//! configured for it in `script_hooks`, calling the global function named
+//! after the hook with a table describing the situation:
+//!
+//! - returning `nil` (or nothing) leaves things unchanged
+//! - returning `false` vetoes the action
+//! - returning `true` approves it where that means something (DCC offers)
+//! - returning a table replaces the fields it sets
+//!
+//! Scripts are read on every call, so edits apply without a restart.
+//!
+//! Hooks are Lua, the language plugins are already written in, rather than a
+//! second embedded engine: a script can share helpers with plugins and there
+//! is one interpreter to sandbox and keep up to date. A script runs on a
+//! blocking thread with an instruction and a memory limit and a timeout, so a
+//! runaway loop fails the hook instead of freezing the runtime.
+
+use mlua::{Function, HookTriggers, Lua};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{json_to_lua, lua_to_json};
+use crate::config::AppConfig;
+use crate::xdcc::XdccSearchResult;
+
+/// Filter or rewrite search results: `on_search_results({query, results})`
+pub const ON_SEARCH_RESULTS: &str = "on_search_results";
+/// Veto or reroute a download before it connects: `on_before_download({id, url, ...})`
+pub const ON_BEFORE_DOWNLOAD: &str = "on_before_download";
+/// Accept (`true`) or reject (`false`) a DCC offer: `on_dcc_offer({sender, filename, ...})`
+pub const ON_DCC_OFFER: &str = "on_dcc_offer";
+/// Rename or recategorize a completed file: `on_complete({id, path, ...})`
+pub const ON_COMPLETE: &str = "on_complete";
+
+pub const HOOKS: &[&str] = &[
+    ON_SEARCH_RESULTS,
+    ON_BEFORE_DOWNLOAD,
+    ON_DCC_OFFER,
+    ON_COMPLETE,
+];
+
+/// Lua instructions a script may run per call
+const MAX_INSTRUCTIONS: u64 = 50_000_000;
+/// Instructions between checks of the limit
+const INSTRUCTION_CHECK: u32 = 10_000;
+/// Memory a script may allocate
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+/// How long a hook is waited for before things are left unchanged
+const HOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a hook decided
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookOutcome {
+    /// No script, the script returned nothing, or it failed
+    Unchanged,
+    /// The script returned `false`
+    Veto,
+    /// The script returned `true`
+    Approve,
+    /// The script returned a value to use instead
+    Changed(serde_json::Value),
+}
+
+/// Hook name -> Lua script path, taken from the config
+#[derive(Debug, Clone, Default)]
+pub struct ScriptHooks {
+    scripts: HashMap<String, String>,
+}
+
+impl ScriptHooks {
+    pub fn from_config(config: &AppConfig) -> Self {
+        for hook in config.script_hooks.keys() {
+            if !HOOKS.contains(&hook.as_str()) {
+                tracing::warn!("Ignoring script for unknown hook '{}'", hook);
+            }
+        }
+        Self {
+            scripts: config
+                .script_hooks
+                .iter()
+                .filter(|(_, path)| !path.is_empty())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn is_set(&self, hook: &str) -> bool {
+        self.scripts.contains_key(hook)
+    }
+
+    /// Run the script of `hook` with `arg`. Failures are logged and leave
+    /// things unchanged so a broken script can't stall downloads.
+    pub async fn run(&self, hook: &str, arg: serde_json::Value) -> HookOutcome {
+        let Some(path) = self.scripts.get(hook).cloned() else {
+            return HookOutcome::Unchanged;
+        };
+        let name = hook.to_string();
+        let script = path.clone();
+        let call = tokio::task::spawn_blocking(move || Self::call(&script, &name, &arg));
+        let result = match tokio::time::timeout(HOOK_TIMEOUT, call).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("timed out after {:?}", HOOK_TIMEOUT)),
+        };
+        match result {
+            Ok(serde_json::Value::Null) => HookOutcome::Unchanged,
+            Ok(serde_json::Value::Bool(false)) => HookOutcome::Veto,
+            Ok(serde_json::Value::Bool(true)) => HookOutcome::Approve,
+            Ok(value) => HookOutcome::Changed(value),
+            Err(e) => {
+                tracing::warn!("Hook {} ({}) failed: {}", hook, path, e);
+                HookOutcome::Unchanged
+            }
+        }
+    }
+
+    fn call(path: &str, hook: &str, arg: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let code = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let lua = Lua::new();
+        lua.set_memory_limit(MAX_MEMORY)
+            .map_err(|e| e.to_string())?;
+        let executed = Cell::new(0u64);
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(INSTRUCTION_CHECK),
+            move |_, _| {
+                executed.set(executed.get() + u64::from(INSTRUCTION_CHECK));
+                if executed.get() > MAX_INSTRUCTIONS {
+                    return Err(mlua::Error::RuntimeError(
+                        "instruction limit reached".to_string(),
+                    ));
+                }
+                Ok(())
+            },
+        );
+        lua.load(&code).exec().map_err(|e| e.to_string())?;
+        let func: Function = lua
+            .globals()
+            .get(hook)
+            .map_err(|_| format!("script doesn't define {}()", hook))?;
+        let arg = json_to_lua(&lua, arg).map_err(|e| e.to_string())?;
+        let value: mlua::Value = func.call(arg).map_err(|e| e.to_string())?;
+        Ok(lua_to_json(value))
+    }
+
+    /// Let `on_search_results` drop, reorder or edit results. It returns the
+    /// new result list; entries that don't parse back are dropped.
+    pub async fn filter_search_results(
+        &self,
+        query: &str,
+        results: Vec<XdccSearchResult>,
+    ) -> Vec<XdccSearchResult> {
+        if !self.is_set(ON_SEARCH_RESULTS) {
+            return results;
+        }
+        let arg = serde_json::json!({ "query": query, "results": results });
+        match self.run(ON_SEARCH_RESULTS, arg).await {
+            HookOutcome::Veto => Vec::new(),
+            HookOutcome::Changed(serde_json::Value::Array(items)) => items
+                .into_iter()
+                .filter_map(|item| serde_json::from_value(item).ok())
+                .collect(),
+            HookOutcome::Changed(_) => {
+                tracing::warn!("{} must return a list of results", ON_SEARCH_RESULTS);
+                results
+            }
+            HookOutcome::Unchanged | HookOutcome::Approve => results,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xdcc::XdccUrl;
+
+    #[tokio::test]
+    async fn test_hook_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("hooks.lua");
+        std::fs::write(
+            &script,
+            r#"
+            function on_search_results(ctx)
+                local kept = {}
+                for _, r in ipairs(ctx.results) do
+                    if not string.find(r.file_name, "CAM") then
+                        r.file_name = string.gsub(r.file_name, " ", ".")
+                        table.insert(kept, r)
+                    end
+                end
+                return kept
+            end
+            function on_dcc_offer(offer)
+                if offer.size > 1000 then return false end
+            end
+            function on_complete(t)
+                return { category = "anime:" .. t.category }
+            end
+            "#,
+        )
+        .unwrap();
+        let path = script.to_string_lossy().to_string();
+        let mut config = AppConfig::default();
+        for hook in [ON_SEARCH_RESULTS, ON_DCC_OFFER, ON_COMPLETE, "on_nothing"] {
+            config.script_hooks.insert(hook.to_string(), path.clone());
+        }
+        let hooks = ScriptHooks::from_config(&config);
+
+        let result = |name: &str| XdccSearchResult {
+            url: XdccUrl::parse("irc://irc.rizon.net/chan/Bot/1").unwrap(),
+            filename: name.to_string(),
+            size: Some(10),
+            size_str: String::new(),
+            bot: "Bot".to_string(),
+            network: "irc.rizon.net".to_string(),
+            channel: "#chan".to_string(),
+            slot: 1,
+            gets: None,
+            last_seen: None,
+            annotations: Default::default(),
+        };
+        let filtered = hooks
+            .filter_search_results("show", vec![result("Show 01.mkv"), result("Movie.CAM.mkv")])
+            .await;
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].filename, "Show.01.mkv");
+
+        assert_eq!(
+            hooks
+                .run(ON_DCC_OFFER, serde_json::json!({"size": 5000}))
+                .await,
+            HookOutcome::Veto
+        );
+        assert_eq!(
+            hooks
+                .run(ON_DCC_OFFER, serde_json::json!({"size": 10}))
+                .await,
+            HookOutcome::Unchanged
+        );
+        assert_eq!(
+            hooks
+                .run(ON_COMPLETE, serde_json::json!({"category": "tv"}))
+                .await,
+            HookOutcome::Changed(serde_json::json!({"category": "anime:tv"}))
+        );
+        // Not configured, or the script doesn't define it
+        assert_eq!(
+            hooks.run(ON_BEFORE_DOWNLOAD, serde_json::json!({})).await,
+            HookOutcome::Unchanged
+        );
+        assert_eq!(
+            hooks.run("on_nothing", serde_json::json!({})).await,
+            HookOutcome::Unchanged
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runaway_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("loop.lua");
+        std::fs::write(
+            &script,
+            "function on_dcc_offer(offer) while true do end end",
+        )
+        .unwrap();
+        let mut config = AppConfig::default();
+        config.script_hooks.insert(
+            ON_DCC_OFFER.to_string(),
+            script.to_string_lossy().to_string(),
+        );
+        let hooks = ScriptHooks::from_config(&config);
+        assert_eq!(
+            hooks.run(ON_DCC_OFFER, serde_json::json!({})).await,
+            HookOutcome::Unchanged
+        );
+    }
+}
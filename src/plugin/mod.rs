@@ -1,16 +1,83 @@
+pub mod hooks;
+
 use mlua::{Function, Lua, Table};
 use std::collections::VecDeque;
 use std::path::Path;
 use std::sync::{Arc, Mutex, RwLock};
 use tokio::sync::mpsc;
 
+use crate::config::AppConfig;
+use crate::release::classify;
+use crate::xdcc::TransferPriority;
+
 #[derive(Debug)]
 pub enum PluginAction {
-    Download(String, Option<String>),
-    Queue(String, Option<String>),
+    Download(String, Option<String>, GrabOptions),
+    Queue(String, Option<String>, GrabOptions),
     MonitorChannel(String, String, String), // plugin_name, network, channel
 }
 
+/// Optional scheduling settings passed with a plugin download/queue request,
+/// e.g. from a watchlist entry
+#[derive(Debug, Clone, Default)]
+pub struct GrabOptions {
+    pub priority: Option<TransferPriority>,
+    pub max_retries: Option<u32>,
+    pub category: Option<String>,
+    /// Download template supplying unset values
+    pub template: Option<String>,
+}
+
+impl GrabOptions {
+    fn from_lua(table: Option<Table>) -> mlua::Result<Self> {
+        let Some(table) = table else {
+            return Ok(Self::default());
+        };
+        let priority = table
+            .get::<_, Option<String>>("priority")?
+            .and_then(|p| serde_json::from_value(serde_json::Value::String(p.to_lowercase())).ok());
+        Ok(Self {
+            priority,
+            max_retries: table.get("max_retries")?,
+            category: table
+                .get::<_, Option<String>>("category")?
+                .filter(|c| !c.is_empty()),
+            template: table
+                .get::<_, Option<String>>("template")?
+                .filter(|t| !t.is_empty()),
+        })
+    }
+
+    /// Fill unset values from the template, then the category settings. The
+    /// category is taken from the options, the template or classified from
+    /// the filename.
+    pub fn resolve(mut self, config: &AppConfig, filename: Option<&str>) -> Self {
+        if let Some(name) = self.template.take() {
+            match config.template(&name) {
+                Some((name, template)) => {
+                    self.template = Some(name.clone());
+                    self.priority = self.priority.or(template.priority);
+                    self.max_retries = self.max_retries.or(template.max_retries);
+                    self.category = self.category.or_else(|| template.category.clone());
+                }
+                None => tracing::warn!("Ignoring unknown download template '{}'", name),
+            }
+        }
+        if self.category.is_none() {
+            self.category = filename.map(|f| classify(f).to_string());
+        }
+        if let Some(cat) = self
+            .category
+            .as_ref()
+            .and_then(|c| config.categories.get(c))
+        {
+            self.priority = self.priority.or(cat.priority);
+            self.max_retries = self.max_retries.or(cat.max_retries);
+        }
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum EventData {
     String(String),
@@ -57,6 +124,48 @@ fn json_to_lua<'lua>(lua: &'lua Lua, val: &serde_json::Value) -> mlua::Result<ml
     }
 }
 
+/// Convert a Lua value back to JSON. Tables with only the keys 1..n (and
+/// empty tables) become arrays, other tables objects.
+fn lua_to_json(val: mlua::Value) -> serde_json::Value {
+    match val {
+        mlua::Value::Boolean(b) => serde_json::Value::Bool(b),
+        mlua::Value::Integer(i) => serde_json::Value::from(i),
+        mlua::Value::Number(n) => serde_json::Number::from_f64(n)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        mlua::Value::String(s) => serde_json::Value::String(s.to_string_lossy().to_string()),
+        mlua::Value::Table(t) => {
+            let len = t.raw_len();
+            let pairs: Vec<(mlua::Value, mlua::Value)> = t.pairs().flatten().collect();
+            if pairs.len() == len {
+                let mut items = vec![serde_json::Value::Null; len];
+                for (k, v) in pairs {
+                    if let mlua::Value::Integer(i) = k {
+                        if i >= 1 && (i as usize) <= len {
+                            items[i as usize - 1] = lua_to_json(v);
+                        }
+                    }
+                }
+                serde_json::Value::Array(items)
+            } else {
+                serde_json::Value::Object(
+                    pairs
+                        .into_iter()
+                        .filter_map(|(k, v)| match k {
+                            mlua::Value::String(s) => {
+                                Some((s.to_string_lossy().to_string(), lua_to_json(v)))
+                            }
+                            mlua::Value::Integer(i) => Some((i.to_string(), lua_to_json(v))),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            }
+        }
+        _ => serde_json::Value::Null,
+    }
+}
+
 impl PluginManager {
     pub fn new() -> Result<(Self, mpsc::UnboundedReceiver<PluginAction>), mlua::Error> {
         let lua = Lua::new();
@@ -125,24 +234,28 @@ impl PluginManager {
             })?;
             botarr_table.set("execute", execute)?;
 
-            // download(url, [filename])
+            // download(url, [filename], [{priority, max_retries, category}])
             let tx_clone = tx.clone();
-            let download =
-                lua.create_function(move |_, (url, filename): (String, Option<String>)| {
+            let download = lua.create_function(
+                move |_, (url, filename, opts): (String, Option<String>, Option<Table>)| {
                     tracing::info!("Plugin requested download: {} (file: {:?})", url, filename);
-                    let _ = tx_clone.send(PluginAction::Download(url, filename));
+                    let opts = GrabOptions::from_lua(opts)?;
+                    let _ = tx_clone.send(PluginAction::Download(url, filename, opts));
                     Ok(())
-                })?;
+                },
+            )?;
             botarr_table.set("download", download)?;
 
-            // queue(url, [filename])
+            // queue(url, [filename], [{priority, max_retries, category}])
             let tx_queue = tx.clone();
-            let queue =
-                lua.create_function(move |_, (url, filename): (String, Option<String>)| {
+            let queue = lua.create_function(
+                move |_, (url, filename, opts): (String, Option<String>, Option<Table>)| {
                     tracing::info!("Plugin requested queue: {} (file: {:?})", url, filename);
-                    let _ = tx_queue.send(PluginAction::Queue(url, filename));
+                    let opts = GrabOptions::from_lua(opts)?;
+                    let _ = tx_queue.send(PluginAction::Queue(url, filename, opts));
                     Ok(())
-                })?;
+                },
+            )?;
             botarr_table.set("queue", queue)?;
 
             // monitor_channel(plugin_name, network, channel)
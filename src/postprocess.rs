@@ -1,13 +1,97 @@
 //! Postprocessing Module
 //!
-//! Handles post-download actions:
-//! - Moving completed files to a separate directory
+//! Handles post-download actions, run as an ordered chain of steps:
+//! - Extracting archives and renaming files from a pattern
+//! - Moving completed files to a separate directory (local, a mounted SMB/NFS
+//!   share, or an rclone remote), verifying the copy and retrying on failure
 //! - Executing external scripts for unpacking/renaming
+//! - Notifying a webhook
 
+use crate::archive::{self, Extracted};
+use crate::filename::{unique_path, FilenamePolicy};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::process::Stdio;
 use tokio::process::Command;
 
+/// Prefix marking a move target as an rclone remote (`rclone:nas:media/tv`)
+const RCLONE_PREFIX: &str = "rclone:";
+
+/// Where completed files are moved to
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveTarget {
+    /// Local directory, including mounted network shares
+    Local(String),
+    /// rclone remote path, e.g. `nas:media/tv`
+    Rclone(String),
+}
+
+impl MoveTarget {
+    pub fn parse(target: &str) -> Self {
+        match target.strip_prefix(RCLONE_PREFIX) {
+            Some(remote) => Self::Rclone(remote.trim_end_matches('/').to_string()),
+            None => Self::Local(target.to_string()),
+        }
+    }
+}
+
+/// A single action in a postprocess chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum PostprocessStep {
+    /// Unpack a zip/rar/7z/tar archive, waiting for all volumes of a
+    /// multi-volume set, and continue with its largest file
+    Extract,
+    /// Rename the file from a pattern such as `{title} - S{season}E{episode}.{ext}`
+    Rename { pattern: String },
+    /// Move the file to a directory or `rclone:` remote
+    Move { dir: String },
+    /// Post the steps so far to a webhook (defaults to the alert webhook)
+    Notify {
+        #[serde(default)]
+        url: String,
+    },
+    /// Run a script with the file path as its argument
+    Script { path: String },
+}
+
+impl PostprocessStep {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Extract => "extract",
+            Self::Rename { .. } => "rename",
+            Self::Move { .. } => "move",
+            Self::Notify { .. } => "notify",
+            Self::Script { .. } => "script",
+        }
+    }
+}
+
+/// Outcome of a postprocess step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    Ok,
+    Failed,
+    /// Not run because an earlier step failed, or nothing to do
+    Skipped,
+}
+
+/// Record of one executed postprocess step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub action: String,
+    pub status: StepStatus,
+    pub output: String,
+}
+
+/// The extract step of a run, if there was one
+pub fn extraction_step(steps: &[StepResult]) -> Option<&StepResult> {
+    steps
+        .iter()
+        .find(|s| s.action == PostprocessStep::Extract.name())
+}
+
 /// Postprocessing configuration
 #[derive(Debug, Clone)]
 pub struct PostprocessConfig {
@@ -15,8 +99,20 @@ pub struct PostprocessConfig {
     pub move_completed_dir: Option<String>,
     /// Path to external postprocessing script
     pub script_path: Option<String>,
+    /// Ordered actions to run instead of move + script
+    pub steps: Vec<PostprocessStep>,
     /// Timeout for script execution in seconds
     pub script_timeout_secs: u64,
+    /// Extra attempts when moving a file fails or doesn't verify
+    pub move_retries: u32,
+    /// rclone executable used for `rclone:` move targets
+    pub rclone_path: String,
+    /// Webhook notify steps post to when they don't name one
+    pub notify_url: String,
+    /// How renamed files are made safe to save
+    pub filename_policy: FilenamePolicy,
+    /// Delete archives (every volume of a set) once extracted
+    pub extract_delete_archives: bool,
 }
 
 impl Default for PostprocessConfig {
@@ -24,8 +120,93 @@ impl Default for PostprocessConfig {
         Self {
             move_completed_dir: None,
             script_path: None,
+            steps: Vec::new(),
             script_timeout_secs: 300, // 5 minutes default
+            move_retries: 3,
+            rclone_path: "rclone".to_string(),
+            notify_url: String::new(),
+            filename_policy: FilenamePolicy::default(),
+            extract_delete_archives: true,
+        }
+    }
+}
+
+impl PostprocessConfig {
+    /// Build the postprocessing config for a download category, applying the
+    /// category's step chain, directory and script overrides on top of the
+    /// global settings
+    pub fn for_category(config: &crate::config::AppConfig, category: Option<&str>) -> Self {
+        let overrides = category.and_then(|c| config.categories.get(c));
+
+        let move_completed_dir = match overrides {
+            Some(o) if !o.move_dir.is_empty() => Some(o.move_dir.clone()),
+            _ if config.move_completed && !config.move_completed_dir.is_empty() => {
+                Some(config.move_completed_dir.clone())
+            }
+            _ => None,
+        };
+        let script_path = match overrides {
+            Some(o) if !o.script.is_empty() => Some(o.script.clone()),
+            _ if config.postprocess_script_enabled && !config.postprocess_script.is_empty() => {
+                Some(config.postprocess_script.clone())
+            }
+            _ => None,
+        };
+        let steps = match overrides {
+            Some(o) if !o.steps.is_empty() => o.steps.clone(),
+            _ => config.postprocess_steps.clone(),
+        };
+
+        Self {
+            move_completed_dir,
+            script_path,
+            steps,
+            script_timeout_secs: config.postprocess_timeout,
+            move_retries: config.move_retries,
+            rclone_path: config.rclone_path.clone(),
+            notify_url: config.alert_webhook_url.clone(),
+            filename_policy: FilenamePolicy::from_config(config),
+            extract_delete_archives: config.extract_delete_archives,
+        }
+    }
+
+    /// Postprocessing config of a transfer: its category's, with the
+    /// destination and step chain of its download template on top
+    pub fn for_transfer(
+        config: &crate::config::AppConfig,
+        category: Option<&str>,
+        template: Option<&str>,
+    ) -> Self {
+        let mut pp = Self::for_category(config, category);
+        if let Some((_, template)) = template.and_then(|t| config.template(t)) {
+            if !template.move_dir.is_empty() {
+                pp.move_completed_dir = Some(template.move_dir.clone());
+            }
+            if !template.steps.is_empty() {
+                pp.steps = template.steps.clone();
+            }
+        }
+        pp
+    }
+
+    /// Steps to run: the configured chain, or move then script
+    pub fn chain(&self) -> Vec<PostprocessStep> {
+        if !self.steps.is_empty() {
+            return self.steps.clone();
+        }
+        let mut chain = Vec::new();
+        if let Some(dir) = self.move_completed_dir.as_ref().filter(|d| !d.is_empty()) {
+            chain.push(PostprocessStep::Move { dir: dir.clone() });
         }
+        if let Some(path) = self.script_path.as_ref().filter(|p| !p.is_empty()) {
+            chain.push(PostprocessStep::Script { path: path.clone() });
+        }
+        chain
+    }
+
+    /// Whether there is anything to do
+    pub fn is_empty(&self) -> bool {
+        self.chain().is_empty()
     }
 }
 
@@ -36,10 +217,17 @@ pub struct PostprocessResult {
     pub script_exit_code: Option<i32>,
     pub script_output: Option<String>,
     pub errors: Vec<String>,
+    /// Every step of the chain with its status and output
+    pub steps: Vec<StepResult>,
+    /// File the chain ended with
+    pub final_path: Option<String>,
 }
 
 /// Run postprocessing on a completed download
 ///
+/// Steps run in order, each on the file the previous one produced; once a
+/// step fails the rest are skipped.
+///
 /// # Arguments
 /// * `source_path` - Full path to the downloaded file
 /// * `config` - Postprocessing configuration
@@ -52,6 +240,8 @@ pub async fn run_postprocess(source_path: &str, config: &PostprocessConfig) -> P
         script_exit_code: None,
         script_output: None,
         errors: Vec::new(),
+        steps: Vec::new(),
+        final_path: None,
     };
 
     let source = Path::new(source_path);
@@ -64,50 +254,302 @@ pub async fn run_postprocess(source_path: &str, config: &PostprocessConfig) -> P
         return result;
     }
 
-    // Current file path (may change after move)
+    // Current file path (changes as steps extract, rename and move it)
     let mut current_path = source_path.to_string();
+    // Why the rest of the chain is skipped, once it is
+    let mut stopped: Option<&str> = None;
+
+    for step in config.chain() {
+        if let Some(reason) = stopped {
+            result.steps.push(StepResult {
+                action: step.name().to_string(),
+                status: StepStatus::Skipped,
+                output: reason.to_string(),
+            });
+            continue;
+        }
 
-    // Step 1: Move file if configured
-    if let Some(ref move_dir) = config.move_completed_dir {
-        if !move_dir.is_empty() {
-            match move_file(&current_path, move_dir).await {
-                Ok(new_path) => {
-                    tracing::info!("Moved file to: {}", new_path);
-                    result.moved_to = Some(new_path.clone());
-                    current_path = new_path;
-                }
-                Err(e) => {
-                    let err = format!("Failed to move file: {}", e);
-                    tracing::error!("{}", err);
-                    result.errors.push(err);
+        let outcome = match &step {
+            PostprocessStep::Extract => {
+                archive::extract(Path::new(&current_path), config.extract_delete_archives)
+                    .await
+                    .map(|extracted| match extracted {
+                        Some(Extracted::Done {
+                            dir,
+                            largest,
+                            files,
+                        }) => {
+                            current_path = largest.to_string_lossy().to_string();
+                            let output = format!(
+                                "Extracted {} file(s) to {}, continuing with {}",
+                                files,
+                                dir.display(),
+                                current_path
+                            );
+                            (StepStatus::Ok, output)
+                        }
+                        // The set isn't complete: leave the volume where it is
+                        Some(Extracted::Waiting(reason)) => {
+                            stopped = Some("Waiting for the archive to be complete");
+                            (StepStatus::Skipped, reason)
+                        }
+                        Some(Extracted::AlreadyDone) => {
+                            stopped = Some("Archive was extracted with another volume");
+                            (
+                                StepStatus::Skipped,
+                                "Already extracted with another volume".to_string(),
+                            )
+                        }
+                        None => (StepStatus::Skipped, "Not an archive".to_string()),
+                    })
+            }
+            PostprocessStep::Rename { pattern } => {
+                rename_file(&current_path, pattern, config.filename_policy)
+                    .await
+                    .map(|renamed| {
+                        current_path = renamed;
+                        (StepStatus::Ok, format!("Renamed to {}", current_path))
+                    })
+            }
+            PostprocessStep::Move { dir } => move_with_retries(&current_path, dir, config)
+                .await
+                .map(|moved| {
+                    tracing::info!("Moved file to: {}", moved);
+                    result.moved_to = Some(moved.clone());
+                    current_path = moved;
+                    (StepStatus::Ok, format!("Moved to {}", current_path))
+                }),
+            PostprocessStep::Notify { url } => {
+                let url = if url.is_empty() {
+                    &config.notify_url
+                } else {
+                    url
+                };
+                if url.is_empty() {
+                    Err("No webhook URL configured".to_string())
+                } else {
+                    let name = Path::new(&current_path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let details = serde_json::json!({
+                        "file": current_path,
+                        "steps": result.steps,
+                    });
+                    crate::alerts::send_notification(
+                        url,
+                        "postprocess",
+                        &format!("Botarr: postprocessed {}", name),
+                        details,
+                    )
+                    .await;
+                    Ok((StepStatus::Ok, format!("Notified {}", url)))
                 }
             }
-        }
+            PostprocessStep::Script { path } => {
+                run_script(path, &current_path, config.script_timeout_secs)
+                    .await
+                    .and_then(|(exit_code, output)| {
+                        tracing::info!("Script exited with code: {}", exit_code);
+                        result.script_exit_code = Some(exit_code);
+                        result.script_output = Some(output.clone());
+                        if exit_code == 0 {
+                            Ok((StepStatus::Ok, output))
+                        } else {
+                            Err(format!(
+                                "Script exited with code {}: {}",
+                                exit_code,
+                                output.trim()
+                            ))
+                        }
+                    })
+            }
+        };
+
+        let (status, output) = outcome.unwrap_or_else(|e| {
+            let err = format!("{} step failed: {}", step.name(), e);
+            tracing::error!("{}", err);
+            result.errors.push(err);
+            stopped = Some("Skipped after an earlier failure");
+            (StepStatus::Failed, e)
+        });
+        result.steps.push(StepResult {
+            action: step.name().to_string(),
+            status,
+            output,
+        });
     }
 
-    // Step 2: Execute script if configured
-    if let Some(ref script) = config.script_path {
-        if !script.is_empty() {
-            match run_script(script, &current_path, config.script_timeout_secs).await {
-                Ok((exit_code, output)) => {
-                    tracing::info!("Script exited with code: {}", exit_code);
-                    result.script_exit_code = Some(exit_code);
-                    result.script_output = Some(output);
-                }
-                Err(e) => {
-                    let err = format!("Script execution failed: {}", e);
-                    tracing::error!("{}", err);
-                    result.errors.push(err);
-                }
+    result.final_path = Some(current_path);
+    result
+}
+
+/// Build a file name from a rename pattern. Placeholders: `{title}`,
+/// `{year}`, `{season}`, `{episode}` (two digits), `{group}`, `{resolution}`,
+/// `{name}` (original name without extension) and `{ext}`.
+pub(crate) fn render_name(pattern: &str, file_name: &str, policy: FilenamePolicy) -> String {
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let release = crate::release::parse_release(file_name);
+    let episode = release.episode.as_ref();
+
+    let name = pattern
+        .replace("{title}", &release.title)
+        .replace(
+            "{year}",
+            &release.year.map(|y| y.to_string()).unwrap_or_default(),
+        )
+        .replace(
+            "{season}",
+            &episode
+                .map(|e| format!("{:02}", e.season))
+                .unwrap_or_default(),
+        )
+        .replace(
+            "{episode}",
+            &episode
+                .map(|e| format!("{:02}", e.episode))
+                .or_else(|| release.absolute_episode.map(|e| format!("{:02}", e)))
+                .unwrap_or_default(),
+        )
+        .replace("{group}", release.group.as_deref().unwrap_or_default())
+        .replace(
+            "{resolution}",
+            release.resolution.as_deref().unwrap_or_default(),
+        )
+        .replace("{name}", &stem)
+        .replace("{ext}", &ext);
+    let name = name.trim();
+    if name.is_empty() {
+        return String::new();
+    }
+    policy.sanitize(name)
+}
+
+/// Rename a file in place from a pattern, adding a ` (n)` suffix if another
+/// file has the name
+async fn rename_file(path: &str, pattern: &str, policy: FilenamePolicy) -> Result<String, String> {
+    let source = Path::new(path);
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| "Invalid source path".to_string())?
+        .to_string_lossy();
+    let new_name = render_name(pattern, &file_name, policy);
+    if new_name.is_empty() {
+        return Err(format!("Pattern {:?} produced an empty name", pattern));
+    }
+    if *file_name == new_name {
+        return Ok(path.to_string());
+    }
+    let target = unique_path(source.parent().unwrap_or(Path::new("")), &new_name);
+    tokio::fs::rename(source, &target)
+        .await
+        .map_err(|e| format!("Cannot rename {}: {}", path, e))?;
+    Ok(target.to_string_lossy().to_string())
+}
+
+/// Move a file to a local directory or rclone remote, retrying with backoff
+async fn move_with_retries(
+    source_path: &str,
+    target: &str,
+    config: &PostprocessConfig,
+) -> Result<String, String> {
+    let target = MoveTarget::parse(target);
+    let mut attempt = 0;
+    loop {
+        let result = match &target {
+            MoveTarget::Local(dir) => move_file(source_path, dir).await.map_err(|e| e.to_string()),
+            MoveTarget::Rclone(remote) => {
+                rclone_move(source_path, remote, &config.rclone_path).await
+            }
+        };
+        match result {
+            Ok(path) => return Ok(path),
+            Err(e) if attempt < config.move_retries => {
+                attempt += 1;
+                let delay = 2u64.pow(attempt).min(60);
+                tracing::warn!(
+                    "Move attempt {} failed: {}. Retrying in {}s",
+                    attempt,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
             }
+            Err(e) => return Err(e),
         }
     }
+}
 
-    result
+/// Upload a file to an rclone remote, verify its size there and remove the
+/// local copy
+async fn rclone_move(source_path: &str, remote: &str, rclone: &str) -> Result<String, String> {
+    let source = Path::new(source_path);
+    let filename = source
+        .file_name()
+        .ok_or_else(|| "Invalid source path".to_string())?
+        .to_string_lossy();
+    let local_size = tokio::fs::metadata(source)
+        .await
+        .map_err(|e| format!("Cannot read {}: {}", source_path, e))?
+        .len();
+    let destination = if remote.ends_with(':') {
+        format!("{}{}", remote, filename)
+    } else {
+        format!("{}/{}", remote, filename)
+    };
+
+    let output = Command::new(rclone)
+        .args(["copyto", source_path, &destination])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", rclone, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "rclone copyto failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Verify the uploaded size before deleting anything
+    let output = Command::new(rclone)
+        .args(["lsjson", &destination])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run {}: {}", rclone, e))?;
+    let listing: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected rclone lsjson output: {}", e))?;
+    let remote_size = listing
+        .get(0)
+        .and_then(|entry| entry.get("Size"))
+        .and_then(|size| size.as_u64());
+    if remote_size != Some(local_size) {
+        return Err(format!(
+            "Verification failed for {}: expected {} bytes, remote has {:?}",
+            destination, local_size, remote_size
+        ));
+    }
+
+    tokio::fs::remove_file(source)
+        .await
+        .map_err(|e| format!("Uploaded, but failed to remove local file: {}", e))?;
+    Ok(destination)
 }
 
 /// Move a file to a target directory
-async fn move_file(source_path: &str, target_dir: &str) -> Result<String, std::io::Error> {
+pub(crate) async fn move_file(
+    source_path: &str,
+    target_dir: &str,
+) -> Result<String, std::io::Error> {
     let source = Path::new(source_path);
     let target_directory = Path::new(target_dir);
 
@@ -122,15 +564,28 @@ async fn move_file(source_path: &str, target_dir: &str) -> Result<String, std::i
         std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid source path")
     })?;
 
-    let target_path = target_directory.join(filename);
+    if target_directory.join(filename) == source {
+        return Ok(source_path.to_string());
+    }
+    // Never replace a file that is already there
+    let target_path = unique_path(target_directory, &filename.to_string_lossy());
     let target_str = target_path.to_string_lossy().to_string();
 
     // Try rename first (fast, same filesystem)
     match tokio::fs::rename(source, &target_path).await {
         Ok(_) => Ok(target_str),
         Err(_) => {
-            // Cross-filesystem: copy then delete
-            tokio::fs::copy(source, &target_path).await?;
+            // Cross-filesystem (e.g. a network share): copy, verify, then delete
+            let copied = tokio::fs::copy(source, &target_path).await?;
+            let expected = tokio::fs::metadata(source).await?.len();
+            let written = tokio::fs::metadata(&target_path).await?.len();
+            if copied != expected || written != expected {
+                let _ = tokio::fs::remove_file(&target_path).await;
+                return Err(std::io::Error::other(format!(
+                    "Copy verification failed: expected {} bytes, wrote {}",
+                    expected, written
+                )));
+            }
             tokio::fs::remove_file(source).await?;
             Ok(target_str)
         }
@@ -215,6 +670,161 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_template_overrides_category() {
+        let mut config = crate::config::AppConfig::default();
+        config.categories.insert(
+            "anime".to_string(),
+            crate::config::CategoryConfig {
+                move_dir: "/media/anime".to_string(),
+                script: "/scripts/anime.sh".to_string(),
+                ..Default::default()
+            },
+        );
+        config.templates.insert(
+            "anime-to-NAS".to_string(),
+            crate::config::DownloadTemplate {
+                move_dir: "/mnt/nas/anime".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let pp = PostprocessConfig::for_transfer(&config, Some("anime"), Some("anime-to-nas"));
+        assert_eq!(pp.move_completed_dir.as_deref(), Some("/mnt/nas/anime"));
+        assert_eq!(pp.script_path.as_deref(), Some("/scripts/anime.sh"));
+
+        let pp = PostprocessConfig::for_transfer(&config, Some("anime"), Some("missing"));
+        assert_eq!(pp.move_completed_dir.as_deref(), Some("/media/anime"));
+    }
+
+    #[test]
+    fn test_move_target_parse() {
+        assert_eq!(
+            MoveTarget::parse("/mnt/nas/tv"),
+            MoveTarget::Local("/mnt/nas/tv".to_string())
+        );
+        assert_eq!(
+            MoveTarget::parse("rclone:nas:media/tv/"),
+            MoveTarget::Rclone("nas:media/tv".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rclone_move_verifies_size() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let remote_dir = temp_dir.path().join("remote");
+        std::fs::create_dir_all(&remote_dir).unwrap();
+
+        // Fake rclone treating "remote:" as a local directory
+        let rclone = temp_dir.path().join("rclone");
+        std::fs::write(
+            &rclone,
+            format!(
+                "#!/bin/sh\n\
+                 dest=\"{}/${{2#remote:}}\"\n\
+                 case \"$1\" in\n\
+                 copyto) cp \"$2\" \"{}/${{3#remote:}}\" ;;\n\
+                 lsjson) echo \"[{{\\\"Size\\\": $(wc -c < \"$dest\")}}]\" ;;\n\
+                 esac\n",
+                remote_dir.display(),
+                remote_dir.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&rclone, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let source = temp_dir.path().join("download.mkv");
+        std::fs::write(&source, "test content").unwrap();
+
+        let moved = rclone_move(
+            source.to_str().unwrap(),
+            "remote:",
+            rclone.to_str().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(moved, "remote:download.mkv");
+        assert!(remote_dir.join("download.mkv").exists());
+        assert!(!source.exists());
+    }
+
+    #[test]
+    fn test_render_name() {
+        assert_eq!(
+            render_name(
+                "{title} - S{season}E{episode} [{resolution}].{ext}",
+                "[Group] Show.Name.S01E02.1080p.mkv",
+                FilenamePolicy::Windows
+            ),
+            "Show Name - S01E02 [1080p].mkv"
+        );
+        assert_eq!(
+            render_name("{name}/x.{ext}", "a.b.mkv", FilenamePolicy::Windows),
+            "a.b_x.mkv"
+        );
+        assert_eq!(
+            render_name(
+                "{title}: {year}.{ext}",
+                "Movie.2020.mkv",
+                FilenamePolicy::Windows
+            ),
+            "Movie_ 2020.mkv"
+        );
+        assert_eq!(
+            render_name(
+                "{title}: {year}.{ext}",
+                "Movie.2020.mkv",
+                FilenamePolicy::Posix
+            ),
+            "Movie: 2020.mkv"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_step_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("Show.Name.S01E02.720p.mkv");
+        std::fs::write(&source, "test content").unwrap();
+        let target_dir = temp_dir.path().join("tv");
+
+        let config = PostprocessConfig {
+            // Ignored in favour of the steps
+            move_completed_dir: Some("/nonexistent".to_string()),
+            steps: vec![
+                PostprocessStep::Extract,
+                PostprocessStep::Rename {
+                    pattern: "{title} S{season}E{episode}.{ext}".to_string(),
+                },
+                PostprocessStep::Move {
+                    dir: target_dir.to_string_lossy().to_string(),
+                },
+                PostprocessStep::Script {
+                    path: "/nonexistent/script.sh".to_string(),
+                },
+                PostprocessStep::Notify { url: String::new() },
+            ],
+            ..Default::default()
+        };
+
+        let result = run_postprocess(source.to_str().unwrap(), &config).await;
+        let statuses: Vec<_> = result.steps.iter().map(|s| s.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StepStatus::Skipped,
+                StepStatus::Ok,
+                StepStatus::Ok,
+                StepStatus::Failed,
+                StepStatus::Skipped,
+            ]
+        );
+        assert!(target_dir.join("Show Name S01E02.mkv").exists());
+        assert_eq!(result.errors.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_run_postprocess_missing_file() {
         let config = PostprocessConfig::default();
@@ -0,0 +1,221 @@
+//! Quarantine Module
+//!
+//! Screens completed downloads before they are postprocessed: the checksum the
+//! bot announced, blocklisted file name patterns and an optional virus scanner.
+//! Files that fail are moved to a quarantine directory until they are released
+//! or purged.
+
+use crate::config::AppConfig;
+use crate::xdcc::PackInfo;
+use regex::RegexBuilder;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// How long the virus scanner may take per file
+const AV_SCAN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Checks applied to completed downloads
+#[derive(Debug, Clone, Default)]
+pub struct ScreenConfig {
+    pub verify_checksums: bool,
+    /// Case-insensitive regexes matched against the file name
+    pub patterns: Vec<String>,
+    /// Scanner command, run with the file path appended
+    pub av_scan_command: String,
+}
+
+impl ScreenConfig {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            verify_checksums: config.verify_checksums,
+            patterns: config.quarantine_patterns.clone(),
+            av_scan_command: config.av_scan_command.clone(),
+        }
+    }
+}
+
+/// Directory quarantined files are kept in
+pub fn quarantine_dir(config: &AppConfig) -> PathBuf {
+    if config.quarantine_dir.is_empty() {
+        Path::new(&config.download_dir).join("quarantine")
+    } else {
+        PathBuf::from(&config.quarantine_dir)
+    }
+}
+
+/// Where the file of a quarantined download is kept
+pub fn quarantined_path(config: &AppConfig, file_name: &str) -> PathBuf {
+    crate::filename::saved_path(&quarantine_dir(config), file_name)
+}
+
+/// Check a completed download, returning why it is suspicious if it is
+pub async fn screen(
+    path: &Path,
+    pack_info: Option<&PackInfo>,
+    config: &ScreenConfig,
+) -> Result<(), String> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(pattern) = matching_pattern(&name, &config.patterns) {
+        return Err(format!("File name matches blocklist pattern {}", pattern));
+    }
+    if config.verify_checksums {
+        if let Some(info) = pack_info {
+            verify_checksum(path, info).await?;
+        }
+    }
+    if !config.av_scan_command.trim().is_empty() {
+        av_scan(path, &config.av_scan_command).await?;
+    }
+    Ok(())
+}
+
+fn compile_pattern(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+/// Check that every blocklist pattern compiles, naming the first that doesn't
+pub fn validate_patterns(patterns: &[String]) -> Result<(), String> {
+    for pattern in patterns {
+        compile_pattern(pattern)
+            .map_err(|e| format!("Invalid quarantine pattern {}: {}", pattern, e))?;
+    }
+    Ok(())
+}
+
+/// First blocklist pattern matching a file name
+fn matching_pattern<'a>(name: &str, patterns: &'a [String]) -> Option<&'a str> {
+    patterns
+        .iter()
+        .find(|p| compile_pattern(p).is_ok_and(|re| re.is_match(name)))
+        .map(|p| p.as_str())
+}
+
+/// Compare the file against the CRC32 and MD5 the bot announced
+async fn verify_checksum(path: &Path, info: &PackInfo) -> Result<(), String> {
+    if info.crc32.is_none() && info.md5.is_none() {
+        return Ok(());
+    }
+    let path = path.to_path_buf();
+    let (crc32, md5) = tokio::task::spawn_blocking(move || hash_file(&path))
+        .await
+        .map_err(|e| format!("Checksum task failed: {}", e))?
+        .map_err(|e| format!("Cannot read file for checksum: {}", e))?;
+
+    if let Some(expected) = &info.crc32 {
+        if !expected.eq_ignore_ascii_case(&crc32) {
+            return Err(format!(
+                "CRC32 mismatch: bot announced {}, file has {}",
+                expected, crc32
+            ));
+        }
+    }
+    if let Some(expected) = &info.md5 {
+        if !expected.eq_ignore_ascii_case(&md5) {
+            return Err(format!(
+                "MD5 mismatch: bot announced {}, file has {}",
+                expected, md5
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// CRC32 and MD5 of a file as hex strings
+pub(crate) fn hash_file(path: &Path) -> std::io::Result<(String, String)> {
+    use md5::Digest;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut crc = crc32fast::Hasher::new();
+    let mut md5 = md5::Md5::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        crc.update(&buf[..n]);
+        md5.update(&buf[..n]);
+    }
+    let md5 = md5
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((format!("{:08X}", crc.finalize()), md5))
+}
+
+/// Run the virus scanner; any non-zero exit counts as suspicious
+async fn av_scan(path: &Path, command: &str) -> Result<(), String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let child = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to run virus scanner {}: {}", program, e))?;
+
+    let output = tokio::time::timeout(AV_SCAN_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| "Virus scan timed out".to_string())?
+        .map_err(|e| format!("Virus scan failed: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let mut report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if report.is_empty() {
+        report = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    }
+    Err(format!(
+        "Virus scan flagged the file (exit {}): {}",
+        output.status.code().unwrap_or(-1),
+        report
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_screen() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("Show.S01E01.mkv");
+        std::fs::write(&path, "123456789").unwrap();
+
+        let config = ScreenConfig {
+            verify_checksums: true,
+            patterns: vec![r"\.(exe|scr)$".to_string()],
+            av_scan_command: String::new(),
+        };
+        let mut info = PackInfo {
+            crc32: Some("cbf43926".to_string()),
+            md5: Some("25f9e794323b453885f5181f1b624d0b".to_string()),
+            ..Default::default()
+        };
+        assert!(screen(&path, Some(&info), &config).await.is_ok());
+
+        info.crc32 = Some("DEADBEEF".to_string());
+        let err = screen(&path, Some(&info), &config).await.unwrap_err();
+        assert!(err.contains("CRC32 mismatch"));
+
+        let exe = temp_dir.path().join("Show.S01E01.mkv.EXE");
+        std::fs::write(&exe, "x").unwrap();
+        let err = screen(&exe, None, &config).await.unwrap_err();
+        assert!(err.contains("blocklist"));
+
+        assert!(validate_patterns(&config.patterns).is_ok());
+        let err = validate_patterns(&[r"\.exe$".to_string(), "(unclosed".to_string()]).unwrap_err();
+        assert!(err.contains("(unclosed"));
+    }
+}
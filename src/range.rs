@@ -0,0 +1,117 @@
+//! Byte Ranges
+//!
+//! Parses the `Range` header of file downloads so interrupted downloads can
+//! resume and players can seek. Only single ranges are served; requests for
+//! several ranges get the whole file, which the HTTP spec allows.
+
+/// Range of a file to send, inclusive at both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// `Content-Range` value for a file of `total` bytes
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// Outcome of a `Range` header for a file of a known length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// No usable range: send the whole file
+    Full,
+    Partial(ByteRange),
+    /// The range lies beyond the end of the file (416)
+    Unsatisfiable,
+}
+
+/// Parse `bytes=start-end`, `bytes=start-` or `bytes=-suffix`
+pub fn parse(header: Option<&str>, len: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let parse = |s: &str| s.parse::<u64>().ok();
+
+    let range = match (start.is_empty(), end.is_empty()) {
+        // Last `end` bytes
+        (true, false) => match parse(end) {
+            Some(0) => return RangeRequest::Unsatisfiable,
+            Some(suffix) => ByteRange {
+                start: len.saturating_sub(suffix),
+                end: len.saturating_sub(1),
+            },
+            None => return RangeRequest::Full,
+        },
+        (false, _) => {
+            let Some(start) = parse(start) else {
+                return RangeRequest::Full;
+            };
+            let end = if end.is_empty() {
+                len.saturating_sub(1)
+            } else {
+                match parse(end) {
+                    Some(end) if end >= start => end.min(len.saturating_sub(1)),
+                    _ => return RangeRequest::Full,
+                }
+            };
+            ByteRange { start, end }
+        }
+        (true, true) => return RangeRequest::Full,
+    };
+    if len == 0 || range.start >= len {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(None, 1000), RangeRequest::Full);
+        assert_eq!(parse(Some("bytes=0-499"), 1000), partial(0, 499));
+        assert_eq!(parse(Some("bytes=500-"), 1000), partial(500, 999));
+        assert_eq!(parse(Some("bytes=-100"), 1000), partial(900, 999));
+        assert_eq!(parse(Some("bytes=900-5000"), 1000), partial(900, 999));
+        assert_eq!(parse(Some("bytes=-5000"), 1000), partial(0, 999));
+
+        assert_eq!(
+            parse(Some("bytes=1000-"), 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse(Some("bytes=-0"), 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse(Some("bytes=0-"), 0), RangeRequest::Unsatisfiable);
+
+        // Malformed, reversed and multiple ranges fall back to the whole file
+        assert_eq!(parse(Some("bytes=abc"), 1000), RangeRequest::Full);
+        assert_eq!(parse(Some("bytes=500-100"), 1000), RangeRequest::Full);
+        assert_eq!(parse(Some("bytes=0-1,5-6"), 1000), RangeRequest::Full);
+        assert_eq!(parse(Some("items=0-1"), 1000), RangeRequest::Full);
+
+        assert_eq!(ByteRange { start: 0, end: 499 }.length(), 500);
+        assert_eq!(
+            ByteRange { start: 0, end: 499 }.content_range(1000),
+            "bytes 0-499/1000"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Release Name Parser
+//!
+//! Extracts title, year, episode and quality details from scene/fansub style
+//! release names such as `Show.Name.S01E02.1080p.WEB.x264-GRP.mkv` or
+//! `[Group] Show Name - 17 [1080p].mkv`.
+
+use crate::library::{EpisodeNumber, NamingScheme};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+static GROUP_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\[(?P<group>[^\]]*)\]\s*").unwrap());
+static YEAR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\s._(\[-]\(?(?P<year>(?:19|20)\d{2})\)?(?:[\s._)\]-]|$)").unwrap()
+});
+static RESOLUTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?P<res>2160p|1080p|720p|576p|480p|4k)\b").unwrap());
+/// Tokens that mark the end of the title in scene names
+static QUALITY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)[\s._\[(-](?:2160p|1080p|720p|576p|480p|4k|web[\s._-]?dl|webrip|web|bluray|bdrip|brrip|hdtv|dvdrip|x264|x265|h\.?264|h\.?265|hevc|remux|complete|proper|repack)\b",
+    )
+    .unwrap()
+});
+/// Fansub style absolute episode: `Show - 17`, `Show - 017v2`
+static ABSOLUTE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s-\s(?P<ep>\d{1,4})(?:v\d)?(?:[\s._\[(]|$)").unwrap());
+static EXTENSION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\.[a-z0-9]{2,4}$").unwrap());
+
+static MUSIC_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:\.(?:mp3|flac|m4a|ogg|opus|wav|aac|ape)$|[\s._\[(-](?:flac|mp3|320kbps|v0|web-flac|discography|ost)\b)")
+        .unwrap()
+});
+
+/// Download category detected from a release name
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Tv,
+    Movie,
+    Anime,
+    Music,
+    Other,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tv => "tv",
+            Self::Movie => "movie",
+            Self::Anime => "anime",
+            Self::Music => "music",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Details parsed from a release name
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ParsedRelease {
+    /// Cleaned-up title with separators replaced by spaces
+    pub title: String,
+    pub year: Option<u32>,
+    pub episode: Option<EpisodeNumber>,
+    /// Absolute episode number for fansub style names
+    pub absolute_episode: Option<u32>,
+    /// Release group from a leading `[Group]` tag
+    pub group: Option<String>,
+    pub resolution: Option<String>,
+}
+
+/// Parse a release name
+pub fn parse_release(name: &str) -> ParsedRelease {
+    let mut parsed = ParsedRelease::default();
+
+    let name = EXTENSION_RE.replace(name.trim(), "");
+    let mut rest: &str = &name;
+    if let Some(caps) = GROUP_TAG_RE.captures(rest) {
+        parsed.group = Some(caps["group"].trim().to_string());
+        rest = &rest[caps.get(0).unwrap().end()..];
+    }
+
+    parsed.resolution = RESOLUTION_RE
+        .captures(rest)
+        .map(|c| c["res"].to_lowercase());
+
+    // The title ends at the earliest episode, year, absolute number or quality marker
+    let mut title_end = rest.len();
+    let scheme = NamingScheme::default();
+    if let Some(ep) = scheme.parse(rest) {
+        parsed.episode = Some(ep);
+        if let Some(title) = scheme.title(rest) {
+            title_end = title_end.min(title.len());
+        }
+    }
+    // A leading year has no separator before it and stays part of the title
+    // (e.g. `2001.A.Space.Odyssey.1968`)
+    if let Some(caps) = YEAR_RE.captures(rest) {
+        parsed.year = caps["year"].parse().ok();
+        title_end = title_end.min(caps.get(0).unwrap().start());
+    }
+    if parsed.episode.is_none() {
+        if let Some(caps) = ABSOLUTE_RE.captures(rest) {
+            parsed.absolute_episode = caps["ep"].parse().ok();
+            title_end = title_end.min(caps.get(0).unwrap().start());
+        }
+    }
+    if let Some(m) = QUALITY_RE.find(rest) {
+        title_end = title_end.min(m.start());
+    }
+
+    parsed.title = rest[..title_end]
+        .split(|c: char| c == '.' || c == '_' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches([' ', '-'])
+        .to_string();
+    parsed
+}
+
+/// Classify a release name into a download category
+pub fn classify(name: &str) -> Category {
+    if MUSIC_RE.is_match(name) {
+        return Category::Music;
+    }
+
+    let parsed = parse_release(name);
+    // Fansub releases: `[Group] Title - 17` or `[Group] Title S01E02 [1080p]`
+    if parsed.absolute_episode.is_some()
+        || (parsed.group.is_some() && parsed.episode.is_some() && !name.contains('.'))
+    {
+        return Category::Anime;
+    }
+    if parsed.episode.is_some() || name.to_lowercase().contains("complete.season") {
+        return Category::Tv;
+    }
+    if parsed.year.is_some() && (parsed.resolution.is_some() || QUALITY_RE.is_match(name)) {
+        return Category::Movie;
+    }
+    Category::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scene_tv() {
+        let p = parse_release("Show.Name.S01E02.1080p.WEB.x264-GRP.mkv");
+        assert_eq!(p.title, "Show Name");
+        assert_eq!(
+            p.episode,
+            Some(EpisodeNumber {
+                season: 1,
+                episode: 2
+            })
+        );
+        assert_eq!(p.resolution.as_deref(), Some("1080p"));
+    }
+
+    #[test]
+    fn test_parse_movie_and_fansub() {
+        let p = parse_release("Movie.Title.2019.720p.BluRay.x264-GRP.mkv");
+        assert_eq!(p.title, "Movie Title");
+        assert_eq!(p.year, Some(2019));
+
+        let p = parse_release("[SubGroup] Some Anime - 17 [1080p].mkv");
+        assert_eq!(p.title, "Some Anime");
+        assert_eq!(p.group.as_deref(), Some("SubGroup"));
+        assert_eq!(p.absolute_episode, Some(17));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("Show.Name.S01E02.1080p.WEB.x264-GRP.mkv"),
+            Category::Tv
+        );
+        assert_eq!(
+            classify("Movie.Title.2019.720p.BluRay.x264-GRP.mkv"),
+            Category::Movie
+        );
+        assert_eq!(
+            classify("[SubGroup] Some Anime - 17 [1080p].mkv"),
+            Category::Anime
+        );
+        assert_eq!(classify("Artist-Album-WEB-FLAC-2020-GRP"), Category::Music);
+        assert_eq!(classify("random_archive.zip"), Category::Other);
+    }
+}
@@ -0,0 +1,106 @@
+//! Request IDs
+//!
+//! Every response carries an `X-Request-Id` header, and everything logged
+//! while handling the request is inside a `request{id=...}` span, so a
+//! response can be matched with its log lines. A client may send its own
+//! `X-Request-Id` to be reused. Responses about a transfer also carry
+//! `X-Transfer-Id`, the id the download task logs under (`transfer{id=...}`).
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+pub const TRANSFER_ID: HeaderName = HeaderName::from_static("x-transfer-id");
+
+/// Id of the request being handled, available to handlers as an extension
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// A client-supplied id, if it is short and plain enough to log as is
+fn client_id(req: &Request) -> Option<String> {
+    let id = req.headers().get(&REQUEST_ID)?.to_str().ok()?;
+    (!id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    .then(|| id.to_string())
+}
+
+/// Transfer a path refers to, e.g. `/api/transfers/<id>/logs`. History
+/// entries keep the id of their transfer.
+fn path_transfer(path: &str) -> Option<&str> {
+    let mut segments = path.trim_matches('/').split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("api"), Some("transfers"), Some(id)) if !id.is_empty() => Some(id),
+        (Some("api"), Some("history"), Some(id))
+            if !id.is_empty() && !matches!(id, "bulk" | "trash") =>
+        {
+            Some(id)
+        }
+        _ => None,
+    }
+}
+
+/// Middleware tagging requests and their log lines with an id
+pub async fn tag(mut req: Request, next: Next) -> Response {
+    let id = client_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(id.clone()));
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let span = tracing::info_span!("request", id = %id);
+    let started = std::time::Instant::now();
+
+    let mut response = next.run(req).instrument(span.clone()).await;
+
+    if path.starts_with("/api/") || path.starts_with("/torznab/") {
+        span.in_scope(|| {
+            tracing::debug!(
+                "{} {} -> {} in {}ms",
+                method,
+                path,
+                response.status().as_u16(),
+                started.elapsed().as_millis()
+            )
+        });
+    }
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        headers.insert(REQUEST_ID, value);
+    }
+    if !headers.contains_key(TRANSFER_ID) {
+        if let Some(value) = path_transfer(&path).and_then(|t| HeaderValue::from_str(t).ok()) {
+            headers.insert(TRANSFER_ID, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids() {
+        let req = |id: &str| {
+            Request::builder()
+                .header("X-Request-Id", id)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        assert_eq!(client_id(&req("sonarr-42")), Some("sonarr-42".to_string()));
+        assert_eq!(client_id(&req("two words")), None);
+        assert_eq!(client_id(&req(&"a".repeat(65))), None);
+
+        assert_eq!(path_transfer("/api/transfers/abc-1"), Some("abc-1"));
+        assert_eq!(path_transfer("/api/transfers/abc-1/logs"), Some("abc-1"));
+        assert_eq!(path_transfer("/api/transfers"), None);
+        assert_eq!(path_transfer("/api/history/abc-1/restore"), Some("abc-1"));
+        assert_eq!(path_transfer("/api/history/trash"), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! SABnzbd Emulation
+//!
+//! Sonarr and Radarr can use Botarr as a SABnzbd download client. The
+//! Newznab feed links to small NZB documents that carry the XDCC pack in a
+//! `<meta>` tag; the *arr uploads them to `/sabnzbd/api?mode=addfile`, which
+//! queues the pack. The queue and history modes report transfers in the
+//! shape SABnzbd uses, so the *arr can follow and import them.
+
+use crate::torznab::escape;
+use crate::xdcc::{TransferPriority, TransferStatus};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// SABnzbd version reported to clients; the *arrs refuse very old ones
+pub const VERSION: &str = "4.3.0";
+
+/// `<meta>` type carrying the `irc://` URL of the pack
+const META_XDCC: &str = "x-botarr-xdcc";
+/// `<meta>` type carrying the advertised size in bytes
+const META_SIZE: &str = "x-botarr-size";
+
+static META: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<meta\s+type="([^"]+)"\s*>([^<]*)</meta>"#).unwrap());
+
+/// Pack described by an NZB document
+#[derive(Debug, Clone, PartialEq)]
+pub struct NzbRelease {
+    pub url: String,
+    pub name: String,
+    pub size: Option<u64>,
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// NZB document for a pack. It lists a single placeholder file, since the
+/// *arrs reject NZBs without files; the data comes over XDCC.
+pub fn nzb_document(name: &str, url: &str, size: Option<u64>, category: &str) -> String {
+    let size = size.unwrap_or(0);
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE nzb PUBLIC "-//newzBin//DTD NZB 1.1//EN" "http://www.newzbin.com/DTD/nzb/nzb-1.1.dtd">
+<nzb xmlns="http://www.newzbin.com/DTD/2003/nzb">
+  <head>
+    <meta type="name">{name}</meta>
+    <meta type="category">{category}</meta>
+    <meta type="{META_XDCC}">{url}</meta>
+    <meta type="{META_SIZE}">{size}</meta>
+  </head>
+  <file poster="Botarr" date="0" subject="&quot;{name}&quot; yEnc (1/1)">
+    <groups><group>alt.binaries.botarr</group></groups>
+    <segments><segment bytes="{size}" number="1">{segment}@botarr</segment></segments>
+  </file>
+</nzb>
+"#,
+        name = escape(name),
+        category = escape(category),
+        url = escape(url),
+        segment = uuid::Uuid::new_v4().simple(),
+    )
+}
+
+/// Pack of an NZB made by [`nzb_document`]. `body` may be the NZB itself or a
+/// multipart upload containing it.
+pub fn parse_nzb(body: &str) -> Option<NzbRelease> {
+    let meta = |kind: &str| {
+        META.captures_iter(body)
+            .find(|c| &c[1] == kind)
+            .map(|c| unescape(c[2].trim()))
+    };
+    let url = meta(META_XDCC)?;
+    Some(NzbRelease {
+        name: meta("name").unwrap_or_else(|| url.clone()),
+        size: meta(META_SIZE)
+            .and_then(|s| s.parse().ok())
+            .filter(|s| *s > 0),
+        url,
+    })
+}
+
+/// Transfer priority for a SABnzbd priority (-100 default, -1 low, 0 normal,
+/// 1 high, 2 force)
+pub fn priority(value: Option<i32>) -> TransferPriority {
+    match value {
+        Some(-1) => TransferPriority::Low,
+        Some(1) => TransferPriority::High,
+        Some(2) => TransferPriority::Urgent,
+        _ => TransferPriority::Normal,
+    }
+}
+
+/// SABnzbd name of a transfer priority
+pub fn priority_name(priority: TransferPriority) -> &'static str {
+    match priority {
+        TransferPriority::Low => "Low",
+        TransferPriority::Normal => "Normal",
+        TransferPriority::High => "High",
+        TransferPriority::Urgent => "Force",
+    }
+}
+
+/// SABnzbd queue status of a transfer that hasn't finished
+pub fn queue_status(status: &TransferStatus) -> &'static str {
+    match status {
+        TransferStatus::Paused => "Paused",
+        TransferStatus::Pending => "Queued",
+        TransferStatus::Connecting | TransferStatus::Joining | TransferStatus::Requesting => {
+            "Fetching"
+        }
+        _ => "Downloading",
+    }
+}
+
+/// Time left as SABnzbd formats it, `H:MM:SS`
+pub fn timeleft(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nzb_round_trip() {
+        let url = "irc://irc.rizon.net/#news/Bot/7";
+        let nzb = nzb_document("Show & Tell S01E02.mkv", url, Some(1024), "tv");
+        assert!(nzb.contains("<file "));
+        let expected = NzbRelease {
+            url: url.to_string(),
+            name: "Show & Tell S01E02.mkv".to_string(),
+            size: Some(1024),
+        };
+        assert_eq!(parse_nzb(&nzb), Some(expected.clone()));
+
+        // As uploaded by the *arr
+        let multipart = format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"name\"; filename=\"a.nzb\"\r\n\
+             Content-Type: application/x-nzb\r\n\r\n{}\r\n--boundary--\r\n",
+            nzb
+        );
+        assert_eq!(parse_nzb(&multipart), Some(expected));
+        assert_eq!(parse_nzb("<nzb><head></head></nzb>"), None);
+
+        assert_eq!(priority(Some(-100)), TransferPriority::Normal);
+        assert_eq!(priority(Some(2)), TransferPriority::Urgent);
+        assert_eq!(timeleft(3725), "1:02:05");
+    }
+}
@@ -0,0 +1,136 @@
+//! Search Result Cache
+//!
+//! `/api/search` pages are cut from one list: the results of a search are
+//! kept for a few minutes, so asking for page 2 doesn't search every provider
+//! again (and get a list that has shifted in between).
+
+use crate::xdcc::query::SearchFilter;
+use crate::xdcc::XdccSearchResult;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the results of a search are kept for its later pages
+const TTL: Duration = Duration::from_secs(5 * 60);
+/// Searches kept at once; the oldest is dropped first
+const MAX_ENTRIES: usize = 32;
+
+/// Results of a search and when it ran
+type Entry = (Instant, Vec<XdccSearchResult>);
+
+/// Results of recent searches by request
+#[derive(Clone, Default)]
+pub struct SearchCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// Cache key of a search: the query sent to providers, the providers asked
+/// and everything that filters or orders the results. Spelling differences
+/// that don't change the results (case, term order, provider order) share
+/// a key.
+pub fn key(
+    query: &str,
+    providers: Option<&[String]>,
+    filter: &SearchFilter,
+    sort: Option<&str>,
+    max_age_days: Option<u64>,
+) -> String {
+    let sorted = |items: &mut dyn Iterator<Item = String>| {
+        let mut items: Vec<_> = items.collect();
+        items.sort();
+        items.dedup();
+        items
+    };
+    let providers = providers.map(|p| sorted(&mut p.iter().map(|s| s.to_lowercase())));
+    serde_json::json!([
+        query
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase(),
+        providers,
+        sorted(&mut filter.include.iter().cloned()),
+        sorted(&mut filter.exclude.iter().cloned()),
+        filter.regex.as_ref().map(|r| r.as_str()),
+        sort,
+        max_age_days,
+    ])
+    .to_string()
+}
+
+impl SearchCache {
+    pub fn get(&self, key: &str) -> Option<Vec<XdccSearchResult>> {
+        self.get_at(key, Instant::now())
+    }
+
+    pub fn insert(&self, key: String, results: &[XdccSearchResult]) {
+        self.insert_at(key, results, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<Vec<XdccSearchResult>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|(at, _)| now.duration_since(*at) < TTL)
+            .map(|(_, results)| results.clone())
+    }
+
+    fn insert_at(&self, key: String, results: &[XdccSearchResult], now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (at, _)| now.duration_since(*at) < TTL);
+        while entries.len() >= MAX_ENTRIES {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(key, (now, results.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_cache() {
+        let cache = SearchCache::default();
+        let now = Instant::now();
+        assert!(cache.get_at("show", now).is_none());
+
+        cache.insert_at("show".to_string(), &[], now);
+        assert!(cache.get_at("show", now + TTL / 2).is_some());
+        assert!(cache.get_at("show", now + TTL).is_none());
+
+        for i in 0..MAX_ENTRIES + 1 {
+            cache.insert_at(i.to_string(), &[], now + Duration::from_secs(i as u64));
+        }
+        assert!(cache.get_at("0", now).is_none());
+        assert!(cache.get_at(&MAX_ENTRIES.to_string(), now).is_some());
+    }
+
+    #[test]
+    fn test_key() {
+        let (query, filter) = SearchFilter::parse_query("One  Piece -raw +1080p -cam");
+        let (other_query, other_filter) = SearchFilter::parse_query("one piece +1080P -CAM -raw");
+        let providers = ["nibl".to_string(), "Sunxdcc".to_string()];
+        let reordered = ["sunxdcc".to_string(), "nibl".to_string()];
+        assert_eq!(
+            key(&query, Some(&providers), &filter, None, None),
+            key(&other_query, Some(&reordered), &other_filter, None, None)
+        );
+
+        let base = key(&query, None, &filter, None, None);
+        assert_ne!(base, key(&query, Some(&providers), &filter, None, None));
+        assert_ne!(base, key(&query, None, &filter, Some("freshness"), None));
+        assert_ne!(base, key(&query, None, &filter, None, Some(7)));
+        assert_ne!(base, key("one piece", None, &filter, None, None));
+        let mut with_regex = filter.clone();
+        with_regex.set_regex("S01").unwrap();
+        assert_ne!(base, key(&query, None, &with_regex, None, None));
+    }
+}
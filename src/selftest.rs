@@ -0,0 +1,479 @@
+//! Self-Test Module
+//!
+//! Checks everything a download depends on (database, download directory, disk
+//! space, search providers, proxy and optionally an IRC network) and reports
+//! pass/fail for each, to make first-install problems easy to pin down. An
+//! optional speed test downloads a known pack to measure real throughput.
+
+use crate::config::AppConfig;
+use crate::db::Database;
+use crate::xdcc::{SearchAggregator, XdccClient, XdccConfig, XdccEvent, XdccUrl};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// Warn when the download directory has less free space than this
+const MIN_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+/// How long to wait for a network to accept the registration
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Set while a speed test runs; they share a nick and a scratch directory
+static SPEED_TEST_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Claim on running the speed test, released when dropped
+struct SpeedTestGuard;
+
+impl SpeedTestGuard {
+    fn acquire() -> Option<Self> {
+        SPEED_TEST_RUNNING
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for SpeedTestGuard {
+    fn drop(&mut self) {
+        SPEED_TEST_RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, result: Result<String, String>) -> Self {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        Self {
+            name: name.into(),
+            passed,
+            detail,
+        }
+    }
+}
+
+/// Measurements of a speed test download
+#[derive(Debug, Clone, Serialize)]
+pub struct SpeedTestResult {
+    pub url: String,
+    /// Seconds until the IRC server accepted the connection
+    pub connect_secs: f64,
+    /// Seconds from the pack request to the bot's DCC offer
+    pub ttfb_secs: Option<f64>,
+    /// Bytes received
+    pub bytes: u64,
+    /// Average bytes per second since the offer
+    pub throughput: f64,
+    /// The whole pack arrived before the time limit
+    pub completed: bool,
+}
+
+impl SpeedTestResult {
+    fn summary(&self) -> String {
+        format!(
+            "{:.2} MB/s over {:.1} MB{}; connect {:.1}s, offer after {}",
+            self.throughput / (1024.0 * 1024.0),
+            self.bytes as f64 / (1024.0 * 1024.0),
+            if self.completed { "" } else { " (stopped)" },
+            self.connect_secs,
+            self.ttfb_secs
+                .map_or("-".to_string(), |s| format!("{:.1}s", s))
+        )
+    }
+}
+
+/// Results of all checks
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<CheckResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speedtest: Option<SpeedTestResult>,
+}
+
+impl SelfTestReport {
+    /// Human readable report, one line per check
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for check in &self.checks {
+            let mark = if check.passed { "PASS" } else { "FAIL" };
+            out.push_str(&format!("[{}] {}: {}\n", mark, check.name, check.detail));
+        }
+        out.push_str(if self.passed {
+            "All checks passed\n"
+        } else {
+            "Some checks failed\n"
+        });
+        out
+    }
+}
+
+/// Run all checks. `network` additionally connects to and registers on a
+/// configured network, and `speedtest` downloads `speedtest_url`.
+pub async fn run(
+    config: &AppConfig,
+    database: &Database,
+    search: &SearchAggregator,
+    network: Option<&str>,
+    speedtest: bool,
+) -> SelfTestReport {
+    let mut checks = vec![
+        CheckResult::new(
+            "database",
+            database
+                .check_writable()
+                .map(|_| "Writable".to_string())
+                .map_err(|e| format!("Not writable: {}", e)),
+        ),
+        CheckResult::new(
+            "download_dir",
+            check_download_dir(Path::new(&config.download_dir)).await,
+        ),
+        CheckResult::new(
+            "free_space",
+            check_free_space(Path::new(&config.download_dir)),
+        ),
+    ];
+
+    for (name, result) in search.ping_providers().await {
+        checks.push(CheckResult::new(
+            format!("provider:{}", name),
+            result
+                .map(|_| "Reachable".to_string())
+                .map_err(|e| e.to_string()),
+        ));
+    }
+
+    if config.proxy_enabled || config.require_proxy {
+        checks.push(CheckResult::new("proxy", check_proxy(config).await));
+    }
+
+    if let Some(network) = network {
+        checks.push(CheckResult::new(
+            format!("network:{}", network),
+            check_network(config, network).await,
+        ));
+    }
+
+    let mut speed = None;
+    if speedtest {
+        let result = speed_test(config).await;
+        checks.push(CheckResult::new(
+            "speedtest",
+            result
+                .as_ref()
+                .map(SpeedTestResult::summary)
+                .map_err(Clone::clone),
+        ));
+        speed = result.ok();
+    }
+
+    SelfTestReport {
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+        speedtest: speed,
+    }
+}
+
+/// Download `speedtest_url` into a scratch directory for up to
+/// `speedtest_max_secs`, timing the connection, the bot's answer and the
+/// transfer, then delete what was received. One speed test runs at a time.
+async fn speed_test(config: &AppConfig) -> Result<SpeedTestResult, String> {
+    if config.speedtest_url.is_empty() {
+        return Err("No speedtest_url configured".to_string());
+    }
+    let _running = SpeedTestGuard::acquire().ok_or("A speed test is already running")?;
+    let url = XdccUrl::parse(&config.speedtest_url).map_err(|e| e.to_string())?;
+    let dir = Path::new(&config.download_dir).join(".botarr-speedtest");
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+
+    let client = XdccClient::new(XdccConfig {
+        nickname: format!("{}_st", config.nickname),
+        resume_enabled: false,
+        info_check: false,
+        manual_offer_approval: false,
+        ..XdccConfig::from_app_config(config, dir.to_string_lossy().to_string(), &url.bot)
+    });
+    let result = measure(&client, url, config).await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    result
+}
+
+async fn measure(
+    client: &XdccClient,
+    url: XdccUrl,
+    config: &AppConfig,
+) -> Result<SpeedTestResult, String> {
+    let source = url.to_string();
+    let start = Instant::now();
+    let mut rx = client
+        .start_download(url)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Generous limit for getting to the offer, then the download time
+    let setup_limit = Duration::from_secs(config.connect_timeout + config.general_timeout);
+    let transfer_limit = Duration::from_secs(config.speedtest_max_secs.max(1));
+    let mut connected = None;
+    let mut requested = None;
+    let mut offered: Option<Instant> = None;
+    let mut bytes = 0;
+    let mut completed = false;
+
+    loop {
+        let deadline = match offered {
+            Some(at) => at + transfer_limit,
+            None => start + setup_limit,
+        };
+        let event = match tokio::time::timeout_at(deadline.into(), rx.recv()).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(_) if offered.is_some() => break,
+            Err(_) => return Err("No DCC offer from the bot in time".to_string()),
+        };
+        match event {
+            XdccEvent::Connected => connected = Some(start.elapsed()),
+            XdccEvent::Requesting(..) => requested = Some(Instant::now()),
+            XdccEvent::DccSend { .. } => offered = Some(Instant::now()),
+            XdccEvent::Progress { downloaded, .. } => bytes = downloaded,
+            XdccEvent::Completed => {
+                completed = true;
+                break;
+            }
+            XdccEvent::Error(e) if bytes == 0 => return Err(e.to_string()),
+            XdccEvent::Error(_) => break,
+            _ => {}
+        }
+    }
+    // Dropping the receiver stops a transfer still running
+    drop(rx);
+
+    let offered = offered.ok_or("The bot never offered the pack")?;
+    let elapsed = offered.elapsed().as_secs_f64();
+
+    Ok(SpeedTestResult {
+        url: source,
+        connect_secs: connected.unwrap_or_default().as_secs_f64(),
+        ttfb_secs: requested.map(|r| offered.duration_since(r).as_secs_f64()),
+        bytes,
+        throughput: if elapsed > 0.0 {
+            bytes as f64 / elapsed
+        } else {
+            0.0
+        },
+        completed,
+    })
+}
+
+/// Create the download directory if needed and write a file to it
+async fn check_download_dir(dir: &Path) -> Result<String, String> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let probe = dir.join(".botarr-selftest");
+    tokio::fs::write(&probe, b"selftest")
+        .await
+        .map_err(|e| format!("Cannot write to {}: {}", dir.display(), e))?;
+    let _ = tokio::fs::remove_file(&probe).await;
+    Ok(format!("{} is writable", dir.display()))
+}
+
+/// Bytes available to unprivileged users on the filesystem of `dir`
+#[cfg(unix)]
+pub fn free_space(dir: &Path) -> Result<u64, String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+        .map_err(|_| "Invalid download directory path".to_string())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` a writable statvfs struct
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(format!(
+            "Cannot stat {}: {}",
+            dir.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_dir: &Path) -> Result<u64, String> {
+    Err("Not checked on this platform".to_string())
+}
+
+#[cfg(unix)]
+fn check_free_space(dir: &Path) -> Result<String, String> {
+    let free = free_space(dir)?;
+    let free_gb = free as f64 / (1024.0 * 1024.0 * 1024.0);
+    if free < MIN_FREE_BYTES {
+        Err(format!("Only {:.2} GB free", free_gb))
+    } else {
+        Ok(format!("{:.1} GB free", free_gb))
+    }
+}
+
+#[cfg(not(unix))]
+fn check_free_space(_dir: &Path) -> Result<String, String> {
+    Ok("Not checked on this platform".to_string())
+}
+
+/// Open a TCP connection to the SOCKS5 proxy
+async fn check_proxy(config: &AppConfig) -> Result<String, String> {
+    if !config.proxy_enabled || config.proxy_url.is_empty() {
+        return Err("Proxy is required but not enabled".to_string());
+    }
+    let proxy = config.proxy_url.trim_start_matches("socks5://");
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        crate::net::connect_tcp(proxy, &config.outbound_bind),
+    )
+    .await
+    .map_err(|_| format!("Timed out connecting to {}", proxy))?
+    .map_err(|e| format!("Cannot connect to {}: {}", proxy, e))?;
+    Ok(format!("{} accepts connections", proxy))
+}
+
+/// Connect to a network the way downloads do and wait for the welcome message
+async fn check_network(config: &AppConfig, network: &str) -> Result<String, String> {
+    let (host, port, ssl, _, _) = config
+        .resolve_network(network)
+        .ok_or_else(|| format!("Network {} is not configured", network))?;
+    let server = format!("{}:{}", host, port);
+    let outbound = crate::net::Outbound::from_config(config);
+
+    let stream = tokio::time::timeout(
+        Duration::from_secs(config.connect_timeout.max(1)),
+        outbound.connect(&server, true),
+    )
+    .await
+    .map_err(|_| format!("Timed out connecting to {}", server))?
+    .map_err(|e| format!("Cannot connect to {}: {}", server, e))?;
+
+    let nick = format!("{}_st", config.nickname);
+    let result = if ssl {
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| format!("TLS setup failed: {}", e))?;
+        let tls = tokio_native_tls::TlsConnector::from(connector)
+            .connect(&host, stream)
+            .await
+            .map_err(|e| format!("TLS handshake with {} failed: {}", server, e))?;
+        register(tls, &nick, &config.username).await
+    } else {
+        register(stream, &nick, &config.username).await
+    };
+    result.map(|_| format!("Registered on {} as {}", server, nick))
+}
+
+/// Register on an IRC connection and QUIT once the server welcomes us
+async fn register<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    nick: &str,
+    user: &str,
+) -> Result<(), String> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    writer
+        .write_all(format!("NICK {}\r\nUSER {} 0 * :Botarr self-test\r\n", nick, user).as_bytes())
+        .await
+        .map_err(|e| format!("Write failed: {}", e))?;
+
+    let welcome = async {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return Err("Server closed the connection".to_string());
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.first() == Some(&"PING") {
+                let pong = line.trim_end().replacen("PING", "PONG", 1);
+                let _ = writer.write_all(format!("{}\r\n", pong).as_bytes()).await;
+                continue;
+            }
+            // ERROR closes the link; 465 means we are banned
+            if parts.first() == Some(&"ERROR") || parts.get(1) == Some(&"465") {
+                return Err(line.trim().to_string());
+            }
+            if parts.get(1) == Some(&"001") {
+                return Ok(());
+            }
+        }
+    };
+
+    let result = tokio::time::timeout(REGISTRATION_TIMEOUT, welcome)
+        .await
+        .map_err(|_| "No welcome from the server in time".to_string())?;
+    let _ = writer.write_all(b"QUIT :Self-test done\r\n").await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_register_waits_for_welcome() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 256];
+            let _ = tokio::io::AsyncReadExt::read(&mut server, &mut buf).await;
+            server
+                .write_all(b"PING :abc\r\n:irc.example.net 001 Botarr_st :Welcome\r\n")
+                .await
+                .unwrap();
+            let _ = tokio::io::AsyncReadExt::read(&mut server, &mut buf).await;
+        });
+        assert!(register(client, "Botarr_st", "botarr").await.is_ok());
+
+        let (client, mut server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            server
+                .write_all(b"ERROR :Closing Link: (K-Lined)\r\n")
+                .await
+                .unwrap();
+        });
+        let err = register(client, "Botarr_st", "botarr").await.unwrap_err();
+        assert!(err.contains("K-Lined"));
+    }
+
+    #[tokio::test]
+    async fn test_speed_test_needs_url() {
+        let mut config = AppConfig::default();
+        assert!(speed_test(&config).await.is_err());
+
+        config.speedtest_url = "irc://irc.rizon.net/chan/Bot/1".to_string();
+        let running = SpeedTestGuard::acquire().unwrap();
+        let err = speed_test(&config).await.unwrap_err();
+        assert!(err.contains("already running"));
+        drop(running);
+        assert!(SpeedTestGuard::acquire().is_some());
+
+        let result = SpeedTestResult {
+            url: "irc://irc.rizon.net/chan/Bot/1".to_string(),
+            connect_secs: 0.8,
+            ttfb_secs: Some(2.14),
+            bytes: 50 * 1024 * 1024,
+            throughput: 2.5 * 1024.0 * 1024.0,
+            completed: false,
+        };
+        assert_eq!(
+            result.summary(),
+            "2.50 MB/s over 50.0 MB (stopped); connect 0.8s, offer after 2.1s"
+        );
+    }
+}
@@ -0,0 +1,549 @@
+//! Simulation Mode
+//!
+//! A mock IRC server with one XDCC bot, run inside the process. With
+//! `BOTARR_SIMULATE=1` every network resolves to it and search only lists
+//! its packs, so the UI can be tried out without touching real networks.
+//! Tests use it to run downloads end to end: resume, retries after a cut
+//! off transfer, queue limits and cancellation.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Network name the simulated packs are listed under
+pub const NETWORK: &str = "simnet";
+/// Channel the bot sits in
+pub const CHANNEL: &str = "#botarr";
+/// Nick of the simulated bot
+pub const BOT: &str = "SimBot";
+
+const SERVER_NAME: &str = "sim.botarr";
+/// How long an offered DCC port waits for the client
+const DCC_ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Address of the mock server downloads go to, once simulation mode is on
+static SERVER: OnceLock<SocketAddr> = OnceLock::new();
+
+/// Whether `BOTARR_SIMULATE` asks for simulation mode
+pub fn requested() -> bool {
+    std::env::var("BOTARR_SIMULATE").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// Send every download to `addr` from now on
+pub fn install(addr: SocketAddr) {
+    let _ = SERVER.set(addr);
+}
+
+/// Mock server downloads go to, in simulation mode
+pub fn server() -> Option<SocketAddr> {
+    SERVER.get().copied()
+}
+
+/// Pack the simulated bot offers
+#[derive(Debug, Clone)]
+pub struct MockPack {
+    pub name: String,
+    pub size: u64,
+}
+
+impl MockPack {
+    fn new(name: &str, size: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+        }
+    }
+}
+
+/// Byte at `offset` of every simulated file, so resumed files can be checked
+pub fn pack_byte(offset: u64) -> u8 {
+    (offset % 251) as u8
+}
+
+#[derive(Debug, Clone)]
+pub struct MockOptions {
+    /// Packs by slot, starting at #1
+    pub packs: Vec<MockPack>,
+    /// DCC speed in bytes per second, 0 for as fast as possible
+    pub speed: u64,
+    /// Cut off this many DCC transfers halfway through
+    pub interrupt: u32,
+}
+
+impl Default for MockOptions {
+    /// Packs of a few open movies at 2 MiB/s, slow enough to follow in the UI
+    fn default() -> Self {
+        const MIB: u64 = 1024 * 1024;
+        Self {
+            packs: vec![
+                MockPack::new("Big.Buck.Bunny.2008.1080p.BluRay.x264-SIM.mkv", 180 * MIB),
+                MockPack::new("Sintel.2010.720p.WEB-DL.x264-SIM.mkv", 90 * MIB),
+                MockPack::new("Tears.of.Steel.2012.2160p.WEB-DL.x265-SIM.mkv", 240 * MIB),
+                MockPack::new("Elephants.Dream.2006.480p.x264-SIM.mkv", 40 * MIB),
+                MockPack::new("Cosmos.Laundromat.S01E01.1080p.WEB.h264-SIM.mkv", 60 * MIB),
+                MockPack::new("Open.Movie.Soundtracks.2015.FLAC-SIM.zip", 25 * MIB),
+            ],
+            speed: 2 * MIB,
+            interrupt: 0,
+        }
+    }
+}
+
+struct MockState {
+    options: MockOptions,
+    interrupt: AtomicU32,
+    sends: AtomicU32,
+    resumes: AtomicU32,
+    active: AtomicU32,
+}
+
+/// Running mock IRC server
+#[derive(Clone)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+}
+
+impl MockServer {
+    /// Listen on a free local port and serve clients until the process ends
+    pub async fn start(options: MockOptions) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let server = Self {
+            addr: listener.local_addr()?,
+            state: Arc::new(MockState {
+                interrupt: AtomicU32::new(options.interrupt),
+                options,
+                sends: AtomicU32::new(0),
+                resumes: AtomicU32::new(0),
+                active: AtomicU32::new(0),
+            }),
+        };
+        let state = server.state.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = irc_session(socket, state).await {
+                        tracing::debug!("Simulated IRC session ended: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn packs(&self) -> &[MockPack] {
+        &self.state.options.packs
+    }
+}
+
+/// What the server did, for tests to check
+#[cfg(test)]
+impl MockServer {
+    /// DCC transfers started
+    pub fn sends(&self) -> u32 {
+        self.state.sends.load(Ordering::SeqCst)
+    }
+
+    /// DCC RESUME requests accepted
+    pub fn resumes(&self) -> u32 {
+        self.state.resumes.load(Ordering::SeqCst)
+    }
+
+    /// DCC transfers running right now
+    pub fn active(&self) -> u32 {
+        self.state.active.load(Ordering::SeqCst)
+    }
+}
+
+/// Serve one IRC client: registration, joins, and the bot's XDCC commands
+async fn irc_session(socket: TcpStream, state: Arc<MockState>) -> std::io::Result<()> {
+    let (reader, mut writer) = socket.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    // DCC transfers write replies through the session too
+    let (tx, mut rx) = mpsc::channel::<String>(16);
+    let mut nick = String::from("*");
+    // Resume offset of each offered DCC port
+    let mut offers: HashMap<u16, Arc<AtomicU64>> = HashMap::new();
+
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => line,
+                None => return Ok(()),
+            },
+            Some(reply) = rx.recv() => {
+                writer.write_all(reply.as_bytes()).await?;
+                continue;
+            }
+        };
+        let (command, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        let mut reply = String::new();
+        match command.to_ascii_uppercase().as_str() {
+            "NICK" => nick = rest.trim_start_matches(':').to_string(),
+            "USER" => {
+                reply = format!(
+                    ":{SERVER_NAME} 001 {nick} :Welcome to the Botarr simulation {nick}\r\n"
+                )
+            }
+            "PING" => reply = format!(":{SERVER_NAME} PONG {rest}\r\n"),
+            "JOIN" => {
+                for channel in rest.trim_start_matches(':').split(',') {
+                    reply.push_str(&format!(
+                        ":{nick}!sim@{SERVER_NAME} JOIN :{channel}\r\n\
+                         :{SERVER_NAME} 353 {nick} = {channel} :{nick} @{BOT}\r\n\
+                         :{SERVER_NAME} 366 {nick} {channel} :End of /NAMES list.\r\n"
+                    ));
+                }
+            }
+            "PRIVMSG" => {
+                let (target, text) = rest.split_once(" :").unwrap_or((rest, ""));
+                if !target.eq_ignore_ascii_case(BOT) {
+                    if !target.starts_with('#') {
+                        reply =
+                            format!(":{SERVER_NAME} 401 {nick} {target} :No such nick/channel\r\n");
+                    }
+                } else if let Some(resume) = text.strip_prefix("\x01DCC RESUME ") {
+                    reply = accept_resume(resume.trim_end_matches('\x01'), &nick, &offers, &state);
+                } else if let Some(slot) = xdcc_slot(text) {
+                    match state.options.packs.get(slot.wrapping_sub(1)) {
+                        Some(pack) => {
+                            let offset = Arc::new(AtomicU64::new(0));
+                            let port = offer_dcc(pack.clone(), offset.clone(), state.clone(), tx.clone()).await?;
+                            offers.insert(port, offset);
+                            reply = format!(
+                                ":{BOT}!bot@{SERVER_NAME} PRIVMSG {nick} :\x01DCC SEND \"{}\" {} {} {}\x01\r\n",
+                                pack.name,
+                                u32::from(Ipv4Addr::LOCALHOST),
+                                port,
+                                pack.size
+                            );
+                        }
+                        None => reply = format!(
+                            ":{BOT}!bot@{SERVER_NAME} NOTICE {nick} :Invalid Pack Number, Try Again\r\n"
+                        ),
+                    }
+                }
+            }
+            "QUIT" => return Ok(()),
+            _ => {}
+        }
+        if !reply.is_empty() {
+            writer.write_all(reply.as_bytes()).await?;
+        }
+    }
+}
+
+/// Slot of an `xdcc send #N` (or `xdcc get`) request
+fn xdcc_slot(text: &str) -> Option<usize> {
+    let lower = text.trim().to_ascii_lowercase();
+    let slot = lower
+        .strip_prefix("xdcc send")
+        .or_else(|| lower.strip_prefix("xdcc get"))?;
+    slot.trim().trim_start_matches('#').parse().ok()
+}
+
+/// Answer `DCC RESUME <file> <port> <position>` with an ACCEPT and note
+/// where the transfer on that port starts
+fn accept_resume(
+    args: &str,
+    nick: &str,
+    offers: &HashMap<u16, Arc<AtomicU64>>,
+    state: &MockState,
+) -> String {
+    let mut parts = args.rsplitn(3, ' ');
+    let (Some(position), Some(port), Some(file)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return String::new();
+    };
+    let (Ok(position), Some(offset)) = (
+        position.parse::<u64>(),
+        port.parse::<u16>().ok().and_then(|p| offers.get(&p)),
+    ) else {
+        return String::new();
+    };
+    offset.store(position, Ordering::SeqCst);
+    state.resumes.fetch_add(1, Ordering::SeqCst);
+    format!(
+        ":{BOT}!bot@{SERVER_NAME} PRIVMSG {nick} :\x01DCC ACCEPT {file} {port} {position}\x01\r\n"
+    )
+}
+
+/// Listen for the client's DCC connection to `pack` and return the port
+async fn offer_dcc(
+    pack: MockPack,
+    offset: Arc<AtomicU64>,
+    state: Arc<MockState>,
+    notices: mpsc::Sender<String>,
+) -> std::io::Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        let Ok(Ok((socket, _))) = tokio::time::timeout(DCC_ACCEPT_TIMEOUT, listener.accept()).await
+        else {
+            return;
+        };
+        state.sends.fetch_add(1, Ordering::SeqCst);
+        state.active.fetch_add(1, Ordering::SeqCst);
+        let cut = state
+            .interrupt
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        let start = offset.load(Ordering::SeqCst);
+        let end = if cut {
+            start + (pack.size - start) / 2
+        } else {
+            pack.size
+        };
+        let sent = send_file(socket, start, end, state.options.speed).await;
+        state.active.fetch_sub(1, Ordering::SeqCst);
+        let notice = match sent {
+            Ok(()) if cut => format!("Transfer of {} interrupted", pack.name),
+            Ok(()) => format!("Transfer of {} completed", pack.name),
+            Err(e) => format!("Transfer of {} aborted: {}", pack.name, e),
+        };
+        let _ = notices
+            .send(format!(":{BOT}!bot@{SERVER_NAME} NOTICE * :{notice}\r\n"))
+            .await;
+    });
+    Ok(port)
+}
+
+/// Send bytes `start..end` of a simulated file at `speed` bytes per second
+async fn send_file(socket: TcpStream, start: u64, end: u64, speed: u64) -> std::io::Result<()> {
+    let (mut acks, mut writer) = socket.into_split();
+    // Nothing is done with the client's ACKs, but they have to be read
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while matches!(acks.read(&mut buf).await, Ok(n) if n > 0) {}
+    });
+
+    let started = Instant::now();
+    let mut buf = vec![0u8; 16384];
+    let mut position = start;
+    while position < end {
+        let len = buf.len().min((end - position) as usize);
+        for (i, byte) in buf[..len].iter_mut().enumerate() {
+            *byte = pack_byte(position + i as u64);
+        }
+        writer.write_all(&buf[..len]).await?;
+        position += len as u64;
+        if speed > 0 {
+            let due = Duration::from_secs_f64((position - start) as f64 / speed as f64);
+            if let Some(ahead) = due.checked_sub(started.elapsed()) {
+                tokio::time::sleep(ahead).await;
+            }
+        }
+    }
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::spawn_download_task;
+    use crate::config::{AppConfig, NetworkConfig};
+    use crate::xdcc::transfer::{EnhancedTransferManager, RequestEtiquette};
+    use crate::xdcc::{
+        TransferPriority, TransferStatus, XdccClient, XdccConfig, XdccEvent, XdccUrl,
+    };
+    use tempfile::TempDir;
+    use tokio::sync::RwLock;
+
+    fn options(sizes: &[u64], speed: u64, interrupt: u32) -> MockOptions {
+        MockOptions {
+            packs: sizes
+                .iter()
+                .enumerate()
+                .map(|(i, size)| MockPack::new(&format!("pack{}.bin", i + 1), *size))
+                .collect(),
+            speed,
+            interrupt,
+        }
+    }
+
+    fn url(slot: i32) -> XdccUrl {
+        XdccUrl::parse(&format!("irc://{}/{}/{}/{}", NETWORK, CHANNEL, BOT, slot)).unwrap()
+    }
+
+    fn assert_pack(path: &std::path::Path, size: u64) {
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(data.len() as u64, size);
+        assert!(data
+            .iter()
+            .enumerate()
+            .all(|(i, b)| *b == pack_byte(i as u64)));
+    }
+
+    /// Events of one download until it completes or fails
+    async fn download(config: XdccConfig, slot: i32) -> Result<(), String> {
+        let mut rx = XdccClient::new(config)
+            .start_download(url(slot))
+            .await
+            .unwrap();
+        loop {
+            match rx.recv().await {
+                Some(XdccEvent::Completed) => return Ok(()),
+                Some(XdccEvent::Error(e)) => return Err(e.to_string()),
+                Some(_) => {}
+                None => return Err("channel closed".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulated_resume() {
+        let server = MockServer::start(options(&[300_000], 0, 1)).await.unwrap();
+        let temp = TempDir::new().unwrap();
+        let config = XdccConfig {
+            nickname: "botarr".to_string(),
+            download_dir: temp.path().to_string_lossy().to_string(),
+            simulated_server: Some(server.addr()),
+            ..XdccConfig::default()
+        };
+
+        // Cut off halfway: the partial file stays for the retry
+        let error = download(config.clone(), 1).await.unwrap_err();
+        assert!(error.contains("150000 of 300000"), "{}", error);
+        let path = temp.path().join("pack1.bin");
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 150_000);
+
+        download(config.clone(), 1).await.unwrap();
+        assert_eq!((server.sends(), server.resumes()), (2, 1));
+        assert_pack(&path, 300_000);
+
+        let error = download(config, 7).await.unwrap_err();
+        assert!(error.contains("Invalid pack"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_simulated_queue() {
+        let server = MockServer::start(options(&[40_000, 2_000_000, 20_000], 400_000, 1))
+            .await
+            .unwrap();
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().to_string_lossy().to_string();
+        let mut app_config = AppConfig {
+            download_dir: dir.clone(),
+            ..AppConfig::default()
+        };
+        app_config.networks.insert(
+            NETWORK.to_string(),
+            NetworkConfig {
+                host: server.addr().ip().to_string(),
+                port: server.addr().port(),
+                ssl: false,
+                autojoin_channels: Vec::new(),
+                join_delay_secs: 0,
+                nickserv_password: String::new(),
+                private: false,
+            },
+        );
+        let config = Arc::new(RwLock::new(app_config));
+        let tm = Arc::new(RwLock::new(EnhancedTransferManager::new(dir.clone())));
+        let (plugins, _actions) = crate::plugin::PluginManager::new().unwrap();
+        let plugins = Arc::new(plugins);
+        let etiquette = RequestEtiquette::default();
+        let start = |(id, url, token): (String, XdccUrl, _)| {
+            spawn_download_task(
+                id,
+                url,
+                token,
+                dir.clone(),
+                tm.clone(),
+                config.clone(),
+                plugins.clone(),
+            )
+        };
+        let status = |id: String| {
+            let tm = tm.clone();
+            async move {
+                tm.read()
+                    .await
+                    .get_transfer(&id)
+                    .await
+                    .unwrap()
+                    .transfer
+                    .status
+            }
+        };
+        let wait_for = |id: String, expected: TransferStatus| async move {
+            for _ in 0..100 {
+                if status(id.clone()).await == expected {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            panic!("{} never became {:?}", id, expected);
+        };
+
+        let mut ids = Vec::new();
+        for slot in 1..=3 {
+            let (id, _) = tm
+                .read()
+                .await
+                .create_transfer(url(slot), TransferPriority::Normal, false, None)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+
+        // The first attempt is cut off and the transfer goes to the back of
+        // the queue for its retry
+        start(tm.read().await.pop_queue(&etiquette).await.unwrap());
+        for _ in 0..200 {
+            if tm.read().await.requeue(&ids[0]).await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // One transfer at a time from the bot: the others wait for the second
+        let popped = tm.read().await.pop_queue(&etiquette).await.unwrap();
+        assert_eq!(popped.0, ids[1]);
+        start(popped);
+        wait_for(ids[1].clone(), TransferStatus::Downloading).await;
+        assert!(tm.read().await.pop_queue(&etiquette).await.is_none());
+
+        // Pausing stops the DCC transfer and frees the slot
+        assert!(tm.read().await.pause_transfer(&ids[1]).await);
+        for _ in 0..100 {
+            if server.active() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(server.active(), 0);
+        assert_eq!(status(ids[1].clone()).await, TransferStatus::Paused);
+        let popped = tm.read().await.pop_queue(&etiquette).await.unwrap();
+        assert_eq!(popped.0, ids[2]);
+        start(popped);
+        wait_for(ids[2].clone(), TransferStatus::Completed).await;
+        assert_pack(&temp.path().join("pack3.bin"), 20_000);
+
+        // The retry resumes the partial file
+        let popped = tm.read().await.pop_queue(&etiquette).await.unwrap();
+        assert_eq!(popped.0, ids[0]);
+        start(popped);
+        wait_for(ids[0].clone(), TransferStatus::Completed).await;
+        assert_eq!(server.resumes(), 1);
+        let retries = tm
+            .read()
+            .await
+            .get_transfer(&ids[0])
+            .await
+            .unwrap()
+            .retry_count;
+        assert_eq!(retries, 1);
+        assert_pack(&temp.path().join("pack1.bin"), 40_000);
+    }
+}
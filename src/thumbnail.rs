@@ -0,0 +1,156 @@
+//! Thumbnail Module
+//!
+//! Grabs a frame of completed videos with ffmpeg and keeps it as a small JPEG
+//! named after the download, so the history view can show at a glance that
+//! the right thing was downloaded.
+
+use crate::library::VIDEO_EXTENSIONS;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Width of thumbnails in pixels; the height keeps the aspect ratio
+const WIDTH: u32 = 320;
+/// Seconds into the video a frame is taken from, in order: past intros and
+/// black leaders, then the start for clips shorter than that
+const OFFSETS: &[u64] = &[60, 0];
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(60);
+/// Start of the names of thumbnail files, so only files Botarr wrote are
+/// ever deleted from `thumbnail_dir`
+const PREFIX: &str = "botarr-thumb-";
+
+/// Where the thumbnail of a download is stored in `dir`
+pub fn thumbnail_path(dir: &str, id: &str) -> PathBuf {
+    let id: String = id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    PathBuf::from(dir).join(format!("{}{}.jpg", PREFIX, id))
+}
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Grab a frame of `video` into `out` with `ffmpeg`
+pub async fn generate(ffmpeg: &str, video: &Path, out: &Path) -> Result<(), String> {
+    if let Some(dir) = out.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    }
+    let mut error = String::new();
+    for offset in OFFSETS {
+        let _ = tokio::fs::remove_file(out).await;
+        let child = Command::new(ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-ss"])
+            .arg(offset.to_string())
+            .arg("-i")
+            .arg(video)
+            .args(["-frames:v", "1", "-q:v", "4", "-vf"])
+            .arg(format!("scale={}:-2", WIDTH))
+            .arg(out)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", ffmpeg, e))?;
+
+        let output = tokio::time::timeout(FFMPEG_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| "ffmpeg timed out".to_string())?
+            .map_err(|e| format!("ffmpeg failed: {}", e))?;
+        // Seeking past the end of a short clip succeeds without writing a frame
+        if output.status.success() && out.is_file() {
+            return Ok(());
+        }
+        error = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    }
+    if error.is_empty() {
+        error = "no frame found".to_string();
+    }
+    Err(format!("ffmpeg couldn't grab a frame: {}", error))
+}
+
+/// Delete thumbnails in `dir` of downloads not in `ids`, returning how many.
+/// Other files in the directory are left alone.
+pub fn remove_orphans(dir: &Path, ids: &HashSet<String>) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let id = name
+            .strip_prefix(PREFIX)
+            .and_then(|rest| rest.strip_suffix(".jpg"));
+        let orphan = id.is_some_and(|id| !ids.contains(id));
+        if orphan
+            && entry.file_type().is_ok_and(|t| t.is_file())
+            && std::fs::remove_file(entry.path()).is_ok()
+        {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_thumbnail_path() {
+        assert_eq!(
+            thumbnail_path("thumbs", "abc-123"),
+            PathBuf::from("thumbs/botarr-thumb-abc-123.jpg")
+        );
+        assert_eq!(
+            thumbnail_path("thumbs", "../../etc/passwd"),
+            PathBuf::from("thumbs/botarr-thumb-etcpasswd.jpg")
+        );
+        assert!(is_video(Path::new("Show.S01E01.MKV")));
+        assert!(!is_video(Path::new("Show.S01E01.rar")));
+        assert!(!is_video(Path::new("Show")));
+    }
+
+    #[test]
+    fn test_remove_orphans() {
+        let dir = TempDir::new().unwrap();
+        let thumbnail_dir = dir.path().to_string_lossy().to_string();
+        let kept = thumbnail_path(&thumbnail_dir, "kept");
+        let gone = thumbnail_path(&thumbnail_dir, "gone");
+        for path in [&kept, &gone] {
+            std::fs::write(path, b"x").unwrap();
+        }
+        // The user's own pictures in a shared folder
+        std::fs::write(dir.path().join("holiday.jpg"), b"x").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"x").unwrap();
+
+        let ids = HashSet::from(["kept".to_string()]);
+        assert_eq!(remove_orphans(dir.path(), &ids), 1);
+        assert!(kept.exists());
+        assert!(!gone.exists());
+        assert!(dir.path().join("holiday.jpg").exists());
+        assert!(dir.path().join("notes.txt").exists());
+        assert_eq!(remove_orphans(&dir.path().join("missing"), &ids), 0);
+    }
+
+    #[tokio::test]
+    async fn test_generate_without_ffmpeg() {
+        let dir = TempDir::new().unwrap();
+        let result = generate(
+            "/nonexistent/ffmpeg",
+            &dir.path().join("video.mkv"),
+            &dir.path().join("thumbs/video.jpg"),
+        )
+        .await;
+        assert!(result.unwrap_err().contains("Failed to run"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Torznab Module
+//!
+//! Minimal Torznab indexer feed so Sonarr and Radarr can search Botarr, plus
+//! the connection presets for wiring them up. Grabbing a release queues the
+//! XDCC download and hands the *arr a placeholder torrent for its Torrent
+//! Blackhole client, which then imports the finished file from the watch
+//! folder. The same feed is served as Newznab, linking to NZBs for the
+//! SABnzbd emulation instead.
+
+use crate::release::{classify, Category};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+/// Torznab categories
+pub const CAT_MOVIES: u32 = 2000;
+pub const CAT_MOVIES_HD: u32 = 2040;
+pub const CAT_AUDIO: u32 = 3000;
+pub const CAT_TV: u32 = 5000;
+pub const CAT_TV_SD: u32 = 5030;
+pub const CAT_TV_HD: u32 = 5040;
+pub const CAT_TV_ANIME: u32 = 5070;
+pub const CAT_OTHER: u32 = 8000;
+
+/// One release in a feed
+#[derive(Debug, Clone)]
+pub struct TorznabItem {
+    pub title: String,
+    pub size: Option<u64>,
+    /// Grab link that queues the download
+    pub link: String,
+    pub guid: String,
+    pub category: u32,
+    pub pub_date: DateTime<Utc>,
+}
+
+/// Torznab category for a release name
+pub fn category_for(name: &str) -> u32 {
+    match classify(name) {
+        Category::Tv => CAT_TV,
+        Category::Anime => CAT_TV_ANIME,
+        Category::Movie => CAT_MOVIES,
+        Category::Music => CAT_AUDIO,
+        Category::Other => CAT_OTHER,
+    }
+}
+
+/// Botarr download category for a Torznab category
+pub fn download_category(category: u32) -> &'static str {
+    match category {
+        CAT_TV_ANIME => "anime",
+        5000..=5999 => "tv",
+        2000..=2999 => "movie",
+        3000..=3999 => "music",
+        _ => "other",
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Answer to `t=caps`
+pub fn caps_xml() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<caps>
+  <server title="Botarr" />
+  <limits max="100" default="100" />
+  <searching>
+    <search available="yes" supportedParams="q" />
+    <tv-search available="yes" supportedParams="q,season,ep" />
+    <movie-search available="yes" supportedParams="q" />
+  </searching>
+  <categories>
+    <category id="{}" name="Movies"><subcat id="{}" name="Movies/HD" /></category>
+    <category id="{}" name="Audio" />
+    <category id="{}" name="TV"><subcat id="{}" name="TV/SD" /><subcat id="{}" name="TV/HD" /><subcat id="{}" name="TV/Anime" /></category>
+    <category id="{}" name="Other" />
+  </categories>
+</caps>
+"#,
+        CAT_MOVIES, CAT_MOVIES_HD, CAT_AUDIO, CAT_TV, CAT_TV_SD, CAT_TV_HD, CAT_TV_ANIME, CAT_OTHER
+    )
+}
+
+/// Search results as a Torznab RSS feed, or a Newznab one with `usenet`
+pub fn feed_xml(items: &[TorznabItem], usenet: bool) -> String {
+    let mut out = String::from(if usenet {
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:newznab="http://www.newznab.com/DTD/2010/feeds/attributes/">
+  <channel>
+    <title>Botarr</title>
+"#
+    } else {
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+  <channel>
+    <title>Botarr</title>
+"#
+    });
+    for item in items {
+        let size = item.size.unwrap_or(0);
+        let link = escape(&item.link);
+        let attrs = if usenet {
+            format!(
+                r#"      <enclosure url="{link}" length="{size}" type="application/x-nzb" />
+      <newznab:attr name="category" value="{cat}" />
+      <newznab:attr name="size" value="{size}" />
+"#,
+                cat = item.category,
+            )
+        } else {
+            format!(
+                r#"      <enclosure url="{link}" length="{size}" type="application/x-bittorrent" />
+      <torznab:attr name="category" value="{cat}" />
+      <torznab:attr name="size" value="{size}" />
+      <torznab:attr name="seeders" value="1" />
+      <torznab:attr name="peers" value="1" />
+      <torznab:attr name="downloadvolumefactor" value="0" />
+      <torznab:attr name="uploadvolumefactor" value="1" />
+"#,
+                cat = item.category,
+            )
+        };
+        out.push_str(&format!(
+            r#"    <item>
+      <title>{title}</title>
+      <guid>{guid}</guid>
+      <link>{link}</link>
+      <pubDate>{date}</pubDate>
+      <size>{size}</size>
+      <category>{cat}</category>
+{attrs}    </item>
+"#,
+            title = escape(&item.title),
+            guid = escape(&item.guid),
+            date = item.pub_date.to_rfc2822(),
+            cat = item.category,
+        ));
+    }
+    out.push_str("  </channel>\n</rss>\n");
+    out
+}
+
+/// Torznab error document
+pub fn error_xml(code: u32, description: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<error code=\"{}\" description=\"{}\" />\n",
+        code,
+        escape(description)
+    )
+}
+
+/// Smallest piece length of placeholder torrents
+const PIECE_LENGTH: u64 = 256 * 1024;
+
+/// Single-file torrent with a dummy piece. The *arr only needs something it
+/// can hash and drop into its blackhole folder; the data comes over XDCC.
+/// The piece is at least as long as the file, so the one hash covers it.
+pub fn placeholder_torrent(name: &str, size: u64) -> Vec<u8> {
+    fn bytes(out: &mut Vec<u8>, value: &[u8]) {
+        out.extend_from_slice(format!("{}:", value.len()).as_bytes());
+        out.extend_from_slice(value);
+    }
+
+    let mut out = b"d8:announce".to_vec();
+    bytes(&mut out, b"http://localhost/announce");
+    out.extend_from_slice(b"4:infod6:length");
+    out.extend_from_slice(format!("i{}e", size.max(1)).as_bytes());
+    out.extend_from_slice(b"4:name");
+    bytes(&mut out, name.as_bytes());
+    let piece_length = size.max(1).next_power_of_two().max(PIECE_LENGTH);
+    out.extend_from_slice(format!("12:piece lengthi{}e6:pieces", piece_length).as_bytes());
+    bytes(&mut out, &[0u8; 20]);
+    out.extend_from_slice(b"7:privatei1eee");
+    out
+}
+
+/// Indexer and download client settings in the shape the Sonarr/Radarr v3
+/// API takes (`POST /api/v3/indexer`, `POST /api/v3/downloadclient`)
+pub fn arr_preset(
+    torznab_url: &str,
+    api_key: &str,
+    categories: &[u32],
+    anime_categories: Option<&[u32]>,
+    torrent_folder: &str,
+    watch_folder: &str,
+) -> Value {
+    let mut indexer_fields = vec![
+        json!({"name": "baseUrl", "value": torznab_url}),
+        json!({"name": "apiPath", "value": "/api"}),
+        json!({"name": "apiKey", "value": api_key}),
+        json!({"name": "categories", "value": categories}),
+        json!({"name": "minimumSeeders", "value": 1}),
+    ];
+    if let Some(anime) = anime_categories {
+        indexer_fields.push(json!({"name": "animeCategories", "value": anime}));
+    }
+
+    json!({
+        "indexer": {
+            "name": "Botarr",
+            "implementation": "Torznab",
+            "configContract": "TorznabSettings",
+            "protocol": "torrent",
+            "enableRss": true,
+            "enableAutomaticSearch": true,
+            "enableInteractiveSearch": true,
+            "priority": 25,
+            "fields": indexer_fields,
+        },
+        "downloadClient": {
+            "name": "Botarr",
+            "implementation": "TorrentBlackhole",
+            "configContract": "TorrentBlackholeSettings",
+            "protocol": "torrent",
+            "enable": true,
+            "priority": 1,
+            "fields": [
+                {"name": "torrentFolder", "value": torrent_folder},
+                {"name": "watchFolder", "value": watch_folder},
+                {"name": "saveMagnetFiles", "value": false},
+                {"name": "readOnly", "value": false},
+            ],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_and_categories() {
+        assert_eq!(category_for("Show.Name.S01E02.720p.mkv"), CAT_TV);
+        assert_eq!(category_for("Movie.Name.2019.1080p.BluRay.mkv"), CAT_MOVIES);
+        assert_eq!(download_category(CAT_TV_HD), "tv");
+        assert_eq!(download_category(CAT_TV_ANIME), "anime");
+
+        let items = [TorznabItem {
+            title: "Show & Tell S01E02".to_string(),
+            size: Some(1024),
+            link: "http://botarr/torznab/grab?url=a&name=b".to_string(),
+            guid: "irc://net/#chan/Bot/1".to_string(),
+            category: CAT_TV,
+            pub_date: Utc::now(),
+        }];
+        let xml = feed_xml(&items, false);
+        assert!(xml.contains("<title>Show &amp; Tell S01E02</title>"));
+        assert!(xml.contains("url=a&amp;name=b"));
+        assert!(xml.contains(r#"<torznab:attr name="size" value="1024" />"#));
+        let xml = feed_xml(&items, true);
+        assert!(xml.contains(r#"type="application/x-nzb""#));
+        assert!(xml.contains(r#"<newznab:attr name="category" value="5000" />"#));
+        assert!(!xml.contains("torznab:"));
+
+        let torrent = placeholder_torrent("file.mkv", 1024);
+        assert!(torrent.starts_with(b"d8:announce"));
+        assert!(torrent.ends_with(b"ee"));
+        let contains = |torrent: &[u8], part: &[u8]| torrent.windows(part.len()).any(|w| w == part);
+        assert!(contains(&torrent, b"12:piece lengthi262144e6:pieces20:"));
+        let torrent = placeholder_torrent("file.mkv", 3 << 30);
+        assert!(contains(
+            &torrent,
+            b"12:piece lengthi4294967296e6:pieces20:"
+        ));
+    }
+}
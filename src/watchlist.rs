@@ -0,0 +1,371 @@
+//! Watchlist
+//!
+//! Saved searches run on a schedule through the search aggregator. Results
+//! that pass an entry's filters and that it hasn't found before are queued
+//! right away, so new releases download without anyone searching for them.
+//! Found files are remembered by name, so the same release offered by
+//! another bot later isn't queued again.
+//!
+//! The autodl plugin's watchlist filters (`plugins/autodl.json`) are a
+//! different thing: they match releases announced on IRC as they appear and
+//! never search. Entries here poll the search providers instead, which also
+//! finds packs that were announced while Botarr was offline.
+
+use crate::db::{WatchlistEntry, WatchlistHit};
+use crate::xdcc::query::{transliterate, SearchFilter};
+use crate::xdcc::{TransferPriority, XdccSearchResult};
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Shortest interval between runs of an entry, to spare the search providers
+pub const MIN_INTERVAL_MINS: u32 = 5;
+/// How often the scheduler looks for entries that are due
+const TICK: Duration = Duration::from_secs(60);
+
+/// Entries being run, so the scheduler and a manual run don't queue the same
+/// files twice
+static RUNNING: LazyLock<Mutex<HashSet<i64>>> = LazyLock::new(Mutex::default);
+
+/// Why a run didn't happen
+#[derive(Debug)]
+pub enum RunError {
+    /// The entry is being run already
+    Busy,
+    Failed(String),
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Busy => write!(f, "Watchlist entry is already running"),
+            Self::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Marks an entry as running until dropped
+struct RunGuard(i64);
+
+impl RunGuard {
+    fn acquire(id: i64) -> Option<Self> {
+        let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        if !running.insert(id) {
+            return None;
+        }
+        Some(Self(id))
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        RUNNING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.0);
+    }
+}
+
+/// What happened to a found file
+enum Queued {
+    Transfer(String),
+    /// Already downloaded, queued or in the library
+    Duplicate,
+    /// Not queued this time; tried again on the next run
+    Failed,
+}
+
+/// Whether an entry should run at `now`
+pub fn is_due(entry: &WatchlistEntry, now: DateTime<Utc>) -> bool {
+    if !entry.enabled {
+        return false;
+    }
+    let Some(last) = entry
+        .last_run_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+    else {
+        return true;
+    };
+    now - last.with_timezone(&Utc) >= chrono::Duration::minutes(entry.interval_mins as i64)
+}
+
+/// Whether a result passes an entry's network, bot and size filters
+pub fn matches(entry: &WatchlistEntry, result: &XdccSearchResult) -> bool {
+    if entry
+        .network
+        .as_deref()
+        .is_some_and(|n| !n.eq_ignore_ascii_case(&result.network))
+    {
+        return false;
+    }
+    if entry
+        .bot
+        .as_deref()
+        .is_some_and(|b| !b.eq_ignore_ascii_case(&result.bot))
+    {
+        return false;
+    }
+    if entry.min_size.is_none() && entry.max_size.is_none() {
+        return true;
+    }
+    result.size.is_some_and(|size| {
+        entry.min_size.is_none_or(|min| size >= min) && entry.max_size.is_none_or(|max| size <= max)
+    })
+}
+
+/// Search for an entry and keep the first source of every matching file
+pub async fn find(
+    state: &AppState,
+    entry: &WatchlistEntry,
+) -> Result<Vec<XdccSearchResult>, String> {
+    let (mut query, mut filter) = SearchFilter::parse_query(&entry.query);
+    if state.config.read().await.search_normalize {
+        query = transliterate(&query);
+        filter.set_normalize(true);
+    }
+    let mut results = state
+        .search_aggregator
+        .search(&query, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    filter.apply(&mut results);
+    let mut seen = HashSet::new();
+    results.retain(|r| matches(entry, r) && seen.insert(r.filename.to_lowercase()));
+    Ok(results)
+}
+
+/// Run an entry once: queue the files it hasn't found before and return them.
+/// Files that fail to queue aren't recorded, so the next run tries them again.
+pub async fn run(state: &AppState, entry: &WatchlistEntry) -> Result<Vec<WatchlistHit>, RunError> {
+    let _running = RunGuard::acquire(entry.id).ok_or(RunError::Busy)?;
+    let failed = |e: String| RunError::Failed(e);
+    let results = find(state, entry).await.map_err(failed)?;
+    let mut hits = Vec::new();
+    for result in results {
+        if state
+            .database
+            .has_watchlist_hit(entry.id, &result.filename)
+            .map_err(|e| failed(e.to_string()))?
+        {
+            continue;
+        }
+        let transfer_id = match queue(state, entry, &result).await {
+            Queued::Transfer(id) => Some(id),
+            Queued::Duplicate => None,
+            Queued::Failed => continue,
+        };
+        let hit = WatchlistHit {
+            file_name: result.filename.clone(),
+            url: result.url.to_string(),
+            transfer_id,
+            found_at: Utc::now().to_rfc3339(),
+        };
+        state
+            .database
+            .add_watchlist_hit(entry.id, &hit)
+            .map_err(|e| failed(e.to_string()))?;
+        hits.push(hit);
+    }
+    state
+        .database
+        .set_watchlist_run(entry.id, &Utc::now().to_rfc3339())
+        .map_err(|e| failed(e.to_string()))?;
+    Ok(hits)
+}
+
+/// Queue a found file with the entry's category and template. Files already
+/// in the library, downloaded or queued are skipped.
+async fn queue(state: &AppState, entry: &WatchlistEntry, result: &XdccSearchResult) -> Queued {
+    if crate::arr::already_in_library(&state.config, &state.arr_library, Some(&result.filename))
+        .await
+    {
+        return Queued::Duplicate;
+    }
+    let template = match entry.template.as_deref() {
+        Some(name) => state
+            .config
+            .read()
+            .await
+            .template(name)
+            .map(|(name, template)| (name.clone(), template.clone())),
+        None => None,
+    };
+    let priority: TransferPriority = template
+        .as_ref()
+        .and_then(|(_, t)| t.priority)
+        .unwrap_or_default();
+    let category = entry
+        .category
+        .clone()
+        .or_else(|| template.as_ref().and_then(|(_, t)| t.category.clone()));
+
+    let tm = state.transfer_manager.read().await;
+    if let Err(e) = tm
+        .check_new(&result.url, Some(result.filename.as_str()))
+        .await
+    {
+        tracing::info!(
+            "Watchlist '{}' skipped {}: {}",
+            entry.name,
+            result.filename,
+            e
+        );
+        return Queued::Duplicate;
+    }
+    let (id, _) = match tm
+        .create_transfer(
+            result.url.clone(),
+            priority,
+            false,
+            Some(result.filename.clone()),
+        )
+        .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            tracing::warn!(
+                "Watchlist '{}' failed to queue {}: {}",
+                entry.name,
+                result.filename,
+                e
+            );
+            return Queued::Failed;
+        }
+    };
+    if category.is_some() {
+        tm.set_category(&id, category).await;
+    }
+    if let Some(size) = result.size {
+        tm.set_expected_size(&id, size).await;
+    }
+    if let Some((name, template)) = &template {
+        tm.apply_template(&id, name, template).await;
+    }
+    tm.add_log(&id, format!("Queued by watchlist entry '{}'", entry.name))
+        .await;
+    tracing::info!(
+        "Watchlist '{}' queued {} as transfer {}",
+        entry.name,
+        result.filename,
+        id
+    );
+    Queued::Transfer(id)
+}
+
+/// Spawn the task running watchlist entries when they are due
+pub fn spawn_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(TICK).await;
+            let entries = match state.database.list_watchlist() {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::warn!("Failed to load the watchlist: {}", e);
+                    continue;
+                }
+            };
+            for entry in entries.iter().filter(|e| is_due(e, Utc::now())) {
+                match run(&state, entry).await {
+                    Ok(hits) if hits.is_empty() => {}
+                    Ok(hits) => tracing::info!(
+                        "Watchlist '{}' found {} new file(s)",
+                        entry.name,
+                        hits.len()
+                    ),
+                    Err(RunError::Busy) => {}
+                    Err(e) => tracing::warn!("Watchlist '{}' failed: {}", entry.name, e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xdcc::XdccUrl;
+
+    fn entry() -> WatchlistEntry {
+        WatchlistEntry {
+            id: 1,
+            name: "Show".to_string(),
+            query: "show".to_string(),
+            min_size: None,
+            max_size: None,
+            network: None,
+            bot: None,
+            category: None,
+            template: None,
+            interval_mins: 60,
+            enabled: true,
+            last_run_at: None,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn result(network: &str, bot: &str, size: Option<u64>) -> XdccSearchResult {
+        XdccSearchResult {
+            url: XdccUrl {
+                network: network.to_string(),
+                channel: "#news".to_string(),
+                bot: bot.to_string(),
+                slot: 1,
+            },
+            filename: "Show.S01E01.mkv".to_string(),
+            size,
+            size_str: String::new(),
+            bot: bot.to_string(),
+            network: network.to_string(),
+            channel: "#news".to_string(),
+            slot: 1,
+            gets: None,
+            last_seen: None,
+            annotations: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_is_due() {
+        let now = Utc::now();
+        let mut entry = entry();
+        assert!(is_due(&entry, now));
+        entry.last_run_at = Some((now - chrono::Duration::minutes(30)).to_rfc3339());
+        assert!(!is_due(&entry, now));
+        entry.last_run_at = Some((now - chrono::Duration::minutes(60)).to_rfc3339());
+        assert!(is_due(&entry, now));
+        entry.enabled = false;
+        assert!(!is_due(&entry, now));
+    }
+
+    #[test]
+    fn test_run_guard() {
+        let guard = RunGuard::acquire(7).unwrap();
+        assert!(RunGuard::acquire(7).is_none());
+        assert!(RunGuard::acquire(8).is_some());
+        drop(guard);
+        assert!(RunGuard::acquire(7).is_some());
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut entry = entry();
+        assert!(matches(&entry, &result("rizon", "Bot", None)));
+
+        entry.network = Some("Rizon".to_string());
+        entry.bot = Some("bot".to_string());
+        assert!(matches(&entry, &result("rizon", "Bot", None)));
+        assert!(!matches(&entry, &result("abjects", "Bot", None)));
+        assert!(!matches(&entry, &result("rizon", "Other", None)));
+
+        entry.min_size = Some(100);
+        entry.max_size = Some(200);
+        assert!(matches(&entry, &result("rizon", "Bot", Some(150))));
+        assert!(!matches(&entry, &result("rizon", "Bot", Some(99))));
+        assert!(!matches(&entry, &result("rizon", "Bot", Some(201))));
+        // Unknown sizes can't be checked
+        assert!(!matches(&entry, &result("rizon", "Bot", None)));
+    }
+}
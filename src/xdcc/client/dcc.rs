@@ -1,20 +1,62 @@
 use std::io::SeekFrom;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
 use std::time::Duration;
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncSeekExt;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 
+use super::throttle::Throttle;
+use crate::filename::{unique_path, FilenamePolicy};
 use crate::xdcc::{XdccError, XdccEvent};
 
 #[derive(Debug)]
 pub struct DccInfo {
     pub filename: String,
-    pub ip: String,
+    /// Sender address: an IPv4 one sent as a 32-bit integer, or an IPv6
+    /// one sent as a literal
+    pub ip: IpAddr,
     pub port: u16,
     pub size: u64,
+    /// Turbo DCC: the sender doesn't wait for ACKs, so none are sent
+    pub turbo: bool,
+    /// Passive (reverse) DCC: the sender offers port 0 with this token and
+    /// connects to us instead
+    pub token: Option<String>,
+}
+
+impl DccInfo {
+    pub fn is_passive(&self) -> bool {
+        self.port == 0 && self.token.is_some()
+    }
+
+    /// Address to connect to for an active offer
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    /// Filename as sent in DCC commands, quoted if it contains spaces
+    pub fn quoted_filename(&self) -> String {
+        if self.filename.contains(' ') {
+            format!("\"{}\"", self.filename)
+        } else {
+            self.filename.clone()
+        }
+    }
+}
+
+/// DCC acknowledgement for `downloaded` bytes: the classic 32-bit position,
+/// or a 64-bit one for files over 4 GiB, where the 32-bit position wraps
+/// and some bots abort the transfer
+pub fn dcc_ack(downloaded: u64, size: u64) -> Vec<u8> {
+    if size > u32::MAX as u64 {
+        downloaded.to_be_bytes().to_vec()
+    } else {
+        (downloaded as u32).to_be_bytes().to_vec()
+    }
 }
 
 pub struct DccResumeInfo {
@@ -22,15 +64,44 @@ pub struct DccResumeInfo {
     pub offset: u64,
 }
 
+/// Whether a line carries a DCC SEND or turbo DCC TSEND offer
+pub fn is_dcc_send(line: &str) -> bool {
+    line.contains("DCC SEND") || line.contains("DCC TSEND")
+}
+
+/// Address of a DCC offer: a 32-bit integer for IPv4, or a literal address
+/// (IPv6, or dotted IPv4 from some clients)
+pub fn parse_dcc_ip(value: &str) -> Option<IpAddr> {
+    match value.parse::<u32>() {
+        Ok(ip_int) => Some(Ipv4Addr::from(ip_int).into()),
+        Err(_) => value.trim_matches(['[', ']']).parse().ok(),
+    }
+}
+
+/// DCC form of `ip`, see [`parse_dcc_ip`]
+pub fn format_dcc_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => u32::from(v4).to_string(),
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map_or(v6.to_string(), |v4| u32::from(v4).to_string()),
+    }
+}
+
 /// Parse DCC SEND message
 /// Format: :bot!... PRIVMSG nick :\x01DCC SEND filename ip port size\x01
+/// Turbo offers use `DCC TSEND` or append a `T` token after the size.
+/// Passive offers have port 0 and a token after the size.
 pub fn parse_dcc_send(line: &str) -> Option<DccInfo> {
-    let dcc_start = line.find("DCC SEND")?;
+    let (dcc_start, command, mut turbo) = match line.find("DCC SEND") {
+        Some(i) => (i, "DCC SEND", false),
+        None => (line.find("DCC TSEND")?, "DCC TSEND", true),
+    };
     let dcc_part = &line[dcc_start..];
 
     // Remove CTCP markers
     let cleaned = dcc_part
-        .trim_start_matches("DCC SEND")
+        .trim_start_matches(command)
         .trim()
         .trim_end_matches('\x01')
         .trim();
@@ -53,49 +124,118 @@ pub fn parse_dcc_send(line: &str) -> Option<DccInfo> {
         return None;
     }
 
-    let ip_int: u32 = parts[0].parse().ok()?;
+    let ip = parse_dcc_ip(parts[0])?;
     let port: u16 = parts[1].parse().ok()?;
     let size: u64 = parts[2].parse().ok()?;
-
-    // Convert IP from integer to dotted format
-    let ip = format!(
-        "{}.{}.{}.{}",
-        (ip_int >> 24) & 0xFF,
-        (ip_int >> 16) & 0xFF,
-        (ip_int >> 8) & 0xFF,
-        ip_int & 0xFF
-    );
+    let mut token = None;
+    for extra in &parts[3..] {
+        if extra.eq_ignore_ascii_case("T") {
+            turbo = true;
+        } else if token.is_none() {
+            token = Some(extra.to_string());
+        }
+    }
 
     Some(DccInfo {
         filename,
         ip,
         port,
         size,
+        turbo,
+        token: (port == 0).then_some(token).flatten(),
     })
 }
 
+/// Listen for a passive DCC connection on the first free port of `ports`,
+/// starting from a random one so concurrent transfers rarely collide
+pub async fn dcc_listen(ports: (u16, u16), bind: Option<IpAddr>) -> std::io::Result<TcpListener> {
+    let (min, max) = (ports.0.min(ports.1), ports.0.max(ports.1));
+    let count = u32::from(max - min) + 1;
+    let start = uuid::Uuid::new_v4().as_u128() as u32 % count;
+    let ip = bind.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+    let mut last_error = None;
+    for i in 0..count {
+        let port = min + ((start + i) % count) as u16;
+        match TcpListener::bind((ip, port)).await {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::ErrorKind::AddrInUse.into()))
+}
+
+/// CTCP answer to a passive offer telling the sender where to connect
+pub fn passive_reply(info: &DccInfo, ip: IpAddr, port: u16) -> String {
+    format!(
+        "\x01DCC SEND {} {} {} {} {}\x01",
+        info.quoted_filename(),
+        format_dcc_ip(ip),
+        port,
+        info.size,
+        info.token.as_deref().unwrap_or_default()
+    )
+}
+
+/// Wait for the sender of a passive offer to connect
+pub async fn dcc_accept(
+    listener: &TcpListener,
+    accept_timeout: Duration,
+) -> Result<TcpStream, String> {
+    let (stream, peer) = timeout(accept_timeout, listener.accept())
+        .await
+        .map_err(|_| "Bot didn't connect for passive DCC".to_string())?
+        .map_err(|e| format!("Passive DCC accept failed: {}", e))?;
+    tracing::info!("Passive DCC connection from {}", peer);
+    Ok(stream)
+}
+
+/// Open the DCC data connection for an offer
+pub async fn dcc_connect(
+    info: &DccInfo,
+    connect_timeout: Duration,
+    outbound: &crate::net::Outbound,
+) -> Result<TcpStream, String> {
+    let addr = info.addr().to_string();
+    tracing::info!(
+        "Connecting to DCC: {} for file: {}{}",
+        addr,
+        info.filename,
+        if info.turbo { " (turbo)" } else { "" }
+    );
+
+    timeout(connect_timeout, outbound.connect(&addr, false))
+        .await
+        .map_err(|_| "DCC connection timed out".to_string())?
+        .map_err(|e| format!("DCC connection failed: {}", e))
+}
+
 pub async fn dcc_receive(
+    mut stream: TcpStream,
     info: DccInfo,
     download_dir: &str,
+    policy: FilenamePolicy,
     seek_offset: u64,
+    throttle: &Throttle,
     tx: mpsc::Sender<XdccEvent>,
 ) -> Result<(), XdccError> {
-    let addr = format!("{}:{}", info.ip, info.port);
-    tracing::info!("Connecting to DCC: {} for file: {}", addr, info.filename);
-
-    let mut stream = timeout(Duration::from_secs(30), TcpStream::connect(&addr))
-        .await
-        .map_err(|_| XdccError::TransferFailed("DCC connection timed out".into()))?
-        .map_err(|e| XdccError::TransferFailed(format!("DCC connection failed: {}", e)))?;
-
     // Create download directory if needed
     tokio::fs::create_dir_all(download_dir).await.ok();
 
-    // Sanitize filename
-    let safe_filename = info
-        .filename
-        .replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
-    let file_path = format!("{}/{}", download_dir, safe_filename);
+    // A partial file is ours to resume or restart, a finished one is kept
+    let safe_filename = policy.sanitize(&info.filename);
+    let mut file_path = Path::new(download_dir).join(&safe_filename);
+    if seek_offset == 0 {
+        let existing = tokio::fs::metadata(&file_path).await.map(|m| m.len());
+        if existing.is_ok_and(|len| len > 0 && len >= info.size) {
+            file_path = unique_path(Path::new(download_dir), &safe_filename);
+            let name = file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(safe_filename);
+            tracing::info!("{} already exists, saving as {}", info.filename, name);
+            let _ = tx.send(XdccEvent::SavingAs(name)).await;
+        }
+    }
 
     let mut file = OpenOptions::new()
         .create(true)
@@ -104,10 +244,10 @@ pub async fn dcc_receive(
         .open(&file_path)
         .await
         .map_err(|e| match e.kind() {
-            std::io::ErrorKind::PermissionDenied
-            | std::io::ErrorKind::NotFound
-            | std::io::ErrorKind::StorageFull
-            | std::io::ErrorKind::WriteZero => {
+            std::io::ErrorKind::StorageFull | std::io::ErrorKind::WriteZero => {
+                XdccError::DiskWrite(format!("Failed to create/open file: {}", e))
+            }
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::NotFound => {
                 XdccError::FatalIo(format!("Failed to create/open file: {}", e))
             }
             _ => XdccError::TransferFailed(format!("Failed to create/open file: {}", e)),
@@ -123,7 +263,7 @@ pub async fn dcc_receive(
         }
     }
 
-    tracing::info!("Saving to: {}", file_path);
+    tracing::info!("Saving to: {}", file_path.display());
 
     let mut downloaded: u64 = seek_offset;
     let mut buf = [0u8; 16384];
@@ -136,30 +276,33 @@ pub async fn dcc_receive(
         match stream.read(&mut buf).await {
             Ok(0) => break,
             Ok(n) => {
+                // Out of space or a failing disk: the transfer is paused, not failed
                 file.write_all(&buf[..n])
                     .await
-                    .map_err(|e| match e.kind() {
-                        std::io::ErrorKind::StorageFull
-                        | std::io::ErrorKind::WriteZero
-                        | std::io::ErrorKind::PermissionDenied => {
-                            XdccError::FatalIo(format!("Write error: {}", e))
-                        }
-                        _ => XdccError::TransferFailed(format!("Write error: {}", e)),
-                    })?;
+                    .map_err(|e| XdccError::DiskWrite(format!("Write error: {}", e)))?;
                 downloaded += n as u64;
                 bytes_since_update += n as u64;
 
-                // Send DCC acknowledgment (required by protocol)
-                let ack = (downloaded as u32).to_be_bytes();
-                let _ = stream.write_all(&ack).await;
+                // Send DCC acknowledgment (required by protocol, except in turbo mode)
+                if !info.turbo {
+                    let _ = stream.write_all(&dcc_ack(downloaded, info.size)).await;
+                }
+
+                // Hold reads back to the speed cap; TCP flow control slows the bot down
+                throttle.consume(n as u64).await;
 
                 // Send progress update every 500ms (for UI)
                 let elapsed = last_update.elapsed();
                 if elapsed.as_millis() >= 500 {
+                    // Nobody is listening anymore: the download was cancelled
+                    if tx.is_closed() {
+                        return Err(XdccError::TransferFailed("Transfer cancelled".into()));
+                    }
                     let speed = bytes_since_update as f64 / elapsed.as_secs_f64();
                     let _ = tx
                         .send(XdccEvent::Progress {
                             downloaded,
+                            received: downloaded - seek_offset,
                             total: info.size,
                             speed,
                         })
@@ -191,6 +334,19 @@ pub async fn dcc_receive(
         }
     }
 
+    // Surface errors of writes still in flight
+    file.flush()
+        .await
+        .map_err(|e| XdccError::DiskWrite(format!("Write error: {}", e)))?;
+
+    // A bot that hangs up early leaves a partial file to resume, not a finished one
+    if info.size > 0 && downloaded < info.size {
+        return Err(XdccError::TransferFailed(format!(
+            "Connection closed after {} of {} bytes",
+            downloaded, info.size
+        )));
+    }
+
     // Final progress update
     let total_time = start_time.elapsed().as_secs_f64();
     let avg_speed = if total_time > 0.0 {
@@ -201,6 +357,7 @@ pub async fn dcc_receive(
     let _ = tx
         .send(XdccEvent::Progress {
             downloaded,
+            received: downloaded - seek_offset,
             total: info.size,
             speed: avg_speed,
         })
@@ -215,3 +372,177 @@ pub async fn dcc_receive(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dcc_send() {
+        let info = parse_dcc_send(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND \"Some File.mkv\" 3232235777 5000 1048576\x01",
+        )
+        .unwrap();
+        assert_eq!(info.filename, "Some File.mkv");
+        assert_eq!(info.ip, "192.168.1.1".parse::<IpAddr>().unwrap());
+        assert_eq!(info.port, 5000);
+        assert_eq!(info.size, 1048576);
+        assert!(!info.turbo);
+
+        let info =
+            parse_dcc_send(":Bot!b@host PRIVMSG me :\x01DCC TSEND file.mkv 3232235777 5000 10\x01")
+                .unwrap();
+        assert!(info.turbo);
+
+        let info = parse_dcc_send(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND file.mkv 3232235777 5000 10 T\x01",
+        )
+        .unwrap();
+        assert!(info.turbo);
+        assert!(!info.is_passive());
+
+        let info = parse_dcc_send(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND \"A B.mkv\" 3232235777 0 10 42\x01",
+        )
+        .unwrap();
+        assert!(info.is_passive());
+        assert_eq!(info.token.as_deref(), Some("42"));
+        assert_eq!(
+            passive_reply(&info, Ipv4Addr::new(10, 0, 0, 2).into(), 50000),
+            "\x01DCC SEND \"A B.mkv\" 167772162 50000 10 42\x01"
+        );
+    }
+
+    #[test]
+    fn test_parse_ipv6_dcc_send() {
+        let info = parse_dcc_send(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND file.mkv 2001:db8::5 5000 1024\x01",
+        )
+        .unwrap();
+        assert_eq!(info.ip, "2001:db8::5".parse::<IpAddr>().unwrap());
+        assert_eq!(info.addr().to_string(), "[2001:db8::5]:5000");
+        assert_eq!(info.size, 1024);
+
+        assert_eq!(
+            parse_dcc_ip("[::1]"),
+            Some(IpAddr::from([0u16, 0, 0, 0, 0, 0, 0, 1]))
+        );
+        assert_eq!(parse_dcc_ip("10.0.0.2"), parse_dcc_ip("167772162"));
+        assert_eq!(parse_dcc_ip("host.example"), None);
+        assert!(parse_dcc_send(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND file.mkv 2001:db8::zz 5000 1024\x01"
+        )
+        .is_none());
+
+        let info =
+            parse_dcc_send(":Bot!b@host PRIVMSG me :\x01DCC SEND file.mkv 3232235777 0 10 7\x01")
+                .unwrap();
+        assert_eq!(
+            passive_reply(&info, "2001:db8::2".parse().unwrap(), 50000),
+            "\x01DCC SEND file.mkv 2001:db8::2 50000 10 7\x01"
+        );
+        assert_eq!(
+            format_dcc_ip("::ffff:10.0.0.2".parse().unwrap()),
+            "167772162"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_dcc_connect() {
+        // Hosts without IPv6 can't run this
+        let Ok(listener) = TcpListener::bind("[::1]:0").await else {
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+        let info = parse_dcc_send(&format!(
+            ":Bot!b@host PRIVMSG me :\x01DCC SEND file.mkv ::1 {} 10\x01",
+            port
+        ))
+        .unwrap();
+        let outbound = crate::net::Outbound::default();
+        let (accepted, connected) = tokio::join!(
+            listener.accept(),
+            dcc_connect(&info, Duration::from_secs(5), &outbound)
+        );
+        assert!(accepted.unwrap().1.is_ipv6());
+        assert!(connected.unwrap().peer_addr().unwrap().is_ipv6());
+    }
+
+    #[tokio::test]
+    async fn test_passive_accept() {
+        let listener = dcc_listen((50000, 50100), Some("127.0.0.1".parse().unwrap()))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!((50000..=50100).contains(&port));
+        let connect = TcpStream::connect(("127.0.0.1", port));
+        let (accepted, connected) =
+            tokio::join!(dcc_accept(&listener, Duration::from_secs(5)), connect);
+        assert!(accepted.is_ok() && connected.is_ok());
+        assert!(dcc_accept(&listener, Duration::from_millis(10))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_dcc_ack() {
+        assert_eq!(dcc_ack(1024, 4096), vec![0, 0, 4, 0]);
+        // Files up to 4 GiB keep 32-bit acks
+        assert_eq!(dcc_ack(u32::MAX as u64, u32::MAX as u64), vec![0xff; 4]);
+        let size = 5 << 30;
+        assert_eq!(dcc_ack(size, size), size.to_be_bytes().to_vec());
+        assert_eq!(dcc_ack(size, size).len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_large_file_acks() {
+        // A 5 GiB file resumed 8 bytes before its end, sent in two chunks
+        let size: u64 = 5 << 30;
+        let offset = size - 8;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let bot = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut acks = Vec::new();
+            for chunk in [b"abcd", b"efgh"] {
+                socket.write_all(chunk).await.unwrap();
+                let mut ack = [0u8; 8];
+                socket.read_exact(&mut ack).await.unwrap();
+                acks.push(u64::from_be_bytes(ack));
+            }
+            acks
+        });
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("big.bin");
+        std::fs::File::create(&path)
+            .unwrap()
+            .set_len(offset)
+            .unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let info = DccInfo {
+            filename: "big.bin".to_string(),
+            ip: Ipv4Addr::LOCALHOST.into(),
+            port,
+            size,
+            turbo: false,
+            token: None,
+        };
+        let (tx, _rx) = mpsc::channel(100);
+        let dir = temp.path().to_string_lossy().to_string();
+        let throttle = Throttle::default();
+        let receive = dcc_receive(
+            stream,
+            info,
+            &dir,
+            FilenamePolicy::Posix,
+            offset,
+            &throttle,
+            tx,
+        );
+        let (received, acks) = tokio::join!(receive, bot);
+        received.unwrap();
+        assert_eq!(acks.unwrap(), vec![size - 4, size]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    }
+}
@@ -0,0 +1,502 @@
+//! HTTP Mirror Downloads
+//!
+//! Some bots advertise HTTP or FTP mirrors of their packs in their notices.
+//! When DCC keeps failing, a transfer can fetch the file from such a mirror
+//! instead. The download reports the same events as a DCC transfer, so the
+//! completion pipeline (screening, hooks, postprocessing) is shared.
+//!
+//! Bots choose the URLs, so a mirror is only fetched from public addresses,
+//! must serve the pack's file by name and must be the size the bot announced.
+
+use super::info::{same_name, similar_size};
+use super::{XdccConfig, XdccEvent};
+use crate::filename::unique_path;
+use crate::xdcc::XdccError;
+use reqwest::dns::{Name, Resolve, Resolving};
+use reqwest::header::{HeaderMap, CONTENT_DISPOSITION, CONTENT_TYPE};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+/// Redirects a mirror may send before it is given up on
+const MAX_REDIRECTS: usize = 5;
+
+/// Whether `url` is a mirror this client can fetch (FTP mirrors are only listed)
+pub fn is_supported(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// File name of a mirror URL: its last path segment, percent-decoded
+pub fn file_name_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, rest) = path.split_once("://")?;
+    let (_, path) = rest.split_once('/')?;
+    let segment = path.rsplit('/').next().filter(|s| !s.is_empty())?;
+    Some(
+        urlencoding::decode(segment)
+            .map(|s| s.into_owned())
+            .unwrap_or_else(|_| segment.to_string()),
+    )
+}
+
+/// File name a response offers in `Content-Disposition`
+fn disposition_name(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+    let mut name = None;
+    for (key, value) in value.split(';').skip(1).filter_map(|p| p.split_once('=')) {
+        match key.trim().to_ascii_lowercase().as_str() {
+            // RFC 5987: UTF-8''percent%20encoded
+            "filename*" => {
+                let encoded = value.trim().rsplit('\'').next().unwrap_or_default();
+                if let Ok(decoded) = urlencoding::decode(encoded) {
+                    return Some(decoded.into_owned()).filter(|n| !n.is_empty());
+                }
+            }
+            "filename" => name = Some(value.trim().trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    name.filter(|n| !n.is_empty())
+}
+
+/// Whether `ip` is on the public internet, not the local machine or network
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local and link-local
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Refuse URLs naming a non-public IP address, which skip name resolution
+fn check_host(url: &reqwest::Url) -> Result<(), String> {
+    let host = url.host_str().unwrap_or_default();
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) if !is_public(ip) => Err(format!("{} is not a public address", ip)),
+        _ => Ok(()),
+    }
+}
+
+/// Resolves mirror hosts, refusing those with a non-public address, so a bot
+/// can't point Botarr at the local machine or network
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+                return Err(
+                    format!("{} resolves to {}, not a public address", host, addr.ip()).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Box<dyn Iterator<Item = SocketAddr> + Send>)
+        })
+    }
+}
+
+/// Start downloading `mirror` into the download directory and return the
+/// event channel, like [`XdccClient::start_download`](super::XdccClient::start_download)
+pub fn start_download(mirror: String, config: XdccConfig) -> mpsc::Receiver<XdccEvent> {
+    let (tx, rx) = mpsc::channel(100);
+    tokio::spawn(async move {
+        match download(&mirror, &config, &tx, true).await {
+            Ok(()) => {
+                let _ = tx.send(XdccEvent::Completed).await;
+            }
+            Err(e) => {
+                tracing::error!("Mirror download of {} failed: {}", mirror, e);
+                let _ = tx.send(XdccEvent::Error(e)).await;
+            }
+        }
+    });
+    rx
+}
+
+/// HTTP client for mirrors, only reaching public addresses if `public_only`
+fn client(config: &XdccConfig, public_only: bool) -> Result<reqwest::Client, XdccError> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(config.timeout_secs))
+        .user_agent(concat!("Botarr/", env!("CARGO_PKG_VERSION")));
+    if let Ok(ip) = config.outbound_bind.parse() {
+        builder = builder.local_address(Some(ip));
+    }
+    if public_only {
+        builder = builder
+            .dns_resolver(PublicResolver)
+            .redirect(reqwest::redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    return attempt.error("too many redirects");
+                }
+                match check_host(attempt.url()) {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e),
+                }
+            }));
+    }
+    builder
+        .build()
+        .map_err(|e| XdccError::ConnectionFailed(format!("Cannot create HTTP client: {}", e)))
+}
+
+async fn download(
+    mirror: &str,
+    config: &XdccConfig,
+    tx: &mpsc::Sender<XdccEvent>,
+    public_only: bool,
+) -> Result<(), XdccError> {
+    let _ = tx.send(XdccEvent::Connecting).await;
+    let _ = tx
+        .send(XdccEvent::Log(format!(
+            "Downloading from mirror {}",
+            mirror
+        )))
+        .await;
+
+    let Some(name) = config.expected_filename.clone() else {
+        return Err(XdccError::TransferFailed(
+            "The pack's file name is unknown, so the mirror can't be checked".into(),
+        ));
+    };
+    if public_only {
+        let url = reqwest::Url::parse(mirror)
+            .map_err(|e| XdccError::TransferFailed(format!("Bad mirror URL: {}", e)))?;
+        check_host(&url).map_err(XdccError::TransferFailed)?;
+    }
+    let mut response = client(config, public_only)?
+        .get(mirror)
+        .send()
+        .await
+        .map_err(|e| XdccError::ConnectionFailed(format!("Cannot reach {}: {}", mirror, e)))?;
+    if !response.status().is_success() {
+        return Err(XdccError::TransferFailed(format!(
+            "Mirror answered {}",
+            response.status()
+        )));
+    }
+
+    // A landing page or another file must not complete the transfer
+    let served: Vec<String> = [
+        disposition_name(response.headers()),
+        file_name_from_url(response.url().as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !served.iter().any(|n| same_name(n, &name)) {
+        return Err(XdccError::TransferFailed(format!(
+            "Mirror serves {}, not {}",
+            if served.is_empty() {
+                "no file name".to_string()
+            } else {
+                served.join(" / ")
+            },
+            name
+        )));
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if content_type.to_ascii_lowercase().starts_with("text/html") {
+        return Err(XdccError::TransferFailed(
+            "Mirror sent a web page instead of the file".into(),
+        ));
+    }
+    let content_length = response.content_length();
+    if let (Some(expected), Some(len)) = (config.expected_size, content_length) {
+        if !similar_size(expected, len) {
+            return Err(XdccError::TransferFailed(format!(
+                "Mirror file is {} bytes, the pack about {}",
+                len, expected
+            )));
+        }
+    }
+
+    let size = content_length.or(config.expected_size).unwrap_or(0);
+    if let Err(reason) = config.size_limits.check(size) {
+        return Err(XdccError::SizeRejected(reason));
+    }
+
+    // Like DCC: a partial file is restarted, a finished one is kept
+    tokio::fs::create_dir_all(&config.download_dir).await.ok();
+    let dir = Path::new(&config.download_dir);
+    let mut safe_filename = config.filename_policy.sanitize(&name);
+    let mut file_path = dir.join(&safe_filename);
+    let existing = tokio::fs::metadata(&file_path).await.map(|m| m.len());
+    if existing.is_ok_and(|len| len > 0 && len >= size) {
+        file_path = unique_path(dir, &safe_filename);
+        safe_filename = file_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(safe_filename);
+        let _ = tx.send(XdccEvent::SavingAs(safe_filename.clone())).await;
+    }
+    let _ = tx
+        .send(XdccEvent::MirrorDownload {
+            url: mirror.to_string(),
+            filename: safe_filename,
+            size,
+        })
+        .await;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&file_path)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::NotFound => {
+                XdccError::FatalIo(format!("Failed to create/open file: {}", e))
+            }
+            _ => XdccError::DiskWrite(format!("Failed to create/open file: {}", e)),
+        })?;
+
+    let start_time = Instant::now();
+    let mut last_update = Instant::now();
+    let mut bytes_since_update: u64 = 0;
+    let mut downloaded: u64 = 0;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| XdccError::TransferFailed(format!("Read error: {}", e)))?
+    {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| XdccError::DiskWrite(format!("Write error: {}", e)))?;
+        downloaded += chunk.len() as u64;
+        bytes_since_update += chunk.len() as u64;
+
+        config.throttle.consume(chunk.len() as u64).await;
+
+        let elapsed = last_update.elapsed();
+        if elapsed.as_millis() >= 500 {
+            // Nobody is listening anymore: the download was cancelled
+            if tx.is_closed() {
+                return Err(XdccError::TransferFailed("Transfer cancelled".into()));
+            }
+            let _ = tx
+                .send(XdccEvent::Progress {
+                    downloaded,
+                    received: downloaded,
+                    total: size,
+                    speed: bytes_since_update as f64 / elapsed.as_secs_f64(),
+                })
+                .await;
+            last_update = Instant::now();
+            bytes_since_update = 0;
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| XdccError::DiskWrite(format!("Write error: {}", e)))?;
+
+    if let Some(expected) = content_length.filter(|len| *len != downloaded) {
+        return Err(XdccError::TransferFailed(format!(
+            "Mirror sent {} of {} bytes",
+            downloaded, expected
+        )));
+    }
+    if let Some(expected) = config
+        .expected_size
+        .filter(|expected| !similar_size(*expected, downloaded))
+    {
+        return Err(XdccError::TransferFailed(format!(
+            "Mirror sent {} bytes, the pack is about {}",
+            downloaded, expected
+        )));
+    }
+    let total_time = start_time.elapsed().as_secs_f64();
+    let _ = tx
+        .send(XdccEvent::Progress {
+            downloaded,
+            received: downloaded,
+            total: size,
+            speed: if total_time > 0.0 {
+                downloaded as f64 / total_time
+            } else {
+                0.0
+            },
+        })
+        .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_file_name_from_url() {
+        assert_eq!(
+            file_name_from_url("https://dl.example.org/packs/Show%20S01E02.mkv?token=1"),
+            Some("Show S01E02.mkv".to_string())
+        );
+        assert_eq!(file_name_from_url("https://dl.example.org/"), None);
+        assert!(is_supported("HTTPS://dl.example.org/a.mkv"));
+        assert!(!is_supported("ftp://ftp.example.org/a.mkv"));
+    }
+
+    #[test]
+    fn test_mirror_checks() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(disposition_name(&headers), None);
+        headers.insert(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"a b.bin\"".parse().unwrap(),
+        );
+        assert_eq!(disposition_name(&headers).as_deref(), Some("a b.bin"));
+        headers.insert(
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"a_b.bin\"; filename*=UTF-8''a%20b.bin"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(disposition_name(&headers).as_deref(), Some("a b.bin"));
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.5",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+        let url = |u: &str| reqwest::Url::parse(u).unwrap();
+        assert!(check_host(&url("http://127.0.0.1:8080/a.bin")).is_err());
+        assert!(check_host(&url("http://[::1]/a.bin")).is_err());
+        assert!(check_host(&url("https://dl.example.org/a.bin")).is_ok());
+    }
+
+    /// Serve `response` to one request on a local port
+    async fn serve(response: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket.write_all(response).await.unwrap();
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_mirror_download() {
+        let port =
+            serve(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello").await;
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = XdccConfig {
+            download_dir: temp.path().to_string_lossy().to_string(),
+            expected_filename: Some("a b.bin".to_string()),
+            expected_size: Some(5),
+            ..XdccConfig::default()
+        };
+        let (tx, mut rx) = mpsc::channel(100);
+        let url = format!("http://127.0.0.1:{}/files/a%20b.bin", port);
+        download(&url, &config, &tx, false).await.unwrap();
+        drop(tx);
+        let mut started = None;
+        while let Some(event) = rx.recv().await {
+            if let XdccEvent::MirrorDownload { filename, size, .. } = event {
+                started = Some((filename, size));
+            }
+        }
+        assert_eq!(started, Some(("a b.bin".to_string(), 5)));
+        assert_eq!(
+            std::fs::read(temp.path().join("a b.bin")).unwrap(),
+            b"hello"
+        );
+
+        // Bots can't point Botarr at the local machine
+        let mut rx = start_download(url, config);
+        loop {
+            match rx.recv().await.unwrap() {
+                XdccEvent::Error(e) => {
+                    assert!(e.to_string().contains("not a public address"));
+                    break;
+                }
+                XdccEvent::Completed => panic!("fetched a local mirror"),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_mismatch() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = XdccConfig {
+            download_dir: temp.path().to_string_lossy().to_string(),
+            expected_filename: Some("Show.S01E01.mkv".to_string()),
+            expected_size: Some(350 * 1024 * 1024),
+            ..XdccConfig::default()
+        };
+        let (tx, _rx) = mpsc::channel(100);
+
+        // A landing page named after nothing
+        let port = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        )
+        .await;
+        let url = format!("http://127.0.0.1:{}/download?id=5", port);
+        let e = download(&url, &config, &tx, false).await.unwrap_err();
+        assert!(e.to_string().contains("not Show.S01E01.mkv"), "{}", e);
+
+        // The right name, but a web page
+        let port = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Disposition: inline; filename=\"Show.S01E01.mkv\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        )
+        .await;
+        let url = format!("http://127.0.0.1:{}/download?id=5", port);
+        let e = download(&url, &config, &tx, false).await.unwrap_err();
+        assert!(e.to_string().contains("web page"), "{}", e);
+
+        // The right name, but not the announced size
+        let port =
+            serve(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello").await;
+        let url = format!("http://127.0.0.1:{}/Show.S01E01.mkv", port);
+        let e = download(&url, &config, &tx, false).await.unwrap_err();
+        assert!(e.to_string().contains("5 bytes"), "{}", e);
+        assert!(!temp.path().join("Show.S01E01.mkv").exists());
+    }
+}
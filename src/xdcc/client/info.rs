@@ -0,0 +1,158 @@
+//! XDCC INFO Responses
+//!
+//! Parses the reply to `xdcc info #N` (iroffer/XDCC Parser style) so the pack
+//! can be checked against what the search result claimed before downloading.
+
+use crate::xdcc::search::parse_size;
+use serde::{Deserialize, Serialize};
+
+/// Pack details reported by a bot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PackInfo {
+    pub filename: Option<String>,
+    pub size: Option<u64>,
+    pub crc32: Option<String>,
+    pub md5: Option<String>,
+    /// Why the pack doesn't match the expected file, if it doesn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mismatch: Option<String>,
+}
+
+impl PackInfo {
+    /// Feed one line of the bot's response, returning whether it was recognized
+    pub fn parse_line(&mut self, line: &str) -> bool {
+        let line = strip_formatting(line);
+        let line = line.trim().trim_start_matches('*').trim();
+        let Some((key, value)) = line.split_once(|c: char| c.is_whitespace() || c == ':') else {
+            return false;
+        };
+        let value = value.trim_start_matches(':').trim();
+        if value.is_empty() {
+            return false;
+        }
+
+        match key.to_lowercase().as_str() {
+            "filename" => self.filename = Some(value.to_string()),
+            "filesize" => {
+                // "367001600 [350MB]" or just "350MB"
+                let first = value.split_whitespace().next().unwrap_or_default();
+                self.size = first
+                    .parse()
+                    .ok()
+                    .or_else(|| parse_size(first))
+                    .or_else(|| parse_size(value));
+            }
+            "crc32" | "crc" => self.crc32 = Some(value.to_uppercase()),
+            "md5sum" | "md5" => self.md5 = Some(value.to_lowercase()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Whether the response contained enough to compare
+    pub fn has_details(&self) -> bool {
+        self.filename.is_some() || self.size.is_some()
+    }
+
+    /// Whether all fields bots usually send have arrived
+    pub fn is_complete(&self) -> bool {
+        self.filename.is_some()
+            && self.size.is_some()
+            && (self.crc32.is_some() || self.md5.is_some())
+    }
+
+    /// Compare against the expected filename and size, recording any mismatch.
+    /// Search sizes are rounded, so sizes within 2% (or 1 MiB) are accepted.
+    pub fn check(&mut self, expected_filename: Option<&str>, expected_size: Option<u64>) {
+        let mut problems = Vec::new();
+        if let (Some(expected), Some(actual)) = (expected_filename, self.filename.as_deref()) {
+            if !same_name(expected, actual) {
+                problems.push(format!("filename is '{}', expected '{}'", actual, expected));
+            }
+        }
+        if let (Some(expected), Some(actual)) = (expected_size, self.size) {
+            if !similar_size(expected, actual) {
+                problems.push(format!(
+                    "size is {} bytes, expected about {}",
+                    actual, expected
+                ));
+            }
+        }
+        self.mismatch = (!problems.is_empty()).then(|| problems.join("; "));
+    }
+}
+
+/// Remove mIRC color/bold codes
+fn strip_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x03' => {
+                // Color code: up to two digits, optionally ",NN"
+                for _ in 0..2 {
+                    chars.next_if(|c| c.is_ascii_digit());
+                }
+                if chars.peek() == Some(&',') {
+                    chars.next();
+                    for _ in 0..2 {
+                        chars.next_if(|c| c.is_ascii_digit());
+                    }
+                }
+            }
+            '\x02' | '\x0f' | '\x16' | '\x1d' | '\x1f' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Filenames compared case-insensitively, ignoring separators bots tend to rewrite
+fn comparable_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether two file names name the same file, as bots tend to rewrite them
+pub fn same_name(a: &str, b: &str) -> bool {
+    comparable_name(a) == comparable_name(b)
+}
+
+/// Whether `actual` bytes is the announced size `expected`. Announced sizes
+/// are rounded, so sizes within 2% (or 1 MiB) are accepted.
+pub fn similar_size(expected: u64, actual: u64) -> bool {
+    expected.abs_diff(actual) <= (expected / 50).max(1024 * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pack_info() {
+        let mut info = PackInfo::default();
+        for line in [
+            "Pack Info for Pack #5:",
+            " Filename       Show.S01E01.1080p.mkv",
+            " Filesize       367001600 [350MB]",
+            " Last Modified  2024-01-01 10:00 UTC",
+            " \x02md5sum\x02         d41d8cd98f00b204e9800998ecf8427e",
+            " crc32          abcd1234",
+        ] {
+            info.parse_line(line);
+        }
+        assert_eq!(info.filename.as_deref(), Some("Show.S01E01.1080p.mkv"));
+        assert_eq!(info.size, Some(367001600));
+        assert_eq!(info.crc32.as_deref(), Some("ABCD1234"));
+        assert!(info.is_complete());
+
+        info.check(Some("show s01e01 1080p.mkv"), Some(350 * 1024 * 1024));
+        assert_eq!(info.mismatch, None);
+
+        info.check(Some("Other.Show.S01E01.mkv"), Some(700 * 1024 * 1024));
+        let mismatch = info.mismatch.unwrap();
+        assert!(mismatch.contains("filename") && mismatch.contains("size"));
+    }
+}
@@ -3,14 +3,36 @@
 //! Handles IRC connection, channel joining, and XDCC transfer requests.
 
 pub mod dcc;
+pub mod http;
+pub mod info;
+pub mod offers;
+pub mod pool;
+pub mod throttle;
 use super::{XdccError, XdccUrl};
+use crate::config::{fallback_host, SizeLimits};
+use crate::filename::FilenamePolicy;
+use crate::plugin::hooks::{self, HookOutcome, ScriptHooks};
+use info::PackInfo;
+use offers::{OfferQueue, PendingOffer};
+use pool::ConnectionPool;
 use std::collections::HashMap;
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
+use throttle::Throttle;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc;
 use tokio::time::timeout;
-use tokio_native_tls::TlsStream;
+
+/// Read half of an IRC connection, plain or TLS
+type IrcReader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+/// Write half of an IRC connection, plain or TLS
+type IrcWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// How long a held DCC offer waits for a decision before it is dropped
+const OFFER_APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long to wait for NickServ and the server at each step of reclaiming the nick
+const NICK_RECLAIM_WAIT: Duration = Duration::from_secs(10);
+/// How long to wait for NickServ to confirm IDENTIFY before joining anyway
+const IDENTIFY_WAIT: Duration = Duration::from_secs(15);
 
 /// Events emitted during XDCC transfer
 #[derive(Debug, Clone)]
@@ -26,18 +48,56 @@ pub enum XdccEvent {
         port: u16,
         size: u64,
     },
+    /// A download from an HTTP mirror started, in place of a DCC offer
+    MirrorDownload {
+        url: String,
+        filename: String,
+        size: u64,
+    },
     Progress {
         downloaded: u64,
+        /// Bytes received over this connection, without a resumed offset
+        received: u64,
         total: u64,
         speed: f64,
     },
     Completed,
     IrcMessage(String, String, String, String), // network, channel, nick, message
     IrcNotice(String, String),                  // nick, message
+    /// Response to the `xdcc info` pre-check, with any mismatch filled in
+    PackInfo(PackInfo),
+    /// Name the file is saved under when a finished file already had the offered one
+    SavingAs(String),
+    /// How the network was resolved, and the `host:port` connected to
+    Resolved(NetworkSource, String),
     Error(XdccError),
     Log(String),
 }
 
+/// Where [`XdccConfig::resolve_network`] found a network's settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkSource {
+    /// A configured network, by its key
+    Configured(String),
+    /// The name looks like a hostname and is used as-is
+    Hostname,
+    /// Guessed from a fallback host template, e.g. `irc.{name}.net`
+    Heuristic(String),
+    /// The mock server of simulation mode
+    Simulated,
+}
+
+impl std::fmt::Display for NetworkSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkSource::Configured(key) => write!(f, "configured:{}", key),
+            NetworkSource::Hostname => write!(f, "hostname"),
+            NetworkSource::Heuristic(template) => write!(f, "heuristic:{}", template),
+            NetworkSource::Simulated => write!(f, "simulated"),
+        }
+    }
+}
+
 /// Configuration for XDCC client
 /// Network configuration alias: (host, port, ssl, autojoin_channels, join_delay_secs, nickserv_password)
 pub type NetworkConfig = (String, u16, bool, Vec<String>, u64, String);
@@ -60,12 +120,60 @@ pub struct XdccConfig {
     pub download_dir: String,
     /// Network name -> (host, port, ssl, autojoin_channels, join_delay_secs, nickserv_password)
     pub networks: HashMap<String, NetworkConfig>,
+    /// Host templates tried for networks that are neither mapped nor a
+    /// hostname; empty to refuse them
+    pub network_fallback_hosts: Vec<String>,
     /// Enable SOCKS5 proxy
     pub proxy_enabled: bool,
     /// SOCKS5 proxy URL (e.g., socks5://127.0.0.1:1080)
     pub proxy_url: String,
     /// Enable DCC Resume
     pub resume_enabled: bool,
+    /// How offered file names are made safe to save
+    pub filename_policy: FilenamePolicy,
+    /// Offers outside this size range are rejected
+    pub size_limits: SizeLimits,
+    /// Send `xdcc info` and compare the reply with the expected file before requesting
+    pub info_check: bool,
+    /// Fail the transfer instead of only flagging it when the pack info doesn't match
+    pub info_abort_on_mismatch: bool,
+    /// Filename the search result claimed for the pack
+    pub expected_filename: Option<String>,
+    /// Size the search result claimed for the pack
+    pub expected_size: Option<u64>,
+    /// Local address or interface outgoing sockets are bound to (empty for default)
+    pub outbound_bind: String,
+    /// Tunnel DCC connections through the proxy too and never connect directly
+    pub require_proxy: bool,
+    /// Skip DCC ACKs even if the bot didn't announce turbo mode
+    pub turbo_dcc: bool,
+    /// Timeout of each DCC connection attempt in seconds
+    pub dcc_connect_timeout_secs: u64,
+    /// Extra DCC connection attempts after the first one fails
+    pub dcc_connect_retries: u32,
+    /// Accept passive (reverse) DCC offers by listening for the bot
+    pub passive_dcc: bool,
+    /// Ports passive DCC listens on
+    pub dcc_ports: (u16, u16),
+    /// Address bots are told to connect to for passive DCC. Empty uses the
+    /// local address of the IRC connection.
+    pub dcc_external_ip: String,
+    /// Hold unsolicited or unexpected DCC offers for approval instead of connecting
+    pub manual_offer_approval: bool,
+    /// Where held offers wait for a decision
+    pub offers: OfferQueue,
+    /// Transfer this session downloads for, listed with held offers
+    pub transfer_id: String,
+    /// Scripts consulted on DCC offers
+    pub hooks: ScriptHooks,
+    /// Download speed limits, global and of this transfer
+    pub throttle: Throttle,
+    /// Shared connections to use instead of opening one for this download
+    pub pool: Option<ConnectionPool>,
+    /// How long a shared connection stays open without downloads
+    pub pool_idle_secs: u64,
+    /// Mock server every network resolves to, in simulation mode
+    pub simulated_server: Option<std::net::SocketAddr>,
 }
 
 impl Default for XdccConfig {
@@ -85,47 +193,214 @@ impl Default for XdccConfig {
             timeout_secs: 120,
             download_dir: "./downloads".to_string(),
             networks: HashMap::new(),
+            network_fallback_hosts: vec!["irc.{name}.net".to_string()],
             proxy_enabled: false,
             proxy_url: String::new(),
             resume_enabled: true,
+            filename_policy: FilenamePolicy::default(),
+            size_limits: SizeLimits::default(),
+            info_check: false,
+            info_abort_on_mismatch: false,
+            expected_filename: None,
+            expected_size: None,
+            outbound_bind: String::new(),
+            require_proxy: false,
+            turbo_dcc: false,
+            dcc_connect_timeout_secs: 30,
+            dcc_connect_retries: 2,
+            passive_dcc: false,
+            dcc_ports: (49152, 65535),
+            dcc_external_ip: String::new(),
+            manual_offer_approval: false,
+            offers: OfferQueue::default(),
+            transfer_id: String::new(),
+            hooks: ScriptHooks::default(),
+            throttle: Throttle::default(),
+            pool: None,
+            pool_idle_secs: 300,
+            simulated_server: None,
         }
     }
 }
 
 impl XdccConfig {
-    /// Resolve network name to (host, port, use_ssl, autojoin_channels, join_delay_secs, nickserv_password)
-    pub fn resolve_network(&self, network: &str) -> NetworkConfig {
+    /// Client settings from the app config for a download from `bot`; the
+    /// per-transfer fields keep their defaults
+    pub fn from_app_config(
+        app_config: &crate::config::AppConfig,
+        download_dir: String,
+        bot: &str,
+    ) -> Self {
+        let config = Self {
+            nickname: app_config.nickname.clone(),
+            username: app_config.username.clone(),
+            realname: app_config.realname.clone(),
+            use_ssl: app_config.use_ssl,
+            connect_timeout_secs: app_config.connect_timeout,
+            timeout_secs: app_config.general_timeout,
+            download_dir,
+            networks: app_config
+                .networks
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.clone(),
+                        (
+                            v.host.clone(),
+                            v.port,
+                            v.ssl,
+                            v.autojoin_channels.clone(),
+                            v.join_delay_secs,
+                            v.nickserv_password.clone(),
+                        ),
+                    )
+                })
+                .collect(),
+            network_fallback_hosts: app_config.network_fallback_hosts.clone(),
+            proxy_enabled: app_config.proxy_enabled,
+            proxy_url: app_config.proxy_url.clone(),
+            resume_enabled: app_config.resume_enabled,
+            filename_policy: FilenamePolicy::from_config(app_config),
+            size_limits: app_config.size_limits(None),
+            info_check: app_config.xdcc_info_check,
+            info_abort_on_mismatch: app_config.xdcc_info_abort_on_mismatch,
+            outbound_bind: app_config.outbound_bind.clone(),
+            require_proxy: app_config.require_proxy,
+            turbo_dcc: app_config
+                .turbo_dcc_bots
+                .iter()
+                .any(|b| b.eq_ignore_ascii_case(bot)),
+            dcc_connect_timeout_secs: app_config.dcc_connect_timeout,
+            dcc_connect_retries: app_config.dcc_connect_retries,
+            passive_dcc: app_config.passive_dcc,
+            dcc_ports: (app_config.dcc_port_min, app_config.dcc_port_max),
+            dcc_external_ip: app_config.dcc_external_ip.clone(),
+            manual_offer_approval: app_config.manual_offer_approval,
+            pool_idle_secs: app_config.shared_irc_idle_secs,
+            simulated_server: crate::simulate::server(),
+            ..Self::default()
+        };
+        if config.simulated_server.is_none() {
+            return config;
+        }
+        // The mock server is local and offers active DCC only
+        Self {
+            proxy_enabled: false,
+            require_proxy: false,
+            outbound_bind: String::new(),
+            passive_dcc: false,
+            ..config
+        }
+    }
+
+    /// Outbound connection settings (bind address and proxy)
+    pub fn outbound(&self) -> crate::net::Outbound {
+        crate::net::Outbound {
+            bind: self.outbound_bind.clone(),
+            proxy_url: if self.proxy_enabled {
+                self.proxy_url.clone()
+            } else {
+                String::new()
+            },
+            require_proxy: self.require_proxy,
+        }
+    }
+
+    /// Resolve network name to (host, port, use_ssl, autojoin_channels, join_delay_secs, nickserv_password),
+    /// `None` for an unmapped name when there is no fallback host template
+    pub fn resolve_network(&self, network: &str) -> Option<NetworkConfig> {
+        self.resolve_network_source(network)
+            .map(|(resolved, _)| resolved)
+    }
+
+    /// [`resolve_network`](Self::resolve_network), along with how the
+    /// settings were found. Only the first fallback template is used; see
+    /// [`lookup_network`](Self::lookup_network).
+    pub fn resolve_network_source(&self, network: &str) -> Option<(NetworkConfig, NetworkSource)> {
+        if let Some(addr) = self.simulated_server {
+            return Some((
+                (
+                    addr.ip().to_string(),
+                    addr.port(),
+                    false,
+                    Vec::new(),
+                    0,
+                    String::new(),
+                ),
+                NetworkSource::Simulated,
+            ));
+        }
+
         // Check explicit mapping (case-insensitive)
         for (key, value) in &self.networks {
             if key.eq_ignore_ascii_case(network) || value.0.eq_ignore_ascii_case(network) {
-                return value.clone();
+                return Some((value.clone(), NetworkSource::Configured(key.clone())));
             }
         }
 
+        let port = if self.use_ssl { 6697 } else { 6667 };
         // If it looks like a hostname (contains a dot), use as-is
         if network.contains('.') {
-            let port = if self.use_ssl { 6697 } else { 6667 };
-            return (
-                network.to_string(),
+            return Some((
+                (
+                    network.to_string(),
+                    port,
+                    self.use_ssl,
+                    Vec::new(),
+                    0,
+                    String::new(),
+                ),
+                NetworkSource::Hostname,
+            ));
+        }
+
+        let template = self.network_fallback_hosts.first()?;
+        Some((
+            (
+                fallback_host(template, network),
                 port,
                 self.use_ssl,
                 Vec::new(),
                 0,
                 String::new(),
-            );
+            ),
+            NetworkSource::Heuristic(template.clone()),
+        ))
+    }
+
+    /// Resolve the network of a download. With several fallback templates
+    /// the first whose host is found in DNS is used, or the first template
+    /// when none is or a proxy resolves names for us.
+    pub async fn lookup_network(
+        &self,
+        network: &str,
+    ) -> Result<(NetworkConfig, NetworkSource), XdccError> {
+        let (resolved, source) = self.resolve_network_source(network).ok_or_else(|| {
+            XdccError::InvalidUrl(format!(
+                "Network {} is not configured and there is no fallback host; add it to networks",
+                network
+            ))
+        })?;
+        if !matches!(source, NetworkSource::Heuristic(_))
+            || self.network_fallback_hosts.len() < 2
+            || !self.outbound().proxy_url.is_empty()
+        {
+            return Ok((resolved, source));
         }
 
-        // Try common heuristics
-        let lower = network.to_lowercase();
-        let port = if self.use_ssl { 6697 } else { 6667 };
-        (
-            format!("irc.{}.net", lower),
-            port,
-            self.use_ssl,
-            Vec::new(),
-            0,
-            String::new(),
-        )
+        for template in &self.network_fallback_hosts {
+            let host = fallback_host(template, network);
+            let lookup = tokio::net::lookup_host((host.as_str(), resolved.1));
+            let found = timeout(Duration::from_secs(self.connect_timeout_secs), lookup)
+                .await
+                .is_ok_and(|r| r.is_ok_and(|mut addrs| addrs.next().is_some()));
+            if found {
+                let mut resolved = resolved;
+                resolved.0 = host;
+                return Ok((resolved, NetworkSource::Heuristic(template.clone())));
+            }
+        }
+        Ok((resolved, source))
     }
 }
 
@@ -160,14 +435,71 @@ impl XdccClient {
 
     async fn download_task(
         url: XdccUrl,
-        config: XdccConfig,
+        mut config: XdccConfig,
         tx: mpsc::Sender<XdccEvent>,
     ) -> Result<(), XdccError> {
         let _ = tx.send(XdccEvent::Connecting).await;
 
         // Resolve network to (host, port, use_ssl, autojoin, delay, nickserv_password)
+        let (network, source) = config.lookup_network(&url.network).await?;
         let (host, port, use_ssl, autojoin_channels, join_delay_secs, nickserv_password) =
-            config.resolve_network(&url.network);
+            network.clone();
+        let _ = tx
+            .send(XdccEvent::Resolved(source, format!("{}:{}", host, port)))
+            .await;
+
+        if let Some(pool) = config.pool.clone() {
+            let lease = pool.lease(&url, &network, &config, &tx).await?;
+            if config.dcc_external_ip.is_empty() && config.outbound().proxy_url.is_empty() {
+                config.dcc_external_ip = lease.local_ip.unwrap_or_default();
+            }
+            let _ = tx.send(XdccEvent::Connected).await;
+            // Time already spent in the channel counts towards the join delay;
+            // the shared connection has identified with NickServ
+            let join_delay_secs = join_delay_secs.saturating_sub(lease.joined_for.as_secs());
+            let (reader, writer) = tokio::io::split(lease.stream);
+            return Self::irc_session_inner(
+                BufReader::new(reader),
+                writer,
+                url,
+                config,
+                tx,
+                autojoin_channels,
+                join_delay_secs,
+                String::new(),
+            )
+            .await;
+        }
+
+        let (reader, writer, local_ip) =
+            Self::connect_irc(&config, &host, port, use_ssl, &tx).await?;
+        // Without a proxy in between, bots reach us where the IRC server sees us
+        if config.dcc_external_ip.is_empty() && config.outbound().proxy_url.is_empty() {
+            config.dcc_external_ip = local_ip.unwrap_or_default();
+        }
+        let _ = tx.send(XdccEvent::Connected).await;
+        Self::irc_session_inner(
+            reader,
+            writer,
+            url,
+            config,
+            tx,
+            autojoin_channels,
+            join_delay_secs,
+            nickserv_password,
+        )
+        .await
+    }
+
+    /// Open the connection to an IRC server, over TLS if `use_ssl`. Returns
+    /// the read and write halves and the local address of the connection.
+    async fn connect_irc(
+        config: &XdccConfig,
+        host: &str,
+        port: u16,
+        use_ssl: bool,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<(IrcReader, IrcWriter, Option<String>), XdccError> {
         let server = format!("{}:{}", host, port);
 
         tracing::info!("Connecting to IRC server: {} (SSL: {})", server, use_ssl);
@@ -179,20 +511,8 @@ impl XdccClient {
             .await;
 
         // Connect with timeout (use shorter connect timeout for fast failure)
-        let connect_future = async {
-            if config.proxy_enabled && !config.proxy_url.is_empty() {
-                // Parse proxy string "host:port" or "socks5://host:port"
-                let proxy_addr = config.proxy_url.trim_start_matches("socks5://");
-                tracing::info!("Connecting via SOCKS5 proxy: {} -> {}", proxy_addr, server);
-
-                match tokio_socks::tcp::Socks5Stream::connect(proxy_addr, server.as_str()).await {
-                    Ok(s) => Ok(s.into_inner()), // Unwrap to get the raw tunnelled TcpStream
-                    Err(e) => Err(std::io::Error::other(e)),
-                }
-            } else {
-                TcpStream::connect(&server).await
-            }
-        };
+        let outbound = config.outbound();
+        let connect_future = outbound.connect(&server, true);
 
         let tcp_stream = timeout(
             Duration::from_secs(config.connect_timeout_secs),
@@ -208,100 +528,29 @@ impl XdccClient {
         .map_err(|e| XdccError::ConnectionFailed(format!("Connection failed: {}", e)))?;
 
         tracing::info!("TCP connected to {}", server);
+        let local_ip = tcp_stream.local_addr().ok().map(|a| a.ip().to_string());
         let _ = tx.send(XdccEvent::Log("TCP connected".to_string())).await;
 
         // Perform TLS handshake if SSL is enabled
-        if use_ssl {
-            tracing::info!("Performing TLS handshake...");
-            let connector = native_tls::TlsConnector::builder()
-                .danger_accept_invalid_certs(true) // Some IRC servers have self-signed certs
-                .build()
-                .map_err(|e| XdccError::ConnectionFailed(format!("TLS setup failed: {}", e)))?;
-
-            let connector = tokio_native_tls::TlsConnector::from(connector);
-            let tls_stream = connector
-                .connect(&host, tcp_stream)
-                .await
-                .map_err(|e| XdccError::ConnectionFailed(format!("TLS handshake failed: {}", e)))?;
-
-            tracing::info!("TLS connection established to {}", server);
-            let _ = tx.send(XdccEvent::Connected).await;
-
-            // Run IRC session over TLS
-            Self::irc_session_tls(
-                tls_stream,
-                url,
-                config,
-                tx,
-                autojoin_channels,
-                join_delay_secs,
-                nickserv_password,
-            )
-            .await
-        } else {
-            let _ = tx.send(XdccEvent::Connected).await;
-            // Run IRC session over plain TCP
-            Self::irc_session_plain(
-                tcp_stream,
-                url,
-                config,
-                tx,
-                autojoin_channels,
-                join_delay_secs,
-                nickserv_password,
-            )
-            .await
+        if !use_ssl {
+            let (reader, writer) = tcp_stream.into_split();
+            return Ok((BufReader::new(Box::new(reader)), Box::new(writer), local_ip));
         }
-    }
+        tracing::info!("Performing TLS handshake...");
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true) // Some IRC servers have self-signed certs
+            .build()
+            .map_err(|e| XdccError::ConnectionFailed(format!("TLS setup failed: {}", e)))?;
+
+        let connector = tokio_native_tls::TlsConnector::from(connector);
+        let tls_stream = connector
+            .connect(host, tcp_stream)
+            .await
+            .map_err(|e| XdccError::ConnectionFailed(format!("TLS handshake failed: {}", e)))?;
 
-    /// IRC session over plain TCP
-    async fn irc_session_plain(
-        stream: TcpStream,
-        url: XdccUrl,
-        config: XdccConfig,
-        tx: mpsc::Sender<XdccEvent>,
-        autojoin_channels: Vec<String>,
-        join_delay_secs: u64,
-        nickserv_password: String,
-    ) -> Result<(), XdccError> {
-        let (reader, writer) = stream.into_split();
-        let reader = BufReader::new(reader);
-        Self::irc_session_inner(
-            reader,
-            writer,
-            url,
-            config,
-            tx,
-            autojoin_channels,
-            join_delay_secs,
-            nickserv_password,
-        )
-        .await
-    }
-
-    /// IRC session over TLS
-    async fn irc_session_tls(
-        stream: TlsStream<TcpStream>,
-        url: XdccUrl,
-        config: XdccConfig,
-        tx: mpsc::Sender<XdccEvent>,
-        autojoin_channels: Vec<String>,
-        join_delay_secs: u64,
-        nickserv_password: String,
-    ) -> Result<(), XdccError> {
-        let (reader, writer) = tokio::io::split(stream);
-        let reader = BufReader::new(reader);
-        Self::irc_session_inner(
-            reader,
-            writer,
-            url,
-            config,
-            tx,
-            autojoin_channels,
-            join_delay_secs,
-            nickserv_password,
-        )
-        .await
+        tracing::info!("TLS connection established to {}", server);
+        let (reader, writer) = tokio::io::split(tls_stream);
+        Ok((BufReader::new(Box::new(reader)), Box::new(writer), local_ip))
     }
 
     /// Core IRC session logic (works with any AsyncRead/AsyncWrite)
@@ -336,13 +585,42 @@ impl XdccClient {
         let mut joined_at: Option<std::time::Instant> = None;
         let mut requested_at: Option<std::time::Instant> = None;
         let mut nick_retries: u32 = 0;
+        // Registered nick held by a ghost session, reclaimed after welcome
+        let mut ghosted_nick: Option<String> = None;
         const MAX_NICK_RETRIES: u32 = 3;
+        // XDCC INFO pre-check state
+        let mut info_requested_at: Option<std::time::Instant> = None;
+        let mut pack_info = PackInfo::default();
+        const INFO_WAIT_SECS: u64 = 10;
 
         loop {
             // Check if we should request NOW (before reading)
             if joined && !requested {
                 if let Some(t) = joined_at {
-                    if t.elapsed().as_secs() >= join_delay_secs {
+                    if t.elapsed().as_secs() >= join_delay_secs
+                        && config.info_check
+                        && info_requested_at.is_none()
+                    {
+                        info_requested_at = Some(std::time::Instant::now());
+                        let _ = tx
+                            .send(XdccEvent::Log(format!(
+                                "Checking pack #{} with xdcc info",
+                                url.slot
+                            )))
+                            .await;
+                        Self::send_raw(
+                            &mut writer,
+                            &format!("PRIVMSG {} :xdcc info #{}", url.bot, url.slot),
+                        )
+                        .await?;
+                    }
+                    let info_pending = info_requested_at.is_some_and(|at| {
+                        !pack_info.is_complete() && at.elapsed().as_secs() < INFO_WAIT_SECS
+                    });
+                    if t.elapsed().as_secs() >= join_delay_secs && !info_pending {
+                        if info_requested_at.is_some() {
+                            Self::verify_pack_info(&mut pack_info, &config, &tx).await?;
+                        }
                         requested = true;
                         requested_at = Some(std::time::Instant::now());
                         tracing::info!("Requesting pack #{} from {}", url.slot, url.bot);
@@ -378,7 +656,12 @@ impl XdccClient {
             buf.clear();
 
             let mut current_timeout = Duration::from_secs(config.timeout_secs);
-            if joined && !requested {
+            if let (Some(at), false) = (info_requested_at, requested) {
+                // Wake up when the info wait is over
+                current_timeout = Duration::from_secs(INFO_WAIT_SECS)
+                    .saturating_sub(at.elapsed())
+                    .max(Duration::from_millis(10));
+            } else if joined && !requested {
                 if let Some(t) = joined_at {
                     let elapsed = t.elapsed().as_secs();
                     if elapsed < join_delay_secs {
@@ -425,6 +708,15 @@ impl XdccClient {
 
                     // Handle 433 ERR_NICKNAMEINUSE — append _ and retry (limited)
                     if line.contains(" 433 ") {
+                        // Bots whitelist the registered nick, so if a ghost of an
+                        // earlier session holds it, connect under a fallback and
+                        // have NickServ free it
+                        if !nickserv_password.is_empty()
+                            && ghosted_nick.is_none()
+                            && current_nick == config.nickname
+                        {
+                            ghosted_nick = Some(current_nick.clone());
+                        }
                         nick_retries += 1;
                         if nick_retries > MAX_NICK_RETRIES {
                             return Err(XdccError::ConnectionFailed(format!(
@@ -465,16 +757,31 @@ impl XdccClient {
 
                     // Check for successful connection (001 numeric = RPL_WELCOME)
                     if line.contains(" 001 ") && !joined {
+                        if let Some(nick) = ghosted_nick.take() {
+                            if Self::recover_nick(
+                                &mut reader,
+                                &mut writer,
+                                &current_nick,
+                                &nick,
+                                &nickserv_password,
+                                &tx,
+                            )
+                            .await?
+                            {
+                                current_nick = nick;
+                            }
+                        }
+
                         // Identify with NickServ before joining if password is configured
                         if !nickserv_password.is_empty() {
-                            tracing::info!("Sending NickServ IDENTIFY");
-                            Self::send_raw(
+                            Self::identify(
+                                &mut reader,
                                 &mut writer,
-                                &format!("PRIVMSG NickServ :IDENTIFY {}", nickserv_password),
+                                &current_nick,
+                                &nickserv_password,
+                                &tx,
                             )
                             .await?;
-                            // Small delay to let the server process IDENTIFY before we JOIN
-                            tokio::time::sleep(Duration::from_secs(2)).await;
                         }
 
                         // Join autojoin channels
@@ -516,8 +823,86 @@ impl XdccClient {
                     }
 
                     // Check for DCC SEND (CTCP)
-                    if line.contains("DCC SEND") {
-                        if let Some(dcc_info) = dcc::parse_dcc_send(line) {
+                    if dcc::is_dcc_send(line) {
+                        if let Some(mut dcc_info) = dcc::parse_dcc_send(line) {
+                            if let Err(reason) = config.size_limits.check(dcc_info.size) {
+                                let sender = Self::sender_nick(line);
+                                if !sender.eq_ignore_ascii_case(&url.bot) {
+                                    let _ = tx
+                                        .send(XdccEvent::Log(format!(
+                                            "Ignoring offer of {} from {}: {}",
+                                            dcc_info.filename, sender, reason
+                                        )))
+                                        .await;
+                                    continue;
+                                }
+                                tracing::warn!(
+                                    "Rejecting offer of {}: {}",
+                                    dcc_info.filename,
+                                    reason
+                                );
+                                Self::send_raw(&mut writer, "QUIT :File size rejected").await?;
+                                return Err(XdccError::SizeRejected(format!(
+                                    "{} ({})",
+                                    reason, dcc_info.filename
+                                )));
+                            }
+                            let verdict = config
+                                .hooks
+                                .run(
+                                    hooks::ON_DCC_OFFER,
+                                    serde_json::json!({
+                                        "sender": Self::sender_nick(line),
+                                        "bot": url.bot,
+                                        "network": url.network,
+                                        "filename": dcc_info.filename,
+                                        "size": dcc_info.size,
+                                        "ip": dcc_info.ip,
+                                        "port": dcc_info.port,
+                                        "requested": requested,
+                                        "expected_filename": config.expected_filename,
+                                        "expected_size": config.expected_size,
+                                    }),
+                                )
+                                .await;
+                            if verdict == HookOutcome::Veto {
+                                let _ = tx
+                                    .send(XdccEvent::Log(format!(
+                                        "Offer of {} rejected by {} hook",
+                                        dcc_info.filename,
+                                        hooks::ON_DCC_OFFER
+                                    )))
+                                    .await;
+                                continue;
+                            }
+                            if config.manual_offer_approval && verdict != HookOutcome::Approve {
+                                let sender = Self::sender_nick(line);
+                                let concern = offers::offer_concern(
+                                    &sender,
+                                    &url.bot,
+                                    requested,
+                                    &dcc_info,
+                                    config.expected_filename.as_deref(),
+                                    config.expected_size,
+                                );
+                                if let Some(reason) = concern {
+                                    let approved = Self::await_approval(
+                                        &mut reader,
+                                        &mut writer,
+                                        &url,
+                                        &sender,
+                                        &dcc_info,
+                                        reason,
+                                        &config,
+                                        &tx,
+                                    )
+                                    .await?;
+                                    if !approved {
+                                        continue;
+                                    }
+                                }
+                            }
+                            dcc_info.turbo |= config.turbo_dcc;
                             tracing::info!(
                                 "Received DCC SEND: {} from {}:{} ({} bytes)",
                                 dcc_info.filename,
@@ -528,9 +913,8 @@ impl XdccClient {
 
                             // Check if file exists and we should resume
                             if config.resume_enabled {
-                                let safe_filename = dcc_info
-                                    .filename
-                                    .replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+                                let safe_filename =
+                                    config.filename_policy.sanitize(&dcc_info.filename);
                                 let file_path =
                                     std::path::Path::new(&config.download_dir).join(&safe_filename);
 
@@ -545,18 +929,17 @@ impl XdccClient {
                                             );
 
                                             // Send DCC RESUME
-                                            // Format: PRIVMSG bot :\x01DCC RESUME "filename" port position\x01
-                                            // Quote filename if it contains spaces
-                                            let quoted_filename = if dcc_info.filename.contains(' ')
-                                            {
-                                                format!("\"{}\"", dcc_info.filename)
-                                            } else {
-                                                dcc_info.filename.clone()
-                                            };
-                                            let resume_msg = format!(
-                                                "\x01DCC RESUME {} {} {}\x01",
-                                                quoted_filename, dcc_info.port, current_size
+                                            // Format: PRIVMSG bot :\x01DCC RESUME "filename" port position [token]\x01
+                                            let mut resume_msg = format!(
+                                                "\x01DCC RESUME {} {} {}",
+                                                dcc_info.quoted_filename(),
+                                                dcc_info.port,
+                                                current_size
                                             );
+                                            if let Some(token) = &dcc_info.token {
+                                                resume_msg.push_str(&format!(" {}", token));
+                                            }
+                                            resume_msg.push('\x01');
                                             Self::send_raw(
                                                 &mut writer,
                                                 &format!("PRIVMSG {} :{}", url.bot, resume_msg),
@@ -576,7 +959,7 @@ impl XdccClient {
                             let _ = tx
                                 .send(XdccEvent::DccSend {
                                     filename: dcc_info.filename.clone(),
-                                    ip: dcc_info.ip.clone(),
+                                    ip: dcc_info.ip.to_string(),
                                     port: dcc_info.port,
                                     size: dcc_info.size,
                                 })
@@ -589,7 +972,25 @@ impl XdccClient {
                                 .await;
 
                             // Start DCC transfer (new file)
-                            dcc::dcc_receive(dcc_info, &config.download_dir, 0, tx.clone()).await?;
+                            let (stream, dcc_info, _) = Self::connect_dcc(
+                                &mut reader,
+                                &mut writer,
+                                &url.bot,
+                                dcc_info,
+                                &config,
+                                &tx,
+                            )
+                            .await?;
+                            dcc::dcc_receive(
+                                stream,
+                                dcc_info,
+                                &config.download_dir,
+                                config.filename_policy,
+                                0,
+                                &config.throttle,
+                                tx.clone(),
+                            )
+                            .await?;
 
                             // Quit IRC after transfer
                             Self::send_raw(&mut writer, "QUIT :Transfer complete").await?;
@@ -608,17 +1009,30 @@ impl XdccClient {
                             let _ = tx
                                 .send(XdccEvent::DccSend {
                                     filename: resume_info.dcc_info.filename.clone(),
-                                    ip: resume_info.dcc_info.ip.clone(),
+                                    ip: resume_info.dcc_info.ip.to_string(),
                                     port: resume_info.dcc_info.port,
                                     size: resume_info.dcc_info.size,
                                 })
                                 .await;
 
-                            // Start DCC transfer (resume)
-                            dcc::dcc_receive(
+                            // Start DCC transfer (resume). A newer offer starts from scratch.
+                            let (stream, dcc_info, replaced) = Self::connect_dcc(
+                                &mut reader,
+                                &mut writer,
+                                &url.bot,
                                 resume_info.dcc_info,
+                                &config,
+                                &tx,
+                            )
+                            .await?;
+                            let offset = if replaced { 0 } else { resume_info.offset };
+                            dcc::dcc_receive(
+                                stream,
+                                dcc_info,
                                 &config.download_dir,
-                                resume_info.offset,
+                                config.filename_policy,
+                                offset,
+                                &config.throttle,
                                 tx.clone(),
                             )
                             .await?;
@@ -655,6 +1069,14 @@ impl XdccClient {
                     // Parse PRIVMSG and NOTICE for plugins
                     if line.contains("PRIVMSG") || line.contains("NOTICE") {
                         if let Some((nick, cmd, target, msg)) = Self::parse_irc_message(line) {
+                            // Bots answer xdcc info by notice or private message
+                            if info_requested_at.is_some()
+                                && !requested
+                                && nick.eq_ignore_ascii_case(&url.bot)
+                                && !target.starts_with('#')
+                            {
+                                pack_info.parse_line(&msg);
+                            }
                             if cmd == "PRIVMSG" && !msg.starts_with("\x01") {
                                 let _ = tx
                                     .send(XdccEvent::IrcMessage(
@@ -705,19 +1127,36 @@ impl XdccClient {
                         let _ = tx
                             .send(XdccEvent::DccSend {
                                 filename: resume_info.dcc_info.filename.clone(),
-                                ip: resume_info.dcc_info.ip.clone(),
+                                ip: resume_info.dcc_info.ip.to_string(),
                                 port: resume_info.dcc_info.port,
                                 size: resume_info.dcc_info.size,
                             })
                             .await;
                         // Start fresh download (offset 0)
-                        dcc::dcc_receive(resume_info.dcc_info, &config.download_dir, 0, tx.clone())
-                            .await?;
+                        let (stream, dcc_info, _) = Self::connect_dcc(
+                            &mut reader,
+                            &mut writer,
+                            &url.bot,
+                            resume_info.dcc_info,
+                            &config,
+                            &tx,
+                        )
+                        .await?;
+                        dcc::dcc_receive(
+                            stream,
+                            dcc_info,
+                            &config.download_dir,
+                            config.filename_policy,
+                            0,
+                            &config.throttle,
+                            tx.clone(),
+                        )
+                        .await?;
                         Self::send_raw(&mut writer, "QUIT :Transfer complete").await?;
                         let _ = tx.send(XdccEvent::Completed).await;
                         return Ok(());
                     }
-                    return Err(XdccError::Timeout(
+                    return Err(XdccError::NoResponse(
                         "Timed out waiting for DCC response from bot".into(),
                     ));
                 }
@@ -725,6 +1164,554 @@ impl XdccClient {
         }
     }
 
+    /// Open the DCC connection, retrying with backoff. While waiting, IRC is
+    /// still read so PINGs are answered and a newer offer from the bot (e.g. on
+    /// another port) replaces the current one. Returns whether it was replaced.
+    async fn connect_dcc<R, W>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        bot: &str,
+        mut info: dcc::DccInfo,
+        config: &XdccConfig,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<(tokio::net::TcpStream, dcc::DccInfo, bool), XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        let connect_timeout = Duration::from_secs(config.dcc_connect_timeout_secs.max(1));
+        let outbound = config.outbound();
+        let mut irc_open = true;
+        let mut replaced = false;
+        let mut attempt = 0;
+
+        loop {
+            let listener = if info.is_passive() {
+                Some(Self::offer_passive(writer, bot, &info, config).await?)
+            } else {
+                None
+            };
+            let connect = async {
+                match &listener {
+                    Some(listener) => dcc::dcc_accept(listener, connect_timeout).await,
+                    None => dcc::dcc_connect(&info, connect_timeout, &outbound).await,
+                }
+            };
+            let error =
+                match Self::watch_offers(reader, writer, bot, connect, &mut irc_open).await? {
+                    Ok(Ok(stream)) => return Ok((stream, info, replaced)),
+                    Ok(Err(e)) => e,
+                    Err(offer) => {
+                        Self::switch_offer(&mut info, offer, tx).await;
+                        replaced = true;
+                        continue;
+                    }
+                };
+
+            attempt += 1;
+            if attempt > config.dcc_connect_retries {
+                return Err(XdccError::TransferFailed(error));
+            }
+            let backoff = Duration::from_secs(2u64.pow(attempt).min(30));
+            let _ = tx
+                .send(XdccEvent::Log(format!(
+                    "{}, retrying in {}s ({}/{})",
+                    error,
+                    backoff.as_secs(),
+                    attempt,
+                    config.dcc_connect_retries
+                )))
+                .await;
+
+            let sleep = tokio::time::sleep(backoff);
+            if let Err(offer) =
+                Self::watch_offers(reader, writer, bot, sleep, &mut irc_open).await?
+            {
+                Self::switch_offer(&mut info, offer, tx).await;
+                replaced = true;
+            }
+        }
+    }
+
+    /// Listen for the sender of a passive offer and tell it where to connect
+    async fn offer_passive<W>(
+        writer: &mut W,
+        bot: &str,
+        info: &dcc::DccInfo,
+        config: &XdccConfig,
+    ) -> Result<tokio::net::TcpListener, XdccError>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        if !config.passive_dcc {
+            return Err(XdccError::FatalIo(
+                "Bot offered a passive DCC send, enable passive_dcc to accept it".into(),
+            ));
+        }
+        if config.require_proxy {
+            return Err(XdccError::FatalIo(
+                "Passive DCC needs a direct connection, which require_proxy forbids".into(),
+            ));
+        }
+        let ip: std::net::IpAddr = config.dcc_external_ip.parse().map_err(|_| {
+            XdccError::FatalIo(format!(
+                "Passive DCC needs an IP address in dcc_external_ip, have '{}'",
+                config.dcc_external_ip
+            ))
+        })?;
+        let bind = crate::net::resolve_bind_address(&config.outbound_bind)
+            .map_err(|e| XdccError::FatalIo(format!("Passive DCC bind failed: {}", e)))?
+            .or_else(|| {
+                ip.is_ipv6()
+                    .then_some(std::net::Ipv6Addr::UNSPECIFIED.into())
+            });
+        let listener = dcc::dcc_listen(config.dcc_ports, bind)
+            .await
+            .map_err(|e| XdccError::TransferFailed(format!("Passive DCC listen failed: {}", e)))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| XdccError::TransferFailed(format!("Passive DCC listen failed: {}", e)))?
+            .port();
+        tracing::info!(
+            "Passive DCC for {}: waiting on {}:{}",
+            info.filename,
+            ip,
+            port
+        );
+        Self::send_raw(
+            writer,
+            &format!("PRIVMSG {} :{}", bot, dcc::passive_reply(info, ip, port)),
+        )
+        .await?;
+        Ok(listener)
+    }
+
+    /// Run `fut` while reading IRC. Returns `Err(offer)` if the bot sent a new
+    /// DCC offer before it finished.
+    async fn watch_offers<R, W, F>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        bot: &str,
+        fut: F,
+        irc_open: &mut bool,
+    ) -> Result<Result<F::Output, dcc::DccInfo>, XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+        F: std::future::Future,
+    {
+        tokio::pin!(fut);
+        // Kept across select iterations: read_until appends partial reads here
+        let mut buf = Vec::new();
+        loop {
+            tokio::select! {
+                output = &mut fut => return Ok(Ok(output)),
+                read = reader.read_until(b'\n', &mut buf), if *irc_open => {
+                    match read {
+                        Ok(0) | Err(_) => *irc_open = false,
+                        Ok(_) => {
+                            let line = String::from_utf8_lossy(&buf).trim().to_string();
+                            buf.clear();
+                            if line.starts_with("PING") {
+                                Self::send_raw(writer, &line.replacen("PING", "PONG", 1)).await?;
+                            } else if dcc::is_dcc_send(&line) && Self::is_from(&line, bot) {
+                                if let Some(offer) = dcc::parse_dcc_send(&line) {
+                                    return Ok(Err(offer));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn switch_offer(
+        info: &mut dcc::DccInfo,
+        offer: dcc::DccInfo,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) {
+        tracing::info!(
+            "Bot re-announced {} on {}:{}, switching to the new offer",
+            offer.filename,
+            offer.ip,
+            offer.port
+        );
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "Bot re-announced the file on {}:{}, using the newest offer",
+                offer.ip, offer.port
+            )))
+            .await;
+        let _ = tx
+            .send(XdccEvent::DccSend {
+                filename: offer.filename.clone(),
+                ip: offer.ip.to_string(),
+                port: offer.port,
+                size: offer.size,
+            })
+            .await;
+        let turbo = info.turbo;
+        *info = offer;
+        info.turbo |= turbo;
+    }
+
+    /// Hold a DCC offer until it is accepted or rejected through the API,
+    /// answering PINGs meanwhile. Returns whether it was accepted.
+    #[allow(clippy::too_many_arguments)]
+    async fn await_approval<R, W>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        url: &XdccUrl,
+        sender: &str,
+        info: &dcc::DccInfo,
+        reason: String,
+        config: &XdccConfig,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<bool, XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        tracing::info!(
+            "Holding DCC offer of {} from {} for approval: {}",
+            info.filename,
+            sender,
+            reason
+        );
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "Holding DCC offer of {} from {} for approval: {}",
+                info.filename, sender, reason
+            )))
+            .await;
+        let (id, decision) = config.offers.hold(PendingOffer {
+            id: String::new(),
+            transfer_id: config.transfer_id.clone(),
+            network: url.network.clone(),
+            sender: sender.to_string(),
+            filename: info.filename.clone(),
+            ip: info.ip.to_string(),
+            port: info.port,
+            size: info.size,
+            reason,
+            received_at: chrono::Utc::now(),
+        });
+
+        let deadline = tokio::time::sleep(OFFER_APPROVAL_TIMEOUT);
+        tokio::pin!(deadline);
+        tokio::pin!(decision);
+        let mut irc_open = true;
+        let mut buf = Vec::new();
+        let accepted = loop {
+            tokio::select! {
+                accepted = &mut decision => break accepted.unwrap_or(false),
+                _ = &mut deadline => {
+                    config.offers.withdraw(&id);
+                    let _ = tx
+                        .send(XdccEvent::Log("DCC offer expired without a decision".to_string()))
+                        .await;
+                    return Ok(false);
+                }
+                // The download was cancelled
+                _ = tx.closed() => {
+                    config.offers.withdraw(&id);
+                    return Err(XdccError::ConnectionFailed("Transfer cancelled".into()));
+                }
+                read = reader.read_until(b'\n', &mut buf), if irc_open => {
+                    match read {
+                        Ok(0) | Err(_) => irc_open = false,
+                        Ok(_) => {
+                            let line = String::from_utf8_lossy(&buf).trim().to_string();
+                            buf.clear();
+                            if line.starts_with("PING") {
+                                Self::send_raw(writer, &line.replacen("PING", "PONG", 1)).await?;
+                            }
+                        }
+                    }
+                }
+            }
+        };
+
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "DCC offer of {} {}",
+                info.filename,
+                if accepted { "accepted" } else { "rejected" }
+            )))
+            .await;
+        Ok(accepted)
+    }
+
+    /// Have NickServ disconnect a ghost session holding our registered nick
+    /// (GHOST, or RECOVER and RELEASE on services without it) and switch to
+    /// it. Returns whether the nick was reclaimed.
+    async fn recover_nick<R, W>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        current: &str,
+        nick: &str,
+        password: &str,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<bool, XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        tracing::info!(
+            "Nick {} is held by another session, sending NickServ GHOST",
+            nick
+        );
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "Nick {} is in use, asking NickServ to disconnect the ghost session",
+                nick
+            )))
+            .await;
+        Self::send_raw(
+            writer,
+            &format!("PRIVMSG NickServ :GHOST {} {}", nick, password),
+        )
+        .await?;
+
+        // Wait for NickServ's answer
+        let mut recover_sent = false;
+        let mut buf = Vec::new();
+        let deadline = tokio::time::Instant::now() + NICK_RECLAIM_WAIT;
+        while let Some(line) = Self::read_line_until(reader, writer, &mut buf, deadline).await? {
+            if !(Self::is_from(&line, "NickServ") && line.contains(" NOTICE ")) {
+                continue;
+            }
+            if line.to_lowercase().contains("unknown command") && !recover_sent {
+                recover_sent = true;
+                Self::send_raw(
+                    writer,
+                    &format!("PRIVMSG NickServ :RECOVER {} {}", nick, password),
+                )
+                .await?;
+                continue;
+            }
+            if recover_sent {
+                // Services holding the nick with an enforcer after RECOVER
+                Self::send_raw(
+                    writer,
+                    &format!("PRIVMSG NickServ :RELEASE {} {}", nick, password),
+                )
+                .await?;
+            }
+            break;
+        }
+
+        // Take the nick back
+        Self::send_raw(writer, &format!("NICK {}", nick)).await?;
+        let deadline = tokio::time::Instant::now() + NICK_RECLAIM_WAIT;
+        while let Some(line) = Self::read_line_until(reader, writer, &mut buf, deadline).await? {
+            if line.contains(" 433 ") || line.contains(" 437 ") {
+                break;
+            }
+            if line.split_whitespace().nth(1) == Some("NICK") && Self::is_from(&line, current) {
+                tracing::info!("Reclaimed nick {}", nick);
+                let _ = tx
+                    .send(XdccEvent::Log(format!("Reclaimed nick {}", nick)))
+                    .await;
+                return Ok(true);
+            }
+        }
+
+        tracing::warn!("Could not reclaim nick {}, staying on {}", nick, current);
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "Could not reclaim nick {}, continuing as {}",
+                nick, current
+            )))
+            .await;
+        Ok(false)
+    }
+
+    /// Identify `nick` with NickServ and wait until services confirm it (a
+    /// NickServ notice, 900 RPL_LOGGEDIN or user mode +r), so channels that
+    /// only serve identified users see us as such when we join. Returns
+    /// whether the identification was confirmed; on a rejection or no answer
+    /// the session carries on unidentified.
+    async fn identify<R, W>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        nick: &str,
+        password: &str,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<bool, XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        tracing::info!("Sending NickServ IDENTIFY");
+        Self::send_raw(writer, &format!("PRIVMSG NickServ :IDENTIFY {}", password)).await?;
+
+        let mut buf = Vec::new();
+        let deadline = tokio::time::Instant::now() + IDENTIFY_WAIT;
+        while let Some(line) = Self::read_line_until(reader, writer, &mut buf, deadline).await? {
+            match Self::identify_reply(&line, nick) {
+                Some(true) => {
+                    tracing::info!("Identified with NickServ as {}", nick);
+                    let _ = tx
+                        .send(XdccEvent::Log(format!(
+                            "Identified with NickServ as {}",
+                            nick
+                        )))
+                        .await;
+                    return Ok(true);
+                }
+                Some(false) => {
+                    tracing::warn!("NickServ rejected IDENTIFY: {}", line);
+                    let _ = tx
+                        .send(XdccEvent::Log(format!(
+                            "NickServ rejected the password for {}, continuing unidentified",
+                            nick
+                        )))
+                        .await;
+                    return Ok(false);
+                }
+                None => {}
+            }
+        }
+
+        tracing::warn!("No NickServ confirmation for {}", nick);
+        let _ = tx
+            .send(XdccEvent::Log(format!(
+                "NickServ did not confirm the identification of {} in time, joining anyway",
+                nick
+            )))
+            .await;
+        Ok(false)
+    }
+
+    /// Whether `line` confirms (`Some(true)`) or rejects (`Some(false)`) the
+    /// NickServ identification of `nick`
+    fn identify_reply(line: &str, nick: &str) -> Option<bool> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.get(1).copied() {
+            // RPL_LOGGEDIN
+            Some("900") => return Some(true),
+            Some("MODE") if parts.get(2).is_some_and(|t| t.eq_ignore_ascii_case(nick)) => {
+                let mut adding = false;
+                for c in parts.get(3)?.trim_start_matches(':').chars() {
+                    match c {
+                        '+' => adding = true,
+                        '-' => adding = false,
+                        'r' if adding => return Some(true),
+                        _ => {}
+                    }
+                }
+                return None;
+            }
+            Some("NOTICE") if Self::is_from(line, "NickServ") => {}
+            _ => return None,
+        }
+
+        let text = line.splitn(4, ' ').nth(3)?.to_lowercase();
+        if [
+            "invalid",
+            "incorrect",
+            "not registered",
+            "isn't registered",
+            "denied",
+        ]
+        .iter()
+        .any(|k| text.contains(k))
+        {
+            Some(false)
+        } else if ["identified", "recognized", "password accepted", "logged in"]
+            .iter()
+            .any(|k| text.contains(k))
+        {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Read the next IRC line before `deadline`, answering PINGs. Returns
+    /// `None` once the deadline passes.
+    async fn read_line_until<R, W>(
+        reader: &mut BufReader<R>,
+        writer: &mut W,
+        buf: &mut Vec<u8>,
+        deadline: tokio::time::Instant,
+    ) -> Result<Option<String>, XdccError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        loop {
+            buf.clear();
+            match tokio::time::timeout_at(deadline, reader.read_until(b'\n', buf)).await {
+                Err(_) => return Ok(None),
+                Ok(Ok(0)) => {
+                    return Err(XdccError::ConnectionFailed(
+                        "Connection closed by server".into(),
+                    ))
+                }
+                Ok(Err(e)) => {
+                    return Err(XdccError::ConnectionFailed(format!("Read error: {}", e)))
+                }
+                Ok(Ok(_)) => {
+                    let line = String::from_utf8_lossy(buf).trim().to_string();
+                    if line.starts_with("PING") {
+                        Self::send_raw(writer, &line.replacen("PING", "PONG", 1)).await?;
+                        continue;
+                    }
+                    return Ok(Some(line));
+                }
+            }
+        }
+    }
+
+    /// Nick a raw IRC line was sent by
+    fn sender_nick(line: &str) -> String {
+        line.strip_prefix(':')
+            .and_then(|l| l.split('!').next())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Whether a raw IRC line was sent by `nick`
+    fn is_from(line: &str, nick: &str) -> bool {
+        line.strip_prefix(':')
+            .and_then(|l| l.split('!').next())
+            .is_some_and(|n| n.eq_ignore_ascii_case(nick))
+    }
+
+    /// Compare the collected `xdcc info` reply with the expected file and report it
+    async fn verify_pack_info(
+        pack_info: &mut PackInfo,
+        config: &XdccConfig,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<(), XdccError> {
+        if !pack_info.has_details() {
+            let _ = tx
+                .send(XdccEvent::Log(
+                    "No xdcc info reply from bot, requesting anyway".to_string(),
+                ))
+                .await;
+            return Ok(());
+        }
+
+        pack_info.check(config.expected_filename.as_deref(), config.expected_size);
+        let _ = tx.send(XdccEvent::PackInfo(pack_info.clone())).await;
+        match &pack_info.mismatch {
+            Some(mismatch) if config.info_abort_on_mismatch => Err(XdccError::InvalidPack(
+                format!("Pack info mismatch: {}", mismatch),
+            )),
+            Some(mismatch) => {
+                let _ = tx
+                    .send(XdccEvent::Log(format!("Pack info mismatch: {}", mismatch)))
+                    .await;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
     async fn send_raw<W: tokio::io::AsyncWrite + Unpin>(
         writer: &mut W,
         msg: &str,
@@ -772,3 +1759,162 @@ impl XdccClient {
         Some((nick, cmd, target, msg))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+
+    #[test]
+    fn test_resolve_network_source() {
+        let mut config = XdccConfig {
+            use_ssl: false,
+            ..XdccConfig::default()
+        };
+        config.networks.insert(
+            "Rizon".to_string(),
+            (
+                "irc.rizon.net".to_string(),
+                6697,
+                true,
+                vec!["#news".to_string()],
+                10,
+                String::new(),
+            ),
+        );
+
+        let (resolved, source) = config.resolve_network_source("rizon").unwrap();
+        assert_eq!(source, NetworkSource::Configured("Rizon".to_string()));
+        assert_eq!((resolved.1, resolved.2, resolved.4), (6697, true, 10));
+        let (_, source) = config.resolve_network_source("IRC.Rizon.net").unwrap();
+        assert_eq!(source, NetworkSource::Configured("Rizon".to_string()));
+
+        let (resolved, source) = config.resolve_network_source("irc.abjects.net").unwrap();
+        assert_eq!(source, NetworkSource::Hostname);
+        assert_eq!((resolved.0.as_str(), resolved.1), ("irc.abjects.net", 6667));
+
+        let (resolved, source) = config.resolve_network_source("Scenep2p").unwrap();
+        assert_eq!(
+            source,
+            NetworkSource::Heuristic("irc.{name}.net".to_string())
+        );
+        assert_eq!(resolved.0, "irc.scenep2p.net");
+        assert_eq!(source.to_string(), "heuristic:irc.{name}.net");
+
+        config.network_fallback_hosts.clear();
+        assert!(config.resolve_network_source("Criten").is_none());
+        assert!(config.resolve_network("irc.criten.net").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_network() {
+        let mut config = XdccConfig {
+            network_fallback_hosts: vec!["{name}.invalid".to_string(), "localhost".to_string()],
+            ..XdccConfig::default()
+        };
+        let (resolved, source) = config.lookup_network("P2P-NET").await.unwrap();
+        assert_eq!(source, NetworkSource::Heuristic("localhost".to_string()));
+        assert_eq!(resolved.0, "localhost");
+
+        config.network_fallback_hosts.clear();
+        let err = config.lookup_network("P2P-NET").await.unwrap_err();
+        assert!(err.is_fatal());
+    }
+
+    #[tokio::test]
+    async fn test_recover_nick() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+
+        // Services answering GHOST, then the server confirming the nick change
+        let services = tokio::spawn(async move {
+            let mut lines = BufReader::new(server_read).lines();
+            let mut seen = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply = if line.starts_with("PRIVMSG NickServ :GHOST") {
+                    ":NickServ!s@services NOTICE botarr_ :botarr has been ghosted.\r\n"
+                } else if line == "NICK botarr" {
+                    ":botarr_!u@host NICK :botarr\r\n"
+                } else {
+                    ""
+                };
+                seen.push(line);
+                server_write.write_all(reply.as_bytes()).await.unwrap();
+                if seen.len() == 2 {
+                    break;
+                }
+            }
+            seen
+        });
+
+        let (tx, _rx) = mpsc::channel(10);
+        let mut reader = BufReader::new(client_read);
+        let reclaimed = XdccClient::recover_nick(
+            &mut reader,
+            &mut client_write,
+            "botarr_",
+            "botarr",
+            "hunter2",
+            &tx,
+        )
+        .await
+        .unwrap();
+        assert!(reclaimed);
+        assert_eq!(
+            services.await.unwrap(),
+            vec!["PRIVMSG NickServ :GHOST botarr hunter2", "NICK botarr"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_identify() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+
+        // The nick is only confirmed after unrelated lines have arrived
+        let services = tokio::spawn(async move {
+            let mut lines = BufReader::new(server_read).lines();
+            let line = lines.next_line().await.unwrap().unwrap();
+            server_write
+                .write_all(
+                    b":irc.test 372 botarr :- MOTD\r\n\
+                      :NickServ!s@services NOTICE botarr :This nickname is registered, identify via /msg NickServ IDENTIFY\r\n\
+                      :irc.test MODE botarr :+i\r\n\
+                      :NickServ!s@services NOTICE botarr :Password accepted - you are now recognized.\r\n",
+                )
+                .await
+                .unwrap();
+            line
+        });
+
+        let (tx, _rx) = mpsc::channel(10);
+        let mut reader = BufReader::new(client_read);
+        let identified =
+            XdccClient::identify(&mut reader, &mut client_write, "botarr", "hunter2", &tx)
+                .await
+                .unwrap();
+        assert!(identified);
+        assert_eq!(
+            services.await.unwrap(),
+            "PRIVMSG NickServ :IDENTIFY hunter2"
+        );
+
+        let reply = |line: &str| XdccClient::identify_reply(line, "botarr");
+        assert_eq!(
+            reply(":irc.test 900 botarr botarr!u@h botarr :You are now logged in"),
+            Some(true)
+        );
+        assert_eq!(reply(":botarr MODE botarr :+ixr"), Some(true));
+        assert_eq!(reply(":irc.test MODE botarr :-r+i"), None);
+        assert_eq!(
+            reply(":NickServ!s@services NOTICE botarr :Invalid password for botarr."),
+            Some(false)
+        );
+        assert_eq!(
+            reply(":Bot!b@h NOTICE botarr :You are now identified"),
+            None
+        );
+    }
+}
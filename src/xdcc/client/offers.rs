@@ -0,0 +1,153 @@
+//! Pending DCC Offers
+//!
+//! In manual approval mode, DCC offers that weren't clearly sent in answer to
+//! our request (another nick, before the request went out, or a different file
+//! than expected) are held here until they are accepted or rejected through
+//! the API instead of being connected to automatically.
+
+use super::dcc::DccInfo;
+use super::info::PackInfo;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// An offer waiting for a decision
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingOffer {
+    pub id: String,
+    pub transfer_id: String,
+    pub network: String,
+    /// Nick that sent the offer
+    pub sender: String,
+    pub filename: String,
+    pub ip: String,
+    pub port: u16,
+    pub size: u64,
+    /// Why the offer wasn't accepted automatically
+    pub reason: String,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Held offers by ID, with the channel the decision is sent on
+type Pending = HashMap<String, (PendingOffer, oneshot::Sender<bool>)>;
+
+/// Offers held for approval, shared between download tasks and the API
+#[derive(Debug, Clone, Default)]
+pub struct OfferQueue {
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl OfferQueue {
+    /// Hold an offer, returning its ID and a receiver for the decision
+    pub fn hold(&self, mut offer: PendingOffer) -> (String, oneshot::Receiver<bool>) {
+        let (tx, rx) = oneshot::channel();
+        offer.id = Uuid::new_v4().to_string();
+        let id = offer.id.clone();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id.clone(), (offer, tx));
+        }
+        (id, rx)
+    }
+
+    /// Offers waiting for a decision, oldest first
+    pub fn list(&self) -> Vec<PendingOffer> {
+        let mut offers: Vec<PendingOffer> = self
+            .pending
+            .lock()
+            .map(|p| p.values().map(|(offer, _)| offer.clone()).collect())
+            .unwrap_or_default();
+        offers.sort_by_key(|o| o.received_at);
+        offers
+    }
+
+    /// Accept or reject an offer, returning it if it was still pending
+    pub fn decide(&self, id: &str, accept: bool) -> Option<PendingOffer> {
+        let (offer, tx) = self.pending.lock().ok()?.remove(id)?;
+        // The download may have given up in the meantime
+        tx.send(accept).ok()?;
+        Some(offer)
+    }
+
+    /// Drop an offer nobody decided on
+    pub fn withdraw(&self, id: &str) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(id);
+        }
+    }
+}
+
+/// Why an offer needs approval, or `None` if it is the expected answer to our
+/// request from the requested bot
+pub fn offer_concern(
+    sender: &str,
+    bot: &str,
+    requested: bool,
+    offer: &DccInfo,
+    expected_filename: Option<&str>,
+    expected_size: Option<u64>,
+) -> Option<String> {
+    if !sender.eq_ignore_ascii_case(bot) {
+        return Some(format!("Unsolicited offer from {}, not {}", sender, bot));
+    }
+    if !requested {
+        return Some("Offer arrived before the pack was requested".to_string());
+    }
+    let mut info = PackInfo {
+        filename: Some(offer.filename.clone()),
+        size: Some(offer.size),
+        ..Default::default()
+    };
+    info.check(expected_filename, expected_size);
+    info.mismatch
+        .map(|m| format!("Offered file doesn't match: {}", m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_concern_and_queue() {
+        let offer = DccInfo {
+            filename: "Show.S01E01.mkv".to_string(),
+            ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            port: 5000,
+            size: 350 * 1024 * 1024,
+            turbo: false,
+            token: None,
+        };
+        let expected = Some("Show.S01E01.mkv");
+        let size = Some(350 * 1024 * 1024);
+        assert_eq!(
+            offer_concern("Bot", "bot", true, &offer, expected, size),
+            None
+        );
+        assert!(offer_concern("Other", "Bot", true, &offer, expected, size)
+            .unwrap()
+            .contains("Unsolicited"));
+        assert!(offer_concern("Bot", "Bot", false, &offer, expected, size).is_some());
+        assert!(offer_concern("Bot", "Bot", true, &offer, Some("Movie.mkv"), size).is_some());
+
+        let queue = OfferQueue::default();
+        let (id, mut rx) = queue.hold(PendingOffer {
+            id: String::new(),
+            transfer_id: "t1".to_string(),
+            network: "Rizon".to_string(),
+            sender: "Other".to_string(),
+            filename: offer.filename.clone(),
+            ip: offer.ip.to_string(),
+            port: offer.port,
+            size: offer.size,
+            reason: "Unsolicited".to_string(),
+            received_at: Utc::now(),
+        });
+        assert_eq!(queue.list().len(), 1);
+        assert!(queue.decide(&id, false).is_some());
+        assert_eq!(rx.try_recv(), Ok(false));
+        assert!(queue.list().is_empty());
+        assert!(queue.decide(&id, true).is_none());
+    }
+}
@@ -0,0 +1,735 @@
+//! Shared IRC Connections
+//!
+//! Instead of opening its own IRC connection per download, a download can
+//! lease one kept open per network. The connection registers once, stays in
+//! the channels it joined and answers PINGs itself; each lease is an
+//! in-memory stream the download session talks IRC over as usual. Commands
+//! from a lease are forwarded to the server (a JOIN only once per channel),
+//! and server lines are routed to the leases they concern: lines from a bot
+//! go to the downloads from that bot, DCC offers to a single one of them,
+//! and channel lines to the downloads in that channel.
+
+use super::{dcc, IrcReader, IrcWriter, NetworkConfig, XdccClient, XdccConfig, XdccEvent};
+use crate::xdcc::{XdccError, XdccUrl};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// Buffer of the in-memory stream between a lease and the connection
+const LEASE_BUFFER: usize = 64 * 1024;
+/// Server lines queued for a lease that isn't reading, e.g. while it
+/// receives a file; later ones are dropped
+const LEASE_BACKLOG: usize = 1024;
+/// Prefix of the lines the connection makes up for a lease
+const POOL_SERVER: &str = "botarr.pool";
+
+/// A download's share of a connection
+#[derive(Debug)]
+pub struct Lease {
+    /// Stream the download session reads and writes IRC lines on
+    pub stream: DuplexStream,
+    /// Local address of the connection, for DCC offers
+    pub local_ip: Option<String>,
+    /// How long the connection has been in the download's channel
+    pub joined_for: Duration,
+}
+
+enum Command {
+    Lease {
+        bot: String,
+        channel: String,
+        stream: DuplexStream,
+        reply: oneshot::Sender<Duration>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Connection {
+    commands: mpsc::Sender<Command>,
+    local_ip: Option<String>,
+}
+
+/// Connections by `host:port`, each behind its own lock so a download
+/// waits for a connection being opened to the same server, not others
+type Slots = HashMap<String, Arc<tokio::sync::Mutex<Option<Connection>>>>;
+
+/// IRC connections shared between downloads, one per server
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionPool {
+    slots: Arc<Mutex<Slots>>,
+}
+
+impl ConnectionPool {
+    /// Lease the connection to `network` for the download of `url`, opening
+    /// it if needed
+    pub async fn lease(
+        &self,
+        url: &XdccUrl,
+        network: &NetworkConfig,
+        config: &XdccConfig,
+        tx: &mpsc::Sender<XdccEvent>,
+    ) -> Result<Lease, XdccError> {
+        let (host, port, use_ssl, _, _, nickserv_password) = network.clone();
+        let key = format!("{}:{}", host, port).to_lowercase();
+        let slot = self
+            .slots
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let mut slot = slot.lock().await;
+        let connection = match slot.as_ref().filter(|c| !c.commands.is_closed()) {
+            Some(connection) => {
+                let _ = tx
+                    .send(XdccEvent::Log(format!(
+                        "Using the shared connection to {}",
+                        key
+                    )))
+                    .await;
+                connection.clone()
+            }
+            None => {
+                let (mut reader, mut writer, local_ip) =
+                    XdccClient::connect_irc(config, &host, port, use_ssl, tx).await?;
+                let nick =
+                    register(&mut reader, &mut writer, config, &nickserv_password, tx).await?;
+                let (commands, receiver) = mpsc::channel(16);
+                let idle = Duration::from_secs(config.pool_idle_secs);
+                tokio::spawn(run(reader, writer, nick, key.clone(), receiver, idle));
+                let connection = Connection { commands, local_ip };
+                *slot = Some(connection.clone());
+                connection
+            }
+        };
+        drop(slot);
+
+        let (stream, pool_side) = tokio::io::duplex(LEASE_BUFFER);
+        let (reply, joined_for) = oneshot::channel();
+        let closed = || XdccError::ConnectionFailed(format!("Shared connection to {} closed", key));
+        connection
+            .commands
+            .send(Command::Lease {
+                bot: url.bot.clone(),
+                channel: url.channel.clone(),
+                stream: pool_side,
+                reply,
+            })
+            .await
+            .map_err(|_| closed())?;
+        Ok(Lease {
+            stream,
+            local_ip: connection.local_ip,
+            joined_for: joined_for.await.map_err(|_| closed())?,
+        })
+    }
+}
+
+/// Register with the server, reclaiming the nick from a ghost and
+/// identifying with NickServ when a password is set. Returns the nick.
+async fn register(
+    reader: &mut IrcReader,
+    writer: &mut IrcWriter,
+    config: &XdccConfig,
+    nickserv_password: &str,
+    tx: &mpsc::Sender<XdccEvent>,
+) -> Result<String, XdccError> {
+    const MAX_NICK_RETRIES: u32 = 3;
+    let mut nick = config.nickname.clone();
+    XdccClient::send_raw(writer, &format!("NICK {}", nick)).await?;
+    XdccClient::send_raw(
+        writer,
+        &format!("USER {} 0 * :{}", config.username, config.realname),
+    )
+    .await?;
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(config.timeout_secs);
+    let mut buf = Vec::with_capacity(1024);
+    let mut retries = 0;
+    let mut ghosted_nick = None;
+    loop {
+        let Some(line) = XdccClient::read_line_until(reader, writer, &mut buf, deadline).await?
+        else {
+            return Err(XdccError::Timeout(
+                "Timed out registering with the IRC server".into(),
+            ));
+        };
+        if line.contains(" 001 ") {
+            break;
+        }
+        if line.contains(" 433 ") || line.contains(" 432 ") {
+            retries += 1;
+            if retries > MAX_NICK_RETRIES {
+                return Err(XdccError::ConnectionFailed(format!(
+                    "Nickname rejected {} times, giving up",
+                    retries - 1
+                )));
+            }
+            if line.contains(" 433 ") {
+                if !nickserv_password.is_empty()
+                    && ghosted_nick.is_none()
+                    && nick == config.nickname
+                {
+                    ghosted_nick = Some(nick.clone());
+                }
+                nick.push('_');
+            } else {
+                nick = format!("bot_{}", nick);
+            }
+            XdccClient::send_raw(writer, &format!("NICK {}", nick)).await?;
+        } else if line.starts_with("ERROR") || line.contains("Closing Link") {
+            return Err(XdccError::ConnectionFailed(format!(
+                "Connection closed: {}",
+                line
+            )));
+        }
+    }
+
+    if let Some(registered) = ghosted_nick {
+        if XdccClient::recover_nick(reader, writer, &nick, &registered, nickserv_password, tx)
+            .await?
+        {
+            nick = registered;
+        }
+    }
+    if !nickserv_password.is_empty() {
+        XdccClient::identify(reader, writer, &nick, nickserv_password, tx).await?;
+    }
+    Ok(nick)
+}
+
+struct LeaseState {
+    bot: String,
+    channel: String,
+    lines: mpsc::Sender<String>,
+    /// Replied to the session's NICK with the welcome
+    welcomed: bool,
+    /// File of the DCC offer routed to it
+    offered: Option<String>,
+}
+
+/// Where a server line goes
+#[derive(Debug, PartialEq)]
+enum Route {
+    /// Answered by the connection itself
+    Pong(String),
+    All,
+    Leases(Vec<u64>),
+}
+
+/// Connection state the routing depends on
+#[derive(Default)]
+struct Router {
+    nick: String,
+    leases: HashMap<u64, LeaseState>,
+    /// Joined channels (lowercase) and when
+    joined: HashMap<String, Instant>,
+    /// Channels a JOIN was sent for
+    joining: HashSet<String>,
+}
+
+impl Router {
+    /// Leases a server line is for, updating the joined channels
+    fn route(&mut self, line: &str) -> Route {
+        if let Some(rest) = line.strip_prefix("PING") {
+            return Route::Pong(format!("PONG{}", rest));
+        }
+        let parts: Vec<&str> = line.split(' ').collect();
+        let sender = XdccClient::sender_nick(line);
+        let (command, params) = if line.starts_with(':') {
+            (
+                parts.get(1).copied().unwrap_or_default(),
+                &parts[2.min(parts.len())..],
+            )
+        } else {
+            (parts[0], &parts[1..])
+        };
+        let param = |i: usize| {
+            params
+                .get(i)
+                .map(|p| p.trim_start_matches(':'))
+                .unwrap_or_default()
+        };
+        let ours = sender.eq_ignore_ascii_case(&self.nick);
+
+        match command {
+            "JOIN" if ours => {
+                let channel = param(0).to_lowercase();
+                self.joining.remove(&channel);
+                self.joined.insert(channel.clone(), Instant::now());
+                return self.in_channel(&channel);
+            }
+            "PART" if ours => {
+                self.joined.remove(&param(0).to_lowercase());
+            }
+            "KICK" if param(1).eq_ignore_ascii_case(&self.nick) => {
+                self.joined.remove(&param(0).to_lowercase());
+            }
+            "NICK" if ours => self.nick = param(0).to_string(),
+            // End of NAMES, which sessions take as joined
+            "366" => return self.in_channel(&param(1).to_lowercase()),
+            // No such nick: for the downloads from that bot only
+            "401" => return Route::Leases(self.bot_leases(param(1))),
+            _ => {}
+        }
+
+        if dcc::is_dcc_send(line) {
+            return self.offer(line, &sender);
+        }
+        if line.contains("DCC ACCEPT") {
+            let ids: Vec<u64> = self
+                .bot_leases(&sender)
+                .into_iter()
+                .filter(|id| {
+                    self.leases[id]
+                        .offered
+                        .as_deref()
+                        .is_some_and(|f| line.contains(f))
+                })
+                .collect();
+            if !ids.is_empty() {
+                return Route::Leases(ids);
+            }
+        }
+        let from_bot = self.bot_leases(&sender);
+        if !from_bot.is_empty() {
+            return Route::Leases(from_bot);
+        }
+        if matches!(
+            command,
+            "PRIVMSG" | "NOTICE" | "JOIN" | "PART" | "KICK" | "TOPIC" | "MODE"
+        ) && param(0).starts_with(['#', '&'])
+        {
+            return self.in_channel(&param(0).to_lowercase());
+        }
+        Route::All
+    }
+
+    /// Leases downloading from `bot`, oldest first
+    fn bot_leases(&self, bot: &str) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.bot.eq_ignore_ascii_case(bot))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn in_channel(&self, channel: &str) -> Route {
+        let mut ids: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.channel.eq_ignore_ascii_case(channel))
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        Route::Leases(ids)
+    }
+
+    /// A DCC offer goes to one download: the one already offered that file,
+    /// else the oldest from the sender without an offer. An offer from
+    /// another nick only goes to a download if it is the only one waiting
+    /// for an offer.
+    fn offer(&mut self, line: &str, sender: &str) -> Route {
+        let filename = dcc::parse_dcc_send(line).map(|i| i.filename);
+        let waiting: Vec<u64> = self
+            .leases
+            .iter()
+            .filter(|(_, l)| l.offered.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        let from_sender = self.bot_leases(sender);
+        let chosen = from_sender
+            .iter()
+            .find(|id| filename.is_some() && self.leases[id].offered == filename)
+            .or_else(|| {
+                from_sender
+                    .iter()
+                    .find(|id| self.leases[id].offered.is_none())
+            })
+            .or_else(|| waiting.first().filter(|_| waiting.len() == 1))
+            .copied();
+        match chosen {
+            Some(id) => {
+                if let Some(lease) = self.leases.get_mut(&id) {
+                    lease.offered = filename;
+                }
+                Route::Leases(vec![id])
+            }
+            None => Route::Leases(Vec::new()),
+        }
+    }
+}
+
+/// Serve the leases of one connection until it closes, or until it had none
+/// for `idle`
+async fn run(
+    reader: IrcReader,
+    mut writer: IrcWriter,
+    nick: String,
+    server: String,
+    mut commands: mpsc::Receiver<Command>,
+    idle: Duration,
+) {
+    tracing::info!("Opened shared IRC connection to {} as {}", server, nick);
+    let (server_tx, mut server_lines) = mpsc::channel::<String>(256);
+    tokio::spawn(read_lines(reader, server_tx));
+    let (lease_tx, mut lease_lines) = mpsc::channel::<(u64, Option<String>)>(256);
+
+    let mut router = Router {
+        nick,
+        ..Router::default()
+    };
+    let mut next_id = 0u64;
+    let mut idle_since = Some(tokio::time::Instant::now());
+
+    loop {
+        let idle_deadline = idle_since.map(|t| t + idle);
+        tokio::select! {
+            line = server_lines.recv() => {
+                let Some(line) = line else {
+                    tracing::warn!("Shared IRC connection to {} closed", server);
+                    break;
+                };
+                tracing::debug!("IRC {} < {}", server, line);
+                match router.route(&line) {
+                    Route::Pong(pong) => {
+                        if XdccClient::send_raw(&mut writer, &pong).await.is_err() {
+                            break;
+                        }
+                    }
+                    Route::All => {
+                        for lease in router.leases.values() {
+                            let _ = lease.lines.try_send(line.clone());
+                        }
+                    }
+                    Route::Leases(ids) => {
+                        for id in ids {
+                            if let Some(lease) = router.leases.get(&id) {
+                                let _ = lease.lines.try_send(line.clone());
+                            }
+                        }
+                    }
+                }
+            }
+            command = commands.recv() => {
+                let Some(Command::Lease { bot, channel, stream, reply }) = command else {
+                    break;
+                };
+                next_id += 1;
+                let (lines, lease_rx) = mpsc::channel(LEASE_BACKLOG);
+                tokio::spawn(serve_lease(next_id, stream, lease_rx, lease_tx.clone()));
+                let joined_for = router
+                    .joined
+                    .get(&channel.to_lowercase())
+                    .map_or(Duration::ZERO, |at| at.elapsed());
+                router.leases.insert(next_id, LeaseState { bot, channel, lines, welcomed: false, offered: None });
+                idle_since = None;
+                let _ = reply.send(joined_for);
+            }
+            Some((id, line)) = lease_lines.recv() => {
+                let forward = match line {
+                    Some(line) => handle_lease_line(&mut router, id, &server, line),
+                    // Gone without quitting, e.g. failed or cancelled: take
+                    // its request out of the bot's queue
+                    None => router.leases.remove(&id).and_then(|lease| {
+                        router
+                            .bot_leases(&lease.bot)
+                            .is_empty()
+                            .then(|| format!("PRIVMSG {} :xdcc remove", lease.bot))
+                    }),
+                };
+                if router.leases.is_empty() && idle_since.is_none() {
+                    idle_since = Some(tokio::time::Instant::now());
+                }
+                if let Some(forward) = forward {
+                    if XdccClient::send_raw(&mut writer, &forward).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(idle_deadline.unwrap_or_else(tokio::time::Instant::now)), if idle_deadline.is_some() => {
+                tracing::info!("Closing idle shared IRC connection to {}", server);
+                let _ = XdccClient::send_raw(&mut writer, "QUIT :Idle").await;
+                break;
+            }
+        }
+    }
+    // Dropping the leases ends their streams, so their sessions see the
+    // connection close
+}
+
+/// Handle a line a download session sent, returning what to send on to the
+/// server
+fn handle_lease_line(router: &mut Router, id: u64, server: &str, line: String) -> Option<String> {
+    let command = line
+        .split(' ')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    let nick = router.nick.clone();
+    let lease = router.leases.get_mut(&id)?;
+    match command.as_str() {
+        // Registration was done once for everyone
+        "NICK" => {
+            if !lease.welcomed {
+                lease.welcomed = true;
+                let _ = lease.lines.try_send(format!(
+                    ":{} 001 {} :Welcome, sharing the connection to {}",
+                    POOL_SERVER, nick, server
+                ));
+            }
+            None
+        }
+        "USER" | "PONG" => None,
+        "QUIT" => {
+            router.leases.remove(&id);
+            None
+        }
+        "JOIN" => {
+            let channels = line.split(' ').nth(1).unwrap_or_default();
+            let mut join = Vec::new();
+            for channel in channels.trim_start_matches(':').split(',') {
+                let key = channel.to_lowercase();
+                if router.joined.contains_key(&key) {
+                    let _ = lease.lines.try_send(format!(
+                        ":{}!{}@{} JOIN :{}",
+                        nick, nick, POOL_SERVER, channel
+                    ));
+                } else if router.joining.insert(key) {
+                    join.push(channel);
+                }
+            }
+            (!join.is_empty()).then(|| format!("JOIN {}", join.join(",")))
+        }
+        _ => Some(line),
+    }
+}
+
+/// Read server lines until the connection closes
+async fn read_lines(mut reader: IrcReader, lines: mpsc::Sender<String>) {
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf).trim().to_string();
+                if !line.is_empty() && lines.send(line).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Pass lines between a lease's stream and the connection until either side
+/// is done
+async fn serve_lease(
+    id: u64,
+    stream: DuplexStream,
+    mut lines: mpsc::Receiver<String>,
+    sent: mpsc::Sender<(u64, Option<String>)>,
+) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        tokio::select! {
+            line = lines.recv() => {
+                let Some(line) = line else { break };
+                if writer.write_all(format!("{}\r\n", line).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            // Partial reads stay in `buf`, so being cancelled loses nothing
+            read = reader.read_until(b'\n', &mut buf) => {
+                match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        let line = String::from_utf8_lossy(&buf).trim().to_string();
+                        buf.clear();
+                        if !line.is_empty() && sent.send((id, Some(line))).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let _ = sent.send((id, None)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn read_line<R: tokio::io::AsyncBufRead + Unpin>(reader: &mut R) -> String {
+        let mut line = String::new();
+        tokio::time::timeout(Duration::from_secs(5), reader.read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        line.trim().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_shared_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut config = XdccConfig {
+            nickname: "botarr".to_string(),
+            ..XdccConfig::default()
+        };
+        config.networks.insert(
+            "Test".to_string(),
+            (
+                "127.0.0.1".to_string(),
+                port,
+                false,
+                Vec::new(),
+                0,
+                String::new(),
+            ),
+        );
+        let (tx, _rx) = mpsc::channel(100);
+        let pool = ConnectionPool::default();
+
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut reader = BufReader::new(reader);
+            assert_eq!(read_line(&mut reader).await, "NICK botarr");
+            read_line(&mut reader).await;
+            writer
+                .write_all(b":irc.test 001 botarr :Welcome\r\n")
+                .await
+                .unwrap();
+            // One JOIN for both downloads
+            assert_eq!(read_line(&mut reader).await, "JOIN #chan");
+            writer
+                .write_all(b":botarr!u@h JOIN :#chan\r\n")
+                .await
+                .unwrap();
+            assert_eq!(read_line(&mut reader).await, "PRIVMSG Bot1 :xdcc send #1");
+            assert_eq!(read_line(&mut reader).await, "PRIVMSG Bot2 :xdcc send #2");
+            writer
+                .write_all(b"PING :irc.test\r\n:Bot1!b@h PRIVMSG botarr :\x01DCC SEND a.mkv 2130706433 5000 10\x01\r\n:Bot2!b@h NOTICE botarr :Queued\r\n")
+                .await
+                .unwrap();
+            assert_eq!(read_line(&mut reader).await, "PONG :irc.test");
+            // Nobody else connects
+            assert!(
+                tokio::time::timeout(Duration::from_millis(300), listener.accept())
+                    .await
+                    .is_err()
+            );
+        });
+
+        let url1 = XdccUrl::parse("irc://Test/#chan/Bot1/1").unwrap();
+        let network = config.resolve_network("Test").unwrap();
+        let lease1 = pool.lease(&url1, &network, &config, &tx).await.unwrap();
+        assert_eq!(lease1.joined_for, Duration::ZERO);
+        let (reader1, mut writer1) = tokio::io::split(lease1.stream);
+        let mut reader1 = BufReader::new(reader1);
+        writer1
+            .write_all(b"NICK botarr\r\nUSER botarr 0 * :x\r\n")
+            .await
+            .unwrap();
+        assert!(read_line(&mut reader1).await.contains(" 001 botarr "));
+        writer1.write_all(b"JOIN #chan\r\n").await.unwrap();
+        assert_eq!(read_line(&mut reader1).await, ":botarr!u@h JOIN :#chan");
+
+        let url2 = XdccUrl::parse("irc://Test/#Chan/Bot2/2").unwrap();
+        let lease2 = pool.lease(&url2, &network, &config, &tx).await.unwrap();
+        let (reader2, mut writer2) = tokio::io::split(lease2.stream);
+        let mut reader2 = BufReader::new(reader2);
+        writer2
+            .write_all(b"NICK botarr\r\nJOIN #Chan\r\n")
+            .await
+            .unwrap();
+        assert!(read_line(&mut reader2).await.contains(" 001 "));
+        assert!(read_line(&mut reader2).await.ends_with("JOIN :#Chan"));
+
+        writer1
+            .write_all(b"PRIVMSG Bot1 :xdcc send #1\r\n")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        writer2
+            .write_all(b"PRIVMSG Bot2 :xdcc send #2\r\n")
+            .await
+            .unwrap();
+
+        // The offer reaches only the download from Bot1, the notice only Bot2's
+        assert!(read_line(&mut reader1).await.contains("DCC SEND a.mkv"));
+        assert_eq!(
+            read_line(&mut reader2).await,
+            ":Bot2!b@h NOTICE botarr :Queued"
+        );
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_route() {
+        let mut router = Router {
+            nick: "me".to_string(),
+            ..Router::default()
+        };
+        for (id, bot) in [(1, "Bot"), (2, "Bot"), (3, "Other")] {
+            let (lines, _) = mpsc::channel(1);
+            router.leases.insert(
+                id,
+                LeaseState {
+                    bot: bot.to_string(),
+                    channel: "#chan".to_string(),
+                    lines,
+                    welcomed: true,
+                    offered: None,
+                },
+            );
+        }
+        let offer = |file: &str| {
+            format!(
+                ":Bot!b@h PRIVMSG me :\x01DCC SEND {} 2130706433 5000 10\x01",
+                file
+            )
+        };
+        // From another nick, with more than one download waiting
+        let stray = ":Mirror!b@h PRIVMSG me :\x01DCC SEND c.mkv 2130706433 5000 10\x01";
+        assert_eq!(router.route(stray), Route::Leases(vec![]));
+        assert_eq!(router.route(&offer("a.mkv")), Route::Leases(vec![1]));
+        assert_eq!(router.route(&offer("b.mkv")), Route::Leases(vec![2]));
+        // Offered again, e.g. on another port
+        assert_eq!(router.route(&offer("a.mkv")), Route::Leases(vec![1]));
+        // Only the download from Other is still waiting
+        assert_eq!(router.route(stray), Route::Leases(vec![3]));
+        assert_eq!(
+            router.route(":irc.test 401 me bot :No such nick/channel"),
+            Route::Leases(vec![1, 2])
+        );
+        assert_eq!(
+            router.route(":x!y@z PRIVMSG #other :hi"),
+            Route::Leases(vec![])
+        );
+        assert_eq!(
+            router.route(":x!y@z PRIVMSG #Chan :hi"),
+            Route::Leases(vec![1, 2, 3])
+        );
+        assert_eq!(
+            router.route(":NickServ!s@services NOTICE me :hello"),
+            Route::All
+        );
+        assert_eq!(router.route("PING :x"), Route::Pong("PONG :x".to_string()));
+        assert_eq!(
+            router.route(":irc.test 366 me #Chan :End of /NAMES list."),
+            Route::Leases(vec![1, 2, 3])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Bandwidth Limits
+//!
+//! Downloads hold their reads back to a global speed limit shared by all
+//! transfers and to an optional limit of their own. Both are token buckets
+//! whose rate can be changed while downloads run; TCP flow control then
+//! slows the bot down to match.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket allowing `rate` bytes per second, with bursts of up to one
+/// second worth of data. Clones share the bucket.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes per second, 0 for no limit
+    rate: u64,
+    /// Bytes that may be read right away; negative when reads are owed
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            rate: 0,
+            tokens: 0.0,
+            refilled: Instant::now(),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        let limiter = Self::default();
+        limiter.set_rate(rate);
+        limiter
+    }
+
+    /// Current limit in bytes per second, 0 for none
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Change the limit; applies to the next read of every download using it
+    pub fn set_rate(&self, rate: u64) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.rate = rate;
+        bucket.tokens = 0.0;
+        bucket.refilled = Instant::now();
+    }
+
+    /// Take `bytes` from the bucket and return how long to wait before
+    /// they may be used
+    fn reserve(&self, bytes: u64) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        if bucket.rate == 0 {
+            return Duration::ZERO;
+        }
+        let rate = bucket.rate as f64;
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate) - bytes as f64;
+        bucket.refilled = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+}
+
+/// Limits a download keeps to: the global one and its own
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    pub global: RateLimiter,
+    pub transfer: RateLimiter,
+}
+
+impl Throttle {
+    /// Wait until `bytes` just read fit in both limits
+    pub async fn consume(&self, bytes: u64) {
+        let wait = self.global.reserve(bytes).max(self.transfer.reserve(bytes));
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let unlimited = RateLimiter::default();
+        assert_eq!(unlimited.reserve(1 << 30), Duration::ZERO);
+
+        let limiter = RateLimiter::new(1000);
+        // Starts empty, so the first reads already wait
+        let wait = limiter.reserve(500);
+        assert!(wait > Duration::from_millis(450) && wait <= Duration::from_millis(500));
+        // Reads owed add up
+        let wait = limiter.reserve(1000);
+        assert!(wait > Duration::from_millis(1400) && wait <= Duration::from_millis(1500));
+
+        // A new limit starts over
+        limiter.set_rate(0);
+        assert_eq!(limiter.reserve(1 << 20), Duration::ZERO);
+        limiter.set_rate(1 << 20);
+        assert_eq!(limiter.rate(), 1 << 20);
+        assert!(limiter.reserve(1024) < Duration::from_millis(2));
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let throttle = Throttle {
+            global: RateLimiter::new(100_000),
+            transfer: RateLimiter::new(10_000),
+        };
+        let start = Instant::now();
+        throttle.consume(2_000).await;
+        // The stricter of the two limits applies
+        assert!(start.elapsed() >= Duration::from_millis(190));
+
+        // Other downloads share the global limit
+        let other = Throttle {
+            global: throttle.global.clone(),
+            transfer: RateLimiter::default(),
+        };
+        other.global.reserve(100_000);
+        assert!(throttle.global.reserve(0) > Duration::from_millis(500));
+    }
+}
@@ -8,12 +8,18 @@
 
 mod client;
 pub mod monitor;
+pub mod packlist;
 pub mod providers;
+pub mod query;
 mod search;
 pub mod transfer;
 
 // Re-export public API items
-pub use client::{XdccClient, XdccConfig, XdccEvent};
+pub use client::info::PackInfo;
+pub use client::offers::OfferQueue;
+pub use client::pool::ConnectionPool;
+pub use client::throttle::{RateLimiter, Throttle};
+pub use client::{http, NetworkSource, XdccClient, XdccConfig, XdccEvent};
 pub use search::SearchAggregator;
 pub use transfer::{EnhancedTransferManager as TransferManager, TransferPriority};
 
@@ -105,6 +111,26 @@ pub struct XdccSearchResult {
     /// Additional metadata from the search provider
     #[serde(rename = "downloads")]
     pub gets: Option<u32>,
+    /// When the provider last saw the pack announced, where it reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
+    /// Details added by Botarr after the provider search
+    #[serde(flatten)]
+    pub annotations: ResultAnnotations,
+}
+
+/// Botarr-side annotations of a search result
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResultAnnotations {
+    /// Season/episode (e.g. `S02E05`) resolved from an absolute-numbered anime release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub episode: Option<String>,
+    /// *arr service that already has this release downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_library: Option<String>,
+    /// TMDB/TVDB details of the parsed release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<crate::metadata::MediaMetadata>,
 }
 
 /// Transfer status
@@ -120,6 +146,34 @@ pub enum TransferStatus {
     Completed,
     Failed,
     Cancelled,
+    /// Completed but failed the checksum, blocklist or virus scan checks
+    Quarantined,
+}
+
+/// How a transfer's file was fetched
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMethod {
+    Dcc,
+    /// From an HTTP mirror the bot advertised
+    Http,
+}
+
+impl TransferMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransferMethod::Dcc => "dcc",
+            TransferMethod::Http => "http",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dcc" => Some(TransferMethod::Dcc),
+            "http" => Some(TransferMethod::Http),
+            _ => None,
+        }
+    }
 }
 
 /// Active or completed XDCC transfer
@@ -134,9 +188,48 @@ pub struct XdccTransfer {
     pub downloaded: u64,
     pub speed: f64,
     pub progress: f64,
+    /// Estimated seconds until the download finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
     pub error: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// Download category (tv, movie, anime, music, other or a custom one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Bot's reply to the xdcc info pre-check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack_info: Option<PackInfo>,
+    /// Postprocess steps run on the completed file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub postprocess: Vec<crate::postprocess::StepResult>,
+    /// Free-form tags, e.g. the automation that queued it
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Download template it was queued with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Transfer that has to complete before this one starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// Where the completed file was left after postprocessing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_to: Option<String>,
+    /// How the network was resolved, e.g. `configured:Rizon` or
+    /// `heuristic:irc.{name}.net`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_source: Option<String>,
+    /// IRC server (`host:port`) the network resolved to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// HTTP/FTP mirrors of the pack the bot advertised in its notices
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// How the file was fetched, once a transfer started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<TransferMethod>,
     #[serde(skip)]
     pub logs: std::collections::VecDeque<String>,
 }
@@ -153,9 +246,22 @@ impl XdccTransfer {
             downloaded: 0,
             speed: 0.0,
             progress: 0.0,
+            eta_secs: None,
             error: None,
             created_at: now,
             updated_at: now,
+            category: None,
+            pack_info: None,
+            postprocess: Vec::new(),
+            labels: Vec::new(),
+            note: None,
+            template: None,
+            after: None,
+            saved_to: None,
+            network_source: None,
+            server: None,
+            mirrors: Vec::new(),
+            method: None,
             logs: std::collections::VecDeque::new(),
         }
     }
@@ -170,10 +276,20 @@ pub enum XdccError {
     TransferFailed(String),
     FatalIo(String),
     SearchFailed(String),
+    /// Provider answered with a body we could not make sense of
+    UnexpectedResponse(String),
+    /// Provider answered with a captcha or anti-bot page
+    Blocked(String),
+    /// The offered file is outside the accepted size range
+    SizeRejected(String),
     InvalidPack(String),
     BotBusy(String),
     NickInUse(String),
     Timeout(String),
+    /// The bot never answered a pack request
+    NoResponse(String),
+    /// Writing the file failed, e.g. because the disk is full
+    DiskWrite(String),
 }
 
 impl XdccError {
@@ -183,13 +299,18 @@ impl XdccError {
             XdccError::InvalidUrl(_) => true,
             XdccError::InvalidPack(_) => true,
             XdccError::FatalIo(_) => true,
+            XdccError::SizeRejected(_) => true,
             XdccError::NickInUse(_) => false, // Can retry with new nick
             XdccError::BotBusy(_) => false,   // Can retry later
             XdccError::ConnectionFailed(_) => false,
             XdccError::ChannelJoinFailed(_) => false,
             XdccError::TransferFailed(_) => false,
             XdccError::SearchFailed(_) => false,
+            XdccError::UnexpectedResponse(_) => false,
+            XdccError::Blocked(_) => false,
             XdccError::Timeout(_) => false,
+            XdccError::NoResponse(_) => false,
+            XdccError::DiskWrite(_) => false,
         }
     }
 }
@@ -206,7 +327,11 @@ impl fmt::Display for XdccError {
             XdccError::TransferFailed(msg) => write!(f, "Transfer failed: {}", msg),
             XdccError::FatalIo(msg) => write!(f, "Fatal IO error: {}", msg),
             XdccError::SearchFailed(msg) => write!(f, "Search failed: {}", msg),
-            XdccError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            XdccError::UnexpectedResponse(msg) => write!(f, "Unexpected response: {}", msg),
+            XdccError::Blocked(msg) => write!(f, "Blocked: {}", msg),
+            XdccError::SizeRejected(msg) => write!(f, "Size Rejected: {}", msg),
+            XdccError::Timeout(msg) | XdccError::NoResponse(msg) => write!(f, "Timeout: {}", msg),
+            XdccError::DiskWrite(msg) => write!(f, "Disk error: {}", msg),
         }
     }
 }
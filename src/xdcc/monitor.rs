@@ -4,7 +4,6 @@ use crate::plugin::PluginManager;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
 use tokio::sync::RwLock;
 
 use serde::Serialize;
@@ -19,12 +18,16 @@ pub struct MonitorStatus {
     pub status: String,
 }
 
+/// Network a monitor task is connected to, and its handle
+pub type MonitorTask = (String, tokio::task::JoinHandle<()>);
+
 pub struct IrcMonitor {
     config: Arc<RwLock<AppConfig>>,
     plugin_manager: Arc<PluginManager>,
     pub active_monitors: Arc<RwLock<Vec<MonitorStatus>>>,
     pub raw_logs: Arc<RwLock<VecDeque<String>>>,
-    pub tasks: Arc<RwLock<HashMap<String, Vec<tokio::task::JoinHandle<()>>>>>,
+    /// Monitor tasks per plugin
+    pub tasks: Arc<RwLock<HashMap<String, Vec<MonitorTask>>>>,
 }
 
 impl IrcMonitor {
@@ -62,6 +65,18 @@ impl IrcMonitor {
                     return;
                 }
 
+                let max_connections = config.read().await.irc_max_connections;
+                if max_connections > 0 && monitors.len() >= max_connections {
+                    tracing::warn!(
+                        "[{}] Not monitoring {} on {}: limit of {} monitors reached",
+                        plugin_name,
+                        channel,
+                        network_name,
+                        max_connections
+                    );
+                    return;
+                }
+
                 monitors.push(MonitorStatus {
                     plugin: plugin_name.clone(),
                     network: network_name.clone(),
@@ -72,6 +87,7 @@ impl IrcMonitor {
 
             let plugin_name_inner = plugin_name.clone();
             let network_name_inner = network_name.clone();
+            let task_network = network_name.clone();
             let channel_inner = channel.clone();
 
             let handle = tokio::spawn(async move {
@@ -105,10 +121,22 @@ impl IrcMonitor {
                 loop {
                     // 1. Resolve network
                     let cfg = config.read().await;
-                    let (host, port, ssl, _autojoin, _delay) = cfg.resolve_network(&network_name);
+                    let Some((host, port, ssl, _autojoin, _delay)) =
+                        cfg.resolve_network(&network_name)
+                    else {
+                        drop(cfg);
+                        tracing::error!(
+                            "Monitor cannot resolve network {}: add it to networks",
+                            network_name
+                        );
+                        update_status("Unknown network. Waiting for a mapping...");
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        continue;
+                    };
                     let nickname = cfg.nickname.clone();
                     let username = cfg.username.clone();
                     let realname = cfg.realname.clone();
+                    let outbound = crate::net::Outbound::from_config(&cfg);
                     drop(cfg);
 
                     let server = format!("{}:{}", host, port);
@@ -117,7 +145,7 @@ impl IrcMonitor {
                     // 2. Connect
                     let connect_res = match tokio::time::timeout(
                         Duration::from_secs(15),
-                        TcpStream::connect(&server),
+                        outbound.connect(&server, false),
                     )
                     .await
                     {
@@ -293,7 +321,7 @@ impl IrcMonitor {
             tasks_lock
                 .entry(plugin_name_for_tasks)
                 .or_insert_with(Vec::new)
-                .push(handle);
+                .push((task_network, handle));
         }); // Close outer spawn
     }
 
@@ -304,7 +332,7 @@ impl IrcMonitor {
         {
             let mut tasks_lock = self.tasks.write().await;
             if let Some(handles) = tasks_lock.remove(plugin_name) {
-                for handle in handles {
+                for (_, handle) in handles {
                     handle.abort();
                 }
             }
@@ -317,6 +345,34 @@ impl IrcMonitor {
         }
     }
 
+    /// Stop every monitor connected to a network, returning how many were stopped
+    pub async fn stop_monitors_for_network(&self, network: &str) -> usize {
+        tracing::info!("Stopping all IRC monitors on network: {}", network);
+
+        let mut stopped = 0;
+        {
+            let mut tasks_lock = self.tasks.write().await;
+            for handles in tasks_lock.values_mut() {
+                handles.retain(|(task_network, handle)| {
+                    if task_network == network {
+                        handle.abort();
+                        stopped += 1;
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+            tasks_lock.retain(|_, handles| !handles.is_empty());
+        }
+
+        {
+            let mut monitors_lock = self.active_monitors.write().await;
+            monitors_lock.retain(|m| m.network != network);
+        }
+        stopped
+    }
+
     /// Parse a generic IRC message
     /// Format: :nick!user@host CMD target :message
     fn parse_irc_message(line: &str) -> Option<(String, String, String, String)> {
@@ -0,0 +1,191 @@
+//! Pack List Parsing
+//!
+//! Parses bot pack lists published outside of IRC, such as iroffer `/packlist.txt`
+//! dumps and the HTML tables rendered by iroffer web UIs.
+
+use super::search::{build_http_client, parse_size};
+use super::XdccError;
+use serde::{Deserialize, Serialize};
+
+/// A single pack entry parsed from a pack list
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PackEntry {
+    pub slot: i32,
+    pub gets: Option<u32>,
+    pub size: Option<u64>,
+    pub size_str: String,
+    pub filename: String,
+}
+
+/// Result of parsing a pack list
+#[derive(Debug, Clone, Default)]
+pub struct ParsedPacklist {
+    /// Bot nickname, if the list advertises it (e.g. `/msg Bot xdcc send #x`)
+    pub bot: Option<String>,
+    pub packs: Vec<PackEntry>,
+}
+
+/// Fetch a pack list over HTTP and parse it
+pub async fn fetch_packlist(url: &str) -> Result<ParsedPacklist, XdccError> {
+    let response = build_http_client(None)
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| XdccError::SearchFailed(format!("Pack list HTTP error: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(XdccError::SearchFailed(format!(
+            "Pack list HTTP status {}",
+            response.status()
+        )));
+    }
+
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| XdccError::SearchFailed(format!("Pack list read error: {}", e)))?;
+
+    Ok(parse_packlist(&decode(&body)))
+}
+
+/// Decode a pack list as UTF-8, or as Latin-1 when it isn't valid UTF-8.
+/// Lists from older bots are often Latin-1, and decoding them lossily would
+/// replace every accented letter with `U+FFFD`.
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// Parse a pack list, auto-detecting plain text vs HTML
+pub fn parse_packlist(content: &str) -> ParsedPacklist {
+    let trimmed = content.trim_start();
+    if trimmed.starts_with('<') || trimmed.to_lowercase().contains("<table") {
+        parse_html_packlist(content)
+    } else {
+        parse_text_packlist(content)
+    }
+}
+
+/// Parse the iroffer text format:
+/// `#1   12x [1.2G] Some.File.Name.mkv`
+pub fn parse_text_packlist(content: &str) -> ParsedPacklist {
+    let pack_re = regex::Regex::new(r"^#(\d+)\s+(\d+)x\s+\[\s*([^\]]*?)\s*\]\s+(.+?)\s*$").unwrap();
+    let bot_re = regex::Regex::new(r"(?i)/msg\s+(\S+)\s+xdcc\s+send").unwrap();
+
+    let mut parsed = ParsedPacklist::default();
+    for line in content.lines() {
+        let line = crate::xdcc::TransferManager::strip_irc_codes(line);
+        let line = line.trim();
+
+        if parsed.bot.is_none() {
+            if let Some(caps) = bot_re.captures(line) {
+                parsed.bot = Some(caps[1].to_string());
+                continue;
+            }
+        }
+
+        if let Some(caps) = pack_re.captures(line) {
+            let Ok(slot) = caps[1].parse::<i32>() else {
+                continue;
+            };
+            let size_str = caps[3].to_string();
+            parsed.packs.push(PackEntry {
+                slot,
+                gets: caps[2].parse().ok(),
+                size: parse_size(&size_str),
+                size_str,
+                filename: caps[4].to_string(),
+            });
+        }
+    }
+    parsed
+}
+
+/// Parse an HTML pack list (iroffer web UI style tables):
+/// rows of `#slot | gets | size | filename`
+pub fn parse_html_packlist(content: &str) -> ParsedPacklist {
+    use scraper::{Html, Selector};
+
+    let document = Html::parse_document(content);
+    let row_selector = Selector::parse("tr").unwrap();
+    let cell_selector = Selector::parse("td").unwrap();
+
+    let mut parsed = ParsedPacklist::default();
+    for row in document.select(&row_selector) {
+        let cells: Vec<String> = row
+            .select(&cell_selector)
+            .map(|c| c.text().collect::<String>().trim().to_string())
+            .collect();
+        if cells.len() < 4 {
+            continue;
+        }
+
+        let Ok(slot) = cells[0].trim_start_matches('#').parse::<i32>() else {
+            continue;
+        };
+        let filename = cells[cells.len() - 1].clone();
+        if filename.is_empty() {
+            continue;
+        }
+        let size_str = cells[cells.len() - 2].clone();
+        parsed.packs.push(PackEntry {
+            slot,
+            gets: cells[1].trim_end_matches('x').parse().ok(),
+            size: parse_size(&size_str),
+            size_str,
+            filename,
+        });
+    }
+
+    // Fall back to the text parser for the bot name (often in a header or footer)
+    parsed.bot = parse_text_packlist(&document.root_element().text().collect::<String>()).bot;
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_packlist() {
+        let content = "\
+** 3 packs **  1 of 2 slots open, Record: 1234.5kB/s
+** To request a file, type \"/msg [XDCC]Bot xdcc send #x\" **
+#1   12x [1.2G] Some.Show.S01E01.1080p.mkv
+#2    0x [ 350M] Another.File.avi
+#10 3x [<1K] tiny.nfo
+";
+        let parsed = parse_text_packlist(content);
+        assert_eq!(parsed.bot.as_deref(), Some("[XDCC]Bot"));
+        assert_eq!(parsed.packs.len(), 3);
+        assert_eq!(parsed.packs[0].slot, 1);
+        assert_eq!(parsed.packs[0].gets, Some(12));
+        assert_eq!(parsed.packs[0].filename, "Some.Show.S01E01.1080p.mkv");
+        assert_eq!(parsed.packs[1].size, Some(350 * 1024 * 1024));
+        assert_eq!(parsed.packs[2].slot, 10);
+    }
+
+    #[test]
+    fn test_parse_html_packlist() {
+        let content = "<html><body><table>
+            <tr><th>Pack</th><th>Gets</th><th>Size</th><th>File</th></tr>
+            <tr><td>#5</td><td>7x</td><td>1.5G</td><td>Movie.2024.mkv</td></tr>
+            </table></body></html>";
+        let parsed = parse_packlist(content);
+        assert_eq!(parsed.packs.len(), 1);
+        assert_eq!(parsed.packs[0].slot, 5);
+        assert_eq!(parsed.packs[0].gets, Some(7));
+        assert_eq!(parsed.packs[0].filename, "Movie.2024.mkv");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode("#1 1x [1M] Café.mkv".as_bytes()),
+            "#1 1x [1M] Café.mkv"
+        );
+        assert_eq!(decode(b"#1 1x [1M] Caf\xe9.mkv"), "#1 1x [1M] Café.mkv");
+    }
+}
@@ -0,0 +1,169 @@
+use super::super::search::{
+    build_provider_client, lenient_items, parse_size, parse_timestamp, ping_url, read_json,
+    LenientNumber, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
+use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// External search provider running as a separate process (a "sidecar")
+///
+/// Sidecars implement a single endpoint: `POST {url}/search` with
+/// `{"query": "..."}`, answering `{"results": [...]}` (or a bare array) of
+/// `{network, channel, bot, pack, filename, size, gets, last_seen}` items.
+pub struct GenericHttpProvider {
+    name: String,
+    client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
+    skipped: AtomicUsize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SidecarResult {
+    network: String,
+    channel: String,
+    bot: String,
+    #[serde(alias = "pack_number", alias = "slot")]
+    pack: LenientNumber,
+    #[serde(alias = "file_name", alias = "name")]
+    filename: String,
+    /// Bytes, or a human-readable size such as `1.2G`
+    #[serde(default, alias = "file_size")]
+    size: LenientNumber,
+    #[serde(default, alias = "downloads")]
+    gets: LenientNumber,
+    /// When the pack was last announced, as Unix time or RFC 3339
+    #[serde(default, alias = "last_announced", alias = "date")]
+    last_seen: LenientNumber,
+}
+
+impl SidecarResult {
+    fn into_result(self) -> Option<XdccSearchResult> {
+        let slot = self.pack.as_i64().filter(|n| *n > 0)? as i32;
+        if self.filename.is_empty() || self.bot.is_empty() || self.network.is_empty() {
+            return None;
+        }
+        let (size, size_str) = match &self.size {
+            LenientNumber::Text(s) => (parse_size(s), s.clone()),
+            n => (n.as_i64().map(|b| b as u64), String::new()),
+        };
+        let channel = if self.channel.starts_with('#') || self.channel.is_empty() {
+            self.channel
+        } else {
+            format!("#{}", self.channel)
+        };
+        Some(XdccSearchResult {
+            url: XdccUrl {
+                network: self.network.clone(),
+                channel: channel.clone(),
+                bot: self.bot.clone(),
+                slot,
+            },
+            filename: self.filename,
+            size,
+            size_str,
+            bot: self.bot,
+            network: self.network,
+            channel,
+            slot,
+            gets: self.gets.as_i64().map(|g| g as u32),
+            last_seen: parse_timestamp(&self.last_seen),
+            annotations: Default::default(),
+        })
+    }
+}
+
+impl GenericHttpProvider {
+    pub fn new(
+        name: &str,
+        url: &str,
+        proxy_url: Option<&str>,
+        settings: &ProviderSettings,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            client: build_provider_client(proxy_url, settings),
+            base_url: url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
+            skipped: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl XdccSearchProvider for GenericHttpProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+        let response = self
+            .client
+            .post(format!("{}/search", self.base_url))
+            .json(&serde_json::json!({ "query": query }))
+            .send()
+            .await
+            .map_err(|e| XdccError::SearchFailed(format!("{} HTTP error: {}", self.name, e)))?;
+
+        let value = read_json(response).await?;
+        let (items, mut skipped) = lenient_items::<SidecarResult>(&value, &["results"])?;
+        let total = items.len();
+        let mut results: Vec<XdccSearchResult> = items
+            .into_iter()
+            .filter_map(SidecarResult::into_result)
+            .collect();
+        skipped += total - results.len();
+        self.skipped.fetch_add(skipped, Ordering::Relaxed);
+
+        tracing::info!("{}: returned {} results", self.name, results.len());
+        self.limits.truncate(&mut results);
+        Ok(results)
+    }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &self.base_url).await
+    }
+
+    fn take_skipped(&self) -> usize {
+        self.skipped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_results() {
+        let value = serde_json::json!({
+            "results": [
+                {"network": "irc.rizon.net", "channel": "news", "bot": "Bot",
+                 "pack": "#12", "filename": "Show.S01E01.mkv", "size": "1.5G", "gets": 40,
+                 "last_seen": 1700000000},
+                {"network": "irc.rizon.net", "channel": "#news", "bot": "Bot",
+                 "pack_number": 3, "file_name": "Movie.mkv", "size": 1024},
+                {"network": "irc.rizon.net", "channel": "#news", "bot": "Bot",
+                 "pack": 0, "filename": "Broken.mkv"},
+                {"filename": "Incomplete.mkv"}
+            ]
+        });
+        let (items, skipped) = lenient_items::<SidecarResult>(&value, &["results"]).unwrap();
+        assert_eq!(skipped, 1);
+        let results: Vec<_> = items
+            .into_iter()
+            .filter_map(SidecarResult::into_result)
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].channel, "#news");
+        assert_eq!(results[0].url.slot, 12);
+        assert_eq!(results[0].size, Some(1610612736));
+        assert_eq!(results[0].gets, Some(40));
+        assert_eq!(results[1].slot, 3);
+        assert_eq!(results[1].size, Some(1024));
+        assert_eq!(results[0].last_seen.unwrap().timestamp(), 1700000000);
+        assert_eq!(results[1].last_seen, None);
+    }
+}
@@ -0,0 +1,64 @@
+use super::super::search::XdccSearchProvider;
+use crate::db::Database;
+use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Searches pack lists imported into the local database
+pub struct LocalPackProvider {
+    database: Arc<Database>,
+}
+
+impl LocalPackProvider {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait]
+impl XdccSearchProvider for LocalPackProvider {
+    fn name(&self) -> &str {
+        "Local"
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+        let packs = self
+            .database
+            .search_packs(query, 500)
+            .map_err(|e| XdccError::SearchFailed(format!("Local pack search error: {}", e)))?;
+
+        Ok(packs
+            .into_iter()
+            .map(|p| XdccSearchResult {
+                url: XdccUrl {
+                    network: p.network.clone(),
+                    channel: p.channel.clone(),
+                    bot: p.bot.clone(),
+                    slot: p.slot,
+                },
+                filename: p.file_name,
+                size: p.size.map(|s| s as u64),
+                size_str: p.size_str,
+                bot: p.bot,
+                network: p.network,
+                channel: p.channel,
+                slot: p.slot,
+                gets: p.gets.map(|g| g as u32),
+                last_seen: chrono::DateTime::parse_from_rfc3339(&p.imported_at)
+                    .ok()
+                    .map(|t| t.with_timezone(&chrono::Utc)),
+                annotations: Default::default(),
+            })
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        self.database
+            .health_check()
+            .map_err(|e| XdccError::SearchFailed(format!("Local pack database error: {}", e)))
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
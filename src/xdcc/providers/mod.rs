@@ -1,9 +1,17 @@
+pub mod generic_http;
+pub mod local;
 pub mod nibl;
+pub mod simulated;
 pub mod skullxdcc;
+pub mod sunxdcc;
 pub mod xdcc_eu;
 pub mod xdcc_rocks;
 
+pub use generic_http::GenericHttpProvider;
+pub use local::LocalPackProvider;
 pub use nibl::NiblProvider;
+pub use simulated::SimulatedProvider;
 pub use skullxdcc::SkullXdccProvider;
+pub use sunxdcc::SunXdccProvider;
 pub use xdcc_eu::XdccEuProvider;
 pub use xdcc_rocks::XdccRocksProvider;
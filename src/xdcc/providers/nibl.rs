@@ -1,12 +1,20 @@
-use super::super::search::{build_http_client, parse_size, XdccSearchProvider};
+use super::super::search::{
+    build_provider_client, lenient_items, parse_size, parse_timestamp, ping_url, read_json,
+    LenientNumber, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
 use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// NIBL search provider (nibl.co.uk) - Anime-focused XDCC search
 /// All bots are on irc.rizon.net / #nibl
 pub struct NiblProvider {
     client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
+    skipped: AtomicUsize,
     /// Cached bot list: maps bot ID → bot name
     bot_cache: tokio::sync::RwLock<Option<NiblBotCache>>,
 }
@@ -22,17 +30,16 @@ impl NiblBotCache {
     }
 }
 
-#[derive(Debug, Deserialize)]
 struct NiblApiResponse<T> {
-    #[serde(default)]
     status: String,
-    #[serde(default)]
     content: Vec<T>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 struct NiblBot {
-    id: i64,
+    #[serde(default)]
+    id: LenientNumber,
+    #[serde(default)]
     name: String,
 }
 
@@ -40,24 +47,39 @@ struct NiblBot {
 #[serde(rename_all = "camelCase")]
 struct NiblPack {
     #[serde(default)]
-    bot_id: i64,
+    bot_id: LenientNumber,
     #[serde(default)]
-    number: i32,
+    number: LenientNumber,
     #[serde(default)]
     name: String,
     #[serde(default)]
     size: String,
     #[serde(default)]
-    sizekbits: u64,
+    sizekbits: LenientNumber,
+    #[serde(default)]
+    last_modified: LenientNumber,
 }
 
 const NIBL_NETWORK: &str = "irc.rizon.net";
 const NIBL_CHANNEL: &str = "#nibl";
+const NIBL_BASE_URL: &str = "https://api.nibl.co.uk";
 
 impl NiblProvider {
     pub fn new(proxy_url: Option<&str>) -> Self {
+        Self::with_settings(proxy_url, &ProviderSettings::default())
+    }
+
+    pub fn with_settings(proxy_url: Option<&str>, settings: &ProviderSettings) -> Self {
+        let base_url = if settings.base_url.is_empty() {
+            NIBL_BASE_URL
+        } else {
+            &settings.base_url
+        };
         Self {
-            client: build_http_client(proxy_url),
+            client: build_provider_client(proxy_url, settings),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
+            skipped: AtomicUsize::new(0),
             bot_cache: tokio::sync::RwLock::new(None),
         }
     }
@@ -77,20 +99,17 @@ impl NiblProvider {
         // Fetch fresh bot list
         let response = self
             .client
-            .get("https://api.nibl.co.uk/nibl/bots")
+            .get(format!("{}/nibl/bots", self.base_url))
             .send()
             .await
             .map_err(|e| XdccError::SearchFailed(format!("NIBL bots HTTP error: {}", e)))?;
 
-        let api_resp: NiblApiResponse<NiblBot> = response
-            .json()
-            .await
-            .map_err(|e| XdccError::SearchFailed(format!("NIBL bots JSON error: {}", e)))?;
+        let api_resp: NiblApiResponse<NiblBot> = Self::parse_response(response).await?.0;
 
         let bots: std::collections::HashMap<i64, String> = api_resp
             .content
             .into_iter()
-            .map(|b| (b.id, b.name))
+            .filter_map(|b| Some((b.id.as_i64()?, b.name)))
             .collect();
 
         tracing::info!("NIBL: cached {} bots", bots.len());
@@ -120,7 +139,8 @@ impl NiblProvider {
         size: u32,
     ) -> Result<NiblApiResponse<NiblPack>, XdccError> {
         let url = format!(
-            "https://api.nibl.co.uk/nibl/search?query={}&botId=-1&page={}&size={}",
+            "{}/nibl/search?query={}&botId=-1&page={}&size={}",
+            self.base_url,
             urlencoding::encode(query),
             page,
             size
@@ -133,10 +153,32 @@ impl NiblProvider {
             .await
             .map_err(|e| XdccError::SearchFailed(format!("NIBL search HTTP error: {}", e)))?;
 
-        response
-            .json()
-            .await
-            .map_err(|e| XdccError::SearchFailed(format!("NIBL search JSON error: {}", e)))
+        let (resp, skipped) = Self::parse_response(response).await?;
+        self.skipped.fetch_add(skipped, Ordering::Relaxed);
+        Ok(resp)
+    }
+
+    /// Parse a `{status, content: [...]}` response, skipping malformed items
+    async fn parse_response<T: serde::de::DeserializeOwned>(
+        response: reqwest::Response,
+    ) -> Result<(NiblApiResponse<T>, usize), XdccError> {
+        let value = read_json(response).await?;
+        let status = value
+            .get("status")
+            .and_then(|s| s.as_str())
+            .unwrap_or_default()
+            .to_string();
+        if status != "OK" {
+            return Ok((
+                NiblApiResponse {
+                    status,
+                    content: Vec::new(),
+                },
+                0,
+            ));
+        }
+        let (content, skipped) = lenient_items(&value, &["content", "data"])?;
+        Ok((NiblApiResponse { status, content }, skipped))
     }
 }
 
@@ -150,7 +192,7 @@ impl XdccSearchProvider for NiblProvider {
         // Ensure bot cache is populated
         self.ensure_bots().await?;
 
-        // Fetch up to 250 results (max 5 pages of 50)
+        // Fetch up to max_pages pages of 50
         let first = self.fetch_page(query, 0, 50).await?;
         if first.status != "OK" {
             return Ok(Vec::new());
@@ -162,7 +204,11 @@ impl XdccSearchProvider for NiblProvider {
 
         // Fetch additional pages if first page was full
         if all_packs.len() >= 50 {
-            for page in 1..5u32 {
+            for page in 1..self.limits.max_pages {
+                if self.limits.is_full(all_packs.len()) {
+                    break;
+                }
+                self.limits.delay().await;
                 match self.fetch_page(query, page, 50).await {
                     Ok(resp) => {
                         if resp.content.is_empty() {
@@ -184,14 +230,16 @@ impl XdccSearchProvider for NiblProvider {
 
         let mut results = Vec::with_capacity(all_packs.len());
         for pack in all_packs {
-            if pack.name.is_empty() || pack.number == 0 {
+            let number = pack.number.as_i64().unwrap_or(0) as i32;
+            if pack.name.is_empty() || number <= 0 {
                 continue;
             }
 
-            let bot_name = self.bot_name(pack.bot_id).await;
+            let bot_name = self.bot_name(pack.bot_id.as_i64().unwrap_or(-1)).await;
 
-            let size_bytes = if pack.sizekbits > 0 {
-                Some(pack.sizekbits)
+            let sizekbits = pack.sizekbits.as_i64().unwrap_or(0);
+            let size_bytes = if sizekbits > 0 {
+                Some(sizekbits as u64)
             } else {
                 parse_size(&pack.size)
             };
@@ -201,7 +249,7 @@ impl XdccSearchProvider for NiblProvider {
                     network: NIBL_NETWORK.to_string(),
                     channel: NIBL_CHANNEL.to_string(),
                     bot: bot_name.clone(),
-                    slot: pack.number,
+                    slot: number,
                 },
                 filename: pack.name,
                 size: size_bytes,
@@ -209,11 +257,22 @@ impl XdccSearchProvider for NiblProvider {
                 bot: bot_name,
                 network: NIBL_NETWORK.to_string(),
                 channel: NIBL_CHANNEL.to_string(),
-                slot: pack.number,
+                slot: number,
                 gets: None,
+                last_seen: parse_timestamp(&pack.last_modified),
+                annotations: Default::default(),
             });
         }
 
+        self.limits.truncate(&mut results);
         Ok(results)
     }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &format!("{}/nibl/bots", self.base_url)).await
+    }
+
+    fn take_skipped(&self) -> usize {
+        self.skipped.swap(0, Ordering::Relaxed)
+    }
 }
@@ -0,0 +1,70 @@
+use super::super::search::XdccSearchProvider;
+use crate::simulate::{MockPack, BOT, CHANNEL, NETWORK};
+use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
+use async_trait::async_trait;
+
+/// Lists the packs of the simulation mode bot
+pub struct SimulatedProvider {
+    packs: Vec<MockPack>,
+}
+
+impl SimulatedProvider {
+    pub fn new(packs: &[MockPack]) -> Self {
+        Self {
+            packs: packs.to_vec(),
+        }
+    }
+}
+
+#[async_trait]
+impl XdccSearchProvider for SimulatedProvider {
+    fn name(&self) -> &str {
+        "Simulated"
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+        let terms: Vec<String> = query
+            .split(|c: char| c.is_whitespace() || c == '.')
+            .filter(|t| !t.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Ok(self
+            .packs
+            .iter()
+            .enumerate()
+            .filter(|(_, pack)| {
+                let name = pack.name.to_lowercase();
+                terms.iter().all(|t| name.contains(t.as_str()))
+            })
+            .map(|(i, pack)| {
+                let slot = i as i32 + 1;
+                XdccSearchResult {
+                    url: XdccUrl {
+                        network: NETWORK.to_string(),
+                        channel: CHANNEL.to_string(),
+                        bot: BOT.to_string(),
+                        slot,
+                    },
+                    filename: pack.name.clone(),
+                    size: Some(pack.size),
+                    size_str: format!("{}M", pack.size >> 20),
+                    bot: BOT.to_string(),
+                    network: NETWORK.to_string(),
+                    channel: CHANNEL.to_string(),
+                    slot,
+                    gets: None,
+                    last_seen: None,
+                    annotations: Default::default(),
+                }
+            })
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        Ok(())
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
@@ -1,22 +1,27 @@
-use super::super::search::{build_http_client, parse_size, XdccSearchProvider};
+use super::super::search::{
+    build_provider_client, lenient_items, lenient_u32, parse_size, ping_url, read_json,
+    LenientNumber, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
 use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// SkullXDCC search provider (skullxdcc.com)
 pub struct SkullXdccProvider {
     client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
+    skipped: AtomicUsize,
 }
 
-#[derive(Debug, Deserialize)]
+const SKULLXDCC_BASE_URL: &str = "https://skullxdcc.com";
+
 struct SkullXdccResponse {
-    #[serde(default)]
     error: bool,
-    #[serde(default)]
     data: Vec<SkullXdccResult>,
-    #[serde(default)]
     total_results: u32,
-    #[serde(default)]
     pages: u32,
 }
 
@@ -29,25 +34,38 @@ struct SkullXdccResult {
     #[serde(default)]
     bot: String,
     #[serde(default)]
-    packnum: i32,
+    packnum: LenientNumber,
     #[serde(default)]
     fname: String,
     #[serde(default)]
     fsize: String,
     #[serde(default)]
-    gets: u32,
+    gets: LenientNumber,
 }
 
 impl SkullXdccProvider {
     pub fn new(proxy_url: Option<&str>) -> Self {
+        Self::with_settings(proxy_url, &ProviderSettings::default())
+    }
+
+    pub fn with_settings(proxy_url: Option<&str>, settings: &ProviderSettings) -> Self {
+        let base_url = if settings.base_url.is_empty() {
+            SKULLXDCC_BASE_URL
+        } else {
+            &settings.base_url
+        };
         Self {
-            client: build_http_client(proxy_url),
+            client: build_provider_client(proxy_url, settings),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
+            skipped: AtomicUsize::new(0),
         }
     }
 
     async fn fetch_page(&self, query: &str, page: u32) -> Result<SkullXdccResponse, XdccError> {
         let url = format!(
-            "https://skullxdcc.com/ws.php?sterm={}&limit_results=250&page={}",
+            "{}/ws.php?sterm={}&limit_results=250&page={}",
+            self.base_url,
             urlencoding::encode(query),
             page
         );
@@ -59,10 +77,24 @@ impl SkullXdccProvider {
             .await
             .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
 
-        response
-            .json()
-            .await
-            .map_err(|e| XdccError::SearchFailed(format!("JSON parse error: {}", e)))
+        let value = read_json(response).await?;
+        if value.get("error").and_then(|e| e.as_bool()) == Some(true) {
+            return Ok(SkullXdccResponse {
+                error: true,
+                data: Vec::new(),
+                total_results: 0,
+                pages: 0,
+            });
+        }
+
+        let (data, skipped) = lenient_items(&value, &["data", "results"])?;
+        self.skipped.fetch_add(skipped, Ordering::Relaxed);
+        Ok(SkullXdccResponse {
+            error: false,
+            data,
+            total_results: lenient_u32(&value, &["total_results", "total"]),
+            pages: lenient_u32(&value, &["pages", "total_pages"]),
+        })
     }
 }
 
@@ -85,28 +117,33 @@ impl XdccSearchProvider for SkullXdccProvider {
         );
 
         let mut all_data = first.data;
-        let max_pages = first.pages.min(5);
+        let max_pages = first.pages.min(self.limits.max_pages);
         for page in 1..max_pages {
+            if self.limits.is_full(all_data.len()) {
+                break;
+            }
+            self.limits.delay().await;
             if let Ok(resp) = self.fetch_page(query, page).await {
                 all_data.extend(resp.data);
             }
         }
 
-        Ok(all_data
+        let mut results: Vec<_> = all_data
             .into_iter()
             .filter(|r| !r.network.is_empty() && !r.bot.is_empty() && !r.fname.is_empty())
-            .map(|r| {
+            .filter_map(|r| {
+                let slot = r.packnum.as_i64()? as i32;
                 let channel = if r.channel.starts_with('#') {
                     r.channel
                 } else {
                     format!("#{}", r.channel)
                 };
-                XdccSearchResult {
+                Some(XdccSearchResult {
                     url: XdccUrl {
                         network: r.network.clone(),
                         channel: channel.clone(),
                         bot: r.bot.clone(),
-                        slot: r.packnum,
+                        slot,
                     },
                     filename: r.fname,
                     size: parse_size(&r.fsize),
@@ -114,10 +151,22 @@ impl XdccSearchProvider for SkullXdccProvider {
                     bot: r.bot,
                     network: r.network,
                     channel,
-                    slot: r.packnum,
-                    gets: Some(r.gets),
-                }
+                    slot,
+                    gets: r.gets.as_i64().map(|g| g as u32),
+                    last_seen: None,
+                    annotations: Default::default(),
+                })
             })
-            .collect())
+            .collect();
+        self.limits.truncate(&mut results);
+        Ok(results)
+    }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &self.base_url).await
+    }
+
+    fn take_skipped(&self) -> usize {
+        self.skipped.swap(0, Ordering::Relaxed)
     }
 }
@@ -0,0 +1,218 @@
+use super::super::search::{
+    build_provider_client, parse_size, ping_url, read_json, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
+use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// SunXDCC search provider (sunxdcc.com)
+pub struct SunXdccProvider {
+    client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
+    skipped: AtomicUsize,
+}
+
+const SUNXDCC_BASE_URL: &str = "https://sunxdcc.com";
+
+/// Column of a SunXDCC response, one entry per result
+fn column<'a>(value: &'a serde_json::Value, key: &str) -> &'a [serde_json::Value] {
+    value
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Text of a cell that may be sent as a string or a number
+fn cell(column: &[serde_json::Value], i: usize) -> String {
+    match column.get(i) {
+        Some(serde_json::Value::String(s)) => s.trim().to_string(),
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Leading number of `#12` or `340x`
+fn number(s: &str) -> Option<i64> {
+    let digits: String = s
+        .trim_start_matches('#')
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Results of a response, which lists each field as a separate array
+/// (`network`, `channel`, `bot`, `packnum`, `fname`, `fsize`, `gets`), and the
+/// number of rows dropped for missing a network, bot, file name or pack
+fn parse_results(value: &serde_json::Value) -> Result<(Vec<XdccSearchResult>, usize), XdccError> {
+    if value.get("fname").and_then(|v| v.as_array()).is_none() {
+        return Err(XdccError::UnexpectedResponse(
+            "no result list under \"fname\"".to_string(),
+        ));
+    }
+    let networks = column(value, "network");
+    let channels = column(value, "channel");
+    let bots = column(value, "bot");
+    let packs = column(value, "packnum");
+    let names = column(value, "fname");
+    let sizes = column(value, "fsize");
+    let gets = column(value, "gets");
+
+    let mut skipped = 0;
+    let mut results = Vec::new();
+    for i in 0..names.len() {
+        let (network, bot, filename) = (cell(networks, i), cell(bots, i), cell(names, i));
+        let slot = number(&cell(packs, i)).filter(|n| *n > 0).map(|n| n as i32);
+        let Some(slot) =
+            slot.filter(|_| !network.is_empty() && !bot.is_empty() && !filename.is_empty())
+        else {
+            skipped += 1;
+            continue;
+        };
+        let channel = cell(channels, i);
+        let channel = if channel.starts_with('#') {
+            channel
+        } else {
+            format!("#{}", channel)
+        };
+        let size_str = cell(sizes, i);
+        results.push(XdccSearchResult {
+            url: XdccUrl {
+                network: network.clone(),
+                channel: channel.clone(),
+                bot: bot.clone(),
+                slot,
+            },
+            filename,
+            size: parse_size(&size_str),
+            size_str,
+            bot,
+            network,
+            channel,
+            slot,
+            gets: number(&cell(gets, i)).map(|g| g as u32),
+            last_seen: None,
+            annotations: Default::default(),
+        });
+    }
+    Ok((results, skipped))
+}
+
+impl SunXdccProvider {
+    pub fn new(proxy_url: Option<&str>) -> Self {
+        Self::with_settings(proxy_url, &ProviderSettings::default())
+    }
+
+    pub fn with_settings(proxy_url: Option<&str>, settings: &ProviderSettings) -> Self {
+        let base_url = if settings.base_url.is_empty() {
+            SUNXDCC_BASE_URL
+        } else {
+            &settings.base_url
+        };
+        Self {
+            client: build_provider_client(proxy_url, settings),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
+            skipped: AtomicUsize::new(0),
+        }
+    }
+
+    async fn fetch_page(&self, query: &str, page: u32) -> Result<Vec<XdccSearchResult>, XdccError> {
+        let url = format!(
+            "{}/deliver.php?sterm={}&page={}",
+            self.base_url,
+            urlencoding::encode(query),
+            page
+        );
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
+
+        let value = read_json(response).await?;
+        // No matches come back as an empty object or array
+        if value.as_array().is_some_and(|a| a.is_empty())
+            || value.as_object().is_some_and(|o| o.is_empty())
+        {
+            return Ok(Vec::new());
+        }
+        let (results, skipped) = parse_results(&value)?;
+        self.skipped.fetch_add(skipped, Ordering::Relaxed);
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl XdccSearchProvider for SunXdccProvider {
+    fn name(&self) -> &str {
+        "SunXDCC"
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+        // The response has no page count: read pages until one comes back empty
+        let mut results = self.fetch_page(query, 0).await?;
+        let mut pages = 1;
+        let mut last_len = results.len();
+        while pages < self.limits.max_pages && last_len > 0 && !self.limits.is_full(results.len()) {
+            self.limits.delay().await;
+            match self.fetch_page(query, pages).await {
+                Ok(page) => {
+                    last_len = page.len();
+                    results.extend(page);
+                }
+                Err(_) => break,
+            }
+            pages += 1;
+        }
+
+        tracing::info!("SunXDCC: {} results across {} pages", results.len(), pages);
+        self.limits.truncate(&mut results);
+        Ok(results)
+    }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &self.base_url).await
+    }
+
+    fn take_skipped(&self) -> usize {
+        self.skipped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_results() {
+        let value = serde_json::json!({
+            "botrec": ["1.2MB/s", "0", "0", "0"],
+            "network": ["irc.rizon.net", "irc.abjects.net", "irc.rizon.net", ""],
+            "bot": ["Ginpachi-Sensei", "MovieBot", "Bot", "Bot"],
+            "channel": ["#Ginpachi-Sensei", "moviegods", "#news", "#news"],
+            "packnum": ["#1234", 7, "#abc", "#2"],
+            "gets": ["340x", "12x", "0x", "1x"],
+            "fsize": ["[1.4G]", "[700M]", "[1K]", "[1K]"],
+            "fname": ["Show.S01E01.mkv", "Movie.2020.mkv", "Broken.mkv", "NoNetwork.mkv"]
+        });
+        let (results, skipped) = parse_results(&value).unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url.slot, 1234);
+        assert_eq!(results[0].url.channel, "#Ginpachi-Sensei");
+        assert_eq!(results[0].size, Some(1503238553));
+        assert_eq!(results[0].size_str, "[1.4G]");
+        assert_eq!(results[0].gets, Some(340));
+        assert_eq!(results[1].channel, "#moviegods");
+        assert_eq!(results[1].slot, 7);
+        assert_eq!(results[1].size, Some(700 * 1024 * 1024));
+
+        assert!(parse_results(&serde_json::json!({"error": "down"})).is_err());
+    }
+}
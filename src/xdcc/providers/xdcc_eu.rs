@@ -1,16 +1,34 @@
-use super::super::search::{build_http_client, parse_size, XdccSearchProvider};
+use super::super::search::{
+    build_provider_client, detect_block, parse_size, ping_url, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
 use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
 use async_trait::async_trait;
 
 /// XDCC.eu search provider (HTML scraping)
 pub struct XdccEuProvider {
     client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
 }
 
+const XDCC_EU_BASE_URL: &str = "https://www.xdcc.eu";
+
 impl XdccEuProvider {
     pub fn new(proxy_url: Option<&str>) -> Self {
+        Self::with_settings(proxy_url, &ProviderSettings::default())
+    }
+
+    pub fn with_settings(proxy_url: Option<&str>, settings: &ProviderSettings) -> Self {
+        let base_url = if settings.base_url.is_empty() {
+            XDCC_EU_BASE_URL
+        } else {
+            &settings.base_url
+        };
         Self {
-            client: build_http_client(proxy_url),
+            client: build_provider_client(proxy_url, settings),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
         }
     }
 }
@@ -25,7 +43,8 @@ impl XdccSearchProvider for XdccEuProvider {
         use scraper::{Html, Selector};
 
         let url = format!(
-            "https://www.xdcc.eu/search.php?searchkey={}",
+            "{}/search.php?searchkey={}",
+            self.base_url,
             urlencoding::encode(query)
         );
 
@@ -36,6 +55,7 @@ impl XdccSearchProvider for XdccEuProvider {
             .await
             .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
 
+        let status = response.status();
         let html = response
             .text()
             .await
@@ -102,9 +122,23 @@ impl XdccSearchProvider for XdccEuProvider {
                 channel,
                 slot,
                 gets,
+                last_seen: None,
+                annotations: Default::default(),
             });
         }
 
+        // An empty page may be a captcha served instead of the result table
+        if results.is_empty() {
+            if let Some(page) = detect_block(status, &html) {
+                return Err(XdccError::Blocked(page.to_string()));
+            }
+        }
+
+        self.limits.truncate(&mut results);
         Ok(results)
     }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &self.base_url).await
+    }
 }
@@ -1,20 +1,26 @@
-use super::super::search::{build_http_client, parse_size, XdccSearchProvider};
+use super::super::search::{
+    build_provider_client, lenient_items, lenient_u32, parse_size, ping_url, read_json,
+    LenientNumber, PageLimits, XdccSearchProvider,
+};
+use crate::config::ProviderSettings;
 use crate::xdcc::{XdccError, XdccSearchResult, XdccUrl};
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// XDCC.rocks search provider
 pub struct XdccRocksProvider {
     client: reqwest::Client,
+    base_url: String,
+    limits: PageLimits,
+    skipped: AtomicUsize,
 }
 
-#[derive(Debug, Deserialize)]
+const XDCC_ROCKS_BASE_URL: &str = "https://xdcc.rocks";
+
 struct XdccRocksResponse {
-    #[serde(default)]
     results: Vec<XdccRocksServer>,
-    #[serde(default)]
     page: u32,
-    #[serde(default)]
     maxpages: u32,
 }
 
@@ -47,9 +53,9 @@ struct XdccRocksBot {
 #[derive(Debug, Deserialize)]
 struct XdccRocksFile {
     #[serde(default)]
-    packnumber: i32,
+    packnumber: LenientNumber,
     #[serde(default)]
-    numdownloads: u32,
+    numdownloads: LenientNumber,
     #[serde(default)]
     file: XdccRocksFileInfo,
 }
@@ -64,14 +70,27 @@ struct XdccRocksFileInfo {
 
 impl XdccRocksProvider {
     pub fn new(proxy_url: Option<&str>) -> Self {
+        Self::with_settings(proxy_url, &ProviderSettings::default())
+    }
+
+    pub fn with_settings(proxy_url: Option<&str>, settings: &ProviderSettings) -> Self {
+        let base_url = if settings.base_url.is_empty() {
+            XDCC_ROCKS_BASE_URL
+        } else {
+            &settings.base_url
+        };
         Self {
-            client: build_http_client(proxy_url),
+            client: build_provider_client(proxy_url, settings),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limits: PageLimits::from_settings(settings),
+            skipped: AtomicUsize::new(0),
         }
     }
 
     async fn fetch_page(&self, query: &str, page: u32) -> Result<XdccRocksResponse, XdccError> {
         let url = format!(
-            "https://xdcc.rocks/search/?searchword={}&getpages=true&page={}",
+            "{}/search/?searchword={}&getpages=true&page={}",
+            self.base_url,
             urlencoding::encode(query),
             page
         );
@@ -83,10 +102,14 @@ impl XdccRocksProvider {
             .await
             .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
 
-        response
-            .json()
-            .await
-            .map_err(|e| XdccError::SearchFailed(format!("JSON parse error: {}", e)))
+        let value = read_json(response).await?;
+        let (results, skipped) = lenient_items(&value, &["results", "servers"])?;
+        self.skipped.fetch_add(skipped, Ordering::Relaxed);
+        Ok(XdccRocksResponse {
+            results,
+            page: lenient_u32(&value, &["page"]),
+            maxpages: lenient_u32(&value, &["maxpages", "pages"]),
+        })
     }
 }
 
@@ -107,8 +130,9 @@ impl XdccSearchProvider for XdccRocksProvider {
         );
 
         let mut all_data = first.results;
-        let max_pages = first.maxpages.min(5);
+        let max_pages = first.maxpages.min(self.limits.max_pages);
         for page in 2..=max_pages {
+            self.limits.delay().await;
             if let Ok(resp) = self.fetch_page(query, page).await {
                 all_data.extend(resp.results);
             }
@@ -131,6 +155,9 @@ impl XdccSearchProvider for XdccRocksProvider {
 
                 for bot in channel.bots {
                     for file in bot.files {
+                        let Some(slot) = file.packnumber.as_i64().map(|n| n as i32) else {
+                            continue;
+                        };
                         if file.file.filename.is_empty() {
                             continue;
                         }
@@ -139,7 +166,7 @@ impl XdccSearchProvider for XdccRocksProvider {
                                 network: network.clone(),
                                 channel: channel_name.clone(),
                                 bot: bot.botname.clone(),
-                                slot: file.packnumber,
+                                slot,
                             },
                             filename: file.file.filename,
                             size: parse_size(&file.file.filesize),
@@ -147,14 +174,25 @@ impl XdccSearchProvider for XdccRocksProvider {
                             bot: bot.botname.clone(),
                             network: network.clone(),
                             channel: channel_name.clone(),
-                            slot: file.packnumber,
-                            gets: Some(file.numdownloads),
+                            slot,
+                            gets: file.numdownloads.as_i64().map(|g| g as u32),
+                            last_seen: None,
+                            annotations: Default::default(),
                         });
                     }
                 }
             }
         }
 
+        self.limits.truncate(&mut results);
         Ok(results)
     }
+
+    async fn ping(&self) -> Result<(), XdccError> {
+        ping_url(&self.client, &self.base_url).await
+    }
+
+    fn take_skipped(&self) -> usize {
+        self.skipped.swap(0, Ordering::Relaxed)
+    }
 }
@@ -0,0 +1,224 @@
+//! Search Query Filters
+//!
+//! XDCC filenames are noisy, so searches accept `+keyword` (required) and
+//! `-keyword` (excluded) terms that are stripped from the provider query and
+//! applied to the aggregated results. An optional regex can narrow them further.
+//!
+//! With `search_normalize`, queries and filenames are transliterated before
+//! matching: full-width characters and CJK brackets become their ASCII forms
+//! and diacritics on Latin letters are dropped, so `pokemon` finds `Pokémon` and `[Grp]` finds
+//! `【Grp】`.
+
+use super::XdccSearchResult;
+use regex::{Regex, RegexBuilder};
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest accepted user-supplied regex
+const MAX_REGEX_LEN: usize = 256;
+/// Compiled program size limit, which bounds large repetitions like `a{1000}{1000}`
+const MAX_REGEX_SIZE: usize = 256 * 1024;
+
+/// Keyword filter applied to aggregated search results
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// Terms that must all appear in the filename
+    pub include: Vec<String>,
+    /// Terms that must not appear in the filename
+    pub exclude: Vec<String>,
+    /// Pattern filenames must match
+    pub regex: Option<Regex>,
+    /// Transliterate filenames before keyword matching
+    pub normalize: bool,
+}
+
+/// Transliterate `s` for matching: full-width forms and CJK brackets become
+/// ASCII, Latin diacritics are dropped and letters without a decomposition
+/// (`ß`, `ø`, `æ`...) are spelled out. Other scripts keep their marks, and
+/// the result is recomposed (NFC). Case is left alone.
+pub fn transliterate(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    // NFKD maps full-width forms to ASCII and splits accented letters
+    let mut latin = false;
+    for c in s.nfkd() {
+        if !is_combining(c) {
+            latin = is_latin(c);
+        }
+        match c {
+            // Combining diacritical marks of a Latin letter
+            '\u{0300}'..='\u{036F}' if latin => {}
+            '【' | '「' | '『' | '〔' | '〖' | '〘' | '〚' => out.push('['),
+            '】' | '」' | '』' | '〕' | '〗' | '〙' | '〛' => out.push(']'),
+            '〈' | '《' => out.push('<'),
+            '〉' | '》' => out.push('>'),
+            '〜' | '～' => out.push('~'),
+            '、' => out.push(','),
+            '。' | '・' => out.push('.'),
+            'ß' => out.push_str("ss"),
+            'æ' => out.push_str("ae"),
+            'Æ' => out.push_str("AE"),
+            'œ' => out.push_str("oe"),
+            'Œ' => out.push_str("OE"),
+            'ø' => out.push('o'),
+            'Ø' => out.push('O'),
+            'đ' => out.push('d'),
+            'Đ' => out.push('D'),
+            'ł' => out.push('l'),
+            'Ł' => out.push('L'),
+            'ı' => out.push('i'),
+            'þ' => out.push_str("th"),
+            'Þ' => out.push_str("TH"),
+            c => out.push(c),
+        }
+    }
+    out.nfc().collect()
+}
+
+/// Combining diacritical marks, which follow the letter they belong to in NFKD
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}')
+}
+
+/// Letters of the Latin script, including the accented ones NFKD leaves whole
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}')
+}
+
+impl SearchFilter {
+    /// Split `+term`/`-term` tokens out of a query, returning the query to send
+    /// to providers and the filter. Required terms stay in the provider query.
+    pub fn parse_query(query: &str) -> (String, Self) {
+        let mut filter = Self::default();
+        let mut terms = Vec::new();
+        for token in query.split_whitespace() {
+            if let Some(term) = token.strip_prefix('-').filter(|t| !t.is_empty()) {
+                filter.exclude.push(term.to_lowercase());
+            } else if let Some(term) = token.strip_prefix('+').filter(|t| !t.is_empty()) {
+                filter.include.push(term.to_lowercase());
+                terms.push(term);
+            } else {
+                terms.push(token);
+            }
+        }
+        (terms.join(" "), filter)
+    }
+
+    /// Add comma-separated include/exclude lists (e.g. from query parameters)
+    pub fn extend(&mut self, include: Option<&str>, exclude: Option<&str>) {
+        let split = |s: &str| {
+            s.split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>()
+        };
+        self.include.extend(include.map(split).unwrap_or_default());
+        self.exclude.extend(exclude.map(split).unwrap_or_default());
+    }
+
+    /// Compile a user-supplied regex, rejecting overly long or complex patterns
+    pub fn set_regex(&mut self, pattern: &str) -> Result<(), String> {
+        if pattern.len() > MAX_REGEX_LEN {
+            return Err(format!(
+                "Regex is too long (max {} characters)",
+                MAX_REGEX_LEN
+            ));
+        }
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(MAX_REGEX_SIZE)
+            .dfa_size_limit(MAX_REGEX_SIZE)
+            .nest_limit(32)
+            .build()
+            .map_err(|e| format!("Invalid regex: {}", e))?;
+        self.regex = Some(regex);
+        Ok(())
+    }
+
+    /// Transliterate the keywords and every filename matched from now on
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.normalize = normalize;
+        if normalize {
+            for term in self.include.iter_mut().chain(self.exclude.iter_mut()) {
+                *term = transliterate(term).to_lowercase();
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.regex.is_none()
+    }
+
+    /// Check a filename against the filter. Keywords are case-insensitive
+    /// substring matches; the regex is used as given.
+    pub fn matches(&self, filename: &str) -> bool {
+        let name = if self.normalize {
+            transliterate(filename).to_lowercase()
+        } else {
+            filename.to_lowercase()
+        };
+        self.include.iter().all(|t| name.contains(t.as_str()))
+            && !self.exclude.iter().any(|t| name.contains(t.as_str()))
+            && self.regex.as_ref().is_none_or(|re| re.is_match(filename))
+    }
+
+    pub fn apply(&self, results: &mut Vec<XdccSearchResult>) {
+        if !self.is_empty() {
+            results.retain(|r| self.matches(&r.filename));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let (query, mut filter) = SearchFilter::parse_query("show +1080p -HEVC - s01");
+        assert_eq!(query, "show 1080p - s01");
+        assert_eq!(filter.include, vec!["1080p"]);
+        assert_eq!(filter.exclude, vec!["hevc"]);
+
+        assert!(filter.matches("Show.S01E01.1080p.x264.mkv"));
+        assert!(!filter.matches("Show.S01E01.1080p.HEVC.mkv"));
+        assert!(!filter.matches("Show.S01E01.720p.mkv"));
+
+        filter.extend(None, Some("x264, "));
+        assert_eq!(filter.exclude, vec!["hevc", "x264"]);
+        assert!(!filter.matches("Show.S01E01.1080p.x264.mkv"));
+    }
+
+    #[test]
+    fn test_regex_filter() {
+        let mut filter = SearchFilter::default();
+        filter.set_regex(r"^\[Grp\].* - 0[1-3] ").unwrap();
+        assert!(filter.matches("[Grp] Show - 02 [1080p].mkv"));
+        assert!(!filter.matches("[Grp] Show - 04 [1080p].mkv"));
+        assert!(!filter.matches("[Other] Show - 02 [1080p].mkv"));
+
+        assert!(filter.set_regex("(unclosed").is_err());
+        assert!(filter.set_regex(&"a".repeat(MAX_REGEX_LEN + 1)).is_err());
+        assert!(filter.set_regex(r"\w{1000}{1000}").is_err());
+    }
+
+    #[test]
+    fn test_transliterate() {
+        assert_eq!(transliterate("Pokémon"), "Pokemon");
+        assert_eq!(
+            transliterate("【Ｇｒｐ】Ｓｈｏｗ　－　０１"),
+            "[Grp]Show - 01"
+        );
+        assert_eq!(transliterate("「Ａｎｉｍｅ」（ＴＶ）"), "[Anime](TV)");
+        assert_eq!(transliterate("Straße.Ærø.Łódź"), "Strasse.AEro.Lodz");
+        // Other scripts keep their marks
+        assert_eq!(transliterate("機動戦士ガンダム"), "機動戦士ガンダム");
+        assert_eq!(transliterate("Йога Ελλάδα"), "Йога Ελλάδα");
+        assert_eq!(transliterate("Café Йо"), "Cafe Йо");
+
+        let (query, mut filter) = SearchFilter::parse_query("show +[grp] +amelie -ｈｅｖｃ");
+        assert_eq!(query, "show [grp] amelie");
+        assert!(!filter.matches("【Grp】 Show - Amélie 1080p.mkv"));
+        filter.set_normalize(true);
+        assert!(filter.matches("【Grp】 Show - Amélie 1080p.mkv"));
+        assert!(!filter.matches("【Grp】 Show - Amélie 1080p HEVC.mkv"));
+        assert!(!filter.matches("[Other] Show - Amelie.mkv"));
+    }
+}
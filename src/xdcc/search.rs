@@ -3,8 +3,34 @@
 //! Implements search functionality for various XDCC search engines.
 use super::providers::*;
 
-use super::{XdccError, XdccSearchResult};
+use super::{XdccError, XdccSearchResult, XdccUrl};
+use crate::config::{AppConfig, ProviderSettings};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Best source for `filename` among search results, preferring the original bot
+fn pick_source(
+    original: &XdccUrl,
+    filename: &str,
+    results: Vec<XdccSearchResult>,
+) -> Option<XdccUrl> {
+    let mut matches: Vec<XdccSearchResult> = results
+        .into_iter()
+        .filter(|r| r.filename.eq_ignore_ascii_case(filename))
+        .collect();
+    if let Some(same_bot) = matches.iter().find(|r| {
+        r.url.bot.eq_ignore_ascii_case(&original.bot)
+            && r.url.network.eq_ignore_ascii_case(&original.network)
+    }) {
+        return Some(same_bot.url.clone());
+    }
+    matches.sort_by_key(|r| std::cmp::Reverse(r.gets.unwrap_or(0)));
+    matches.into_iter().next().map(|r| r.url)
+}
 
 /// Trait for XDCC search providers
 #[async_trait]
@@ -14,20 +40,129 @@ pub trait XdccSearchProvider: Send + Sync {
 
     /// Search for files matching the query
     async fn search(&self, query: &str) -> Result<Vec<XdccSearchResult>, XdccError>;
+
+    /// Check that the provider's site is reachable
+    async fn ping(&self) -> Result<(), XdccError>;
+
+    /// Whether the provider searches a remote site, rather than local or
+    /// simulated data that is always at hand
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    /// Number of malformed items skipped since the last call
+    fn take_skipped(&self) -> usize {
+        0
+    }
+}
+
+/// Kind of the last provider failure
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderErrorKind {
+    /// Network or HTTP status failure
+    Http,
+    /// Response could not be parsed
+    Parse,
+    /// Captcha or anti-bot page instead of results
+    Blocked,
+    Timeout,
+    Other,
+}
+
+/// Structured provider error
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderError {
+    pub kind: ProviderErrorKind,
+    pub message: String,
+}
+
+impl From<&XdccError> for ProviderError {
+    fn from(e: &XdccError) -> Self {
+        let kind = match e {
+            XdccError::SearchFailed(_) => ProviderErrorKind::Http,
+            XdccError::UnexpectedResponse(_) => ProviderErrorKind::Parse,
+            XdccError::Blocked(_) => ProviderErrorKind::Blocked,
+            XdccError::Timeout(_) => ProviderErrorKind::Timeout,
+            _ => ProviderErrorKind::Other,
+        };
+        Self {
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Outcome of the last search against a provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub name: String,
+    /// False if the last search failed
+    pub ok: bool,
+    pub last_search_at: Option<DateTime<Utc>>,
+    pub result_count: usize,
+    /// Malformed items dropped while parsing the last response
+    pub skipped_items: usize,
+    pub error: Option<ProviderError>,
+    /// Skipped by searches until then after a captcha or block page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_until: Option<DateTime<Utc>>,
 }
 
 /// Aggregates multiple search providers
 pub struct SearchAggregator {
     providers: Vec<Box<dyn XdccSearchProvider>>,
+    status: RwLock<HashMap<String, ProviderStatus>>,
+    /// How long a blocked provider is left alone
+    block_backoff: chrono::Duration,
 }
 
 impl SearchAggregator {
     pub fn new() -> Self {
         Self {
             providers: Vec::new(),
+            status: RwLock::new(HashMap::new()),
+            block_backoff: chrono::Duration::minutes(30),
         }
     }
 
+    /// Build the default providers with the HTTP overrides from the config
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut agg = Self::new();
+        agg.block_backoff = chrono::Duration::minutes(config.provider_block_backoff_mins as i64);
+        agg.add_provider(Box::new(SkullXdccProvider::with_settings(
+            None,
+            &config.provider_settings_for("SkullXDCC"),
+        )));
+        agg.add_provider(Box::new(XdccRocksProvider::with_settings(
+            None,
+            &config.provider_settings_for("XDCC.rocks"),
+        )));
+        agg.add_provider(Box::new(XdccEuProvider::with_settings(
+            None,
+            &config.provider_settings_for("XDCC.eu"),
+        )));
+        agg.add_provider(Box::new(NiblProvider::with_settings(
+            None,
+            &config.provider_settings_for("NIBL"),
+        )));
+        agg.add_provider(Box::new(SunXdccProvider::with_settings(
+            None,
+            &config.provider_settings_for("SunXDCC"),
+        )));
+        let mut sidecars: Vec<_> = config.external_providers.iter().collect();
+        sidecars.sort();
+        for (name, url) in sidecars {
+            agg.add_provider(Box::new(GenericHttpProvider::new(
+                name,
+                url,
+                None,
+                &config.provider_settings_for(name),
+            )));
+        }
+        agg
+    }
+
     pub fn with_default_providers(proxy_url: Option<&str>) -> Self {
         let mut agg = Self::new();
         // Add all providers - search runs in parallel
@@ -35,6 +170,7 @@ impl SearchAggregator {
         agg.add_provider(Box::new(XdccRocksProvider::new(proxy_url)));
         agg.add_provider(Box::new(XdccEuProvider::new(proxy_url)));
         agg.add_provider(Box::new(NiblProvider::new(proxy_url)));
+        agg.add_provider(Box::new(SunXdccProvider::new(proxy_url)));
         agg
     }
 
@@ -49,36 +185,112 @@ impl SearchAggregator {
         query: &str,
         target_providers: Option<&[String]>,
     ) -> Result<Vec<XdccSearchResult>, XdccError> {
+        Ok(self.search_reporting(query, target_providers).await.0)
+    }
+
+    /// Search like `search`, also naming the providers that were skipped as
+    /// blocked or failed, whose results are missing
+    async fn search_reporting(
+        &self,
+        query: &str,
+        target_providers: Option<&[String]>,
+    ) -> (Vec<XdccSearchResult>, Vec<String>) {
         use futures::future::join_all;
 
-        // Run searches in parallel (filtered)
-        let futures: Vec<_> = self
-            .providers
-            .iter()
-            .filter(|p| match target_providers {
-                Some(targets) => targets.iter().any(|t| t.eq_ignore_ascii_case(p.name())),
-                None => true,
-            })
-            .map(|p| p.search(query))
-            .collect();
-        let results = join_all(futures).await;
+        // Run searches in parallel (filtered), leaving blocked providers alone
+        let now = Utc::now();
+        let mut unanswered = Vec::new();
+        let selected: Vec<_> = {
+            let status = self.status.read().unwrap();
+            self.providers
+                .iter()
+                .filter(|p| match target_providers {
+                    Some(targets) => targets.iter().any(|t| t.eq_ignore_ascii_case(p.name())),
+                    None => true,
+                })
+                .filter(|p| {
+                    let until = status.get(p.name()).and_then(|s| s.blocked_until);
+                    let blocked = until.is_some_and(|until| until > now);
+                    if blocked {
+                        tracing::debug!("Skipping blocked provider {}", p.name());
+                        unanswered.push(p.name().to_string());
+                    }
+                    !blocked
+                })
+                .collect()
+        };
+        let results = join_all(selected.iter().map(|p| p.search(query))).await;
 
         let mut all_results = Vec::new();
-        for result in results {
-            match result {
+        for (provider, result) in selected.iter().zip(results) {
+            let skipped_items = provider.take_skipped();
+            if skipped_items > 0 {
+                tracing::warn!(
+                    "Provider {} skipped {} malformed items",
+                    provider.name(),
+                    skipped_items
+                );
+            }
+
+            let status = match result {
                 Ok(r) => {
-                    tracing::info!(
-                        "Provider {} returned {} results",
-                        // We need to re-match the result to the provider name, but for logging we can't easily get the index after filtering
-                        "XDCC", // simplified log to avoid index complexity
-                        r.len()
-                    );
+                    tracing::info!("Provider {} returned {} results", provider.name(), r.len());
+                    let status = ProviderStatus {
+                        name: provider.name().to_string(),
+                        ok: true,
+                        last_search_at: Some(Utc::now()),
+                        result_count: r.len(),
+                        skipped_items,
+                        error: None,
+                        blocked_until: None,
+                    };
                     all_results.extend(r);
+                    status
+                }
+                Err(XdccError::Blocked(page)) => {
+                    let until = Utc::now() + self.block_backoff;
+                    let message = format!(
+                        "{} answered with a {} instead of results. Open the site in a browser, \
+                         or set a cookie or user agent for it in provider_settings. Searches \
+                         skip it until {}.",
+                        provider.name(),
+                        page,
+                        until.format("%H:%M UTC")
+                    );
+                    tracing::warn!("{}", message);
+                    ProviderStatus {
+                        name: provider.name().to_string(),
+                        ok: false,
+                        last_search_at: Some(Utc::now()),
+                        result_count: 0,
+                        skipped_items,
+                        error: Some(ProviderError {
+                            kind: ProviderErrorKind::Blocked,
+                            message,
+                        }),
+                        blocked_until: Some(until),
+                    }
                 }
                 Err(e) => {
-                    tracing::warn!("Search provider failed: {}", e);
+                    tracing::warn!("Search provider {} failed: {}", provider.name(), e);
+                    ProviderStatus {
+                        name: provider.name().to_string(),
+                        ok: false,
+                        last_search_at: Some(Utc::now()),
+                        result_count: 0,
+                        skipped_items,
+                        error: Some(ProviderError::from(&e)),
+                        blocked_until: None,
+                    }
                 }
+            };
+            if !status.ok {
+                unanswered.push(provider.name().to_string());
             }
+            self.status
+                .write()
+                .unwrap()
+                .insert(provider.name().to_string(), status);
         }
 
         // Filter scenep2p bots with |P|
@@ -90,7 +302,84 @@ impl SearchAggregator {
         let mut seen = std::collections::HashSet::new();
         all_results.retain(|r| seen.insert(r.url.clone()));
 
-        Ok(all_results)
+        (all_results, unanswered)
+    }
+}
+
+impl SearchAggregator {
+    /// Search for a file again to find where it is offered now: the same bot
+    /// (packs get renumbered) or, if that bot no longer lists it, the most
+    /// downloaded other source
+    pub async fn find_source(
+        &self,
+        original: &XdccUrl,
+        filename: &str,
+    ) -> Result<Option<XdccUrl>, XdccError> {
+        let results = self.search(filename, None).await?;
+        Ok(pick_source(original, filename, results))
+    }
+
+    /// Like `find_source`, but fails unless every provider answered: a bot
+    /// missing from partial results may only be listed by a provider that
+    /// didn't
+    pub async fn find_source_everywhere(
+        &self,
+        original: &XdccUrl,
+        filename: &str,
+    ) -> Result<Option<XdccUrl>, XdccError> {
+        let (results, unanswered) = self.search_reporting(filename, None).await;
+        if !unanswered.is_empty() {
+            return Err(XdccError::SearchFailed(format!(
+                "no answer from {}",
+                unanswered.join(", ")
+            )));
+        }
+        Ok(pick_source(original, filename, results))
+    }
+
+    /// Outcome of the last search for every provider
+    pub fn provider_statuses(&self) -> Vec<ProviderStatus> {
+        let status = self.status.read().unwrap();
+        self.providers
+            .iter()
+            .map(|p| {
+                status.get(p.name()).cloned().unwrap_or(ProviderStatus {
+                    name: p.name().to_string(),
+                    ok: true,
+                    last_search_at: None,
+                    result_count: 0,
+                    skipped_items: 0,
+                    error: None,
+                    blocked_until: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Names of remote providers that currently respond to a ping
+    pub async fn reachable_providers(&self) -> Vec<String> {
+        use futures::future::join_all;
+
+        let remote: Vec<_> = self.providers.iter().filter(|p| p.is_remote()).collect();
+        let checks = join_all(remote.iter().map(|p| p.ping())).await;
+        remote
+            .into_iter()
+            .zip(checks)
+            .filter(|(_, r)| r.is_ok())
+            .map(|(p, _)| p.name().to_string())
+            .collect()
+    }
+
+    /// Ping every provider
+    pub async fn ping_providers(&self) -> Vec<(String, Result<(), XdccError>)> {
+        use futures::future::join_all;
+
+        let checks = join_all(self.providers.iter().map(|p| p.ping())).await;
+        self.providers
+            .iter()
+            .zip(checks)
+            .map(|(p, r)| (p.name().to_string(), r))
+            .collect()
     }
 }
 
@@ -102,10 +391,80 @@ impl Default for SearchAggregator {
 
 // ============= Helper Functions =============
 
+/// Pagination limits of a search provider
+#[derive(Debug, Clone, Copy)]
+pub struct PageLimits {
+    pub max_pages: u32,
+    /// 0 for no limit
+    pub max_results: usize,
+    pub page_delay: std::time::Duration,
+}
+
+impl PageLimits {
+    pub fn from_settings(settings: &ProviderSettings) -> Self {
+        Self {
+            max_pages: settings.max_pages.unwrap_or(5).max(1),
+            max_results: settings.max_results.unwrap_or(0),
+            page_delay: std::time::Duration::from_millis(settings.page_delay_ms.unwrap_or(0)),
+        }
+    }
+
+    /// Whether enough results have been collected to stop paginating
+    pub fn is_full(&self, collected: usize) -> bool {
+        self.max_results > 0 && collected >= self.max_results
+    }
+
+    /// Drop results beyond the cap
+    pub fn truncate<T>(&self, results: &mut Vec<T>) {
+        if self.max_results > 0 {
+            results.truncate(self.max_results);
+        }
+    }
+
+    /// Wait before requesting the next page
+    pub async fn delay(&self) {
+        if !self.page_delay.is_zero() {
+            tokio::time::sleep(self.page_delay).await;
+        }
+    }
+}
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
 pub fn build_http_client(proxy_url: Option<&str>) -> reqwest::Client {
+    build_provider_client(proxy_url, &ProviderSettings::default())
+}
+
+/// Build an HTTP client with a provider's User-Agent and extra headers
+pub fn build_provider_client(
+    proxy_url: Option<&str>,
+    settings: &ProviderSettings,
+) -> reqwest::Client {
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+    let user_agent = if settings.user_agent.is_empty() {
+        DEFAULT_USER_AGENT
+    } else {
+        &settings.user_agent
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &settings.headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => tracing::warn!("Ignoring invalid provider header: {}", name),
+        }
+    }
+
     let mut builder = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(30))
-        .user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36");
+        .user_agent(user_agent)
+        .default_headers(headers);
 
     if let Some(proxy) = proxy_url {
         if let Ok(proxy) = reqwest::Proxy::all(proxy) {
@@ -116,6 +475,167 @@ pub fn build_http_client(proxy_url: Option<&str>) -> reqwest::Client {
     builder.build().unwrap_or_default()
 }
 
+/// Issue a GET against a provider URL and treat any non-server-error response as reachable
+pub async fn ping_url(client: &reqwest::Client, url: &str) -> Result<(), XdccError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
+
+    if response.status().is_server_error() {
+        return Err(XdccError::SearchFailed(format!(
+            "HTTP status {}",
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+/// Recognize a captcha, anti-bot or access-denied page served instead of
+/// results, returning what kind of page it is
+pub fn detect_block(status: reqwest::StatusCode, body: &str) -> Option<&'static str> {
+    let head: String = body
+        .chars()
+        .take(64 * 1024)
+        .collect::<String>()
+        .to_lowercase();
+    if head.contains("cf-challenge")
+        || head.contains("challenge-platform")
+        || head.contains("cf_chl_")
+        || head.contains("<title>just a moment")
+        || head.contains("attention required! | cloudflare")
+    {
+        return Some("Cloudflare challenge page");
+    }
+    if head.contains("ddos-guard") {
+        return Some("DDoS-Guard check page");
+    }
+    if head.contains("captcha") {
+        return Some("captcha page");
+    }
+    let html = head.trim_start().starts_with("<!doctype html") || head.contains("<html");
+    match status.as_u16() {
+        403 if html => Some("\"access denied\" page (HTTP 403)"),
+        429 if html => Some("rate limit page (HTTP 429)"),
+        _ => None,
+    }
+}
+
+/// Read a provider response as loosely typed JSON
+pub async fn read_json(response: reqwest::Response) -> Result<serde_json::Value, XdccError> {
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| XdccError::SearchFailed(format!("HTTP error: {}", e)))?;
+
+    if !status.is_success() {
+        if let Some(page) = detect_block(status, &body) {
+            return Err(XdccError::Blocked(page.to_string()));
+        }
+        return Err(XdccError::SearchFailed(format!("HTTP status {}", status)));
+    }
+    serde_json::from_str(&body).map_err(|e| {
+        if let Some(page) = detect_block(status, &body) {
+            return XdccError::Blocked(page.to_string());
+        }
+        let snippet: String = body.chars().take(120).collect();
+        XdccError::UnexpectedResponse(format!("invalid JSON ({}): {}", e, snippet))
+    })
+}
+
+/// Extract the item list from a JSON response, looking under the first of `keys`
+/// that holds an array (or the root itself). Items that don't deserialize are
+/// skipped instead of failing the whole response; their count is returned.
+pub fn lenient_items<T: DeserializeOwned>(
+    value: &serde_json::Value,
+    keys: &[&str],
+) -> Result<(Vec<T>, usize), XdccError> {
+    let array = keys
+        .iter()
+        .find_map(|k| value.get(*k).and_then(|v| v.as_array()))
+        .or_else(|| value.as_array())
+        .ok_or_else(|| XdccError::UnexpectedResponse(format!("no result list under {:?}", keys)))?;
+
+    let mut skipped = 0;
+    let items = array
+        .iter()
+        .filter_map(|item| match serde_json::from_value::<T>(item.clone()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                tracing::debug!("Skipping malformed provider item: {}", e);
+                skipped += 1;
+                None
+            }
+        })
+        .collect();
+    Ok((items, skipped))
+}
+
+/// Read a numeric field that may be sent as a number or a string
+pub fn lenient_u32(value: &serde_json::Value, keys: &[&str]) -> u32 {
+    keys.iter()
+        .filter_map(|k| value.get(*k))
+        .find_map(|v| match v {
+            serde_json::Value::Number(n) => n.as_u64().map(|n| n as u32),
+            serde_json::Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Number that providers send either as a JSON number or a string
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LenientNumber {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Missing(()),
+}
+
+impl Default for LenientNumber {
+    fn default() -> Self {
+        Self::Missing(())
+    }
+}
+
+impl LenientNumber {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(n) => Some(*n),
+            Self::Float(f) => Some(*f as i64),
+            Self::Text(s) => s.trim().trim_start_matches('#').parse().ok(),
+            Self::Missing(()) => None,
+        }
+    }
+}
+
+/// Time a provider sends as Unix seconds (or milliseconds), RFC 3339 or
+/// `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn parse_timestamp(value: &LenientNumber) -> Option<DateTime<Utc>> {
+    let secs = match value {
+        LenientNumber::Text(s) => {
+            let s = s.trim();
+            if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+                return Some(t.with_timezone(&Utc));
+            }
+            if let Ok(t) = chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+                return Some(t.and_utc());
+            }
+            s.parse().ok()?
+        }
+        n => n.as_i64()?,
+    };
+    let secs = if secs > 100_000_000_000 {
+        secs / 1000
+    } else {
+        secs
+    };
+    DateTime::from_timestamp(secs, 0).filter(|_| secs > 0)
+}
+
 pub fn parse_size(size_str: &str) -> Option<u64> {
     let size_str = size_str
         .trim()
@@ -155,6 +675,168 @@ pub fn parse_size(size_str: &str) -> Option<u64> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_detect_block() {
+        use reqwest::StatusCode;
+        let cloudflare = "<!DOCTYPE html><html><head><title>Just a moment...</title>";
+        assert_eq!(
+            detect_block(StatusCode::FORBIDDEN, cloudflare),
+            Some("Cloudflare challenge page")
+        );
+        assert_eq!(
+            detect_block(
+                StatusCode::OK,
+                "<form><div class=\"g-recaptcha\"></div></form>"
+            ),
+            Some("captcha page")
+        );
+        assert!(
+            detect_block(StatusCode::FORBIDDEN, "<html><body>Forbidden</body></html>").is_some()
+        );
+        assert_eq!(
+            detect_block(StatusCode::FORBIDDEN, "{\"error\": \"bad key\"}"),
+            None
+        );
+        assert_eq!(
+            detect_block(StatusCode::OK, "<html><table></table></html>"),
+            None
+        );
+    }
+
+    struct BlockedProvider(std::sync::Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl XdccSearchProvider for BlockedProvider {
+        fn name(&self) -> &str {
+            "Blocked"
+        }
+        async fn search(&self, _query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(XdccError::Blocked("captcha page".to_string()))
+        }
+        async fn ping(&self) -> Result<(), XdccError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocked_provider_backoff() {
+        let calls = std::sync::Arc::new(AtomicUsize::new(0));
+        let mut agg = SearchAggregator::new();
+        agg.add_provider(Box::new(BlockedProvider(calls.clone())));
+        agg.search("show", None).await.unwrap();
+        agg.search("show", None).await.unwrap();
+
+        let status = &agg.provider_statuses()[0];
+        let error = status.error.as_ref().unwrap();
+        assert_eq!(error.kind, ProviderErrorKind::Blocked);
+        assert!(error.message.contains("captcha page"));
+        assert!(status.blocked_until.is_some_and(|t| t > Utc::now()));
+        // The second search left it alone
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    struct LocalProvider;
+
+    #[async_trait]
+    impl XdccSearchProvider for LocalProvider {
+        fn name(&self) -> &str {
+            "Local"
+        }
+        async fn search(&self, _query: &str) -> Result<Vec<XdccSearchResult>, XdccError> {
+            Ok(Vec::new())
+        }
+        async fn ping(&self) -> Result<(), XdccError> {
+            Ok(())
+        }
+        fn is_remote(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reachable_providers() {
+        let mut agg = SearchAggregator::new();
+        agg.add_provider(Box::new(LocalProvider));
+        // Local data being at hand doesn't make the providers reachable
+        assert!(agg.reachable_providers().await.is_empty());
+        assert_eq!(agg.ping_providers().await.len(), 1);
+
+        agg.add_provider(Box::new(BlockedProvider(Default::default())));
+        assert_eq!(agg.reachable_providers().await, vec!["Blocked".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_find_source_everywhere() {
+        let original = XdccUrl::parse("irc://irc.rizon.net/chan/Bot/5").unwrap();
+        let mut agg = SearchAggregator::new();
+        agg.add_provider(Box::new(LocalProvider));
+        assert!(matches!(
+            agg.find_source_everywhere(&original, "a.mkv").await,
+            Ok(None)
+        ));
+
+        agg.add_provider(Box::new(BlockedProvider(Default::default())));
+        assert!(agg
+            .find_source_everywhere(&original, "a.mkv")
+            .await
+            .is_err());
+        // Still skipped while blocked
+        assert!(agg
+            .find_source_everywhere(&original, "a.mkv")
+            .await
+            .is_err());
+        assert!(matches!(
+            agg.find_source(&original, "a.mkv").await,
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn test_pick_source() {
+        let result = |bot: &str, slot: i32, name: &str, gets: u32| {
+            let url = XdccUrl {
+                network: "irc.rizon.net".to_string(),
+                channel: "#chan".to_string(),
+                bot: bot.to_string(),
+                slot,
+            };
+            XdccSearchResult {
+                filename: name.to_string(),
+                size: None,
+                size_str: String::new(),
+                bot: bot.to_string(),
+                network: url.network.clone(),
+                channel: url.channel.clone(),
+                slot,
+                gets: Some(gets),
+                url,
+                last_seen: None,
+                annotations: Default::default(),
+            }
+        };
+        let original = XdccUrl::parse("irc://irc.rizon.net/chan/OldBot/5").unwrap();
+        let results = vec![
+            result("Other", 1, "Show.S01E01.mkv", 10),
+            result("Popular", 2, "show.s01e01.mkv", 90),
+            result("Popular", 3, "Show.S01E02.mkv", 500),
+        ];
+        assert_eq!(
+            pick_source(&original, "Show.S01E01.mkv", results.clone()).map(|u| u.bot),
+            Some("Popular".to_string())
+        );
+
+        // The original bot renumbered the pack
+        let mut renumbered = results;
+        renumbered.push(result("OldBot", 9, "Show.S01E01.mkv", 1));
+        assert_eq!(
+            pick_source(&original, "Show.S01E01.mkv", renumbered).map(|u| u.slot),
+            Some(9)
+        );
+        assert!(pick_source(&original, "Missing.mkv", Vec::new()).is_none());
+    }
 
     #[test]
     fn test_parse_size() {
@@ -164,4 +846,52 @@ mod tests {
         assert_eq!(parse_size("1.2GB"), Some(1288490188));
         assert_eq!(parse_size(""), None);
     }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let ts = |v: serde_json::Value| {
+            parse_timestamp(&serde_json::from_value(v).unwrap()).map(|t| t.timestamp())
+        };
+        assert_eq!(ts(serde_json::json!(1700000000)), Some(1700000000));
+        assert_eq!(ts(serde_json::json!(1700000000123i64)), Some(1700000000));
+        assert_eq!(ts(serde_json::json!("1700000000")), Some(1700000000));
+        assert_eq!(
+            ts(serde_json::json!("2023-11-14T22:13:20Z")),
+            Some(1700000000)
+        );
+        assert_eq!(
+            ts(serde_json::json!("2023-11-14 22:13:20")),
+            Some(1700000000)
+        );
+        assert_eq!(ts(serde_json::json!("yesterday")), None);
+        assert_eq!(ts(serde_json::json!(0)), None);
+        assert_eq!(parse_timestamp(&LenientNumber::default()), None);
+    }
+
+    #[test]
+    fn test_lenient_items() {
+        #[derive(Deserialize)]
+        struct Item {
+            name: String,
+            #[serde(default)]
+            num: LenientNumber,
+        }
+
+        let value = serde_json::json!({
+            "results": [
+                {"name": "a", "num": 1},
+                {"name": "b", "num": "#2"},
+                {"num": 3},
+                {"name": "c"}
+            ]
+        });
+        let (items, skipped) = lenient_items::<Item>(&value, &["data", "results"]).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].name, "a");
+        assert_eq!(items[1].num.as_i64(), Some(2));
+        assert_eq!(items[2].num.as_i64(), None);
+
+        assert!(lenient_items::<Item>(&serde_json::json!({"error": "x"}), &["results"]).is_err());
+    }
 }
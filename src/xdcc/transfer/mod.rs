@@ -6,17 +6,127 @@
 //! - Bot reliability tracking
 //! - Download history and analytics
 
-use super::{TransferStatus, XdccTransfer, XdccUrl};
+use super::client::http;
+use super::client::info::same_name;
+use super::{
+    ConnectionPool, OfferQueue, RateLimiter, TransferMethod, TransferStatus, XdccTransfer, XdccUrl,
+};
+use crate::config::DownloadTemplate;
+use crate::filename::{saved_path, FilenamePolicy};
 use chrono::Utc;
 use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 pub mod models;
+pub mod notices;
 pub use models::*;
 
+/// How often traffic counted since the last write is added to the database
+const TRAFFIC_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Traffic received since it was last written, by network
+struct PendingTraffic {
+    bytes: HashMap<String, u64>,
+    flushed_at: std::time::Instant,
+}
+
+/// How often the progress of a running transfer is checkpointed
+const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The source with the best record of speed times reliability among `current`
+/// and `alternatives`, if it isn't `current`. Bots without a record count as
+/// average and bots that ignore us are left out.
+fn fastest_source(
+    current: &XdccUrl,
+    alternatives: Vec<XdccUrl>,
+    stats: &HashMap<String, BotStats>,
+) -> Option<XdccUrl> {
+    let known: Vec<f64> = stats
+        .values()
+        .filter(|s| s.successful_downloads > 0 && !s.ignored())
+        .map(|s| s.average_speed * s.reliability_score)
+        .collect();
+    let average = known.iter().sum::<f64>() / known.len().max(1) as f64;
+    let score = |url: &XdccUrl| {
+        stats
+            .get(&format!("{}@{}", url.bot, url.network))
+            .filter(|s| s.successful_downloads > 0)
+            .map_or(average, |s| s.average_speed * s.reliability_score)
+    };
+
+    let current_score = score(current);
+    alternatives
+        .into_iter()
+        .filter(|url| {
+            !url.bot.eq_ignore_ascii_case(&current.bot)
+                || !url.network.eq_ignore_ascii_case(&current.network)
+        })
+        .filter(|url| {
+            !stats
+                .get(&format!("{}@{}", url.bot, url.network))
+                .is_some_and(|s| s.ignored())
+        })
+        .map(|url| (score(&url), url))
+        .filter(|(s, _)| *s > current_score)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, url)| url)
+}
+
+/// Whether the transfer `t` waits for has completed. A dependency that is no
+/// longer listed (cleared or deleted) doesn't hold it back.
+fn dependency_done(transfers: &HashMap<String, EnhancedTransfer>, t: &EnhancedTransfer) -> bool {
+    t.transfer
+        .after
+        .as_ref()
+        .and_then(|after| transfers.get(after))
+        .is_none_or(|d| d.transfer.status == TransferStatus::Completed)
+}
+
+/// The transfer `t` waits for, if it ended without completing, so `t` never
+/// will start
+fn dependency_ended<'a>(
+    transfers: &'a HashMap<String, EnhancedTransfer>,
+    t: &EnhancedTransfer,
+) -> Option<&'a EnhancedTransfer> {
+    let d = transfers.get(t.transfer.after.as_ref()?)?;
+    matches!(
+        d.transfer.status,
+        TransferStatus::Failed | TransferStatus::Cancelled | TransferStatus::Quarantined
+    )
+    .then_some(d)
+}
+
+/// Key of `last_requests` entries
+fn request_key(kind: &str, name: &str) -> String {
+    format!("{}:{}", kind, name.to_lowercase())
+}
+
+fn channel_key(url: &XdccUrl) -> String {
+    format!("{}@{}", url.channel, url.network)
+}
+
+/// Whether making `id` wait for `after` would close a loop of transfers
+/// waiting for each other
+fn creates_cycle(transfers: &HashMap<String, EnhancedTransfer>, id: &str, after: &str) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    let mut next = Some(after);
+    while let Some(current) = next {
+        if current == id {
+            return true;
+        }
+        if !seen.insert(current) {
+            break;
+        }
+        next = transfers
+            .get(current)
+            .and_then(|t| t.transfer.after.as_deref());
+    }
+    false
+}
+
 /// Enhanced Transfer Manager with queue and retry support
 pub struct EnhancedTransferManager {
     /// Active transfers indexed by ID
@@ -37,6 +147,18 @@ pub struct EnhancedTransferManager {
     download_dir: String,
     /// Database connection for history persistence
     database: Option<Arc<crate::db::Database>>,
+    /// Recent failures for aggregated alerts
+    failure_window: Arc<RwLock<crate::alerts::FailureWindow>>,
+    /// DCC offers held for manual approval
+    offers: OfferQueue,
+    /// IRC connections shared between downloads
+    connection_pool: ConnectionPool,
+    /// When the queue last started a request, by `bot:` and `channel:` key
+    last_requests: Arc<RwLock<HashMap<String, std::time::Instant>>>,
+    /// Speed limit shared by all downloads
+    bandwidth: RateLimiter,
+    /// Traffic not yet added to the database
+    traffic: Arc<Mutex<PendingTraffic>>,
 }
 
 impl EnhancedTransferManager {
@@ -51,6 +173,15 @@ impl EnhancedTransferManager {
             max_history: 50,
             download_dir,
             database: None,
+            failure_window: Arc::new(RwLock::new(Default::default())),
+            offers: OfferQueue::default(),
+            connection_pool: ConnectionPool::default(),
+            last_requests: Arc::new(RwLock::new(HashMap::new())),
+            bandwidth: RateLimiter::default(),
+            traffic: Arc::new(Mutex::new(PendingTraffic {
+                bytes: HashMap::new(),
+                flushed_at: std::time::Instant::now(),
+            })),
         }
     }
 
@@ -59,6 +190,21 @@ impl EnhancedTransferManager {
         self.database = Some(database);
     }
 
+    /// DCC offers held for manual approval
+    pub fn offers(&self) -> OfferQueue {
+        self.offers.clone()
+    }
+
+    /// IRC connections shared between downloads
+    pub fn connection_pool(&self) -> ConnectionPool {
+        self.connection_pool.clone()
+    }
+
+    /// Speed limit shared by all downloads
+    pub fn bandwidth(&self) -> RateLimiter {
+        self.bandwidth.clone()
+    }
+
     /// Save transfer to database
     fn save_to_database(&self, enhanced_transfer: &EnhancedTransfer) {
         if let Some(db) = &self.database {
@@ -81,10 +227,59 @@ impl EnhancedTransferManager {
                 error: transfer.error.clone(),
                 created_at: transfer.created_at.to_rfc3339(),
                 completed_at: transfer.updated_at.to_rfc3339(),
+                category: transfer.category.clone(),
+                labels: transfer.labels.clone(),
+                note: transfer.note.clone(),
+                template: transfer.template.clone(),
+                after: transfer.after.clone(),
+                method: transfer.method.map(|m| m.as_str().to_string()),
+                extraction: crate::postprocess::extraction_step(&transfer.postprocess).cloned(),
+                max_speed_kbps: enhanced_transfer.max_speed_kbps,
             };
             if let Err(e) = db.insert_download(&record) {
                 tracing::error!("Failed to save download history to database: {}", e);
             }
+            let events = crate::db::TransferEvents {
+                logs: transfer.logs.iter().cloned().collect(),
+                speed_samples: enhanced_transfer.speed_samples.clone(),
+            };
+            if let Err(e) = db.save_transfer_events(&transfer.id, &events) {
+                tracing::warn!("Failed to save transfer events: {}", e);
+            }
+            self.checkpoint(enhanced_transfer);
+        }
+    }
+
+    /// Checkpoint an unfinished transfer, or drop its checkpoint once it finished
+    fn checkpoint(&self, enhanced_transfer: &EnhancedTransfer) {
+        let Some(db) = &self.database else { return };
+        let transfer = &enhanced_transfer.transfer;
+        let result = match transfer.status {
+            TransferStatus::Completed
+            | TransferStatus::Failed
+            | TransferStatus::Cancelled
+            | TransferStatus::Quarantined => {
+                db.delete_transfer_checkpoint(&transfer.id).map(|_| ())
+            }
+            _ => db.save_transfer_checkpoint(&crate::db::TransferCheckpoint {
+                id: transfer.id.clone(),
+                status: format!("{:?}", transfer.status),
+                downloaded: transfer.downloaded,
+                size: transfer.size,
+                file_name: transfer.filename.clone(),
+                download_dir: enhanced_transfer.download_dir.clone(),
+                retry_count: enhanced_transfer.retry_count,
+                queue_position: if transfer.status == TransferStatus::Pending {
+                    enhanced_transfer.queue_position
+                } else {
+                    None
+                },
+                updated_at: Utc::now().to_rfc3339(),
+                max_speed_kbps: enhanced_transfer.max_speed_kbps,
+            }),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to checkpoint transfer {}: {}", transfer.id, e);
         }
     }
 
@@ -129,6 +324,7 @@ impl EnhancedTransferManager {
             "Completed" => TransferStatus::Completed,
             "Failed" => TransferStatus::Failed,
             "Cancelled" => TransferStatus::Cancelled,
+            "Quarantined" => TransferStatus::Quarantined,
             "Pending" => TransferStatus::Pending,
             _ => TransferStatus::Failed,
         });
@@ -150,6 +346,7 @@ impl EnhancedTransferManager {
             } else {
                 0.0
             },
+            eta_secs: None,
             error: record.error.clone(),
             created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                 .unwrap_or_else(|_| Utc::now().into())
@@ -157,6 +354,18 @@ impl EnhancedTransferManager {
             updated_at: chrono::DateTime::parse_from_rfc3339(&record.completed_at)
                 .unwrap_or_else(|_| Utc::now().into())
                 .into(),
+            category: record.category.clone(),
+            pack_info: None,
+            postprocess: Vec::new(),
+            labels: record.labels.clone(),
+            note: record.note.clone(),
+            template: record.template.clone(),
+            after: record.after.clone(),
+            saved_to: None,
+            network_source: None,
+            server: None,
+            mirrors: Vec::new(),
+            method: record.method.as_deref().and_then(TransferMethod::parse),
             logs: std::collections::VecDeque::new(),
         };
 
@@ -231,6 +440,52 @@ impl EnhancedTransferManager {
         words.join(" ")
     }
 
+    /// Queued or running transfer of the same pack a request would attach
+    /// to: its ID, status and priority
+    pub async fn find_unfinished(
+        &self,
+        url: &XdccUrl,
+    ) -> Option<(String, TransferStatus, TransferPriority)> {
+        let url_str = url.to_string();
+        let transfers = self.transfers.read().await;
+        let existing = transfers.values().find(|t| {
+            t.transfer.url.to_string() == url_str
+                && !matches!(
+                    t.transfer.status,
+                    TransferStatus::Completed
+                        | TransferStatus::Failed
+                        | TransferStatus::Cancelled
+                        | TransferStatus::Quarantined
+                )
+        })?;
+        Some((
+            existing.transfer.id.clone(),
+            existing.transfer.status.clone(),
+            existing.priority,
+        ))
+    }
+
+    /// Attach a request to a queued or running transfer of the same pack,
+    /// returning its ID and status. A higher requested priority is applied to
+    /// the existing transfer.
+    pub async fn coalesce_request(
+        &self,
+        url: &XdccUrl,
+        priority: TransferPriority,
+    ) -> Option<(String, TransferStatus)> {
+        let (id, status, current) = self.find_unfinished(url).await?;
+
+        if priority > current {
+            self.set_priority(&id, priority).await;
+        }
+        self.add_log(
+            &id,
+            "Duplicate request attached to this transfer".to_string(),
+        )
+        .await;
+        Some((id, status))
+    }
+
     pub async fn create_transfer(
         &self,
         url: XdccUrl,
@@ -238,8 +493,17 @@ impl EnhancedTransferManager {
         start_paused: bool,
         filename: Option<String>,
     ) -> Result<(String, CancellationToken), String> {
-        let url_str = url.to_string();
         let clean_filename = filename.as_ref().map(|f| Self::strip_irc_codes(f));
+        self.check_new(&url, clean_filename.as_deref()).await?;
+        Ok(self
+            .insert_transfer(url, priority, start_paused, clean_filename)
+            .await)
+    }
+
+    /// Why a new transfer of `url` (saved as `filename`) would be refused as
+    /// a duplicate of an active, finished or recorded one, if it would be
+    pub async fn check_new(&self, url: &XdccUrl, filename: Option<&str>) -> Result<(), String> {
+        let url_str = url.to_string();
 
         // Prevent duplicates in active transfers
         {
@@ -268,7 +532,7 @@ impl EnhancedTransferManager {
         }
 
         // Prevent smart duplicates (Title Match)
-        if let Some(ref fname) = clean_filename {
+        if let Some(fname) = filename {
             let normalized_new = Self::normalize_title(fname);
             if !normalized_new.is_empty() {
                 // Check database for same release title
@@ -312,20 +576,43 @@ impl EnhancedTransferManager {
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Queue a pack again even though it was downloaded before, e.g. from
+    /// history. Callers check for a queued or running copy first.
+    pub async fn redownload(
+        &self,
+        url: XdccUrl,
+        priority: TransferPriority,
+        filename: Option<String>,
+    ) -> (String, CancellationToken) {
+        self.insert_transfer(url, priority, false, filename).await
+    }
 
+    async fn insert_transfer(
+        &self,
+        url: XdccUrl,
+        priority: TransferPriority,
+        start_paused: bool,
+        filename: Option<String>,
+    ) -> (String, CancellationToken) {
         let id = Uuid::new_v4().to_string();
         let mut transfer = XdccTransfer::new(id.clone(), url);
-        if clean_filename.is_some() {
-            transfer.filename = clean_filename;
+        if filename.is_some() {
+            transfer.filename = filename;
         }
         let mut enhanced = EnhancedTransfer::new(transfer);
         enhanced.priority = priority;
+        if start_paused {
+            enhanced.transfer.status = TransferStatus::Paused;
+        }
 
         let token = CancellationToken::new();
 
         {
             let mut transfers = self.transfers.write().await;
-            transfers.insert(id.clone(), enhanced.clone());
+            transfers.insert(id.clone(), enhanced);
         }
 
         {
@@ -333,38 +620,65 @@ impl EnhancedTransferManager {
             tokens.insert(id.clone(), token.clone());
         }
 
-        if start_paused {
-            let mut transfers = self.transfers.write().await;
-            if let Some(t) = transfers.get_mut(&id) {
-                t.transfer.status = TransferStatus::Paused;
-            }
-        } else {
+        if !start_paused {
             // Add to queue
             self.add_to_queue(id.clone(), priority).await;
         }
 
-        self.save_to_database(&enhanced);
+        // Saved with its place in the queue
+        if let Some(enhanced) = self.transfers.read().await.get(&id) {
+            self.save_to_database(enhanced);
+        }
 
-        Ok((id, token))
+        (id, token)
     }
 
-    /// Add transfer to priority queue
-    async fn add_to_queue(&self, id: String, _priority: TransferPriority) {
-        let mut queue = self.queue.write().await;
+    /// Queue a transfer waiting to be retried again, so the retry waits for a
+    /// free slot like any other transfer
+    pub async fn requeue(&self, id: &str) -> bool {
+        let priority = {
+            let transfers = self.transfers.read().await;
+            match transfers.get(id) {
+                Some(t) if t.transfer.status == TransferStatus::Pending => t.priority,
+                _ => return false,
+            }
+        };
+        if self.queue.read().await.iter().any(|q| q == id) {
+            return false;
+        }
+        self.add_to_queue(id.to_string(), priority).await;
+        true
+    }
 
-        // Find insertion position based on priority
-        let pos = queue
+    /// Index in `queue` a transfer of `priority` goes to: after every queued
+    /// transfer of the same or a higher priority
+    fn priority_slot(
+        queue: &VecDeque<String>,
+        transfers: &HashMap<String, EnhancedTransfer>,
+        priority: TransferPriority,
+    ) -> usize {
+        queue
             .iter()
-            .position(|_queue_id| {
-                // This is simplified - in reality we'd look up the priority
-                false // For now, just append
-            })
-            .unwrap_or(queue.len());
+            .position(|id| transfers.get(id).is_some_and(|t| t.priority < priority))
+            .unwrap_or(queue.len())
+    }
+
+    /// Place in the queue (from 1) a new transfer of `priority` would get
+    pub async fn queue_position_for(&self, priority: TransferPriority) -> usize {
+        let queue = self.queue.read().await;
+        let transfers = self.transfers.read().await;
+        Self::priority_slot(&queue, &transfers, priority) + 1
+    }
+
+    /// Add transfer to priority queue
+    async fn add_to_queue(&self, id: String, priority: TransferPriority) {
+        let mut queue = self.queue.write().await;
+        let mut transfers = self.transfers.write().await;
 
+        let pos = Self::priority_slot(&queue, &transfers, priority);
         queue.insert(pos, id.clone());
 
         // Update queue positions
-        let mut transfers = self.transfers.write().await;
         for (idx, queue_id) in queue.iter().enumerate() {
             if let Some(transfer) = transfers.get_mut(queue_id) {
                 transfer.queue_position = Some(idx + 1);
@@ -383,13 +697,29 @@ impl EnhancedTransferManager {
         }
     }
 
-    /// Pop an item from the queue to start processing, ensuring max 1 active per network
-    pub async fn pop_queue(&self) -> Option<(String, XdccUrl, CancellationToken)> {
+    /// Pop an item from the queue to start processing, ensuring max 1 active
+    /// per network and pacing requests by `etiquette`
+    pub async fn pop_queue(
+        &self,
+        etiquette: &RequestEtiquette,
+    ) -> Option<(String, XdccUrl, CancellationToken)> {
+        self.cancel_orphaned_dependents().await;
         let mut queue = self.queue.write().await;
         let transfers = self.transfers.read().await;
 
-        // Find currently active networks
-        let mut active_networks = std::collections::HashSet::new();
+        let bot_stats = self.bot_stats.read().await;
+        let mut last_requests = self.last_requests.write().await;
+        let recently_requested = |key: &str| {
+            last_requests
+                .get(key)
+                .is_some_and(|at| at.elapsed() < etiquette.request_gap)
+        };
+
+        // Count active transfers per network and per bot, and the channels
+        // joined for them
+        let mut active_networks: HashMap<&str, usize> = HashMap::new();
+        let mut active_bots: HashMap<String, usize> = HashMap::new();
+        let mut active_channels: HashMap<&str, std::collections::HashSet<String>> = HashMap::new();
         for t in transfers.values() {
             if matches!(
                 t.transfer.status,
@@ -398,25 +728,69 @@ impl EnhancedTransferManager {
                     | TransferStatus::Requesting
                     | TransferStatus::Downloading
             ) {
-                active_networks.insert(t.transfer.url.network.clone());
+                let url = &t.transfer.url;
+                *active_networks.entry(url.network.as_str()).or_default() += 1;
+                *active_bots
+                    .entry(format!("{}@{}", url.bot, url.network))
+                    .or_default() += 1;
+                active_channels
+                    .entry(url.network.as_str())
+                    .or_default()
+                    .insert(url.channel.to_lowercase());
             }
         }
 
-        // Find the first item in queue that isn't on an active network
+        // Find the first queued item whose network is idle, or only busy with the
+        // same bot while it still has room for another parallel transfer
         let mut selected_index = None;
         for (i, id) in queue.iter().enumerate() {
             if let Some(t) = transfers.get(id) {
-                if !active_networks.contains(&t.transfer.url.network) {
+                if !dependency_done(&transfers, t) {
+                    continue;
+                }
+                let url = &t.transfer.url;
+                let key = format!("{}@{}", url.bot, url.network);
+                if !etiquette.request_gap.is_zero()
+                    && (recently_requested(&request_key("bot", &key))
+                        || recently_requested(&request_key("channel", &channel_key(url))))
+                {
+                    continue;
+                }
+                if etiquette.max_channels_per_network > 0 {
+                    let joined = active_channels.get(url.network.as_str());
+                    if joined.is_some_and(|c| {
+                        !c.contains(&url.channel.to_lowercase())
+                            && c.len() >= etiquette.max_channels_per_network
+                    }) {
+                        continue;
+                    }
+                }
+                let stats = bot_stats.get(&key);
+                if stats.is_some_and(|s| s.recently_full() || s.ignored()) {
+                    continue;
+                }
+                let on_network = active_networks
+                    .get(url.network.as_str())
+                    .copied()
+                    .unwrap_or(0);
+                let with_bot = active_bots.get(&key).copied().unwrap_or(0);
+                let limit = stats.map_or(1, |s| s.parallel_limit());
+                if on_network == with_bot && with_bot < limit {
                     selected_index = Some(i);
                     break;
                 }
             }
         }
+        drop(bot_stats);
 
         if let Some(idx) = selected_index {
             let id = queue.remove(idx).unwrap();
             if let Some(t) = transfers.get(&id) {
                 let url = t.transfer.url.clone();
+                let now = std::time::Instant::now();
+                let bot_key = format!("{}@{}", url.bot, url.network);
+                last_requests.insert(request_key("bot", &bot_key), now);
+                last_requests.insert(request_key("channel", &channel_key(&url)), now);
                 let tokens = self.cancel_tokens.read().await;
                 if let Some(token) = tokens.get(&id) {
                     let token = token.clone();
@@ -432,6 +806,135 @@ impl EnhancedTransferManager {
         None
     }
 
+    /// Cancel queued transfers whose dependency failed, was cancelled or was
+    /// quarantined; they would otherwise wait in the queue forever
+    async fn cancel_orphaned_dependents(&self) {
+        let orphaned: Vec<(String, String)> = {
+            let queue = self.queue.read().await;
+            let transfers = self.transfers.read().await;
+            queue
+                .iter()
+                .filter_map(|id| {
+                    let d = dependency_ended(&transfers, transfers.get(id)?)?;
+                    let name = d.transfer.filename.as_deref().unwrap_or(&d.transfer.id);
+                    let status = format!("{:?}", d.transfer.status).to_lowercase();
+                    Some((id.clone(), format!("Dependency {} {}", name, status)))
+                })
+                .collect()
+        };
+        for (id, reason) in orphaned {
+            tracing::warn!("Cancelling transfer {}: {}", id, reason);
+            self.add_log(&id, reason.clone()).await;
+            if let Some(t) = self.transfers.write().await.get_mut(&id) {
+                t.transfer.error = Some(reason);
+            }
+            self.cancel_transfer(&id).await;
+        }
+    }
+
+    /// Estimate when each queued transfer will start, walking the queue the
+    /// way `pop_queue` does: at most `concurrency` transfers at once, one at a
+    /// time per network, and not before the transfer it waits for. Durations
+    /// come from live ETAs, or the bot's (or overall) average speed. Transfers
+    /// queued behind something that can't be estimated get no estimate.
+    pub async fn queue_estimates(
+        &self,
+        concurrency: usize,
+    ) -> HashMap<String, chrono::DateTime<Utc>> {
+        let queue = self.queue.read().await;
+        let transfers = self.transfers.read().await;
+        let bot_stats = self.bot_stats.read().await;
+        let fallback_speed = self.analytics.read().await.average_download_speed;
+
+        // Seconds from now until the transfer finishes
+        let remaining = |t: &EnhancedTransfer| -> Option<f64> {
+            if let Some(eta) = t.transfer.eta_secs {
+                return Some(eta as f64);
+            }
+            let url = &t.transfer.url;
+            let speed = bot_stats
+                .get(&format!("{}@{}", url.bot, url.network))
+                .map(|s| s.average_speed)
+                .filter(|s| *s > 0.0)
+                .unwrap_or(fallback_speed);
+            let size = t.transfer.size?;
+            (speed > 0.0).then(|| size.saturating_sub(t.transfer.downloaded) as f64 / speed)
+        };
+
+        // When each slot, network and transfer frees up; None if unknown
+        let mut slots: Vec<Option<f64>> = Vec::new();
+        let mut networks: HashMap<&str, Option<f64>> = HashMap::new();
+        let mut ends: HashMap<&str, Option<f64>> = HashMap::new();
+        for t in transfers.values() {
+            if matches!(
+                t.transfer.status,
+                TransferStatus::Connecting
+                    | TransferStatus::Joining
+                    | TransferStatus::Requesting
+                    | TransferStatus::Downloading
+            ) {
+                let end = remaining(t);
+                slots.push(end);
+                let network = networks
+                    .entry(t.transfer.url.network.as_str())
+                    .or_insert(Some(0.0));
+                *network = network.zip(end).map(|(a, b)| a.max(b));
+                ends.insert(t.transfer.id.as_str(), end);
+            }
+        }
+        // Transfers beyond the limit keep a slot busy until enough finish
+        slots.sort_by(|a, b| match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        let concurrency = concurrency.max(1);
+        let busy = slots.len().saturating_sub(concurrency);
+        slots.drain(..busy);
+        slots.resize(concurrency, Some(0.0));
+
+        let now = Utc::now();
+        let mut estimates = HashMap::new();
+        for id in queue.iter() {
+            let Some(t) = transfers.get(id) else {
+                continue;
+            };
+            let Some((slot, free_at)) = slots
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| s.map(|s| (i, s)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+            else {
+                break;
+            };
+            let network = networks
+                .get(t.transfer.url.network.as_str())
+                .copied()
+                .unwrap_or(Some(0.0));
+            let dependency = match t.transfer.after.as_deref() {
+                Some(after) if !dependency_done(&transfers, t) => {
+                    ends.get(after).copied().unwrap_or(None)
+                }
+                _ => Some(0.0),
+            };
+            let Some(start) = network.zip(dependency).map(|(n, d)| free_at.max(n).max(d)) else {
+                ends.insert(id.as_str(), None);
+                continue;
+            };
+
+            estimates.insert(
+                id.clone(),
+                now + chrono::Duration::milliseconds((start * 1000.0) as i64),
+            );
+            let end = remaining(t).map(|d| start + d);
+            slots[slot] = end;
+            networks.insert(t.transfer.url.network.as_str(), end);
+            ends.insert(id.as_str(), end);
+        }
+        estimates
+    }
+
     /// Get current queue size (Pending transfers)
     pub async fn queue_size(&self) -> usize {
         let transfers = self.transfers.read().await;
@@ -459,16 +962,44 @@ impl EnhancedTransferManager {
         false
     }
 
+    /// Set a transfer's own speed limit in KiB/s, or clear it with `None` so
+    /// its template's applies again. Returns the updated transfer; the caller
+    /// applies the resulting limit to its limiter.
+    pub async fn set_speed_limit(
+        &self,
+        id: &str,
+        max_speed_kbps: Option<u64>,
+    ) -> Option<EnhancedTransfer> {
+        let updated = {
+            let mut transfers = self.transfers.write().await;
+            let transfer = transfers.get_mut(id)?;
+            transfer.max_speed_kbps = max_speed_kbps;
+            transfer.clone()
+        };
+        self.save_to_database(&updated);
+        let message = match max_speed_kbps {
+            Some(0) => "Speed limit removed".to_string(),
+            Some(kbps) => format!("Speed limited to {} KiB/s", kbps),
+            None => "Speed limit reset to the template's".to_string(),
+        };
+        self.add_log(id, message).await;
+        Some(updated)
+    }
+
     /// Requeue a transfer with new priority
-    async fn requeue_transfer(&self, id: String, _priority: TransferPriority) {
-        let mut queue = self.queue.write().await;
+    async fn requeue_transfer(&self, id: String, priority: TransferPriority) {
+        {
+            let mut queue = self.queue.write().await;
+            let transfers = self.transfers.read().await;
 
-        // Remove from current position
-        queue.retain(|queue_id| queue_id != &id);
+            // Remove from current position
+            queue.retain(|queue_id| queue_id != &id);
 
-        // Re-insert based on priority
-        let pos = queue.iter().position(|_| false).unwrap_or(queue.len());
-        queue.insert(pos, id);
+            // Re-insert based on priority
+            let pos = Self::priority_slot(&queue, &transfers, priority);
+            queue.insert(pos, id);
+        }
+        self.update_queue_positions().await;
     }
 
     /// Retry a failed transfer
@@ -491,6 +1022,7 @@ impl EnhancedTransferManager {
             // to continue from where the partial file left off
             transfer.transfer.speed = 0.0;
             transfer.transfer.updated_at = Utc::now();
+            self.save_to_database(transfer);
 
             let priority = transfer.priority;
             let id = id.to_string();
@@ -512,6 +1044,44 @@ impl EnhancedTransferManager {
         false
     }
 
+    /// Pause a queued or running transfer. Its download stops, keeping the
+    /// partial file, and it leaves the queue until resumed; the download it
+    /// resumes with gets a fresh cancellation token.
+    pub async fn pause_transfer(&self, id: &str) -> bool {
+        let enhanced = {
+            let mut transfers = self.transfers.write().await;
+            let Some(t) = transfers.get_mut(id) else {
+                return false;
+            };
+            if !matches!(
+                t.transfer.status,
+                TransferStatus::Pending
+                    | TransferStatus::Connecting
+                    | TransferStatus::Joining
+                    | TransferStatus::Requesting
+                    | TransferStatus::Downloading
+            ) {
+                return false;
+            }
+            t.transfer.status = TransferStatus::Paused;
+            t.transfer.speed = 0.0;
+            t.transfer.updated_at = Utc::now();
+            t.clone()
+        };
+        {
+            let mut tokens = self.cancel_tokens.write().await;
+            if let Some(token) = tokens.insert(id.to_string(), CancellationToken::new()) {
+                token.cancel();
+            }
+        }
+        self.queue.write().await.retain(|q| q != id);
+        self.update_queue_positions().await;
+        self.save_to_database(&enhanced);
+        self.add_log(id, "Paused".to_string()).await;
+        tracing::info!("Paused transfer {}", id);
+        true
+    }
+
     /// Resume a paused transfer
     pub async fn resume_transfer(&self, id: &str) -> bool {
         let mut transfers = self.transfers.write().await;
@@ -519,6 +1089,7 @@ impl EnhancedTransferManager {
             if transfer.transfer.status == TransferStatus::Paused {
                 transfer.transfer.status = TransferStatus::Pending;
                 transfer.transfer.updated_at = Utc::now();
+                self.save_to_database(transfer);
                 let priority = transfer.priority;
                 let id = id.to_string();
                 drop(transfers);
@@ -530,6 +1101,161 @@ impl EnhancedTransferManager {
         false
     }
 
+    /// Queued transfers with a known file name that have been pending for at
+    /// least `threshold`. Each is handed out once per `threshold`.
+    pub async fn take_stale_pending(
+        &self,
+        threshold: chrono::Duration,
+    ) -> Vec<(String, XdccUrl, String)> {
+        let queue = self.queue.read().await;
+        let mut transfers = self.transfers.write().await;
+        let now = Utc::now();
+        let mut stale = Vec::new();
+        for id in queue.iter() {
+            let Some(t) = transfers.get_mut(id) else {
+                continue;
+            };
+            if t.transfer.status != TransferStatus::Pending
+                || now - t.transfer.updated_at < threshold
+                || t.researched_at.is_some_and(|at| now - at < threshold)
+            {
+                continue;
+            }
+            if let Some(filename) = t.transfer.filename.clone() {
+                t.researched_at = Some(now);
+                stale.push((id.clone(), t.transfer.url.clone(), filename));
+            }
+        }
+        stale
+    }
+
+    /// Point a pending transfer at another pack of the same file, logging
+    /// the substitution. Returns false if it has left the queue meanwhile.
+    pub async fn retarget(&self, id: &str, url: XdccUrl, reason: &str) -> bool {
+        let old = {
+            let queue = self.queue.read().await;
+            let mut transfers = self.transfers.write().await;
+            match transfers.get_mut(id) {
+                Some(t)
+                    if t.transfer.status == TransferStatus::Pending
+                        && queue.iter().any(|q| q == id) =>
+                {
+                    t.transfer.updated_at = Utc::now();
+                    std::mem::replace(&mut t.transfer.url, url.clone())
+                }
+                _ => return false,
+            }
+        };
+        self.add_log(id, format!("{}, switched from {} to {}", reason, old, url))
+            .await;
+        if let Some(t) = self.get_transfer(id).await {
+            self.save_to_database(&t);
+        }
+        true
+    }
+
+    /// Find the pack of each queued transfer's file whose bot has the best
+    /// speed and reliability record, among packs of the same name in stored
+    /// search results. Each transfer is considered once. The returned future
+    /// holds no lock on the manager, so the database lookups don't hold up
+    /// anything else; pass its result to [`Self::move_to_faster_sources`].
+    pub async fn find_faster_sources(
+        &self,
+    ) -> impl std::future::Future<Output = Vec<(String, XdccUrl)>> + Send + 'static {
+        let candidates: Vec<(String, XdccUrl, String)> = {
+            let queue = self.queue.read().await;
+            let mut transfers = self.transfers.write().await;
+            queue
+                .iter()
+                .filter_map(|id| {
+                    let t = transfers.get_mut(id)?;
+                    if t.source_checked || t.transfer.status != TransferStatus::Pending {
+                        return None;
+                    }
+                    t.source_checked = true;
+                    let filename = t.transfer.filename.clone()?;
+                    Some((id.clone(), t.transfer.url.clone(), filename))
+                })
+                .collect()
+        };
+
+        let database = self.database.clone();
+        let bot_stats = self.bot_stats.clone();
+        async move {
+            let Some(db) = database.filter(|_| !candidates.is_empty()) else {
+                return Vec::new();
+            };
+            let found = tokio::task::spawn_blocking(move || {
+                candidates
+                    .into_iter()
+                    .filter_map(|(id, url, filename)| {
+                        match db.find_alternative_sources(&filename) {
+                            Ok(alternatives) => Some((id, url, alternatives)),
+                            Err(e) => {
+                                tracing::warn!("Failed to look up sources of {}: {}", filename, e);
+                                None
+                            }
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+            let stats = bot_stats.read().await;
+            found
+                .into_iter()
+                .filter_map(|(id, url, alternatives)| {
+                    Some((id, fastest_source(&url, alternatives, &stats)?))
+                })
+                .collect()
+        }
+    }
+
+    /// Move transfers to the faster sources found for them
+    pub async fn move_to_faster_sources(&self, moves: Vec<(String, XdccUrl)>) {
+        for (id, best) in moves {
+            let reason = format!("{} has a better speed and reliability record", best.bot);
+            if self.retarget(&id, best, &reason).await {
+                tracing::info!("Transfer {}: {}", id, reason);
+            }
+        }
+    }
+
+    /// Pause a transfer whose file could not be written, so it can be resumed
+    /// once there is space again instead of failing
+    pub async fn pause_for_disk_error(&self, id: &str, error: String) {
+        let enhanced = {
+            let mut transfers = self.transfers.write().await;
+            let Some(t) = transfers.get_mut(id) else {
+                return;
+            };
+            t.transfer.status = TransferStatus::Paused;
+            t.transfer.error = Some(error);
+            t.transfer.speed = 0.0;
+            t.transfer.updated_at = Utc::now();
+            t.clone()
+        };
+        self.queue.write().await.retain(|q| q != id);
+        self.update_queue_positions().await;
+        self.save_to_database(&enhanced);
+    }
+
+    /// Save a paused transfer to `dir` from now on and queue it again
+    pub async fn relocate(&self, id: &str, dir: String) -> Result<(), String> {
+        {
+            let mut transfers = self.transfers.write().await;
+            let t = transfers.get_mut(id).ok_or("Transfer not found")?;
+            if t.transfer.status != TransferStatus::Paused {
+                return Err("Only paused transfers can be relocated".to_string());
+            }
+            t.download_dir = Some(dir.clone());
+            t.transfer.error = None;
+        }
+        self.add_log(id, format!("Relocated to {}", dir)).await;
+        self.resume_transfer(id).await;
+        Ok(())
+    }
+
     /// Record bot statistics
     pub async fn record_bot_success(&self, bot: &str, network: &str, bytes: u64, speed: f64) {
         let key = format!("{}@{}", bot, network);
@@ -553,31 +1279,165 @@ impl EnhancedTransferManager {
         bot_stat.record_failure();
     }
 
-    /// Get all bot statistics sorted by reliability
-    pub async fn get_all_bot_stats(&self) -> Vec<BotStats> {
-        let stats = self.bot_stats.read().await;
-        let mut all_stats: Vec<_> = stats.values().cloned().collect();
-        all_stats.sort_by(|a, b| {
-            b.reliability_score
-                .partial_cmp(&a.reliability_score)
-                .unwrap()
-        });
-        all_stats
+    /// Record slot information, or that it ignores us, from a notice sent by
+    /// the bot serving a transfer
+    pub async fn record_bot_notice(&self, id: &str, nick: &str, message: &str) {
+        let url = match self.transfers.read().await.get(id) {
+            Some(t) if t.transfer.url.bot.eq_ignore_ascii_case(nick) => t.transfer.url.clone(),
+            _ => return,
+        };
+        let notice = notices::parse_slot_notice(message);
+        let ignored = notices::is_ignore_notice(message);
+        self.record_mirrors(id, notices::parse_mirrors(&Self::strip_irc_codes(message)))
+            .await;
+
+        let key = format!("{}@{}", url.bot, url.network);
+        let ignored_until = {
+            let mut stats = self.bot_stats.write().await;
+            let stat = stats
+                .entry(key)
+                .or_insert_with(|| BotStats::new(url.bot.clone(), url.network.clone()));
+            // Any answer means the bot hears us
+            stat.silent_requests = 0;
+            if let Some(notice) = &notice {
+                stat.record_slot_notice(notice);
+            }
+            if ignored {
+                stat.mark_ignored(message.to_string());
+            }
+            stat.ignored_until
+        };
+        if let Some(notice) = notice {
+            self.add_log(id, format!("Bot queue notice: {}", notice))
+                .await;
+        }
+        if let (true, Some(until)) = (ignored, ignored_until) {
+            self.log_bot_ignored(id, &url, until).await;
+        }
     }
 
-    /// Get transfer by ID
-    pub async fn get_transfer(&self, id: &str) -> Option<EnhancedTransfer> {
-        let transfers = self.transfers.read().await;
-        transfers.get(id).cloned()
+    /// Remember mirrors the bot of a transfer advertised. Links naming
+    /// another file than the pack's are left out; the rest are checked
+    /// again when downloaded.
+    async fn record_mirrors(&self, id: &str, mirrors: Vec<String>) {
+        let mut added = Vec::new();
+        {
+            let mut transfers = self.transfers.write().await;
+            let Some(transfer) = transfers.get_mut(id) else {
+                return;
+            };
+            let Some(pack) = transfer.transfer.filename.clone() else {
+                return;
+            };
+            for mirror in mirrors {
+                let other_file = http::file_name_from_url(&mirror)
+                    .is_some_and(|name| name.contains('.') && !same_name(&name, &pack));
+                if !other_file && !transfer.transfer.mirrors.contains(&mirror) {
+                    transfer.transfer.mirrors.push(mirror.clone());
+                    added.push(mirror);
+                }
+            }
+        }
+        for mirror in added {
+            self.add_log(id, format!("Bot advertised a mirror: {}", mirror))
+                .await;
+        }
     }
 
-    /// Add a log message to a specific transfer
-    pub async fn add_log(&self, id: &str, msg: String) {
+    /// Record how the file of a transfer is fetched
+    pub async fn set_method(&self, id: &str, method: TransferMethod) {
         let mut transfers = self.transfers.write().await;
         if let Some(transfer) = transfers.get_mut(id) {
-            let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-            transfer
-                .transfer
+            transfer.transfer.method = Some(method);
+        }
+    }
+
+    /// Count a pack request the bot of a transfer never answered. A bot that
+    /// keeps not answering while others on its network still serve us is
+    /// marked as ignoring us.
+    pub async fn record_bot_silence(&self, id: &str) {
+        let Some(url) = self
+            .transfers
+            .read()
+            .await
+            .get(id)
+            .map(|t| t.transfer.url.clone())
+        else {
+            return;
+        };
+
+        let key = format!("{}@{}", url.bot, url.network);
+        let until = {
+            let mut stats = self.bot_stats.write().await;
+            let day_ago = Utc::now() - chrono::Duration::days(1);
+            let others_served = stats.values().any(|s| {
+                s.network.eq_ignore_ascii_case(&url.network)
+                    && !s.bot_name.eq_ignore_ascii_case(&url.bot)
+                    && s.last_success_at.is_some_and(|at| at > day_ago)
+            });
+            let stat = stats
+                .entry(key)
+                .or_insert_with(|| BotStats::new(url.bot.clone(), url.network.clone()));
+            if !stat.record_silence() || !others_served {
+                return;
+            }
+            stat.mark_ignored(format!(
+                "{} requests in a row went unanswered",
+                stat.silent_requests
+            ));
+            stat.ignored_until
+        };
+        if let Some(until) = until {
+            self.log_bot_ignored(id, &url, until).await;
+        }
+    }
+
+    async fn log_bot_ignored(&self, id: &str, url: &XdccUrl, until: chrono::DateTime<Utc>) {
+        tracing::warn!(
+            "{} on {} seems to ignore us, holding requests to it until {}",
+            url.bot,
+            url.network,
+            until
+        );
+        self.add_log(
+            id,
+            format!(
+                "{} seems to ignore us, holding requests to it until {}",
+                url.bot,
+                until.format("%Y-%m-%d %H:%M UTC")
+            ),
+        )
+        .await;
+    }
+
+    /// Get all bot statistics sorted by reliability
+    pub async fn get_all_bot_stats(&self) -> Vec<BotStats> {
+        let stats = self.bot_stats.read().await;
+        let mut all_stats: Vec<_> = stats.values().cloned().collect();
+        // Ties in a fixed order, so pages of the list don't overlap
+        all_stats.sort_by(|a, b| {
+            b.reliability_score
+                .partial_cmp(&a.reliability_score)
+                .unwrap()
+                .then_with(|| a.network.cmp(&b.network))
+                .then_with(|| a.bot_name.cmp(&b.bot_name))
+        });
+        all_stats
+    }
+
+    /// Get transfer by ID
+    pub async fn get_transfer(&self, id: &str) -> Option<EnhancedTransfer> {
+        let transfers = self.transfers.read().await;
+        transfers.get(id).cloned()
+    }
+
+    /// Add a log message to a specific transfer
+    pub async fn add_log(&self, id: &str, msg: String) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            transfer
+                .transfer
                 .logs
                 .push_back(format!("[{}] {}", timestamp, msg));
             if transfer.transfer.logs.len() > 100 {
@@ -588,17 +1448,38 @@ impl EnhancedTransferManager {
 
     /// Get logs for a specific transfer
     pub async fn get_logs(&self, id: &str) -> Vec<String> {
+        self.get_events(id).await.logs
+    }
+
+    /// Logs and speed samples of a transfer, from memory while it is known
+    /// there, or from the database archive
+    pub async fn get_events(&self, id: &str) -> crate::db::TransferEvents {
         let transfers = self.transfers.read().await;
         if let Some(transfer) = transfers.get(id) {
-            return transfer.transfer.logs.iter().cloned().collect();
+            return crate::db::TransferEvents {
+                logs: transfer.transfer.logs.iter().cloned().collect(),
+                speed_samples: transfer.speed_samples.clone(),
+            };
+        }
+        drop(transfers);
+
+        if let Some(db) = &self.database {
+            match db.get_transfer_events(id) {
+                Ok(Some(events)) => return events,
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to read events of {}: {}", id, e),
+            }
         }
 
         let history = self.history.read().await;
         if let Some(transfer) = history.iter().find(|t| t.id == id) {
-            return transfer.logs.iter().cloned().collect();
+            return crate::db::TransferEvents {
+                logs: transfer.logs.iter().cloned().collect(),
+                speed_samples: Vec::new(),
+            };
         }
 
-        Vec::new()
+        Default::default()
     }
 
     /// List all active transfers
@@ -609,6 +1490,46 @@ impl EnhancedTransferManager {
         list
     }
 
+    /// Build a lightweight summary of all transfers
+    pub async fn get_summary(&self) -> TransferSummary {
+        let mut summary = TransferSummary::default();
+
+        {
+            let transfers = self.transfers.read().await;
+            for t in transfers.values() {
+                match t.transfer.status {
+                    TransferStatus::Connecting
+                    | TransferStatus::Joining
+                    | TransferStatus::Requesting
+                    | TransferStatus::Downloading => {
+                        summary.active += 1;
+                        summary.total_speed += t.transfer.speed;
+                    }
+                    TransferStatus::Pending => summary.queued += 1,
+                    TransferStatus::Paused => summary.paused += 1,
+                    TransferStatus::Completed => summary.completed += 1,
+                    TransferStatus::Failed => summary.failed += 1,
+                    TransferStatus::Cancelled => summary.cancelled += 1,
+                    TransferStatus::Quarantined => summary.quarantined += 1,
+                }
+            }
+        }
+
+        let history = self.history.read().await;
+        summary.last_completed = history
+            .iter()
+            .filter(|t| t.status == TransferStatus::Completed)
+            .max_by_key(|t| t.updated_at)
+            .map(|t| CompletedItem {
+                id: t.id.clone(),
+                file_name: t.filename.clone(),
+                size: t.size,
+                completed_at: t.updated_at,
+            });
+
+        summary
+    }
+
     /// Get download history
     pub async fn get_history(&self, limit: usize) -> Vec<XdccTransfer> {
         let history = self.history.read().await;
@@ -664,6 +1585,12 @@ impl EnhancedTransferManager {
             transfer.transfer.updated_at = Utc::now();
 
             self.save_to_database(transfer);
+            if matches!(
+                status,
+                TransferStatus::Completed | TransferStatus::Failed | TransferStatus::Cancelled
+            ) {
+                tokio::spawn(self.flush_traffic());
+            }
 
             // Move to history if completed/failed
             if matches!(status, TransferStatus::Completed | TransferStatus::Failed) {
@@ -686,18 +1613,281 @@ impl EnhancedTransferManager {
         }
     }
 
-    /// Update transfer progress
-    pub async fn update_progress(&self, id: &str, downloaded: u64, speed: f64) {
-        let mut transfers = self.transfers.write().await;
-        if let Some(transfer) = transfers.get_mut(id) {
+    /// Update transfer progress. `received` counts the bytes of the current
+    /// connection, which go towards the network's traffic; the resumed part
+    /// of `downloaded` was counted when it arrived.
+    pub async fn update_progress(&self, id: &str, downloaded: u64, received: u64, speed: f64) {
+        let (network, new_bytes, checkpoint) = {
+            let mut transfers = self.transfers.write().await;
+            let Some(transfer) = transfers.get_mut(id) else {
+                return;
+            };
+            let new_bytes = received.saturating_sub(transfer.received);
+            transfer.received = received;
             transfer.transfer.downloaded = downloaded;
             transfer.transfer.speed = speed;
+            transfer.update_eta(speed);
+            transfer.record_speed_sample(speed);
             if let Some(size) = transfer.transfer.size {
                 if size > 0 {
                     transfer.transfer.progress = (downloaded as f64 / size as f64) * 100.0;
                 }
             }
             transfer.transfer.updated_at = Utc::now();
+            let checkpoint = transfer
+                .checkpointed_at
+                .is_none_or(|at| at.elapsed() >= CHECKPOINT_INTERVAL)
+                .then(|| {
+                    transfer.checkpointed_at = Some(std::time::Instant::now());
+                    transfer.clone()
+                });
+            (transfer.transfer.url.network.clone(), new_bytes, checkpoint)
+        };
+        if let Some(transfer) = checkpoint {
+            self.checkpoint(&transfer);
+        }
+        if new_bytes > 0 {
+            let due = {
+                let mut traffic = self.traffic.lock().unwrap_or_else(|e| e.into_inner());
+                *traffic.bytes.entry(network).or_default() += new_bytes;
+                traffic.flushed_at.elapsed() >= TRAFFIC_FLUSH_INTERVAL
+            };
+            if due {
+                tokio::spawn(self.flush_traffic());
+            }
+        }
+    }
+
+    /// Add the traffic counted since the last write to the database, off the
+    /// async runtime. Runs once a minute while downloading, when a transfer
+    /// ends and on shutdown.
+    pub fn flush_traffic(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let bytes = {
+            let mut traffic = self.traffic.lock().unwrap_or_else(|e| e.into_inner());
+            traffic.flushed_at = std::time::Instant::now();
+            std::mem::take(&mut traffic.bytes)
+        };
+        let database = self.database.clone();
+        async move {
+            let Some(db) = database.filter(|_| !bytes.is_empty()) else {
+                return;
+            };
+            let written = tokio::task::spawn_blocking(move || {
+                for (network, bytes) in bytes {
+                    if let Err(e) = db.add_traffic(&network, bytes) {
+                        tracing::warn!("Failed to record traffic: {}", e);
+                    }
+                }
+            })
+            .await;
+            if let Err(e) = written {
+                tracing::warn!("Failed to record traffic: {}", e);
+            }
+        }
+    }
+
+    /// Check whether a transfer's ETA just dropped to `threshold_secs`,
+    /// returning the transfer when it did
+    pub async fn check_eta_notice(&self, id: &str, threshold_secs: u64) -> Option<XdccTransfer> {
+        let mut transfers = self.transfers.write().await;
+        let transfer = transfers.get_mut(id)?;
+        transfer
+            .eta_reached(threshold_secs)
+            .then(|| transfer.transfer.clone())
+    }
+
+    /// Set the download category of a transfer
+    pub async fn set_category(&self, id: &str, category: Option<String>) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.category = category;
+            transfer.transfer.updated_at = Utc::now();
+        }
+    }
+
+    /// Replace the labels and note of a transfer or history record,
+    /// returning whether it exists
+    pub async fn set_annotations(
+        &self,
+        id: &str,
+        labels: Vec<String>,
+        note: Option<String>,
+    ) -> bool {
+        let mut seen = std::collections::HashSet::new();
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty() && seen.insert(l.to_lowercase()))
+            .collect();
+        let note = note.filter(|n| !n.trim().is_empty());
+        let mut found = false;
+        {
+            let mut transfers = self.transfers.write().await;
+            if let Some(t) = transfers.get_mut(id) {
+                t.transfer.labels = labels.clone();
+                t.transfer.note = note.clone();
+                found = true;
+            }
+        }
+        {
+            let mut history = self.history.write().await;
+            if let Some(t) = history.iter_mut().find(|t| t.id == id) {
+                t.labels = labels.clone();
+                t.note = note.clone();
+                found = true;
+            }
+        }
+        if let Some(db) = &self.database {
+            found |= db
+                .set_annotations(id, &labels, note.as_deref())
+                .unwrap_or(false);
+        }
+        found
+    }
+
+    /// Make a transfer wait for another to complete before it starts, or clear
+    /// its dependency with `None`
+    pub async fn set_after(&self, id: &str, after: Option<String>) -> Result<(), String> {
+        let mut transfers = self.transfers.write().await;
+        if let Some(ref after) = after {
+            if !transfers.contains_key(after) {
+                return Err(format!("Unknown transfer: {}", after));
+            }
+            if creates_cycle(&transfers, id, after) {
+                return Err(format!(
+                    "Waiting for {} would create a dependency cycle",
+                    after
+                ));
+            }
+        }
+        let transfer = transfers
+            .get_mut(id)
+            .ok_or_else(|| "Transfer not found".to_string())?;
+        transfer.transfer.after = after;
+        transfer.transfer.updated_at = Utc::now();
+        self.save_to_database(transfer);
+        Ok(())
+    }
+
+    /// Groups of transfers linked by dependencies, each listed from the
+    /// transfer that starts first
+    pub async fn list_jobs(&self) -> Vec<TransferJob> {
+        let transfers = self.transfers.read().await;
+        let mut dependents: HashMap<&str, Vec<&EnhancedTransfer>> = HashMap::new();
+        for t in transfers.values() {
+            if let Some(after) = t.transfer.after.as_deref() {
+                if transfers.contains_key(after) {
+                    dependents.entry(after).or_default().push(t);
+                }
+            }
+        }
+
+        let mut jobs = Vec::new();
+        for root in transfers.values() {
+            let is_root = root
+                .transfer
+                .after
+                .as_deref()
+                .is_none_or(|a| !transfers.contains_key(a));
+            if !is_root || !dependents.contains_key(root.transfer.id.as_str()) {
+                continue;
+            }
+            let mut members = vec![root.clone()];
+            let mut i = 0;
+            while i < members.len() {
+                if let Some(next) = dependents.get(members[i].transfer.id.as_str()) {
+                    let mut next = next.clone();
+                    next.sort_by_key(|t| t.transfer.created_at);
+                    members.extend(next.into_iter().cloned());
+                }
+                i += 1;
+            }
+            jobs.push(TransferJob::new(members));
+        }
+        jobs.sort_by_key(|j| j.created_at);
+        jobs
+    }
+
+    /// Job a transfer belongs to, if it is linked to others
+    pub async fn job_of(&self, id: &str) -> Option<TransferJob> {
+        self.list_jobs()
+            .await
+            .into_iter()
+            .find(|j| j.transfers.iter().any(|t| t.transfer.id == id))
+    }
+
+    /// Tag a transfer with a download template's name, labels and retry limit
+    pub async fn apply_template(&self, id: &str, name: &str, template: &DownloadTemplate) {
+        let (labels, note) = {
+            let mut transfers = self.transfers.write().await;
+            let Some(t) = transfers.get_mut(id) else {
+                return;
+            };
+            t.transfer.template = Some(name.to_string());
+            if let Some(max_retries) = template.max_retries {
+                t.max_retries = max_retries;
+            }
+            let mut labels = t.transfer.labels.clone();
+            labels.extend(template.labels.iter().cloned());
+            (labels, t.transfer.note.clone())
+        };
+        self.set_annotations(id, labels, note).await;
+    }
+
+    pub async fn set_pack_info(&self, id: &str, info: super::PackInfo) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.pack_info = Some(info);
+            transfer.transfer.updated_at = Utc::now();
+        }
+    }
+
+    /// Record the postprocess steps run on a completed transfer
+    pub async fn set_postprocess(&self, id: &str, steps: Vec<crate::postprocess::StepResult>) {
+        if let (Some(db), Some(extraction)) =
+            (&self.database, crate::postprocess::extraction_step(&steps))
+        {
+            if let Err(e) = db.set_download_extraction(id, extraction) {
+                tracing::warn!("Failed to record extraction of {}: {}", id, e);
+            }
+        }
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.postprocess = steps;
+            transfer.transfer.updated_at = Utc::now();
+        }
+    }
+
+    /// Record where the completed file was left
+    pub async fn set_saved_to(&self, id: &str, path: String) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.saved_to = Some(path);
+        }
+    }
+
+    /// Record how the network of a transfer was resolved and the server it
+    /// resolved to
+    pub async fn set_resolution(&self, id: &str, source: String, server: String) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.network_source = Some(source);
+            transfer.transfer.server = Some(server);
+        }
+    }
+
+    /// Record the size a search result claimed, before the bot reports the real one
+    pub async fn set_expected_size(&self, id: &str, size: u64) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.size.get_or_insert(size);
+        }
+    }
+
+    pub async fn set_max_retries(&self, id: &str, max_retries: u32) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.max_retries = max_retries;
         }
     }
 
@@ -705,12 +1895,53 @@ impl EnhancedTransferManager {
     pub async fn set_file_info(&self, id: &str, filename: String, size: u64) {
         let mut transfers = self.transfers.write().await;
         if let Some(transfer) = transfers.get_mut(id) {
+            // A new connection starts counting its bytes from zero
+            transfer.received = 0;
             transfer.transfer.filename = Some(filename);
             transfer.transfer.size = Some(size);
             transfer.transfer.updated_at = Utc::now();
         }
     }
 
+    /// Note the file of a running transfer in the resume index, so it can be
+    /// resumed even after the transfer is gone
+    pub async fn record_partial_file(&self, id: &str, policy: FilenamePolicy) {
+        let Some(db) = &self.database else { return };
+        let transfers = self.transfers.read().await;
+        let Some(t) = transfers.get(id) else { return };
+        let (Some(name), Some(size)) = (&t.transfer.filename, t.transfer.size) else {
+            return;
+        };
+        let dir = t.download_dir.as_deref().unwrap_or(&self.download_dir);
+        let path = std::path::Path::new(dir).join(policy.sanitize(name));
+        if let Err(e) = db.save_partial_file(
+            &path.to_string_lossy(),
+            &t.transfer.url.to_string(),
+            name,
+            size,
+            t.transfer.downloaded,
+            Some(id),
+        ) {
+            tracing::warn!("Failed to record partial file of {}: {}", id, e);
+        }
+    }
+
+    /// Drop a finished transfer from the resume index
+    pub async fn forget_partial_file(&self, id: &str) {
+        if let Some(db) = &self.database {
+            let _ = db.delete_partial_files_of(id);
+        }
+    }
+
+    /// Record the name the file is saved under when it differs from the offered one
+    pub async fn set_filename(&self, id: &str, filename: String) {
+        let mut transfers = self.transfers.write().await;
+        if let Some(transfer) = transfers.get_mut(id) {
+            transfer.transfer.filename = Some(filename);
+            transfer.transfer.updated_at = Utc::now();
+        }
+    }
+
     /// Mark transfer as failed with auto-retry
     /// Returns Some((url, token)) if retry should happen, so caller can spawn new download task
     pub async fn set_failed(
@@ -840,6 +2071,76 @@ impl EnhancedTransferManager {
         None
     }
 
+    /// Count a failed attempt towards the hourly failure alert
+    pub async fn record_failure_for_alert(
+        &self,
+        error: &str,
+        threshold: usize,
+    ) -> Option<crate::alerts::FailureAlert> {
+        self.failure_window.write().await.record(error, threshold)
+    }
+
+    /// Mark a completed transfer whose file failed screening as quarantined
+    /// and move it to history
+    pub async fn set_quarantined(&self, id: &str, reason: String) {
+        let transfer_copy = {
+            let mut transfers = self.transfers.write().await;
+            let Some(transfer) = transfers.get_mut(id) else {
+                return;
+            };
+            transfer.transfer.status = TransferStatus::Quarantined;
+            transfer.transfer.error = Some(reason);
+            transfer.transfer.updated_at = Utc::now();
+            transfer.transfer.progress = 100.0;
+            self.save_to_database(transfer);
+            transfer.transfer.clone()
+        };
+
+        self.record_bot_failure(&transfer_copy.url.bot, &transfer_copy.url.network)
+            .await;
+        self.add_to_history(&transfer_copy).await;
+        self.update_analytics(&transfer_copy, false).await;
+        self.cleanup_transfer_state(id).await;
+    }
+
+    /// Set the outcome of a quarantined download after it was released or
+    /// purged, in active transfers, history and the database
+    pub async fn resolve_quarantine(
+        &self,
+        id: &str,
+        status: TransferStatus,
+        error: Option<String>,
+    ) {
+        {
+            let mut transfers = self.transfers.write().await;
+            if let Some(transfer) = transfers.get_mut(id) {
+                transfer.transfer.status = status.clone();
+                transfer.transfer.error = error.clone();
+                transfer.transfer.updated_at = Utc::now();
+            }
+        }
+        {
+            let mut history = self.history.write().await;
+            for transfer in history.iter_mut().filter(|t| t.id == id) {
+                transfer.status = status.clone();
+                transfer.error = error.clone();
+            }
+        }
+        if let Some(db) = &self.database {
+            match db.get_download(id) {
+                Ok(Some(mut record)) => {
+                    record.status = format!("{:?}", status);
+                    record.error = error;
+                    if let Err(e) = db.insert_download(&record) {
+                        tracing::error!("Failed to update quarantined download {}: {}", id, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => tracing::error!("Failed to load quarantined download {}: {}", id, e),
+            }
+        }
+    }
+
     /// Mark transfer as completed and move to history
     pub async fn set_completed(&self, id: &str) {
         let (bot, network, bytes, speed, transfer_copy) = {
@@ -921,7 +2222,10 @@ impl EnhancedTransferManager {
             if let Some(transfer) = transfers.get(id) {
                 matches!(
                     transfer.transfer.status,
-                    TransferStatus::Completed | TransferStatus::Failed | TransferStatus::Cancelled
+                    TransferStatus::Completed
+                        | TransferStatus::Failed
+                        | TransferStatus::Cancelled
+                        | TransferStatus::Quarantined
                 )
             } else {
                 false
@@ -977,13 +2281,21 @@ impl EnhancedTransferManager {
             let mut queue = self.queue.write().await;
             queue.retain(|queue_id| queue_id != id);
 
+            if let Some(db) = &self.database {
+                let _ = db.delete_transfer_checkpoint(id);
+            }
             tracing::info!("Removed transfer {}", id);
         }
 
         removed
     }
 
-    /// Delete history item
+    /// Directory files of trashed history items are kept in
+    pub fn trash_dir(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.download_dir).join(".trash")
+    }
+
+    /// Move a history item to the trash, and its file too if requested
     pub async fn delete_history_item(&self, id: &str, delete_file: bool) -> bool {
         tracing::info!(
             "Attempting to delete history item: {}, delete_file: {}",
@@ -1016,19 +2328,25 @@ impl EnhancedTransferManager {
             return false;
         }
 
-        // Delete the file if requested and we have a filename
+        // Move the file to the trash if requested and we have a filename
+        let mut trash_path = None;
         if delete_file {
             if let Some(filename) = filename_to_delete {
-                let safe_filename =
-                    filename.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
-                let path = std::path::Path::new(&self.download_dir).join(&safe_filename);
+                let path = saved_path(std::path::Path::new(&self.download_dir), &filename);
+                let saved_name = path.file_name().unwrap_or_default().to_string_lossy();
+                let target = self.trash_dir().join(format!("{}_{}", id, saved_name));
 
-                tracing::info!("Attempting to delete file at path: {:?}", path);
+                tracing::info!("Moving file {:?} to the trash", path);
 
+                // The trash lives inside the download directory, so this is a rename
                 if path.exists() {
-                    match tokio::fs::remove_file(&path).await {
-                        Ok(_) => tracing::info!("Successfully deleted file: {:?}", path),
-                        Err(e) => tracing::error!("Failed to delete file {:?}: {}", path, e),
+                    let moved = match tokio::fs::create_dir_all(self.trash_dir()).await {
+                        Ok(_) => tokio::fs::rename(&path, &target).await,
+                        Err(e) => Err(e),
+                    };
+                    match moved {
+                        Ok(_) => trash_path = Some(target.to_string_lossy().to_string()),
+                        Err(e) => tracing::error!("Failed to trash file {:?}: {}", path, e),
                     }
                 } else {
                     tracing::warn!("File not found for deletion: {:?}", path);
@@ -1038,45 +2356,137 @@ impl EnhancedTransferManager {
             }
         }
 
-        // Always attempt to delete from database
         if let Some(db) = &self.database {
-            let _ = db.delete_download(id);
+            let _ = db.trash_download(id, trash_path.as_deref());
         }
 
         true
     }
-    /// Restore incomplete transfers from the database
+
+    /// Take a history item out of the trash, moving its file back
+    pub async fn restore_history_item(&self, id: &str) -> Result<(), String> {
+        let db = self.database.as_ref().ok_or("No database")?;
+        let trashed = db
+            .get_trashed_download(id)
+            .map_err(|e| format!("Database error: {}", e))?
+            .ok_or("History item not in the trash")?;
+
+        if let (Some(trash_path), Some(filename)) = (&trashed.trash_path, &trashed.record.file_name)
+        {
+            // The trashed file keeps its saved name behind the `<id>_` prefix
+            let prefix = format!("{}_", id);
+            let safe_filename = std::path::Path::new(trash_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .and_then(|n| n.strip_prefix(&prefix).map(str::to_string))
+                .unwrap_or_else(|| FilenamePolicy::Posix.sanitize(filename));
+            let path = std::path::Path::new(&self.download_dir).join(&safe_filename);
+            if path.exists() {
+                return Err(format!(
+                    "{} already exists in the download directory",
+                    safe_filename
+                ));
+            }
+            tokio::fs::rename(trash_path, &path)
+                .await
+                .map_err(|e| format!("Failed to restore file: {}", e))?;
+        }
+
+        db.restore_download(id)
+            .map_err(|e| format!("Database error: {}", e))?;
+        let (transfer, _) = Self::record_to_transfer(&trashed.record, None);
+        self.add_to_history(&transfer).await;
+        tracing::info!("Restored history item {} from the trash", id);
+        Ok(())
+    }
+    /// Restore incomplete transfers from the database, with the progress,
+    /// file and queue place of their last checkpoint. Paused transfers stay
+    /// paused; the others are queued again, those that were running ahead of
+    /// the waiting ones, and resume their partial file once the bot offers it.
     pub async fn restore_incomplete_transfers(&self) -> Vec<(String, XdccUrl, CancellationToken)> {
         let mut restored = Vec::new();
+        let Some(db) = &self.database else {
+            return restored;
+        };
+        let Ok(records) = db.get_incomplete_downloads() else {
+            return restored;
+        };
+        let mut checkpoints = db.list_transfer_checkpoints().unwrap_or_else(|e| {
+            tracing::warn!("Failed to read transfer checkpoints: {}", e);
+            HashMap::new()
+        });
+        let partials = db.list_partial_files().unwrap_or_default();
 
-        if let Some(db) = &self.database {
-            if let Ok(records) = db.get_incomplete_downloads() {
-                for record in records {
-                    let (mut transfer, priority) =
-                        Self::record_to_transfer(&record, Some(TransferStatus::Pending));
-                    // For incomplete restores, clear error and reset timestamps
-                    transfer.error = None;
-                    transfer.updated_at = Utc::now();
+        let mut entries: Vec<_> = records
+            .into_iter()
+            .map(|record| {
+                let checkpoint = checkpoints.remove(&record.id);
+                (record, checkpoint)
+            })
+            .collect();
+        entries.sort_by_key(|(record, checkpoint)| {
+            let position = checkpoint.as_ref().and_then(|c| c.queue_position);
+            (position.is_some(), position, record.created_at.clone())
+        });
 
-                    let mut enhanced = EnhancedTransfer::new(transfer);
-                    enhanced.priority = priority;
+        for (record, checkpoint) in entries {
+            let paused = checkpoint
+                .as_ref()
+                .map_or(record.status.as_str(), |c| c.status.as_str())
+                == "Paused";
+            let status = if paused {
+                TransferStatus::Paused
+            } else {
+                TransferStatus::Pending
+            };
+            let (mut transfer, priority) = Self::record_to_transfer(&record, Some(status));
+            // For incomplete restores, clear error and reset timestamps
+            transfer.error = None;
+            transfer.updated_at = Utc::now();
+
+            let mut enhanced = EnhancedTransfer::new(transfer);
+            enhanced.priority = priority;
+            enhanced.max_speed_kbps = record.max_speed_kbps;
+            if let Some(checkpoint) = checkpoint {
+                let transfer = &mut enhanced.transfer;
+                transfer.filename = checkpoint.file_name.or(transfer.filename.take());
+                transfer.size = checkpoint.size.or(transfer.size);
+                transfer.downloaded = checkpoint.downloaded;
+                enhanced.retry_count = checkpoint.retry_count;
+                enhanced.download_dir = checkpoint.download_dir;
+                enhanced.max_speed_kbps = checkpoint.max_speed_kbps;
+            }
+            // The partial file on disk is what the bot will be asked to resume from
+            let partial = partials
+                .iter()
+                .find(|p| p.transfer_id.as_deref() == Some(record.id.as_str()));
+            if let Some(meta) = partial.and_then(|p| std::fs::metadata(&p.path).ok()) {
+                enhanced.transfer.downloaded = meta.len();
+            }
+            if let Some(size) = enhanced.transfer.size.filter(|s| *s > 0) {
+                enhanced.transfer.progress =
+                    (enhanced.transfer.downloaded as f64 / size as f64) * 100.0;
+            }
 
-                    let token = CancellationToken::new();
-                    let url = enhanced.transfer.url.clone();
+            let token = CancellationToken::new();
+            let url = enhanced.transfer.url.clone();
 
-                    {
-                        let mut transfers = self.transfers.write().await;
-                        transfers.insert(record.id.clone(), enhanced);
-                    }
-                    {
-                        let mut tokens = self.cancel_tokens.write().await;
-                        tokens.insert(record.id.clone(), token.clone());
-                    }
+            {
+                let mut transfers = self.transfers.write().await;
+                transfers.insert(record.id.clone(), enhanced);
+            }
+            {
+                let mut tokens = self.cancel_tokens.write().await;
+                tokens.insert(record.id.clone(), token.clone());
+            }
 
-                    self.add_to_queue(record.id.clone(), priority).await;
-                    restored.push((record.id, url, token));
-                }
+            if !paused {
+                self.add_to_queue(record.id.clone(), priority).await;
             }
+            restored.push((record.id, url, token));
+        }
+        if !restored.is_empty() {
+            tracing::info!("Restored {} unfinished transfers", restored.len());
         }
 
         restored
@@ -1119,3 +2529,639 @@ impl Default for EnhancedTransferManager {
         Self::new("./downloads".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retarget_stale_pending() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = |bot: &str| XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: bot.to_string(),
+            slot: 7,
+        };
+        let (id, _) = tm
+            .create_transfer(
+                url("Gone"),
+                TransferPriority::Normal,
+                false,
+                Some("Show.01.mkv".to_string()),
+            )
+            .await
+            .unwrap();
+        tm.create_transfer(url("Other"), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+
+        assert!(tm
+            .take_stale_pending(chrono::Duration::minutes(30))
+            .await
+            .is_empty());
+        let queued_at = tm.get_transfer(&id).await.unwrap().transfer.updated_at;
+        let stale = tm.take_stale_pending(chrono::Duration::zero()).await;
+        assert_eq!(
+            stale,
+            vec![(id.clone(), url("Gone"), "Show.01.mkv".to_string())]
+        );
+        let t = tm.get_transfer(&id).await.unwrap();
+        assert_eq!(t.transfer.updated_at, queued_at);
+        // Handed out once per threshold
+        assert!(tm
+            .take_stale_pending(chrono::Duration::minutes(30))
+            .await
+            .is_empty());
+
+        assert!(
+            tm.retarget(&id, url("New"), "Gone no longer offers this file")
+                .await
+        );
+        let t = tm.get_transfer(&id).await.unwrap();
+        assert_eq!(t.transfer.url, url("New"));
+        assert!(t
+            .transfer
+            .logs
+            .back()
+            .unwrap()
+            .contains("Gone no longer offers"));
+
+        tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        assert!(!tm.retarget(&id, url("Gone"), "test").await);
+    }
+
+    #[tokio::test]
+    async fn test_restore_checkpoints() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().to_string_lossy().to_string();
+        let db = Arc::new(crate::db::Database::new(":memory:").unwrap());
+        let mut tm = EnhancedTransferManager::new(dir.clone());
+        tm.set_database(db.clone());
+        let url = |slot| XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot,
+        };
+        let mut ids = Vec::new();
+        for (slot, paused) in [(1, false), (2, false), (3, true), (4, false)] {
+            let (id, _) = tm
+                .create_transfer(url(slot), TransferPriority::Normal, paused, None)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        let (running, waiting, paused, cancelled) = (&ids[0], &ids[1], &ids[2], &ids[3]);
+        tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        tm.update_status(running, TransferStatus::Downloading).await;
+        tm.set_file_info(running, "a.bin".to_string(), 100).await;
+        tm.update_progress(running, 40, 40, 1.0).await;
+        tm.set_speed_limit(waiting, Some(250)).await.unwrap();
+        tm.cancel_transfer(cancelled).await;
+        // The file got further than the last checkpoint
+        let path = temp.path().join("a.bin");
+        std::fs::write(&path, [0u8; 60]).unwrap();
+        db.save_partial_file(
+            &path.to_string_lossy(),
+            &url(1).to_string(),
+            "a.bin",
+            100,
+            40,
+            Some(running),
+        )
+        .unwrap();
+
+        let mut restarted = EnhancedTransferManager::new(dir);
+        restarted.set_database(db.clone());
+        let restored: Vec<String> = restarted
+            .restore_incomplete_transfers()
+            .await
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        assert_eq!(
+            &restored,
+            &[running.clone(), paused.clone(), waiting.clone()]
+        );
+
+        let t = restarted.get_transfer(running).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Pending);
+        assert_eq!(t.transfer.filename.as_deref(), Some("a.bin"));
+        assert_eq!((t.transfer.downloaded, t.transfer.progress), (60, 60.0));
+        let t = restarted.get_transfer(paused).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Paused);
+        let t = restarted.get_transfer(waiting).await.unwrap();
+        assert_eq!(t.max_speed_kbps, Some(250));
+
+        let etiquette = RequestEtiquette::default();
+        assert_eq!(&restarted.pop_queue(&etiquette).await.unwrap().0, running);
+        restarted
+            .update_status(running, TransferStatus::Downloading)
+            .await;
+        restarted
+            .update_status(running, TransferStatus::Completed)
+            .await;
+        assert!(!db
+            .list_transfer_checkpoints()
+            .unwrap()
+            .contains_key(running));
+        assert_eq!(restarted.queue_size().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_traffic_counts_received_bytes() {
+        let db = Arc::new(crate::db::Database::new(":memory:").unwrap());
+        let mut tm = EnhancedTransferManager::new("downloads".to_string());
+        tm.set_database(db.clone());
+        let url = XdccUrl::parse("irc://irc.rizon.net/news/Bot/7").unwrap();
+        let (id, _) = tm
+            .create_transfer(url, TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        // Resumed at 1000 bytes, then 300 and 500 bytes in
+        tm.set_file_info(&id, "a.bin".to_string(), 5000).await;
+        tm.update_progress(&id, 1300, 300, 1.0).await;
+        tm.update_progress(&id, 1500, 500, 1.0).await;
+        // Written in batches, not on every update
+        assert_eq!(db.month_traffic().unwrap(), 0);
+        tm.flush_traffic().await;
+        assert_eq!(db.month_traffic().unwrap(), 500);
+
+        // A retry connects again and counts from zero
+        tm.set_file_info(&id, "a.bin".to_string(), 5000).await;
+        tm.update_progress(&id, 1600, 100, 1.0).await;
+        tm.flush_traffic().await;
+        assert_eq!(db.month_traffic().unwrap(), 600);
+    }
+
+    #[tokio::test]
+    async fn test_priority_queue() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = |slot| XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot,
+        };
+        let mut ids = Vec::new();
+        for (slot, priority) in [
+            (1, TransferPriority::Normal),
+            (2, TransferPriority::Low),
+            (3, TransferPriority::Normal),
+        ] {
+            let (id, _) = tm
+                .create_transfer(url(slot), priority, false, None)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        assert_eq!(tm.queue_position_for(TransferPriority::High).await, 1);
+        assert_eq!(tm.queue_position_for(TransferPriority::Normal).await, 3);
+        assert_eq!(tm.queue_position_for(TransferPriority::Low).await, 4);
+
+        let (high, _) = tm
+            .create_transfer(url(4), TransferPriority::High, false, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            tm.get_transfer(&high).await.unwrap().queue_position,
+            Some(1)
+        );
+        assert_eq!(
+            tm.get_transfer(&ids[1]).await.unwrap().queue_position,
+            Some(4)
+        );
+
+        // Raising a transfer's priority moves it ahead of lower ones
+        assert!(tm.set_priority(&ids[1], TransferPriority::Urgent).await);
+        assert_eq!(
+            tm.get_transfer(&ids[1]).await.unwrap().queue_position,
+            Some(1)
+        );
+        assert_eq!(
+            tm.get_transfer(&high).await.unwrap().queue_position,
+            Some(2)
+        );
+        let (first, _, _) = tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        assert_eq!(first, ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot: 7,
+        };
+        let (id, _) = tm
+            .create_transfer(url, TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        let etiquette = RequestEtiquette::default();
+        let (_, _, token) = tm.pop_queue(&etiquette).await.unwrap();
+        tm.update_status(&id, TransferStatus::Downloading).await;
+        tm.update_progress(&id, 500, 500, 100.0).await;
+
+        assert!(tm.pause_transfer(&id).await);
+        assert!(token.is_cancelled());
+        let t = tm.get_transfer(&id).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Paused);
+        assert_eq!((t.transfer.downloaded, t.transfer.speed), (500, 0.0));
+        assert!(!tm.pause_transfer(&id).await);
+        assert!(!tm.requeue(&id).await);
+
+        assert!(tm.resume_transfer(&id).await);
+        let (popped, _, token) = tm.pop_queue(&etiquette).await.unwrap();
+        assert_eq!(popped, id);
+        assert!(!token.is_cancelled());
+        assert!(!tm.pause_transfer("missing").await);
+    }
+
+    #[tokio::test]
+    async fn test_retry_requeued() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot: 7,
+        };
+        let (id, _) = tm
+            .create_transfer(url, TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        assert!(!tm.requeue(&id).await);
+        tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        tm.update_status(&id, TransferStatus::Downloading).await;
+
+        assert!(tm
+            .set_failed(&id, "Connection reset".to_string(), false)
+            .await
+            .is_some());
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_none());
+        assert!(tm.requeue(&id).await);
+        assert!(!tm.requeue(&id).await);
+        assert_eq!(
+            tm.pop_queue(&RequestEtiquette::default()).await.unwrap().0,
+            id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disk_error_relocate() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot: 7,
+        };
+        let (id, _) = tm
+            .create_transfer(url, TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        assert!(tm.relocate(&id, "/mnt/spare".to_string()).await.is_err());
+
+        tm.pause_for_disk_error(&id, "Disk error: No space left on device".to_string())
+            .await;
+        let t = tm.get_transfer(&id).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Paused);
+        assert!(t.transfer.error.is_some());
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_none());
+
+        tm.relocate(&id, "/mnt/spare".to_string()).await.unwrap();
+        let t = tm.get_transfer(&id).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Pending);
+        assert_eq!(t.transfer.error, None);
+        assert_eq!(t.download_dir.as_deref(), Some("/mnt/spare"));
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_request() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot: 7,
+        };
+        assert!(tm
+            .coalesce_request(&url, TransferPriority::Normal)
+            .await
+            .is_none());
+        assert!(tm.check_new(&url, Some("Show.S01E01.mkv")).await.is_ok());
+
+        let (id, _) = tm
+            .create_transfer(
+                url.clone(),
+                TransferPriority::Normal,
+                true,
+                Some("Show.S01E01.mkv".to_string()),
+            )
+            .await
+            .unwrap();
+        // Checking doesn't attach anything
+        let (found, _, priority) = tm.find_unfinished(&url).await.unwrap();
+        assert_eq!(
+            (found.as_str(), priority),
+            (id.as_str(), TransferPriority::Normal)
+        );
+        let other = XdccUrl {
+            slot: 8,
+            ..url.clone()
+        };
+        assert!(tm
+            .check_new(&other, Some("Show.S01E01.mkv"))
+            .await
+            .unwrap_err()
+            .contains("Duplicate release"));
+        let (existing, status) = tm
+            .coalesce_request(&url, TransferPriority::High)
+            .await
+            .unwrap();
+        assert_eq!(existing, id);
+        assert_eq!(status, TransferStatus::Paused);
+        assert_eq!(
+            tm.get_transfer(&id).await.unwrap().priority,
+            TransferPriority::High
+        );
+
+        tm.set_completed(&id).await;
+        assert!(tm
+            .coalesce_request(&url, TransferPriority::Normal)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_gap() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        for slot in [1, 2] {
+            let url = XdccUrl {
+                network: "irc.rizon.net".to_string(),
+                channel: "#news".to_string(),
+                bot: "Bot".to_string(),
+                slot,
+            };
+            tm.create_transfer(url, TransferPriority::Normal, false, None)
+                .await
+                .unwrap();
+        }
+        let etiquette = RequestEtiquette {
+            request_gap: std::time::Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert!(tm.pop_queue(&etiquette).await.is_some());
+        // Same bot and channel right after the first request
+        assert!(tm.pop_queue(&etiquette).await.is_none());
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_bot_ignored() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = |bot: &str, slot| XdccUrl {
+            network: "irc.rizon.net".to_string(),
+            channel: "#news".to_string(),
+            bot: bot.to_string(),
+            slot,
+        };
+        let (silent, _) = tm
+            .create_transfer(url("Silent", 1), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        let ignored = |stats: Vec<BotStats>, bot: &str| {
+            stats.iter().any(|s| s.bot_name == bot && s.ignored())
+        };
+
+        // Nobody serves us on the network, so it may be us
+        for _ in 0..3 {
+            tm.record_bot_silence(&silent).await;
+        }
+        assert!(!ignored(tm.get_all_bot_stats().await, "Silent"));
+
+        tm.record_bot_success("Other", "irc.rizon.net", 10, 1000.0)
+            .await;
+        tm.record_bot_silence(&silent).await;
+        assert!(ignored(tm.get_all_bot_stats().await, "Silent"));
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_none());
+
+        let (banned, _) = tm
+            .create_transfer(url("Strict", 2), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        tm.record_bot_notice(&banned, "Strict", "** All Slots Full")
+            .await;
+        assert!(!ignored(tm.get_all_bot_stats().await, "Strict"));
+        tm.record_bot_notice(&banned, "Strict", "You have been ignored for 10 minutes")
+            .await;
+        assert!(ignored(tm.get_all_bot_stats().await, "Strict"));
+    }
+
+    #[tokio::test]
+    async fn test_record_mirrors() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let (id, _) = tm
+            .create_transfer(
+                XdccUrl::parse("irc://irc.rizon.net/news/Bot/1").unwrap(),
+                TransferPriority::Normal,
+                false,
+                None,
+            )
+            .await
+            .unwrap();
+        let notice = "** Also at https://dl.example.org/Show.S01E01.mkv and https://dl.example.org/get?id=1, see https://example.org/rules.html";
+        // Unknown pack: nothing to check the links against
+        tm.record_bot_notice(&id, "Bot", notice).await;
+        assert!(tm
+            .get_transfer(&id)
+            .await
+            .unwrap()
+            .transfer
+            .mirrors
+            .is_empty());
+
+        tm.set_file_info(&id, "Show.S01E01.mkv".to_string(), 100)
+            .await;
+        tm.record_bot_notice(&id, "Bot", notice).await;
+        assert_eq!(
+            tm.get_transfer(&id).await.unwrap().transfer.mirrors,
+            vec![
+                "https://dl.example.org/Show.S01E01.mkv".to_string(),
+                "https://dl.example.org/get?id=1".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fastest_source() {
+        let url =
+            |bot: &str| XdccUrl::parse(&format!("irc://irc.rizon.net/news/{}/1", bot)).unwrap();
+        let mut stats = HashMap::new();
+        for (bot, speed) in [("Fast", 9000.0), ("Slow", 1000.0), ("Banned", 50000.0)] {
+            let mut s = BotStats::new(bot.to_string(), "irc.rizon.net".to_string());
+            s.record_success(100, speed);
+            stats.insert(format!("{}@irc.rizon.net", bot), s);
+        }
+        stats
+            .get_mut("Banned@irc.rizon.net")
+            .unwrap()
+            .mark_ignored("test".to_string());
+        let all = || vec![url("Slow"), url("Fast"), url("Banned"), url("New")];
+
+        assert_eq!(
+            fastest_source(&url("Slow"), all(), &stats),
+            Some(url("Fast"))
+        );
+        assert_eq!(fastest_source(&url("Fast"), all(), &stats), None);
+        // The same bot in another case isn't another source
+        assert_eq!(
+            fastest_source(&url("Slow"), vec![url("SLOW")], &stats),
+            None
+        );
+        // A bot without a record counts as average, which beats a slow one
+        assert_eq!(
+            fastest_source(&url("Slow"), vec![url("New")], &stats),
+            Some(url("New"))
+        );
+        assert_eq!(
+            fastest_source(&url("New"), all(), &stats),
+            Some(url("Fast"))
+        );
+    }
+
+    #[test]
+    fn test_startup_warmup() {
+        let secs = std::time::Duration::from_secs;
+        let etiquette = RequestEtiquette {
+            startup_warmup: secs(90),
+            ..Default::default()
+        };
+        assert_eq!(etiquette.concurrency(4, secs(0)), 1);
+        assert_eq!(etiquette.concurrency(4, secs(29)), 1);
+        assert_eq!(etiquette.concurrency(4, secs(30)), 2);
+        assert_eq!(etiquette.concurrency(4, secs(89)), 3);
+        assert_eq!(etiquette.concurrency(4, secs(90)), 4);
+        assert_eq!(RequestEtiquette::default().concurrency(4, secs(0)), 4);
+    }
+
+    #[tokio::test]
+    async fn test_queue_estimates() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let mut ids = Vec::new();
+        for (network, slot) in [("irc.one.net", 1), ("irc.one.net", 2), ("irc.two.net", 3)] {
+            let url = XdccUrl {
+                network: network.to_string(),
+                channel: "#news".to_string(),
+                bot: "Bot".to_string(),
+                slot,
+            };
+            let (id, _) = tm
+                .create_transfer(url, TransferPriority::Normal, false, None)
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+        tm.record_bot_success("Bot", "irc.one.net", 0, 1000.0).await;
+        {
+            let mut transfers = tm.transfers.write().await;
+            for id in &ids {
+                transfers.get_mut(id).unwrap().transfer.size = Some(100_000);
+            }
+        }
+        let (first, _, _) = tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        tm.update_status(&first, TransferStatus::Downloading).await;
+
+        let estimates = tm.queue_estimates(3).await;
+        let in_secs = |id: &String| (estimates[id] - Utc::now()).num_seconds();
+        // Waits for the first one on its network; the other network is free
+        assert!((95..=100).contains(&in_secs(&ids[1])));
+        assert!(in_secs(&ids[2]) <= 0);
+        assert!(!estimates.contains_key(&first));
+
+        // A single slot: irc.two.net waits for both irc.one.net transfers
+        let estimates = tm.queue_estimates(1).await;
+        let in_secs = |id: &String| (estimates[id] - Utc::now()).num_seconds();
+        assert!((195..=200).contains(&in_secs(&ids[2])));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_dependencies() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = |network: &str, slot| XdccUrl {
+            network: network.to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot,
+        };
+        let (part2, _) = tm
+            .create_transfer(url("irc.one.net", 2), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        let (part1, _) = tm
+            .create_transfer(url("irc.two.net", 1), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+
+        tm.set_after(&part2, Some(part1.clone())).await.unwrap();
+        assert!(tm.set_after(&part1, Some(part2.clone())).await.is_err());
+        assert!(tm.set_after(&part1, Some(part1.clone())).await.is_err());
+        assert!(tm.set_after(&part1, Some("missing".into())).await.is_err());
+
+        let jobs = tm.list_jobs().await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, part1);
+        assert_eq!(jobs[0].total, 2);
+        assert_eq!(jobs[0].status, "waiting");
+
+        // part2 is first in the queue but waits for part1
+        assert_eq!(
+            tm.pop_queue(&RequestEtiquette::default())
+                .await
+                .map(|(id, _, _)| id),
+            Some(part1.clone())
+        );
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_none());
+        tm.set_completed(&part1).await;
+        assert_eq!(
+            tm.pop_queue(&RequestEtiquette::default())
+                .await
+                .map(|(id, _, _)| id),
+            Some(part2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_dependency() {
+        let tm = EnhancedTransferManager::new("downloads".to_string());
+        let url = |network: &str, slot| XdccUrl {
+            network: network.to_string(),
+            channel: "#news".to_string(),
+            bot: "Bot".to_string(),
+            slot,
+        };
+        let (part1, _) = tm
+            .create_transfer(url("irc.one.net", 1), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        let (part2, _) = tm
+            .create_transfer(url("irc.two.net", 2), TransferPriority::Normal, false, None)
+            .await
+            .unwrap();
+        tm.set_after(&part2, Some(part1.clone())).await.unwrap();
+
+        tm.pop_queue(&RequestEtiquette::default()).await.unwrap();
+        tm.set_failed(&part1, "Bot went away".to_string(), true)
+            .await;
+        // part2 is taken out of the queue instead of waiting forever
+        assert!(tm.pop_queue(&RequestEtiquette::default()).await.is_none());
+        let t = tm.get_transfer(&part2).await.unwrap();
+        assert_eq!(t.transfer.status, TransferStatus::Cancelled);
+        assert!(t.transfer.error.unwrap().contains(&part1));
+        assert_eq!(tm.queue_size().await, 0);
+    }
+}
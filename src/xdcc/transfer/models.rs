@@ -1,5 +1,6 @@
-use crate::xdcc::XdccTransfer;
-use chrono::{DateTime, Utc};
+use super::notices::SlotNotice;
+use crate::xdcc::{RateLimiter, TransferStatus, XdccTransfer};
+use chrono::{DateTime, Timelike, Utc};
 use serde::{Deserialize, Serialize};
 
 /// Transfer priority levels
@@ -13,6 +14,89 @@ pub enum TransferPriority {
     Urgent = 3,
 }
 
+/// How long to hold back new requests after a bot reports all slots full
+const SLOTS_FULL_BACKOFF_SECS: i64 = 300;
+/// How long to hold back requests to a bot that ignores or banned us
+const IGNORED_COOLOFF_SECS: i64 = 6 * 3600;
+/// Unanswered requests in a row after which a bot counts as ignoring us
+const SILENT_REQUESTS_LIMIT: u32 = 3;
+/// Completed downloads an hour needs before it can be the best one
+const MIN_HOUR_SUCCESSES: u32 = 2;
+/// Seconds between kept speed samples
+const SPEED_SAMPLE_SECS: u64 = 10;
+/// Speed samples kept per transfer (four hours at one per 10 seconds)
+const MAX_SPEED_SAMPLES: usize = 1440;
+
+/// Downloads in one hour of the day (UTC)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct HourStats {
+    pub attempts: u32,
+    pub successes: u32,
+    /// Mean speed of the successful downloads in bytes/s
+    pub average_speed: f64,
+}
+
+impl HourStats {
+    /// Count a download, with its speed if it succeeded
+    fn record(&mut self, speed: Option<f64>) {
+        self.attempts += 1;
+        if let Some(speed) = speed {
+            self.successes += 1;
+            self.average_speed += (speed - self.average_speed) / self.successes as f64;
+        }
+    }
+
+    fn merge(&mut self, other: &HourStats) {
+        let successes = self.successes + other.successes;
+        if successes > 0 {
+            self.average_speed = (self.average_speed * self.successes as f64
+                + other.average_speed * other.successes as f64)
+                / successes as f64;
+        }
+        self.attempts += other.attempts;
+        self.successes = successes;
+    }
+
+    /// Expected speed, discounted by the failure rate
+    fn score(&self) -> f64 {
+        self.average_speed * self.successes as f64 / self.attempts.max(1) as f64
+    }
+}
+
+/// Hour of the day (UTC) with the best speed and success rate, among hours
+/// with enough completed downloads to tell
+pub fn best_hour(hours: &[HourStats]) -> Option<u32> {
+    hours
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.successes >= MIN_HOUR_SUCCESSES)
+        .max_by(|(_, a), (_, b)| a.score().total_cmp(&b.score()))
+        .map(|(hour, _)| hour as u32)
+}
+
+/// Downloads by hour of the day of every bot on a network
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetworkHours {
+    pub hours: Vec<HourStats>,
+    pub best_hour: Option<u32>,
+}
+
+/// Combine the hourly statistics of the bots on each network
+pub fn network_hours(stats: &[BotStats]) -> std::collections::BTreeMap<String, NetworkHours> {
+    let mut networks: std::collections::BTreeMap<String, NetworkHours> = Default::default();
+    for bot in stats.iter().filter(|s| !s.hours.is_empty()) {
+        let network = networks.entry(bot.network.clone()).or_default();
+        network.hours.resize(24, HourStats::default());
+        for (total, hour) in network.hours.iter_mut().zip(&bot.hours) {
+            total.merge(hour);
+        }
+    }
+    for network in networks.values_mut() {
+        network.best_hour = best_hour(&network.hours);
+    }
+    networks
+}
+
 /// Bot reliability statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BotStats {
@@ -25,6 +109,32 @@ pub struct BotStats {
     pub average_speed: f64,
     pub last_seen: DateTime<Utc>,
     pub reliability_score: f64, // 0.0 to 1.0
+    /// Simultaneous transfers per user the bot allows, learned from its notices
+    #[serde(default)]
+    pub max_transfers: Option<u32>,
+    /// When the bot last reported all slots full
+    #[serde(default)]
+    pub slots_full_at: Option<DateTime<Utc>>,
+    /// Last reported position in the bot's queue
+    #[serde(default)]
+    pub queue_position: Option<u32>,
+    /// When a download from the bot last completed
+    #[serde(default)]
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Pack requests in a row the bot did not answer
+    #[serde(default)]
+    pub silent_requests: u32,
+    /// The bot seems to ignore or have banned us; no requests go to it until then
+    #[serde(default)]
+    pub ignored_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ignored_reason: Option<String>,
+    /// Downloads by the hour of the day (UTC) they ended in
+    #[serde(default)]
+    pub hours: Vec<HourStats>,
+    /// Hour of the day (UTC) the bot has been fastest and most reliable in
+    #[serde(default)]
+    pub best_hour: Option<u32>,
 }
 
 impl BotStats {
@@ -39,10 +149,67 @@ impl BotStats {
             average_speed: 0.0,
             last_seen: Utc::now(),
             reliability_score: 0.5, // Start with neutral score
+            max_transfers: None,
+            slots_full_at: None,
+            queue_position: None,
+            last_success_at: None,
+            silent_requests: 0,
+            ignored_until: None,
+            ignored_reason: None,
+            hours: Vec::new(),
+            best_hour: None,
+        }
+    }
+
+    /// Apply slot information from a bot notice
+    pub fn record_slot_notice(&mut self, notice: &SlotNotice) {
+        if let Some(limit) = notice.per_user_limit {
+            self.max_transfers = Some(limit.max(1));
         }
+        if notice.slots_full {
+            self.slots_full_at = Some(Utc::now());
+        }
+        if notice.queue_position.is_some() {
+            self.queue_position = notice.queue_position;
+        }
+        self.last_seen = Utc::now();
+    }
+
+    /// How many transfers to run against this bot at once (1 until it tells us otherwise)
+    pub fn parallel_limit(&self) -> usize {
+        self.max_transfers.unwrap_or(1) as usize
+    }
+
+    /// Whether the bot recently reported all slots full
+    pub fn recently_full(&self) -> bool {
+        self.slots_full_at
+            .is_some_and(|at| Utc::now() - at < chrono::Duration::seconds(SLOTS_FULL_BACKOFF_SECS))
+    }
+
+    /// Whether the bot is in its cool-off for ignoring us
+    pub fn ignored(&self) -> bool {
+        self.ignored_until.is_some_and(|until| Utc::now() < until)
+    }
+
+    /// Hold back requests to the bot for the cool-off period
+    pub fn mark_ignored(&mut self, reason: String) {
+        self.ignored_until = Some(Utc::now() + chrono::Duration::seconds(IGNORED_COOLOFF_SECS));
+        self.ignored_reason = Some(reason);
+        self.silent_requests = 0;
+    }
+
+    /// Count a pack request the bot never answered. Returns true once enough
+    /// went unanswered in a row to suspect it ignores us.
+    pub fn record_silence(&mut self) -> bool {
+        self.silent_requests += 1;
+        self.silent_requests >= SILENT_REQUESTS_LIMIT
     }
 
     pub fn record_success(&mut self, bytes: u64, speed: f64) {
+        self.last_success_at = Some(Utc::now());
+        self.silent_requests = 0;
+        self.ignored_until = None;
+        self.ignored_reason = None;
         self.total_downloads += 1;
         self.successful_downloads += 1;
         self.total_bytes += bytes;
@@ -56,6 +223,7 @@ impl BotStats {
 
         self.last_seen = Utc::now();
         self.update_reliability_score();
+        self.record_hour(Utc::now().hour(), Some(speed));
     }
 
     pub fn record_failure(&mut self) {
@@ -63,6 +231,13 @@ impl BotStats {
         self.failed_downloads += 1;
         self.last_seen = Utc::now();
         self.update_reliability_score();
+        self.record_hour(Utc::now().hour(), None);
+    }
+
+    fn record_hour(&mut self, hour: u32, speed: Option<f64>) {
+        self.hours.resize(24, HourStats::default());
+        self.hours[hour as usize % 24].record(speed);
+        self.best_hour = best_hour(&self.hours);
     }
 
     fn update_reliability_score(&mut self) {
@@ -82,6 +257,93 @@ impl BotStats {
     }
 }
 
+/// Pacing the queue applies to pack requests so big batch grabs don't trip
+/// channel anti-leech bots
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestEtiquette {
+    /// Minimum time between requests to the same bot or channel
+    pub request_gap: std::time::Duration,
+    /// Channels joined for downloads at once per network, 0 for no limit
+    pub max_channels_per_network: usize,
+    /// Time after startup over which concurrency ramps up to the queue limit
+    pub startup_warmup: std::time::Duration,
+}
+
+impl RequestEtiquette {
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        Self {
+            request_gap: std::time::Duration::from_secs(config.request_gap_secs),
+            max_channels_per_network: config.max_channels_per_network,
+            startup_warmup: std::time::Duration::from_secs(config.queue_warmup_secs),
+        }
+    }
+
+    /// Transfers allowed at once `uptime` after startup: 1 at first, growing
+    /// evenly to `limit` by the end of the warmup, so a restored queue doesn't
+    /// open every connection at once
+    pub fn concurrency(&self, limit: usize, uptime: std::time::Duration) -> usize {
+        if limit <= 1 || uptime >= self.startup_warmup {
+            return limit;
+        }
+        let ramp = uptime.as_secs_f64() / self.startup_warmup.as_secs_f64();
+        1 + ((limit - 1) as f64 * ramp) as usize
+    }
+}
+
+/// Transfers linked by dependencies, e.g. the parts of a multi-part archive
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferJob {
+    /// ID of the transfer that starts first
+    pub id: String,
+    /// `completed`, `failed`, `running` or `waiting`
+    pub status: String,
+    pub completed: usize,
+    pub total: usize,
+    pub created_at: DateTime<Utc>,
+    /// In dependency order
+    pub transfers: Vec<EnhancedTransfer>,
+}
+
+impl TransferJob {
+    pub fn new(transfers: Vec<EnhancedTransfer>) -> Self {
+        let statuses: Vec<&TransferStatus> = transfers.iter().map(|t| &t.transfer.status).collect();
+        let completed = statuses
+            .iter()
+            .filter(|s| ***s == TransferStatus::Completed)
+            .count();
+        let status = if completed == statuses.len() {
+            "completed"
+        } else if statuses.iter().any(|s| {
+            matches!(
+                s,
+                TransferStatus::Failed | TransferStatus::Cancelled | TransferStatus::Quarantined
+            )
+        }) {
+            "failed"
+        } else if statuses.iter().any(|s| {
+            matches!(
+                s,
+                TransferStatus::Connecting
+                    | TransferStatus::Joining
+                    | TransferStatus::Requesting
+                    | TransferStatus::Downloading
+            )
+        }) {
+            "running"
+        } else {
+            "waiting"
+        };
+        Self {
+            id: transfers[0].transfer.id.clone(),
+            status: status.to_string(),
+            completed,
+            total: transfers.len(),
+            created_at: transfers[0].transfer.created_at,
+            transfers,
+        }
+    }
+}
+
 /// Enhanced transfer with queue management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnhancedTransfer {
@@ -91,6 +353,47 @@ pub struct EnhancedTransfer {
     pub retry_count: u32,
     pub max_retries: u32,
     pub queue_position: Option<usize>,
+    /// When a pending transfer is expected to start, filled in when listing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_start: Option<DateTime<Utc>>,
+    /// Smoothed download speed the ETA is based on
+    #[serde(skip)]
+    pub eta_speed: f64,
+    /// ETA was above the notification threshold at some point
+    #[serde(skip)]
+    pub eta_armed: bool,
+    /// "Almost done" notification was sent
+    #[serde(skip)]
+    pub eta_notified: bool,
+    /// (seconds since the first sample, bytes per second), archived with the logs
+    #[serde(skip)]
+    pub speed_samples: Vec<(u64, f64)>,
+    /// When the first speed sample was taken
+    #[serde(skip)]
+    pub sampling_since: Option<std::time::Instant>,
+    /// Other sources of the file were considered for the `fastest` source policy
+    #[serde(skip)]
+    pub source_checked: bool,
+    /// When the file was last searched for again because the transfer sat in
+    /// the queue
+    #[serde(skip)]
+    pub researched_at: Option<DateTime<Utc>>,
+    /// Directory the file is saved in, when moved away from the download directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_dir: Option<String>,
+    /// When the progress was last checkpointed to the database
+    #[serde(skip)]
+    pub checkpointed_at: Option<std::time::Instant>,
+    /// Bytes received over the current connection when last reported, so
+    /// only new ones count towards the network's traffic
+    #[serde(skip)]
+    pub received: u64,
+    /// Speed limit of this transfer in KiB/s (0 = none), instead of its template's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_speed_kbps: Option<u64>,
+    /// Holds the running download to its speed limit
+    #[serde(skip)]
+    pub limiter: RateLimiter,
 }
 
 impl EnhancedTransfer {
@@ -101,7 +404,72 @@ impl EnhancedTransfer {
             retry_count: 0,
             max_retries: 3,
             queue_position: None,
+            estimated_start: None,
+            eta_speed: 0.0,
+            eta_armed: false,
+            eta_notified: false,
+            speed_samples: Vec::new(),
+            sampling_since: None,
+            source_checked: false,
+            researched_at: None,
+            download_dir: None,
+            checkpointed_at: None,
+            received: 0,
+            max_speed_kbps: None,
+            limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Keep a speed sample every `SPEED_SAMPLE_SECS`, dropping the oldest
+    /// once `MAX_SPEED_SAMPLES` are kept
+    pub fn record_speed_sample(&mut self, speed: f64) {
+        let started = *self
+            .sampling_since
+            .get_or_insert_with(std::time::Instant::now);
+        let secs = started.elapsed().as_secs();
+        if self
+            .speed_samples
+            .last()
+            .is_some_and(|(at, _)| secs < at + SPEED_SAMPLE_SECS)
+        {
+            return;
+        }
+        if self.speed_samples.len() >= MAX_SPEED_SAMPLES {
+            self.speed_samples.remove(0);
+        }
+        self.speed_samples.push((secs, speed));
+    }
+
+    /// Fold a speed sample into the smoothed speed and refresh the ETA
+    pub fn update_eta(&mut self, speed: f64) {
+        self.eta_speed = if self.eta_speed > 0.0 {
+            self.eta_speed * 0.8 + speed * 0.2
+        } else {
+            speed
+        };
+        self.transfer.eta_secs = match self.transfer.size {
+            Some(size) if self.eta_speed > 0.0 => {
+                Some((size.saturating_sub(self.transfer.downloaded) as f64 / self.eta_speed) as u64)
+            }
+            _ => None,
+        };
+    }
+
+    /// Whether the ETA just dropped to `threshold_secs`, for transfers whose
+    /// ETA was longer than that earlier (each transfer notifies once)
+    pub fn eta_reached(&mut self, threshold_secs: u64) -> bool {
+        let Some(eta) = self.transfer.eta_secs else {
+            return false;
+        };
+        if eta > threshold_secs {
+            self.eta_armed = true;
+            return false;
+        }
+        if self.eta_armed && !self.eta_notified {
+            self.eta_notified = true;
+            return true;
         }
+        false
     }
 
     pub fn can_retry(&self) -> bool {
@@ -109,6 +477,30 @@ impl EnhancedTransfer {
     }
 }
 
+/// Compact transfer overview for dashboards and status bars
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferSummary {
+    pub active: usize,
+    pub queued: usize,
+    pub paused: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub quarantined: usize,
+    /// Combined speed of all active transfers in bytes/sec
+    pub total_speed: f64,
+    pub last_completed: Option<CompletedItem>,
+}
+
+/// Most recently completed transfer, as reported by the summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletedItem {
+    pub id: String,
+    pub file_name: Option<String>,
+    pub size: Option<u64>,
+    pub completed_at: DateTime<Utc>,
+}
+
 /// Download analytics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadAnalytics {
@@ -136,3 +528,33 @@ impl Default for DownloadAnalytics {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_hour() {
+        let mut fast = BotStats::new("Fast".to_string(), "irc.rizon.net".to_string());
+        let mut slow = BotStats::new("Slow".to_string(), "irc.rizon.net".to_string());
+        // Fast at 3 but unreliable at 4, one lucky download at 5
+        fast.record_hour(3, Some(4000.0));
+        fast.record_hour(3, Some(6000.0));
+        fast.record_hour(4, Some(9000.0));
+        fast.record_hour(4, Some(9000.0));
+        fast.record_hour(4, None);
+        fast.record_hour(4, None);
+        fast.record_hour(5, Some(50000.0));
+        assert_eq!(fast.hours[3].average_speed, 5000.0);
+        assert_eq!(fast.best_hour, Some(3));
+
+        slow.record_hour(20, Some(1000.0));
+        assert_eq!(slow.best_hour, None);
+        slow.record_hour(5, Some(1000.0));
+        let networks = network_hours(&[fast, slow]);
+        let rizon = &networks["irc.rizon.net"];
+        assert_eq!(rizon.hours[5].successes, 2);
+        assert_eq!(rizon.hours[5].average_speed, 25500.0);
+        assert_eq!(rizon.best_hour, Some(5));
+    }
+}
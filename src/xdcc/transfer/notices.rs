@@ -0,0 +1,152 @@
+//! Bot Queue Notices
+//!
+//! Parses iroffer-style notices such as "All Slots Full, Added you to the main
+//! queue ... in position 2" or "You can only have 1 transfer at a time" so the
+//! queue processor knows how many parallel requests a bot accepts,
+//! notices telling us the bot ignores or banned us, and HTTP/FTP mirrors a
+//! bot advertises for its packs.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+static PER_USER_LIMIT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)you (?:can only have|already have) (\d+) transfers?").unwrap()
+});
+static QUEUE_POSITION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)in position #?(\d+)").unwrap());
+static IGNORED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)you (?:have been|are being|are now|are) (?:ignored|banned|blacklisted)|you (?:have been|are) (?:added to|on|in) (?:the|my) (?:ignore|ban|black) ?list|hammering protection").unwrap()
+});
+static MIRROR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\b(?:https?|ftp)://[^\s<>"'\x02\x03\x0f\x16\x1d\x1f]+"#).unwrap()
+});
+
+/// Slot information reported by a bot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotNotice {
+    /// The bot has no free slots and queued the request
+    pub slots_full: bool,
+    /// Maximum simultaneous transfers per user
+    pub per_user_limit: Option<u32>,
+    /// Position in the bot's queue
+    pub queue_position: Option<u32>,
+}
+
+impl std::fmt::Display for SlotNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.slots_full {
+            parts.push("all slots full".to_string());
+        }
+        if let Some(limit) = self.per_user_limit {
+            parts.push(format!("{} transfer(s) per user", limit));
+        }
+        if let Some(pos) = self.queue_position {
+            parts.push(format!("queue position {}", pos));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Parse a bot notice, returning `None` when it carries no slot information
+pub fn parse_slot_notice(message: &str) -> Option<SlotNotice> {
+    let lower = message.to_lowercase();
+    let notice = SlotNotice {
+        slots_full: lower.contains("all slots full") || lower.contains("no slots open"),
+        per_user_limit: PER_USER_LIMIT_RE
+            .captures(message)
+            .and_then(|c| c[1].parse().ok()),
+        queue_position: QUEUE_POSITION_RE
+            .captures(message)
+            .and_then(|c| c[1].parse().ok()),
+    };
+    (notice != SlotNotice::default()).then_some(notice)
+}
+
+/// Whether a bot notice says it ignores or banned us
+pub fn is_ignore_notice(message: &str) -> bool {
+    IGNORED_RE.is_match(message)
+}
+
+/// HTTP, HTTPS and FTP URLs in a bot notice, e.g. "Also available at
+/// https://mirror.example/file.mkv"
+pub fn parse_mirrors(message: &str) -> Vec<String> {
+    MIRROR_RE
+        .find_iter(message)
+        .map(|m| {
+            m.as_str()
+                .trim_end_matches(['.', ',', ';', ':', ')', ']', '!', '?'])
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slot_notice() {
+        let n = parse_slot_notice(
+            "** All Slots Full, Added you to the main queue for pack 5 (\"file.mkv\") in position 2. To Remove yourself at a later time type \"/MSG Bot XDCC REMOVE 5\".",
+        )
+        .unwrap();
+        assert!(n.slots_full);
+        assert_eq!(n.queue_position, Some(2));
+        assert_eq!(n.per_user_limit, None);
+
+        let n = parse_slot_notice(
+            "** You can only have 1 transfer at a time, Added you to the main queue for pack 7 in position 1.",
+        )
+        .unwrap();
+        assert!(!n.slots_full);
+        assert_eq!(n.per_user_limit, Some(1));
+
+        let n = parse_slot_notice("** You already have 2 transfers in progress").unwrap();
+        assert_eq!(n.per_user_limit, Some(2));
+
+        assert_eq!(
+            parse_slot_notice("** Sending you pack #5 (\"file.mkv\")"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ignore_notice() {
+        assert!(is_ignore_notice(
+            "*** Hammering Protection Triggered, You Have Been Ignored For 10 Minutes"
+        ));
+        assert!(is_ignore_notice(
+            "** XDCC SEND denied, you are on the ban list"
+        ));
+        assert!(is_ignore_notice("You are banned from this bot"));
+        assert!(!is_ignore_notice("** Sending you pack #5 (\"file.mkv\")"));
+        assert!(is_ignore_notice(
+            "You have been added to the ignore list for 6 hours"
+        ));
+        assert!(!is_ignore_notice(
+            "** All Slots Full, Added you to the main queue"
+        ));
+        // Release names echoed back in ordinary notices
+        assert!(!is_ignore_notice(
+            "** All Slots Full, Added you to the main queue for pack 12 (\"The.Blacklist.S01E01.720p.mkv\") in position 3"
+        ));
+        assert!(!is_ignore_notice(
+            "** Sending you pack #4 (\"Schindlers.List.1993.mkv\"), which is 2GB"
+        ));
+    }
+
+    #[test]
+    fn test_parse_mirrors() {
+        assert_eq!(
+            parse_mirrors(
+                "** Pack also on \x02HTTP\x02: https://dl.example.org/packs/file%20name.mkv, FTP (ftp://ftp.example.org/file.mkv)."
+            ),
+            vec![
+                "https://dl.example.org/packs/file%20name.mkv".to_string(),
+                "ftp://ftp.example.org/file.mkv".to_string(),
+            ]
+        );
+        assert!(parse_mirrors("** Sending you pack #5 (\"file.mkv\")").is_empty());
+    }
+}
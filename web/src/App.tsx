@@ -17,7 +17,12 @@ import { XdccSearchResult, XdccTransfer, BotStats } from './types';
 type TabType = 'search' | 'activities' | 'history' | 'plugins' | 'autodl' | 'client' | 'settings';
 
 function App() {
-    const [activeTab, setActiveTab] = useState<TabType>('search');
+    const [activeTab, setActiveTab] = useState<TabType>(() => {
+        // Allow deep links such as /?tab=activities (used by the /add quick-add redirect)
+        const tab = new URLSearchParams(window.location.search).get('tab') as TabType | null;
+        const tabs: TabType[] = ['search', 'activities', 'history', 'plugins', 'autodl', 'client', 'settings'];
+        return tab && tabs.includes(tab) ? tab : 'search';
+    });
     const [searchResults, setSearchResults] = useState<XdccSearchResult[]>([]);
     const [transfers, setTransfers] = useState<XdccTransfer[]>([]);
     const [stats, setStats] = useState<BotStats[]>([]);
@@ -47,7 +52,7 @@ function App() {
             fetch('/api/download', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ url })
+                body: JSON.stringify({ url, filename: nextItem.file_name, size: nextItem.file_size || undefined })
             }).then(() => {
                 setDownloadQueue(prev => prev.slice(1));
                 showToast("Started queued download", "success");
@@ -117,7 +122,7 @@ function App() {
             await fetch('/api/download', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ url, filename: result.file_name })
+                body: JSON.stringify({ url, filename: result.file_name, size: result.file_size || undefined })
             });
             showToast("Download started", "success");
             fetchUpdates();
@@ -169,6 +174,12 @@ function App() {
         showToast("Starting transfer...", "success");
     };
 
+    const handlePause = async (id: string) => {
+        await fetch(`/api/transfers/${id}/pause`, { method: 'POST' });
+        fetchUpdates();
+        showToast("Transfer paused", "success");
+    };
+
     const handleDelete = async (id: string) => {
         await fetch(`/api/transfers/${id}`, { method: 'DELETE' });
         fetchUpdates();
@@ -257,7 +268,7 @@ function App() {
                             ).length}
                         />
                         <div className="flex-1 min-h-0">
-                            <TransferList transfers={transfers} onCancel={handleCancel} onRetry={handleRetry} onResume={handleResume} onDelete={handleDelete} onClearFinished={handleClearFinished} />
+                            <TransferList transfers={transfers} onCancel={handleCancel} onRetry={handleRetry} onResume={handleResume} onPause={handlePause} onDelete={handleDelete} onClearFinished={handleClearFinished} />
                         </div>
                     </div>
                 )}
@@ -16,6 +16,7 @@ export const SearchBar: React.FC<SearchBarProps> = ({ onSearch, isLoading }) =>
         { id: 'XDCC.rocks', name: 'XDCC.rocks' },
         { id: 'XDCC.eu', name: 'XDCC.eu' },
         { id: 'NIBL', name: 'NIBL' },
+        { id: 'SunXDCC', name: 'SunXDCC' },
     ];
 
     const handleSubmit = (e: React.FormEvent) => {
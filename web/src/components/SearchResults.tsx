@@ -1,6 +1,6 @@
 import React, { useState, useMemo } from 'react';
 import { XdccSearchResult } from '../types';
-import { formatBytes } from '../utils/format';
+import { formatAge, formatBytes } from '../utils/format';
 import { Filter, Grid, List } from 'lucide-react';
 
 interface SearchResultsProps {
@@ -15,6 +15,7 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
     const [maxSize, setMaxSize] = useState<number | ''>('');
     const [selectedServer, setSelectedServer] = useState<string>('');
     const [viewMode, setViewMode] = useState<'grid' | 'list'>('grid');
+    const [newestFirst, setNewestFirst] = useState(false);
 
     // Normalize server/network names for deduplication
     const normalizeServer = (s: string) => {
@@ -38,7 +39,7 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
     }, [results]);
 
     const filteredResults = useMemo(() => {
-        return results.filter(res => {
+        const filtered = results.filter(res => {
             // Text filter
             if (filterQuery && !res.file_name.toLowerCase().includes(filterQuery.toLowerCase())) {
                 return false;
@@ -56,7 +57,13 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
 
             return true;
         });
-    }, [results, filterQuery, selectedServer, minSize, maxSize]);
+        if (newestFirst) {
+            // Undated results go last
+            const seen = (r: XdccSearchResult) => (r.last_seen ? new Date(r.last_seen).getTime() : 0);
+            filtered.sort((a, b) => seen(b) - seen(a));
+        }
+        return filtered;
+    }, [results, filterQuery, selectedServer, minSize, maxSize, newestFirst]);
 
     return (
         <div className="animate-fade-in">
@@ -104,6 +111,15 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
                     />
                 </div>
 
+                <label className="flex items-center gap-2 text-sm text-secondary cursor-pointer">
+                    <input
+                        type="checkbox"
+                        checked={newestFirst}
+                        onChange={(e) => setNewestFirst(e.target.checked)}
+                    />
+                    Newest first
+                </label>
+
                 <div className="flex items-center gap-2 bg-surface border border-white/10 rounded p-1 ml-auto">
                     <button 
                         onClick={() => setViewMode('grid')}
@@ -138,6 +154,7 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
                                     <th className="py-2 px-4 font-medium">Bot</th>
                                     <th className="py-2 px-4 font-medium">Server</th>
                                     <th className="py-2 px-4 font-medium text-right">Downloads</th>
+                                    <th className="py-2 px-4 font-medium text-right">Seen</th>
                                     <th className="py-2 px-4 font-medium text-right">Actions</th>
                                 </tr>
                             </thead>
@@ -151,6 +168,7 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
                                         <td className="py-3 px-4 whitespace-nowrap"><span className="text-xs bg-white/5 px-2 py-1 rounded">{res.bot} #{res.pack_number}</span></td>
                                         <td className="py-3 px-4 whitespace-nowrap text-muted text-xs">{res.server}</td>
                                         <td className="py-3 px-4 text-right">{res.downloads}</td>
+                                        <td className="py-3 px-4 text-right whitespace-nowrap text-muted text-xs" title={res.last_seen}>{res.last_seen ? formatAge(res.last_seen) : '-'}</td>
                                         <td className="py-3 px-4 text-right whitespace-nowrap">
                                             {onQueueDownload && (
                                                 <button 
@@ -172,7 +190,7 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
                                 ))}
                                 {filteredResults.length === 0 && (
                                     <tr>
-                                        <td colSpan={6} className="py-12 text-center text-muted">
+                                        <td colSpan={7} className="py-12 text-center text-muted">
                                             No matching results found.
                                         </td>
                                     </tr>
@@ -191,7 +209,10 @@ export const SearchResults: React.FC<SearchResultsProps> = ({ results, onDownloa
                                 </div>
                                 <div className="flex justify-between text-xs text-secondary mt-2">
                                     <span>{formatBytes(res.file_size)}</span>
-                                    <span>{res.downloads} dl</span>
+                                    <span>
+                                        {res.downloads} dl
+                                        {res.last_seen && <span title={res.last_seen}> · seen {formatAge(res.last_seen)}</span>}
+                                    </span>
                                 </div>
                                 <div className="mt-3 pt-3 border-t border-white/5 flex justify-between items-center">
                                     <span className="text-xs bg-white/5 px-2 py-1 rounded">{res.bot} #{res.pack_number}</span>
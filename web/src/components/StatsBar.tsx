@@ -11,6 +11,7 @@ interface StatsBarProps {
 
 export const StatsBar: React.FC<StatsBarProps> = ({ stats, queueSize, activeDownloads }) => {
     const totalVolume = stats.reduce((acc, curr) => acc + curr.total_bytes, 0);
+    const ignoredBots = stats.filter(s => s.ignored_until && new Date(s.ignored_until) > new Date());
 
     return (
         <div className="grid grid-cols-1 md:grid-cols-3 gap-4 mb-8">
@@ -41,6 +42,14 @@ export const StatsBar: React.FC<StatsBarProps> = ({ stats, queueSize, activeDown
                 <div>
                     <h3 className="text-secondary text-sm font-medium">Queue Size</h3>
                     <p className="text-2xl font-bold text-white">{queueSize}</p>
+                    {ignoredBots.length > 0 && (
+                        <p
+                            className="text-xs text-warning"
+                            title={ignoredBots.map(b => `${b.bot_name}@${b.network}: ${b.ignored_reason ?? ''}`).join('\n')}
+                        >
+                            {ignoredBots.length} bot{ignoredBots.length > 1 ? 's' : ''} ignoring us
+                        </p>
+                    )}
                 </div>
             </div>
         </div>
@@ -1,10 +1,10 @@
 import React from 'react';
 import { XdccTransfer } from '../types';
-import { formatSpeed } from '../utils/format';
-import { X, RefreshCw, Trash2, Play, Terminal } from 'lucide-react';
+import { formatEta, formatSpeed } from '../utils/format';
+import { X, RefreshCw, Trash2, Play, Pause, Terminal } from 'lucide-react';
 import { TransferLogsModal } from './TransferLogsModal';
 
-const FINISHED_STATUSES = ['completed', 'failed', 'cancelled'];
+const FINISHED_STATUSES = ['completed', 'failed', 'cancelled', 'quarantined'];
 const ACTIVE_STATUSES = ['pending', 'connecting', 'joining', 'requesting', 'downloading', 'paused'];
 
 interface TransferListProps {
@@ -12,11 +12,12 @@ interface TransferListProps {
     onCancel: (id: string) => void;
     onRetry: (id: string) => void;
     onResume: (id: string) => void;
+    onPause: (id: string) => void;
     onDelete: (id: string) => void;
     onClearFinished?: () => void;
 }
 
-export const TransferList: React.FC<TransferListProps & { onRefresh?: () => void }> = ({ transfers, onCancel, onRetry, onResume, onDelete, onClearFinished, onRefresh }) => {
+export const TransferList: React.FC<TransferListProps & { onRefresh?: () => void }> = ({ transfers, onCancel, onRetry, onResume, onPause, onDelete, onClearFinished, onRefresh }) => {
     const hasFinished = transfers.some(t => FINISHED_STATUSES.includes(t.status));
     const [selectedTransfer, setSelectedTransfer] = React.useState<XdccTransfer | null>(null);
     const [filter, setFilter] = React.useState<'all' | 'active' | 'pending' | 'paused' | 'failed' | 'completed'>('all');
@@ -111,8 +112,15 @@ export const TransferList: React.FC<TransferListProps & { onRefresh?: () => void
                                     <>
                                         <span>{formatSpeed(transfer.speed)}</span>
                                         <span>{Math.round(transfer.progress)}%</span>
+                                        {transfer.eta_secs != null && <span>ETA {formatEta(transfer.eta_secs)}</span>}
                                     </>
                                 )}
+                                {transfer.status === 'pending' && transfer.queue_position != null && (
+                                    <span title={transfer.estimated_start ? new Date(transfer.estimated_start).toLocaleString() : undefined}>
+                                        #{transfer.queue_position} in queue
+                                        {transfer.estimated_start && `, starts in ${formatEta(Math.max(0, Math.round((Date.parse(transfer.estimated_start) - Date.now()) / 1000)))}`}
+                                    </span>
+                                )}
                                 {ACTIVE_STATUSES.includes(transfer.status) && transfer.status !== 'downloading' && (
                                     <span className="text-primary font-semibold uppercase tracking-wider animate-pulse bg-primary/10 px-2 py-0.5 rounded">
                                         {transfer.status}
@@ -179,13 +187,22 @@ export const TransferList: React.FC<TransferListProps & { onRefresh?: () => void
                                     </button>
                                 </div>
                             ) : (
-                                <button
-                                    onClick={() => onCancel(transfer.id)}
-                                    className="p-2 hover:bg-error/20 rounded-lg text-secondary hover:text-error transition-colors"
-                                    title="Cancel"
-                                >
-                                    <X size={18} />
-                                </button>
+                                <div className="flex items-center gap-1">
+                                    <button
+                                        onClick={() => onPause(transfer.id)}
+                                        className="p-2 hover:bg-white/10 rounded-lg text-secondary hover:text-white transition-colors"
+                                        title="Pause"
+                                    >
+                                        <Pause size={18} />
+                                    </button>
+                                    <button
+                                        onClick={() => onCancel(transfer.id)}
+                                        className="p-2 hover:bg-error/20 rounded-lg text-secondary hover:text-error transition-colors"
+                                        title="Cancel"
+                                    >
+                                        <X size={18} />
+                                    </button>
+                                </div>
                             )}
                         </div>
                     </div>
@@ -210,6 +227,7 @@ function getStatusColor(status: string) {
         case 'completed': return 'bg-info';
         case 'failed': return 'bg-error';
         case 'cancelled': return 'bg-secondary';
+        case 'quarantined': return 'bg-orange-500';
         case 'paused': return 'bg-yellow-500';
         default: return 'bg-warning';
     }
@@ -41,6 +41,20 @@ export const FilterForm: React.FC<FilterFormProps> = ({ form, setForm, onSave, o
                     <label className="block text-xs uppercase tracking-wider text-secondary mb-1">Lua Exclude Pattern</label>
                     <input type="text" value={form.exclude || ''} onChange={e => setForm({...form, exclude: e.target.value})} className="w-full bg-black/40 border border-white/10 rounded px-3 py-2 text-sm focus:border-primary/50 outline-none" placeholder="FRENCH|GERMAN" />
                 </div>
+                <div>
+                    <label className="block text-xs uppercase tracking-wider text-secondary mb-1">Priority</label>
+                    <select value={form.priority || ''} onChange={e => setForm({...form, priority: (e.target.value || undefined) as AutodlFilter['priority']})} className="w-full bg-black/40 border border-white/10 rounded px-3 py-2 text-sm focus:border-primary/50 outline-none">
+                        <option value="">Category default</option>
+                        <option value="low">Low</option>
+                        <option value="normal">Normal</option>
+                        <option value="high">High</option>
+                        <option value="urgent">Urgent</option>
+                    </select>
+                </div>
+                <div>
+                    <label className="block text-xs uppercase tracking-wider text-secondary mb-1">Max Retries</label>
+                    <input type="number" min={0} max={10} value={form.max_retries ?? ''} onChange={e => setForm({...form, max_retries: e.target.value === '' ? undefined : Number(e.target.value)})} className="w-full bg-black/40 border border-white/10 rounded px-3 py-2 text-sm focus:border-primary/50 outline-none" placeholder="Category default" />
+                </div>
                 <div className="md:col-span-2">
                     <label className="block text-xs uppercase tracking-wider text-secondary mb-1">Smart Keywords (comma-separated)</label>
                     <input type="text" value={(form.keywords || []).join(', ')} onChange={e => setForm({...form, keywords: e.target.value.split(',').map(s=>s.trim()).filter(Boolean)})} className="w-full bg-black/40 border border-white/10 rounded px-3 py-2 text-sm focus:border-primary/50 outline-none" placeholder="event, 2026, world, cup" />
@@ -1,6 +1,6 @@
 import React, { useState } from 'react';
 import { DownloadHistoryItem } from '../../hooks/useHistory';
-import { CheckSquare, Square, RefreshCw, X, Trash2, Terminal, ChevronLeft, ChevronRight } from 'lucide-react';
+import { CheckSquare, Square, RefreshCw, X, Trash2, Terminal, ChevronLeft, ChevronRight, Download } from 'lucide-react';
 import { formatBytes } from '../../utils/format';
 import { TransferLogsModal } from '../TransferLogsModal';
 
@@ -53,10 +53,8 @@ export const DownloadHistory: React.FC<DownloadHistoryProps> = ({
 
     const handleBulkDelete = async (deleteFiles: boolean) => {
         if (selectedDownloads.size === 0) return;
-        const msg = deleteFiles
-            ? `Delete ${selectedDownloads.size} items AND their files from disk?`
-            : `Remove ${selectedDownloads.size} items from history?`;
-        if (!window.confirm(msg)) return;
+        // Deleting files is confirmed once the server reports what it would delete
+        if (!deleteFiles && !window.confirm(`Remove ${selectedDownloads.size} items from history?`)) return;
 
         await bulkDeleteDownloads(selectedDownloads, deleteFiles);
         setSelectedDownloads(new Set());
@@ -131,6 +129,14 @@ export const DownloadHistory: React.FC<DownloadHistoryProps> = ({
                                         </button>
                                     </td>
                                     <td className="px-4 py-3 text-sm font-medium truncate max-w-xs cursor-pointer hover:text-primary transition-colors flex items-center gap-2" title={item.file_name || `Pack #${item.slot} from ${item.bot}`} onClick={() => setSelectedDownloadLog(item)}>
+                                        {item.thumbnail && (
+                                            <img
+                                                src={`/api/history/${encodeURIComponent(item.id)}/thumbnail`}
+                                                alt=""
+                                                loading="lazy"
+                                                className="h-9 w-16 object-cover rounded shrink-0"
+                                            />
+                                        )}
                                         {item.file_name || (item.slot ? `Pack #${item.slot} from ${item.bot}` : 'Unknown')}
                                         <Terminal size={14} className="text-secondary opacity-50" />
                                     </td>
@@ -157,6 +163,16 @@ export const DownloadHistory: React.FC<DownloadHistoryProps> = ({
                                                     <RefreshCw size={16} />
                                                 </button>
                                             )}
+                                            {item.status.toLowerCase() === 'completed' && (
+                                                <a
+                                                    href={`/api/files/${encodeURIComponent(item.id)}/download`}
+                                                    download
+                                                    className="p-1.5 text-secondary hover:text-white rounded"
+                                                    title="Download file"
+                                                >
+                                                    <Download size={16} />
+                                                </a>
+                                            )}
                                             <button
                                                 onClick={() => deleteDownload(item.id, false)}
                                                 className="p-1.5 text-secondary hover:text-white rounded"
@@ -131,7 +131,12 @@ export const SearchHistory: React.FC<SearchHistoryProps> = ({
                                                 {selectedSearches.has(item.id) ? <CheckSquare size={18} /> : <Square size={18} />}
                                             </button>
                                         </td>
-                                        <td className="px-4 py-3 font-mono text-sm">{item.query}</td>
+                                        <td className="px-4 py-3 font-mono text-sm">
+                                            {item.query}
+                                            {(item.search_count ?? 1) > 1 && (
+                                                <span className="ml-2 text-xs text-secondary">×{item.search_count}</span>
+                                            )}
+                                        </td>
                                         <td className="px-4 py-3">
                                             <button
                                                 onClick={() => setExpandedSearch(expandedSearch === item.id ? null : item.id)}
@@ -208,6 +208,17 @@ export const GeneralSettings: React.FC<GeneralSettingsProps> = ({ settings, upda
                                     className="w-full bg-surface border border-white/10 rounded-lg px-4 py-2 text-white focus:outline-none focus:border-primary/50"
                                 />
                             </div>
+
+                            <div className="col-span-2">
+                                <label className="block text-sm text-secondary mb-2">External IP</label>
+                                <input
+                                    type="text"
+                                    value={settings.dcc_external_ip ?? ''}
+                                    onChange={e => updateSetting('dcc_external_ip', e.target.value)}
+                                    placeholder="Address of the IRC connection"
+                                    className="w-full bg-surface border border-white/10 rounded-lg px-4 py-2 text-white focus:outline-none focus:border-primary/50"
+                                />
+                            </div>
                         </div>
                     )}
 
@@ -286,7 +297,7 @@ export const GeneralSettings: React.FC<GeneralSettingsProps> = ({ settings, upda
                     <div>
                         <label className="block text-sm text-secondary mb-3">Enabled Providers</label>
                         <div className="space-y-2">
-                            {['SkullXDCC', 'XDCC.rocks', 'XDCC.eu', 'NIBL'].map(provider => (
+                            {['SkullXDCC', 'XDCC.rocks', 'XDCC.eu', 'NIBL', 'SunXDCC'].map(provider => (
                                 <label key={provider} className="flex items-center gap-3">
                                     <input
                                         type="checkbox"
@@ -169,6 +169,18 @@ export const NetworkSettings: React.FC<NetworkSettingsProps> = ({
                                 className="w-full bg-surface border border-white/10 rounded px-3 py-1.5 text-sm text-white focus:outline-none focus:border-primary/50"
                             />
                         </div>
+                        <div className="mt-3">
+                            <label className="flex items-center gap-2">
+                                <input
+                                    type="checkbox"
+                                    checked={network.private ?? false}
+                                    onChange={e => updateNetwork(name, 'private', e.target.checked)}
+                                    className="w-4 h-4 rounded accent-primary"
+                                />
+                                <span className="text-sm">Private</span>
+                                <span className="text-xs text-muted/60">(never store its packs in search history or send them in alerts)</span>
+                            </label>
+                        </div>
                     </div>
                 ))}
 
@@ -9,6 +9,9 @@ export interface AutodlFilter {
     match: string;
     exclude: string;
     keywords: string[];
+    priority?: 'low' | 'normal' | 'high' | 'urgent';
+    max_retries?: number;
+    category?: string;
 }
 
 export const useAutodl = () => {
@@ -1,5 +1,6 @@
 import { useState, useCallback } from 'react';
 import { useToast } from './useToast';
+import { formatBytes } from '../utils/format';
 
 export interface SearchHistoryItem {
     id: number;
@@ -7,6 +8,7 @@ export interface SearchHistoryItem {
     results_count: number;
     results_json?: string;
     searched_at: string;
+    search_count?: number;
 }
 
 export interface DownloadHistoryItem {
@@ -19,6 +21,8 @@ export interface DownloadHistoryItem {
     slot: number;
     status: string;
     completed_at: string;
+    /** A frame of the video is served at /api/history/{id}/thumbnail */
+    thumbnail?: boolean;
 }
 
 interface PaginatedResponse<T> {
@@ -164,11 +168,26 @@ export const useHistory = () => {
     const bulkDeleteDownloads = async (selectedIds: Set<string>, deleteFiles: boolean) => {
         if (selectedIds.size === 0) return;
         try {
-            await fetch('/api/history/bulk', {
+            const body = JSON.stringify({ ids: Array.from(selectedIds), delete_files: deleteFiles });
+            let res = await fetch('/api/history/bulk', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ ids: Array.from(selectedIds), delete_files: deleteFiles }),
+                body,
             });
+            // Deleting files has to be confirmed with a token the server hands out
+            if (res.status === 428) {
+                const { confirm_token, items, bytes } = await res.json();
+                if (!window.confirm(`Delete ${items} items AND their files (${formatBytes(bytes)}) from disk?`)) return;
+                res = await fetch('/api/history/bulk', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json', 'X-Confirm-Token': confirm_token },
+                    body,
+                });
+            }
+            if (!res.ok) {
+                showToast('Failed to delete history items', 'error');
+                return;
+            }
             setDownloads(prev => prev.filter(d => !selectedIds.has(d.id)));
             setDownloadTotal(prev => prev - selectedIds.size);
         } catch (e) {
@@ -2,6 +2,11 @@ import { useState, useEffect } from 'react';
 import { AppConfig, NetworkConfig } from '../types';
 import { useToast } from './useToast';
 
+// Tags this tab's own changes so their config_changed events are skipped
+const CLIENT_ID = Math.random().toString(36).slice(2, 10);
+let requestCount = 0;
+const requestId = () => `ui-${CLIENT_ID}-${++requestCount}`;
+
 export const useSettings = () => {
     const [settings, setSettings] = useState<AppConfig | null>(null);
     const [loading, setLoading] = useState(true);
@@ -12,6 +17,24 @@ export const useSettings = () => {
         fetchSettings();
     }, []);
 
+    // Reload when another tab or an automation changes the config
+    useEffect(() => {
+        const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+        const socket = new WebSocket(`${protocol}//${window.location.host}/api/events`);
+        socket.onmessage = (event) => {
+            try {
+                const msg = JSON.parse(event.data);
+                if (msg.type === 'config_changed' && !msg.request_id?.startsWith(`ui-${CLIENT_ID}-`)) {
+                    fetchSettings();
+                    showToast('Settings were changed elsewhere and have been reloaded', 'success');
+                }
+            } catch (e) {
+                console.error('Failed to parse event', e);
+            }
+        };
+        return () => socket.close();
+    }, []);
+
     const fetchSettings = async () => {
         try {
             const res = await fetch('/api/settings');
@@ -33,7 +56,7 @@ export const useSettings = () => {
         try {
             const res = await fetch('/api/settings', {
                 method: 'PUT',
-                headers: { 'Content-Type': 'application/json' },
+                headers: { 'Content-Type': 'application/json', 'X-Request-Id': requestId() },
                 body: JSON.stringify(settings)
             });
             if (res.ok) {
@@ -69,7 +92,7 @@ export const useSettings = () => {
         try {
             await fetch(`/api/settings/networks/${encodeURIComponent(newNetworkName)}`, {
                 method: 'PUT',
-                headers: { 'Content-Type': 'application/json' },
+                headers: { 'Content-Type': 'application/json', 'X-Request-Id': requestId() },
                 body: JSON.stringify(network)
             });
 
@@ -89,7 +112,10 @@ export const useSettings = () => {
         if (!settings) return;
 
         try {
-            await fetch(`/api/settings/networks/${encodeURIComponent(name)}`, { method: 'DELETE' });
+            await fetch(`/api/settings/networks/${encodeURIComponent(name)}`, {
+                method: 'DELETE',
+                headers: { 'X-Request-Id': requestId() }
+            });
             const newNetworks = { ...settings.networks };
             delete newNetworks[name];
             setSettings({ ...settings, networks: newNetworks });
@@ -6,6 +6,8 @@ export interface XdccSearchResult {
     file_name: string;
     downloads: number;
     channel: string;
+    /** When the provider last saw the pack announced (ISO 8601), if it reports it */
+    last_seen?: string;
 }
 
 export interface SearchResponse {
@@ -13,7 +15,7 @@ export interface SearchResponse {
     count: number;
 }
 
-export type TransferStatus = 'pending' | 'connecting' | 'joining' | 'requesting' | 'downloading' | 'completed' | 'failed' | 'cancelled' | 'paused';
+export type TransferStatus = 'pending' | 'connecting' | 'joining' | 'requesting' | 'downloading' | 'completed' | 'failed' | 'cancelled' | 'quarantined' | 'paused';
 export type TransferPriority = 'low' | 'normal' | 'high' | 'urgent';
 
 export interface XdccTransfer {
@@ -30,6 +32,7 @@ export interface XdccTransfer {
     downloaded: number;
     speed: number;
     progress: number;
+    eta_secs?: Option<number>;
     error: Option<string>;
     created_at: number;
     updated_at: number;
@@ -37,6 +40,7 @@ export interface XdccTransfer {
     retry_count: number;
     max_retries: number;
     queue_position: Option<number>;
+    estimated_start?: string;
 }
 
 // Helper type for TS since Rust Option ends up as null | T or just T?
@@ -65,6 +69,11 @@ export interface BotStats {
     total_bytes: number;
     average_speed: number;
     reliability_score: number;
+    /** Requests to the bot are held back until then, as it seems to ignore us */
+    ignored_until?: string;
+    ignored_reason?: string;
+    /** Hour of the day (UTC) the bot has been fastest and most reliable in */
+    best_hour?: number;
 }
 
 export interface NetworkConfig {
@@ -74,6 +83,7 @@ export interface NetworkConfig {
     autojoin_channels: string[];
     join_delay_secs: number;
     nickserv_password: string;
+    private?: boolean;
 }
 
 export interface AppConfig {
@@ -91,6 +101,7 @@ export interface AppConfig {
     passive_dcc: boolean;
     dcc_port_min: number;
     dcc_port_max: number;
+    dcc_external_ip?: string;
     resume_enabled: boolean;
     enabled_providers: string[];
     results_per_page: number;
@@ -21,6 +21,20 @@ export function formatSpeed(bytesPerSec: number) {
     return `${formatBytes(bytesPerSec)}/s`
 }
 
+export function formatEta(secs: number) {
+    if (secs < 60) return `${secs}s`
+    const mins = Math.round(secs / 60)
+    if (mins < 60) return `${mins}m`
+    return `${Math.floor(mins / 60)}h ${mins % 60}m`
+}
+
+export function formatAge(iso: string) {
+    const secs = Math.max(0, Math.round((Date.now() - new Date(iso).getTime()) / 1000))
+    if (secs < 3600) return `${Math.floor(secs / 60)}m ago`
+    if (secs < 86400) return `${Math.floor(secs / 3600)}h ago`
+    return `${Math.floor(secs / 86400)}d ago`
+}
+
 export function truncateMiddle(text: string, maxLength: number) {
     if (text.length <= maxLength) return text;
     const side = Math.floor((maxLength - 3) / 2);